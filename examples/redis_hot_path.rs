@@ -0,0 +1,208 @@
+//! Redis 心跳、释放热路径的延迟对比
+//!
+//! 对同一个 Redis 分别执行旧的逐条命令实现（心跳 4 次往返、释放 6 次往返）和当前的 Lua 脚本实现（各 2 次往返），
+//! 输出 p50、p99 和平均延迟。使用 `REDIS_URL`（默认 redis://127.0.0.1:6379）和 `BENCH_ITERATIONS`（默认 2000），
+//! 其他 Redis 配置与服务相同，键前缀固定为 `bench:<pid>:`，结束后删除。旧实现按 id 键查找锁，因此使用 `keys` 索引布局。
+//!
+//! ```bash
+//! REDIS_URL=redis://127.0.0.1:6379 cargo run --release --example redis_hot_path
+//! ```
+
+use anyhow::Result;
+use chrono::Utc;
+use fe_lock_service::config::RedisIdIndex;
+use fe_lock_service::models::{AcquireLockRequest, LockInfo};
+use fe_lock_service::storage::redis::{RedisOptions, RedisStorage};
+use fe_lock_service::storage::LockStorage;
+use fe_lock_service::Config;
+use redis::aio::MultiplexedConnection;
+use redis::AsyncCommands;
+use std::time::{Duration, Instant};
+
+const TIMEOUT: u64 = 300;
+
+/// 旧实现使用的比较后写入脚本
+const COMPARE_AND_SET: &str = r"
+if redis.call('GET', KEYS[1]) ~= ARGV[1] then
+    return 0
+end
+redis.call('SET', KEYS[1], ARGV[2], 'EX', ARGV[3])
+return 1
+";
+
+/// 旧实现使用的比较后删除脚本
+const COMPARE_AND_DELETE: &str = r"
+if redis.call('GET', KEYS[1]) ~= ARGV[1] then
+    return 0
+end
+redis.call('DEL', KEYS[1])
+return 1
+";
+
+struct Bench {
+    storage: RedisStorage,
+    conn: MultiplexedConnection,
+    prefix: String,
+}
+
+impl Bench {
+    fn new_lock(&self, business_id: &str) -> Result<LockInfo> {
+        let request: AcquireLockRequest = serde_json::from_value(serde_json::json!({
+            "namespace": "bench",
+            "user_id": "bench-user",
+            "user_name": "bench-user",
+            "business_id": business_id,
+        }))?;
+        Ok(LockInfo::new(uuid::Uuid::new_v4().to_string(), &request, TIMEOUT, Utc::now()))
+    }
+
+    async fn acquire(&self, business_id: &str) -> Result<LockInfo> {
+        let lock_info = self.new_lock(business_id)?;
+        anyhow::ensure!(self.storage.try_acquire(lock_info.clone()).await?.acquired, "lock {} is held", business_id);
+        Ok(lock_info)
+    }
+
+    /// 旧心跳：GET id 键、GET 锁数据、比较后写入、EXPIRE id 键
+    async fn sequential_heartbeat(&self, lock_id: &str) -> Result<()> {
+        let mut conn = self.conn.clone();
+        let id_key = format!("{}id:{}", self.prefix, lock_id);
+        let lock_key: String = conn.get(&id_key).await?;
+        let full_lock_key = format!("{}data:{}", self.prefix, lock_key);
+        let data: String = conn.get(&full_lock_key).await?;
+        let mut lock_info: LockInfo = serde_json::from_str(&data)?;
+        lock_info.last_heartbeat = Utc::now();
+        lock_info.version += 1;
+        let _: i32 = redis::Script::new(COMPARE_AND_SET)
+            .key(&full_lock_key)
+            .arg(&data)
+            .arg(serde_json::to_string(&lock_info)?)
+            .arg(TIMEOUT)
+            .invoke_async(&mut conn)
+            .await?;
+        let _: () = conn.expire(&id_key, TIMEOUT as i64).await?;
+        Ok(())
+    }
+
+    /// 旧释放：GET id 键、GET 锁数据、比较后删除、DEL id 键、SREM 持有人索引、记录持有时长
+    async fn sequential_release(&self, lock_id: &str) -> Result<()> {
+        let mut conn = self.conn.clone();
+        let id_key = format!("{}id:{}", self.prefix, lock_id);
+        let lock_key: String = conn.get(&id_key).await?;
+        let full_lock_key = format!("{}data:{}", self.prefix, lock_key);
+        let data: String = conn.get(&full_lock_key).await?;
+        let lock_info: LockInfo = serde_json::from_str(&data)?;
+        let _: i32 = redis::Script::new(COMPARE_AND_DELETE)
+            .key(&full_lock_key)
+            .arg(&data)
+            .invoke_async(&mut conn)
+            .await?;
+        let _: () = conn.del(&id_key).await?;
+        let _: () = conn
+            .srem(format!("{}user:{}", self.prefix, lock_info.user_id), &lock_key)
+            .await?;
+        let held_secs = lock_info.held_secs_at(Utc::now());
+        let hold_time_key = format!("{}stats:hold_time", self.prefix);
+        let _: () = redis::pipe()
+            .hincr(&hold_time_key, "bucket:0", 1)
+            .ignore()
+            .hincr(&hold_time_key, "count", 1)
+            .ignore()
+            .hincr(&hold_time_key, "sum", held_secs)
+            .ignore()
+            .cmd("ZADD")
+            .arg(format!("{}stats:max_hold", self.prefix))
+            .arg("GT")
+            .arg(held_secs)
+            .arg(&lock_key)
+            .ignore()
+            .query_async(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn cleanup(&self) -> Result<()> {
+        let mut conn = self.conn.clone();
+        let keys: Vec<String> = {
+            let mut iter = conn.scan_match::<_, String>(format!("{}*", self.prefix)).await?;
+            let mut keys = Vec::new();
+            while let Some(key) = iter.next_item().await {
+                keys.push(key);
+            }
+            keys
+        };
+        let mut conn = self.conn.clone();
+        for chunk in keys.chunks(500) {
+            let _: () = conn.del(chunk).await?;
+        }
+        Ok(())
+    }
+}
+
+fn report(name: &str, mut samples: Vec<Duration>) {
+    samples.sort();
+    let percentile = |p: f64| samples[((samples.len() as f64 * p).ceil() as usize).clamp(1, samples.len()) - 1];
+    let mean = samples.iter().sum::<Duration>() / samples.len() as u32;
+    println!(
+        "{:<24} p50 {:>9.3?}  p99 {:>9.3?}  mean {:>9.3?}",
+        name,
+        percentile(0.50),
+        percentile(0.99),
+        mean
+    );
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let url = std::env::var("REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1:6379".to_string());
+    let iterations: usize = std::env::var("BENCH_ITERATIONS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(2000)
+        .max(1);
+    let config = Config::from_env();
+    let mut options = RedisOptions::from_config(&config, &url);
+    options.prefix = format!("bench:{}:", std::process::id());
+    options.id_index = RedisIdIndex::Keys;
+    let bench = Bench {
+        prefix: options.prefix.clone(),
+        storage: RedisStorage::new(options).await?,
+        conn: redis::Client::open(url.as_str())?.get_multiplexed_async_connection().await?,
+    };
+
+    let lock_info = bench.acquire("heartbeat").await?;
+    // 预热，加载脚本
+    bench.sequential_heartbeat(&lock_info.lock_id).await?;
+    bench.storage.update_heartbeat(&lock_info.lock_id).await?;
+
+    let mut sequential = Vec::with_capacity(iterations);
+    let mut scripted = Vec::with_capacity(iterations);
+    for _ in 0..iterations {
+        let started = Instant::now();
+        bench.sequential_heartbeat(&lock_info.lock_id).await?;
+        sequential.push(started.elapsed());
+        let started = Instant::now();
+        bench.storage.update_heartbeat(&lock_info.lock_id).await?;
+        scripted.push(started.elapsed());
+    }
+    println!("heartbeat ({} iterations)", iterations);
+    report("  sequential (4 RTT)", sequential);
+    report("  scripted (2 RTT)", scripted);
+
+    let mut sequential = Vec::with_capacity(iterations);
+    let mut scripted = Vec::with_capacity(iterations);
+    for i in 0..iterations {
+        let lock_info = bench.acquire(&format!("release-{}", i)).await?;
+        let started = Instant::now();
+        bench.sequential_release(&lock_info.lock_id).await?;
+        sequential.push(started.elapsed());
+        let lock_info = bench.acquire(&format!("release-{}", i)).await?;
+        let started = Instant::now();
+        bench.storage.release(&lock_info.lock_id).await?;
+        scripted.push(started.elapsed());
+    }
+    println!("release ({} iterations)", iterations);
+    report("  sequential (6 RTT)", sequential);
+    report("  scripted (2 RTT)", scripted);
+
+    bench.cleanup().await
+}
@@ -0,0 +1,212 @@
+use crate::client::LockClient;
+use crate::error::Error;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+const CODE_ADMIN_LOCK_NOT_FOUND: i32 = 4002;
+
+/// 锁信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LockInfo {
+    pub lock_id: String,
+    pub namespace: String,
+    pub user_id: String,
+    pub user_name: String,
+    pub business_id: String,
+    pub timeout: u64,
+    pub locked_at: DateTime<Utc>,
+    pub last_heartbeat: DateTime<Utc>,
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub max_hold_seconds: Option<u64>,
+    /// 心跳超时后的宽限期（秒），宽限期内心跳仍然有效
+    #[serde(default)]
+    pub grace_seconds: u64,
+    /// 待执行的抢占，持有人应在 deadline 前保存并释放锁
+    #[serde(default)]
+    pub preemption: Option<PreemptionNotice>,
+    /// 版本号，条件获取接管已过期的锁时递增
+    #[serde(default)]
+    pub version: u64,
+    /// 持有原因
+    #[serde(default)]
+    pub reason: Option<String>,
+    /// 申请锁的客户端网络信息
+    #[serde(default)]
+    pub client: Option<ClientContext>,
+}
+
+/// 申请锁的客户端网络信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClientContext {
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub hostname: Option<String>,
+}
+
+/// 抢占通知
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PreemptionNotice {
+    pub user_id: String,
+    pub user_name: String,
+    pub deadline: DateTime<Utc>,
+}
+
+impl LockInfo {
+    /// 超时时间为 0 的永久锁，不会过期
+    pub fn is_permanent(&self) -> bool {
+        self.timeout == 0
+    }
+
+    /// 不再心跳时的过期时间，取心跳超时和最长持有时间中较早的一个，永久锁为 None
+    pub fn expires_at(&self) -> Option<DateTime<Utc>> {
+        let heartbeat_expiry = (!self.is_permanent())
+            .then(|| self.last_heartbeat + Duration::seconds(self.timeout as i64));
+        let hold_deadline = self
+            .max_hold_seconds
+            .map(|max_hold| self.locked_at + Duration::seconds(max_hold as i64));
+        match (heartbeat_expiry, hold_deadline) {
+            (Some(heartbeat_expiry), Some(deadline)) => Some(heartbeat_expiry.min(deadline)),
+            (heartbeat_expiry, deadline) => heartbeat_expiry.or(deadline),
+        }
+    }
+}
+
+/// 强制释放的目标
+#[derive(Debug, Clone)]
+pub enum ForceReleaseTarget {
+    LockId(String),
+    Key {
+        namespace: String,
+        business_id: String,
+    },
+}
+
+#[derive(Serialize)]
+struct ForceReleaseBody<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lock_id: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    namespace: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    business_id: Option<&'a str>,
+}
+
+#[derive(Deserialize)]
+struct ForceReleaseData {
+    lock: LockInfo,
+}
+
+#[derive(Serialize)]
+struct ReleaseByTagBody<'a> {
+    tag: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    namespace: Option<&'a str>,
+}
+
+#[derive(Deserialize)]
+struct ReleaseByTagData {
+    locks: Vec<LockInfo>,
+}
+
+fn map_admin_error(code: i32, message: String) -> Error {
+    match code {
+        CODE_ADMIN_LOCK_NOT_FOUND => Error::LockNotFound { code, message },
+        _ => Error::Api { code, message },
+    }
+}
+
+/// 管理接口
+impl LockClient {
+    /// 列出锁，可按命名空间和标签过滤
+    pub async fn list_locks(
+        &self,
+        namespace: Option<&str>,
+        tag: Option<&str>,
+    ) -> Result<Vec<LockInfo>, Error> {
+        let mut params = Vec::new();
+        if let Some(namespace) = namespace {
+            params.push(format!("namespace={}", encode(namespace)));
+        }
+        if let Some(tag) = tag {
+            params.push(format!("tag={}", encode(tag)));
+        }
+        let path = if params.is_empty() {
+            "/api/admin/locks".to_string()
+        } else {
+            format!("/api/admin/locks?{}", params.join("&"))
+        };
+        self.get(&path, map_admin_error).await
+    }
+
+    /// 按 lock_id 查看锁详情，锁不存在时返回 `None`
+    pub async fn get_lock(&self, lock_id: &str) -> Result<Option<LockInfo>, Error> {
+        match self
+            .get(
+                &format!("/api/admin/locks/{}", encode(lock_id)),
+                map_admin_error,
+            )
+            .await
+        {
+            Ok(lock_info) => Ok(Some(lock_info)),
+            Err(Error::LockNotFound { .. }) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// 强制释放锁，返回被释放的锁信息
+    pub async fn force_release(&self, target: &ForceReleaseTarget) -> Result<LockInfo, Error> {
+        let body = match target {
+            ForceReleaseTarget::LockId(lock_id) => ForceReleaseBody {
+                lock_id: Some(lock_id),
+                namespace: None,
+                business_id: None,
+            },
+            ForceReleaseTarget::Key {
+                namespace,
+                business_id,
+            } => ForceReleaseBody {
+                lock_id: None,
+                namespace: Some(namespace),
+                business_id: Some(business_id),
+            },
+        };
+        let data: ForceReleaseData = self
+            .post("/api/admin/lock/force-release", &body, map_admin_error)
+            .await?;
+        Ok(data.lock)
+    }
+
+    /// 按标签批量释放锁，返回被释放的锁
+    pub async fn release_by_tag(
+        &self,
+        tag: &str,
+        namespace: Option<&str>,
+    ) -> Result<Vec<LockInfo>, Error> {
+        let data: ReleaseByTagData = self
+            .post(
+                "/api/admin/lock/release-by-tag",
+                &ReleaseByTagBody { tag, namespace },
+                map_admin_error,
+            )
+            .await?;
+        Ok(data.locks)
+    }
+}
+
+/// 对 URL 路径/查询参数做百分号编码
+pub(crate) fn encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
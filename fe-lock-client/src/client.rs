@@ -0,0 +1,430 @@
+use crate::admin::{encode, LockInfo};
+use crate::error::Error;
+use crate::guard::LockGuard;
+use crate::retry::RetryPolicy;
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use reqwest::Method;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+const CODE_LOCK_HELD: i32 = 1001;
+const CODE_HEARTBEAT_NOT_FOUND: i32 = 2001;
+const CODE_RELEASE_NOT_FOUND: i32 = 3001;
+
+/// 申请锁参数
+#[derive(Debug, Clone, Serialize)]
+pub struct AcquireOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    namespace: Option<String>,
+    user_id: String,
+    user_name: String,
+    business_id: String,
+    timeout: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hostname: Option<String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    metadata: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+}
+
+impl AcquireOptions {
+    pub fn new(
+        business_id: impl Into<String>,
+        user_id: impl Into<String>,
+        user_name: impl Into<String>,
+    ) -> Self {
+        Self {
+            namespace: None,
+            user_id: user_id.into(),
+            user_name: user_name.into(),
+            business_id: business_id.into(),
+            timeout: 60,
+            reason: None,
+            hostname: None,
+            metadata: BTreeMap::new(),
+            tags: Vec::new(),
+        }
+    }
+
+    /// 命名空间，不设置时使用服务端默认值
+    pub fn namespace(mut self, namespace: impl Into<String>) -> Self {
+        self.namespace = Some(namespace.into());
+        self
+    }
+
+    /// 超时时间（秒）
+    pub fn timeout(mut self, timeout: u64) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// 持有原因，会在其他人申请冲突时返回
+    pub fn reason(mut self, reason: impl Into<String>) -> Self {
+        self.reason = Some(reason.into());
+        self
+    }
+
+    /// 客户端主机名，锁卡住时用于定位持有锁的机器
+    pub fn hostname(mut self, hostname: impl Into<String>) -> Self {
+        self.hostname = Some(hostname.into());
+        self
+    }
+
+    /// 附加信息，例如文档标题、页面地址，会在锁状态中返回
+    pub fn metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+
+    /// 标签，可按标签查询或批量释放锁
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.tags.push(tag.into());
+        self
+    }
+
+    pub fn timeout_secs(&self) -> u64 {
+        self.timeout
+    }
+}
+
+/// 服务端统一响应结构
+#[derive(Debug, Deserialize)]
+struct ApiResponse<T> {
+    code: i32,
+    message: String,
+    data: Option<T>,
+    success: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct AcquireData {
+    lock_id: String,
+    /// 服务端建议的心跳间隔（毫秒），旧版本服务端不返回
+    #[serde(default)]
+    heartbeat_interval_ms: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LockStatusData {
+    lock: Option<LockInfo>,
+}
+
+#[derive(Serialize)]
+struct ReleaseByKeyRequest<'a> {
+    namespace: &'a str,
+    business_id: &'a str,
+    user_id: &'a str,
+}
+
+#[derive(Serialize)]
+struct LockIdRequest<'a> {
+    lock_id: &'a str,
+}
+
+#[derive(Serialize)]
+struct UserIdRequest<'a> {
+    user_id: &'a str,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReleaseAllData {
+    locks: Vec<LockInfo>,
+}
+
+/// 锁服务客户端，可廉价克隆
+#[derive(Debug, Clone)]
+pub struct LockClient {
+    base_url: String,
+    http: reqwest::Client,
+    retry: RetryPolicy,
+}
+
+/// 客户端构造器
+#[derive(Debug)]
+pub struct LockClientBuilder {
+    base_url: String,
+    request_timeout: Duration,
+    retry: RetryPolicy,
+    bearer_token: Option<String>,
+}
+
+impl LockClientBuilder {
+    /// 单次 HTTP 请求超时时间，默认 5 秒
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    /// 临时错误的重试策略，默认最多重试 3 次
+    pub fn retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// 请求携带的 Bearer 令牌（管理接口需要）
+    pub fn bearer_token(mut self, token: impl Into<String>) -> Self {
+        self.bearer_token = Some(token.into());
+        self
+    }
+
+    pub fn build(self) -> Result<LockClient, Error> {
+        let base_url = self.base_url.trim_end_matches('/').to_string();
+        if !base_url.starts_with("http://") && !base_url.starts_with("https://") {
+            return Err(Error::InvalidUrl(base_url));
+        }
+
+        let mut headers = HeaderMap::new();
+        if let Some(token) = &self.bearer_token {
+            let mut value =
+                HeaderValue::from_str(&format!("Bearer {}", token)).map_err(|_| Error::Api {
+                    code: -1,
+                    message: "invalid bearer token".to_string(),
+                })?;
+            value.set_sensitive(true);
+            headers.insert(AUTHORIZATION, value);
+        }
+
+        let http = reqwest::Client::builder()
+            .timeout(self.request_timeout)
+            .default_headers(headers)
+            .build()?;
+
+        Ok(LockClient {
+            base_url,
+            http,
+            retry: self.retry,
+        })
+    }
+}
+
+impl LockClient {
+    /// 使用默认配置创建客户端
+    pub fn new(base_url: impl Into<String>) -> Result<Self, Error> {
+        Self::builder(base_url).build()
+    }
+
+    pub fn builder(base_url: impl Into<String>) -> LockClientBuilder {
+        LockClientBuilder {
+            base_url: base_url.into(),
+            request_timeout: Duration::from_secs(5),
+            retry: RetryPolicy::default(),
+            bearer_token: None,
+        }
+    }
+
+    /// 申请锁，返回 lock_id
+    pub async fn acquire(&self, options: &AcquireOptions) -> Result<String, Error> {
+        Ok(self.acquire_data(options).await?.lock_id)
+    }
+
+    async fn acquire_data(&self, options: &AcquireOptions) -> Result<AcquireData, Error> {
+        self.post("/api/lock/acquire", options, |code, message| match code {
+            CODE_LOCK_HELD => Error::LockHeld { code, message },
+            _ => Error::Api { code, message },
+        })
+        .await
+    }
+
+    /// 查询锁状态，未被持有时返回 `None`
+    pub async fn status(
+        &self,
+        namespace: &str,
+        business_id: &str,
+    ) -> Result<Option<LockInfo>, Error> {
+        let path = format!(
+            "/api/lock/status?namespace={}&business_id={}",
+            encode(namespace),
+            encode(business_id)
+        );
+        let data: LockStatusData = self
+            .get(&path, |code, message| Error::Api { code, message })
+            .await?;
+        Ok(data.lock)
+    }
+
+    /// 发送心跳
+    pub async fn heartbeat(&self, lock_id: &str) -> Result<(), Error> {
+        let _: serde_json::Value = self
+            .post(
+                "/api/lock/heartbeat",
+                &LockIdRequest { lock_id },
+                |code, message| match code {
+                    CODE_HEARTBEAT_NOT_FOUND => Error::LockNotFound { code, message },
+                    _ => Error::Api { code, message },
+                },
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// 释放锁
+    pub async fn release(&self, lock_id: &str) -> Result<(), Error> {
+        let _: serde_json::Value = self
+            .post(
+                "/api/lock/release",
+                &LockIdRequest { lock_id },
+                |code, message| match code {
+                    CODE_RELEASE_NOT_FOUND => Error::LockNotFound { code, message },
+                    _ => Error::Api { code, message },
+                },
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// 按业务键释放锁，服务端校验 user_id 为当前持有人，适用于丢失 lock_id 的场景
+    pub async fn release_by_key(
+        &self,
+        namespace: &str,
+        business_id: &str,
+        user_id: &str,
+    ) -> Result<(), Error> {
+        let _: serde_json::Value = self
+            .post(
+                "/api/lock/release",
+                &ReleaseByKeyRequest {
+                    namespace,
+                    business_id,
+                    user_id,
+                },
+                |code, message| match code {
+                    CODE_RELEASE_NOT_FOUND => Error::LockNotFound { code, message },
+                    _ => Error::Api { code, message },
+                },
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// 查询用户持有的锁
+    pub async fn locks_by_user(&self, user_id: &str) -> Result<Vec<LockInfo>, Error> {
+        self.get(
+            &format!("/api/lock/by-user/{}", encode(user_id)),
+            |code, message| Error::Api { code, message },
+        )
+        .await
+    }
+
+    /// 释放用户持有的所有锁，返回被释放的锁，适用于退出登录或崩溃恢复
+    pub async fn release_all(&self, user_id: &str) -> Result<Vec<LockInfo>, Error> {
+        let data: ReleaseAllData = self
+            .post(
+                "/api/lock/release-all",
+                &UserIdRequest { user_id },
+                |code, message| Error::Api { code, message },
+            )
+            .await?;
+        Ok(data.locks)
+    }
+
+    /// 申请锁并返回自动心跳的 [`LockGuard`]，guard 被丢弃时自动释放锁
+    pub async fn lock(&self, options: AcquireOptions) -> Result<LockGuard, Error> {
+        let data = self.acquire_data(&options).await?;
+        // 按服务端建议的间隔心跳；服务端未返回时在超时时间的三分之一处发送，留出重试余量
+        let interval = match data.heartbeat_interval_ms {
+            Some(ms) => Duration::from_millis(ms.max(100)),
+            None => Duration::from_secs((options.timeout_secs() / 3).max(1)),
+        };
+        Ok(LockGuard::new(self.clone(), data.lock_id, interval))
+    }
+
+    /// 发送 POST 请求
+    pub(crate) async fn post<Req, Resp, F>(
+        &self,
+        path: &str,
+        body: &Req,
+        map_error: F,
+    ) -> Result<Resp, Error>
+    where
+        Req: Serialize + ?Sized,
+        Resp: DeserializeOwned,
+        F: Fn(i32, String) -> Error,
+    {
+        let body = serde_json::to_value(body).map_err(|e| Error::Api {
+            code: -1,
+            message: format!("failed to encode request: {}", e),
+        })?;
+        self.send(Method::POST, path, Some(&body), map_error).await
+    }
+
+    /// 发送 GET 请求
+    pub(crate) async fn get<Resp, F>(&self, path: &str, map_error: F) -> Result<Resp, Error>
+    where
+        Resp: DeserializeOwned,
+        F: Fn(i32, String) -> Error,
+    {
+        self.send(Method::GET, path, None, map_error).await
+    }
+
+    /// 发送请求，对临时错误按重试策略重试
+    async fn send<Resp, F>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&serde_json::Value>,
+        map_error: F,
+    ) -> Result<Resp, Error>
+    where
+        Resp: DeserializeOwned,
+        F: Fn(i32, String) -> Error,
+    {
+        let mut attempt = 0;
+        loop {
+            match self.send_once(method.clone(), path, body, &map_error).await {
+                Err(e) if e.is_transient() && attempt < self.retry.max_retries() => {
+                    attempt += 1;
+                    let backoff = self.retry.backoff(attempt);
+                    log::warn!(
+                        "Request to {} failed ({}), retrying in {:?} (attempt {}/{})",
+                        path,
+                        e,
+                        backoff,
+                        attempt,
+                        self.retry.max_retries()
+                    );
+                    tokio::time::sleep(backoff).await;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn send_once<Resp, F>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&serde_json::Value>,
+        map_error: &F,
+    ) -> Result<Resp, Error>
+    where
+        Resp: DeserializeOwned,
+        F: Fn(i32, String) -> Error,
+    {
+        let url = format!("{}{}", self.base_url, path);
+        let mut request = self.http.request(method, url);
+        if let Some(body) = body {
+            request = request.json(body);
+        }
+        let response = request.send().await?;
+        let status = response.status();
+
+        let api_response: ApiResponse<Resp> = match response.json().await {
+            Ok(api_response) => api_response,
+            Err(_) if !status.is_success() => return Err(Error::Status(status)),
+            Err(e) => return Err(Error::Http(e)),
+        };
+
+        if !api_response.success {
+            return Err(map_error(api_response.code, api_response.message));
+        }
+        api_response.data.ok_or(Error::Api {
+            code: api_response.code,
+            message: "response has no data".to_string(),
+        })
+    }
+}
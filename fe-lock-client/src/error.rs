@@ -0,0 +1,48 @@
+/// 客户端错误
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// 锁已被其他用户持有
+    #[error("lock already held: {message}")]
+    LockHeld { code: i32, message: String },
+
+    /// 锁不存在、已过期或不属于当前用户
+    #[error("lock not found: {message}")]
+    LockNotFound { code: i32, message: String },
+
+    /// 服务端返回的其他业务错误
+    #[error("api error {code}: {message}")]
+    Api { code: i32, message: String },
+
+    /// 网络或 HTTP 错误
+    #[error("http error: {0}")]
+    Http(#[from] reqwest::Error),
+
+    /// 服务端返回了非预期的 HTTP 状态码
+    #[error("unexpected http status: {0}")]
+    Status(reqwest::StatusCode),
+
+    /// 无效的服务地址
+    #[error("invalid base url: {0}")]
+    InvalidUrl(String),
+}
+
+impl Error {
+    /// 是否为可重试的临时错误
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Error::Http(e) => e.is_connect() || e.is_timeout() || e.is_request(),
+            Error::Status(status) => status.is_server_error(),
+            _ => false,
+        }
+    }
+
+    /// 服务端返回的业务错误码
+    pub fn code(&self) -> Option<i32> {
+        match self {
+            Error::LockHeld { code, .. }
+            | Error::LockNotFound { code, .. }
+            | Error::Api { code, .. } => Some(*code),
+            _ => None,
+        }
+    }
+}
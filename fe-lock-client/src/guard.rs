@@ -0,0 +1,110 @@
+use crate::client::LockClient;
+use crate::error::Error;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// 持有中的锁
+///
+/// 创建后在后台任务中定期发送心跳；调用 [`LockGuard::release`] 或被丢弃时释放锁。
+/// 心跳失败（锁已过期或被释放）后 [`LockGuard::is_lost`] 返回 `true`，调用方应停止受保护的操作。
+pub struct LockGuard {
+    client: LockClient,
+    lock_id: String,
+    lost: Arc<AtomicBool>,
+    heartbeat_task: Option<JoinHandle<()>>,
+    released: bool,
+}
+
+impl LockGuard {
+    pub(crate) fn new(client: LockClient, lock_id: String, interval: Duration) -> Self {
+        let lost = Arc::new(AtomicBool::new(false));
+        let heartbeat_task = tokio::spawn(heartbeat_loop(
+            client.clone(),
+            lock_id.clone(),
+            interval,
+            lost.clone(),
+        ));
+
+        Self {
+            client,
+            lock_id,
+            lost,
+            heartbeat_task: Some(heartbeat_task),
+            released: false,
+        }
+    }
+
+    pub fn lock_id(&self) -> &str {
+        &self.lock_id
+    }
+
+    /// 锁是否已丢失
+    pub fn is_lost(&self) -> bool {
+        self.lost.load(Ordering::Acquire)
+    }
+
+    /// 停止心跳并释放锁
+    pub async fn release(mut self) -> Result<(), Error> {
+        self.stop_heartbeat();
+        self.released = true;
+        self.client.release(&self.lock_id).await
+    }
+
+    fn stop_heartbeat(&mut self) {
+        if let Some(task) = self.heartbeat_task.take() {
+            task.abort();
+        }
+    }
+}
+
+impl Drop for LockGuard {
+    fn drop(&mut self) {
+        self.stop_heartbeat();
+        if self.released || self.is_lost() {
+            return;
+        }
+
+        // 在当前运行时中异步释放；运行时已关闭时只能等待锁超时
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => {
+                let client = self.client.clone();
+                let lock_id = std::mem::take(&mut self.lock_id);
+                handle.spawn(async move {
+                    if let Err(e) = client.release(&lock_id).await {
+                        log::warn!("Failed to release lock {} on drop: {}", lock_id, e);
+                    }
+                });
+            }
+            Err(_) => log::warn!(
+                "LockGuard for {} dropped outside a tokio runtime, lock will expire by timeout",
+                self.lock_id
+            ),
+        }
+    }
+}
+
+async fn heartbeat_loop(
+    client: LockClient,
+    lock_id: String,
+    interval: Duration,
+    lost: Arc<AtomicBool>,
+) {
+    let mut ticker = tokio::time::interval(interval);
+    // 第一次 tick 立即返回，跳过
+    ticker.tick().await;
+    loop {
+        ticker.tick().await;
+        match client.heartbeat(&lock_id).await {
+            Ok(()) => {}
+            Err(e @ Error::LockNotFound { .. }) => {
+                log::warn!("Lock {} lost: {}", lock_id, e);
+                lost.store(true, Ordering::Release);
+                return;
+            }
+            // 其他错误已按重试策略重试过，下个周期继续尝试
+            Err(e) => log::warn!("Heartbeat for lock {} failed: {}", lock_id, e),
+        }
+    }
+}
@@ -0,0 +1,27 @@
+//! fe-lock-service 的 Rust 客户端
+//!
+//! ```no_run
+//! use fe_lock_client::{AcquireOptions, LockClient};
+//!
+//! # async fn run() -> Result<(), fe_lock_client::Error> {
+//! let client = LockClient::new("http://127.0.0.1:8080")?;
+//! let guard = client
+//!     .lock(AcquireOptions::new("order_001", "user123", "张三").namespace("order"))
+//!     .await?;
+//! // 持有锁期间后台自动发送心跳
+//! guard.release().await?;
+//! # Ok(())
+//! # }
+//! ```
+
+mod admin;
+mod client;
+mod error;
+mod guard;
+mod retry;
+
+pub use admin::{ClientContext, ForceReleaseTarget, LockInfo, PreemptionNotice};
+pub use client::{AcquireOptions, LockClient, LockClientBuilder};
+pub use error::Error;
+pub use guard::LockGuard;
+pub use retry::RetryPolicy;
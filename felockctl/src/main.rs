@@ -0,0 +1,425 @@
+//! fe-lock-service 运维命令行工具，通过管理接口查看和处理锁
+
+use chrono::{Local, Utc};
+use clap::{Parser, Subcommand};
+use fe_lock_client::{Error, ForceReleaseTarget, LockClient, LockInfo};
+use std::collections::{BTreeMap, HashMap};
+use std::process::ExitCode;
+use std::time::Duration;
+
+#[derive(Parser)]
+#[command(name = "felockctl", version, about = "fe-lock-service 运维命令行工具")]
+struct Cli {
+    /// 服务地址
+    #[arg(long, env = "FELOCK_URL", default_value = "http://127.0.0.1:8080")]
+    url: String,
+
+    /// 管理接口令牌（对应服务端 ADMIN_TOKEN）
+    #[arg(long, env = "FELOCK_TOKEN", hide_env_values = true)]
+    token: Option<String>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// 列出当前持有的锁
+    List {
+        /// 只列出指定命名空间
+        #[arg(short, long)]
+        namespace: Option<String>,
+        /// 只列出带有指定标签的锁
+        #[arg(short, long)]
+        tag: Option<String>,
+        /// 以 JSON 格式输出
+        #[arg(long)]
+        json: bool,
+    },
+    /// 查看锁详情
+    Inspect {
+        lock_id: String,
+        /// 以 JSON 格式输出
+        #[arg(long)]
+        json: bool,
+    },
+    /// 强制释放锁（按 lock_id，或按 namespace + business_id）
+    ForceRelease {
+        #[arg(required_unless_present = "business_id", conflicts_with_all = ["namespace", "business_id"])]
+        lock_id: Option<String>,
+        #[arg(short, long, default_value = "default")]
+        namespace: String,
+        #[arg(short, long)]
+        business_id: Option<String>,
+    },
+    /// 按标签批量强制释放锁
+    ReleaseTag {
+        tag: String,
+        /// 只释放指定命名空间内的锁
+        #[arg(short, long)]
+        namespace: Option<String>,
+    },
+    /// 锁统计信息
+    Stats {
+        /// 以 JSON 格式输出
+        #[arg(long)]
+        json: bool,
+    },
+    /// 持续观察锁的变化
+    Watch {
+        /// 只观察指定命名空间
+        #[arg(short, long)]
+        namespace: Option<String>,
+        /// 轮询间隔（秒）
+        #[arg(short, long, default_value_t = 2)]
+        interval: u64,
+    },
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    let mut builder = LockClient::builder(&cli.url);
+    if let Some(token) = &cli.token {
+        builder = builder.bearer_token(token);
+    }
+    let client = match builder.build() {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let result = match cli.command {
+        Command::List {
+            namespace,
+            tag,
+            json,
+        } => list(&client, namespace.as_deref(), tag.as_deref(), json).await,
+        Command::Inspect { lock_id, json } => inspect(&client, &lock_id, json).await,
+        Command::ForceRelease {
+            lock_id,
+            namespace,
+            business_id,
+        } => {
+            let target = match (lock_id, business_id) {
+                (Some(lock_id), _) => ForceReleaseTarget::LockId(lock_id),
+                (None, Some(business_id)) => ForceReleaseTarget::Key {
+                    namespace,
+                    business_id,
+                },
+                (None, None) => unreachable!("clap requires lock_id or business_id"),
+            };
+            force_release(&client, &target).await
+        }
+        Command::ReleaseTag { tag, namespace } => {
+            release_tag(&client, &tag, namespace.as_deref()).await
+        }
+        Command::Stats { json } => stats(&client, json).await,
+        Command::Watch {
+            namespace,
+            interval,
+        } => watch(&client, namespace.as_deref(), interval.max(1)).await,
+    };
+
+    match result {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn list(
+    client: &LockClient,
+    namespace: Option<&str>,
+    tag: Option<&str>,
+    json: bool,
+) -> Result<ExitCode, Error> {
+    let locks = client.list_locks(namespace, tag).await?;
+    if json {
+        print_json(&locks);
+    } else if locks.is_empty() {
+        println!("No locks held");
+    } else {
+        print_table(&locks);
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+async fn inspect(client: &LockClient, lock_id: &str, json: bool) -> Result<ExitCode, Error> {
+    let Some(lock) = client.get_lock(lock_id).await? else {
+        eprintln!("Lock {} not found", lock_id);
+        return Ok(ExitCode::FAILURE);
+    };
+
+    if json {
+        print_json(&lock);
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    println!("Lock ID:        {}", lock.lock_id);
+    println!("Namespace:      {}", lock.namespace);
+    println!("Business ID:    {}", lock.business_id);
+    println!("User:           {} ({})", lock.user_name, lock.user_id);
+    if let Some(reason) = &lock.reason {
+        println!("Reason:         {}", reason);
+    }
+    if let Some(client) = &lock.client {
+        let unknown = || "-".to_string();
+        println!(
+            "Client:         {} (host: {}, agent: {})",
+            client.ip.clone().unwrap_or_else(unknown),
+            client.hostname.clone().unwrap_or_else(unknown),
+            client.user_agent.clone().unwrap_or_else(unknown)
+        );
+    }
+    if lock.is_permanent() {
+        println!("Timeout:        permanent");
+    } else {
+        println!("Timeout:        {}s", lock.timeout);
+    }
+    if let Some(max_hold) = lock.max_hold_seconds {
+        println!("Max hold:       {}", format_secs(max_hold as i64));
+    }
+    println!(
+        "Locked at:      {} (held {})",
+        lock.locked_at
+            .with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M:%S"),
+        format_secs((Utc::now() - lock.locked_at).num_seconds())
+    );
+    println!(
+        "Last heartbeat: {} ({} ago)",
+        lock.last_heartbeat
+            .with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M:%S"),
+        format_secs((Utc::now() - lock.last_heartbeat).num_seconds())
+    );
+    println!("Expires in:     {}", format_remaining(&lock));
+    if let Some(preemption) = &lock.preemption {
+        println!(
+            "Preempted by:   {} ({}), transfer in {}",
+            preemption.user_name,
+            preemption.user_id,
+            format_secs((preemption.deadline - Utc::now()).num_seconds().max(0))
+        );
+    }
+    if !lock.tags.is_empty() {
+        println!("Tags:           {}", lock.tags.join(", "));
+    }
+    if !lock.metadata.is_empty() {
+        println!("Metadata:");
+        for (key, value) in &lock.metadata {
+            println!("  {}: {}", key, value);
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+async fn force_release(
+    client: &LockClient,
+    target: &ForceReleaseTarget,
+) -> Result<ExitCode, Error> {
+    match client.force_release(target).await {
+        Ok(lock) => {
+            println!(
+                "Released lock {} ({}/{}) held by {} ({})",
+                lock.lock_id, lock.namespace, lock.business_id, lock.user_name, lock.user_id
+            );
+            Ok(ExitCode::SUCCESS)
+        }
+        Err(Error::LockNotFound { message, .. }) => {
+            eprintln!("{}", message);
+            Ok(ExitCode::FAILURE)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+async fn release_tag(
+    client: &LockClient,
+    tag: &str,
+    namespace: Option<&str>,
+) -> Result<ExitCode, Error> {
+    let released = client.release_by_tag(tag, namespace).await?;
+    for lock in &released {
+        println!(
+            "Released lock {} ({}/{}) held by {} ({})",
+            lock.lock_id, lock.namespace, lock.business_id, lock.user_name, lock.user_id
+        );
+    }
+    println!("{} lock(s) released", released.len());
+    Ok(ExitCode::SUCCESS)
+}
+
+async fn stats(client: &LockClient, json: bool) -> Result<ExitCode, Error> {
+    let locks = client.list_locks(None, None).await?;
+
+    let mut per_namespace: BTreeMap<&str, usize> = BTreeMap::new();
+    let mut per_user: HashMap<&str, usize> = HashMap::new();
+    for lock in &locks {
+        *per_namespace.entry(&lock.namespace).or_default() += 1;
+        *per_user.entry(&lock.user_id).or_default() += 1;
+    }
+    let oldest = locks.iter().min_by_key(|lock| lock.locked_at);
+    let expiring_soon = locks
+        .iter()
+        .filter(|lock| {
+            remaining_secs(lock)
+                .is_some_and(|remaining| remaining <= (lock.timeout as i64 / 3).max(1))
+        })
+        .count();
+
+    if json {
+        print_json(&serde_json::json!({
+            "total": locks.len(),
+            "namespaces": per_namespace,
+            "users": per_user.len(),
+            "expiring_soon": expiring_soon,
+            "oldest_lock_id": oldest.map(|lock| &lock.lock_id),
+        }));
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    println!("Total locks:   {}", locks.len());
+    println!("Holders:       {}", per_user.len());
+    println!("Expiring soon: {}", expiring_soon);
+    if let Some(lock) = oldest {
+        println!(
+            "Oldest lock:   {} ({}/{}, held {})",
+            lock.lock_id,
+            lock.namespace,
+            lock.business_id,
+            format_secs((Utc::now() - lock.locked_at).num_seconds())
+        );
+    }
+    if !per_namespace.is_empty() {
+        println!();
+        println!("{:<24} {:>8}", "NAMESPACE", "LOCKS");
+        for (namespace, count) in per_namespace {
+            println!("{:<24} {:>8}", namespace, count);
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+async fn watch(
+    client: &LockClient,
+    namespace: Option<&str>,
+    interval: u64,
+) -> Result<ExitCode, Error> {
+    let mut known: HashMap<String, LockInfo> = client
+        .list_locks(namespace, None)
+        .await?
+        .into_iter()
+        .map(|lock| (lock.lock_id.clone(), lock))
+        .collect();
+
+    println!(
+        "Watching {} lock(s), polling every {}s (Ctrl+C to stop)",
+        known.len(),
+        interval
+    );
+
+    let mut ticker = tokio::time::interval(Duration::from_secs(interval));
+    ticker.tick().await;
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = tokio::signal::ctrl_c() => return Ok(ExitCode::SUCCESS),
+        }
+
+        let locks = match client.list_locks(namespace, None).await {
+            Ok(locks) => locks,
+            Err(e) => {
+                eprintln!("[{}] poll failed: {}", Local::now().format("%H:%M:%S"), e);
+                continue;
+            }
+        };
+
+        let mut current: HashMap<String, LockInfo> = locks
+            .into_iter()
+            .map(|lock| (lock.lock_id.clone(), lock))
+            .collect();
+        let now = Local::now().format("%H:%M:%S");
+
+        for (lock_id, lock) in &current {
+            if !known.contains_key(lock_id) {
+                println!(
+                    "[{}] + {}/{} {} by {} ({})",
+                    now, lock.namespace, lock.business_id, lock_id, lock.user_name, lock.user_id
+                );
+            }
+        }
+        for (lock_id, lock) in known.drain() {
+            if !current.contains_key(&lock_id) {
+                println!(
+                    "[{}] - {}/{} {} by {} ({})",
+                    now, lock.namespace, lock.business_id, lock_id, lock.user_name, lock.user_id
+                );
+            }
+        }
+
+        std::mem::swap(&mut known, &mut current);
+    }
+}
+
+fn print_table(locks: &[LockInfo]) {
+    println!(
+        "{:<36}  {:<16}  {:<24}  {:<16}  {:>8}  {:>10}",
+        "LOCK ID", "NAMESPACE", "BUSINESS ID", "USER", "HELD", "EXPIRES IN"
+    );
+    for lock in locks {
+        println!(
+            "{:<36}  {:<16}  {:<24}  {:<16}  {:>8}  {:>10}",
+            lock.lock_id,
+            truncate(&lock.namespace, 16),
+            truncate(&lock.business_id, 24),
+            truncate(&lock.user_id, 16),
+            format_secs((Utc::now() - lock.locked_at).num_seconds()),
+            format_remaining(lock)
+        );
+    }
+}
+
+fn print_json<T: serde::Serialize + ?Sized>(value: &T) {
+    match serde_json::to_string_pretty(value) {
+        Ok(json) => println!("{}", json),
+        Err(e) => eprintln!("error: failed to encode output: {}", e),
+    }
+}
+
+/// 距离过期的剩余秒数，永久锁为 None
+fn remaining_secs(lock: &LockInfo) -> Option<i64> {
+    lock.expires_at()
+        .map(|expires_at| (expires_at - Utc::now()).num_seconds().max(0))
+}
+
+fn format_remaining(lock: &LockInfo) -> String {
+    remaining_secs(lock).map_or_else(|| "never".to_string(), format_secs)
+}
+
+fn format_secs(secs: i64) -> String {
+    let secs = secs.max(0);
+    if secs < 60 {
+        format!("{}s", secs)
+    } else if secs < 3600 {
+        format!("{}m{}s", secs / 60, secs % 60)
+    } else {
+        format!("{}h{}m", secs / 3600, secs % 3600 / 60)
+    }
+}
+
+fn truncate(value: &str, max: usize) -> String {
+    if value.chars().count() <= max {
+        value.to_string()
+    } else {
+        let mut truncated: String = value.chars().take(max - 1).collect();
+        truncated.push('…');
+        truncated
+    }
+}
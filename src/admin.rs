@@ -0,0 +1,1552 @@
+use crate::auth;
+use crate::chaos::ChaosInjector;
+use crate::checkout::OverdueTracker;
+use crate::holdcap::HoldCapMonitor;
+use crate::clock::Clock;
+use crate::cluster::{Cluster, ClusterMembers};
+use crate::codec::{self, Body};
+use crate::config::Config;
+use crate::events::audit::RedisAuditSink;
+use crate::events::delivery::{DeadLetter, EventSinkStatus};
+use crate::events::{EventBus, LockEvent, LockEventType};
+use crate::handoff::Handoff;
+use crate::legacy;
+use crate::lockid::LockIdGenerator;
+use crate::lockops::{LockOps, OpError};
+use crate::models::{
+    accepts_problem_json, error_status, AdminCallbackQuery, AuditEntry, AuditQuery, AdminLoginQuery, AdminSessionInfo, ApiResponse, ChaosSettings, LockError, DeadLetterQuery, ExpireLocksRequest, ExportRecord, ForceReleaseRequest, HandoffRequest, HandoffStatus, ImportQuery, ImportReport, LegacyImportQuery, LegacyImportReport, ListLocksQuery,
+    LockExport, LockInfo, NamespaceFreeze, NamespacePolicy, NamespacePolicyRequest, ReleaseByTagRequest, ReplayReport, StuckLock, OverdueCheckout, HoldCapStats, storage_error_code,
+};
+use crate::notices::PendingNotices;
+use crate::oidc::{self, AdminSession, OidcLogin};
+use crate::presence;
+use crate::storage::{LockStorage, RECORD_KINDS};
+use crate::stuck::StuckLockDetector;
+use crate::validation::{Validate, ValidJson, ValidQuery};
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::{from_fn, Compress, Next};
+use actix_web::http::header;
+use actix_web::cookie::{time, Cookie, SameSite};
+use actix_web::{guard, web, Error, HttpMessage, HttpRequest, HttpResponse, Responder};
+use chrono::{DateTime, Utc};
+use futures_util::StreamExt;
+use log::{error, info, warn};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// 导入请求体大小上限
+const IMPORT_BODY_LIMIT: usize = 64 * 1024 * 1024;
+
+/// NDJSON 导入每次写入的锁数量
+const IMPORT_BATCH: usize = 1000;
+
+/// NDJSON 导入单行大小上限
+const IMPORT_LINE_LIMIT: usize = 1024 * 1024;
+
+/// 注册管理接口（`/admin/*`）
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/admin")
+            .wrap(from_fn(require_admin_token))
+            .service(web::resource("/locks").wrap(Compress::default()).route(web::get().to(list_locks)))
+            .route("/locks/{lock_id}", web::get().to(get_lock))
+            .route("/lock/force-release", web::post().to(force_release))
+            .route("/lock/release-by-tag", web::post().to(release_by_tag))
+            .route("/lock/expire", web::post().to(expire_locks))
+            .route("/cleanup", web::post().to(cleanup))
+            .route("/stuck-locks", web::get().to(stuck_locks))
+            .route("/overdue-checkouts", web::get().to(overdue_checkouts))
+            .route("/hold-caps", web::get().to(hold_caps))
+            .route("/namespace", web::get().to(list_namespaces))
+            // 租户的命名空间为 `<tenant_id>/<namespace>`，冻结接口先于策略接口注册
+            .route("/namespace/{name:[^/]+(/[^/]+)?}/freeze", web::put().to(freeze_namespace))
+            .route("/namespace/{name:[^/]+(/[^/]+)?}/freeze", web::delete().to(unfreeze_namespace))
+            .route("/namespace/{name:[^/]+(/[^/]+)?}", web::get().to(get_namespace))
+            .route("/namespace/{name:[^/]+(/[^/]+)?}", web::put().to(put_namespace))
+            .route("/namespace/{name:[^/]+(/[^/]+)?}", web::delete().to(delete_namespace))
+            .service(web::resource("/export").wrap(Compress::default()).route(web::get().to(export_state)))
+            .service(
+                web::resource("/import")
+                    .app_data(web::JsonConfig::default().limit(IMPORT_BODY_LIMIT))
+                    .app_data(web::PayloadConfig::new(IMPORT_BODY_LIMIT))
+                    // NDJSON 请求体流式读取，不受 IMPORT_BODY_LIMIT 限制
+                    .route(
+                        web::post()
+                            .guard(guard::fn_guard(|ctx| codec::is_ndjson(ctx.head().headers())))
+                            .to(import_ndjson),
+                    )
+                    .route(web::post().to(import_state)),
+            )
+            .service(
+                web::resource("/import/legacy")
+                    .app_data(web::PayloadConfig::new(IMPORT_BODY_LIMIT))
+                    .route(web::post().to(import_legacy)),
+            )
+            .route("/chaos", web::get().to(get_chaos))
+            .route("/chaos", web::put().to(put_chaos))
+            .route("/cluster/members", web::get().to(cluster_members))
+            .route("/events", web::get().to(event_delivery))
+            .route("/events/dead-letters", web::get().to(dead_letters))
+            .route("/events/dead-letters/replay", web::post().to(replay_dead_letters))
+            .route("/audit", web::get().to(audit))
+            .route("/handoff", web::get().to(handoff_status))
+            .route("/handoff", web::post().to(handoff_fence))
+            .route("/handoff", web::delete().to(handoff_unfence))
+            .route("/handoff/complete", web::post().to(handoff_complete))
+            .route("/auth/login", web::get().to(auth_login))
+            .route("/auth/callback", web::get().to(auth_callback))
+            .route("/auth/session", web::get().to(auth_session))
+            .route("/auth/logout", web::post().to(auth_logout)),
+    );
+}
+
+/// 要求请求携带 `Authorization: Bearer <ADMIN_TOKEN>`；启用 OIDC 登录时也接受登录会话 Cookie，
+/// 按会话的角色限制可访问的接口，修改类请求还需携带 CSRF 令牌，见 [`crate::oidc`]。两者都未配置时拒绝全部请求
+async fn require_admin_token(
+    config: web::Data<Config>,
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let path = req.path().split_once("/admin/").map_or("", |(_, path)| path).to_string();
+    let login = req.app_data::<web::Data<Arc<OidcLogin>>>().cloned();
+    if login.is_some() && (path == "auth/login" || path == "auth/callback") {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    }
+
+    let token_authorized = config
+        .admin_token
+        .as_ref()
+        .is_some_and(|token| auth::bearer_matches(req.headers(), token));
+    if token_authorized {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    }
+
+    let storage = req.app_data::<web::Data<Arc<dyn LockStorage>>>().cloned();
+    let session = match (login, storage, req.cookie(oidc::SESSION_COOKIE)) {
+        (Some(login), Some(storage), Some(cookie)) => {
+            match login.session(storage.as_ref().as_ref(), cookie.value(), crate::clock::now(req.request())).await {
+                Ok(session) => session,
+                Err(e) => {
+                    log::error!("[ADMIN] Failed to load login session: {:#}", e);
+                    return Ok(reject(req, LockError::StorageUnavailable, "Failed to load login session".to_string()));
+                }
+            }
+        }
+        _ => None,
+    };
+    let Some(session) = session else {
+        warn!("[ADMIN] Unauthorized request: {} {}", req.method(), req.path());
+        return Ok(reject(req, LockError::Unauthorized, "Unauthorized".to_string()));
+    };
+    if !session.role.allows(req.method().as_str(), &path) {
+        warn!("[ADMIN] {} ({:?}) is not allowed to {} {}", session.subject, session.role, req.method(), req.path());
+        let message = format!("Role {:?} is not allowed to {} {}", session.role, req.method(), req.path());
+        return Ok(reject(req, LockError::Forbidden, message));
+    }
+    let csrf_valid = req
+        .headers()
+        .get(oidc::CSRF_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| auth::constant_time_eq(value, &session.csrf_token));
+    if !matches!(req.method().as_str(), "GET" | "HEAD" | "OPTIONS") && !csrf_valid {
+        warn!("[ADMIN] Missing or invalid CSRF token: {} {}", req.method(), req.path());
+        return Ok(reject(req, LockError::Forbidden, "Missing or invalid CSRF token".to_string()));
+    }
+
+    req.extensions_mut().insert(session);
+    Ok(next.call(req).await?.map_into_boxed_body())
+}
+
+/// 认证失败的响应，不受 HTTP_STATUS_MODE 影响，始终返回对应的 4xx 状态码
+fn reject(req: ServiceRequest, code: LockError, message: String) -> ServiceResponse<BoxBody> {
+    let code = code.code();
+    let error = ApiResponse::<()>::error(code, message);
+    let response = if accepts_problem_json(req.request()) {
+        error.into_problem_response(req.request())
+    } else {
+        error.respond_with_status(req.request(), error_status(code))
+    };
+    req.into_response(response)
+}
+
+/// 列出锁，`Accept: application/x-ndjson` 时以 NDJSON 流式输出，每行一个锁
+#[utoipa::path(
+    get,
+    path = "/api/admin/locks",
+    tag = "admin",
+    params(ListLocksQuery),
+    responses(
+        (status = 200, description = "锁列表", body = ApiResponse<Vec<LockInfo>>),
+        (status = 200, description = "NDJSON 锁列表，每行一个锁", content_type = "application/x-ndjson", body = LockInfo)
+    )
+)]
+pub async fn list_locks(
+    req: HttpRequest,
+    storage: web::Data<Arc<dyn LockStorage>>,
+    query: ValidQuery<ListLocksQuery>,
+) -> HttpResponse {
+    match storage.list_locks().await {
+        Ok(mut locks) => {
+            locks.retain(|lock_info| query.matches(lock_info));
+            locks.sort_by(|a, b| {
+                (&a.namespace, &a.business_id).cmp(&(&b.namespace, &b.business_id))
+            });
+            if codec::accepts_ndjson(&req) {
+                return codec::ndjson(locks.into_iter());
+            }
+            ApiResponse::success(locks).respond_to(&req)
+        }
+        Err(e) => {
+            error!("Failed to list locks: {}", e);
+            ApiResponse::<Vec<LockInfo>>::error(
+                storage_error_code(LockError::AdminStorageError, &e),
+                format!("Failed to list locks: {}", e),
+            )
+            .respond_to(&req)
+        }
+    }
+}
+
+/// 查看锁详情
+#[utoipa::path(
+    get,
+    path = "/api/admin/locks/{lock_id}",
+    tag = "admin",
+    params(("lock_id" = String, Path, description = "锁 ID")),
+    responses(
+        (status = 200, description = "锁详情", body = ApiResponse<LockInfo>),
+        (status = 200, description = "锁不存在", body = ApiResponse<LockInfo>)
+    )
+)]
+pub async fn get_lock(
+    storage: web::Data<Arc<dyn LockStorage>>,
+    lock_id: web::Path<String>,
+) -> ApiResponse<LockInfo> {
+    match storage.get_lock_by_id(&lock_id).await {
+        Ok(Some(lock_info)) => ApiResponse::success(lock_info),
+        Ok(None) => ApiResponse::<LockInfo>::error(
+            LockError::AdminNotFound,
+            "Lock not found".to_string(),
+        ),
+        Err(e) => {
+            error!("Failed to get lock: {}", e);
+            ApiResponse::<LockInfo>::error(
+                storage_error_code(LockError::AdminStorageError, &e),
+                format!("Failed to get lock: {}", e),
+            )
+        }
+    }
+}
+
+/// 强制释放锁（不校验持有人）
+#[utoipa::path(
+    post,
+    path = "/api/admin/lock/force-release",
+    tag = "admin",
+    request_body = ForceReleaseRequest,
+    responses(
+        (status = 200, description = "强制释放成功", body = ApiResponse<serde_json::Value>),
+        (status = 200, description = "锁不存在", body = ApiResponse<serde_json::Value>)
+    )
+)]
+pub async fn force_release(
+    storage: web::Data<Arc<dyn LockStorage>>,
+    events: web::Data<Arc<EventBus>>,
+    req: ValidJson<ForceReleaseRequest>,
+) -> ApiResponse<serde_json::Value> {
+    // 优先按 lock_id 定位，否则按 namespace + business_id 定位
+    let lock_key = match (&req.lock_id, &req.business_id) {
+        (Some(lock_id), _) => match storage.get_lock_by_id(lock_id).await {
+            Ok(Some(lock_info)) => lock_info.get_lock_key(),
+            Ok(None) => {
+                return ApiResponse::<serde_json::Value>::error(
+                    LockError::AdminNotFound,
+                    "Lock not found".to_string(),
+                )
+            }
+            Err(e) => {
+                error!("Failed to get lock: {}", e);
+                return ApiResponse::<serde_json::Value>::error(
+                    storage_error_code(LockError::AdminStorageError, &e),
+                    format!("Failed to get lock: {}", e),
+                );
+            }
+        },
+        (None, Some(business_id)) => format!("{}:{}", req.namespace, business_id),
+        (None, None) => {
+            return ApiResponse::<serde_json::Value>::error(
+                LockError::InvalidAdminRequest,
+                "Either lock_id or business_id is required".to_string(),
+            )
+        }
+    };
+
+    match storage.force_release(&lock_key).await {
+        Ok(Some(lock_info)) => {
+            info!(
+                "[ADMIN FORCE RELEASE] Lock force released - lock_id: {}, lock_key: {}",
+                lock_info.lock_id, lock_key
+            );
+            events.publish(LockEvent::new(LockEventType::ForceReleased, lock_info.clone()));
+            ApiResponse::success(serde_json::json!({
+                "released": true,
+                "lock": lock_info
+            }))
+        }
+        Ok(None) => ApiResponse::<serde_json::Value>::error(
+            LockError::AdminNotFound,
+            "Lock not found".to_string(),
+        ),
+        Err(e) => {
+            error!("Failed to force release lock: {}", e);
+            ApiResponse::<serde_json::Value>::error(
+                storage_error_code(LockError::AdminStorageError, &e),
+                format!("Failed to force release lock: {}", e),
+            )
+        }
+    }
+}
+
+/// 按标签批量释放锁（不校验持有人）
+#[utoipa::path(
+    post,
+    path = "/api/admin/lock/release-by-tag",
+    tag = "admin",
+    request_body = ReleaseByTagRequest,
+    responses(
+        (status = 200, description = "释放结果", body = ApiResponse<serde_json::Value>)
+    )
+)]
+pub async fn release_by_tag(
+    storage: web::Data<Arc<dyn LockStorage>>,
+    events: web::Data<Arc<EventBus>>,
+    req: ValidJson<ReleaseByTagRequest>,
+) -> ApiResponse<serde_json::Value> {
+    let filter = ListLocksQuery {
+        namespace: req.namespace.clone(),
+        tag: Some(req.tag.clone()),
+    };
+    let locks = match storage.list_locks().await {
+        Ok(locks) => locks,
+        Err(e) => {
+            error!("Failed to list locks: {}", e);
+            return ApiResponse::<serde_json::Value>::error(
+                storage_error_code(LockError::AdminStorageError, &e),
+                format!("Failed to list locks: {}", e),
+            );
+        }
+    };
+
+    // 按 lock_id 释放，列出之后被重新申请的锁不会被误释放
+    let mut released = Vec::new();
+    for lock_info in locks.into_iter().filter(|lock_info| filter.matches(lock_info)) {
+        match storage.release(&lock_info.lock_id).await {
+            Ok(Some(lock_info)) => {
+                events.publish(LockEvent::new(LockEventType::ForceReleased, lock_info.clone()));
+                released.push(lock_info);
+            }
+            Ok(None) => {}
+            Err(e) => {
+                error!("Failed to release lock {}: {}", lock_info.lock_id, e);
+                return ApiResponse::<serde_json::Value>::error(
+                    LockError::AdminStorageError,
+                    format!(
+                        "Failed to release lock {} after releasing {} locks: {}",
+                        lock_info.lock_id,
+                        released.len(),
+                        e
+                    ),
+                );
+            }
+        }
+    }
+
+    info!(
+        "[ADMIN RELEASE BY TAG] Released {} locks - tag: {}, namespace: {:?}",
+        released.len(),
+        req.tag,
+        req.namespace
+    );
+    ApiResponse::success(serde_json::json!({
+        "released": released.len(),
+        "locks": released
+    }))
+}
+
+/// 立即执行一次过期清理，与定时清理相同，发布 expired 事件；Redis 存储由键过期自动删除，始终返回 0
+#[utoipa::path(
+    post,
+    path = "/api/admin/cleanup",
+    tag = "admin",
+    responses(
+        (status = 200, description = "被清理的锁数量和锁（不含在场记录），以及被清理的在场记录数量", body = ApiResponse<serde_json::Value>)
+    )
+)]
+pub async fn cleanup(ops: web::Data<LockOps>) -> ApiResponse<serde_json::Value> {
+    let expired = match ops.cleanup_expired().await {
+        Ok(expired) => expired,
+        Err(e) => {
+            error!("Failed to cleanup expired locks: {}", e);
+            return ApiResponse::<serde_json::Value>::error(
+                storage_error_code(LockError::AdminStorageError, &e),
+                format!("Failed to cleanup expired locks: {}", e),
+            );
+        }
+    };
+    let (presence, locks): (Vec<LockInfo>, Vec<LockInfo>) = expired
+        .into_iter()
+        .partition(|lock_info| presence::is_presence_namespace(&lock_info.namespace));
+    info!(
+        "[ADMIN CLEANUP] Removed {} expired locks and {} presence records",
+        locks.len(),
+        presence.len()
+    );
+    ApiResponse::success(serde_json::json!({
+        "removed": locks.len(),
+        "presence_removed": presence.len(),
+        "locks": locks
+    }))
+}
+
+/// 按持有时长或心跳间隔批量强制过期锁（不校验持有人）
+#[utoipa::path(
+    post,
+    path = "/api/admin/lock/expire",
+    tag = "admin",
+    request_body = ExpireLocksRequest,
+    responses(
+        (status = 200, description = "被过期的锁，dry_run 时为满足条件的锁", body = ApiResponse<serde_json::Value>),
+        (status = 200, description = "未指定 older_than_seconds 或 no_heartbeat_for", body = ApiResponse<serde_json::Value>)
+    )
+)]
+pub async fn expire_locks(
+    storage: web::Data<Arc<dyn LockStorage>>,
+    events: web::Data<Arc<EventBus>>,
+    clock: web::Data<Arc<dyn Clock>>,
+    req: ValidJson<ExpireLocksRequest>,
+) -> ApiResponse<serde_json::Value> {
+    let locks = match storage.list_locks().await {
+        Ok(locks) => locks,
+        Err(e) => {
+            error!("Failed to list locks: {}", e);
+            return ApiResponse::<serde_json::Value>::error(
+                storage_error_code(LockError::AdminStorageError, &e),
+                format!("Failed to list locks: {}", e),
+            );
+        }
+    };
+
+    let now = clock.now();
+    let mut matched: Vec<LockInfo> = locks
+        .into_iter()
+        .filter(|lock_info| !lock_info.is_expired_at(now) && req.matches(lock_info, now))
+        .collect();
+    matched.sort_by_key(|lock_info| lock_info.locked_at);
+    if req.dry_run {
+        return ApiResponse::success(serde_json::json!({
+            "expired": matched.len(),
+            "dry_run": true,
+            "locks": matched
+        }));
+    }
+
+    // 按 lock_id 释放，列出之后被续期的锁仍会被释放，重新申请的锁不受影响
+    let mut expired = Vec::new();
+    for lock_info in matched {
+        match storage.release(&lock_info.lock_id).await {
+            Ok(Some(lock_info)) => {
+                events.publish(LockEvent::new(LockEventType::ForceReleased, lock_info.clone()));
+                expired.push(lock_info);
+            }
+            Ok(None) => {}
+            Err(e) => {
+                error!("Failed to expire lock {}: {}", lock_info.lock_id, e);
+                return ApiResponse::<serde_json::Value>::error(
+                    LockError::AdminStorageError,
+                    format!(
+                        "Failed to expire lock {} after expiring {} locks: {}",
+                        lock_info.lock_id,
+                        expired.len(),
+                        e
+                    ),
+                );
+            }
+        }
+    }
+
+    info!(
+        "[ADMIN EXPIRE] Expired {} locks - older_than_seconds: {:?}, no_heartbeat_for: {:?}, namespace: {:?}",
+        expired.len(),
+        req.older_than_seconds,
+        req.no_heartbeat_for,
+        req.namespace
+    );
+    ApiResponse::success(serde_json::json!({
+        "expired": expired.len(),
+        "dry_run": false,
+        "locks": expired
+    }))
+}
+
+/// 列出命名空间策略
+#[utoipa::path(
+    get,
+    path = "/api/admin/namespace",
+    tag = "admin",
+    responses(
+        (status = 200, description = "命名空间策略列表", body = ApiResponse<Vec<NamespacePolicy>>)
+    )
+)]
+pub async fn list_namespaces(storage: web::Data<Arc<dyn LockStorage>>) -> ApiResponse<Vec<NamespacePolicy>> {
+    match storage.list_namespaces().await {
+        Ok(mut namespaces) => {
+            namespaces.sort_by(|a, b| a.name.cmp(&b.name));
+            ApiResponse::success(namespaces)
+        }
+        Err(e) => {
+            error!("Failed to list namespaces: {}", e);
+            ApiResponse::<Vec<NamespacePolicy>>::error(
+                storage_error_code(LockError::AdminStorageError, &e),
+                format!("Failed to list namespaces: {}", e),
+            )
+        }
+    }
+}
+
+/// 查看命名空间策略
+#[utoipa::path(
+    get,
+    path = "/api/admin/namespace/{name}",
+    tag = "admin",
+    params(("name" = String, Path, description = "命名空间")),
+    responses(
+        (status = 200, description = "命名空间策略", body = ApiResponse<NamespacePolicy>),
+        (status = 200, description = "命名空间不存在", body = ApiResponse<NamespacePolicy>)
+    )
+)]
+pub async fn get_namespace(
+    storage: web::Data<Arc<dyn LockStorage>>,
+    name: web::Path<String>,
+) -> ApiResponse<NamespacePolicy> {
+    match storage.get_namespace(&name).await {
+        Ok(Some(policy)) => ApiResponse::success(policy),
+        Ok(None) => ApiResponse::<NamespacePolicy>::error(
+            LockError::AdminNotFound,
+            "Namespace not found".to_string(),
+        ),
+        Err(e) => {
+            error!("Failed to get namespace: {}", e);
+            ApiResponse::<NamespacePolicy>::error(
+                storage_error_code(LockError::AdminStorageError, &e),
+                format!("Failed to get namespace: {}", e),
+            )
+        }
+    }
+}
+
+/// 创建或更新命名空间策略
+#[utoipa::path(
+    put,
+    path = "/api/admin/namespace/{name}",
+    tag = "admin",
+    params(("name" = String, Path, description = "命名空间")),
+    request_body = NamespacePolicyRequest,
+    responses(
+        (status = 200, description = "保存成功", body = ApiResponse<NamespacePolicy>),
+        (status = 200, description = "策略不合法", body = ApiResponse<NamespacePolicy>)
+    )
+)]
+pub async fn put_namespace(
+    storage: web::Data<Arc<dyn LockStorage>>,
+    config: web::Data<Config>,
+    name: web::Path<String>,
+    req: Body<NamespacePolicyRequest>,
+) -> ApiResponse<NamespacePolicy> {
+    let policy = req.into_inner().into_policy(name.into_inner());
+    if let Err(message) = policy.validate(config.lock_max_timeout) {
+        return ApiResponse::<NamespacePolicy>::error(LockError::InvalidAdminRequest, message);
+    }
+    if config.nats_url.is_none() && policy.event_routes.iter().any(|route| route.nats_subject.is_some()) {
+        return ApiResponse::<NamespacePolicy>::error(LockError::InvalidAdminRequest, "event route nats_subject requires NATS_URL".to_string());
+    }
+
+    match storage.put_namespace(policy.clone()).await {
+        Ok(()) => {
+            info!(
+                "[ADMIN NAMESPACE] Namespace policy saved - name: {}, default_timeout: {:?}, max_timeout: {:?}, max_locks: {:?}, allow_queue: {}, hierarchical: {}, advisory: {}, event_routes: {}",
+                policy.name, policy.default_timeout, policy.max_timeout, policy.max_locks, policy.allow_queue, policy.hierarchical, policy.advisory, policy.event_routes.len()
+            );
+            ApiResponse::success(policy)
+        }
+        Err(e) => {
+            error!("Failed to save namespace: {}", e);
+            ApiResponse::<NamespacePolicy>::error(
+                storage_error_code(LockError::AdminStorageError, &e),
+                format!("Failed to save namespace: {}", e),
+            )
+        }
+    }
+}
+
+/// 删除命名空间策略（命名空间下已有的锁不受影响）
+#[utoipa::path(
+    delete,
+    path = "/api/admin/namespace/{name}",
+    tag = "admin",
+    params(("name" = String, Path, description = "命名空间")),
+    responses(
+        (status = 200, description = "删除成功", body = ApiResponse<serde_json::Value>),
+        (status = 200, description = "命名空间不存在", body = ApiResponse<serde_json::Value>)
+    )
+)]
+pub async fn delete_namespace(
+    storage: web::Data<Arc<dyn LockStorage>>,
+    name: web::Path<String>,
+) -> ApiResponse<serde_json::Value> {
+    match storage.delete_namespace(&name).await {
+        Ok(true) => {
+            info!("[ADMIN NAMESPACE] Namespace policy deleted - name: {}", name);
+            ApiResponse::success(serde_json::json!({
+                "deleted": true
+            }))
+        }
+        Ok(false) => ApiResponse::<serde_json::Value>::error(
+            LockError::AdminNotFound,
+            "Namespace not found".to_string(),
+        ),
+        Err(e) => {
+            error!("Failed to delete namespace: {}", e);
+            ApiResponse::<serde_json::Value>::error(
+                storage_error_code(LockError::AdminStorageError, &e),
+                format!("Failed to delete namespace: {}", e),
+            )
+        }
+    }
+}
+
+/// 冻结命名空间：冻结期间新的申请返回错误码 1019，已持有的锁照常心跳和释放；
+/// 指定 from、until 时只在该时间段内冻结，命名空间没有策略时以默认策略创建；
+/// 命名空间中当前持有的锁在下一次心跳时收到 `namespace_frozen` 通知
+#[utoipa::path(
+    put,
+    path = "/api/admin/namespace/{name}/freeze",
+    tag = "admin",
+    params(("name" = String, Path, description = "命名空间")),
+    request_body = NamespaceFreeze,
+    responses(
+        (status = 200, description = "冻结后的策略", body = ApiResponse<NamespacePolicy>),
+        (status = 200, description = "冻结设置不合法", body = ApiResponse<NamespacePolicy>)
+    )
+)]
+pub async fn freeze_namespace(
+    storage: web::Data<Arc<dyn LockStorage>>,
+    config: web::Data<Config>,
+    clock: web::Data<Arc<dyn Clock>>,
+    notices: web::Data<Arc<PendingNotices>>,
+    name: web::Path<String>,
+    req: Body<NamespaceFreeze>,
+) -> ApiResponse<NamespacePolicy> {
+    let mut policy = match storage.get_namespace(&name).await {
+        Ok(policy) => policy.unwrap_or_else(|| NamespacePolicy::unrestricted(&name)),
+        Err(e) => {
+            error!("Failed to get namespace: {}", e);
+            return ApiResponse::<NamespacePolicy>::error(
+                storage_error_code(LockError::AdminStorageError, &e),
+                format!("Failed to get namespace: {}", e),
+            );
+        }
+    };
+    policy.freeze = Some(req.into_inner());
+    if let Err(message) = policy.validate(config.lock_max_timeout) {
+        return ApiResponse::<NamespacePolicy>::error(LockError::InvalidAdminRequest, message);
+    }
+
+    match storage.put_namespace(policy.clone()).await {
+        Ok(()) => {
+            let freeze = policy.freeze.as_ref();
+            warn!(
+                "[ADMIN NAMESPACE] Namespace frozen - name: {}, from: {:?}, until: {:?}, reason: {:?}",
+                policy.name,
+                freeze.and_then(|freeze| freeze.from),
+                freeze.and_then(|freeze| freeze.until),
+                freeze.and_then(|freeze| freeze.reason.as_deref())
+            );
+            if let Some(freeze) = freeze {
+                let now = clock.now();
+                match storage.list_prefix(&format!("{}:", policy.name)).await {
+                    Ok(locks) => {
+                        let held: Vec<LockInfo> = locks
+                            .into_iter()
+                            .filter(|lock_info| lock_info.namespace == policy.name && !lock_info.is_expired_at(now))
+                            .collect();
+                        notices.notify_frozen(&held, freeze, now);
+                    }
+                    Err(e) => warn!("Failed to list locks of frozen namespace {}: {}", policy.name, e),
+                }
+            }
+            ApiResponse::success(policy)
+        }
+        Err(e) => {
+            error!("Failed to save namespace: {}", e);
+            ApiResponse::<NamespacePolicy>::error(
+                storage_error_code(LockError::AdminStorageError, &e),
+                format!("Failed to save namespace: {}", e),
+            )
+        }
+    }
+}
+
+/// 解除命名空间冻结（包括尚未开始的定时冻结）
+#[utoipa::path(
+    delete,
+    path = "/api/admin/namespace/{name}/freeze",
+    tag = "admin",
+    params(("name" = String, Path, description = "命名空间")),
+    responses(
+        (status = 200, description = "解除冻结后的策略", body = ApiResponse<NamespacePolicy>),
+        (status = 200, description = "命名空间不存在", body = ApiResponse<NamespacePolicy>)
+    )
+)]
+pub async fn unfreeze_namespace(
+    storage: web::Data<Arc<dyn LockStorage>>,
+    name: web::Path<String>,
+) -> ApiResponse<NamespacePolicy> {
+    let mut policy = match storage.get_namespace(&name).await {
+        Ok(Some(policy)) => policy,
+        Ok(None) => {
+            return ApiResponse::<NamespacePolicy>::error(LockError::AdminNotFound, "Namespace not found".to_string())
+        }
+        Err(e) => {
+            error!("Failed to get namespace: {}", e);
+            return ApiResponse::<NamespacePolicy>::error(
+                storage_error_code(LockError::AdminStorageError, &e),
+                format!("Failed to get namespace: {}", e),
+            );
+        }
+    };
+    if policy.freeze.take().is_none() {
+        return ApiResponse::success(policy);
+    }
+
+    match storage.put_namespace(policy.clone()).await {
+        Ok(()) => {
+            info!("[ADMIN NAMESPACE] Namespace unfrozen - name: {}", policy.name);
+            ApiResponse::success(policy)
+        }
+        Err(e) => {
+            error!("Failed to save namespace: {}", e);
+            ApiResponse::<NamespacePolicy>::error(
+                storage_error_code(LockError::AdminStorageError, &e),
+                format!("Failed to save namespace: {}", e),
+            )
+        }
+    }
+}
+
+/// 导出全部锁和命名空间策略，导出结果与存储后端无关，可导入到任意后端
+///
+/// `Accept: application/x-ndjson` 时以 NDJSON 流式输出，每行一条记录，`type` 为 `namespace` 或 `lock`，命名空间策略在前。
+#[utoipa::path(
+    get,
+    path = "/api/admin/export",
+    tag = "admin",
+    responses(
+        (status = 200, description = "锁状态", body = ApiResponse<LockExport>),
+        (status = 200, description = "NDJSON 锁状态，每行一条记录", content_type = "application/x-ndjson", body = Object)
+    )
+)]
+pub async fn export_state(req: HttpRequest, storage: web::Data<Arc<dyn LockStorage>>) -> HttpResponse {
+    match export(storage.as_ref().as_ref()).await {
+        Ok(export) => {
+            info!(
+                "[ADMIN EXPORT] Exported {} locks and {} namespaces",
+                export.locks.len(),
+                export.namespaces.len()
+            );
+            if codec::accepts_ndjson(&req) {
+                return codec::ndjson(
+                    export
+                        .namespaces
+                        .into_iter()
+                        .map(ExportRecord::Namespace)
+                        .chain(export.locks.into_iter().map(|lock_info| ExportRecord::Lock(Box::new(lock_info)))),
+                );
+            }
+            ApiResponse::success(export).respond_to(&req)
+        }
+        Err(e) => {
+            error!("Failed to export locks: {}", e);
+            ApiResponse::<LockExport>::error(storage_error_code(LockError::AdminStorageError, &e), format!("Failed to export locks: {}", e))
+                .respond_to(&req)
+        }
+    }
+}
+
+/// 导入锁和命名空间策略
+///
+/// 锁按原样写入（保留 lock_id、时间戳和版本号），已过期的锁、存储中已存在的相同 lock_id 的锁以及
+/// lock_key 已被其他未过期的锁占用的锁会被跳过；命名空间策略覆盖同名策略。`dry_run=true` 时只返回导入结果，不写入存储。
+#[utoipa::path(
+    post,
+    path = "/api/admin/import",
+    tag = "admin",
+    params(ImportQuery),
+    request_body = LockExport,
+    responses(
+        (status = 200, description = "导入结果", body = ApiResponse<ImportReport>),
+        (status = 200, description = "命名空间策略不合法", body = ApiResponse<ImportReport>)
+    )
+)]
+pub async fn import_state(
+    storage: web::Data<Arc<dyn LockStorage>>,
+    clock: web::Data<Arc<dyn Clock>>,
+    config: web::Data<Config>,
+    query: ValidQuery<ImportQuery>,
+    req: ValidJson<LockExport>,
+) -> ApiResponse<ImportReport> {
+    let LockExport { locks, namespaces, .. } = req.0;
+    for policy in &namespaces {
+        if let Err(message) = policy.validate(config.lock_max_timeout) {
+            return ApiResponse::<ImportReport>::error(
+                LockError::InvalidAdminRequest,
+                format!("Invalid namespace policy {}: {}", policy.name, message),
+            );
+        }
+    }
+
+    let mut report = ImportReport {
+        dry_run: query.dry_run,
+        ..ImportReport::default()
+    };
+    match import(storage.as_ref().as_ref(), locks, namespaces, &mut report, clock.now()).await {
+        Ok(()) => {
+            info!(
+                "[ADMIN IMPORT] Import finished - dry_run: {}, imported: {}, unchanged: {}, expired: {}, conflicts: {}, namespaces: {}",
+                report.dry_run,
+                report.imported,
+                report.unchanged,
+                report.expired,
+                report.conflicts.len(),
+                report.namespaces
+            );
+            ApiResponse::success(report)
+        }
+        Err(e) => {
+            error!("Failed to import locks: {}", e);
+            ApiResponse::<ImportReport>::error(
+                LockError::AdminStorageError,
+                format!(
+                    "Failed to import locks after importing {} locks: {}",
+                    report.imported, e
+                ),
+            )
+        }
+    }
+}
+
+/// 导入 NDJSON 格式的导出结果
+///
+/// 请求体按行流式读取，锁每 [`IMPORT_BATCH`] 条写入一次，可用 `Content-Encoding: zstd` 或 `gzip` 压缩。
+/// 某一行格式或校验失败时停止导入并返回错误，此前的行已经写入，可先以 `dry_run=true` 检查。
+pub async fn import_ndjson(
+    req: HttpRequest,
+    storage: web::Data<Arc<dyn LockStorage>>,
+    clock: web::Data<Arc<dyn Clock>>,
+    config: web::Data<Config>,
+    query: ValidQuery<ImportQuery>,
+    payload: web::Payload,
+) -> ApiResponse<ImportReport> {
+    let storage = storage.as_ref().as_ref();
+    let mut report = ImportReport {
+        dry_run: query.dry_run,
+        ..ImportReport::default()
+    };
+    let mut payload = actix_web::dev::Decompress::from_headers(payload.into_inner(), req.headers());
+    let mut buf = Vec::new();
+    let mut batch = Vec::new();
+    let mut line_no = 0;
+    let mut eof = false;
+    while !eof {
+        match payload.next().await {
+            Some(Ok(chunk)) => buf.extend_from_slice(&chunk),
+            Some(Err(e)) => {
+                return ApiResponse::<ImportReport>::error(LockError::InvalidAdminRequest, format!("Failed to read import body: {}", e))
+            }
+            None => eof = true,
+        }
+        let mut lines: Vec<Vec<u8>> = Vec::new();
+        while let Some(pos) = buf.iter().position(|b| *b == b'\n') {
+            lines.push(buf.drain(..=pos).collect());
+        }
+        if eof && !buf.is_empty() {
+            lines.push(std::mem::take(&mut buf));
+        } else if buf.len() > IMPORT_LINE_LIMIT {
+            return ApiResponse::<ImportReport>::error(
+                LockError::InvalidAdminRequest,
+                format!("Line {} exceeds {} bytes", line_no + 1, IMPORT_LINE_LIMIT),
+            );
+        }
+        for line in lines {
+            line_no += 1;
+            if line.iter().all(u8::is_ascii_whitespace) {
+                continue;
+            }
+            let record = match serde_json::from_slice::<ExportRecord>(&line) {
+                Ok(record) => record,
+                Err(e) => return import_line_error(line_no, LockError::InvalidAdminRequest, e.to_string(), &report),
+            };
+            if let Err(errors) = record.validate() {
+                let message = errors
+                    .errors
+                    .iter()
+                    .map(|error| format!("{} {}", error.field, error.message))
+                    .collect::<Vec<_>>()
+                    .join("; ");
+                return import_line_error(line_no, LockError::ValidationFailed, message, &report);
+            }
+            let namespaces = match record {
+                ExportRecord::Lock(lock_info) => {
+                    batch.push(*lock_info);
+                    if batch.len() < IMPORT_BATCH {
+                        continue;
+                    }
+                    Vec::new()
+                }
+                ExportRecord::Namespace(policy) => {
+                    if let Err(message) = policy.validate(config.lock_max_timeout) {
+                        let message = format!("Invalid namespace policy {}: {}", policy.name, message);
+                        return import_line_error(line_no, LockError::InvalidAdminRequest, message, &report);
+                    }
+                    vec![policy]
+                }
+            };
+            if let Err(e) = import(storage, std::mem::take(&mut batch), namespaces, &mut report, clock.now()).await {
+                return import_failed(&report, e);
+            }
+        }
+    }
+    if let Err(e) = import(storage, batch, Vec::new(), &mut report, clock.now()).await {
+        return import_failed(&report, e);
+    }
+    info!(
+        "[ADMIN IMPORT] NDJSON import finished - dry_run: {}, imported: {}, unchanged: {}, expired: {}, conflicts: {}, namespaces: {}",
+        report.dry_run,
+        report.imported,
+        report.unchanged,
+        report.expired,
+        report.conflicts.len(),
+        report.namespaces
+    );
+    ApiResponse::success(report)
+}
+
+fn import_line_error(line_no: usize, code: LockError, message: String, report: &ImportReport) -> ApiResponse<ImportReport> {
+    ApiResponse::error(
+        code,
+        format!(
+            "Invalid import line {} after importing {} locks: {}",
+            line_no, report.imported, message
+        ),
+    )
+}
+
+fn import_failed(report: &ImportReport, e: anyhow::Error) -> ApiResponse<ImportReport> {
+    error!("Failed to import locks: {}", e);
+    ApiResponse::error(
+        storage_error_code(LockError::AdminStorageError, &e),
+        format!("Failed to import locks after importing {} locks: {}", report.imported, e),
+    )
+}
+
+/// 从旧锁表迁移：将旧系统中的锁记录批量创建为锁
+///
+/// 请求体为 JSON 数组，或 `Content-Type: text/csv` 的 CSV（首行为表头，列名同 JSON 字段）。每行单独校验，
+/// 校验失败、同一用户已持有或已被其他用户持有的行跳过并在结果中列出，其余行创建为锁，不产生锁事件。
+/// `dry_run=true` 时只返回迁移结果，不写入存储。
+#[utoipa::path(
+    post,
+    path = "/api/admin/import/legacy",
+    tag = "admin",
+    params(LegacyImportQuery),
+    request_body(content = Vec<LegacyLockRecord>, description = "JSON 数组，或 Content-Type 为 text/csv 的 CSV"),
+    responses(
+        (status = 200, description = "迁移结果", body = ApiResponse<LegacyImportReport>),
+        (status = 200, description = "请求体格式不正确", body = ApiResponse<LegacyImportReport>)
+    )
+)]
+pub async fn import_legacy(
+    storage: web::Data<Arc<dyn LockStorage>>,
+    clock: web::Data<Arc<dyn Clock>>,
+    config: web::Data<Config>,
+    id_generator: web::Data<Arc<dyn LockIdGenerator>>,
+    http_req: HttpRequest,
+    query: ValidQuery<LegacyImportQuery>,
+    body: web::Bytes,
+) -> ApiResponse<LegacyImportReport> {
+    let content_type = http_req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok());
+    let records = match legacy::parse(content_type, &body) {
+        Ok(records) => records,
+        Err(message) => {
+            return ApiResponse::<LegacyImportReport>::error(LockError::InvalidAdminRequest, format!("Invalid legacy import: {}", message))
+        }
+    };
+
+    let mut report = LegacyImportReport {
+        dry_run: query.dry_run,
+        ..LegacyImportReport::default()
+    };
+    match legacy::import(
+        storage.as_ref().as_ref(),
+        records,
+        query.timeout,
+        &config,
+        id_generator.as_ref().as_ref(),
+        &mut report,
+        clock.now(),
+    )
+    .await
+    {
+        Ok(()) => {
+            info!(
+                "[ADMIN IMPORT] Legacy import finished - dry_run: {}, total: {}, imported: {}, unchanged: {}, conflicts: {}, invalid: {}",
+                report.dry_run,
+                report.total,
+                report.imported,
+                report.unchanged,
+                report.conflicts.len(),
+                report.invalid.len()
+            );
+            ApiResponse::success(report)
+        }
+        Err(e) => {
+            error!("Failed to import legacy locks: {}", e);
+            ApiResponse::<LegacyImportReport>::error(
+                storage_error_code(LockError::AdminStorageError, &e),
+                format!(
+                    "Failed to import legacy locks after importing {} locks: {}",
+                    report.imported, e
+                ),
+            )
+        }
+    }
+}
+
+/// 查看故障注入设置
+#[utoipa::path(
+    get,
+    path = "/api/admin/chaos",
+    tag = "admin",
+    responses(
+        (status = 200, description = "故障注入设置", body = ApiResponse<ChaosSettings>),
+        (status = 200, description = "未启用故障注入", body = ApiResponse<ChaosSettings>)
+    )
+)]
+pub async fn get_chaos(chaos: Option<web::Data<Arc<ChaosInjector>>>) -> ApiResponse<ChaosSettings> {
+    match chaos {
+        Some(chaos) => ApiResponse::success(chaos.settings()),
+        None => chaos_disabled(),
+    }
+}
+
+/// 调整故障注入设置，立即生效，重启后恢复为环境变量中的设置
+#[utoipa::path(
+    put,
+    path = "/api/admin/chaos",
+    tag = "admin",
+    request_body = ChaosSettings,
+    responses(
+        (status = 200, description = "调整成功", body = ApiResponse<ChaosSettings>),
+        (status = 200, description = "未启用故障注入", body = ApiResponse<ChaosSettings>)
+    )
+)]
+pub async fn put_chaos(
+    chaos: Option<web::Data<Arc<ChaosInjector>>>,
+    req: ValidJson<ChaosSettings>,
+) -> ApiResponse<ChaosSettings> {
+    let Some(chaos) = chaos else {
+        return chaos_disabled();
+    };
+    let settings = req.0;
+    warn!(
+        "[ADMIN CHAOS] Fault injection updated - latency: {}ms at {}, error rate: {}, expire rate: {}",
+        settings.latency_ms, settings.latency_rate, settings.error_rate, settings.expire_rate
+    );
+    chaos.update(settings.clone());
+    ApiResponse::success(settings)
+}
+
+fn chaos_disabled() -> ApiResponse<ChaosSettings> {
+    ApiResponse::<ChaosSettings>::error(
+        LockError::AdminNotFound,
+        "Fault injection is disabled, set CHAOS_ENABLED=true to enable".to_string(),
+    )
+}
+
+/// 查看集群成员，包含本实例和通过 gossip 得知的其他实例
+#[utoipa::path(
+    get,
+    path = "/api/admin/cluster/members",
+    tag = "admin",
+    responses(
+        (status = 200, description = "集群成员", body = ApiResponse<ClusterMembers>),
+        (status = 200, description = "未启用集群发现", body = ApiResponse<ClusterMembers>)
+    )
+)]
+pub async fn cluster_members(cluster: Option<web::Data<Arc<Cluster>>>) -> ApiResponse<ClusterMembers> {
+    match cluster {
+        Some(cluster) => ApiResponse::success(cluster.members()),
+        None => ApiResponse::<ClusterMembers>::error(
+            LockError::AdminNotFound,
+            "Cluster discovery is disabled, set CLUSTER_DISCOVERY to enable".to_string(),
+        ),
+    }
+}
+
+/// 查看卡住的锁：最近一次检测时持有时间超过命名空间阈值的锁，按持有时长降序
+#[utoipa::path(
+    get,
+    path = "/api/admin/stuck-locks",
+    tag = "admin",
+    responses(
+        (status = 200, description = "卡住的锁", body = ApiResponse<Vec<StuckLock>>)
+    )
+)]
+pub async fn stuck_locks(stuck_detector: web::Data<Arc<StuckLockDetector>>) -> ApiResponse<Vec<StuckLock>> {
+    ApiResponse::success(stuck_detector.list())
+}
+
+/// 查看逾期的借出：最近一次检测时超过预计归还时间仍未释放的锁，按逾期时长降序
+#[utoipa::path(
+    get,
+    path = "/api/admin/overdue-checkouts",
+    tag = "admin",
+    responses(
+        (status = 200, description = "逾期的借出", body = ApiResponse<Vec<OverdueCheckout>>)
+    )
+)]
+pub async fn overdue_checkouts(overdue_tracker: web::Data<Arc<OverdueTracker>>) -> ApiResponse<Vec<OverdueCheckout>> {
+    ApiResponse::success(overdue_tracker.list())
+}
+
+/// 查看各命名空间最长持有时间的执行统计：受限制的锁、收到提醒和达到上限的累计数量
+#[utoipa::path(
+    get,
+    path = "/api/admin/hold-caps",
+    tag = "admin",
+    responses(
+        (status = 200, description = "按命名空间的统计", body = ApiResponse<Vec<HoldCapStats>>)
+    )
+)]
+pub async fn hold_caps(hold_cap_monitor: web::Data<Arc<HoldCapMonitor>>) -> ApiResponse<Vec<HoldCapStats>> {
+    ApiResponse::success(hold_cap_monitor.stats())
+}
+
+/// 查看锁事件的投递状态：每个可靠投递目标的缓冲事件数、投递延迟、重试和死信数量
+#[utoipa::path(
+    get,
+    path = "/api/admin/events",
+    tag = "admin",
+    responses(
+        (status = 200, description = "投递状态", body = ApiResponse<Vec<EventSinkStatus>>)
+    )
+)]
+pub async fn event_delivery(
+    events: web::Data<Arc<EventBus>>,
+    clock: web::Data<Arc<dyn Clock>>,
+) -> ApiResponse<Vec<EventSinkStatus>> {
+    ApiResponse::success(events.delivery_status(clock.now()))
+}
+
+/// 查看重试耗尽或因缓冲队列已满未能投递的事件
+#[utoipa::path(
+    get,
+    path = "/api/admin/events/dead-letters",
+    tag = "admin",
+    params(DeadLetterQuery),
+    responses(
+        (status = 200, description = "死信，按失败时间排列", body = ApiResponse<Vec<DeadLetter>>)
+    )
+)]
+pub async fn dead_letters(
+    events: web::Data<Arc<EventBus>>,
+    query: web::Query<DeadLetterQuery>,
+) -> ApiResponse<Vec<DeadLetter>> {
+    ApiResponse::success(events.dead_letters(query.sink.as_deref()))
+}
+
+/// 将死信重新放入缓冲队列投递
+#[utoipa::path(
+    post,
+    path = "/api/admin/events/dead-letters/replay",
+    tag = "admin",
+    params(DeadLetterQuery),
+    responses(
+        (status = 200, description = "重新投递的事件数", body = ApiResponse<ReplayReport>)
+    )
+)]
+pub async fn replay_dead_letters(
+    events: web::Data<Arc<EventBus>>,
+    query: web::Query<DeadLetterQuery>,
+) -> ApiResponse<ReplayReport> {
+    let replayed = events.replay_dead_letters(query.sink.as_deref());
+    info!(
+        "[ADMIN EVENTS] Replaying {} dead-lettered events, sink: {}",
+        replayed,
+        query.sink.as_deref().unwrap_or("all")
+    );
+    ApiResponse::success(ReplayReport { replayed })
+}
+
+/// 读取审计 Stream，从旧到新排列；传入上次读到的最后一条记录 ID 可继续读取之后的记录
+#[utoipa::path(
+    get,
+    path = "/api/admin/audit",
+    tag = "admin",
+    params(AuditQuery),
+    responses(
+        (status = 200, description = "审计记录，未启用审计 Stream 时返回错误码 4002", body = ApiResponse<Vec<AuditEntry>>)
+    )
+)]
+pub async fn audit(
+    audit: Option<web::Data<Arc<RedisAuditSink>>>,
+    query: ValidQuery<AuditQuery>,
+) -> ApiResponse<Vec<AuditEntry>> {
+    let Some(audit) = audit else {
+        return ApiResponse::error(LockError::AdminNotFound, "Audit stream is disabled".to_string());
+    };
+    match audit.tail(query.after.as_deref(), query.count).await {
+        Ok(entries) => ApiResponse::success(entries),
+        Err(e) => {
+            error!("[ADMIN] Failed to read audit stream: {}", e);
+            ApiResponse::error(
+                storage_error_code(LockError::AdminStorageError, &e),
+                format!("Failed to read audit stream: {}", e),
+            )
+        }
+    }
+}
+
+/// 本实例的交接状态和纪元
+#[utoipa::path(
+    get,
+    path = "/api/admin/handoff",
+    tag = "admin",
+    responses(
+        (status = 200, description = "交接状态，非内存存储时返回错误码 4002", body = ApiResponse<HandoffStatus>)
+    )
+)]
+pub async fn handoff_status(handoff: Option<web::Data<Arc<Handoff>>>) -> ApiResponse<HandoffStatus> {
+    match handoff {
+        Some(handoff) => ApiResponse::success(handoff.status()),
+        None => handoff_disabled(),
+    }
+}
+
+/// 隔离本实例：拒绝管理接口以外的请求，停止持久化和过期锁清理，由新实例接收锁状态
+///
+/// 同一个新实例重复隔离时返回原状态，已被其他实例隔离时返回错误码 4003。
+#[utoipa::path(
+    post,
+    path = "/api/admin/handoff",
+    tag = "admin",
+    request_body = HandoffRequest,
+    responses(
+        (status = 200, description = "隔离后的交接状态", body = ApiResponse<HandoffStatus>)
+    )
+)]
+pub async fn handoff_fence(
+    handoff: Option<web::Data<Arc<Handoff>>>,
+    clock: web::Data<Arc<dyn Clock>>,
+    req: ValidJson<HandoffRequest>,
+) -> ApiResponse<HandoffStatus> {
+    let Some(handoff) = handoff else {
+        return handoff_disabled();
+    };
+    match handoff.fence(&req.successor, clock.now()).await {
+        Ok(status) => ApiResponse::success(status),
+        Err(message) => ApiResponse::error(LockError::InvalidAdminRequest, message),
+    }
+}
+
+/// 解除隔离，新实例接收失败时调用，本实例继续处理请求；交接已完成时返回错误码 4003
+#[utoipa::path(
+    delete,
+    path = "/api/admin/handoff",
+    tag = "admin",
+    responses(
+        (status = 200, description = "解除隔离后的交接状态", body = ApiResponse<HandoffStatus>)
+    )
+)]
+pub async fn handoff_unfence(handoff: Option<web::Data<Arc<Handoff>>>) -> ApiResponse<HandoffStatus> {
+    let Some(handoff) = handoff else {
+        return handoff_disabled();
+    };
+    match handoff.unfence() {
+        Ok(status) => ApiResponse::success(status),
+        Err(message) => ApiResponse::error(LockError::InvalidAdminRequest, message),
+    }
+}
+
+/// 新实例已接收锁状态，本实例不再允许解除隔离，可以停止
+#[utoipa::path(
+    post,
+    path = "/api/admin/handoff/complete",
+    tag = "admin",
+    request_body = HandoffRequest,
+    responses(
+        (status = 200, description = "交接完成后的状态，本实例未被该新实例隔离时返回错误码 4003", body = ApiResponse<HandoffStatus>)
+    )
+)]
+pub async fn handoff_complete(
+    handoff: Option<web::Data<Arc<Handoff>>>,
+    req: ValidJson<HandoffRequest>,
+) -> ApiResponse<HandoffStatus> {
+    let Some(handoff) = handoff else {
+        return handoff_disabled();
+    };
+    match handoff.complete(&req.successor) {
+        Ok(status) => ApiResponse::success(status),
+        Err(message) => ApiResponse::error(LockError::InvalidAdminRequest, message),
+    }
+}
+
+fn handoff_disabled() -> ApiResponse<HandoffStatus> {
+    ApiResponse::error(LockError::AdminNotFound, "Handoff requires memory storage".to_string())
+}
+
+/// 跳转到身份提供方登录管理接口
+#[utoipa::path(
+    get,
+    path = "/api/admin/auth/login",
+    tag = "admin",
+    params(AdminLoginQuery),
+    responses(
+        (status = 302, description = "跳转到身份提供方的授权地址"),
+        (status = 200, description = "未启用 OIDC 登录（4002）或身份提供方不可用（4007）", body = ApiResponse<AdminSessionInfo>)
+    )
+)]
+pub async fn auth_login(
+    req: HttpRequest,
+    login: Option<web::Data<Arc<OidcLogin>>>,
+    storage: web::Data<Arc<dyn LockStorage>>,
+    clock: web::Data<Arc<dyn Clock>>,
+    query: ValidQuery<AdminLoginQuery>,
+) -> HttpResponse {
+    let Some(login) = login else {
+        return login_disabled().respond_to(&req);
+    };
+    match login.begin(storage.as_ref().as_ref(), query.return_to.clone(), clock.now()).await {
+        Ok((url, state)) => HttpResponse::Found()
+            .insert_header((header::LOCATION, url))
+            .cookie(
+                auth_cookie(&login, oidc::LOGIN_COOKIE, state)
+                    .http_only(true)
+                    .max_age(time::Duration::seconds(oidc::LOGIN_TTL))
+                    .finish(),
+            )
+            .finish(),
+        Err(e) => ApiResponse::<AdminSessionInfo>::error(e.code, e.message).respond_to(&req),
+    }
+}
+
+/// 身份提供方的登录回调，成功后设置会话 Cookie 和 CSRF Cookie，
+/// 发起登录时指定了 return_to 则跳转过去，否则返回会话信息
+#[utoipa::path(
+    get,
+    path = "/api/admin/auth/callback",
+    tag = "admin",
+    params(AdminCallbackQuery),
+    responses(
+        (status = 302, description = "登录成功，跳转到 return_to"),
+        (status = 200, description = "登录成功", body = ApiResponse<AdminSessionInfo>),
+        (status = 200, description = "登录失败（4006）、没有管理角色（4005）或身份提供方不可用（4007）", body = ApiResponse<AdminSessionInfo>)
+    )
+)]
+pub async fn auth_callback(
+    req: HttpRequest,
+    login: Option<web::Data<Arc<OidcLogin>>>,
+    storage: web::Data<Arc<dyn LockStorage>>,
+    clock: web::Data<Arc<dyn Clock>>,
+    query: web::Query<AdminCallbackQuery>,
+) -> HttpResponse {
+    let Some(login) = login else {
+        return login_disabled().respond_to(&req);
+    };
+    let result = match (&query.error, &query.code, &query.state) {
+        (Some(error), _, _) => Err(OpError::new(
+            LockError::LoginFailed,
+            format!(
+                "Identity provider rejected the login: {} {}",
+                error,
+                query.error_description.as_deref().unwrap_or_default()
+            )
+            .trim_end()
+            .to_string(),
+        )),
+        // state 需与发起登录的浏览器中的登录状态 Cookie 一致，防止登录 CSRF
+        (None, Some(code), Some(state)) if req.cookie(oidc::LOGIN_COOKIE).is_some_and(|cookie| cookie.value() == state) => {
+            login.complete(storage.as_ref().as_ref(), state, code, clock.now()).await
+        }
+        _ => Err(OpError::new(LockError::LoginFailed, "Login state is invalid or expired".to_string())),
+    };
+
+    let mut response = match result {
+        Ok((session, return_to)) => {
+            info!("[ADMIN] {} logged in via OIDC", session.subject);
+            let mut response = match return_to {
+                Some(return_to) => HttpResponse::Found().insert_header((header::LOCATION, return_to)).finish(),
+                None => ApiResponse::success(AdminSessionInfo::from(&session)).respond_to(&req),
+            };
+            let max_age = time::Duration::seconds((session.expires_at - clock.now()).num_seconds());
+            let cookies = [
+                auth_cookie(&login, oidc::SESSION_COOKIE, session.id.clone())
+                    .http_only(true)
+                    .max_age(max_age)
+                    .finish(),
+                auth_cookie(&login, oidc::CSRF_COOKIE, session.csrf_token.clone())
+                    .max_age(max_age)
+                    .finish(),
+            ];
+            for cookie in cookies {
+                let _ = response.add_cookie(&cookie);
+            }
+            response
+        }
+        Err(e) => {
+            warn!("[ADMIN] OIDC login failed: {}", e.message);
+            ApiResponse::<AdminSessionInfo>::error(e.code, e.message).respond_to(&req)
+        }
+    };
+    let _ = response.add_removal_cookie(&auth_cookie(&login, oidc::LOGIN_COOKIE, String::new()).finish());
+    response
+}
+
+/// 查看当前的登录会话
+#[utoipa::path(
+    get,
+    path = "/api/admin/auth/session",
+    tag = "admin",
+    responses(
+        (status = 200, description = "当前会话", body = ApiResponse<AdminSessionInfo>),
+        (status = 200, description = "使用令牌认证，没有登录会话", body = ApiResponse<AdminSessionInfo>)
+    )
+)]
+pub async fn auth_session(session: Option<web::ReqData<AdminSession>>) -> ApiResponse<AdminSessionInfo> {
+    match session {
+        Some(session) => ApiResponse::success(AdminSessionInfo::from(&*session)),
+        None => ApiResponse::<AdminSessionInfo>::error(LockError::AdminNotFound, "No login session".to_string()),
+    }
+}
+
+/// 注销当前的登录会话，需携带 CSRF 令牌
+#[utoipa::path(
+    post,
+    path = "/api/admin/auth/logout",
+    tag = "admin",
+    responses(
+        (status = 200, description = "已注销", body = ApiResponse<bool>),
+        (status = 200, description = "使用令牌认证，没有登录会话", body = ApiResponse<bool>)
+    )
+)]
+pub async fn auth_logout(
+    req: HttpRequest,
+    login: Option<web::Data<Arc<OidcLogin>>>,
+    storage: web::Data<Arc<dyn LockStorage>>,
+    session: Option<web::ReqData<AdminSession>>,
+) -> HttpResponse {
+    let (Some(login), Some(session)) = (login, session) else {
+        return ApiResponse::<bool>::error(LockError::AdminNotFound, "No login session".to_string()).respond_to(&req);
+    };
+    let logged_out = match login.logout(storage.as_ref().as_ref(), &session.id).await {
+        Ok(logged_out) => logged_out,
+        Err(e) => {
+            log::error!("[ADMIN] Failed to remove login session: {:#}", e);
+            return ApiResponse::<bool>::error(LockError::StorageUnavailable, "Failed to remove login session".to_string())
+                .respond_to(&req);
+        }
+    };
+    info!("[ADMIN] {} logged out", session.subject);
+    let mut response = ApiResponse::success(logged_out).respond_to(&req);
+    for name in [oidc::SESSION_COOKIE, oidc::CSRF_COOKIE] {
+        let _ = response.add_removal_cookie(&auth_cookie(&login, name, String::new()).finish());
+    }
+    response
+}
+
+fn login_disabled() -> ApiResponse<AdminSessionInfo> {
+    ApiResponse::error(LockError::AdminNotFound, "OIDC login is disabled, set OIDC_ISSUER to enable".to_string())
+}
+
+/// 登录相关 Cookie 的公共属性：整站可用，SameSite=Lax 使身份提供方跳转回来时携带登录状态
+fn auth_cookie<'a>(login: &OidcLogin, name: &'a str, value: String) -> actix_web::cookie::CookieBuilder<'a> {
+    Cookie::build(name, value)
+        .path("/")
+        .same_site(SameSite::Lax)
+        .secure(login.secure_cookies())
+}
+
+/// 读取存储中的全部锁、命名空间策略和记录
+pub async fn export(storage: &dyn LockStorage) -> anyhow::Result<LockExport> {
+    let mut locks = storage.list_locks().await?;
+    locks.sort_by(|a, b| (&a.namespace, &a.business_id).cmp(&(&b.namespace, &b.business_id)));
+    let mut namespaces = storage.list_namespaces().await?;
+    namespaces.sort_by(|a, b| a.name.cmp(&b.name));
+    let mut records = BTreeMap::new();
+    for kind in RECORD_KINDS {
+        let values: BTreeMap<String, String> = storage.list_records(kind).await?.into_iter().collect();
+        if !values.is_empty() {
+            records.insert(kind.to_string(), values);
+        }
+    }
+    Ok(LockExport {
+        exported_at: Utc::now(),
+        locks,
+        namespaces,
+        records,
+    })
+}
+
+/// 将锁和命名空间策略写入存储，截至 now 已过期的锁不导入，结果累计到 report，`report.dry_run` 为 true 时不写入
+pub async fn import(
+    storage: &dyn LockStorage,
+    locks: Vec<LockInfo>,
+    namespaces: Vec<NamespacePolicy>,
+    report: &mut ImportReport,
+    now: DateTime<Utc>,
+) -> anyhow::Result<()> {
+    for policy in namespaces {
+        if !report.dry_run {
+            storage.put_namespace(policy).await?;
+        }
+        report.namespaces += 1;
+    }
+
+    for lock_info in locks {
+        if lock_info.is_expired_at(now) {
+            report.expired += 1;
+            continue;
+        }
+        let lock_key = lock_info.get_lock_key();
+        match storage.get_lock(&lock_key).await? {
+            Some(existing_lock) if existing_lock.lock_id == lock_info.lock_id => {
+                report.unchanged += 1;
+                continue;
+            }
+            Some(existing_lock) if !existing_lock.is_expired_at(now) => {
+                report.conflicts.push(lock_key);
+                continue;
+            }
+            _ => {}
+        }
+        if report.dry_run || storage.restore(lock_info).await? {
+            report.imported += 1;
+        } else {
+            report.conflicts.push(lock_key);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,40 @@
+//! 提示性（advisory）命名空间
+//!
+//! 命名空间策略开启 `advisory` 后申请锁总是成功：每个申请人各自持有一把锁，业务键保存为 `<business_id>#<user_id>`，
+//! 申请响应和锁状态中返回该 business_id 的所有当前持有人，由调用方决定如何处理（如"3 人正在查看，1 人正在编辑"）。
+//! 持有人照常心跳和按 lock_id 释放，锁超时未心跳同样会过期。
+
+use crate::models::LockInfo;
+use crate::storage::LockStorage;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+
+/// business_id 与持有人之间的分隔符，提示性命名空间的 business_id 中不能包含
+pub const SEPARATOR: char = '#';
+
+pub fn is_valid_business_id(business_id: &str) -> bool {
+    !business_id.contains(SEPARATOR)
+}
+
+/// 持有人的锁保存时使用的 business_id
+pub fn holder_business_id(business_id: &str, user_id: &str) -> String {
+    format!("{}{}{}", business_id, SEPARATOR, user_id)
+}
+
+/// business_id 的所有截至 now 未过期的持有人的锁，按获取时间排列
+pub async fn holders(
+    storage: &dyn LockStorage,
+    namespace: &str,
+    business_id: &str,
+    now: DateTime<Utc>,
+) -> Result<Vec<LockInfo>> {
+    let key_prefix = format!("{}:{}{}", namespace, business_id, SEPARATOR);
+    let mut holders: Vec<LockInfo> = storage
+        .list_prefix(&key_prefix)
+        .await?
+        .into_iter()
+        .filter(|lock_info| lock_info.namespace == namespace && !lock_info.is_expired_at(now))
+        .collect();
+    holders.sort_by_key(|lock_info| lock_info.locked_at);
+    Ok(holders)
+}
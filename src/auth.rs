@@ -0,0 +1,29 @@
+//! 共享令牌认证
+//!
+//! 管理接口的 `ADMIN_TOKEN` 和 Raft 节点间的 `RAFT_PEER_TOKEN` 都通过 `Authorization: Bearer <token>` 传递，
+//! 比较时耗时与令牌内容无关，避免按响应时间逐字节猜出令牌。
+
+use actix_web::http::header::{self, HeaderMap};
+
+/// 请求头中的 Bearer 令牌
+pub fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+}
+
+/// 请求携带的 Bearer 令牌与 expected 一致
+pub fn bearer_matches(headers: &HeaderMap, expected: &str) -> bool {
+    bearer_token(headers).is_some_and(|token| constant_time_eq(token, expected))
+}
+
+/// 常量时间比较，长度不同时直接返回 false（只泄露长度）
+pub fn constant_time_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let diff = a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y));
+    std::hint::black_box(diff) == 0
+}
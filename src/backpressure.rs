@@ -0,0 +1,145 @@
+//! 并发请求上限（背压）
+//!
+//! 配置 `MAX_INFLIGHT_REQUESTS` 后，`/api` 下同时处理的请求数达到上限时，新请求最多排队等待 `REQUEST_QUEUE_TIMEOUT_MS`，
+//! 排队数达到 `MAX_QUEUED_REQUESTS` 或等待超时即返回 HTTP 503（错误码 7006）和 `Retry-After` 响应头，不受 `HTTP_STATUS_MODE` 影响，
+//! 避免过载时请求无限堆积、延迟持续上升。管理接口和等待锁释放的长轮询不受限制。
+//! 处理中和排队中的请求数无论是否配置上限都会统计，见 `/metrics`。
+
+use crate::config::Config;
+use crate::latency::{self, Stage};
+use crate::lockops::OpError;
+use crate::models::{accepts_problem_json, ApiResponse, LockError};
+use crate::timeout::LONG_POLL_PATHS;
+use crate::v2;
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderValue, RETRY_AFTER};
+use actix_web::http::StatusCode;
+use actix_web::middleware::Next;
+use actix_web::{web, Error};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+
+/// 并发请求的计数和上限
+pub struct Backpressure {
+    permits: Option<Semaphore>, // 未配置上限时为 None
+    max_inflight: usize,
+    max_queued: usize,
+    queue_timeout: Duration,
+    retry_after: u64,
+    inflight: AtomicUsize,
+    queued: AtomicUsize,
+    rejected: AtomicU64,
+}
+
+/// 处理中或排队中的请求，离开时计数减一
+struct Tracked<'a>(&'a AtomicUsize);
+
+impl<'a> Tracked<'a> {
+    fn enter(counter: &'a AtomicUsize) -> Self {
+        counter.fetch_add(1, Ordering::Relaxed);
+        Self(counter)
+    }
+}
+
+impl Drop for Tracked<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl Backpressure {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            permits: (config.max_inflight_requests > 0).then(|| Semaphore::new(config.max_inflight_requests)),
+            max_inflight: config.max_inflight_requests,
+            max_queued: config.max_queued_requests,
+            queue_timeout: Duration::from_millis(config.request_queue_timeout_ms),
+            retry_after: config.overload_retry_after,
+            inflight: AtomicUsize::new(0),
+            queued: AtomicUsize::new(0),
+            rejected: AtomicU64::new(0),
+        }
+    }
+
+    /// 正在处理的请求数
+    pub fn inflight(&self) -> usize {
+        self.inflight.load(Ordering::Relaxed)
+    }
+
+    /// 正在排队等待的请求数
+    pub fn queued(&self) -> usize {
+        self.queued.load(Ordering::Relaxed)
+    }
+
+    /// 因过载被拒绝的请求总数
+    pub fn rejected(&self) -> u64 {
+        self.rejected.load(Ordering::Relaxed)
+    }
+
+    /// 同时处理的最大请求数，0 表示不限制
+    pub fn max_inflight(&self) -> usize {
+        self.max_inflight
+    }
+}
+
+/// 限制 `/api` 下同时处理的请求数，过载时返回 503
+pub async fn backpressure_guard(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let backpressure = req.app_data::<web::Data<Arc<Backpressure>>>().cloned();
+    let exempt = req.path().starts_with("/api/admin")
+        || req.path().starts_with("/api/swagger-ui")
+        || LONG_POLL_PATHS.contains(&req.path());
+    let Some(backpressure) = backpressure.filter(|_| !exempt) else {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    };
+
+    let permit = match &backpressure.permits {
+        None => None,
+        Some(permits) => match permits.try_acquire() {
+            Ok(permit) => Some(permit),
+            Err(_) => {
+                let _queued = Tracked::enter(&backpressure.queued);
+                if backpressure.queued() > backpressure.max_queued {
+                    return Ok(overloaded(req, &backpressure));
+                }
+                match latency::time(Stage::Queue, tokio::time::timeout(backpressure.queue_timeout, permits.acquire())).await {
+                    Ok(Ok(permit)) => Some(permit),
+                    _ => return Ok(overloaded(req, &backpressure)),
+                }
+            }
+        },
+    };
+
+    let _inflight = Tracked::enter(&backpressure.inflight);
+    let response = next.call(req).await;
+    drop(permit);
+    Ok(response?.map_into_boxed_body())
+}
+
+fn overloaded(req: ServiceRequest, backpressure: &Backpressure) -> ServiceResponse<BoxBody> {
+    backpressure.rejected.fetch_add(1, Ordering::Relaxed);
+    log::warn!(
+        "[BACKPRESSURE] Rejected {} {}: {} requests in flight, {} queued",
+        req.method(),
+        req.path(),
+        backpressure.inflight(),
+        backpressure.queued()
+    );
+    let error = ApiResponse::<()>::error(LockError::Overloaded, "Server overloaded, retry later".to_string());
+    let mut response = if v2::is_v2(req.request()) {
+        v2::error_response(req.request(), OpError::new(error.code, error.message))
+    } else if accepts_problem_json(req.request()) {
+        error.into_problem_response(req.request())
+    } else {
+        error.respond_with_status(req.request(), StatusCode::SERVICE_UNAVAILABLE)
+    };
+    response
+        .headers_mut()
+        .insert(RETRY_AFTER, HeaderValue::from(backpressure.retry_after));
+    req.into_response(response)
+}
@@ -0,0 +1,90 @@
+//! 故障注入（混沌测试模式）
+//!
+//! 仅在 `CHAOS_ENABLED=true` 时启用，供客户端 SDK 验证重试和心跳逻辑，不要在生产环境开启。
+//! 按配置的概率为 `/api` 下的请求注入延迟、直接返回 HTTP 500（错误码 7002），或在心跳前使锁提前过期，
+//! 管理接口 `/api/admin/chaos` 可在运行时查看和调整。管理接口和 Swagger UI 不受影响。
+
+use crate::config::Config;
+use crate::lockops::OpError;
+use crate::models::{accepts_problem_json, ApiResponse, ChaosSettings, LockError};
+use crate::v2;
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::StatusCode;
+use actix_web::middleware::Next;
+use actix_web::{web, Error};
+use parking_lot::RwLock;
+use rand::Rng;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub struct ChaosInjector {
+    settings: RwLock<ChaosSettings>,
+}
+
+impl ChaosInjector {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            settings: RwLock::new(ChaosSettings {
+                latency_rate: config.chaos_latency_rate,
+                latency_ms: config.chaos_latency_ms,
+                error_rate: config.chaos_error_rate,
+                expire_rate: config.chaos_expire_rate,
+            }),
+        }
+    }
+
+    pub fn settings(&self) -> ChaosSettings {
+        self.settings.read().clone()
+    }
+
+    pub fn update(&self, settings: ChaosSettings) {
+        *self.settings.write() = settings;
+    }
+
+    /// 本次心跳前是否使锁提前过期
+    pub fn should_expire(&self) -> bool {
+        roll(self.settings.read().expire_rate)
+    }
+}
+
+fn roll(rate: f64) -> bool {
+    rate > 0.0 && rand::thread_rng().gen_bool(rate.min(1.0))
+}
+
+/// 按概率注入延迟和 HTTP 500，未启用故障注入时直接放行
+pub async fn chaos_guard(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let chaos = req
+        .app_data::<web::Data<Arc<ChaosInjector>>>()
+        .filter(|_| {
+            !req.path().starts_with("/api/admin") && !req.path().starts_with("/api/swagger-ui")
+        })
+        .cloned();
+    let Some(chaos) = chaos else {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    };
+
+    let settings = chaos.settings();
+    if roll(settings.latency_rate) && settings.latency_ms > 0 {
+        log::warn!("[CHAOS] Delaying {} {} by {}ms", req.method(), req.path(), settings.latency_ms);
+        tokio::time::sleep(Duration::from_millis(settings.latency_ms)).await;
+    }
+
+    if roll(settings.error_rate) {
+        log::warn!("[CHAOS] Injecting HTTP 500 for {} {}", req.method(), req.path());
+        let error = ApiResponse::<()>::error(LockError::InjectedFault, "Injected fault".to_string());
+        let response = if v2::is_v2(req.request()) {
+            v2::error_response(req.request(), OpError::new(error.code, error.message))
+        } else if accepts_problem_json(req.request()) {
+            error.into_problem_response(req.request())
+        } else {
+            error.respond_with_status(req.request(), StatusCode::INTERNAL_SERVER_ERROR)
+        };
+        return Ok(req.into_response(response));
+    }
+
+    Ok(next.call(req).await?.map_into_boxed_body())
+}
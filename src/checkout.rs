@@ -0,0 +1,116 @@
+//! 借出与逾期检测
+//!
+//! 借出（`/api/lock/checkout`）是登记了预计归还时间的获取锁，适合素材编辑这类"借出—归还"的工作方式；
+//! 普通申请指定 `due_in_seconds` 效果相同。预计归还时间保存在锁信息的 `due_at` 中，只用于提醒，不影响锁的过期。
+//! 后台任务每 `LOCK_CHECKOUT_CHECK_INTERVAL` 秒扫描一次，超过预计归还时间仍未释放的锁视为逾期：
+//! 第一次检测到时发布 `overdue` 锁事件，配置 `LOCK_CHECKOUT_WEBHOOK_URL` 时同时回调该地址，逾期的锁可通过管理接口查看。
+//! 检测结果保存在本实例进程内，多实例部署时每个实例独立检测，事件和回调也由每个实例各发送一次；
+//! 配置 `LEADER_ELECTION=true` 时只由当选的实例检测，见 [`crate::leader`]。
+
+use crate::events::{EventBus, LockEvent, LockEventType};
+use crate::http;
+use crate::models::OverdueCheckout;
+use crate::storage::LockStorage;
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use log::{error, warn};
+use std::collections::HashMap;
+
+/// 回调请求超时
+const CALLBACK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+pub struct OverdueTracker {
+    webhook_url: Option<String>,
+    client: reqwest::Client,
+    overdue: DashMap<String, OverdueCheckout>, // lock_id -> 最近一次扫描时逾期的锁
+}
+
+impl OverdueTracker {
+    pub fn new(webhook_url: Option<String>) -> Self {
+        Self {
+            webhook_url,
+            client: http::client(CALLBACK_TIMEOUT),
+            overdue: DashMap::new(),
+        }
+    }
+
+    /// 最近一次扫描时逾期的锁，按逾期时长降序
+    pub fn list(&self) -> Vec<OverdueCheckout> {
+        let mut overdue: Vec<OverdueCheckout> = self.overdue.iter().map(|entry| entry.value().clone()).collect();
+        overdue.sort_by_key(|entry| std::cmp::Reverse(entry.overdue_seconds));
+        overdue
+    }
+
+    /// 清空检测结果，本实例不再运行检测时调用
+    pub fn clear(&self) {
+        self.overdue.clear();
+    }
+
+    /// 扫描全部锁，替换检测结果，新逾期的锁记录日志、发布事件并回调
+    pub async fn scan(&self, storage: &dyn LockStorage, events: &EventBus, now: DateTime<Utc>) {
+        let locks = match storage.list_locks().await {
+            Ok(locks) => locks,
+            Err(e) => {
+                error!("[CHECKOUT] Failed to list locks: {}", e);
+                return;
+            }
+        };
+
+        let mut overdue = HashMap::new();
+        for lock_info in locks {
+            let Some(due_at) = lock_info.due_at.filter(|due_at| *due_at <= now) else {
+                continue;
+            };
+            if lock_info.is_expired_at(now) {
+                continue;
+            }
+            let detected_at = self
+                .overdue
+                .get(&lock_info.lock_id)
+                .map(|entry| entry.detected_at);
+            let newly_overdue = detected_at.is_none();
+            let entry = OverdueCheckout {
+                overdue_seconds: lock_info.overdue_secs_at(now),
+                lock: lock_info,
+                due_at,
+                detected_at: detected_at.unwrap_or(now),
+            };
+            if newly_overdue {
+                warn!(
+                    "[CHECKOUT] Lock not returned by {} - lock_id: {}, namespace: {}, business_id: {}, user_id: {}",
+                    due_at, entry.lock.lock_id, entry.lock.namespace, entry.lock.business_id, entry.lock.user_id
+                );
+                events.publish(LockEvent::new(LockEventType::Overdue, entry.lock.clone()));
+                self.notify(&entry);
+            }
+            overdue.insert(entry.lock.lock_id.clone(), entry);
+        }
+        self.overdue.retain(|lock_id, _| overdue.contains_key(lock_id));
+        for (lock_id, entry) in overdue {
+            self.overdue.insert(lock_id, entry);
+        }
+    }
+
+    /// 配置了回调地址时异步回调，失败只记录日志
+    fn notify(&self, entry: &OverdueCheckout) {
+        let Some(webhook_url) = self.webhook_url.clone() else {
+            return;
+        };
+        let client = self.client.clone();
+        let entry = entry.clone();
+        tokio::spawn(async move {
+            let result = client
+                .post(&webhook_url)
+                .json(&entry)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status());
+            if let Err(e) = result {
+                error!(
+                    "[CHECKOUT] Failed to deliver overdue checkout {} to {}: {}",
+                    entry.lock.lock_id, webhook_url, e
+                );
+            }
+        });
+    }
+}
@@ -0,0 +1,33 @@
+//! 时钟
+//!
+//! 锁的过期判断通过 [`Clock`] 取当前时间，生产环境使用系统时钟 [`SystemClock`]，
+//! 测试时可换成手动推进的 [`crate::testing::ManualClock`]，无需等待即可验证过期逻辑。
+
+use actix_web::{web, HttpRequest};
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
+
+/// 当前时间的来源
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// 系统时钟
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// 系统时钟的共享实例
+pub fn system() -> Arc<dyn Clock> {
+    Arc::new(SystemClock)
+}
+
+/// 请求所在应用注册的时钟的当前时间，未注册时钟时取系统时间
+pub fn now(req: &HttpRequest) -> DateTime<Utc> {
+    req.app_data::<web::Data<Arc<dyn Clock>>>()
+        .map_or_else(Utc::now, |clock| clock.now())
+}
@@ -0,0 +1,273 @@
+//! 种子地址发现
+//!
+//! - static：`CLUSTER_PEERS` 中的固定地址
+//! - dns：查询 `CLUSTER_DNS_SRV` 的 SRV 记录（如 Kubernetes Headless Service 的 `_http._tcp.<service>.<namespace>.svc.cluster.local`），
+//!   使用 `/etc/resolv.conf` 中的第一个 DNS 服务器
+//! - kubernetes：以 Pod 的 ServiceAccount 读取 `CLUSTER_K8S_SERVICE` 的 Endpoints，需要 endpoints 的 get 权限
+
+use crate::config::{ClusterDiscovery, Config};
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Deserialize;
+use std::net::IpAddr;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+
+const SERVICE_ACCOUNT_DIR: &str = "/var/run/secrets/kubernetes.io/serviceaccount";
+
+/// DNS 查询和 Kubernetes API 请求的超时
+const DISCOVERY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// SRV 记录类型
+const TYPE_SRV: u16 = 33;
+
+/// 按配置发现其他实例的地址（host:port）
+pub async fn discover(config: &Config) -> Result<Vec<String>> {
+    match config.cluster_discovery {
+        ClusterDiscovery::Off => Ok(Vec::new()),
+        ClusterDiscovery::Static => Ok(config.cluster_peers.clone()),
+        ClusterDiscovery::Dns => {
+            let name = config
+                .cluster_dns_srv
+                .as_deref()
+                .ok_or_else(|| anyhow!("CLUSTER_DNS_SRV is required for dns discovery"))?;
+            resolve_srv(name).await
+        }
+        ClusterDiscovery::Kubernetes => kubernetes_endpoints(config).await,
+    }
+}
+
+/// 地址对应的 HTTP 地址，未指定协议时使用 http
+pub fn base_url(addr: &str) -> String {
+    if addr.contains("://") {
+        addr.trim_end_matches('/').to_string()
+    } else {
+        format!("http://{}", addr)
+    }
+}
+
+/// host:port，IPv6 地址加方括号
+fn host_port(host: &str, port: u16) -> String {
+    if host.contains(':') {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// DNS SRV
+// ---------------------------------------------------------------------------
+
+/// 查询 SRV 记录，按优先级和权重排序
+pub async fn resolve_srv(name: &str) -> Result<Vec<String>> {
+    let nameserver = nameserver().await;
+    let bind = if nameserver.is_ipv6() { "[::]:0" } else { "0.0.0.0:0" };
+    let socket = UdpSocket::bind(bind).await?;
+    socket.connect((nameserver, 53)).await?;
+
+    let id: u16 = rand::random();
+    socket.send(&srv_query(id, name)?).await?;
+    let mut buf = [0u8; 4096];
+    let len = tokio::time::timeout(DISCOVERY_TIMEOUT, socket.recv(&mut buf))
+        .await
+        .map_err(|_| anyhow!("DNS query for {} timed out", name))??;
+
+    let mut records = parse_srv_response(id, &buf[..len]).with_context(|| format!("Invalid DNS response for {}", name))?;
+    records.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| b.1.cmp(&a.1)));
+    Ok(records.into_iter().map(|(_, _, addr)| addr).collect())
+}
+
+/// `/etc/resolv.conf` 中的第一个 DNS 服务器，未配置时使用本机
+async fn nameserver() -> IpAddr {
+    tokio::fs::read_to_string("/etc/resolv.conf")
+        .await
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("nameserver"))
+        .find_map(|addr| addr.trim().parse().ok())
+        .unwrap_or(IpAddr::from([127, 0, 0, 1]))
+}
+
+fn srv_query(id: u16, name: &str) -> Result<Vec<u8>> {
+    let mut query = Vec::with_capacity(18 + name.len());
+    query.extend_from_slice(&id.to_be_bytes());
+    query.extend_from_slice(&[0x01, 0x00]); // 递归查询
+    query.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]); // 1 个问题
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            bail!("Invalid DNS name {}", name);
+        }
+        query.push(label.len() as u8);
+        query.extend_from_slice(label.as_bytes());
+    }
+    query.push(0);
+    query.extend_from_slice(&TYPE_SRV.to_be_bytes());
+    query.extend_from_slice(&1u16.to_be_bytes()); // IN
+    Ok(query)
+}
+
+/// 解析应答中的 SRV 记录：(优先级, 权重, host:port)
+fn parse_srv_response(id: u16, message: &[u8]) -> Result<Vec<(u16, u16, String)>> {
+    if message.len() < 12 || read_u16(message, 0)? != id {
+        bail!("unexpected DNS response");
+    }
+    let flags = read_u16(message, 2)?;
+    if flags & 0x0200 != 0 {
+        bail!("DNS response truncated");
+    }
+    match flags & 0x000f {
+        0 => {}
+        3 => bail!("no such name"),
+        rcode => bail!("DNS error {}", rcode),
+    }
+    let questions = read_u16(message, 4)?;
+    let answers = read_u16(message, 6)?;
+
+    let mut pos = 12;
+    for _ in 0..questions {
+        pos = skip_name(message, pos)? + 4;
+    }
+    let mut records = Vec::new();
+    for _ in 0..answers {
+        pos = skip_name(message, pos)?;
+        let record_type = read_u16(message, pos)?;
+        let length = read_u16(message, pos + 8)? as usize;
+        let data = pos + 10;
+        if record_type == TYPE_SRV {
+            let priority = read_u16(message, data)?;
+            let weight = read_u16(message, data + 2)?;
+            let port = read_u16(message, data + 4)?;
+            let target = read_name(message, data + 6)?;
+            records.push((priority, weight, host_port(target.trim_end_matches('.'), port)));
+        }
+        pos = data + length;
+    }
+    Ok(records)
+}
+
+fn read_u16(message: &[u8], pos: usize) -> Result<u16> {
+    message
+        .get(pos..pos + 2)
+        .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+        .ok_or_else(|| anyhow!("DNS message too short"))
+}
+
+/// 跳过名称，返回名称之后的位置
+fn skip_name(message: &[u8], mut pos: usize) -> Result<usize> {
+    loop {
+        let len = *message.get(pos).ok_or_else(|| anyhow!("DNS message too short"))?;
+        match len {
+            0 => return Ok(pos + 1),
+            len if len & 0xc0 == 0xc0 => return Ok(pos + 2),
+            len => pos += 1 + len as usize,
+        }
+    }
+}
+
+/// 读取名称，支持压缩指针
+fn read_name(message: &[u8], mut pos: usize) -> Result<String> {
+    let mut labels = Vec::new();
+    // 压缩指针最多跟随的次数，避免循环指针
+    for _ in 0..64 {
+        let len = *message.get(pos).ok_or_else(|| anyhow!("DNS message too short"))?;
+        match len {
+            0 => return Ok(labels.join(".")),
+            len if len & 0xc0 == 0xc0 => pos = (read_u16(message, pos)? & 0x3fff) as usize,
+            len => {
+                let label = message
+                    .get(pos + 1..pos + 1 + len as usize)
+                    .ok_or_else(|| anyhow!("DNS message too short"))?;
+                labels.push(String::from_utf8_lossy(label).into_owned());
+                pos += 1 + len as usize;
+            }
+        }
+    }
+    bail!("DNS name too long")
+}
+
+// ---------------------------------------------------------------------------
+// Kubernetes Endpoints
+// ---------------------------------------------------------------------------
+
+#[derive(Deserialize)]
+struct Endpoints {
+    #[serde(default)]
+    subsets: Vec<EndpointSubset>,
+}
+
+#[derive(Deserialize)]
+struct EndpointSubset {
+    #[serde(default)]
+    addresses: Vec<EndpointAddress>,
+    #[serde(default)]
+    ports: Vec<EndpointPort>,
+}
+
+#[derive(Deserialize)]
+struct EndpointAddress {
+    ip: String,
+}
+
+#[derive(Deserialize)]
+struct EndpointPort {
+    name: Option<String>,
+    port: u16,
+}
+
+/// Service 的就绪地址，端口取 `CLUSTER_K8S_PORT_NAME` 指定的端口，未指定时取第一个端口
+async fn kubernetes_endpoints(config: &Config) -> Result<Vec<String>> {
+    let service = config
+        .cluster_k8s_service
+        .as_deref()
+        .ok_or_else(|| anyhow!("CLUSTER_K8S_SERVICE is required for kubernetes discovery"))?;
+    let namespace = match &config.cluster_k8s_namespace {
+        Some(namespace) => namespace.clone(),
+        None => tokio::fs::read_to_string(format!("{}/namespace", SERVICE_ACCOUNT_DIR))
+            .await
+            .context("Failed to read the pod namespace, set CLUSTER_K8S_NAMESPACE")?
+            .trim()
+            .to_string(),
+    };
+    let token = tokio::fs::read_to_string(format!("{}/token", SERVICE_ACCOUNT_DIR))
+        .await
+        .context("Failed to read the service account token")?;
+    let ca = tokio::fs::read(format!("{}/ca.crt", SERVICE_ACCOUNT_DIR))
+        .await
+        .context("Failed to read the service account CA certificate")?;
+
+    let host = std::env::var("KUBERNETES_SERVICE_HOST").unwrap_or_else(|_| "kubernetes.default.svc".to_string());
+    let port = std::env::var("KUBERNETES_SERVICE_PORT")
+        .ok()
+        .and_then(|port| port.parse().ok())
+        .unwrap_or(443);
+    let client = reqwest::Client::builder()
+        .add_root_certificate(reqwest::Certificate::from_pem(&ca)?)
+        .timeout(DISCOVERY_TIMEOUT)
+        .build()?;
+    let endpoints: Endpoints = client
+        .get(format!(
+            "https://{}/api/v1/namespaces/{}/endpoints/{}",
+            host_port(&host, port),
+            namespace,
+            service
+        ))
+        .bearer_auth(token.trim())
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    let mut addrs = Vec::new();
+    for subset in endpoints.subsets {
+        let port = match &config.cluster_k8s_port_name {
+            Some(name) => subset.ports.iter().find(|port| port.name.as_ref() == Some(name)),
+            None => subset.ports.first(),
+        };
+        let Some(port) = port else {
+            continue;
+        };
+        addrs.extend(subset.addresses.iter().map(|address| host_port(&address.ip, port.port)));
+    }
+    Ok(addrs)
+}
@@ -0,0 +1,388 @@
+//! 集群成员
+//!
+//! 多实例部署时，各实例通过 `CLUSTER_DISCOVERY` 指定的方式（固定地址、DNS SRV 或 Kubernetes Endpoints）发现种子地址，
+//! 之后每隔 `CLUSTER_GOSSIP_INTERVAL_MS` 随机选择 `CLUSTER_GOSSIP_FANOUT` 个实例，经 `POST /cluster/gossip`
+//! 交换各自已知的成员状态（存储健康状态和分片状态），成员列表通过 `GET /api/admin/cluster/members` 查看。
+//!
+//! 每个实例维护自己的心跳计数，其他实例只接受更大的（启动时间, 心跳计数），并按本地收到更新的时间判断存活：
+//! 超过 `CLUSTER_SUSPECT_TIMEOUT` 为 suspect，超过 `CLUSTER_DEAD_TIMEOUT` 为 dead 且不再传播，再经过同样时长后移除。
+//!
+//! gossip 请求需携带 `Authorization: Bearer <CLUSTER_PEER_TOKEN>`；成员表最多保存 [`MAX_MEMBERS`] 个实例，
+//! 避免伪造或异常的成员列表占满内存。
+
+pub mod discovery;
+
+use crate::auth;
+use crate::config::{ClusterDiscovery, Config};
+use crate::health;
+use crate::storage::failover::FailoverStorage;
+use crate::storage::sharded::ShardedStorage;
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::{from_fn, Next};
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use log::{debug, info, warn};
+use parking_lot::RwLock;
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use utoipa::ToSchema;
+
+/// 成员表和单次 gossip 中的最大实例数
+pub const MAX_MEMBERS: usize = 1024;
+
+/// 实例的存活状态，由本实例按最近一次收到该实例心跳的时间判断
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum MemberState {
+    Alive,
+    Suspect,
+    Dead,
+}
+
+/// gossip 交换的实例状态
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct MemberInfo {
+    #[schema(example = "fe-lock-0")]
+    pub node_id: String,
+    /// 其他实例访问该实例的地址
+    #[schema(example = "10.0.0.1:8080")]
+    pub addr: String,
+    /// 启动时间（毫秒时间戳），实例重启后增大
+    pub incarnation: i64,
+    /// 心跳计数，每轮 gossip 加 1
+    pub heartbeat: u64,
+    /// 存储状态：ready、degraded 或 unavailable
+    #[schema(example = "ready")]
+    pub status: String,
+    #[schema(example = "redis")]
+    pub storage: String,
+    /// 分片模式下各 Redis 分片在该实例上的状态
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub shards: Vec<ShardStatus>,
+    #[schema(example = "0.1.0")]
+    pub version: String,
+    pub started_at: DateTime<Utc>,
+}
+
+/// Redis 分片的状态
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ShardStatus {
+    #[schema(example = "10.0.0.10:6379/0")]
+    pub shard: String,
+    #[schema(example = "ready")]
+    pub status: String,
+}
+
+/// 成员列表中的实例
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ClusterMember {
+    #[serde(flatten)]
+    pub info: MemberInfo,
+    pub state: MemberState,
+    /// 本实例最近一次收到该实例心跳的时间
+    pub last_seen: DateTime<Utc>,
+    /// 是否为本实例
+    #[serde(rename = "self")]
+    pub is_self: bool,
+}
+
+/// 集群成员列表
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ClusterMembers {
+    /// 本实例的 node_id
+    #[schema(example = "fe-lock-0")]
+    pub node_id: String,
+    pub members: Vec<ClusterMember>,
+}
+
+/// gossip 请求和响应
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GossipMessage {
+    pub members: Vec<MemberInfo>,
+}
+
+struct Member {
+    info: MemberInfo,
+    updated: Instant, // 本地收到更新的时间，用于判断存活
+    last_seen: DateTime<Utc>,
+}
+
+pub struct Cluster {
+    config: Config,
+    local: RwLock<MemberInfo>,
+    members: DashMap<String, Member>,
+    seeds: RwLock<Vec<String>>,
+    client: reqwest::Client,
+}
+
+impl Cluster {
+    /// 按配置创建集群成员表，`CLUSTER_DISCOVERY=off` 时返回 None
+    pub fn from_config(config: &Config) -> Option<Self> {
+        if config.cluster_discovery == ClusterDiscovery::Off {
+            return None;
+        }
+        let started_at = Utc::now();
+        let local = MemberInfo {
+            node_id: config.cluster_node_id.clone(),
+            addr: config.cluster_advertise_addr.clone(),
+            incarnation: started_at.timestamp_millis(),
+            heartbeat: 0,
+            status: "ready".to_string(),
+            storage: config.storage_type.name().to_string(),
+            shards: Vec::new(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            started_at,
+        };
+        // gossip 请求的超时不超过一轮的间隔，避免无响应的实例拖慢下一轮
+        let timeout = Duration::from_millis(config.cluster_gossip_interval_ms.clamp(200, 5000));
+        let client = reqwest::Client::builder()
+            .timeout(timeout)
+            .build()
+            .unwrap_or_default();
+        Some(Self {
+            config: config.clone(),
+            local: RwLock::new(local),
+            members: DashMap::new(),
+            seeds: RwLock::new(Vec::new()),
+            client,
+        })
+    }
+
+    pub fn node_id(&self) -> &str {
+        &self.config.cluster_node_id
+    }
+
+    /// 本实例和已知的其他实例，按 node_id 排序
+    pub fn members(&self) -> ClusterMembers {
+        let now = Instant::now();
+        let mut members = vec![ClusterMember {
+            info: self.local.read().clone(),
+            state: MemberState::Alive,
+            last_seen: Utc::now(),
+            is_self: true,
+        }];
+        members.extend(self.members.iter().map(|member| ClusterMember {
+            info: member.info.clone(),
+            state: self.state(member.updated, now),
+            last_seen: member.last_seen,
+            is_self: false,
+        }));
+        members.sort_by(|a, b| a.info.node_id.cmp(&b.info.node_id));
+        ClusterMembers {
+            node_id: self.node_id().to_string(),
+            members,
+        }
+    }
+
+    fn state(&self, updated: Instant, now: Instant) -> MemberState {
+        let silent = now.saturating_duration_since(updated);
+        if silent >= Duration::from_secs(self.config.cluster_dead_timeout) {
+            MemberState::Dead
+        } else if silent >= Duration::from_secs(self.config.cluster_suspect_timeout) {
+            MemberState::Suspect
+        } else {
+            MemberState::Alive
+        }
+    }
+
+    /// 发送给其他实例的状态：本实例和未判定为 dead 的实例
+    fn gossip_message(&self) -> GossipMessage {
+        let now = Instant::now();
+        let mut members = vec![self.local.read().clone()];
+        members.extend(
+            self.members
+                .iter()
+                .filter(|member| self.state(member.updated, now) != MemberState::Dead)
+                .map(|member| member.info.clone())
+                .take(MAX_MEMBERS - 1),
+        );
+        GossipMessage { members }
+    }
+
+    /// 合并其他实例发来的状态，只接受更新的（启动时间, 心跳计数）
+    pub fn merge(&self, members: Vec<MemberInfo>) {
+        for info in members {
+            if info.node_id == self.node_id() {
+                continue;
+            }
+            let newer = self.members.get(&info.node_id).is_none_or(|member| {
+                (info.incarnation, info.heartbeat) > (member.info.incarnation, member.info.heartbeat)
+            });
+            if !newer {
+                continue;
+            }
+            if !self.members.contains_key(&info.node_id) {
+                if self.members.len() >= MAX_MEMBERS - 1 {
+                    warn!("[CLUSTER] Member table is full, ignoring {} at {}", info.node_id, info.addr);
+                    continue;
+                }
+                info!("[CLUSTER] Discovered member {} at {}", info.node_id, info.addr);
+            }
+            self.members.insert(
+                info.node_id.clone(),
+                Member {
+                    info,
+                    updated: Instant::now(),
+                    last_seen: Utc::now(),
+                },
+            );
+        }
+    }
+
+    /// 移除 dead 之后又经过 `CLUSTER_DEAD_TIMEOUT` 的实例
+    fn prune(&self) {
+        let now = Instant::now();
+        let retention = Duration::from_secs(self.config.cluster_dead_timeout.saturating_mul(2));
+        self.members.retain(|node_id, member| {
+            let keep = now.saturating_duration_since(member.updated) < retention;
+            if !keep {
+                info!("[CLUSTER] Removed dead member {} at {}", node_id, member.info.addr);
+            }
+            keep
+        });
+    }
+
+    /// 增加心跳计数并刷新本实例的存储状态
+    fn refresh_local(&self, failover: Option<&FailoverStorage>, sharded: Option<&ShardedStorage>) {
+        let health = failover.map(|failover| failover.health());
+        let shards = sharded.map(|sharded| sharded.health());
+        let mut local = self.local.write();
+        local.heartbeat += 1;
+        local.status = health::storage_status(health.as_ref(), shards.as_deref()).to_string();
+        local.shards = shards
+            .unwrap_or_default()
+            .into_iter()
+            .map(|shard| ShardStatus {
+                status: health::storage_status(Some(&shard.health), None).to_string(),
+                shard: shard.shard,
+            })
+            .collect();
+    }
+
+    /// 本轮联系的实例：未判定为 dead 的已知实例和尚未加入成员表的种子地址
+    fn gossip_targets(&self) -> Vec<String> {
+        let now = Instant::now();
+        let mut known: Vec<String> = self
+            .members
+            .iter()
+            .filter(|member| self.state(member.updated, now) != MemberState::Dead)
+            .map(|member| member.info.addr.clone())
+            .collect();
+        let mut candidates: Vec<String> = self
+            .seeds
+            .read()
+            .iter()
+            .filter(|seed| **seed != self.config.cluster_advertise_addr && !known.contains(seed))
+            .cloned()
+            .collect();
+        candidates.append(&mut known);
+        let mut rng = rand::thread_rng();
+        candidates
+            .choose_multiple(&mut rng, self.config.cluster_gossip_fanout.max(1))
+            .cloned()
+            .collect()
+    }
+
+    async fn exchange(&self, addr: &str) -> anyhow::Result<()> {
+        let url = format!("{}/cluster/gossip", discovery::base_url(addr));
+        let reply: GossipMessage = self
+            .client
+            .post(url)
+            .bearer_auth(self.config.cluster_peer_token.as_deref().unwrap_or_default())
+            .json(&self.gossip_message())
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        self.merge(reply.members);
+        Ok(())
+    }
+
+    /// 定时发现种子地址并与其他实例交换状态，需在 tokio 运行时中调用
+    pub fn spawn(
+        self: &Arc<Self>,
+        failover: Option<Arc<FailoverStorage>>,
+        sharded: Option<Arc<ShardedStorage>>,
+    ) {
+        info!(
+            "[CLUSTER] Joining as {} at {} via {:?} discovery",
+            self.node_id(),
+            self.config.cluster_advertise_addr,
+            self.config.cluster_discovery
+        );
+
+        let cluster = self.clone();
+        tokio::spawn(async move {
+            let mut interval =
+                tokio::time::interval(Duration::from_secs(cluster.config.cluster_discovery_interval.max(1)));
+            loop {
+                interval.tick().await;
+                match discovery::discover(&cluster.config).await {
+                    Ok(seeds) => *cluster.seeds.write() = seeds,
+                    Err(e) => warn!("[CLUSTER] Peer discovery failed: {:#}", e),
+                }
+            }
+        });
+
+        let cluster = self.clone();
+        tokio::spawn(async move {
+            let mut interval =
+                tokio::time::interval(Duration::from_millis(cluster.config.cluster_gossip_interval_ms.max(1)));
+            loop {
+                interval.tick().await;
+                cluster.refresh_local(failover.as_deref(), sharded.as_deref());
+                cluster.prune();
+                let targets = cluster.gossip_targets();
+                let results =
+                    futures_util::future::join_all(targets.iter().map(|addr| cluster.exchange(addr))).await;
+                for (addr, result) in targets.iter().zip(results) {
+                    if let Err(e) = result {
+                        debug!("[CLUSTER] Gossip with {} failed: {}", addr, e);
+                    }
+                }
+            }
+        });
+    }
+}
+
+/// 注册节点间的 gossip 接口
+pub fn configure_routes(cfg: &mut web::ServiceConfig, cluster: Arc<Cluster>) {
+    cfg.service(
+        web::scope("/cluster")
+            .app_data(web::Data::new(cluster))
+            .wrap(from_fn(peer_guard))
+            .route("/gossip", web::post().to(gossip)),
+    );
+}
+
+/// 要求请求携带 `Authorization: Bearer <CLUSTER_PEER_TOKEN>`，在解析请求体之前检查；未配置令牌时拒绝所有请求
+async fn peer_guard(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let authorized = req
+        .app_data::<web::Data<Arc<Cluster>>>()
+        .and_then(|cluster| cluster.config.cluster_peer_token.as_deref())
+        .is_some_and(|token| auth::bearer_matches(req.headers(), token));
+    if !authorized {
+        warn!("[CLUSTER] Unauthorized peer request: {} {}", req.method(), req.path());
+        return Ok(req.into_response(HttpResponse::Unauthorized().finish()));
+    }
+    Ok(next.call(req).await?.map_into_boxed_body())
+}
+
+/// 合并对方的状态并返回本实例的状态
+async fn gossip(cluster: web::Data<Arc<Cluster>>, message: web::Json<GossipMessage>) -> HttpResponse {
+    if message.members.len() > MAX_MEMBERS {
+        warn!("[CLUSTER] Rejected gossip with {} members", message.members.len());
+        return HttpResponse::PayloadTooLarge().finish();
+    }
+    let reply = cluster.gossip_message();
+    cluster.merge(message.into_inner().members);
+    HttpResponse::Ok().json(reply)
+}
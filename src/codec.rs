@@ -0,0 +1,138 @@
+//! 请求体与响应的内容协商
+//!
+//! 除 JSON 外支持 MessagePack，供高频心跳的嵌入式客户端省去 JSON 解析开销：
+//! 请求头 `Content-Type: application/msgpack` 时按 MessagePack 解析请求体，`Accept` 包含 `application/msgpack` 时
+//! 响应以 MessagePack 编码，字段名和结构与 JSON 相同。处理器使用 [`Body`]（或带校验的 [`crate::validation::ValidJson`]）
+//! 提取请求体，响应统一经 [`respond`] 输出。
+//!
+//! 锁列表和导出接口在 `Accept` 包含 `application/x-ndjson` 时以 NDJSON（每行一条记录）分块流式输出，见 [`ndjson`]，
+//! 不在内存中拼接完整的 JSON 数组；这些接口同时按 `Accept-Encoding` 以 zstd、gzip 或 br 压缩响应。
+
+use crate::latency::{self, Stage};
+use actix_web::dev::Payload;
+use actix_web::error::ErrorBadRequest;
+use actix_web::http::{header, StatusCode};
+use actix_web::{web, FromRequest, HttpRequest, HttpResponse};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::future::Future;
+use std::ops::Deref;
+use std::pin::Pin;
+
+/// MessagePack 的媒体类型
+pub const MSGPACK: &str = "application/msgpack";
+/// NDJSON 的媒体类型
+pub const NDJSON: &str = "application/x-ndjson";
+
+/// NDJSON 响应每个分块包含的记录数
+const NDJSON_CHUNK: usize = 1000;
+
+/// 部分客户端使用的非标准媒体类型，按 MessagePack 处理
+const X_MSGPACK: &str = "application/x-msgpack";
+
+/// 请求是否通过 Accept 头要求 MessagePack 响应
+pub fn accepts_msgpack(req: &HttpRequest) -> bool {
+    req.headers()
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|accept| accept.contains(MSGPACK) || accept.contains(X_MSGPACK))
+}
+
+/// 请求是否通过 Accept 头要求 NDJSON 响应
+pub fn accepts_ndjson(req: &HttpRequest) -> bool {
+    req.headers()
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|accept| accept.contains(NDJSON))
+}
+
+/// 请求体是否为 MessagePack
+fn is_msgpack(req: &HttpRequest) -> bool {
+    content_type_is(req.headers(), &[MSGPACK, X_MSGPACK])
+}
+
+/// 请求体是否为 NDJSON
+pub fn is_ndjson(headers: &header::HeaderMap) -> bool {
+    content_type_is(headers, &[NDJSON])
+}
+
+fn content_type_is(headers: &header::HeaderMap, types: &[&str]) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|mime| types.iter().any(|t| mime.trim().eq_ignore_ascii_case(t)))
+}
+
+/// 以 NDJSON 分块流式输出，每块 [`NDJSON_CHUNK`] 条记录，只在输出时逐块序列化
+pub fn ndjson<T, I>(records: I) -> HttpResponse
+where
+    T: Serialize,
+    I: Iterator<Item = T> + 'static,
+{
+    let chunks = futures_util::stream::unfold(records, |mut records| async move {
+        let mut chunk = Vec::new();
+        for record in records.by_ref().take(NDJSON_CHUNK) {
+            if let Err(e) = serde_json::to_writer(&mut chunk, &record) {
+                return Some((Err(actix_web::error::ErrorInternalServerError(e)), records));
+            }
+            chunk.push(b'\n');
+        }
+        (!chunk.is_empty()).then(|| (Ok(web::Bytes::from(chunk)), records))
+    });
+    HttpResponse::Ok().content_type(NDJSON).streaming(chunks)
+}
+
+/// 按 Accept 头以 JSON 或 MessagePack 编码响应
+pub fn respond<T: Serialize>(req: &HttpRequest, status: StatusCode, body: &T) -> HttpResponse {
+    latency::time_sync(Stage::Serialize, || {
+        if !accepts_msgpack(req) {
+            return HttpResponse::build(status).json(body);
+        }
+        match rmp_serde::to_vec_named(body) {
+            Ok(bytes) => HttpResponse::build(status).content_type(MSGPACK).body(bytes),
+            Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+        }
+    })
+}
+
+/// JSON 或 MessagePack 请求体，按 Content-Type 选择解析方式
+///
+/// MessagePack 请求体的大小限制由 [`web::PayloadConfig`] 决定（默认 256 KiB）。
+pub struct Body<T>(pub T);
+
+impl<T> Body<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for Body<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: DeserializeOwned + 'static> FromRequest for Body<T> {
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, actix_web::Error>>>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        if is_msgpack(req) {
+            let bytes = web::Bytes::from_request(req, payload);
+            Box::pin(latency::time(Stage::Deserialize, async move {
+                let bytes = bytes.await?;
+                rmp_serde::from_slice(&bytes)
+                    .map(Body)
+                    .map_err(|e| ErrorBadRequest(format!("MessagePack deserialize error: {}", e)))
+            }))
+        } else {
+            let json = web::Json::<T>::from_request(req, payload);
+            Box::pin(latency::time(Stage::Deserialize, async move {
+                Ok(Body(json.await?.into_inner()))
+            }))
+        }
+    }
+}
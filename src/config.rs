@@ -1,18 +1,177 @@
-use serde::Deserialize;
+use ipnet::IpNet;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::env;
+use std::fmt;
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Clone, Deserialize)]
 pub struct Config {
     pub storage_type: StorageType,
     pub redis_url: Option<String>,
     pub redis_username: Option<String>,
     pub redis_password: Option<String>,
     pub redis_db: Option<i64>,
+    pub redis_key_prefix: String, // Redis 键前缀，含环境/租户段
+    pub redis_tls_ca_cert: Option<String>,     // rediss:// 使用的根证书（PEM），不配置则使用系统证书
+    pub redis_tls_client_cert: Option<String>, // 双向 TLS 的客户端证书（PEM）
+    pub redis_tls_client_key: Option<String>,  // 双向 TLS 的客户端私钥（PEM）
+    pub redis_connect_timeout_ms: u64,  // 建立连接的超时（毫秒）
+    pub redis_response_timeout_ms: u64, // 等待命令响应的超时（毫秒），0 表示不限制
+    pub redis_reconnect_retries: usize, // 断线后重连的最大尝试次数
+    pub redis_reconnect_backoff_ms: u64, // 重连退避的基准时长（毫秒），第 n 次重连前最多等待 基准 * 2^n
+    pub redis_reconcile_interval: u64, // id 索引与锁数据对账的间隔（秒），0 表示不对账
+    pub redis_id_index: RedisIdIndex,  // lock_id 到锁键的索引布局
+    pub redis_shards: Vec<String>, // 分片模式下各 Redis 的地址，配置后忽略 REDIS_URL
+    pub redis_shard_vnodes: usize, // 每个分片在一致性哈希环上的虚拟节点数
     pub server_host: String,
     pub server_port: u16,
+    pub server_listen: Vec<String>,     // 监听的 TCP 地址（host:port），默认为 SERVER_HOST:SERVER_PORT，可为空
+    pub server_uds_path: Option<String>, // 监听的 Unix 域套接字路径
+    pub server_uds_mode: Option<u32>,    // Unix 域套接字文件权限（八进制，如 660）
+    pub server_keep_alive: u64,          // 空闲的 keep-alive 连接保持的时间（秒），0 表示不保持
+    pub server_max_connections: usize,   // 每个 worker 同时接受的最大连接数
+    pub server_client_request_timeout_ms: u64, // 连接建立后读完请求头的超时（毫秒），0 表示不限制
+    pub management_listen: Vec<String>, // 管理端口监听的 TCP 地址，配置后管理接口和指标不再由公共地址提供
+    pub management_uds_path: Option<String>, // 管理接口监听的 Unix 域套接字路径
+    pub management_token: Option<String>,    // 管理端口的认证令牌，默认与 ADMIN_TOKEN 相同
+    pub trusted_proxies: Vec<IpNet>, // 受信任的反向代理（IP 或 CIDR），来自这些地址的请求按 X-Forwarded-For 识别客户端 IP
+    pub public_ip_allowlist: Vec<IpNet>, // 允许访问公共接口的客户端地址（IP 或 CIDR），为空时不限制
+    pub public_ip_denylist: Vec<IpNet>,  // 禁止访问公共接口的客户端地址，优先于允许列表
+    pub admin_ip_allowlist: Vec<IpNet>,  // 允许访问管理接口和指标的客户端地址，为空时不限制
+    pub admin_ip_denylist: Vec<IpNet>,   // 禁止访问管理接口和指标的客户端地址，优先于允许列表
     pub memory_persist_enabled: bool,
     pub memory_persist_path: String,
     pub memory_persist_interval: u64, // 秒
+    pub memory_persist_format: PersistFormat,
+    pub memory_persist_change_threshold: u64, // 变更次数达到该值时立即持久化，0 表示不启用
+    pub memory_persist_sharded: bool, // 按命名空间分片持久化，MEMORY_PERSIST_PATH 为目录（或对象存储前缀）
+    pub memory_persist_keep: usize, // 保留的旧快照份数，最新的快照损坏时依次回退
+    pub memory_persist_incremental: bool, // 两次全量快照之间只写入有变更的锁（增量快照），分片持久化时不使用
+    pub memory_persist_compact_interval: u64, // 增量持久化时写入全量快照的间隔（秒）
+    pub memory_persist_key: Option<String>,         // 持久化文件的加密密钥（base64 编码的 32 字节）
+    pub memory_persist_key_file: Option<String>,    // 从文件读取加密密钥
+    pub memory_persist_key_command: Option<String>, // 执行命令读取加密密钥（如调用 KMS 解密），输出 base64 编码的密钥
+    pub handoff_from: Option<String>, // 启动时接收锁状态的旧实例地址（管理接口所在的 http://host:port），需要内存存储
+    pub handoff_timeout_ms: u64,      // 交接中每个请求的超时（毫秒）
+    pub storage_field_keys: Option<String>,      // 锁信息敏感字段的加密密钥（key_id:base64，逗号分隔，第一个用于加密）
+    pub storage_field_keys_file: Option<String>, // 从文件读取敏感字段的加密密钥，格式同上
+    pub storage_field_rewrap_interval: u64,      // 将明文和旧密钥加密的锁重新以主密钥加密的间隔（秒），0 表示不改写
+    pub memory_persist_s3_endpoint: Option<String>, // 持久化到 s3:// 时的 S3 兼容端点（如 MinIO），不配置则使用 AWS S3
+    pub memory_persist_s3_region: String,
+    pub memory_persist_s3_access_key: Option<String>,
+    pub memory_persist_s3_secret_key: Option<String>,
+    pub memory_persist_s3_session_token: Option<String>,
+    pub memory_max_locks: usize,              // 内存存储的最大锁数量，0 表示不限制
+    pub memory_eviction: MemoryEviction,      // 达到最大锁数量时的处理方式
+    pub raft_node_id: u64,
+    pub raft_members: BTreeMap<u64, String>, // node_id -> host:port
+    pub raft_heartbeat_interval_ms: u64,
+    pub raft_election_timeout_ms: u64,
+    pub raft_snapshot_logs: u64, // 每累计多少条日志生成一次快照
+    pub raft_data_dir: String,   // 投票、日志和快照的保存目录
+    pub raft_peer_token: Option<String>, // 节点间 `/raft/*` 接口的认证令牌
+    pub nats_url: Option<String>,
+    pub nats_subject_prefix: String,
+    pub event_buffer_size: usize,    // 每个可靠投递目标的事件缓冲队列长度
+    pub event_max_retries: u32,      // 投递失败后的最大重试次数，耗尽后进入死信队列
+    pub event_retry_backoff_ms: u64, // 重试退避基准（毫秒），第 n 次重试前等待 基准 * 2^n，不超过 30 秒
+    pub event_dead_letter_size: usize, // 每个投递目标保留的死信数量
+    pub audit_stream_enabled: bool,    // 将锁事件追加到 Redis Stream `<REDIS_KEY_PREFIX>audit`，需要 Redis 存储
+    pub audit_stream_max_len: usize,   // 审计 Stream 保留的大致条数
+    pub admin_token: Option<String>, // 管理接口令牌，与 OIDC 登录都未配置时不提供管理接口
+    pub oidc_issuer: Option<String>, // 管理接口 OIDC 登录的身份提供方，配置后管理接口要求令牌或登录会话
+    pub oidc_client_id: String,
+    pub oidc_client_secret: Option<String>,
+    pub oidc_redirect_url: Option<String>, // 身份提供方回调的地址，指向 `/api/admin/auth/callback`
+    pub oidc_scopes: String,
+    pub oidc_groups_claim: String, // ID Token 中用户组所在的声明
+    pub oidc_role_mapping: Vec<(String, AdminRole)>, // 用户组 -> 管理角色
+    pub oidc_session_ttl: u64,     // 登录会话的有效期（秒）
+    pub lock_default_timeout: u64,   // 申请锁未传 timeout 时的默认超时（秒）
+    pub lock_max_timeout: u64,       // 允许的最大超时（秒）
+    pub lock_heartbeat_grace: u64,   // 心跳超时后锁仍然有效的宽限期（秒）
+    pub lock_heartbeat_grace_percent: u64, // 按超时时间的百分比计算的宽限期，与 lock_heartbeat_grace 取较大者
+    pub lock_heartbeat_interval_percent: u64, // 建议的心跳间隔占超时时间的百分比
+    pub lock_heartbeat_interval_jitter_percent: u64, // 建议的心跳间隔的随机浮动幅度（百分比）
+    pub lock_metadata_max_bytes: usize, // 锁附加信息的最大字节数
+    pub lock_waiter_ttl: u64,        // 等待者超过该时间（秒）未重试则移出等待队列
+    pub lock_queue_aging: u64,       // 等待者每等待该时间（秒）有效优先级加 1，0 表示不老化
+    pub lock_preemptors: Vec<String>, // 允许抢占锁的用户
+    pub lock_preempt_grace: u64,     // 抢占的默认宽限期（秒）
+    pub lock_unique_business_ids: Vec<String>, // 匹配这些模式（`*` 为通配符）的 business_id 同一时间只能在一个命名空间中被锁定
+    pub lock_takeover_timeout: u64,  // 接管请求等待持有人答复的时间（秒）
+    pub lock_takeover_webhook_url: Option<String>, // 接管事件的回调地址
+    pub lock_warning_callback_hosts: Vec<String>, // 允许作为过期提醒回调地址的主机（`*.` 开头匹配子域名），为空时不允许指定回调地址
+    pub lock_drain_timeout: u64,     // 排空的默认时长（秒），截止时仍未释放的锁由服务端释放
+    pub presence_timeout: u64,       // 在场者未再次登记即离开的默认时间（秒）
+    pub tenants: Vec<TenantKey>,     // 租户的 API Key 和配额，与 TENANT_JWT_SECRET 均未配置时不区分租户
+    pub tenant_jwt_secret: Option<String>, // 租户 JWT 的 HS256 密钥
+    pub tenant_max_locks: u64,       // 未单独配置配额的租户同时持有的最大锁数量，0 表示不限制
+    pub lock_max_per_user: u64,      // 每个用户同时持有的最大锁数量，0 表示不限制
+    pub lock_user_soft_quota: u64,   // 用户同时持有的锁达到该数量时发布提醒事件，不拒绝申请，0 表示不提醒
+    pub lock_user_quota_warn_percent: u64, // 用户持有的锁达到 lock_max_per_user 的该百分比时发布提醒事件，0 表示不提醒
+    pub lock_max_per_namespace: u64, // 未配置 max_locks 的命名空间同时持有的最大锁数量，0 表示不限制
+    pub lock_tombstone_ttl: u64,     // 锁释放或过期后保留墓碑的时间（秒），0 表示不保留
+    pub lock_recovery_window: u64,   // 启动后恢复的锁须心跳确认的时间（秒），0 表示不要求确认
+    pub lock_stuck_threshold: u64,   // 未配置 stuck_after 的命名空间的锁持有超过该时间（秒）视为卡住，0 表示不检测
+    pub lock_stuck_check_interval: u64, // 卡住的锁的检测间隔（秒）
+    pub lock_stuck_webhook_url: Option<String>, // 检测到卡住的锁时的回调地址
+    pub lock_checkout_check_interval: u64, // 借出逾期的检测间隔（秒）
+    pub lock_hold_cap_check_interval: u64, // 命名空间最长持有时间的提醒和统计间隔（秒）
+    pub lock_checkout_webhook_url: Option<String>, // 检测到逾期未归还的锁时的回调地址
+    pub lock_id_scheme: LockIdScheme, // lock_id 的生成方式
+    pub lock_id_node: Option<u16>,    // 雪花 ID 的节点号（0-1023），Raft 模式默认为 RAFT_NODE_ID
+    pub http_status_mode: HttpStatusMode,
+    pub storage_failover: FailoverMode,
+    pub storage_failure_threshold: u32, // 连续失败该次数后断路器打开
+    pub storage_retry_interval: u64,    // 断路器打开后探测存储是否恢复的间隔（秒）
+    pub storage_cache_ttl_ms: u64,      // 锁状态读缓存时长（毫秒），0 表示不缓存
+    pub storage_retries: u32,           // 暂时性错误的最大重试次数
+    pub storage_retry_backoff_ms: u64,  // 重试退避基准（毫秒），第 n 次重试前最多等待 基准 * 2^n
+    pub storage_op_timeout_ms: u64,     // 单个存储操作的超时（毫秒），0 表示不限制
+    pub replica_storage_type: Option<String>, // 副本存储：redis、memory 或已注册的存储名称，None 表示不复制
+    pub replica_redis_url: Option<String>,    // 副本 Redis 的地址
+    pub replica_redis_key_prefix: String,     // 副本 Redis 的键前缀
+    pub replica_persist_path: Option<String>, // 内存副本的持久化路径，支持 s3://
+    pub replication_mode: ReplicationMode,
+    pub replication_queue_size: usize,        // 异步复制的队列长度，队列满时丢弃
+    pub replication_reconcile_interval: u64,  // 主副本对账的间隔（秒），0 表示不对账
+    pub request_timeout_ms: u64,        // `/api` 下请求的整体处理超时（毫秒），0 表示不限制
+    pub max_inflight_requests: usize,   // `/api` 下同时处理的最大请求数，0 表示不限制
+    pub max_queued_requests: usize,     // 达到并发上限时最多排队等待的请求数
+    pub request_queue_timeout_ms: u64,  // 排队等待的最长时间（毫秒），超时后拒绝
+    pub overload_retry_after: u64,      // 拒绝请求时 Retry-After 响应头的秒数
+    pub server_timing_header: bool,     // `/api` 下的响应是否带 Server-Timing 耗时分解头
+    pub chaos_enabled: bool,            // 是否启用故障注入（仅用于客户端测试）
+    pub chaos_latency_rate: f64,        // 注入延迟的概率
+    pub chaos_latency_ms: u64,          // 注入的延迟（毫秒）
+    pub chaos_error_rate: f64,          // 返回 HTTP 500 的概率
+    pub chaos_expire_rate: f64,         // 心跳前使锁提前过期的概率
+    pub history_store: HistoryStoreType, // 锁历史的存储方式
+    pub history_size: usize,             // 每个锁保留的历史持有记录条数
+    pub history_postgres_url: Option<String>,
+    pub stats_contention_window: u64, // 锁竞争分析的滑动窗口（秒）
+    pub stats_sample_interval: u64,   // 时间序列的采样间隔（秒），0 表示不采样
+    pub stats_sample_retention: u64,  // 时间序列保留的时长（秒）
+    pub cleanup_interval_seconds: u64, // 过期锁的清理间隔（秒），Redis 存储自动过期，不使用
+    pub readyz_self_test: SelfTestMode,  // 就绪探针的存储读写自检
+    pub readyz_self_test_timeout_ms: u64, // 每个存储后端自检的超时（毫秒）
+    pub cluster_discovery: ClusterDiscovery, // 集群成员的发现方式
+    pub cluster_node_id: String,             // 本实例在集群中的标识，默认为主机名
+    pub cluster_advertise_addr: String,      // 其他实例访问本实例的地址（host:port）
+    pub cluster_peers: Vec<String>,          // static 发现方式的种子地址（host:port）
+    pub cluster_dns_srv: Option<String>,     // dns 发现方式查询的 SRV 记录名
+    pub cluster_k8s_service: Option<String>, // kubernetes 发现方式读取 Endpoints 的 Service 名称
+    pub cluster_k8s_namespace: Option<String>, // Service 所在的命名空间，默认为 Pod 所在的命名空间
+    pub cluster_k8s_port_name: Option<String>, // Endpoints 中使用的端口名，默认取第一个端口
+    pub cluster_discovery_interval: u64, // 重新发现种子地址的间隔（秒）
+    pub cluster_gossip_interval_ms: u64, // 每轮 gossip 的间隔（毫秒）
+    pub cluster_gossip_fanout: usize,    // 每轮 gossip 联系的实例数
+    pub cluster_suspect_timeout: u64, // 超过该时长（秒）未收到心跳的实例标记为 suspect
+    pub cluster_dead_timeout: u64,    // 超过该时长（秒）未收到心跳的实例标记为 dead，再经过同样时长后移除
+    pub cluster_peer_token: Option<String>, // 节点间 `/cluster/gossip` 接口的认证令牌
+    pub leader_election: bool, // 多实例之间选举一个实例运行清理、对账和检测等后台任务
+    pub leader_lease: u64,     // 选举锁的租期（秒），持有实例停止续期后经过该时长由其他实例接替
 }
 
 #[derive(Debug, Clone, Deserialize, PartialEq)]
@@ -20,6 +179,132 @@ pub struct Config {
 pub enum StorageType {
     Memory,
     Redis,
+    Raft,
+    Custom(String), // 通过 StorageRegistry 注册的存储
+}
+
+impl StorageType {
+    pub fn name(&self) -> &str {
+        match self {
+            StorageType::Memory => "memory",
+            StorageType::Redis => "redis",
+            StorageType::Raft => "raft",
+            StorageType::Custom(name) => name,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PersistFormat {
+    Json,
+    Bincode,
+}
+
+/// 内存存储达到最大锁数量时的处理方式
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum MemoryEviction {
+    Reject,       // 直接拒绝新的锁
+    EvictExpired, // 先清理已过期但尚未回收的锁，仍然已满时拒绝
+}
+
+/// lock_id 的生成方式
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum LockIdScheme {
+    UuidV4,    // 随机 UUID
+    UuidV7,    // 按时间有序的 UUID
+    Snowflake, // 毫秒时间戳 + 节点号 + 序号的 64 位整数
+}
+
+/// 就绪探针的存储读写自检
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SelfTestMode {
+    Off,      // 不自检
+    OnDemand, // 仅 `/readyz?deep=true` 时自检
+    Always,   // 每次探针都自检
+}
+
+/// Redis 中 lock_id 到锁键的索引布局
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RedisIdIndex {
+    Hash, // 所有锁共用一个哈希，与锁数据在同一脚本中原子写入，不过期
+    Keys, // 每把锁一个 id 键，随锁数据设置过期时间（旧布局）
+}
+
+/// 锁历史的存储方式
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryStoreType {
+    Off,      // 不记录历史
+    Memory,   // 本地内存环形缓冲区
+    Redis,    // Redis Stream，多个实例共享
+    Postgres, // PostgreSQL 表
+}
+
+/// Redis 故障时的处理方式
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum FailoverMode {
+    Off,      // 不使用断路器，每个请求都访问 Redis
+    FailFast, // 断路器打开后接口直接返回 503
+    Memory,   // 断路器打开后转到本地内存存储（降级模式）
+}
+
+/// 副本存储的写入方式
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReplicationMode {
+    Sync,  // 等待副本写入完成后再返回
+    Async, // 放入队列由后台任务写入，不增加请求延迟
+}
+
+/// 集群成员的发现方式
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ClusterDiscovery {
+    Off,        // 不加入集群
+    Static,     // CLUSTER_PEERS 中的固定地址
+    Dns,        // DNS SRV 记录
+    Kubernetes, // Kubernetes Service 的 Endpoints
+}
+
+/// 管理接口的角色，由 OIDC 用户组映射而来，权限依次递增
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum AdminRole {
+    Viewer,   // 只读
+    Operator, // 只读，以及强制释放、按标签释放和提前过期锁
+    Admin,    // 全部管理接口
+}
+
+/// 租户的 API Key，`TENANTS` 中的一项 `<tenant_id>:<api_key>[:<max_locks>]`
+#[derive(Clone, Deserialize)]
+pub struct TenantKey {
+    pub tenant_id: String,
+    pub api_key: String,
+    pub max_locks: Option<u64>, // 同时持有的最大锁数量，不配置时使用 TENANT_MAX_LOCKS
+}
+
+impl fmt::Debug for TenantKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TenantKey")
+            .field("tenant_id", &self.tenant_id)
+            .field("api_key", &"***")
+            .field("max_locks", &self.max_locks)
+            .finish()
+    }
+}
+
+/// 接口响应的 HTTP 状态码模式
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum HttpStatusMode {
+    Legacy, // 所有结果均返回 200，通过 code 区分
+    Strict, // 按结果返回 400/401/404/409/429/500 等状态码
 }
 
 impl Config {
@@ -28,9 +313,11 @@ impl Config {
             .unwrap_or_else(|_| "memory".to_string())
             .to_lowercase();
 
-        let storage_type = match storage_type.as_str() {
+        let storage_type = match storage_type.trim() {
+            "" | "memory" => StorageType::Memory,
             "redis" => StorageType::Redis,
-            _ => StorageType::Memory,
+            "raft" => StorageType::Raft,
+            name => StorageType::Custom(name.to_string()),
         };
 
         let redis_url = if storage_type == StorageType::Redis {
@@ -47,12 +334,93 @@ impl Config {
         let redis_db = env::var("REDIS_DB")
             .ok()
             .and_then(|s| s.parse::<i64>().ok());
+        // 多个部署共用一个 Redis 时，以前缀或环境/租户段隔离各自的键
+        let redis_key_prefix = {
+            let prefix = env::var("REDIS_KEY_PREFIX").unwrap_or_else(|_| "lock:".to_string());
+            match env::var("REDIS_KEY_TENANT").ok().filter(|tenant| !tenant.is_empty()) {
+                Some(tenant) => format!("{}{}:", prefix, tenant),
+                None => prefix,
+            }
+        };
+        let redis_tls_ca_cert = env::var("REDIS_TLS_CA_CERT").ok().filter(|path| !path.is_empty());
+        let redis_tls_client_cert = env::var("REDIS_TLS_CLIENT_CERT").ok().filter(|path| !path.is_empty());
+        let redis_tls_client_key = env::var("REDIS_TLS_CLIENT_KEY").ok().filter(|path| !path.is_empty());
+        let redis_connect_timeout_ms = env::var("REDIS_CONNECT_TIMEOUT_MS")
+            .unwrap_or_else(|_| "5000".to_string())
+            .parse()
+            .unwrap_or(5000);
+        let redis_response_timeout_ms = env::var("REDIS_RESPONSE_TIMEOUT_MS")
+            .unwrap_or_else(|_| "3000".to_string())
+            .parse()
+            .unwrap_or(3000);
+        let redis_reconnect_retries = env::var("REDIS_RECONNECT_RETRIES")
+            .unwrap_or_else(|_| "6".to_string())
+            .parse()
+            .unwrap_or(6);
+        let redis_reconnect_backoff_ms = env::var("REDIS_RECONNECT_BACKOFF_MS")
+            .unwrap_or_else(|_| "100".to_string())
+            .parse()
+            .unwrap_or(100);
+        let redis_reconcile_interval = env::var("REDIS_RECONCILE_INTERVAL")
+            .unwrap_or_else(|_| "300".to_string())
+            .parse()
+            .unwrap_or(300);
+        let redis_id_index = match env::var("REDIS_ID_INDEX")
+            .unwrap_or_else(|_| "hash".to_string())
+            .to_lowercase()
+            .as_str()
+        {
+            "keys" => RedisIdIndex::Keys,
+            _ => RedisIdIndex::Hash,
+        };
+        // 格式：redis://10.0.0.1:6379,redis://10.0.0.2:6379
+        let redis_shards = env::var("REDIS_SHARDS")
+            .unwrap_or_default()
+            .split(',')
+            .map(|url| url.trim().to_string())
+            .filter(|url| !url.is_empty())
+            .collect();
+        let redis_shard_vnodes = env::var("REDIS_SHARD_VNODES")
+            .unwrap_or_else(|_| "160".to_string())
+            .parse()
+            .unwrap_or(160);
 
         let server_host = env::var("SERVER_HOST").unwrap_or_else(|_| "127.0.0.1".to_string());
         let server_port = env::var("SERVER_PORT")
             .unwrap_or_else(|_| "8080".to_string())
             .parse()
             .unwrap_or(8080);
+        let server_listen = match env::var("SERVER_LISTEN") {
+            Ok(listen) => listen
+                .split(',')
+                .map(|addr| addr.trim().to_string())
+                .filter(|addr| !addr.is_empty())
+                .collect(),
+            Err(_) => vec![format!("{}:{}", server_host, server_port)],
+        };
+        let server_uds_path = env::var("SERVER_UDS_PATH").ok().filter(|path| !path.is_empty());
+        let server_uds_mode = env::var("SERVER_UDS_MODE")
+            .ok()
+            .and_then(|mode| u32::from_str_radix(mode.trim(), 8).ok());
+        let server_keep_alive = env::var("SERVER_KEEP_ALIVE")
+            .unwrap_or_else(|_| "5".to_string())
+            .parse()
+            .unwrap_or(5);
+        let server_max_connections = env::var("SERVER_MAX_CONNECTIONS")
+            .unwrap_or_else(|_| "25000".to_string())
+            .parse()
+            .unwrap_or(25000);
+        let server_client_request_timeout_ms = env::var("SERVER_CLIENT_REQUEST_TIMEOUT_MS")
+            .unwrap_or_else(|_| "5000".to_string())
+            .parse()
+            .unwrap_or(5000);
+
+        // 格式：10.0.0.0/8,127.0.0.1，单个地址视为 /32（IPv6 为 /128）
+        let trusted_proxies = ip_nets("TRUSTED_PROXIES");
+        let public_ip_allowlist = ip_nets("PUBLIC_IP_ALLOWLIST");
+        let public_ip_denylist = ip_nets("PUBLIC_IP_DENYLIST");
+        let admin_ip_allowlist = ip_nets("ADMIN_IP_ALLOWLIST");
+        let admin_ip_denylist = ip_nets("ADMIN_IP_DENYLIST");
 
         let memory_persist_enabled = env::var("MEMORY_PERSIST_ENABLED")
             .unwrap_or_else(|_| "true".to_string())
@@ -67,17 +435,1000 @@ impl Config {
             .parse()
             .unwrap_or(30);
 
+        let memory_persist_format = match env::var("MEMORY_PERSIST_FORMAT")
+            .unwrap_or_else(|_| "json".to_string())
+            .to_lowercase()
+            .as_str()
+        {
+            "bincode" => PersistFormat::Bincode,
+            _ => PersistFormat::Json,
+        };
+
+        let memory_persist_change_threshold = env::var("MEMORY_PERSIST_CHANGE_THRESHOLD")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse()
+            .unwrap_or(0);
+
+        let memory_persist_sharded = env::var("MEMORY_PERSIST_SHARDED")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()
+            .unwrap_or(false);
+
+        let memory_persist_keep = env::var("MEMORY_PERSIST_KEEP")
+            .unwrap_or_else(|_| "2".to_string())
+            .parse()
+            .unwrap_or(2);
+
+        let memory_persist_incremental = env::var("MEMORY_PERSIST_INCREMENTAL")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()
+            .unwrap_or(false);
+
+        let memory_persist_compact_interval = env::var("MEMORY_PERSIST_COMPACT_INTERVAL")
+            .unwrap_or_else(|_| "600".to_string())
+            .parse()
+            .unwrap_or(600);
+
+        let memory_persist_key = env::var("MEMORY_PERSIST_KEY").ok().filter(|key| !key.is_empty());
+        let memory_persist_key_file = env::var("MEMORY_PERSIST_KEY_FILE").ok().filter(|path| !path.is_empty());
+        let memory_persist_key_command = env::var("MEMORY_PERSIST_KEY_COMMAND")
+            .ok()
+            .filter(|command| !command.is_empty());
+        let handoff_from = env::var("HANDOFF_FROM")
+            .ok()
+            .map(|url| url.trim().trim_end_matches('/').to_string())
+            .filter(|url| !url.is_empty());
+        let handoff_timeout_ms = env::var("HANDOFF_TIMEOUT_MS")
+            .unwrap_or_else(|_| "30000".to_string())
+            .parse()
+            .unwrap_or(30000);
+        let storage_field_keys = env::var("STORAGE_FIELD_KEYS").ok().filter(|keys| !keys.is_empty());
+        let storage_field_keys_file = env::var("STORAGE_FIELD_KEYS_FILE").ok().filter(|path| !path.is_empty());
+        let storage_field_rewrap_interval = env::var("STORAGE_FIELD_REWRAP_INTERVAL")
+            .unwrap_or_else(|_| "300".to_string())
+            .parse()
+            .unwrap_or(300);
+
+        let memory_persist_s3_endpoint = env::var("MEMORY_PERSIST_S3_ENDPOINT")
+            .ok()
+            .filter(|endpoint| !endpoint.is_empty());
+        let memory_persist_s3_region = env::var("MEMORY_PERSIST_S3_REGION")
+            .or_else(|_| env::var("AWS_REGION"))
+            .unwrap_or_else(|_| "us-east-1".to_string());
+        let memory_persist_s3_access_key = env::var("AWS_ACCESS_KEY_ID").ok().filter(|key| !key.is_empty());
+        let memory_persist_s3_secret_key = env::var("AWS_SECRET_ACCESS_KEY").ok().filter(|key| !key.is_empty());
+        let memory_persist_s3_session_token = env::var("AWS_SESSION_TOKEN").ok().filter(|token| !token.is_empty());
+
+        let memory_max_locks = env::var("MEMORY_MAX_LOCKS")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse()
+            .unwrap_or(0);
+
+        let memory_eviction = match env::var("MEMORY_EVICTION_POLICY")
+            .unwrap_or_else(|_| "evict_expired".to_string())
+            .to_lowercase()
+            .as_str()
+        {
+            "reject" => MemoryEviction::Reject,
+            _ => MemoryEviction::EvictExpired,
+        };
+
+        let raft_node_id = env::var("RAFT_NODE_ID")
+            .unwrap_or_else(|_| "1".to_string())
+            .parse()
+            .unwrap_or(1);
+
+        // 格式：1=127.0.0.1:8080,2=127.0.0.1:8081,3=127.0.0.1:8082
+        let raft_members = env::var("RAFT_MEMBERS")
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|member| {
+                let (id, addr) = member.trim().split_once('=')?;
+                Some((id.trim().parse().ok()?, addr.trim().to_string()))
+            })
+            .collect();
+
+        let raft_heartbeat_interval_ms = env::var("RAFT_HEARTBEAT_INTERVAL_MS")
+            .unwrap_or_else(|_| "250".to_string())
+            .parse()
+            .unwrap_or(250);
+
+        let raft_election_timeout_ms = env::var("RAFT_ELECTION_TIMEOUT_MS")
+            .unwrap_or_else(|_| "1000".to_string())
+            .parse()
+            .unwrap_or(1000);
+
+        let raft_snapshot_logs = env::var("RAFT_SNAPSHOT_LOGS")
+            .unwrap_or_else(|_| "5000".to_string())
+            .parse()
+            .unwrap_or(5000);
+
+        let raft_data_dir =
+            env::var("RAFT_DATA_DIR").unwrap_or_else(|_| format!("./data/raft-{}", raft_node_id));
+        let raft_peer_token = env::var("RAFT_PEER_TOKEN").ok().filter(|token| !token.is_empty());
+
+        let nats_url = env::var("NATS_URL").ok();
+        let nats_subject_prefix =
+            env::var("NATS_SUBJECT_PREFIX").unwrap_or_else(|_| "locks".to_string());
+        let audit_stream_enabled = env::var("AUDIT_STREAM_ENABLED")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()
+            .unwrap_or(false);
+        let audit_stream_max_len = env::var("AUDIT_STREAM_MAX_LEN")
+            .unwrap_or_else(|_| "100000".to_string())
+            .parse::<usize>()
+            .unwrap_or(100000)
+            .max(1);
+        let event_buffer_size = env::var("EVENT_BUFFER_SIZE")
+            .unwrap_or_else(|_| "10000".to_string())
+            .parse()
+            .unwrap_or(10000);
+        let event_max_retries = env::var("EVENT_MAX_RETRIES")
+            .unwrap_or_else(|_| "5".to_string())
+            .parse()
+            .unwrap_or(5);
+        let event_retry_backoff_ms = env::var("EVENT_RETRY_BACKOFF_MS")
+            .unwrap_or_else(|_| "200".to_string())
+            .parse()
+            .unwrap_or(200);
+        let event_dead_letter_size = env::var("EVENT_DEAD_LETTER_SIZE")
+            .unwrap_or_else(|_| "1000".to_string())
+            .parse()
+            .unwrap_or(1000);
+
+        let admin_token = env::var("ADMIN_TOKEN").ok().filter(|token| !token.is_empty());
+        let management_token = env::var("MANAGEMENT_TOKEN")
+            .ok()
+            .filter(|token| !token.is_empty())
+            .or_else(|| admin_token.clone());
+        let management_listen = env::var("MANAGEMENT_LISTEN")
+            .unwrap_or_default()
+            .split(',')
+            .map(|addr| addr.trim().to_string())
+            .filter(|addr| !addr.is_empty())
+            .collect();
+        let management_uds_path = env::var("MANAGEMENT_UDS_PATH").ok().filter(|path| !path.is_empty());
+
+        let oidc_issuer = env::var("OIDC_ISSUER")
+            .ok()
+            .map(|issuer| issuer.trim_end_matches('/').to_string())
+            .filter(|issuer| !issuer.is_empty());
+        let oidc_client_id = env::var("OIDC_CLIENT_ID").unwrap_or_default();
+        let oidc_client_secret = env::var("OIDC_CLIENT_SECRET").ok().filter(|secret| !secret.is_empty());
+        let oidc_redirect_url = env::var("OIDC_REDIRECT_URL").ok().filter(|url| !url.is_empty());
+        let oidc_scopes = env::var("OIDC_SCOPES").unwrap_or_else(|_| "openid profile email".to_string());
+        let oidc_groups_claim = env::var("OIDC_GROUPS_CLAIM").unwrap_or_else(|_| "groups".to_string());
+        let oidc_role_mapping = env::var("OIDC_ROLE_MAPPING")
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|entry| {
+                let (group, role) = entry.trim().rsplit_once(':')?;
+                let role = match role.trim().to_lowercase().as_str() {
+                    "viewer" => AdminRole::Viewer,
+                    "operator" => AdminRole::Operator,
+                    "admin" => AdminRole::Admin,
+                    _ => return None,
+                };
+                Some((group.trim().to_string(), role)).filter(|(group, _)| !group.is_empty())
+            })
+            .collect();
+        let oidc_session_ttl = env::var("OIDC_SESSION_TTL")
+            .unwrap_or_else(|_| "28800".to_string())
+            .parse()
+            .unwrap_or(28800);
+
+        let server_timing_header = env::var("SERVER_TIMING_HEADER")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()
+            .unwrap_or(false);
+
+        let chaos_enabled = env::var("CHAOS_ENABLED")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()
+            .unwrap_or(false);
+        let chaos_rate = |name: &str| -> f64 {
+            env::var(name)
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(0.0_f64)
+                .clamp(0.0, 1.0)
+        };
+        let chaos_latency_rate = chaos_rate("CHAOS_LATENCY_RATE");
+        let chaos_error_rate = chaos_rate("CHAOS_ERROR_RATE");
+        let chaos_expire_rate = chaos_rate("CHAOS_EXPIRE_RATE");
+        let chaos_latency_ms = env::var("CHAOS_LATENCY_MS")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse()
+            .unwrap_or(0);
+
+        let lock_max_timeout = env::var("LOCK_MAX_TIMEOUT")
+            .unwrap_or_else(|_| "86400".to_string())
+            .parse()
+            .unwrap_or(86400);
+
+        let lock_default_timeout = env::var("LOCK_DEFAULT_TIMEOUT")
+            .unwrap_or_else(|_| "60".to_string())
+            .parse::<u64>()
+            .unwrap_or(60)
+            .min(lock_max_timeout);
+
+        let lock_heartbeat_grace = env::var("LOCK_HEARTBEAT_GRACE")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse()
+            .unwrap_or(0);
+
+        let lock_heartbeat_grace_percent = env::var("LOCK_HEARTBEAT_GRACE_PERCENT")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse::<u64>()
+            .unwrap_or(0)
+            .min(100);
+
+        let lock_heartbeat_interval_percent = env::var("LOCK_HEARTBEAT_INTERVAL_PERCENT")
+            .unwrap_or_else(|_| "33".to_string())
+            .parse::<u64>()
+            .unwrap_or(33)
+            .clamp(1, 100);
+
+        let lock_heartbeat_interval_jitter_percent = env::var("LOCK_HEARTBEAT_INTERVAL_JITTER_PERCENT")
+            .unwrap_or_else(|_| "10".to_string())
+            .parse::<u64>()
+            .unwrap_or(10)
+            .min(50);
+
+        let http_status_mode = match env::var("HTTP_STATUS_MODE")
+            .unwrap_or_else(|_| "legacy".to_string())
+            .to_lowercase()
+            .as_str()
+        {
+            "strict" => HttpStatusMode::Strict,
+            _ => HttpStatusMode::Legacy,
+        };
+
+        let storage_failover = match env::var("STORAGE_FAILOVER")
+            .unwrap_or_else(|_| "fail_fast".to_string())
+            .to_lowercase()
+            .as_str()
+        {
+            "off" => FailoverMode::Off,
+            "memory" => FailoverMode::Memory,
+            _ => FailoverMode::FailFast,
+        };
+        let storage_failure_threshold = env::var("STORAGE_FAILURE_THRESHOLD")
+            .unwrap_or_else(|_| "3".to_string())
+            .parse()
+            .unwrap_or(3);
+        let storage_retry_interval = env::var("STORAGE_RETRY_INTERVAL")
+            .unwrap_or_else(|_| "5".to_string())
+            .parse::<u64>()
+            .unwrap_or(5)
+            .max(1);
+        let storage_retries = env::var("STORAGE_RETRIES")
+            .unwrap_or_else(|_| "2".to_string())
+            .parse()
+            .unwrap_or(2);
+        let storage_retry_backoff_ms = env::var("STORAGE_RETRY_BACKOFF_MS")
+            .unwrap_or_else(|_| "50".to_string())
+            .parse()
+            .unwrap_or(50);
+        let storage_op_timeout_ms = env::var("STORAGE_OP_TIMEOUT_MS")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse()
+            .unwrap_or(0);
+        let replica_storage_type = env::var("REPLICA_STORAGE_TYPE")
+            .ok()
+            .map(|value| value.trim().to_lowercase())
+            .filter(|value| !value.is_empty() && value != "off");
+        let replica_redis_url = env::var("REPLICA_REDIS_URL").ok().filter(|value| !value.is_empty());
+        let replica_redis_key_prefix =
+            env::var("REPLICA_REDIS_KEY_PREFIX").unwrap_or_else(|_| "fe_lock_replica:".to_string());
+        let replica_persist_path = env::var("REPLICA_PERSIST_PATH").ok().filter(|value| !value.is_empty());
+        let replication_mode = match env::var("REPLICATION_MODE")
+            .unwrap_or_else(|_| "async".to_string())
+            .to_lowercase()
+            .as_str()
+        {
+            "sync" => ReplicationMode::Sync,
+            _ => ReplicationMode::Async,
+        };
+        let replication_queue_size = env::var("REPLICATION_QUEUE_SIZE")
+            .unwrap_or_else(|_| "10000".to_string())
+            .parse()
+            .unwrap_or(10000usize)
+            .max(1);
+        let replication_reconcile_interval = env::var("REPLICATION_RECONCILE_INTERVAL")
+            .unwrap_or_else(|_| "300".to_string())
+            .parse()
+            .unwrap_or(300);
+        let request_timeout_ms = env::var("REQUEST_TIMEOUT_MS")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse()
+            .unwrap_or(0);
+        let max_inflight_requests = env::var("MAX_INFLIGHT_REQUESTS")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse()
+            .unwrap_or(0);
+        let max_queued_requests = env::var("MAX_QUEUED_REQUESTS")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse()
+            .unwrap_or(0);
+        let request_queue_timeout_ms = env::var("REQUEST_QUEUE_TIMEOUT_MS")
+            .unwrap_or_else(|_| "1000".to_string())
+            .parse()
+            .unwrap_or(1000);
+        let overload_retry_after = env::var("OVERLOAD_RETRY_AFTER")
+            .unwrap_or_else(|_| "1".to_string())
+            .parse()
+            .unwrap_or(1);
+        let storage_cache_ttl_ms = env::var("STORAGE_CACHE_TTL_MS")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse()
+            .unwrap_or(0);
+
+        let lock_metadata_max_bytes = env::var("LOCK_METADATA_MAX_BYTES")
+            .unwrap_or_else(|_| "4096".to_string())
+            .parse()
+            .unwrap_or(4096);
+
+        let lock_waiter_ttl = env::var("LOCK_WAITER_TTL")
+            .unwrap_or_else(|_| "30".to_string())
+            .parse()
+            .unwrap_or(30);
+
+        let lock_queue_aging = env::var("LOCK_QUEUE_AGING")
+            .unwrap_or_else(|_| "10".to_string())
+            .parse()
+            .unwrap_or(10);
+
+        let lock_preemptors = env::var("LOCK_PREEMPTORS")
+            .map(|value| {
+                value
+                    .split(',')
+                    .map(|user_id| user_id.trim().to_string())
+                    .filter(|user_id| !user_id.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let lock_unique_business_ids = env::var("LOCK_UNIQUE_BUSINESS_IDS")
+            .map(|value| {
+                value
+                    .split(',')
+                    .map(|pattern| pattern.trim().to_string())
+                    .filter(|pattern| !pattern.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let lock_preempt_grace = env::var("LOCK_PREEMPT_GRACE")
+            .unwrap_or_else(|_| "30".to_string())
+            .parse()
+            .unwrap_or(30);
+
+        let lock_drain_timeout = env::var("LOCK_DRAIN_TIMEOUT")
+            .unwrap_or_else(|_| "30".to_string())
+            .parse()
+            .unwrap_or(30);
+
+        let lock_takeover_timeout = env::var("LOCK_TAKEOVER_TIMEOUT")
+            .unwrap_or_else(|_| "60".to_string())
+            .parse()
+            .unwrap_or(60);
+        let lock_takeover_webhook_url = env::var("LOCK_TAKEOVER_WEBHOOK_URL")
+            .ok()
+            .filter(|url| !url.is_empty());
+        let lock_warning_callback_hosts = env::var("LOCK_WARNING_CALLBACK_HOSTS")
+            .unwrap_or_default()
+            .split(',')
+            .map(|host| host.trim().to_lowercase())
+            .filter(|host| !host.is_empty())
+            .collect();
+
+        let presence_timeout = env::var("PRESENCE_TIMEOUT")
+            .unwrap_or_else(|_| "30".to_string())
+            .parse()
+            .unwrap_or(30);
+
+        let tenants = env::var("TENANTS")
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|entry| {
+                let mut parts = entry.trim().splitn(3, ':');
+                let tenant_id = parts.next()?.trim();
+                let api_key = parts.next()?.trim();
+                if !crate::validation::is_valid_namespace(tenant_id) || api_key.is_empty() {
+                    return None;
+                }
+                Some(TenantKey {
+                    tenant_id: tenant_id.to_string(),
+                    api_key: api_key.to_string(),
+                    max_locks: parts.next().and_then(|max_locks| max_locks.trim().parse().ok()),
+                })
+            })
+            .collect();
+        let tenant_jwt_secret = env::var("TENANT_JWT_SECRET").ok().filter(|secret| !secret.is_empty());
+        let tenant_max_locks = env::var("TENANT_MAX_LOCKS")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse()
+            .unwrap_or(0);
+
+        let lock_max_per_user = env::var("LOCK_MAX_PER_USER")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse()
+            .unwrap_or(0);
+        let lock_user_soft_quota = env::var("LOCK_USER_SOFT_QUOTA")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse()
+            .unwrap_or(0);
+        let lock_user_quota_warn_percent = env::var("LOCK_USER_QUOTA_WARN_PERCENT")
+            .unwrap_or_else(|_| "80".to_string())
+            .parse::<u64>()
+            .unwrap_or(80)
+            .min(100);
+        let lock_max_per_namespace = env::var("LOCK_MAX_PER_NAMESPACE")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse()
+            .unwrap_or(0);
+
+        let lock_tombstone_ttl = env::var("LOCK_TOMBSTONE_TTL")
+            .unwrap_or_else(|_| "300".to_string())
+            .parse()
+            .unwrap_or(300);
+
+        let lock_recovery_window = env::var("LOCK_RECOVERY_WINDOW")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse()
+            .unwrap_or(0);
+
+        let lock_stuck_threshold = env::var("LOCK_STUCK_THRESHOLD")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse()
+            .unwrap_or(0);
+        let lock_stuck_check_interval = env::var("LOCK_STUCK_CHECK_INTERVAL")
+            .unwrap_or_else(|_| "60".to_string())
+            .parse::<u64>()
+            .unwrap_or(60)
+            .max(1);
+        let lock_stuck_webhook_url = env::var("LOCK_STUCK_WEBHOOK_URL")
+            .ok()
+            .filter(|url| !url.is_empty());
+        let lock_checkout_check_interval = env::var("LOCK_CHECKOUT_CHECK_INTERVAL")
+            .unwrap_or_else(|_| "60".to_string())
+            .parse::<u64>()
+            .unwrap_or(60)
+            .max(1);
+        let lock_hold_cap_check_interval = env::var("LOCK_HOLD_CAP_CHECK_INTERVAL")
+            .unwrap_or_else(|_| "5".to_string())
+            .parse::<u64>()
+            .unwrap_or(5)
+            .max(1);
+        let lock_checkout_webhook_url = env::var("LOCK_CHECKOUT_WEBHOOK_URL")
+            .ok()
+            .filter(|url| !url.is_empty());
+
+        let lock_id_scheme = match env::var("LOCK_ID_SCHEME")
+            .unwrap_or_else(|_| "uuid_v4".to_string())
+            .to_lowercase()
+            .as_str()
+        {
+            "uuid_v7" => LockIdScheme::UuidV7,
+            "snowflake" => LockIdScheme::Snowflake,
+            _ => LockIdScheme::UuidV4,
+        };
+        let lock_id_node = env::var("LOCK_ID_NODE").ok().and_then(|node| node.parse().ok());
+
+        let history_store = match env::var("HISTORY_STORE")
+            .unwrap_or_else(|_| "memory".to_string())
+            .to_lowercase()
+            .as_str()
+        {
+            "off" => HistoryStoreType::Off,
+            "redis" => HistoryStoreType::Redis,
+            "postgres" => HistoryStoreType::Postgres,
+            _ => HistoryStoreType::Memory,
+        };
+        let history_size = env::var("HISTORY_SIZE")
+            .unwrap_or_else(|_| "20".to_string())
+            .parse()
+            .unwrap_or(20);
+        let history_postgres_url = env::var("HISTORY_POSTGRES_URL").ok().filter(|url| !url.is_empty());
+
+        let stats_contention_window = env::var("STATS_CONTENTION_WINDOW")
+            .unwrap_or_else(|_| "3600".to_string())
+            .parse()
+            .unwrap_or(3600);
+
+        let stats_sample_interval = env::var("STATS_SAMPLE_INTERVAL")
+            .unwrap_or_else(|_| "15".to_string())
+            .parse()
+            .unwrap_or(15);
+
+        let stats_sample_retention = env::var("STATS_SAMPLE_RETENTION")
+            .unwrap_or_else(|_| "86400".to_string())
+            .parse()
+            .unwrap_or(86400);
+
+        let cleanup_interval_seconds = env::var("CLEANUP_INTERVAL_SECONDS")
+            .unwrap_or_else(|_| "60".to_string())
+            .parse::<u64>()
+            .unwrap_or(60)
+            .max(1);
+
+        let readyz_self_test = match env::var("READYZ_SELF_TEST")
+            .unwrap_or_else(|_| "off".to_string())
+            .to_lowercase()
+            .as_str()
+        {
+            "on_demand" => SelfTestMode::OnDemand,
+            "always" => SelfTestMode::Always,
+            _ => SelfTestMode::Off,
+        };
+        let readyz_self_test_timeout_ms = env::var("READYZ_SELF_TEST_TIMEOUT_MS")
+            .unwrap_or_else(|_| "2000".to_string())
+            .parse()
+            .unwrap_or(2000);
+
+        let cluster_discovery = match env::var("CLUSTER_DISCOVERY")
+            .unwrap_or_else(|_| "off".to_string())
+            .to_lowercase()
+            .as_str()
+        {
+            "static" => ClusterDiscovery::Static,
+            "dns" => ClusterDiscovery::Dns,
+            "kubernetes" | "k8s" => ClusterDiscovery::Kubernetes,
+            _ => ClusterDiscovery::Off,
+        };
+        let cluster_node_id = env::var("CLUSTER_NODE_ID")
+            .or_else(|_| env::var("HOSTNAME"))
+            .ok()
+            .filter(|id| !id.is_empty())
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        // 监听所有地址时以主机名作为默认的访问地址
+        let cluster_advertise_addr = env::var("CLUSTER_ADVERTISE_ADDR")
+            .ok()
+            .filter(|addr| !addr.is_empty())
+            .unwrap_or_else(|| {
+                let host = match server_host.as_str() {
+                    "0.0.0.0" | "::" | "[::]" => env::var("HOSTNAME").unwrap_or_else(|_| "localhost".to_string()),
+                    host => host.to_string(),
+                };
+                format!("{}:{}", host, server_port)
+            });
+        let cluster_peers = env::var("CLUSTER_PEERS")
+            .unwrap_or_default()
+            .split(',')
+            .map(|addr| addr.trim().to_string())
+            .filter(|addr| !addr.is_empty())
+            .collect();
+        let cluster_dns_srv = env::var("CLUSTER_DNS_SRV").ok().filter(|name| !name.is_empty());
+        let cluster_k8s_service = env::var("CLUSTER_K8S_SERVICE").ok().filter(|name| !name.is_empty());
+        let cluster_k8s_namespace = env::var("CLUSTER_K8S_NAMESPACE").ok().filter(|name| !name.is_empty());
+        let cluster_k8s_port_name = env::var("CLUSTER_K8S_PORT_NAME").ok().filter(|name| !name.is_empty());
+        let cluster_discovery_interval = env::var("CLUSTER_DISCOVERY_INTERVAL")
+            .unwrap_or_else(|_| "30".to_string())
+            .parse()
+            .unwrap_or(30);
+        let cluster_gossip_interval_ms = env::var("CLUSTER_GOSSIP_INTERVAL_MS")
+            .unwrap_or_else(|_| "1000".to_string())
+            .parse()
+            .unwrap_or(1000);
+        let cluster_gossip_fanout = env::var("CLUSTER_GOSSIP_FANOUT")
+            .unwrap_or_else(|_| "3".to_string())
+            .parse()
+            .unwrap_or(3);
+        let cluster_suspect_timeout = env::var("CLUSTER_SUSPECT_TIMEOUT")
+            .unwrap_or_else(|_| "5".to_string())
+            .parse()
+            .unwrap_or(5);
+        let cluster_dead_timeout = env::var("CLUSTER_DEAD_TIMEOUT")
+            .unwrap_or_else(|_| "30".to_string())
+            .parse()
+            .unwrap_or(30);
+        let cluster_peer_token = env::var("CLUSTER_PEER_TOKEN").ok().filter(|token| !token.is_empty());
+        let leader_election = env::var("LEADER_ELECTION")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()
+            .unwrap_or(false);
+        let leader_lease = env::var("LEADER_LEASE")
+            .unwrap_or_else(|_| "15".to_string())
+            .parse::<u64>()
+            .unwrap_or(15)
+            .max(3);
+
         Self {
             storage_type,
             redis_url,
             redis_username,
             redis_password,
             redis_db,
+            redis_key_prefix,
+            redis_tls_ca_cert,
+            redis_tls_client_cert,
+            redis_tls_client_key,
+            redis_connect_timeout_ms,
+            redis_response_timeout_ms,
+            redis_reconnect_retries,
+            redis_reconnect_backoff_ms,
+            redis_reconcile_interval,
+            redis_id_index,
+            redis_shards,
+            redis_shard_vnodes,
             server_host,
             server_port,
+            server_listen,
+            server_uds_path,
+            server_uds_mode,
+            server_keep_alive,
+            server_max_connections,
+            server_client_request_timeout_ms,
+            management_listen,
+            management_uds_path,
+            management_token,
+            trusted_proxies,
+            public_ip_allowlist,
+            public_ip_denylist,
+            admin_ip_allowlist,
+            admin_ip_denylist,
             memory_persist_enabled,
             memory_persist_path,
             memory_persist_interval,
+            memory_persist_format,
+            memory_persist_change_threshold,
+            memory_persist_sharded,
+            memory_persist_keep,
+            memory_persist_incremental,
+            memory_persist_compact_interval,
+            memory_persist_key,
+            memory_persist_key_file,
+            memory_persist_key_command,
+            handoff_from,
+            handoff_timeout_ms,
+            storage_field_keys,
+            storage_field_keys_file,
+            storage_field_rewrap_interval,
+            memory_persist_s3_endpoint,
+            memory_persist_s3_region,
+            memory_persist_s3_access_key,
+            memory_persist_s3_secret_key,
+            memory_persist_s3_session_token,
+            memory_max_locks,
+            memory_eviction,
+            raft_node_id,
+            raft_members,
+            raft_heartbeat_interval_ms,
+            raft_election_timeout_ms,
+            raft_snapshot_logs,
+            raft_data_dir,
+            raft_peer_token,
+            nats_url,
+            nats_subject_prefix,
+            event_buffer_size,
+            event_max_retries,
+            event_retry_backoff_ms,
+            event_dead_letter_size,
+            audit_stream_enabled,
+            audit_stream_max_len,
+            admin_token,
+            oidc_issuer,
+            oidc_client_id,
+            oidc_client_secret,
+            oidc_redirect_url,
+            oidc_scopes,
+            oidc_groups_claim,
+            oidc_role_mapping,
+            oidc_session_ttl,
+            lock_default_timeout,
+            lock_max_timeout,
+            lock_heartbeat_grace,
+            lock_heartbeat_grace_percent,
+            lock_heartbeat_interval_percent,
+            lock_heartbeat_interval_jitter_percent,
+            lock_metadata_max_bytes,
+            lock_waiter_ttl,
+            lock_queue_aging,
+            lock_preemptors,
+            lock_preempt_grace,
+            lock_unique_business_ids,
+            lock_takeover_timeout,
+            lock_takeover_webhook_url,
+            lock_warning_callback_hosts,
+            lock_drain_timeout,
+            presence_timeout,
+            tenants,
+            tenant_jwt_secret,
+            tenant_max_locks,
+            lock_max_per_user,
+            lock_user_soft_quota,
+            lock_user_quota_warn_percent,
+            lock_max_per_namespace,
+            lock_tombstone_ttl,
+            lock_recovery_window,
+            lock_stuck_threshold,
+            lock_stuck_check_interval,
+            lock_stuck_webhook_url,
+            lock_checkout_check_interval,
+            lock_hold_cap_check_interval,
+            lock_checkout_webhook_url,
+            lock_id_scheme,
+            lock_id_node,
+            http_status_mode,
+            storage_failover,
+            storage_failure_threshold,
+            storage_retry_interval,
+            storage_cache_ttl_ms,
+            storage_retries,
+            storage_retry_backoff_ms,
+            storage_op_timeout_ms,
+            replica_storage_type,
+            replica_redis_url,
+            replica_redis_key_prefix,
+            replica_persist_path,
+            replication_mode,
+            replication_queue_size,
+            replication_reconcile_interval,
+            request_timeout_ms,
+            max_inflight_requests,
+            max_queued_requests,
+            request_queue_timeout_ms,
+            overload_retry_after,
+            server_timing_header,
+            chaos_enabled,
+            chaos_latency_rate,
+            chaos_latency_ms,
+            chaos_error_rate,
+            chaos_expire_rate,
+            history_store,
+            history_size,
+            history_postgres_url,
+            stats_contention_window,
+            stats_sample_interval,
+            stats_sample_retention,
+            cleanup_interval_seconds,
+            readyz_self_test,
+            readyz_self_test_timeout_ms,
+            cluster_discovery,
+            cluster_node_id,
+            cluster_advertise_addr,
+            cluster_peers,
+            cluster_dns_srv,
+            cluster_k8s_service,
+            cluster_k8s_namespace,
+            cluster_k8s_port_name,
+            cluster_discovery_interval,
+            cluster_gossip_interval_ms,
+            cluster_gossip_fanout,
+            cluster_suspect_timeout,
+            cluster_dead_timeout,
+            cluster_peer_token,
+            leader_election,
+            leader_lease,
+        }
+    }
+
+    /// 超时时间为 timeout（秒）的锁的宽限期，永久锁为 0
+    pub fn heartbeat_grace(&self, timeout: u64) -> u64 {
+        if timeout == 0 {
+            return 0;
+        }
+        self.lock_heartbeat_grace
+            .max(timeout.saturating_mul(self.lock_heartbeat_grace_percent) / 100)
+    }
+
+    /// 用户同时持有的锁达到该数量时发布提醒事件：软配额与 `LOCK_MAX_PER_USER` 的 `LOCK_USER_QUOTA_WARN_PERCENT`% 中较小者，
+    /// 均未配置时为 None
+    pub fn user_quota_warning(&self) -> Option<u64> {
+        let soft = (self.lock_user_soft_quota > 0).then_some(self.lock_user_soft_quota);
+        let percent = (self.lock_max_per_user > 0 && self.lock_user_quota_warn_percent > 0)
+            .then(|| (self.lock_max_per_user * self.lock_user_quota_warn_percent).div_ceil(100).max(1));
+        soft.into_iter().chain(percent).min()
+    }
+
+    /// 是否在独立的管理端口提供管理接口和指标
+    pub fn separate_management(&self) -> bool {
+        !self.management_listen.is_empty() || self.management_uds_path.is_some()
+    }
+}
+
+/// 调试输出中隐藏令牌、密码和密钥，已配置的值输出为 `***`，地址中的密码同样替换为 `***`
+impl fmt::Debug for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Config")
+            .field("storage_type", &self.storage_type)
+            .field("redis_url", &self.redis_url.as_deref().map(redact_url))
+            .field("redis_username", &self.redis_username)
+            .field("redis_password", &redact(&self.redis_password))
+            .field("redis_db", &self.redis_db)
+            .field("redis_key_prefix", &self.redis_key_prefix)
+            .field("redis_tls_ca_cert", &self.redis_tls_ca_cert)
+            .field("redis_tls_client_cert", &self.redis_tls_client_cert)
+            .field("redis_tls_client_key", &redact(&self.redis_tls_client_key))
+            .field("redis_connect_timeout_ms", &self.redis_connect_timeout_ms)
+            .field("redis_response_timeout_ms", &self.redis_response_timeout_ms)
+            .field("redis_reconnect_retries", &self.redis_reconnect_retries)
+            .field("redis_reconnect_backoff_ms", &self.redis_reconnect_backoff_ms)
+            .field("redis_reconcile_interval", &self.redis_reconcile_interval)
+            .field("redis_id_index", &self.redis_id_index)
+            .field("redis_shards", &self.redis_shards.iter().map(|url| redact_url(url)).collect::<Vec<_>>())
+            .field("redis_shard_vnodes", &self.redis_shard_vnodes)
+            .field("server_host", &self.server_host)
+            .field("server_port", &self.server_port)
+            .field("server_listen", &self.server_listen)
+            .field("server_uds_path", &self.server_uds_path)
+            .field("server_uds_mode", &self.server_uds_mode)
+            .field("server_keep_alive", &self.server_keep_alive)
+            .field("server_max_connections", &self.server_max_connections)
+            .field("server_client_request_timeout_ms", &self.server_client_request_timeout_ms)
+            .field("management_listen", &self.management_listen)
+            .field("management_uds_path", &self.management_uds_path)
+            .field("management_token", &redact(&self.management_token))
+            .field("trusted_proxies", &self.trusted_proxies)
+            .field("public_ip_allowlist", &self.public_ip_allowlist)
+            .field("public_ip_denylist", &self.public_ip_denylist)
+            .field("admin_ip_allowlist", &self.admin_ip_allowlist)
+            .field("admin_ip_denylist", &self.admin_ip_denylist)
+            .field("memory_persist_enabled", &self.memory_persist_enabled)
+            .field("memory_persist_path", &self.memory_persist_path)
+            .field("memory_persist_interval", &self.memory_persist_interval)
+            .field("memory_persist_format", &self.memory_persist_format)
+            .field("memory_persist_change_threshold", &self.memory_persist_change_threshold)
+            .field("memory_persist_sharded", &self.memory_persist_sharded)
+            .field("memory_persist_keep", &self.memory_persist_keep)
+            .field("memory_persist_incremental", &self.memory_persist_incremental)
+            .field("memory_persist_compact_interval", &self.memory_persist_compact_interval)
+            .field("memory_persist_key", &redact(&self.memory_persist_key))
+            .field("memory_persist_key_file", &self.memory_persist_key_file)
+            .field("memory_persist_key_command", &self.memory_persist_key_command)
+            .field("handoff_from", &self.handoff_from)
+            .field("handoff_timeout_ms", &self.handoff_timeout_ms)
+            .field("storage_field_keys", &redact(&self.storage_field_keys))
+            .field("storage_field_keys_file", &self.storage_field_keys_file)
+            .field("storage_field_rewrap_interval", &self.storage_field_rewrap_interval)
+            .field("memory_persist_s3_endpoint", &self.memory_persist_s3_endpoint)
+            .field("memory_persist_s3_region", &self.memory_persist_s3_region)
+            .field("memory_persist_s3_access_key", &redact(&self.memory_persist_s3_access_key))
+            .field("memory_persist_s3_secret_key", &redact(&self.memory_persist_s3_secret_key))
+            .field("memory_persist_s3_session_token", &redact(&self.memory_persist_s3_session_token))
+            .field("memory_max_locks", &self.memory_max_locks)
+            .field("memory_eviction", &self.memory_eviction)
+            .field("raft_node_id", &self.raft_node_id)
+            .field("raft_members", &self.raft_members)
+            .field("raft_heartbeat_interval_ms", &self.raft_heartbeat_interval_ms)
+            .field("raft_election_timeout_ms", &self.raft_election_timeout_ms)
+            .field("raft_snapshot_logs", &self.raft_snapshot_logs)
+            .field("raft_data_dir", &self.raft_data_dir)
+            .field("raft_peer_token", &redact(&self.raft_peer_token))
+            .field("nats_url", &self.nats_url.as_deref().map(redact_url))
+            .field("nats_subject_prefix", &self.nats_subject_prefix)
+            .field("event_buffer_size", &self.event_buffer_size)
+            .field("event_max_retries", &self.event_max_retries)
+            .field("event_retry_backoff_ms", &self.event_retry_backoff_ms)
+            .field("event_dead_letter_size", &self.event_dead_letter_size)
+            .field("audit_stream_enabled", &self.audit_stream_enabled)
+            .field("audit_stream_max_len", &self.audit_stream_max_len)
+            .field("admin_token", &redact(&self.admin_token))
+            .field("oidc_issuer", &self.oidc_issuer)
+            .field("oidc_client_id", &self.oidc_client_id)
+            .field("oidc_client_secret", &redact(&self.oidc_client_secret))
+            .field("oidc_redirect_url", &self.oidc_redirect_url)
+            .field("oidc_scopes", &self.oidc_scopes)
+            .field("oidc_groups_claim", &self.oidc_groups_claim)
+            .field("oidc_role_mapping", &self.oidc_role_mapping)
+            .field("oidc_session_ttl", &self.oidc_session_ttl)
+            .field("lock_default_timeout", &self.lock_default_timeout)
+            .field("lock_max_timeout", &self.lock_max_timeout)
+            .field("lock_heartbeat_grace", &self.lock_heartbeat_grace)
+            .field("lock_heartbeat_grace_percent", &self.lock_heartbeat_grace_percent)
+            .field("lock_heartbeat_interval_percent", &self.lock_heartbeat_interval_percent)
+            .field("lock_heartbeat_interval_jitter_percent", &self.lock_heartbeat_interval_jitter_percent)
+            .field("lock_metadata_max_bytes", &self.lock_metadata_max_bytes)
+            .field("lock_waiter_ttl", &self.lock_waiter_ttl)
+            .field("lock_queue_aging", &self.lock_queue_aging)
+            .field("lock_preemptors", &self.lock_preemptors)
+            .field("lock_preempt_grace", &self.lock_preempt_grace)
+            .field("lock_unique_business_ids", &self.lock_unique_business_ids)
+            .field("lock_takeover_timeout", &self.lock_takeover_timeout)
+            .field("lock_takeover_webhook_url", &self.lock_takeover_webhook_url.as_deref().map(redact_url))
+            .field("lock_warning_callback_hosts", &self.lock_warning_callback_hosts)
+            .field("lock_drain_timeout", &self.lock_drain_timeout)
+            .field("presence_timeout", &self.presence_timeout)
+            .field("tenants", &self.tenants)
+            .field("tenant_jwt_secret", &redact(&self.tenant_jwt_secret))
+            .field("tenant_max_locks", &self.tenant_max_locks)
+            .field("lock_max_per_user", &self.lock_max_per_user)
+            .field("lock_user_soft_quota", &self.lock_user_soft_quota)
+            .field("lock_user_quota_warn_percent", &self.lock_user_quota_warn_percent)
+            .field("lock_max_per_namespace", &self.lock_max_per_namespace)
+            .field("lock_tombstone_ttl", &self.lock_tombstone_ttl)
+            .field("lock_recovery_window", &self.lock_recovery_window)
+            .field("lock_stuck_threshold", &self.lock_stuck_threshold)
+            .field("lock_stuck_check_interval", &self.lock_stuck_check_interval)
+            .field("lock_stuck_webhook_url", &self.lock_stuck_webhook_url.as_deref().map(redact_url))
+            .field("lock_checkout_check_interval", &self.lock_checkout_check_interval)
+            .field("lock_hold_cap_check_interval", &self.lock_hold_cap_check_interval)
+            .field("lock_checkout_webhook_url", &self.lock_checkout_webhook_url.as_deref().map(redact_url))
+            .field("lock_id_scheme", &self.lock_id_scheme)
+            .field("lock_id_node", &self.lock_id_node)
+            .field("http_status_mode", &self.http_status_mode)
+            .field("storage_failover", &self.storage_failover)
+            .field("storage_failure_threshold", &self.storage_failure_threshold)
+            .field("storage_retry_interval", &self.storage_retry_interval)
+            .field("storage_cache_ttl_ms", &self.storage_cache_ttl_ms)
+            .field("storage_retries", &self.storage_retries)
+            .field("storage_retry_backoff_ms", &self.storage_retry_backoff_ms)
+            .field("storage_op_timeout_ms", &self.storage_op_timeout_ms)
+            .field("replica_storage_type", &self.replica_storage_type)
+            .field("replica_redis_url", &self.replica_redis_url.as_deref().map(redact_url))
+            .field("replica_redis_key_prefix", &self.replica_redis_key_prefix)
+            .field("replica_persist_path", &self.replica_persist_path)
+            .field("replication_mode", &self.replication_mode)
+            .field("replication_queue_size", &self.replication_queue_size)
+            .field("replication_reconcile_interval", &self.replication_reconcile_interval)
+            .field("request_timeout_ms", &self.request_timeout_ms)
+            .field("max_inflight_requests", &self.max_inflight_requests)
+            .field("max_queued_requests", &self.max_queued_requests)
+            .field("request_queue_timeout_ms", &self.request_queue_timeout_ms)
+            .field("overload_retry_after", &self.overload_retry_after)
+            .field("server_timing_header", &self.server_timing_header)
+            .field("chaos_enabled", &self.chaos_enabled)
+            .field("chaos_latency_rate", &self.chaos_latency_rate)
+            .field("chaos_latency_ms", &self.chaos_latency_ms)
+            .field("chaos_error_rate", &self.chaos_error_rate)
+            .field("chaos_expire_rate", &self.chaos_expire_rate)
+            .field("history_store", &self.history_store)
+            .field("history_size", &self.history_size)
+            .field("history_postgres_url", &self.history_postgres_url.as_deref().map(redact_url))
+            .field("stats_contention_window", &self.stats_contention_window)
+            .field("stats_sample_interval", &self.stats_sample_interval)
+            .field("stats_sample_retention", &self.stats_sample_retention)
+            .field("cleanup_interval_seconds", &self.cleanup_interval_seconds)
+            .field("readyz_self_test", &self.readyz_self_test)
+            .field("readyz_self_test_timeout_ms", &self.readyz_self_test_timeout_ms)
+            .field("cluster_discovery", &self.cluster_discovery)
+            .field("cluster_node_id", &self.cluster_node_id)
+            .field("cluster_advertise_addr", &self.cluster_advertise_addr)
+            .field("cluster_peers", &self.cluster_peers)
+            .field("cluster_dns_srv", &self.cluster_dns_srv)
+            .field("cluster_k8s_service", &self.cluster_k8s_service)
+            .field("cluster_k8s_namespace", &self.cluster_k8s_namespace)
+            .field("cluster_k8s_port_name", &self.cluster_k8s_port_name)
+            .field("cluster_discovery_interval", &self.cluster_discovery_interval)
+            .field("cluster_gossip_interval_ms", &self.cluster_gossip_interval_ms)
+            .field("cluster_gossip_fanout", &self.cluster_gossip_fanout)
+            .field("cluster_suspect_timeout", &self.cluster_suspect_timeout)
+            .field("cluster_dead_timeout", &self.cluster_dead_timeout)
+            .field("cluster_peer_token", &redact(&self.cluster_peer_token))
+            .field("leader_election", &self.leader_election)
+            .field("leader_lease", &self.leader_lease)
+            .finish()
+    }
+}
+
+fn redact(secret: &Option<String>) -> Option<&'static str> {
+    secret.as_ref().map(|_| "***")
+}
+
+/// 地址中的密码替换为 `***`，无法解析的地址整体隐藏
+fn redact_url(url: &str) -> String {
+    match reqwest::Url::parse(url) {
+        Ok(mut parsed) => {
+            if parsed.password().is_some() {
+                let _ = parsed.set_password(Some("***"));
+            }
+            parsed.to_string()
         }
+        Err(_) => "***".to_string(),
     }
 }
+
+/// 解析逗号分隔的 IP 或 CIDR 列表，单个地址视为 /32（IPv6 为 /128），无法解析的条目被忽略
+fn ip_nets(var: &str) -> Vec<IpNet> {
+    env::var(var)
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|net| {
+            let net = net.trim();
+            net.parse()
+                .ok()
+                .or_else(|| net.parse::<std::net::IpAddr>().ok().map(IpNet::from))
+        })
+        .collect()
+}
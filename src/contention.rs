@@ -0,0 +1,150 @@
+//! 锁竞争分析
+//!
+//! 按分钟分桶统计最近 `STATS_CONTENTION_WINDOW` 秒内每个锁的申请冲突次数、平均等待时长和平均持有时长，
+//! 通过 `GET /api/stats/contention` 查询。等待时长为用户首次申请失败到获取到锁的时间，
+//! 持有时长取自释放、过期、强制释放和抢占事件。统计仅包含当前实例处理的请求和事件。
+
+use crate::events::{EventSink, LockEvent, LockEventType};
+use crate::models::{ContentionEntry, ContentionStats};
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use dashmap::DashMap;
+use std::collections::BTreeMap;
+
+/// 分桶粒度（秒）
+const BUCKET_SECS: i64 = 60;
+
+#[derive(Default)]
+struct Bucket {
+    conflicts: u64,
+    waits: u64,
+    wait_secs: f64,
+    holds: u64,
+    hold_secs: f64,
+}
+
+pub struct ContentionTracker {
+    window_secs: u64,
+    buckets: DashMap<String, BTreeMap<i64, Bucket>>, // lock_key -> 分桶起始时间 -> 计数
+    waiting: DashMap<(String, String), DateTime<Utc>>, // (lock_key, user_id) -> 首次申请失败时间
+}
+
+impl ContentionTracker {
+    pub fn new(window_secs: u64) -> Self {
+        Self {
+            window_secs: window_secs.max(BUCKET_SECS as u64),
+            buckets: DashMap::new(),
+            waiting: DashMap::new(),
+        }
+    }
+
+    /// 记录一次因锁被占用而失败的申请
+    pub fn record_conflict(&self, lock_key: &str, user_id: &str) {
+        let now = Utc::now();
+        self.waiting
+            .entry((lock_key.to_string(), user_id.to_string()))
+            .or_insert(now);
+        self.record(lock_key, now, |bucket| bucket.conflicts += 1);
+    }
+
+    fn record(&self, lock_key: &str, at: DateTime<Utc>, update: impl FnOnce(&mut Bucket)) {
+        let start = at.timestamp().div_euclid(BUCKET_SECS) * BUCKET_SECS;
+        let mut buckets = self.buckets.entry(lock_key.to_string()).or_default();
+        update(buckets.entry(start).or_default());
+    }
+
+    fn window_start(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        now - Duration::seconds(self.window_secs as i64)
+    }
+
+    /// 清理窗口外的分桶和等待记录
+    pub fn prune(&self) {
+        let window_start = self.window_start(Utc::now());
+        let oldest_bucket = window_start.timestamp().div_euclid(BUCKET_SECS) * BUCKET_SECS;
+        self.buckets.retain(|_, buckets| {
+            *buckets = buckets.split_off(&oldest_bucket);
+            !buckets.is_empty()
+        });
+        self.waiting.retain(|_, since| *since >= window_start);
+    }
+
+    /// 窗口内申请冲突次数最多的 top_n 个锁，key_prefix 只统计锁键以其开头的锁
+    pub fn snapshot(&self, top_n: usize, key_prefix: Option<&str>) -> ContentionStats {
+        let now = Utc::now();
+        let oldest_bucket = self.window_start(now).timestamp().div_euclid(BUCKET_SECS) * BUCKET_SECS;
+        let mut top_contended: Vec<ContentionEntry> = self
+            .buckets
+            .iter()
+            .filter(|entry| key_prefix.is_none_or(|key_prefix| entry.key().starts_with(key_prefix)))
+            .filter_map(|entry| {
+                let mut total = Bucket::default();
+                for bucket in entry.value().range(oldest_bucket..).map(|(_, bucket)| bucket) {
+                    total.conflicts += bucket.conflicts;
+                    total.waits += bucket.waits;
+                    total.wait_secs += bucket.wait_secs;
+                    total.holds += bucket.holds;
+                    total.hold_secs += bucket.hold_secs;
+                }
+                (total.conflicts > 0).then(|| ContentionEntry {
+                    lock_key: entry.key().clone(),
+                    conflicts: total.conflicts,
+                    waits: total.waits,
+                    avg_wait_secs: average(total.wait_secs, total.waits),
+                    holds: total.holds,
+                    avg_hold_secs: average(total.hold_secs, total.holds),
+                })
+            })
+            .collect();
+        top_contended.sort_by(|a, b| {
+            b.conflicts
+                .cmp(&a.conflicts)
+                .then_with(|| a.lock_key.cmp(&b.lock_key))
+        });
+        top_contended.truncate(top_n);
+
+        ContentionStats {
+            window_secs: self.window_secs,
+            top_contended,
+        }
+    }
+}
+
+fn average(sum: f64, count: u64) -> Option<f64> {
+    (count > 0).then(|| sum / count as f64)
+}
+
+#[async_trait]
+impl EventSink for ContentionTracker {
+    fn name(&self) -> &str {
+        "contention"
+    }
+
+    async fn publish(&self, event: &LockEvent) -> Result<()> {
+        let lock_key = event.lock.get_lock_key();
+        match event.event {
+            LockEventType::Acquired => {
+                if let Some((_, since)) = self.waiting.remove(&(lock_key.clone(), event.lock.user_id.clone())) {
+                    let wait_secs = (event.timestamp - since).num_milliseconds().max(0) as f64 / 1000.0;
+                    self.record(&lock_key, event.timestamp, |bucket| {
+                        bucket.waits += 1;
+                        bucket.wait_secs += wait_secs;
+                    });
+                }
+            }
+            LockEventType::Released
+            | LockEventType::Expired
+            | LockEventType::ForceReleased
+            | LockEventType::Preempted
+            | LockEventType::TakeoverApproved => {
+                let hold_secs = event.lock.held_secs_at(event.timestamp);
+                self.record(&lock_key, event.timestamp, |bucket| {
+                    bucket.holds += 1;
+                    bucket.hold_secs += hold_secs;
+                });
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
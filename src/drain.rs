@@ -0,0 +1,115 @@
+//! 持有人停止前的锁排空 `POST /api/lock/release-session-graceful`
+//!
+//! 工作进程停止时一次释放全部锁，所有等待者会在同一时刻涌入。持有人可以先将自己（或某个会话）的锁标记为排空：
+//! 服务端为每把锁发布带有 `deadline` 的 `draining` 事件，等待者据此提前得知锁即将空闲；排空中的锁不再允许持有人重入申请，
+//! 持有人照常心跳，完成手头工作后逐个释放（即确认），截止时间到达仍未释放的锁由服务端释放并发布 `released` 事件。
+//! 排空状态保存在本实例进程内，多实例部署时重入检查只在接受排空请求的实例上生效，实例重启后未释放的锁按各自的超时时间过期。
+
+use crate::clock::Clock;
+use crate::events::{EventBus, LockEvent, LockEventType};
+use crate::models::LockInfo;
+use crate::storage::LockStorage;
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+
+#[derive(Clone)]
+struct Draining {
+    lock_id: String,
+    user_id: String,
+    deadline: DateTime<Utc>,
+}
+
+#[derive(Default)]
+pub struct DrainScheduler {
+    draining: DashMap<String, Draining>, // lock_key -> 排空中的锁
+}
+
+impl DrainScheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 将锁标记为排空，已在排空中时保留较早的截止时间，返回是否为新标记
+    pub fn start(&self, lock_info: &LockInfo, deadline: DateTime<Utc>) -> bool {
+        let mut started = false;
+        let mut entry = self
+            .draining
+            .entry(lock_info.get_lock_key())
+            .or_insert_with(|| {
+                started = true;
+                Draining {
+                    lock_id: lock_info.lock_id.clone(),
+                    user_id: lock_info.user_id.clone(),
+                    deadline,
+                }
+            });
+        if entry.lock_id != lock_info.lock_id {
+            *entry = Draining {
+                lock_id: lock_info.lock_id.clone(),
+                user_id: lock_info.user_id.clone(),
+                deadline,
+            };
+            started = true;
+        }
+        entry.deadline = entry.deadline.min(deadline);
+        started
+    }
+
+    /// user_id 持有的 lock_key 正在排空时返回截止时间
+    pub fn deadline(&self, lock_key: &str, user_id: &str) -> Option<DateTime<Utc>> {
+        self.draining
+            .get(lock_key)
+            .filter(|draining| draining.user_id == user_id)
+            .map(|draining| draining.deadline)
+    }
+
+    /// 锁已释放，不再排空
+    pub fn finish(&self, lock_info: &LockInfo) {
+        self.draining
+            .remove_if(&lock_info.get_lock_key(), |_, draining| draining.lock_id == lock_info.lock_id);
+    }
+
+    /// 移出已释放的锁，释放截止时间已到的锁
+    pub async fn release_due(&self, storage: &dyn LockStorage, events: &EventBus, clock: &dyn Clock) {
+        let draining: Vec<(String, Draining)> = self
+            .draining
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect();
+
+        for (lock_key, draining) in draining {
+            let now = clock.now();
+            match storage.get_lock_by_id(&draining.lock_id).await {
+                Ok(Some(lock_info)) if !lock_info.is_expired_at(now) => {
+                    if now < draining.deadline {
+                        continue;
+                    }
+                    match storage.release(&draining.lock_id).await {
+                        Ok(Some(released)) => {
+                            log::info!(
+                                "[DRAIN] Drain deadline passed, lock released - lock_id: {}, lock_key: {}, user_id: {}",
+                                released.lock_id,
+                                lock_key,
+                                released.user_id
+                            );
+                            events.publish(LockEvent::new(LockEventType::Released, released));
+                        }
+                        Ok(None) => {}
+                        Err(e) => {
+                            log::error!("[DRAIN] Failed to release draining lock {}: {}", draining.lock_id, e);
+                            continue;
+                        }
+                    }
+                }
+                // 持有人已释放或锁已过期
+                Ok(_) => {}
+                Err(e) => {
+                    log::error!("[DRAIN] Failed to load lock {}: {}", draining.lock_id, e);
+                    continue;
+                }
+            }
+            self.draining
+                .remove_if(&lock_key, |_, entry| entry.lock_id == draining.lock_id);
+        }
+    }
+}
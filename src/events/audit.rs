@@ -0,0 +1,84 @@
+use crate::events::{EventSink, LockEvent};
+use crate::models::AuditEntry;
+use crate::storage::fieldcrypt::FieldCipher;
+use crate::storage::redis::RedisStorage;
+use anyhow::Result;
+use async_trait::async_trait;
+use std::sync::Arc;
+
+/// 将锁事件追加到 Redis Stream `<prefix>audit`，多个实例写入同一个 Stream，
+/// 每条记录包含 `event`（事件类型）、`lock_key` 和 `payload`（JSON 事件），其他服务可用消费者组读取。
+/// 配置了锁信息字段加密时 payload 中的 user_name 和 metadata 为密文，`tail` 返回时解密
+pub struct RedisAuditSink {
+    storage: Arc<RedisStorage>,
+    max_len: usize,
+    cipher: Option<Arc<FieldCipher>>,
+}
+
+impl RedisAuditSink {
+    pub fn new(storage: Arc<RedisStorage>, max_len: usize) -> Self {
+        Self {
+            storage,
+            max_len,
+            cipher: None,
+        }
+    }
+
+    /// 写入前加密、读取后解密事件中的敏感字段
+    pub fn with_cipher(mut self, cipher: Option<Arc<FieldCipher>>) -> Self {
+        self.cipher = cipher;
+        self
+    }
+
+    /// 审计记录，从旧到新：指定 after 时为该 ID 之后的 count 条，否则为最新的 count 条
+    pub async fn tail(&self, after: Option<&str>, count: usize) -> Result<Vec<AuditEntry>> {
+        self.storage
+            .audit(after, count)
+            .await?
+            .into_iter()
+            .map(|(id, mut fields)| {
+                let payload = fields.remove("payload").unwrap_or_default();
+                let mut event: serde_json::Value = serde_json::from_str(&payload)?;
+                if let Some(cipher) = &self.cipher {
+                    // 加密密钥已移除的记录保留密文
+                    let mut opened = event.clone();
+                    match cipher.open_event(&mut opened) {
+                        Ok(()) => event = opened,
+                        Err(e) => log::warn!("Failed to decrypt audit entry {}: {}", id, e),
+                    }
+                }
+                Ok(AuditEntry { id, event })
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl EventSink for RedisAuditSink {
+    fn name(&self) -> &str {
+        "audit"
+    }
+
+    fn reliable(&self) -> bool {
+        true
+    }
+
+    async fn publish(&self, event: &LockEvent) -> Result<()> {
+        let payload = match &self.cipher {
+            Some(cipher) => serde_json::to_string(&cipher.seal_event(event)?)?,
+            None => serde_json::to_string(event)?,
+        };
+        let event_type = serde_json::to_value(event.event)?;
+        self.storage
+            .append_audit(
+                &[
+                    ("event", event_type.as_str().unwrap_or_default()),
+                    ("lock_key", &event.lock.get_lock_key()),
+                    ("payload", &payload),
+                ],
+                self.max_len,
+            )
+            .await?;
+        Ok(())
+    }
+}
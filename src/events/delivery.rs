@@ -0,0 +1,228 @@
+//! 事件的可靠投递
+//!
+//! [`EventSink::reliable`] 为 true 的投递目标（NATS、锁历史）注册到事件总线时包装为 [`ReliableSink`]：
+//! 事件先进入该目标的缓冲队列（`EVENT_BUFFER_SIZE`），由后台任务按发布顺序逐个投递，失败时按指数退避重试 `EVENT_MAX_RETRIES` 次，
+//! 重试耗尽或缓冲队列已满的事件进入死信队列（保留最近 `EVENT_DEAD_LETTER_SIZE` 条），可通过管理接口查看和重新投递，不会静默丢弃。
+//! 投递保证为至少一次：重试和重新投递可能使目标收到重复事件，消费方应按 lock_id、事件类型和时间戳去重。
+//! 缓冲和死信队列保存在进程内存中，进程退出时丢失。
+
+use super::{EventSink, LockEvent};
+use crate::config::Config;
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use utoipa::ToSchema;
+
+/// 重试退避的上限
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// 可靠投递的缓冲、重试和死信配置
+#[derive(Debug, Clone)]
+pub struct DeliveryOptions {
+    pub buffer_size: usize,
+    pub max_retries: u32,
+    pub retry_backoff: Duration, // 第 n 次重试前等待 基准 * 2^n，不超过 30 秒
+    pub dead_letter_size: usize,
+}
+
+impl Default for DeliveryOptions {
+    fn default() -> Self {
+        Self {
+            buffer_size: 10000,
+            max_retries: 5,
+            retry_backoff: Duration::from_millis(200),
+            dead_letter_size: 1000,
+        }
+    }
+}
+
+impl DeliveryOptions {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            buffer_size: config.event_buffer_size,
+            max_retries: config.event_max_retries,
+            retry_backoff: Duration::from_millis(config.event_retry_backoff_ms),
+            dead_letter_size: config.event_dead_letter_size,
+        }
+    }
+}
+
+/// 投递目标的投递状态
+#[derive(Debug, Serialize, ToSchema)]
+pub struct EventSinkStatus {
+    #[schema(example = "nats")]
+    pub sink: String,
+    /// 缓冲队列中等待投递的事件数
+    pub buffered: usize,
+    /// 投递成功的事件总数
+    pub delivered: u64,
+    /// 重试总次数
+    pub retries: u64,
+    /// 进入死信队列的事件总数
+    pub dead_lettered: u64,
+    /// 死信队列中保留的事件数
+    pub dead_letters: usize,
+    /// 正在投递的事件已等待的时间（毫秒），空闲时为 0
+    pub lag_ms: u64,
+    pub last_error: Option<String>,
+    pub last_error_at: Option<DateTime<Utc>>,
+}
+
+/// 投递失败的事件
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct DeadLetter {
+    #[schema(example = "nats")]
+    pub sink: String,
+    #[schema(value_type = Object)]
+    pub event: LockEvent,
+    /// 投递次数，缓冲队列已满时为 0
+    pub attempts: u32,
+    pub error: String,
+    pub failed_at: DateTime<Utc>,
+}
+
+/// 带缓冲队列、重试和死信队列的投递目标
+pub struct ReliableSink {
+    sink: Arc<dyn EventSink>,
+    options: DeliveryOptions,
+    queue: mpsc::Sender<LockEvent>,
+    receiver: Mutex<Option<mpsc::Receiver<LockEvent>>>, // 第一次发布时启动投递任务并取走
+    delivered: AtomicU64,
+    retries: AtomicU64,
+    dead_lettered: AtomicU64,
+    in_flight_since: Mutex<Option<DateTime<Utc>>>, // 正在投递的事件的发布时间
+    last_error: Mutex<Option<(String, DateTime<Utc>)>>,
+    dead_letters: Mutex<VecDeque<DeadLetter>>,
+}
+
+impl ReliableSink {
+    pub fn new(sink: Arc<dyn EventSink>, options: DeliveryOptions) -> Self {
+        let (queue, receiver) = mpsc::channel(options.buffer_size.max(1));
+        Self {
+            sink,
+            options,
+            queue,
+            receiver: Mutex::new(Some(receiver)),
+            delivered: AtomicU64::new(0),
+            retries: AtomicU64::new(0),
+            dead_lettered: AtomicU64::new(0),
+            in_flight_since: Mutex::new(None),
+            last_error: Mutex::new(None),
+            dead_letters: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        self.sink.name()
+    }
+
+    /// 放入缓冲队列，队列已满时直接进入死信队列
+    pub fn enqueue(self: &Arc<Self>, event: LockEvent) {
+        if let Some(receiver) = self.receiver.lock().take() {
+            tokio::spawn(self.clone().run(receiver));
+        }
+        if let Err(e) = self.queue.try_send(event) {
+            let event = match e {
+                mpsc::error::TrySendError::Full(event) | mpsc::error::TrySendError::Closed(event) => event,
+            };
+            self.dead_letter(event, 0, "Event buffer full".to_string());
+        }
+    }
+
+    async fn run(self: Arc<Self>, mut receiver: mpsc::Receiver<LockEvent>) {
+        while let Some(event) = receiver.recv().await {
+            *self.in_flight_since.lock() = Some(event.timestamp);
+            self.deliver(event).await;
+            *self.in_flight_since.lock() = None;
+        }
+    }
+
+    async fn deliver(&self, event: LockEvent) {
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let error = match self.sink.publish(&event).await {
+                Ok(()) => {
+                    self.delivered.fetch_add(1, Ordering::Relaxed);
+                    return;
+                }
+                Err(e) => e.to_string(),
+            };
+            *self.last_error.lock() = Some((error.clone(), Utc::now()));
+            if attempts > self.options.max_retries {
+                self.dead_letter(event, attempts, error);
+                return;
+            }
+            self.retries.fetch_add(1, Ordering::Relaxed);
+            let backoff = self
+                .options
+                .retry_backoff
+                .saturating_mul(1 << (attempts - 1).min(16))
+                .min(MAX_BACKOFF);
+            log::warn!(
+                "[EVENTS] Failed to publish {:?} event for lock {} to {} (attempt {}), retrying in {:?}: {}",
+                event.event, event.lock.lock_id, self.name(), attempts, backoff, error
+            );
+            tokio::time::sleep(backoff).await;
+        }
+    }
+
+    fn dead_letter(&self, event: LockEvent, attempts: u32, error: String) {
+        log::error!(
+            "[EVENTS] Dead-lettered {:?} event for lock {} to {} after {} attempts: {}",
+            event.event, event.lock.lock_id, self.name(), attempts, error
+        );
+        self.dead_lettered.fetch_add(1, Ordering::Relaxed);
+        let mut dead_letters = self.dead_letters.lock();
+        if self.options.dead_letter_size == 0 {
+            return;
+        }
+        if dead_letters.len() >= self.options.dead_letter_size {
+            dead_letters.pop_front();
+        }
+        dead_letters.push_back(DeadLetter {
+            sink: self.name().to_string(),
+            event,
+            attempts,
+            error,
+            failed_at: Utc::now(),
+        });
+    }
+
+    pub fn status(&self, now: DateTime<Utc>) -> EventSinkStatus {
+        let last_error = self.last_error.lock().clone();
+        EventSinkStatus {
+            sink: self.name().to_string(),
+            buffered: self.options.buffer_size.max(1) - self.queue.capacity(),
+            delivered: self.delivered.load(Ordering::Relaxed),
+            retries: self.retries.load(Ordering::Relaxed),
+            dead_lettered: self.dead_lettered.load(Ordering::Relaxed),
+            dead_letters: self.dead_letters.lock().len(),
+            lag_ms: self
+                .in_flight_since
+                .lock()
+                .map_or(0, |since| (now - since).num_milliseconds().max(0) as u64),
+            last_error_at: last_error.as_ref().map(|(_, at)| *at),
+            last_error: last_error.map(|(error, _)| error),
+        }
+    }
+
+    pub fn dead_letters(&self) -> Vec<DeadLetter> {
+        self.dead_letters.lock().iter().cloned().collect()
+    }
+
+    /// 将死信队列中的事件重新放入缓冲队列，返回重新投递的数量
+    pub fn replay(self: &Arc<Self>) -> usize {
+        let dead_letters: Vec<DeadLetter> = self.dead_letters.lock().drain(..).collect();
+        let count = dead_letters.len();
+        for dead_letter in dead_letters {
+            self.enqueue(dead_letter.event);
+        }
+        count
+    }
+}
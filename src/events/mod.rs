@@ -0,0 +1,187 @@
+pub mod audit;
+pub mod delivery;
+pub mod nats;
+pub mod routing;
+
+use crate::models::{LockInfo, TakeoverRequest, UserQuotaUsage};
+use delivery::{DeadLetter, DeliveryOptions, EventSinkStatus, ReliableSink};
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+/// 锁事件类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LockEventType {
+    Acquired,
+    Released,
+    Expired,
+    ForceReleased,
+    /// 锁即将过期且没有心跳，仅发送给登记了过期提醒的持有人
+    ExpiringSoon,
+    /// 预约到达开始时间并已授予
+    ReservationGranted,
+    /// 时间窗内锁始终被占用，预约已放弃
+    ReservationMissed,
+    /// 锁已被抢占，持有人应在 `lock.preemption.deadline` 前保存并释放
+    PreemptionPending,
+    /// 宽限期结束，锁已转移给抢占人（事件中为原持有人的锁）
+    Preempted,
+    /// 有人请求接管锁，持有人可在 `takeover.expires_at` 前同意或拒绝
+    TakeoverRequested,
+    /// 持有人同意接管，锁已转移给申请人（事件中为原持有人的锁）
+    TakeoverApproved,
+    /// 持有人拒绝接管
+    TakeoverDeclined,
+    /// 接管请求超时未答复，或持有人已释放锁
+    TakeoverExpired,
+    /// 超过预计归还时间仍未释放，每次借出只发送一次
+    Overdue,
+    /// 持有人正在停止，锁将在 `deadline` 前释放
+    Draining,
+    /// 锁即将达到命名空间的最长持有时间，心跳不能延长，持有人应在 `deadline` 前保存并释放
+    HoldLimitApproaching,
+    /// 用户持有的锁达到提醒数量，事件中为使其达到的锁，`quota` 为当前数量和配额
+    UserQuotaWarning,
+}
+
+/// 锁事件
+#[derive(Debug, Clone, Serialize)]
+pub struct LockEvent {
+    pub event: LockEventType,
+    pub timestamp: DateTime<Utc>,
+    pub lock: LockInfo,
+    /// 接管事件对应的接管请求
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub takeover: Option<TakeoverRequest>,
+    /// 排空事件中锁的释放截止时间
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deadline: Option<DateTime<Utc>>,
+    /// 配额提醒事件中用户持有的锁数量和配额
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quota: Option<UserQuotaUsage>,
+}
+
+impl LockEvent {
+    pub fn new(event: LockEventType, lock: LockInfo) -> Self {
+        Self {
+            event,
+            timestamp: Utc::now(),
+            lock,
+            takeover: None,
+            deadline: None,
+            quota: None,
+        }
+    }
+
+    pub fn with_takeover(mut self, takeover: TakeoverRequest) -> Self {
+        self.takeover = Some(takeover);
+        self
+    }
+
+    pub fn with_deadline(mut self, deadline: DateTime<Utc>) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    pub fn with_quota(mut self, quota: UserQuotaUsage) -> Self {
+        self.quota = Some(quota);
+        self
+    }
+}
+
+/// 事件投递目标
+#[async_trait]
+pub trait EventSink: Send + Sync {
+    /// 投递目标名称（用于日志）
+    fn name(&self) -> &str;
+
+    /// 投递事件
+    async fn publish(&self, event: &LockEvent) -> Result<()>;
+
+    /// 是否需要可靠投递：为 true 时按发布顺序投递，失败时重试，重试耗尽后进入死信队列，见 [`delivery`]。
+    /// 进程内的投递目标不会失败，默认为 false，每个事件单独投递
+    fn reliable(&self) -> bool {
+        false
+    }
+}
+
+/// 事件总线，将事件分发给所有已注册的投递目标
+#[derive(Default)]
+pub struct EventBus {
+    sinks: Vec<Arc<dyn EventSink>>,
+    reliable_sinks: Vec<Arc<ReliableSink>>,
+    delivery: DeliveryOptions,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 使用指定的缓冲、重试和死信配置投递可靠投递目标
+    pub fn with_delivery(delivery: DeliveryOptions) -> Self {
+        Self {
+            delivery,
+            ..Self::default()
+        }
+    }
+
+    pub fn register(&mut self, sink: Arc<dyn EventSink>) {
+        log::info!("[EVENTS] Registered event sink: {}", sink.name());
+        if sink.reliable() {
+            self.reliable_sinks
+                .push(Arc::new(ReliableSink::new(sink, self.delivery.clone())));
+        } else {
+            self.sinks.push(sink);
+        }
+    }
+
+    /// 可靠投递目标的投递状态
+    pub fn delivery_status(&self, now: DateTime<Utc>) -> Vec<EventSinkStatus> {
+        self.reliable_sinks.iter().map(|sink| sink.status(now)).collect()
+    }
+
+    /// 死信队列中的事件，sink 为 None 时返回所有投递目标的
+    pub fn dead_letters(&self, sink: Option<&str>) -> Vec<DeadLetter> {
+        self.reliable_sinks(sink).flat_map(|sink| sink.dead_letters()).collect()
+    }
+
+    /// 重新投递死信队列中的事件，返回重新投递的数量
+    pub fn replay_dead_letters(&self, sink: Option<&str>) -> usize {
+        self.reliable_sinks(sink).map(|sink| sink.replay()).sum()
+    }
+
+    fn reliable_sinks<'a>(&'a self, name: Option<&'a str>) -> impl Iterator<Item = &'a Arc<ReliableSink>> {
+        self.reliable_sinks
+            .iter()
+            .filter(move |sink| name.is_none_or(|name| sink.name() == name))
+    }
+
+    /// 异步分发事件，不阻塞调用方
+    pub fn publish(&self, event: LockEvent) {
+        for sink in &self.reliable_sinks {
+            sink.enqueue(event.clone());
+        }
+        if self.sinks.is_empty() {
+            return;
+        }
+
+        let event = Arc::new(event);
+        for sink in &self.sinks {
+            let sink = sink.clone();
+            let event = event.clone();
+            tokio::spawn(async move {
+                if let Err(e) = sink.publish(&event).await {
+                    log::error!(
+                        "[EVENTS] Failed to publish {:?} event for lock {} to {}: {}",
+                        event.event, event.lock.lock_id, sink.name(), e
+                    );
+                }
+            });
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use crate::events::{EventSink, LockEvent};
+use anyhow::Result;
+use async_trait::async_trait;
+use bytes::Bytes;
+
+/// 将锁事件发布到 NATS，主题格式为 `<prefix>.<namespace>.<business_id>`
+pub struct NatsSink {
+    client: async_nats::Client,
+    subject_prefix: String,
+}
+
+impl NatsSink {
+    pub async fn new(url: &str, subject_prefix: String) -> Result<Self> {
+        // NATS 暂不可用时不阻塞启动，连接成功前发布的消息由客户端缓冲
+        let client = async_nats::ConnectOptions::new()
+            .retry_on_initial_connect()
+            .connect(url)
+            .await?;
+        Ok(Self {
+            client,
+            subject_prefix,
+        })
+    }
+
+    fn subject(&self, event: &LockEvent) -> String {
+        format!(
+            "{}.{}.{}",
+            self.subject_prefix,
+            subject_token(&event.lock.namespace),
+            subject_token(&event.lock.business_id)
+        )
+    }
+}
+
+/// 将任意字符串转换为合法的 NATS 主题片段（不含 `.`、通配符和空白）
+fn subject_token(value: &str) -> String {
+    if value.is_empty() {
+        return "_".to_string();
+    }
+    value
+        .chars()
+        .map(|c| match c {
+            '.' | '*' | '>' => '_',
+            c if c.is_whitespace() => '_',
+            c => c,
+        })
+        .collect()
+}
+
+#[async_trait]
+impl EventSink for NatsSink {
+    fn name(&self) -> &str {
+        "nats"
+    }
+
+    fn reliable(&self) -> bool {
+        true
+    }
+
+    async fn publish(&self, event: &LockEvent) -> Result<()> {
+        let payload = serde_json::to_vec(event)?;
+        self.client
+            .publish(self.subject(event), Bytes::from(payload))
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,116 @@
+//! 按命名空间路由锁事件
+//!
+//! 命名空间策略的 `event_routes` 将该命名空间的锁事件额外投递到指定的回调地址或 NATS 主题，可按事件类型过滤。
+//! 规则随命名空间策略保存在存储中，通过管理接口修改后最多 [`ROUTE_CACHE_TTL`] 即在所有实例生效，无需重启。
+//! 路由投递是可靠投递目标（`routes`），经过缓冲、重试和死信队列，见 [`super::delivery`]；
+//! 同一事件有多个规则时，一个规则投递失败的重试也会重新投递其他规则，消费方需去重。
+
+use super::{EventSink, LockEvent};
+use crate::http;
+use crate::models::EventRoute;
+use crate::storage::LockStorage;
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use bytes::Bytes;
+use dashmap::DashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::OnceCell;
+
+/// 命名空间路由规则的缓存时长
+pub const ROUTE_CACHE_TTL: Duration = Duration::from_secs(5);
+
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct EventRouter {
+    storage: Arc<dyn LockStorage>,
+    nats_url: Option<String>,
+    nats: OnceCell<async_nats::Client>, // 第一次投递到 NATS 主题时连接
+    client: reqwest::Client,
+    routes: DashMap<String, (Instant, Arc<Vec<EventRoute>>)>, // namespace -> 缓存的规则
+}
+
+impl EventRouter {
+    pub fn new(storage: Arc<dyn LockStorage>, nats_url: Option<String>) -> Self {
+        Self {
+            storage,
+            nats_url,
+            nats: OnceCell::new(),
+            client: http::client(WEBHOOK_TIMEOUT),
+            routes: DashMap::new(),
+        }
+    }
+
+    async fn routes(&self, namespace: &str) -> Result<Arc<Vec<EventRoute>>> {
+        if let Some(entry) = self.routes.get(namespace) {
+            let (loaded_at, routes) = entry.value();
+            if loaded_at.elapsed() < ROUTE_CACHE_TTL {
+                return Ok(routes.clone());
+            }
+        }
+        let routes = Arc::new(
+            self.storage
+                .get_namespace(namespace)
+                .await?
+                .map(|policy| policy.event_routes)
+                .unwrap_or_default(),
+        );
+        self.routes
+            .insert(namespace.to_string(), (Instant::now(), routes.clone()));
+        Ok(routes)
+    }
+
+    async fn deliver(&self, route: &EventRoute, event: &LockEvent) -> Result<()> {
+        if let Some(webhook_url) = &route.webhook_url {
+            self.client
+                .post(webhook_url)
+                .json(event)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status())
+                .with_context(|| format!("webhook {}", webhook_url))?;
+        }
+        if let Some(subject) = &route.nats_subject {
+            let nats_url = self
+                .nats_url
+                .as_ref()
+                .ok_or_else(|| anyhow!("NATS subject {} requires NATS_URL", subject))?;
+            let client = self
+                .nats
+                .get_or_try_init(|| async_nats::connect(nats_url.as_str()))
+                .await
+                .with_context(|| format!("connect to NATS {}", nats_url))?;
+            client
+                .publish(subject.clone(), Bytes::from(serde_json::to_vec(event)?))
+                .await
+                .with_context(|| format!("NATS subject {}", subject))?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl EventSink for EventRouter {
+    fn name(&self) -> &str {
+        "routes"
+    }
+
+    fn reliable(&self) -> bool {
+        true
+    }
+
+    async fn publish(&self, event: &LockEvent) -> Result<()> {
+        let routes = self.routes(&event.lock.namespace).await?;
+        let mut errors = Vec::new();
+        for route in routes.iter().filter(|route| route.matches(event.event)) {
+            if let Err(e) = self.deliver(route, event).await {
+                errors.push(format!("{:#}", e));
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!(errors.join("; ")))
+        }
+    }
+}
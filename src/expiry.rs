@@ -0,0 +1,174 @@
+//! 锁即将过期提醒
+//!
+//! 申请锁时指定 `warn_before_seconds` 的持有人，在锁剩余有效时间不足该秒数且期间没有心跳时收到一次提醒：
+//! 通过事件总线发布 `expiring_soon` 事件，指定 `warning_callback_url` 时同时以 POST 回调该地址。
+//! 提醒后再次心跳会重新开始计时；设置了最长持有时间的锁在到期前同样会收到提醒。登记信息保存在本实例进程内，多实例部署时由处理申请的实例负责提醒。
+//!
+//! 回调由服务端发起，为避免客户端借此访问内网地址，回调地址的主机必须在 `LOCK_WARNING_CALLBACK_HOSTS` 中，
+//! 未配置时不允许指定回调地址；回调不跟随重定向。
+
+use crate::events::{EventBus, LockEvent, LockEventType};
+use crate::http;
+use crate::models::{AcquireLockRequest, LockInfo};
+use crate::storage::LockStorage;
+use crate::validation::MAX_DURATION_SECS;
+use chrono::{DateTime, Duration, Utc};
+use dashmap::DashMap;
+
+/// 回调请求超时
+const CALLBACK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+struct Watch {
+    warn_before: Duration,
+    callback_url: Option<String>,
+    expires_at: DateTime<Utc>, // 最近一次检查时锁的过期时间
+    warned: bool,
+}
+
+impl Watch {
+    /// 未提醒时到达提醒时间、已提醒时到达过期时间，才需要读取存储中的锁确认状态
+    fn is_due(&self, now: DateTime<Utc>) -> bool {
+        if self.warned {
+            now >= self.expires_at
+        } else {
+            self.expires_at
+                .checked_sub_signed(self.warn_before)
+                .is_none_or(|warn_at| now >= warn_at)
+        }
+    }
+}
+
+pub struct ExpiryWatcher {
+    watches: DashMap<String, Watch>, // lock_id -> 提醒登记
+    callback_hosts: Vec<String>,     // 允许的回调主机，`*.` 开头时匹配子域名
+    client: reqwest::Client,
+}
+
+impl ExpiryWatcher {
+    pub fn new(callback_hosts: Vec<String>) -> Self {
+        Self {
+            watches: DashMap::new(),
+            callback_hosts,
+            client: http::client(CALLBACK_TIMEOUT),
+        }
+    }
+
+    /// 回调地址的主机是否在允许的列表中，不允许时返回原因
+    pub fn check_callback(&self, callback_url: &str) -> Result<(), String> {
+        if self.callback_hosts.is_empty() {
+            return Err("warning_callback_url is disabled, set LOCK_WARNING_CALLBACK_HOSTS to allow it".to_string());
+        }
+        let host = reqwest::Url::parse(callback_url)
+            .ok()
+            .filter(|url| matches!(url.scheme(), "http" | "https"))
+            .and_then(|url| url.host_str().map(str::to_lowercase))
+            .ok_or_else(|| "warning_callback_url must be an http or https URL with a host".to_string())?;
+        let allowed = self.callback_hosts.iter().any(|allowed| match allowed.strip_prefix("*.") {
+            Some(domain) => host.strip_suffix(domain).is_some_and(|sub| sub.ends_with('.')),
+            None => *allowed == host,
+        });
+        if !allowed {
+            return Err(format!("warning_callback_url host {} is not in LOCK_WARNING_CALLBACK_HOSTS", host));
+        }
+        Ok(())
+    }
+
+    /// 申请成功后按请求登记提醒，未指定 `warn_before_seconds` 时不登记
+    pub fn watch(&self, lock_info: &LockInfo, request: &AcquireLockRequest) {
+        let (Some(warn_before), Some(expires_at)) =
+            (request.warn_before_seconds, lock_info.expires_at())
+        else {
+            return;
+        };
+        self.watches.insert(
+            lock_info.lock_id.clone(),
+            Watch {
+                warn_before: Duration::seconds(warn_before.min(MAX_DURATION_SECS) as i64),
+                callback_url: request
+                    .warning_callback_url
+                    .clone()
+                    .filter(|callback_url| self.check_callback(callback_url).is_ok()),
+                expires_at,
+                warned: false,
+            },
+        );
+    }
+
+    /// 检查截至 now 到期的登记，发送提醒并清理已释放或已过期的锁
+    pub async fn check(&self, storage: &dyn LockStorage, events: &EventBus, now: DateTime<Utc>) {
+        let due: Vec<String> = self
+            .watches
+            .iter()
+            .filter(|entry| entry.value().is_due(now))
+            .map(|entry| entry.key().clone())
+            .collect();
+
+        for lock_id in due {
+            let lock_info = match storage.get_lock_by_id(&lock_id).await {
+                Ok(Some(lock_info)) if !lock_info.is_expired_at(now) => lock_info,
+                Ok(_) => {
+                    self.watches.remove(&lock_id);
+                    continue;
+                }
+                Err(e) => {
+                    log::error!("[EXPIRY] Failed to load lock {}: {}", lock_id, e);
+                    continue;
+                }
+            };
+
+            let callback_url = {
+                let Some(mut watch) = self.watches.get_mut(&lock_id) else {
+                    continue;
+                };
+                // 心跳推迟了过期时间则重新计时，达到最长持有时间前的心跳不会推迟过期时间
+                if let Some(expires_at) = lock_info.expires_at().filter(|e| *e > watch.expires_at) {
+                    watch.expires_at = expires_at;
+                    watch.warned = false;
+                }
+                if watch.warned || !watch.is_due(now) {
+                    continue;
+                }
+                watch.warned = true;
+                watch.callback_url.clone()
+            };
+
+            log::info!(
+                "[EXPIRY] Lock expiring soon - lock_id: {}, remaining: {}s",
+                lock_id,
+                lock_info.remaining_secs_at(now).unwrap_or_default()
+            );
+            let event = LockEvent::new(LockEventType::ExpiringSoon, lock_info);
+            if let Some(callback_url) = callback_url {
+                self.notify(callback_url, event.clone());
+            }
+            events.publish(event);
+        }
+    }
+
+    /// 异步回调持有人登记的地址，失败只记录日志
+    fn notify(&self, callback_url: String, event: LockEvent) {
+        let client = self.client.clone();
+        tokio::spawn(async move {
+            let result = client
+                .post(&callback_url)
+                .json(&event)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status());
+            if let Err(e) = result {
+                log::error!(
+                    "[EXPIRY] Failed to deliver expiry warning for lock {} to {}: {}",
+                    event.lock.lock_id,
+                    callback_url,
+                    e
+                );
+            }
+        });
+    }
+}
+
+impl Default for ExpiryWatcher {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
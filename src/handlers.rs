@@ -1,32 +1,282 @@
+use crate::clock::Clock;
+use crate::codec;
+use crate::cluster::{ClusterMember, ClusterMembers, MemberInfo, MemberState, ShardStatus};
+use crate::config::{AdminRole, Config};
+use crate::contention::ContentionTracker;
+use crate::usage::UsageTracker;
+use crate::events::delivery::{DeadLetter, EventSinkStatus};
+use crate::events::{EventBus, LockEvent, LockEventType};
+use crate::admin;
 use crate::models::{
-    AcquireLockRequest, AcquireLockSuccess, ApiResponse, HeartbeatRequest,
-    LockInfo, ReleaseLockRequest,
+    AcquireCheck, AcquireConflict, AcquireHolder, AcquireLockFailure, AcquireOutcome, AcquireQueued, AcquireLockRequest, AdvisoryHolder, AcquireLockSuccess, ApiResponse, ChaosSettings, LockError, ForceReleaseRequest, HeartbeatRequest,
+    ClientContext, ContendedLock, ContentionEntry, ContentionStats, DeadlockDetected, HeartbeatSuccess, HeldLock, Histogram, HistogramBucket, HistoryEntry, ListLocksQuery, LockExpiry, LockInfo, LockStats, LockStatus, LockStatusQuery, StuckLock, OverdueCheckout, HoldCapStats, Tombstone,
+    LockConditionFailed, NamespaceFreeze, NamespacePolicy, NamespacePolicyRequest, EventRoute, PathConflict, BusinessIdConflict, LockNotice, LockNoticeType, PreemptionNotice, PreemptionPending, ProblemDetails, ReleaseAllRequest, ReleaseByTagRequest, ExpireLocksRequest, ReplayReport, AuditEntry, HandoffRequest, HandoffState, HandoffStatus, LockExport, ImportReport, LegacyImportError, LegacyImportReport, LegacyLockRecord, AdminSessionInfo,
+    RateLimitDecision, RateLimitRequest, ReleaseLockRequest, CheckoutRequest, CheckinSuccess, VerifyLockRequest, LockVerification, VerifyFailure, GracefulReleaseRequest, GracefulReleaseStarted, ReleaseReason, CancelReservationRequest, ListReservationsQuery, Reservation,
+    ReserveLockRequest, RequestTakeoverRequest, RespondTakeoverRequest, PendingTakeoversQuery, TakeoverRequest, TakeoverState, TakeoverStatusQuery, CreateSessionRequest, SequenceQuery, SequenceRange, ServerTime, ErrorCodeInfo, SessionInfo, SessionRequest, StatsQuery, UserStatsQuery, UserUsage, UserUsageSort, UserUsageStats, UserQuotaUsage, LeaveQueueQuery, QueuedWaiter, TimeSeries, TimeSeriesMetric, TimeSeriesPoint, TimeSeriesQuery, WaitReleaseQuery,
+    PresenceJoinRequest, PresenceJoinSuccess, PresenceLeaveRequest, PresenceList, PresenceListQuery, PresenceMember,
+    storage_error_code,
 };
+use crate::notify::ReleaseNotifier;
+use crate::peer;
+use crate::presence;
+use crate::history::LockHistory;
+use crate::lockid::LockIdGenerator;
+use crate::lockops::LockOps;
+use crate::queue::WaitQueue;
+use crate::reservation::ReservationScheduler;
+use crate::sessions::SessionRegistry;
+use crate::takeover::{TakeoverBroker, TakeoverError};
+use crate::timeseries::StatsSampler;
+use crate::tenant::{self, Tenant};
+use crate::storage::failover::StorageUnavailable;
 use crate::storage::LockStorage;
-use actix_web::{web, HttpResponse};
+use crate::v2::{self, LockHolder, LockStatusV2, LockView, ReleaseSuccess, V2Error, V2ErrorResponse, V2Success};
+use crate::validation::{FieldError, ValidJson, ValidQuery, ValidationErrors};
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
 use log::{error, info};
 use std::sync::Arc;
+use std::time::Duration;
 use utoipa::OpenApi;
 
 #[derive(OpenApi)]
 #[openapi(
     paths(
         acquire_lock,
+        acquire_lock_outcome,
+        can_acquire,
+        lock_status,
+        lock_history,
+        lock_queue,
+        leave_queue,
+        wait_release,
+        list_locks,
+        list_user_locks,
         heartbeat,
-        release_lock
+        release_lock,
+        checkout,
+        checkin,
+        verify_lock,
+        release_session_graceful,
+        release_all,
+        create_session,
+        session_heartbeat,
+        close_session,
+        next_sequence,
+        check_rate_limit,
+        reserve_lock,
+        cancel_reservation,
+        list_reservations,
+        request_takeover,
+        respond_takeover,
+        takeover_status,
+        pending_takeovers,
+        presence_join,
+        presence_leave,
+        presence_list,
+        stats,
+        contention_stats,
+        user_stats,
+        stats_timeseries,
+        server_time,
+        error_codes,
+        admin::list_locks,
+        admin::get_lock,
+        admin::force_release,
+        admin::release_by_tag,
+        admin::expire_locks,
+        admin::cleanup,
+        admin::stuck_locks,
+        admin::overdue_checkouts,
+        admin::hold_caps,
+        admin::list_namespaces,
+        admin::get_namespace,
+        admin::put_namespace,
+        admin::delete_namespace,
+        admin::freeze_namespace,
+        admin::unfreeze_namespace,
+        admin::export_state,
+        admin::import_state,
+        admin::import_legacy,
+        admin::get_chaos,
+        admin::put_chaos,
+        admin::cluster_members,
+        admin::event_delivery,
+        admin::dead_letters,
+        admin::replay_dead_letters,
+        admin::audit,
+        admin::handoff_status,
+        admin::handoff_fence,
+        admin::handoff_unfence,
+        admin::handoff_complete,
+        admin::auth_login,
+        admin::auth_callback,
+        admin::auth_session,
+        admin::auth_logout,
+        v2::acquire_lock,
+        v2::lock_status,
+        v2::heartbeat,
+        v2::release_lock
     ),
     components(
         schemas(
             AcquireLockRequest,
             AcquireLockSuccess,
+            AcquireLockFailure,
+            AcquireOutcome,
+            AcquireHolder,
+            AcquireConflict,
+            AcquireQueued,
+            AcquireCheck,
+            DeadlockDetected,
+            PathConflict,
+            BusinessIdConflict,
+            LockConditionFailed,
+            PreemptionPending,
+            PreemptionNotice,
+            LockNotice,
+            LockNoticeType,
+            AdvisoryHolder,
+            LockStatus,
+            LockExpiry,
+            Tombstone,
+            StuckLock,
+            OverdueCheckout,
+            HoldCapStats,
+            HistoryEntry,
+            ClientContext,
+            ReleaseReason,
             HeartbeatRequest,
+            HeartbeatSuccess,
             ReleaseLockRequest,
+            CheckoutRequest,
+            CheckinSuccess,
+            VerifyLockRequest,
+            LockVerification,
+            VerifyFailure,
+            GracefulReleaseRequest,
+            GracefulReleaseStarted,
+            ReleaseAllRequest,
+            CreateSessionRequest,
+            SessionRequest,
+            SessionInfo,
+            SequenceRange,
+            RateLimitRequest,
+            RateLimitDecision,
+            ReserveLockRequest,
+            CancelReservationRequest,
+            Reservation,
+            RequestTakeoverRequest,
+            RespondTakeoverRequest,
+            TakeoverRequest,
+            TakeoverState,
+            PresenceJoinRequest,
+            PresenceLeaveRequest,
+            PresenceMember,
+            PresenceList,
+            PresenceJoinSuccess,
+            ForceReleaseRequest,
+            ReleaseByTagRequest,
+            ExpireLocksRequest,
+            LockExport,
+            ImportReport,
+            LegacyLockRecord,
+            LegacyImportError,
+            LegacyImportReport,
+            AdminSessionInfo,
+            AdminRole,
+            ChaosSettings,
+            ClusterMembers,
+            ClusterMember,
+            MemberInfo,
+            MemberState,
+            ShardStatus,
+            EventSinkStatus,
+            DeadLetter,
+            ReplayReport,
+            AuditEntry,
+            HandoffRequest,
+            HandoffState,
+            HandoffStatus,
+            NamespacePolicy,
+            NamespacePolicyRequest,
+            NamespaceFreeze,
+            EventRoute,
+            LockEventType,
+            LockInfo,
+            LockStats,
+            ContendedLock,
+            ContentionStats,
+            ContentionEntry,
+            UserUsageStats,
+            UserUsage,
+            UserUsageSort,
+            UserQuotaUsage,
+            QueuedWaiter,
+            TimeSeries,
+            TimeSeriesMetric,
+            TimeSeriesPoint,
+            HeldLock,
+            Histogram,
+            HistogramBucket,
+            ServerTime,
+            ErrorCodeInfo,
             ApiResponse<AcquireLockSuccess>,
+            ApiResponse<HeartbeatSuccess>,
+            ApiResponse<AcquireOutcome>,
+            ApiResponse<AcquireCheck>,
             ApiResponse<serde_json::Value>,
+            ApiResponse<LockInfo>,
+            ApiResponse<LockStatus>,
+            ApiResponse<Vec<HistoryEntry>>,
+            ApiResponse<LockStats>,
+            ApiResponse<ContentionStats>,
+            ApiResponse<UserUsageStats>,
+            ApiResponse<TimeSeries>,
+            ApiResponse<Vec<QueuedWaiter>>,
+            ApiResponse<SequenceRange>,
+            ApiResponse<RateLimitDecision>,
+            ApiResponse<ServerTime>,
+            ApiResponse<Reservation>,
+            ApiResponse<Vec<Reservation>>,
+            ApiResponse<TakeoverRequest>,
+            ApiResponse<Vec<TakeoverRequest>>,
+            ApiResponse<PresenceJoinSuccess>,
+            ApiResponse<PresenceList>,
+            ApiResponse<Vec<LockInfo>>,
+            ApiResponse<Vec<StuckLock>>,
+            ApiResponse<NamespacePolicy>,
+            ApiResponse<ChaosSettings>,
+            ApiResponse<ClusterMembers>,
+            ApiResponse<Vec<EventSinkStatus>>,
+            ApiResponse<Vec<DeadLetter>>,
+            ApiResponse<ReplayReport>,
+            ApiResponse<Vec<AuditEntry>>,
+            ApiResponse<HandoffStatus>,
+            ApiResponse<LegacyImportReport>,
+            ApiResponse<Vec<NamespacePolicy>>,
+            ProblemDetails,
+            V2Success<AcquireLockSuccess>,
+            V2Success<HeartbeatSuccess>,
+            V2Success<LockStatusV2>,
+            V2Success<ReleaseSuccess>,
+            V2ErrorResponse,
+            V2Error,
+            LockHolder,
+            LockView,
+            LockStatusV2,
+            ReleaseSuccess,
+            FieldError,
+            ValidationErrors,
         )
     ),
     tags(
-        (name = "lock", description = "分布式锁接口")
+        (name = "lock", description = "分布式锁接口"),
+        (name = "lock-v2", description = "分布式锁接口 v2：HTTP 状态码、统一的错误对象和持有人信息"),
+        (name = "session", description = "客户端会话接口"),
+        (name = "presence", description = "在线状态接口"),
+        (name = "sequence", description = "分布式序列接口"),
+        (name = "ratelimit", description = "限流接口"),
+        (name = "admin", description = "管理接口")
     ),
     info(
         title = "分布式锁服务 API",
@@ -48,129 +298,940 @@ pub struct ApiDoc;
     )
 )]
 pub async fn acquire_lock(
-    storage: web::Data<Arc<dyn LockStorage>>,
-    req: web::Json<AcquireLockRequest>,
+    ops: web::Data<LockOps>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+    req: ValidJson<AcquireLockRequest>,
+) -> ApiResponse<AcquireLockSuccess> {
+    let client = peer::client_context(&http_req, req.hostname.clone(), &config.trusted_proxies);
+    ops.acquire(&req, client).await.into()
+}
+
+/// 申请锁，以 `outcome` 区分获取（granted）、重入（reentrant）、冲突（conflict）和排队（queued）；
+/// 参数与申请锁接口相同，行为也相同，冲突和排队以成功响应返回，其他失败仍返回错误码
+#[utoipa::path(
+    post,
+    path = "/api/lock/acquire-outcome",
+    tag = "lock",
+    request_body = AcquireLockRequest,
+    responses(
+        (status = 200, description = "申请结果", body = ApiResponse<AcquireOutcome>),
+        (status = 200, description = "申请失败，如超出配额、命名空间冻结或存储不可用", body = ApiResponse<AcquireOutcome>)
+    )
+)]
+pub async fn acquire_lock_outcome(
+    ops: web::Data<LockOps>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+    req: ValidJson<AcquireLockRequest>,
+) -> ApiResponse<AcquireOutcome> {
+    let client = peer::client_context(&http_req, req.hostname.clone(), &config.trusted_proxies);
+    ops.acquire_outcome(&req, client).await.into()
+}
+
+/// 申请锁预检：以与申请锁相同的参数检查申请是否会成功，不获取锁也不排队
+#[utoipa::path(
+    post,
+    path = "/api/lock/can-acquire",
+    tag = "lock",
+    request_body = AcquireLockRequest,
+    responses(
+        (status = 200, description = "预检结果", body = ApiResponse<AcquireCheck>)
+    )
+)]
+pub async fn can_acquire(ops: web::Data<LockOps>, req: ValidJson<AcquireLockRequest>) -> ApiResponse<AcquireCheck> {
+    ops.check_acquire(&req).await.into()
+}
+
+/// 查询锁状态
+#[utoipa::path(
+    get,
+    path = "/api/lock/status",
+    tag = "lock",
+    params(LockStatusQuery),
+    responses(
+        (status = 200, description = "锁状态，带 ETag", body = ApiResponse<LockStatus>),
+        (status = 304, description = "锁状态未变化（If-None-Match 与 ETag 相同）")
+    )
+)]
+pub async fn lock_status(
+    req: HttpRequest,
+    ops: web::Data<LockOps>,
+    query: ValidQuery<LockStatusQuery>,
 ) -> HttpResponse {
+    ApiResponse::from(ops.status(&query.namespace, &query.business_id).await).respond_with_etag(&req)
+}
+
+/// 查询锁的等待队列，按获取顺序排列；队列保存在本实例进程内，只包含经由本实例申请的等待者
+#[utoipa::path(
+    get,
+    path = "/api/lock/queue",
+    tag = "lock",
+    params(LockStatusQuery),
+    responses(
+        (status = 200, description = "等待者列表", body = ApiResponse<Vec<QueuedWaiter>>)
+    )
+)]
+pub async fn lock_queue(
+    queue: web::Data<Arc<WaitQueue>>,
+    clock: web::Data<Arc<dyn Clock>>,
+    query: ValidQuery<LockStatusQuery>,
+) -> ApiResponse<Vec<QueuedWaiter>> {
+    let lock_key = format!("{}:{}", query.namespace, query.business_id);
+    ApiResponse::success(queue.list(&lock_key, clock.now()))
+}
+
+/// 等待者放弃排队
+#[utoipa::path(
+    delete,
+    path = "/api/lock/queue",
+    tag = "lock",
+    params(LeaveQueueQuery),
+    responses(
+        (status = 200, description = "已移出等待队列，返回剩余的等待者", body = ApiResponse<Vec<QueuedWaiter>>),
+        (status = 200, description = "用户不在等待队列中（错误码 1018）", body = ApiResponse<Vec<QueuedWaiter>>)
+    )
+)]
+pub async fn leave_queue(
+    queue: web::Data<Arc<WaitQueue>>,
+    clock: web::Data<Arc<dyn Clock>>,
+    query: ValidQuery<LeaveQueueQuery>,
+) -> ApiResponse<Vec<QueuedWaiter>> {
+    let lock_key = format!("{}:{}", query.namespace, query.business_id);
+    if !queue.cancel(&lock_key, &query.user_id, clock.now()) {
+        return ApiResponse::error(LockError::NotInWaitQueue, format!("User {} is not waiting for this lock", query.user_id));
+    }
     info!(
-        "[ACQUIRE] Attempting to acquire lock - namespace: {}, business_id: {}, user_id: {}, user_name: {}, timeout: {}s",
-        req.namespace, req.business_id, req.user_id, req.user_name, req.timeout
+        "[QUEUE] Waiter left the queue - namespace: {}, business_id: {}, user_id: {}",
+        query.namespace, query.business_id, query.user_id
     );
+    ApiResponse::success(queue.list(&lock_key, clock.now()))
+}
+
+/// 查询锁最近的持有记录，按获取时间从新到旧排列
+#[utoipa::path(
+    get,
+    path = "/api/lock/history",
+    tag = "lock",
+    params(LockStatusQuery),
+    responses(
+        (status = 200, description = "持有记录", body = ApiResponse<Vec<HistoryEntry>>)
+    )
+)]
+pub async fn lock_history(
+    history: Option<web::Data<Arc<LockHistory>>>,
+    query: ValidQuery<LockStatusQuery>,
+) -> ApiResponse<Vec<HistoryEntry>> {
+    let Some(history) = history else {
+        return ApiResponse::error(LockError::AdminNotFound, "Lock history is disabled".to_string());
+    };
+    let lock_key = format!("{}:{}", query.namespace, query.business_id);
+    match history.history(&lock_key).await {
+        Ok(entries) => ApiResponse::success(entries),
+        Err(e) => {
+            error!("Failed to get lock history: {}", e);
+            ApiResponse::error(storage_error_code(LockError::HistoryFailed, &e), format!("Failed to get lock history: {}", e))
+        }
+    }
+}
+
+/// 等待锁被释放（长轮询），锁空闲时立即返回，超时仍被持有时返回当前持有人
+#[utoipa::path(
+    get,
+    path = "/api/lock/wait-release",
+    tag = "lock",
+    params(WaitReleaseQuery),
+    responses(
+        (status = 200, description = "锁状态，locked 为 false 表示锁已释放", body = ApiResponse<LockStatus>)
+    )
+)]
+pub async fn wait_release(
+    storage: web::Data<Arc<dyn LockStorage>>,
+    clock: web::Data<Arc<dyn Clock>>,
+    notifier: web::Data<Arc<ReleaseNotifier>>,
+    query: ValidQuery<WaitReleaseQuery>,
+) -> ApiResponse<LockStatus> {
+    let lock_key = format!("{}:{}", query.namespace, query.business_id);
+    match notifier
+        .wait(
+            storage.get_ref().as_ref(),
+            clock.get_ref().as_ref(),
+            &lock_key,
+            Duration::from_secs(query.timeout),
+        )
+        .await
+    {
+        Ok(lock) => ApiResponse::success(LockStatus {
+            locked: lock.is_some(),
+            expiry: lock.as_ref().map(LockExpiry::from),
+            lock,
+            tombstone: None,
+            holders: None,
+            unconfirmed: false,
+            confirm_before: None,
+        }),
+        Err(e) => {
+            error!("Failed to wait for lock release: {}", e);
+            ApiResponse::<LockStatus>::error(storage_error_code(LockError::StatusFailed, &e), format!("Failed to get lock status: {}", e))
+        }
+    }
+}
 
-    let lock_info = LockInfo::new(&req);
-    let lock_key = lock_info.get_lock_key();
-
-    match storage.try_acquire(lock_info.clone()).await {
-        Ok(acquired) => {
-            if acquired {
-                // 检查是否是重复申请（返回现有锁ID）
-                match storage.get_lock(&lock_key).await {
-                    Ok(Some(existing_lock)) => {
-                        info!(
-                            "[ACQUIRE SUCCESS] Lock acquired - lock_id: {}, namespace: {}, business_id: {}, user_id: {}, user_name: {}",
-                            existing_lock.lock_id, existing_lock.namespace, existing_lock.business_id, 
-                            existing_lock.user_id, existing_lock.user_name
-                        );
-                        HttpResponse::Ok().json(ApiResponse::success(AcquireLockSuccess {
-                            lock_id: existing_lock.lock_id,
-                        }))
-                    }
-                    _ => {
-                        info!(
-                            "[ACQUIRE SUCCESS] Lock acquired - lock_id: {}, namespace: {}, business_id: {}, user_id: {}, user_name: {}",
-                            lock_info.lock_id, lock_info.namespace, lock_info.business_id, 
-                            lock_info.user_id, lock_info.user_name
-                        );
-                        HttpResponse::Ok().json(ApiResponse::success(AcquireLockSuccess {
-                            lock_id: lock_info.lock_id,
-                        }))
-                    }
-                }
-            } else {
-                // 获取当前锁的持有人信息
-                match storage.get_lock(&lock_key).await {
-                    Ok(Some(existing_lock)) => {
-                        info!(
-                            "[ACQUIRE FAILED] Lock already held - namespace: {}, business_id: {}, current_holder: {} (user_id: {}), locked_at: {}, requested_by: {} (user_id: {})",
-                            existing_lock.namespace, existing_lock.business_id, existing_lock.user_name, 
-                            existing_lock.user_id, existing_lock.locked_at, req.user_name, req.user_id
-                        );
-                        HttpResponse::Ok().json(ApiResponse::<AcquireLockSuccess>::error(
-                            1001,
-                            format!(
-                                "Lock already held by {}",
-                                existing_lock.user_name
-                            ),
-                        ))
-                    }
-                    Ok(None) => {
-                        error!("Lock acquisition failed but no lock info found");
-                        HttpResponse::Ok().json(ApiResponse::<AcquireLockSuccess>::error(
-                            1002,
-                            "Lock acquisition failed".to_string(),
-                        ))
-                    }
-                    Err(e) => {
-                        error!("Failed to get lock info: {}", e);
-                        HttpResponse::Ok().json(ApiResponse::<AcquireLockSuccess>::error(
-                            1003,
-                            format!("Failed to get lock info: {}", e),
-                        ))
-                    }
-                }
+/// 查询锁列表，可按命名空间和标签过滤，`Accept: application/x-ndjson` 时以 NDJSON 流式输出，每行一个锁
+#[utoipa::path(
+    get,
+    path = "/api/lock/list",
+    tag = "lock",
+    params(ListLocksQuery),
+    responses(
+        (status = 200, description = "锁列表，带 ETag", body = ApiResponse<Vec<LockInfo>>),
+        (status = 200, description = "NDJSON 锁列表，每行一个锁", content_type = "application/x-ndjson", body = LockInfo),
+        (status = 304, description = "锁列表未变化（If-None-Match 与 ETag 相同）")
+    )
+)]
+pub async fn list_locks(
+    req: HttpRequest,
+    storage: web::Data<Arc<dyn LockStorage>>,
+    clock: web::Data<Arc<dyn Clock>>,
+    tenant: Option<web::ReqData<Tenant>>,
+    query: ValidQuery<ListLocksQuery>,
+) -> HttpResponse {
+    let locks = match &tenant {
+        Some(tenant) => storage.list_prefix(&tenant.key_prefix()).await,
+        None => storage.list_locks().await,
+    };
+    match locks {
+        Ok(mut locks) => {
+            let now = clock.now();
+            locks.retain(|lock_info| query.matches(lock_info) && !lock_info.is_expired_at(now));
+            locks.sort_by(|a, b| {
+                (&a.namespace, &a.business_id).cmp(&(&b.namespace, &b.business_id))
+            });
+            if codec::accepts_ndjson(&req) {
+                return codec::ndjson(locks.into_iter());
             }
+            ApiResponse::success(locks).respond_with_etag(&req)
         }
         Err(e) => {
-            error!("Failed to acquire lock: {}", e);
-            HttpResponse::Ok().json(ApiResponse::<AcquireLockSuccess>::error(
-                1004,
-                format!("Failed to acquire lock: {}", e),
-            ))
+            error!("Failed to list locks: {}", e);
+            ApiResponse::<Vec<LockInfo>>::error(
+                storage_error_code(LockError::ListFailed, &e),
+                format!("Failed to list locks: {}", e),
+            )
+            .respond_to(&req)
         }
     }
 }
 
-/// 心跳接口
+/// 查询用户持有的锁
+#[utoipa::path(
+    get,
+    path = "/api/lock/by-user/{user_id}",
+    tag = "lock",
+    params(("user_id" = String, Path, description = "持有人 user_id")),
+    responses(
+        (status = 200, description = "用户持有的锁", body = ApiResponse<Vec<LockInfo>>)
+    )
+)]
+pub async fn list_user_locks(
+    storage: web::Data<Arc<dyn LockStorage>>,
+    tenant: Option<web::ReqData<Tenant>>,
+    user_id: web::Path<String>,
+) -> ApiResponse<Vec<LockInfo>> {
+    match storage.list_by_user(&user_id).await {
+        Ok(mut locks) => {
+            if let Some(tenant) = &tenant {
+                locks.retain(|lock_info| tenant.owns_lock(lock_info));
+            }
+            locks.sort_by(|a, b| {
+                (&a.namespace, &a.business_id).cmp(&(&b.namespace, &b.business_id))
+            });
+            ApiResponse::success(locks)
+        }
+        Err(e) => {
+            error!("Failed to list locks of user {}: {}", user_id, e);
+            ApiResponse::<Vec<LockInfo>>::error(
+                storage_error_code(LockError::ListFailed, &e),
+                format!("Failed to list locks: {}", e),
+            )
+        }
+    }
+}
+
+/// 预约锁接口
 #[utoipa::path(
     post,
-    path = "/api/lock/heartbeat",
+    path = "/api/lock/reserve",
     tag = "lock",
-    request_body = HeartbeatRequest,
+    request_body = ReserveLockRequest,
     responses(
-        (status = 200, description = "心跳成功", body = ApiResponse<serde_json::Value>),
-        (status = 200, description = "锁不存在或已过期", body = ApiResponse<serde_json::Value>)
+        (status = 200, description = "预约成功", body = ApiResponse<Reservation>),
+        (status = 200, description = "时间窗与已有预约重叠", body = ApiResponse<Reservation>)
     )
 )]
-pub async fn heartbeat(
+pub async fn reserve_lock(
     storage: web::Data<Arc<dyn LockStorage>>,
-    req: web::Json<HeartbeatRequest>,
+    config: web::Data<Config>,
+    reservations: web::Data<Arc<ReservationScheduler>>,
+    id_generator: web::Data<Arc<dyn LockIdGenerator>>,
+    req: ValidJson<ReserveLockRequest>,
+) -> ApiResponse<Reservation> {
+    let policy = match storage.get_namespace(&req.namespace).await {
+        Ok(policy) => policy.unwrap_or_else(|| NamespacePolicy::unrestricted(&req.namespace)),
+        Err(e) => {
+            error!("Failed to load namespace policy: {}", e);
+            return ApiResponse::<Reservation>::error(
+                storage_error_code(LockError::PolicyLookupFailed, &e),
+                format!("Failed to load namespace policy: {}", e),
+            );
+        }
+    };
+    if let Err(message) = policy.resolve_timeout(
+        Some(req.hold_for),
+        config.lock_default_timeout,
+        config.lock_max_timeout,
+    ) {
+        return ApiResponse::<Reservation>::error(LockError::InvalidLockRequest, message);
+    }
+
+    let metadata_size = req.metadata_size();
+    if metadata_size > config.lock_metadata_max_bytes {
+        return ApiResponse::<Reservation>::error(
+            LockError::InvalidLockRequest,
+            format!(
+                "metadata size {} bytes exceeds the limit of {} bytes",
+                metadata_size, config.lock_metadata_max_bytes
+            ),
+        );
+    }
+
+    let reservation = Reservation::new(id_generator.next_id(), &req);
+    match reservations.reserve(storage.as_ref().as_ref(), reservation.clone()).await {
+        Ok(Ok(())) => {
+            info!(
+                "[RESERVE] Lock reserved - lock_id: {}, namespace: {}, business_id: {}, user_id: {}, acquire_at: {}, ends_at: {}",
+                reservation.lock_id, reservation.namespace, reservation.business_id,
+                reservation.user_id, reservation.acquire_at, reservation.ends_at
+            );
+            ApiResponse::success(reservation)
+        }
+        Ok(Err(existing)) => {
+            info!(
+                "[RESERVE REJECTED] Overlapping reservation - namespace: {}, business_id: {}, requested_by: {}, reserved_by: {}",
+                reservation.namespace, reservation.business_id, reservation.user_id, existing.user_id
+            );
+            ApiResponse::<Reservation>::error(
+                LockError::ReservationConflict,
+                format!(
+                    "Lock already reserved by {} from {} to {}",
+                    existing.user_name, existing.acquire_at, existing.ends_at
+                ),
+            )
+        }
+        Err(e) => {
+            error!("Failed to save reservation: {}", e);
+            ApiResponse::<Reservation>::error(
+                storage_error_code(LockError::ReservationFailed, &e),
+                format!("Failed to save reservation: {}", e),
+            )
+        }
+    }
+}
+
+/// 取消预约接口
+#[utoipa::path(
+    post,
+    path = "/api/lock/reserve/cancel",
+    tag = "lock",
+    request_body = CancelReservationRequest,
+    responses(
+        (status = 200, description = "取消成功", body = ApiResponse<Reservation>),
+        (status = 200, description = "预约不存在或不属于当前用户", body = ApiResponse<Reservation>)
+    )
+)]
+pub async fn cancel_reservation(
+    storage: web::Data<Arc<dyn LockStorage>>,
+    reservations: web::Data<Arc<ReservationScheduler>>,
+    tenant: Option<web::ReqData<Tenant>>,
+    req: ValidJson<CancelReservationRequest>,
+) -> ApiResponse<Reservation> {
+    match reservations
+        .cancel(storage.as_ref().as_ref(), &req.lock_id, &req.user_id, tenant.as_deref())
+        .await
+    {
+        Ok(Some(reservation)) => {
+            info!("[RESERVE] Reservation cancelled - lock_id: {}", req.lock_id);
+            ApiResponse::success(reservation)
+        }
+        Ok(None) => ApiResponse::<Reservation>::error(
+            LockError::ReservationNotFound,
+            "Reservation not found or not owned".to_string(),
+        ),
+        Err(e) => {
+            error!("Failed to cancel reservation: {}", e);
+            ApiResponse::<Reservation>::error(
+                storage_error_code(LockError::ReservationFailed, &e),
+                format!("Failed to cancel reservation: {}", e),
+            )
+        }
+    }
+}
+
+/// 预约列表接口，按开始时间排列
+#[utoipa::path(
+    get,
+    path = "/api/lock/reservations",
+    tag = "lock",
+    params(ListReservationsQuery),
+    responses(
+        (status = 200, description = "尚未授予的预约", body = ApiResponse<Vec<Reservation>>)
+    )
+)]
+pub async fn list_reservations(
+    storage: web::Data<Arc<dyn LockStorage>>,
+    reservations: web::Data<Arc<ReservationScheduler>>,
+    tenant: Option<web::ReqData<Tenant>>,
+    query: ValidQuery<ListReservationsQuery>,
+) -> ApiResponse<Vec<Reservation>> {
+    let mut list = match reservations.list(storage.as_ref().as_ref(), query.namespace.as_deref()).await {
+        Ok(list) => list,
+        Err(e) => {
+            error!("Failed to list reservations: {}", e);
+            return ApiResponse::<Vec<Reservation>>::error(
+                storage_error_code(LockError::ReservationFailed, &e),
+                format!("Failed to list reservations: {}", e),
+            );
+        }
+    };
+    if let Some(tenant) = &tenant {
+        list.retain(|reservation| tenant.owns(&reservation.namespace));
+    }
+    ApiResponse::success(list)
+}
+
+/// 请求接管锁接口
+#[utoipa::path(
+    post,
+    path = "/api/lock/request-takeover",
+    tag = "lock",
+    request_body = RequestTakeoverRequest,
+    responses(
+        (status = 200, description = "接管请求已登记，等待持有人答复", body = ApiResponse<TakeoverRequest>),
+        (status = 200, description = "锁未被他人持有", body = ApiResponse<TakeoverRequest>)
+    )
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn request_takeover(
+    storage: web::Data<Arc<dyn LockStorage>>,
+    events: web::Data<Arc<EventBus>>,
+    config: web::Data<Config>,
+    takeovers: web::Data<Arc<TakeoverBroker>>,
+    id_generator: web::Data<Arc<dyn LockIdGenerator>>,
+    clock: web::Data<Arc<dyn Clock>>,
+    http_req: HttpRequest,
+    req: ValidJson<RequestTakeoverRequest>,
+) -> ApiResponse<TakeoverRequest> {
+    let now = clock.now();
+    let lock_key = format!("{}:{}", req.namespace, req.business_id);
+    let holder = match storage.get_lock(&lock_key).await {
+        Ok(Some(holder)) if !holder.is_expired_at(now) => holder,
+        Ok(_) => {
+            return ApiResponse::<TakeoverRequest>::error(
+                LockError::TakeoverNotPossible,
+                "Lock is not held, acquire it directly".to_string(),
+            )
+        }
+        Err(e) => {
+            error!("Failed to get lock: {}", e);
+            return ApiResponse::<TakeoverRequest>::error(
+                storage_error_code(LockError::TakeoverFailed, &e),
+                format!("Failed to get lock: {}", e),
+            );
+        }
+    };
+    if holder.user_id == req.user_id {
+        return ApiResponse::<TakeoverRequest>::error(LockError::TakeoverNotPossible, "Lock is already held by the requester".to_string());
+    }
+
+    let policy = match storage.get_namespace(&req.namespace).await {
+        Ok(policy) => policy.unwrap_or_else(|| NamespacePolicy::unrestricted(&req.namespace)),
+        Err(e) => {
+            error!("Failed to load namespace policy: {}", e);
+            return ApiResponse::<TakeoverRequest>::error(
+                storage_error_code(LockError::TakeoverFailed, &e),
+                format!("Failed to load namespace policy: {}", e),
+            );
+        }
+    };
+    let timeout = match policy.resolve_timeout(req.timeout, config.lock_default_timeout, config.lock_max_timeout) {
+        Ok(timeout) => timeout,
+        Err(message) => return ApiResponse::<TakeoverRequest>::error(LockError::InvalidLockRequest, message),
+    };
+
+    let client = peer::client_context(&http_req, req.hostname.clone(), &config.trusted_proxies);
+    let mut lock_info = req.to_lock_info(id_generator.next_id(), timeout, client, now);
+    lock_info.grace_seconds = config.heartbeat_grace(timeout);
+    let (request, created) = takeovers.request(
+        id_generator.next_id(),
+        &holder,
+        lock_info,
+        req.message.clone(),
+        chrono::Duration::seconds(config.lock_takeover_timeout as i64),
+        now,
+    );
+    if created {
+        info!(
+            "[TAKEOVER] Takeover requested - request_id: {}, namespace: {}, business_id: {}, holder: {}, requested_by: {}",
+            request.request_id, request.namespace, request.business_id, holder.user_id, request.user_id
+        );
+        takeovers.notify_requested(&events, holder, request.clone());
+    }
+    ApiResponse::success(request)
+}
+
+/// 答复接管请求接口
+#[utoipa::path(
+    post,
+    path = "/api/lock/takeover/respond",
+    tag = "lock",
+    request_body = RespondTakeoverRequest,
+    responses(
+        (status = 200, description = "已答复，同意时锁已转移给申请人", body = ApiResponse<TakeoverRequest>),
+        (status = 200, description = "请求不存在、已答复或持有人的锁已释放", body = ApiResponse<TakeoverRequest>)
+    )
+)]
+pub async fn respond_takeover(
+    storage: web::Data<Arc<dyn LockStorage>>,
+    events: web::Data<Arc<EventBus>>,
+    takeovers: web::Data<Arc<TakeoverBroker>>,
+    clock: web::Data<Arc<dyn Clock>>,
+    tenant: Option<web::ReqData<Tenant>>,
+    req: ValidJson<RespondTakeoverRequest>,
+) -> ApiResponse<TakeoverRequest> {
+    match tenant::may_access_lock(storage.as_ref().as_ref(), tenant.as_deref(), &req.lock_id).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return ApiResponse::<TakeoverRequest>::error(
+                LockError::TakeoverNotFound,
+                "Takeover request not found or already answered".to_string(),
+            )
+        }
+        Err(e) => {
+            error!("Failed to get lock: {}", e);
+            return ApiResponse::<TakeoverRequest>::error(
+                storage_error_code(LockError::TakeoverFailed, &e),
+                format!("Failed to get lock: {}", e),
+            );
+        }
+    }
+    match takeovers
+        .respond(storage.as_ref().as_ref(), &events, &req.request_id, &req.lock_id, req.approve, clock.now())
+        .await
+    {
+        Ok(request) => ApiResponse::success(request),
+        Err(TakeoverError::NotFound) => ApiResponse::<TakeoverRequest>::error(
+            LockError::TakeoverNotFound,
+            "Takeover request not found or already answered".to_string(),
+        ),
+        Err(TakeoverError::LockGone) => ApiResponse::<TakeoverRequest>::error(
+            LockError::TakeoverNotPossible,
+            "Lock has been released or has expired".to_string(),
+        ),
+        Err(TakeoverError::Storage(e)) => {
+            error!("Failed to transfer lock: {}", e);
+            ApiResponse::<TakeoverRequest>::error(
+                storage_error_code(LockError::TakeoverFailed, &e),
+                format!("Failed to transfer lock: {}", e),
+            )
+        }
+    }
+}
+
+/// 查询接管请求接口，申请人据此得知答复结果
+#[utoipa::path(
+    get,
+    path = "/api/lock/takeover/status",
+    tag = "lock",
+    params(TakeoverStatusQuery),
+    responses(
+        (status = 200, description = "接管请求，批准后 lock_id 为申请人持有的锁", body = ApiResponse<TakeoverRequest>),
+        (status = 200, description = "请求不存在或不属于该用户", body = ApiResponse<TakeoverRequest>)
+    )
+)]
+pub async fn takeover_status(
+    takeovers: web::Data<Arc<TakeoverBroker>>,
+    tenant: Option<web::ReqData<Tenant>>,
+    query: ValidQuery<TakeoverStatusQuery>,
+) -> ApiResponse<TakeoverRequest> {
+    let request = takeovers
+        .get(&query.request_id, &query.user_id)
+        .filter(|request| tenant.as_ref().is_none_or(|tenant| tenant.owns(&request.namespace)));
+    match request {
+        Some(request) => ApiResponse::success(request),
+        None => ApiResponse::<TakeoverRequest>::error(LockError::TakeoverNotFound, "Takeover request not found".to_string()),
+    }
+}
+
+/// 待答复的接管请求接口，持有人未订阅事件时可轮询
+#[utoipa::path(
+    get,
+    path = "/api/lock/takeover/pending",
+    tag = "lock",
+    params(PendingTakeoversQuery),
+    responses(
+        (status = 200, description = "发给该锁持有人、尚未答复的接管请求", body = ApiResponse<Vec<TakeoverRequest>>)
+    )
+)]
+pub async fn pending_takeovers(
+    takeovers: web::Data<Arc<TakeoverBroker>>,
+    tenant: Option<web::ReqData<Tenant>>,
+    query: ValidQuery<PendingTakeoversQuery>,
+) -> ApiResponse<Vec<TakeoverRequest>> {
+    let mut requests = takeovers.pending(&query.lock_id);
+    if let Some(tenant) = &tenant {
+        requests.retain(|request| tenant.owns(&request.namespace));
+    }
+    ApiResponse::success(requests)
+}
+
+/// 登记在场接口，同一用户再次登记即刷新心跳
+#[utoipa::path(
+    post,
+    path = "/api/presence/join",
+    tag = "presence",
+    request_body = PresenceJoinRequest,
+    responses(
+        (status = 200, description = "已登记，返回所有在场者和当前持有的锁", body = ApiResponse<PresenceJoinSuccess>)
+    )
+)]
+pub async fn presence_join(
+    storage: web::Data<Arc<dyn LockStorage>>,
+    config: web::Data<Config>,
+    id_generator: web::Data<Arc<dyn LockIdGenerator>>,
+    clock: web::Data<Arc<dyn Clock>>,
+    req: ValidJson<PresenceJoinRequest>,
+) -> ApiResponse<PresenceJoinSuccess> {
+    let timeout = req.timeout.unwrap_or(config.presence_timeout);
+    if timeout > config.lock_max_timeout {
+        return ApiResponse::<PresenceJoinSuccess>::error(
+            LockError::InvalidLockRequest,
+            format!("timeout must be at most {} seconds", config.lock_max_timeout),
+        );
+    }
+
+    let now = clock.now();
+    let storage = storage.as_ref().as_ref();
+    let entry = match presence::join(storage, req.to_lock_info(id_generator.next_id(), timeout, now)).await {
+        Ok(entry) => entry,
+        Err(e) => {
+            error!("Failed to join presence: {}", e);
+            return ApiResponse::<PresenceJoinSuccess>::error(
+                storage_error_code(LockError::PresenceFailed, &e),
+                format!("Failed to join presence: {}", e),
+            );
+        }
+    };
+    match presence::list(storage, &req.namespace, &req.business_id, now).await {
+        Ok(list) => ApiResponse::success(PresenceJoinSuccess {
+            presence_id: entry.lock_id.clone(),
+            expires_at: entry.expires_at(),
+            members: list.members,
+            lock: list.lock,
+        }),
+        Err(e) => {
+            error!("Failed to list presence: {}", e);
+            ApiResponse::<PresenceJoinSuccess>::error(
+                storage_error_code(LockError::PresenceFailed, &e),
+                format!("Failed to list presence: {}", e),
+            )
+        }
+    }
+}
+
+/// 离开接口，未在场时同样返回成功
+#[utoipa::path(
+    post,
+    path = "/api/presence/leave",
+    tag = "presence",
+    request_body = PresenceLeaveRequest,
+    responses(
+        (status = 200, description = "已离开", body = ApiResponse<serde_json::Value>)
+    )
+)]
+pub async fn presence_leave(
+    storage: web::Data<Arc<dyn LockStorage>>,
+    req: ValidJson<PresenceLeaveRequest>,
+) -> ApiResponse<serde_json::Value> {
+    match presence::leave(storage.as_ref().as_ref(), &req.namespace, &req.business_id, &req.user_id).await {
+        Ok(present) => ApiResponse::success(serde_json::json!({ "present": present })),
+        Err(e) => {
+            error!("Failed to leave presence: {}", e);
+            ApiResponse::<serde_json::Value>::error(
+                storage_error_code(LockError::PresenceFailed, &e),
+                format!("Failed to leave presence: {}", e),
+            )
+        }
+    }
+}
+
+/// 在场者接口
+#[utoipa::path(
+    get,
+    path = "/api/presence/list",
+    tag = "presence",
+    params(PresenceListQuery),
+    responses(
+        (status = 200, description = "所有在场者和当前持有的锁", body = ApiResponse<PresenceList>)
+    )
+)]
+pub async fn presence_list(
+    storage: web::Data<Arc<dyn LockStorage>>,
+    clock: web::Data<Arc<dyn Clock>>,
+    query: ValidQuery<PresenceListQuery>,
+) -> ApiResponse<PresenceList> {
+    match presence::list(storage.as_ref().as_ref(), &query.namespace, &query.business_id, clock.now()).await {
+        Ok(list) => ApiResponse::success(list),
+        Err(e) => {
+            error!("Failed to list presence: {}", e);
+            ApiResponse::<PresenceList>::error(
+                storage_error_code(LockError::PresenceFailed, &e),
+                format!("Failed to list presence: {}", e),
+            )
+        }
+    }
+}
+
+/// 锁统计信息
+#[utoipa::path(
+    get,
+    path = "/api/stats",
+    tag = "lock",
+    params(StatsQuery),
+    responses(
+        (status = 200, description = "锁统计信息，带 ETag", body = ApiResponse<LockStats>),
+        (status = 304, description = "统计信息未变化（If-None-Match 与 ETag 相同）")
+    )
+)]
+pub async fn stats(
+    req: HttpRequest,
+    storage: web::Data<Arc<dyn LockStorage>>,
+    clock: web::Data<Arc<dyn Clock>>,
+    tenant: Option<web::ReqData<Tenant>>,
+    query: ValidQuery<StatsQuery>,
 ) -> HttpResponse {
-    info!("Heartbeat request: lock_id={}", req.lock_id);
-
-    match storage.update_heartbeat(&req.lock_id).await {
-        Ok(updated) => {
-            if updated {
-                info!("Heartbeat updated successfully: {}", req.lock_id);
-                HttpResponse::Ok().json(ApiResponse::success(serde_json::json!({
-                    "updated": true
-                })))
-            } else {
-                info!("Lock not found or expired: {}", req.lock_id);
-                HttpResponse::Ok().json(ApiResponse::<serde_json::Value>::error(
-                    2001,
-                    "Lock not found or expired".to_string(),
-                ))
-            }
+    let result = match &tenant {
+        Some(tenant) => tenant::stats(storage.as_ref().as_ref(), tenant, query.top, clock.now()).await,
+        None => storage.stats(query.top).await,
+    };
+    match result {
+        Ok(stats) => ApiResponse::success(stats),
+        Err(e) => {
+            error!("Failed to get lock stats: {}", e);
+            ApiResponse::<LockStats>::error(storage_error_code(LockError::StatsFailed, &e), format!("Failed to get lock stats: {}", e))
+        }
+    }
+    .respond_with_etag(&req)
+}
+
+/// 从序列分配 batch 个连续递增的值，所有实例共享同一序列
+#[utoipa::path(
+    post,
+    path = "/api/sequence/next",
+    tag = "sequence",
+    params(SequenceQuery),
+    responses(
+        (status = 200, description = "分配到的序列值", body = ApiResponse<SequenceRange>)
+    )
+)]
+pub async fn next_sequence(
+    storage: web::Data<Arc<dyn LockStorage>>,
+    query: ValidQuery<SequenceQuery>,
+) -> ApiResponse<SequenceRange> {
+    match storage.next_sequence(&query.name, query.batch).await {
+        Ok(last) => ApiResponse::success(SequenceRange {
+            name: query.name.clone(),
+            first: last - query.batch + 1,
+            last,
+        }),
+        Err(e) if e.is::<StorageUnavailable>() => {
+            ApiResponse::<SequenceRange>::error(LockError::StorageUnavailable, "Storage unavailable, retry later".to_string())
         }
         Err(e) => {
-            error!("Failed to update heartbeat: {}", e);
-            HttpResponse::Ok().json(ApiResponse::<serde_json::Value>::error(
-                2002,
-                format!("Failed to update heartbeat: {}", e),
-            ))
+            error!("Failed to allocate sequence {}: {}", query.name, e);
+            ApiResponse::<SequenceRange>::error(storage_error_code(LockError::SequenceFailed, &e), format!("Failed to allocate sequence: {}", e))
         }
     }
 }
 
+/// 令牌桶限流检查，放行时扣减令牌，所有实例共享同一令牌桶
+#[utoipa::path(
+    post,
+    path = "/api/ratelimit/check",
+    tag = "ratelimit",
+    request_body = RateLimitRequest,
+    responses(
+        (status = 200, description = "限流检查结果", body = ApiResponse<RateLimitDecision>)
+    )
+)]
+pub async fn check_rate_limit(
+    storage: web::Data<Arc<dyn LockStorage>>,
+    req: ValidJson<RateLimitRequest>,
+) -> ApiResponse<RateLimitDecision> {
+    match storage.take_tokens(&req.key, req.bucket(), req.cost).await {
+        Ok(decision) => ApiResponse::success(decision),
+        Err(e) => {
+            error!("Failed to check rate limit {}: {}", req.key, e);
+            ApiResponse::<RateLimitDecision>::error(storage_error_code(LockError::RateLimitFailed, &e), format!("Failed to check rate limit: {}", e))
+        }
+    }
+}
+
+/// 锁竞争分析，统计最近 `STATS_CONTENTION_WINDOW` 秒内当前实例处理的申请
+#[utoipa::path(
+    get,
+    path = "/api/stats/contention",
+    tag = "lock",
+    params(StatsQuery),
+    responses(
+        (status = 200, description = "锁竞争分析", body = ApiResponse<ContentionStats>)
+    )
+)]
+pub async fn contention_stats(
+    contention: web::Data<Arc<ContentionTracker>>,
+    tenant: Option<web::ReqData<Tenant>>,
+    query: ValidQuery<StatsQuery>,
+) -> ApiResponse<ContentionStats> {
+    let key_prefix = tenant.as_ref().map(|tenant| tenant.key_prefix());
+    ApiResponse::success(contention.snapshot(query.top, key_prefix.as_deref()))
+}
+
+/// 按用户的用量：当前持有的锁数量取自存储，获取次数和累计持有时长取自本实例的锁事件
+#[utoipa::path(
+    get,
+    path = "/api/stats/users",
+    tag = "lock",
+    params(UserStatsQuery),
+    responses(
+        (status = 200, description = "按用户的用量", body = ApiResponse<UserUsageStats>)
+    )
+)]
+pub async fn user_stats(
+    storage: web::Data<Arc<dyn LockStorage>>,
+    usage: web::Data<Arc<UsageTracker>>,
+    config: web::Data<Config>,
+    clock: web::Data<Arc<dyn Clock>>,
+    tenant: Option<web::ReqData<Tenant>>,
+    query: ValidQuery<UserStatsQuery>,
+) -> ApiResponse<UserUsageStats> {
+    let tenant = tenant.as_deref();
+    let locks = match tenant {
+        Some(tenant) => storage.list_prefix(&tenant.key_prefix()).await,
+        None => storage.list_locks().await,
+    };
+    match locks {
+        Ok(locks) => ApiResponse::success(UserUsageStats {
+            warning_threshold: config.user_quota_warning(),
+            max_per_user: (config.lock_max_per_user > 0).then_some(config.lock_max_per_user),
+            users: usage.snapshot(&locks, tenant, query.sort, query.top, clock.now()),
+        }),
+        Err(e) => {
+            error!("Failed to get user stats: {}", e);
+            ApiResponse::error(storage_error_code(LockError::StatsFailed, &e), format!("Failed to get user stats: {}", e))
+        }
+    }
+}
+
+/// 锁统计时间序列，取自本实例每 `STATS_SAMPLE_INTERVAL` 秒的采样，可作为 Grafana JSON 数据源
+#[utoipa::path(
+    get,
+    path = "/api/stats/timeseries",
+    tag = "lock",
+    params(TimeSeriesQuery),
+    responses(
+        (status = 200, description = "按 step 聚合的时间序列", body = ApiResponse<TimeSeries>),
+        (status = 200, description = "未启用采样（错误码 5005）", body = ApiResponse<TimeSeries>)
+    )
+)]
+pub async fn stats_timeseries(
+    sampler: web::Data<Arc<StatsSampler>>,
+    clock: web::Data<Arc<dyn Clock>>,
+    tenant: Option<web::ReqData<Tenant>>,
+    query: ValidQuery<TimeSeriesQuery>,
+) -> ApiResponse<TimeSeries> {
+    if sampler.interval() == 0 {
+        return ApiResponse::<TimeSeries>::error(LockError::StatsSamplingDisabled, "Stats sampling is disabled (STATS_SAMPLE_INTERVAL=0)".to_string());
+    }
+    let tenant = tenant.as_deref();
+    let from = query.from.and_then(chrono::DateTime::from_timestamp_millis);
+    let to = query
+        .to
+        .and_then(chrono::DateTime::from_timestamp_millis)
+        .unwrap_or_else(|| clock.now());
+    let mut series = sampler.query(
+        query.metric,
+        query.namespace.as_deref(),
+        tenant,
+        query.step.unwrap_or(sampler.interval()),
+        from,
+        to,
+    );
+    if let Some(tenant) = tenant {
+        series.namespace = series
+            .namespace
+            .map(|namespace| namespace.trim_start_matches(&tenant.key_prefix()).to_string());
+    }
+    ApiResponse::success(series)
+}
+
+/// 服务端当前时间，锁的过期时间均以服务端时钟计算，客户端可据此估算本地时钟偏差
+#[utoipa::path(
+    get,
+    path = "/api/time",
+    tag = "lock",
+    responses(
+        (status = 200, description = "服务端时间", body = ApiResponse<ServerTime>)
+    )
+)]
+pub async fn server_time(clock: web::Data<Arc<dyn Clock>>) -> ApiResponse<ServerTime> {
+    let now = clock.now();
+    ApiResponse::success(ServerTime {
+        now,
+        unix_ms: now.timestamp_millis(),
+    })
+}
+
+/// 错误码目录，供客户端生成错误码常量；错误码不会被复用，新接口的错误码在目录中登记
+#[utoipa::path(
+    get,
+    path = "/api/errors",
+    tag = "lock",
+    responses(
+        (status = 200, description = "全部错误码，按错误码升序", body = ApiResponse<Vec<ErrorCodeInfo>>)
+    )
+)]
+pub async fn error_codes() -> ApiResponse<Vec<ErrorCodeInfo>> {
+    ApiResponse::success(LockError::ALL.iter().copied().map(ErrorCodeInfo::from).collect())
+}
+
+/// 心跳接口
+#[utoipa::path(
+    post,
+    path = "/api/lock/heartbeat",
+    tag = "lock",
+    request_body = HeartbeatRequest,
+    responses(
+        (status = 200, description = "心跳成功", body = ApiResponse<HeartbeatSuccess>),
+        (status = 200, description = "锁不存在或已过期", body = ApiResponse<HeartbeatSuccess>)
+    )
+)]
+pub async fn heartbeat(
+    ops: web::Data<LockOps>,
+    tenant: Option<web::ReqData<Tenant>>,
+    req: ValidJson<HeartbeatRequest>,
+) -> ApiResponse<HeartbeatSuccess> {
+    ops.heartbeat(&req, tenant.as_deref()).await.into()
+}
+
 /// 释放锁接口
 #[utoipa::path(
     post,
@@ -183,32 +1244,279 @@ pub async fn heartbeat(
     )
 )]
 pub async fn release_lock(
+    ops: web::Data<LockOps>,
+    tenant: Option<web::ReqData<Tenant>>,
+    req: ValidJson<ReleaseLockRequest>,
+) -> ApiResponse<serde_json::Value> {
+    ops.release(&req, tenant.as_deref())
+        .await
+        .map(|lock_info| {
+            serde_json::json!({
+                "released": true,
+                "version": lock_info.version
+            })
+        })
+        .into()
+}
+
+/// 借出：获取锁并登记预计归还时间，超过后仍未归还会发布 `overdue` 事件并列入管理接口的逾期报告
+#[utoipa::path(
+    post,
+    path = "/api/lock/checkout",
+    tag = "lock",
+    request_body = CheckoutRequest,
+    responses(
+        (status = 200, description = "借出成功，返回 due_at", body = ApiResponse<AcquireLockSuccess>),
+        (status = 200, description = "借出失败，与申请锁相同", body = ApiResponse<AcquireLockFailure>)
+    )
+)]
+pub async fn checkout(
+    ops: web::Data<LockOps>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+    req: ValidJson<CheckoutRequest>,
+) -> ApiResponse<AcquireLockSuccess> {
+    let client = peer::client_context(&http_req, req.hostname.clone(), &config.trusted_proxies);
+    ops.acquire(&req.acquire_request(), client).await.into()
+}
+
+/// 归还借出的锁，与释放锁相同，响应中说明是否逾期
+#[utoipa::path(
+    post,
+    path = "/api/lock/checkin",
+    tag = "lock",
+    request_body = ReleaseLockRequest,
+    responses(
+        (status = 200, description = "归还结果", body = ApiResponse<CheckinSuccess>)
+    )
+)]
+pub async fn checkin(
+    ops: web::Data<LockOps>,
+    tenant: Option<web::ReqData<Tenant>>,
+    req: ValidJson<ReleaseLockRequest>,
+) -> ApiResponse<CheckinSuccess> {
+    ops.release(&req, tenant.as_deref())
+        .await
+        .map(|lock_info| CheckinSuccess::new(&lock_info, ops.clock.now()))
+        .into()
+}
+
+/// 校验锁：资源服务在写入前确认客户端提交的 lock_id 仍然有效且属于声称的业务键和持有人
+#[utoipa::path(
+    post,
+    path = "/api/lock/verify",
+    tag = "lock",
+    request_body = VerifyLockRequest,
+    responses(
+        (status = 200, description = "校验结果，未通过时 valid 为 false 并返回 reason", body = ApiResponse<LockVerification>)
+    )
+)]
+pub async fn verify_lock(ops: web::Data<LockOps>, req: ValidJson<VerifyLockRequest>) -> ApiResponse<LockVerification> {
+    ops.verify(&req).await.into()
+}
+
+
+/// 释放用户持有的所有锁，用于退出登录或崩溃恢复
+#[utoipa::path(
+    post,
+    path = "/api/lock/release-all",
+    tag = "lock",
+    request_body = ReleaseAllRequest,
+    responses(
+        (status = 200, description = "释放结果", body = ApiResponse<serde_json::Value>)
+    )
+)]
+pub async fn release_all(
     storage: web::Data<Arc<dyn LockStorage>>,
-    req: web::Json<ReleaseLockRequest>,
-) -> HttpResponse {
-    info!("[RELEASE] Attempting to release lock - lock_id: {}", req.lock_id);
-
-    match storage.release(&req.lock_id).await {
-        Ok(released) => {
-            if released {
-                info!("[RELEASE SUCCESS] Lock released - lock_id: {}", req.lock_id);
-                HttpResponse::Ok().json(ApiResponse::success(serde_json::json!({
-                    "released": true
-                })))
-            } else {
-                info!("[RELEASE FAILED] Lock not found or not owned - lock_id: {}", req.lock_id);
-                HttpResponse::Ok().json(ApiResponse::<serde_json::Value>::error(
-                    3001,
-                    "Lock not found or not owned".to_string(),
-                ))
-            }
+    events: web::Data<Arc<EventBus>>,
+    tenant: Option<web::ReqData<Tenant>>,
+    req: ValidJson<ReleaseAllRequest>,
+) -> ApiResponse<serde_json::Value> {
+    let result = match &tenant {
+        Some(tenant) => release_tenant_locks(storage.as_ref().as_ref(), tenant, &req.user_id).await,
+        None => storage.release_all_by_user(&req.user_id).await,
+    };
+    let released = match result {
+        Ok(released) => released,
+        Err(e) => {
+            error!("Failed to release locks of user {}: {}", req.user_id, e);
+            return ApiResponse::<serde_json::Value>::error(
+                storage_error_code(LockError::ReleaseFailed, &e),
+                format!("Failed to release locks: {}", e),
+            );
+        }
+    };
+
+    info!(
+        "[RELEASE ALL] Released {} locks - user_id: {}",
+        released.len(),
+        req.user_id
+    );
+    for lock_info in &released {
+        events.publish(LockEvent::new(LockEventType::Released, lock_info.clone()));
+    }
+    ApiResponse::success(serde_json::json!({
+        "released": released.len(),
+        "locks": released
+    }))
+}
+
+/// 排空用户（或其某个会话）持有的锁，用于工作进程停止前逐个释放，避免所有锁同时空闲
+#[utoipa::path(
+    post,
+    path = "/api/lock/release-session-graceful",
+    tag = "lock",
+    request_body = GracefulReleaseRequest,
+    responses(
+        (status = 200, description = "排空中的锁和截止时间", body = ApiResponse<GracefulReleaseStarted>)
+    )
+)]
+pub async fn release_session_graceful(
+    ops: web::Data<LockOps>,
+    tenant: Option<web::ReqData<Tenant>>,
+    req: ValidJson<GracefulReleaseRequest>,
+) -> ApiResponse<GracefulReleaseStarted> {
+    ops.release_gracefully(&req, tenant.as_deref()).await.into()
+}
+
+/// 释放用户在租户中持有的锁，其他租户中同名用户的锁不受影响
+async fn release_tenant_locks(
+    storage: &dyn LockStorage,
+    tenant: &Tenant,
+    user_id: &str,
+) -> anyhow::Result<Vec<LockInfo>> {
+    let mut released = Vec::new();
+    for lock_info in storage.list_by_user(user_id).await? {
+        if tenant.owns_lock(&lock_info) {
+            released.extend(storage.release(&lock_info.lock_id).await?);
+        }
+    }
+    Ok(released)
+}
+
+/// 创建会话
+#[utoipa::path(
+    post,
+    path = "/api/session/create",
+    tag = "session",
+    request_body = CreateSessionRequest,
+    responses(
+        (status = 200, description = "会话信息", body = ApiResponse<SessionInfo>)
+    )
+)]
+pub async fn create_session(
+    config: web::Data<Config>,
+    storage: web::Data<Arc<dyn LockStorage>>,
+    sessions: web::Data<Arc<SessionRegistry>>,
+    clock: web::Data<Arc<dyn Clock>>,
+    req: ValidJson<CreateSessionRequest>,
+) -> ApiResponse<SessionInfo> {
+    let timeout = req.timeout.unwrap_or(config.lock_default_timeout);
+    if timeout > config.lock_max_timeout {
+        return ApiResponse::<SessionInfo>::error(
+            LockError::InvalidSessionRequest,
+            format!(
+                "timeout {}s exceeds the maximum of {}s",
+                timeout, config.lock_max_timeout
+            ),
+        );
+    }
+
+    let session = match sessions
+        .create(storage.get_ref().as_ref(), &req.user_id, timeout, clock.now())
+        .await
+    {
+        Ok(session) => session,
+        Err(e) => {
+            error!("Failed to create session for {}: {}", req.user_id, e);
+            return ApiResponse::<SessionInfo>::error(
+                storage_error_code(LockError::SessionFailed, &e),
+                format!("Failed to create session: {}", e),
+            );
+        }
+    };
+    info!(
+        "[CLIENT SESSION] Session created - session_id: {}, user_id: {}, timeout: {}s",
+        session.session_id, session.user_id, session.timeout
+    );
+    ApiResponse::success(session)
+}
+
+/// 会话心跳，为会话中的所有锁续期
+#[utoipa::path(
+    post,
+    path = "/api/session/heartbeat",
+    tag = "session",
+    request_body = SessionRequest,
+    responses(
+        (status = 200, description = "会话信息", body = ApiResponse<SessionInfo>),
+        (status = 200, description = "会话不存在或已结束", body = ApiResponse<SessionInfo>)
+    )
+)]
+pub async fn session_heartbeat(
+    storage: web::Data<Arc<dyn LockStorage>>,
+    sessions: web::Data<Arc<SessionRegistry>>,
+    clock: web::Data<Arc<dyn Clock>>,
+    req: ValidJson<SessionRequest>,
+) -> ApiResponse<SessionInfo> {
+    match sessions.heartbeat(&req.session_id, storage.get_ref().as_ref(), clock.now()).await {
+        Ok(Some(session)) => ApiResponse::success(session),
+        Ok(None) => ApiResponse::<SessionInfo>::error(
+            LockError::SessionNotFound,
+            format!("Session not found or expired: {}", req.session_id),
+        ),
+        Err(e) => {
+            error!("Failed to renew session {}: {}", req.session_id, e);
+            ApiResponse::<SessionInfo>::error(
+                storage_error_code(LockError::SessionFailed, &e),
+                format!("Failed to renew session locks: {}", e),
+            )
+        }
+    }
+}
+
+/// 关闭会话并释放其中的所有锁
+#[utoipa::path(
+    post,
+    path = "/api/session/close",
+    tag = "session",
+    request_body = SessionRequest,
+    responses(
+        (status = 200, description = "释放结果", body = ApiResponse<serde_json::Value>),
+        (status = 200, description = "会话不存在或已结束", body = ApiResponse<serde_json::Value>)
+    )
+)]
+pub async fn close_session(
+    storage: web::Data<Arc<dyn LockStorage>>,
+    events: web::Data<Arc<EventBus>>,
+    sessions: web::Data<Arc<SessionRegistry>>,
+    req: ValidJson<SessionRequest>,
+) -> ApiResponse<serde_json::Value> {
+    match sessions
+        .close(&req.session_id, storage.get_ref().as_ref(), &events)
+        .await
+    {
+        Ok(Some(released)) => {
+            info!(
+                "[CLIENT SESSION] Session closed - session_id: {}, released: {}",
+                req.session_id,
+                released.len()
+            );
+            ApiResponse::success(serde_json::json!({
+                "released": released.len(),
+                "locks": released
+            }))
         }
+        Ok(None) => ApiResponse::<serde_json::Value>::error(
+            LockError::SessionNotFound,
+            format!("Session not found or expired: {}", req.session_id),
+        ),
         Err(e) => {
-            error!("Failed to release lock: {}", e);
-            HttpResponse::Ok().json(ApiResponse::<serde_json::Value>::error(
-                3002,
-                format!("Failed to release lock: {}", e),
-            ))
+            error!("Failed to close session {}: {}", req.session_id, e);
+            ApiResponse::<serde_json::Value>::error(
+                storage_error_code(LockError::SessionFailed, &e),
+                format!("Failed to release session locks: {}", e),
+            )
         }
     }
 }
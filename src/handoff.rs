@@ -0,0 +1,467 @@
+//! 实例间的锁状态交接 `/api/admin/handoff`
+//!
+//! 内存存储的锁只在实例进程内，滚动更新时新实例从持久化文件恢复会丢失最后一次持久化之后的变更。新实例配置
+//! `HANDOFF_FROM=<旧实例地址>` 后，启动时在开始监听之前从旧实例接收锁状态：
+//! 1. 经 `GET /api/admin/export` 复制全部锁、命名空间策略以及预约、客户端会话等记录，此时旧实例照常处理请求；
+//! 2. `POST /api/admin/handoff` 隔离旧实例：旧实例拒绝 `/api` 下除只读管理接口外的全部请求（错误码 7008，HTTP 503），
+//!    等待处理中的请求和后台任务完成后才返回（超过 `HANDOFF_TIMEOUT_MS` 时解除隔离并返回错误），
+//!    之后 WebSocket 会话发送 `error` 帧后断开（不释放会话中的锁），`/readyz` 返回 503，
+//!    持久化、过期清理、预约、抢占、排空、接管、会话超时和恢复窗口等任务停止，状态不再变化；
+//! 3. 再次导出并与第 1 步的结果对齐，补上期间的变更，移除期间已释放的锁；
+//! 4. `POST /api/admin/handoff/complete` 通知旧实例交接完成，旧实例不再允许解除隔离，新实例的纪元为旧实例的纪元加 1，开始处理请求。
+//!
+//! 隔离之后任一步骤失败时新实例经 `DELETE /api/admin/handoff` 解除旧实例的隔离并退出，旧实例继续处理请求。
+//! 同一时刻只有一个实例处理锁请求，隔离期间（通常为一次导出和导入的时间）的请求返回 503，客户端按 `Retry-After` 重试。
+//! 交接锁、命名空间策略以及 [`RECORD_KINDS`] 中的预约、客户端会话和管理接口的登录状态，等待队列、WebSocket 会话、排空等进程内的状态不交接，
+//! 客户端重新等待或重新建立连接即可。
+
+use crate::clock::Clock;
+use crate::config::Config;
+use crate::lockops::OpError;
+use crate::migrate::{http_client, service_reply};
+use crate::models::{
+    accepts_problem_json, ApiResponse, HandoffRequest, HandoffState, HandoffStatus, LockError, LockExport,
+};
+use crate::storage::{LockStorage, RECORD_KINDS};
+use crate::v2;
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderValue, RETRY_AFTER};
+use actix_web::middleware::Next;
+use actix_web::{web, Error};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use parking_lot::Mutex;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{OwnedRwLockReadGuard, RwLock};
+
+/// 对齐的结果
+#[derive(Debug, Default)]
+struct SyncReport {
+    copied: usize,
+    removed: usize,
+}
+
+pub struct Handoff {
+    node_id: String,
+    timeout: Duration,
+    status: Mutex<HandoffStatus>,
+    gate: Arc<RwLock<()>>, // 处理中的请求和任务持有读锁，隔离时获取写锁等待它们完成
+}
+
+/// 可能修改锁状态的请求或任务，持有期间本实例的隔离不会完成
+pub struct InFlight {
+    _guard: Option<OwnedRwLockReadGuard<()>>,
+}
+
+/// 开始一个可能修改锁状态的请求或任务，本实例已被隔离时返回 None；未启用交接时总是允许
+pub fn admit(handoff: Option<&Arc<Handoff>>) -> Option<InFlight> {
+    match handoff {
+        Some(handoff) => handoff.admit(),
+        None => Some(InFlight { _guard: None }),
+    }
+}
+
+impl Handoff {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            node_id: config.cluster_node_id.clone(),
+            timeout: Duration::from_millis(config.handoff_timeout_ms),
+            status: Mutex::new(HandoffStatus {
+                state: HandoffState::Serving,
+                epoch: 1,
+                successor: None,
+                fenced_at: None,
+                predecessor: None,
+            }),
+            gate: Arc::new(RwLock::new(())),
+        }
+    }
+
+    /// 开始一个可能修改锁状态的请求或任务，已隔离时返回 None
+    pub fn admit(&self) -> Option<InFlight> {
+        // 先持有读锁再检查状态：检查之后才开始的隔离会等待该读锁释放
+        let guard = self.gate.clone().try_read_owned().ok()?;
+        if self.fenced() {
+            return None;
+        }
+        Some(InFlight { _guard: Some(guard) })
+    }
+
+    pub fn status(&self) -> HandoffStatus {
+        self.status.lock().clone()
+    }
+
+    /// 本实例已被隔离或已完成交接，不再处理锁请求
+    pub fn fenced(&self) -> bool {
+        self.status.lock().state != HandoffState::Serving
+    }
+
+    /// 隔离本实例并等待处理中的请求和任务完成，超时时解除隔离；同一个新实例重复隔离时返回原状态
+    pub async fn fence(&self, successor: &str, now: DateTime<Utc>) -> Result<HandoffStatus, String> {
+        {
+            let mut status = self.status.lock();
+            match status.state {
+                HandoffState::Serving => {
+                    status.state = HandoffState::Fenced;
+                    status.successor = Some(successor.to_string());
+                    status.fenced_at = Some(now);
+                    warn!("[HANDOFF] Instance fenced by {}, rejecting lock requests", successor);
+                }
+                _ if status.successor.as_deref() == Some(successor) => return Ok(status.clone()),
+                _ => {
+                    return Err(format!(
+                        "Instance is already fenced by {}",
+                        status.successor.as_deref().unwrap_or_default()
+                    ))
+                }
+            }
+        }
+
+        if tokio::time::timeout(self.timeout, self.gate.write()).await.is_err() {
+            let _ = self.unfence();
+            return Err(format!(
+                "In-flight requests did not finish within {}ms",
+                self.timeout.as_millis()
+            ));
+        }
+        info!("[HANDOFF] In-flight requests finished, lock state is frozen");
+        Ok(self.status())
+    }
+
+    /// 解除隔离，交接已完成时不允许解除
+    pub fn unfence(&self) -> Result<HandoffStatus, String> {
+        let mut status = self.status.lock();
+        match status.state {
+            HandoffState::Retired => Err(format!(
+                "Handoff to {} is already complete",
+                status.successor.as_deref().unwrap_or_default()
+            )),
+            HandoffState::Fenced => {
+                warn!(
+                    "[HANDOFF] Handoff to {} aborted, serving lock requests again",
+                    status.successor.as_deref().unwrap_or_default()
+                );
+                status.state = HandoffState::Serving;
+                status.successor = None;
+                status.fenced_at = None;
+                Ok(status.clone())
+            }
+            HandoffState::Serving => Ok(status.clone()),
+        }
+    }
+
+    /// 交接完成，只有隔离本实例的新实例可以完成
+    pub fn complete(&self, successor: &str) -> Result<HandoffStatus, String> {
+        let mut status = self.status.lock();
+        if status.state == HandoffState::Serving || status.successor.as_deref() != Some(successor) {
+            return Err(format!("Instance is not fenced by {}", successor));
+        }
+        if status.state == HandoffState::Fenced {
+            info!("[HANDOFF] Handoff to {} complete, instance retired", successor);
+            status.state = HandoffState::Retired;
+        }
+        Ok(status.clone())
+    }
+
+    /// 从 from 指向的旧实例接收锁状态并隔离旧实例，需在开始处理请求之前调用
+    pub async fn receive(&self, config: &Config, from: &str, storage: &dyn LockStorage, clock: &dyn Clock) -> Result<()> {
+        let client = reqwest::Client::builder().timeout(self.timeout).build()?;
+        let request = HandoffRequest {
+            successor: self.node_id.clone(),
+        };
+
+        // 旧实例仍在处理请求时先复制一次，隔离后只需补上期间的变更
+        let export: LockExport = service_reply(http_client(config, client.get(format!("{}/api/admin/export", from))))
+            .await
+            .with_context(|| format!("Failed to export locks from {}", from))?;
+        let report = sync(storage, export, clock.now()).await?;
+        info!("[HANDOFF] Copied {} locks from {}, fencing it", report.copied, from);
+
+        let fenced: HandoffStatus = service_reply(http_client(
+            config,
+            client.post(format!("{}/api/admin/handoff", from)).json(&request),
+        ))
+        .await
+        .with_context(|| format!("Failed to fence {}", from))?;
+
+        let result = async {
+            let export: LockExport =
+                service_reply(http_client(config, client.get(format!("{}/api/admin/export", from)))).await?;
+            sync(storage, export, clock.now()).await
+        }
+        .await;
+        let report = match result {
+            Ok(report) => report,
+            Err(e) => {
+                let unfenced: Result<HandoffStatus> =
+                    service_reply(http_client(config, client.delete(format!("{}/api/admin/handoff", from)))).await;
+                if let Err(unfence_error) = unfenced {
+                    warn!("[HANDOFF] Failed to unfence {}: {}", from, unfence_error);
+                }
+                return Err(e.context(format!("Failed to copy locks from {} after fencing it", from)));
+            }
+        };
+
+        // 旧实例未收到完成通知时仍处于隔离状态，不影响本实例处理请求
+        let completed: Result<HandoffStatus> = service_reply(http_client(
+            config,
+            client.post(format!("{}/api/admin/handoff/complete", from)).json(&request),
+        ))
+        .await;
+        if let Err(e) = completed {
+            warn!("[HANDOFF] Failed to notify {} of completion: {}", from, e);
+        }
+
+        let mut status = self.status.lock();
+        status.epoch = fenced.epoch + 1;
+        status.predecessor = Some(from.to_string());
+        info!(
+            "[HANDOFF] Received lock state from {} - epoch: {}, updated: {}, removed: {}",
+            from, status.epoch, report.copied, report.removed
+        );
+        Ok(())
+    }
+}
+
+/// 使存储中的锁、命名空间策略和记录与 export 一致：写入缺少或不同的锁，移除 export 中没有的锁、策略和记录，截至 now 已过期的锁不写入
+async fn sync(storage: &dyn LockStorage, export: LockExport, now: DateTime<Utc>) -> Result<SyncReport> {
+    let mut report = SyncReport::default();
+
+    let names: BTreeSet<String> = export.namespaces.iter().map(|policy| policy.name.clone()).collect();
+    for policy in storage.list_namespaces().await? {
+        if !names.contains(&policy.name) {
+            storage.delete_namespace(&policy.name).await?;
+        }
+    }
+    for policy in export.namespaces {
+        storage.put_namespace(policy).await?;
+    }
+
+    for kind in RECORD_KINDS {
+        let mut records = export.records.get(*kind).cloned().unwrap_or_default();
+        for (id, current) in storage.list_records(kind).await? {
+            match records.remove(&id) {
+                Some(value) if value == current => {}
+                value => {
+                    storage.swap_record(kind, &id, Some(&current), value.as_deref()).await?;
+                }
+            }
+        }
+        for (id, value) in records {
+            storage.swap_record(kind, &id, None, Some(&value)).await?;
+        }
+    }
+
+    let mut locks = BTreeMap::new();
+    for lock_info in export.locks.into_iter().filter(|lock_info| !lock_info.is_expired_at(now)) {
+        locks.insert(lock_info.get_lock_key(), lock_info);
+    }
+    for existing in storage.list_locks().await? {
+        let lock_key = existing.get_lock_key();
+        if !locks.contains_key(&lock_key) && storage.force_release(&lock_key).await?.is_some() {
+            report.removed += 1;
+        }
+    }
+    for (lock_key, lock_info) in locks {
+        // 心跳、抢占通知等会修改锁，逐个字段比较
+        if let Some(existing) = storage.get_lock(&lock_key).await? {
+            if serde_json::to_value(&existing)? == serde_json::to_value(&lock_info)? {
+                continue;
+            }
+            storage.force_release(&lock_key).await?;
+        }
+        if storage.restore(lock_info).await? {
+            report.copied += 1;
+        }
+    }
+    Ok(report)
+}
+
+/// 本实例已被隔离时拒绝交接和只读管理接口以外的请求，未隔离时请求处理期间持有 [`InFlight`]
+pub async fn fence_guard(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let handoff = req.app_data::<web::Data<Arc<Handoff>>>().cloned();
+    let read_only = matches!(req.method().as_str(), "GET" | "HEAD" | "OPTIONS");
+    let exempt = req.path().starts_with("/api/admin/handoff")
+        || req.path().starts_with("/api/admin") && read_only
+        || req.path().starts_with("/api/swagger-ui");
+    let Some(handoff) = handoff.filter(|_| !exempt) else {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    };
+    if let Some(_in_flight) = handoff.admit() {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    }
+
+    let status = handoff.status();
+    let code = LockError::InstanceFenced;
+    let error = ApiResponse::<()>::error(
+        code,
+        format!(
+            "Instance handed off to {}, retry later",
+            status.successor.as_deref().unwrap_or_default()
+        ),
+    );
+    let mut response = if v2::is_v2(req.request()) {
+        v2::error_response(req.request(), OpError::new(error.code, error.message))
+    } else if accepts_problem_json(req.request()) {
+        error.into_problem_response(req.request())
+    } else {
+        error.respond_with_status(req.request(), code.status())
+    };
+    response.headers_mut().insert(RETRY_AFTER, HeaderValue::from(1));
+    Ok(req.into_response(response))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::NamespacePolicy;
+    use crate::reservation;
+    use crate::storage::memory::MemoryStorage;
+    use crate::testing;
+
+    fn policy(name: &str) -> NamespacePolicy {
+        NamespacePolicy {
+            name: name.to_string(),
+            default_timeout: None,
+            max_timeout: None,
+            max_locks: None,
+            allow_queue: true,
+            hierarchical: false,
+            advisory: false,
+            event_routes: Vec::new(),
+            stuck_after: None,
+            freeze: None,
+            max_hold_seconds: None,
+            hold_warning_seconds: None,
+        }
+    }
+
+    fn handoff(timeout_ms: u64) -> Handoff {
+        let mut config = Config::from_env();
+        config.handoff_timeout_ms = timeout_ms;
+        Handoff::new(&config)
+    }
+
+    #[tokio::test]
+    async fn sync_aligns_storage_with_export() {
+        let now = Utc::now();
+        let storage = MemoryStorage::new();
+        let released = testing::lock_info("order", "1", "u1", 300, now);
+        let unchanged = testing::lock_info("order", "2", "u1", 300, now);
+        let renewed = testing::lock_info("order", "3", "u1", 300, now);
+        for lock_info in [&released, &unchanged, &renewed] {
+            assert!(storage.try_acquire(lock_info.clone()).await.unwrap().acquired);
+        }
+        storage.put_namespace(policy("stale")).await.unwrap();
+        let kind = reservation::RECORD_KIND;
+        storage.swap_record(kind, "gone", None, Some("{}")).await.unwrap();
+        storage.swap_record(kind, "changed", None, Some("old")).await.unwrap();
+
+        // 期间 renewed 心跳过一次，released 已释放，added 为新获取的锁，expired 截至 now 已过期
+        let mut heartbeat = renewed.clone();
+        heartbeat.last_heartbeat = now + chrono::Duration::seconds(5);
+        heartbeat.version = 2;
+        let added = testing::lock_info("order", "4", "u2", 300, now);
+        let expired = testing::lock_info("order", "5", "u2", 30, now - chrono::Duration::seconds(60));
+        let export = LockExport {
+            exported_at: now,
+            locks: vec![unchanged.clone(), heartbeat.clone(), added.clone(), expired.clone()],
+            namespaces: vec![policy("order")],
+            records: BTreeMap::from([(
+                kind.to_string(),
+                BTreeMap::from([
+                    ("changed".to_string(), "new".to_string()),
+                    ("added".to_string(), "{}".to_string()),
+                ]),
+            )]),
+        };
+
+        let report = sync(&storage, export, now).await.unwrap();
+
+        assert_eq!((report.copied, report.removed), (2, 1));
+        let mut lock_ids: Vec<String> = storage
+            .list_locks()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|lock_info| lock_info.lock_id)
+            .collect();
+        lock_ids.sort();
+        let mut expected = vec![unchanged.lock_id, heartbeat.lock_id.clone(), added.lock_id];
+        expected.sort();
+        assert_eq!(lock_ids, expected);
+        let synced = storage.get_lock(&heartbeat.get_lock_key()).await.unwrap().unwrap();
+        assert_eq!((synced.version, synced.last_heartbeat), (2, heartbeat.last_heartbeat));
+        assert!(storage.get_lock(&expired.get_lock_key()).await.unwrap().is_none());
+
+        let names: Vec<String> = storage
+            .list_namespaces()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|policy| policy.name)
+            .collect();
+        assert_eq!(names, ["order"]);
+        let mut records = storage.list_records(kind).await.unwrap();
+        records.sort();
+        assert_eq!(
+            records,
+            [
+                ("added".to_string(), "{}".to_string()),
+                ("changed".to_string(), "new".to_string())
+            ]
+        );
+
+        // 再次对齐时没有变化
+        let export = LockExport {
+            exported_at: now,
+            locks: storage.list_locks().await.unwrap(),
+            namespaces: storage.list_namespaces().await.unwrap(),
+            records: BTreeMap::from([(kind.to_string(), records.into_iter().collect())]),
+        };
+        let report = sync(&storage, export, now).await.unwrap();
+        assert_eq!((report.copied, report.removed), (0, 0));
+    }
+
+    #[tokio::test]
+    async fn fence_waits_for_in_flight_requests() {
+        let handoff = Arc::new(handoff(5_000));
+        let in_flight = handoff.admit().unwrap();
+
+        let fencing = tokio::spawn({
+            let handoff = handoff.clone();
+            async move { handoff.fence("new", Utc::now()).await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!fencing.is_finished());
+        assert!(handoff.fenced());
+        assert!(handoff.admit().is_none());
+
+        drop(in_flight);
+        let status = fencing.await.unwrap().unwrap();
+        assert_eq!(status.state, HandoffState::Fenced);
+        assert!(handoff.fence("other", Utc::now()).await.is_err());
+
+        assert_eq!(handoff.complete("new").unwrap().state, HandoffState::Retired);
+        assert!(handoff.unfence().is_err());
+        assert!(handoff.admit().is_none());
+    }
+
+    #[tokio::test]
+    async fn fence_times_out_and_resumes_serving() {
+        let handoff = handoff(50);
+        let _in_flight = handoff.admit().unwrap();
+
+        assert!(handoff.fence("new", Utc::now()).await.is_err());
+
+        assert_eq!(handoff.status().state, HandoffState::Serving);
+        assert!(handoff.complete("new").is_err());
+    }
+}
@@ -0,0 +1,216 @@
+//! 就绪探针 `GET /readyz`
+//!
+//! Redis 断路器打开且未启用降级（`STORAGE_FAILOVER=fail_fast`）时返回 503，其余情况返回 200，
+//! 降级期间状态为 `degraded`。其他存储后端始终就绪。实例已被新实例隔离（见 [`crate::handoff`]）时状态为 `fenced`，返回 503。
+//!
+//! 配置 `READYZ_SELF_TEST` 后探针还会对存储做一次完整的读写自检：在保留的命名空间 `__readyz` 中申请、查询并释放一个探测锁，
+//! 任一步骤失败或超时即返回 503，并报告每个存储后端各步骤的耗时。自检直接访问存储，不发布锁事件也不计入锁历史。
+
+use crate::codec;
+use crate::config::{Config, SelfTestMode};
+use crate::handoff::Handoff;
+use crate::models::LockInfo;
+use crate::storage::failover::{FailoverStorage, StorageHealth};
+use crate::storage::sharded::{ShardHealth, ShardedStorage};
+use crate::storage::LockStorage;
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpRequest, HttpResponse};
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// 自检使用的保留命名空间，申请锁等接口不允许使用
+pub const PROBE_NAMESPACE: &str = "__readyz";
+/// 探测锁的持有人
+const PROBE_USER: &str = "readyz";
+/// 探测锁的超时时间（秒），释放失败时探测锁自行过期
+const PROBE_TIMEOUT: u64 = 30;
+
+#[derive(Debug, Deserialize)]
+pub struct ReadyzQuery {
+    /// 执行存储自检，需 `READYZ_SELF_TEST=on_demand`
+    #[serde(default)]
+    deep: bool,
+}
+
+/// 单个存储后端的自检结果
+#[derive(Debug, Serialize)]
+pub struct SelfTestResult {
+    pub ok: bool,
+    /// 各步骤耗时（毫秒）：acquire、get、release 和 total
+    pub latency_ms: BTreeMap<&'static str, f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// 存储读写自检
+pub struct SelfTest {
+    mode: SelfTestMode,
+    timeout: Duration,
+    business_id: String, // 每个实例固定的探测锁，避免统计中出现大量探测锁
+    backends: Vec<(&'static str, Arc<dyn LockStorage>)>,
+    running: tokio::sync::Mutex<()>, // 同一实例的自检串行执行，避免探测锁相互冲突
+}
+
+impl SelfTest {
+    pub fn new(config: &Config, backends: Vec<(&'static str, Arc<dyn LockStorage>)>) -> Self {
+        Self {
+            mode: config.readyz_self_test.clone(),
+            timeout: Duration::from_millis(config.readyz_self_test_timeout_ms),
+            business_id: format!("probe-{}", Uuid::new_v4()),
+            backends,
+            running: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// 本次探针是否需要自检
+    fn requested(&self, deep: bool) -> bool {
+        match self.mode {
+            SelfTestMode::Off => false,
+            SelfTestMode::OnDemand => deep,
+            SelfTestMode::Always => true,
+        }
+    }
+
+    /// 依次自检每个存储后端
+    pub async fn run(&self) -> BTreeMap<&'static str, SelfTestResult> {
+        let _running = self.running.lock().await;
+        let mut results = BTreeMap::new();
+        for (name, storage) in &self.backends {
+            results.insert(*name, self.run_backend(storage.as_ref()).await);
+        }
+        results
+    }
+
+    async fn run_backend(&self, storage: &dyn LockStorage) -> SelfTestResult {
+        let started = Instant::now();
+        let mut latency_ms = BTreeMap::new();
+        let result = tokio::time::timeout(self.timeout, self.round_trip(storage, &mut latency_ms)).await;
+        latency_ms.insert("total", elapsed_ms(started));
+        let error = match result {
+            Ok(Ok(())) => None,
+            Ok(Err(e)) => Some(e.to_string()),
+            Err(_) => Some(format!("self-test timed out after {}ms", self.timeout.as_millis())),
+        };
+        SelfTestResult {
+            ok: error.is_none(),
+            latency_ms,
+            error,
+        }
+    }
+
+    /// 申请、查询并释放探测锁
+    async fn round_trip(&self, storage: &dyn LockStorage, latency_ms: &mut BTreeMap<&'static str, f64>) -> Result<()> {
+        let now = Utc::now();
+        let probe = LockInfo {
+            lock_id: Uuid::new_v4().to_string(),
+            namespace: PROBE_NAMESPACE.to_string(),
+            user_id: PROBE_USER.to_string(),
+            user_name: PROBE_USER.to_string(),
+            business_id: self.business_id.clone(),
+            timeout: PROBE_TIMEOUT,
+            locked_at: now,
+            last_heartbeat: now,
+            metadata: BTreeMap::new(),
+            tags: Vec::new(),
+            max_hold_seconds: None,
+            grace_seconds: 0,
+            preemption: None,
+            version: 1,
+            reason: None,
+            client: None,
+            due_at: None,
+        };
+        let lock_key = probe.get_lock_key();
+
+        // 上次自检释放失败时探测锁可能仍在，先按持有人清理
+        storage.release_owned(&lock_key, PROBE_USER).await?;
+
+        if !timed(latency_ms, "acquire", storage.try_acquire(probe.clone())).await?.acquired {
+            return Err(anyhow!("probe lock {} is held by someone else", lock_key));
+        }
+        let stored = timed(latency_ms, "get", storage.get_lock(&lock_key)).await?;
+        if stored.map(|lock| lock.lock_id) != Some(probe.lock_id.clone()) {
+            return Err(anyhow!("probe lock {} was not found after acquire", lock_key));
+        }
+        if timed(latency_ms, "release", storage.release(&probe.lock_id)).await?.is_none() {
+            return Err(anyhow!("probe lock {} was not found on release", lock_key));
+        }
+        Ok(())
+    }
+}
+
+async fn timed<T>(
+    latency_ms: &mut BTreeMap<&'static str, f64>,
+    step: &'static str,
+    future: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    let started = Instant::now();
+    let result = future.await;
+    latency_ms.insert(step, elapsed_ms(started));
+    result
+}
+
+fn elapsed_ms(started: Instant) -> f64 {
+    (started.elapsed().as_secs_f64() * 1000.0 * 1000.0).round() / 1000.0
+}
+
+/// 按断路器状态得出存储状态：ready、degraded 或 unavailable
+pub fn storage_status(health: Option<&StorageHealth>, shards: Option<&[ShardHealth]>) -> &'static str {
+    match (health, shards) {
+        (Some(health), _) if health.degraded => "degraded",
+        (Some(health), _) if health.circuit_open => "unavailable",
+        // 分片模式下全部分片不可用才视为不可用，部分分片不可用或降级时为 degraded
+        (_, Some(shards)) if shards.iter().all(|shard| shard.health.circuit_open && !shard.health.degraded) => {
+            "unavailable"
+        }
+        (_, Some(shards)) if shards.iter().any(|shard| shard.health.circuit_open) => "degraded",
+        _ => "ready",
+    }
+}
+
+pub async fn readyz(
+    failover: Option<web::Data<Arc<FailoverStorage>>>,
+    sharded: Option<web::Data<Arc<ShardedStorage>>>,
+    self_test: web::Data<Arc<SelfTest>>,
+    handoff: Option<web::Data<Arc<Handoff>>>,
+    query: web::Query<ReadyzQuery>,
+    req: HttpRequest,
+) -> HttpResponse {
+    let health = failover.map(|failover| failover.health());
+    let shards = sharded.map(|sharded| sharded.health());
+    let mut status = storage_status(health.as_ref(), shards.as_deref());
+
+    let mut body = json!({ "status": status });
+    if let Some(health) = health {
+        body["storage"] = json!(health);
+    }
+    if let Some(shards) = shards {
+        body["shards"] = json!(shards);
+    }
+    if self_test.requested(query.deep) {
+        let results = self_test.run().await;
+        if results.values().any(|result| !result.ok) {
+            status = "unavailable";
+        }
+        body["self_test"] = json!(results);
+    }
+    if let Some(handoff) = handoff.filter(|handoff| handoff.fenced()) {
+        status = "fenced";
+        body["handoff"] = json!(handoff.status());
+    }
+    body["status"] = json!(status);
+
+    let status = if status == "unavailable" || status == "fenced" {
+        StatusCode::SERVICE_UNAVAILABLE
+    } else {
+        StatusCode::OK
+    };
+    codec::respond(&req, status, &body)
+}
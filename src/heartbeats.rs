@@ -0,0 +1,132 @@
+//! 建议的心跳间隔和过于频繁的心跳统计
+//!
+//! 申请和心跳成功时返回 `heartbeat_interval_ms`：超时时间的 `LOCK_HEARTBEAT_INTERVAL_PERCENT`%（默认 33%），
+//! 随机浮动 ±`LOCK_HEARTBEAT_INTERVAL_JITTER_PERCENT`%（默认 10%），同时获取的大量锁不会在同一时刻心跳。
+//! 超时时间已按命名空间策略的默认值和上限确定，永久锁不返回。客户端按建议间隔心跳时，调整配置即可降低全部客户端的心跳量。
+//!
+//! 本实例记录每把锁最近一次获取或心跳的时间，间隔不足建议间隔（不含浮动）四分之一的心跳计为过于频繁，
+//! 按命名空间累计，通过 `/metrics` 查看；每把锁第一次过于频繁时记录日志，便于找到对应的客户端。
+//! 同一把锁的心跳分散到多个实例时只比较到达同一实例的相邻心跳，统计数量偏少。
+
+use crate::config::Config;
+use crate::models::LockInfo;
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use log::info;
+use rand::Rng;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+/// 间隔不足建议间隔的该分之一的心跳计为过于频繁
+const TOO_FREQUENT_DIVISOR: u64 = 4;
+
+/// 锁最近一次获取或心跳
+struct Beat {
+    timeout: u64,
+    at: Instant,
+    reported: bool, // 已记录过于频繁的日志
+}
+
+/// 命名空间的心跳数量
+#[derive(Default, Clone, Copy)]
+pub struct HeartbeatCounts {
+    pub heartbeats: u64,
+    pub too_frequent: u64,
+}
+
+pub struct HeartbeatAdvisor {
+    percent: u64,
+    jitter_percent: u64,
+    beats: DashMap<String, Beat>,            // lock_id -> 最近一次获取或心跳
+    counts: DashMap<String, HeartbeatCounts>, // namespace -> 累计数量
+}
+
+impl HeartbeatAdvisor {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            percent: config.lock_heartbeat_interval_percent,
+            jitter_percent: config.lock_heartbeat_interval_jitter_percent,
+            beats: DashMap::new(),
+            counts: DashMap::new(),
+        }
+    }
+
+    /// 不含浮动的建议间隔（毫秒），永久锁为 None
+    fn base_interval_ms(&self, timeout: u64) -> Option<u64> {
+        (timeout > 0).then(|| timeout.saturating_mul(10 * self.percent))
+    }
+
+    /// 超时时间为 timeout 的锁的建议心跳间隔（毫秒），永久锁为 None
+    pub fn recommend(&self, timeout: u64) -> Option<u64> {
+        let base = self.base_interval_ms(timeout)?;
+        let jitter = base * self.jitter_percent / 100;
+        Some((base - jitter + rand::thread_rng().gen_range(0..=2 * jitter)).max(1))
+    }
+
+    /// 锁已获取（或重入），记录为最近一次活动，返回建议间隔
+    pub fn acquired(&self, lock_info: &LockInfo) -> Option<u64> {
+        if lock_info.timeout > 0 {
+            self.beats
+                .entry(lock_info.lock_id.clone())
+                .and_modify(|beat| beat.at = Instant::now())
+                .or_insert_with(|| Beat {
+                    timeout: lock_info.timeout,
+                    at: Instant::now(),
+                    reported: false,
+                });
+        }
+        self.recommend(lock_info.timeout)
+    }
+
+    /// 心跳成功，与上一次活动比较是否过于频繁，返回建议间隔
+    pub fn heartbeat(&self, lock_info: &LockInfo) -> Option<u64> {
+        let base = self.base_interval_ms(lock_info.timeout)?;
+        let now = Instant::now();
+        // 本实例未见过上一次活动时不判断
+        let too_frequent = match self.beats.entry(lock_info.lock_id.clone()) {
+            Entry::Occupied(mut entry) => {
+                let beat = entry.get_mut();
+                let elapsed = now.duration_since(beat.at);
+                beat.at = now;
+                beat.timeout = lock_info.timeout;
+                (elapsed < Duration::from_millis(base / TOO_FREQUENT_DIVISOR))
+                    .then(|| (elapsed, !std::mem::replace(&mut beat.reported, true)))
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(Beat {
+                    timeout: lock_info.timeout,
+                    at: now,
+                    reported: false,
+                });
+                None
+            }
+        };
+        let mut counts = self.counts.entry(lock_info.namespace.clone()).or_default();
+        counts.heartbeats += 1;
+        if let Some((elapsed, first)) = too_frequent {
+            counts.too_frequent += 1;
+            if first {
+                info!(
+                    "[HEARTBEAT] Lock heartbeating more often than needed - lock_id: {}, namespace: {}, user_id: {}, interval: {}ms, recommended: {}ms",
+                    lock_info.lock_id, lock_info.namespace, lock_info.user_id, elapsed.as_millis(), base
+                );
+            }
+        }
+        self.recommend(lock_info.timeout)
+    }
+
+    /// 移出超过两倍超时时间（至少一分钟）没有活动的锁，即已释放或已过期的锁
+    pub fn prune(&self) {
+        self.beats.retain(|_, beat| {
+            beat.at.elapsed() < Duration::from_secs(beat.timeout.saturating_mul(2).max(60))
+        });
+    }
+
+    /// 各命名空间的累计数量
+    pub fn counts(&self) -> BTreeMap<String, HeartbeatCounts> {
+        self.counts
+            .iter()
+            .map(|entry| (entry.key().clone(), *entry.value()))
+            .collect()
+    }
+}
@@ -0,0 +1,49 @@
+//! 层级路径锁
+//!
+//! 命名空间策略开启 `hierarchical` 后，business_id 视为以 `/` 分隔的路径（如 `project/42/doc/7`）。
+//! 锁定某个路径相当于在其所有祖先路径上持有意向锁：路径的祖先或后代被他人锁定时申请失败，
+//! 同一用户在父子路径上持有的锁互不冲突。冲突检查与获取锁不是原子操作，并发申请父子路径时以存储为准各自成功。
+
+use crate::models::LockInfo;
+use crate::storage::LockStorage;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+
+/// 路径分隔符
+pub const SEPARATOR: char = '/';
+
+/// 路径由非空的段组成，不以分隔符开头或结尾
+pub fn is_valid_path(path: &str) -> bool {
+    path.split(SEPARATOR).all(|segment| !segment.is_empty())
+}
+
+/// 由近到远列出路径的所有祖先，`a/b/c` 返回 `a/b`、`a`
+pub fn ancestors(path: &str) -> Vec<&str> {
+    path.match_indices(SEPARATOR)
+        .rev()
+        .map(|(index, _)| &path[..index])
+        .collect()
+}
+
+/// 查找与 lock_info 的路径冲突的锁：同一命名空间内祖先或后代路径上由他人持有的截至 now 未过期的锁
+pub async fn find_conflict(
+    storage: &dyn LockStorage,
+    lock_info: &LockInfo,
+    now: DateTime<Utc>,
+) -> Result<Option<LockInfo>> {
+    let conflicts = |other: &LockInfo| other.user_id != lock_info.user_id && !other.is_expired_at(now);
+
+    for ancestor in ancestors(&lock_info.business_id) {
+        let lock_key = format!("{}:{}", lock_info.namespace, ancestor);
+        if let Some(other) = storage.get_lock(&lock_key).await?.filter(|other| conflicts(other)) {
+            return Ok(Some(other));
+        }
+    }
+
+    let key_prefix = format!("{}{}", lock_info.get_lock_key(), SEPARATOR);
+    Ok(storage
+        .list_prefix(&key_prefix)
+        .await?
+        .into_iter()
+        .find(|other| other.namespace == lock_info.namespace && conflicts(other)))
+}
@@ -0,0 +1,44 @@
+use crate::history::{HistoryRecord, HistoryStore};
+use anyhow::Result;
+use async_trait::async_trait;
+use dashmap::DashMap;
+use std::collections::VecDeque;
+
+/// 本地内存环形缓冲区，每个锁最多保留 capacity 条记录，重启后丢失
+pub struct MemoryHistoryStore {
+    capacity: usize,
+    records: DashMap<String, VecDeque<HistoryRecord>>, // lock_key -> 记录
+}
+
+impl MemoryHistoryStore {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            records: DashMap::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl HistoryStore for MemoryHistoryStore {
+    fn name(&self) -> &str {
+        "memory"
+    }
+
+    async fn append(&self, lock_key: &str, record: HistoryRecord) -> Result<()> {
+        let mut records = self.records.entry(lock_key.to_string()).or_default();
+        if records.len() >= self.capacity {
+            records.pop_front();
+        }
+        records.push_back(record);
+        Ok(())
+    }
+
+    async fn records(&self, lock_key: &str) -> Result<Vec<HistoryRecord>> {
+        Ok(self
+            .records
+            .get(lock_key)
+            .map(|records| records.iter().cloned().collect())
+            .unwrap_or_default())
+    }
+}
@@ -0,0 +1,197 @@
+//! 锁历史
+//!
+//! 按 lock_key 保留最近 `HISTORY_SIZE` 个持有人的获取、释放时间和释放原因，通过 `GET /api/lock/history` 查询。
+//! 历史由订阅锁事件的 `LockHistory` 写入，存储可选本地内存环形缓冲区、Redis Stream 或 PostgreSQL 表。
+//! 配置了锁信息字段加密时，`user_name` 加密后写入，查询时解密。
+
+pub mod memory;
+pub mod postgres;
+pub mod redis;
+
+use crate::config::{Config, HistoryStoreType};
+use crate::events::{EventSink, LockEvent, LockEventType};
+use crate::models::{ClientContext, HistoryEntry, ReleaseReason};
+use crate::storage::fieldcrypt::FieldCipher;
+use crate::storage::redis::RedisStorage;
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// 一条历史记录，对应一次获取或释放
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryRecord {
+    pub lock_id: String,
+    pub user_id: String,
+    pub user_name: String,
+    pub locked_at: DateTime<Utc>,
+    /// 事件时间
+    pub at: DateTime<Utc>,
+    /// 释放原因，为空表示获取
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub released: Option<ReleaseReason>,
+    /// 申请锁的客户端网络信息
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client: Option<ClientContext>,
+}
+
+impl HistoryRecord {
+    fn from_event(event: &LockEvent) -> Option<Self> {
+        let released = match event.event {
+            LockEventType::Acquired | LockEventType::ReservationGranted => None,
+            LockEventType::Released => Some(ReleaseReason::Explicit),
+            LockEventType::Expired => Some(ReleaseReason::Expired),
+            LockEventType::ForceReleased => Some(ReleaseReason::Forced),
+            LockEventType::Preempted => Some(ReleaseReason::Preempted),
+            LockEventType::TakeoverApproved => Some(ReleaseReason::TakenOver),
+            _ => return None,
+        };
+        Some(Self {
+            lock_id: event.lock.lock_id.clone(),
+            user_id: event.lock.user_id.clone(),
+            user_name: event.lock.user_name.clone(),
+            locked_at: event.lock.locked_at,
+            at: event.timestamp,
+            released,
+            client: event.lock.client.clone(),
+        })
+    }
+}
+
+/// 历史记录的存储
+#[async_trait]
+pub trait HistoryStore: Send + Sync {
+    /// 存储名称（用于日志）
+    fn name(&self) -> &str;
+
+    /// 追加一条记录，超出容量时丢弃该锁最早的记录
+    async fn append(&self, lock_key: &str, record: HistoryRecord) -> Result<()>;
+
+    /// 返回锁保留的全部记录
+    async fn records(&self, lock_key: &str) -> Result<Vec<HistoryRecord>>;
+}
+
+pub struct LockHistory {
+    store: Arc<dyn HistoryStore>,
+    size: usize,
+    cipher: Option<Arc<FieldCipher>>, // 加密 user_name，未配置字段加密时为 None
+}
+
+impl LockHistory {
+    pub fn new(store: Arc<dyn HistoryStore>, size: usize) -> Self {
+        Self { store, size, cipher: None }
+    }
+
+    /// 写入前加密、读取后解密 user_name
+    pub fn with_cipher(mut self, cipher: Option<Arc<FieldCipher>>) -> Self {
+        self.cipher = cipher;
+        self
+    }
+
+    /// 按配置创建锁历史，`HISTORY_STORE=off` 时返回 None。Redis Stream 使用 Redis 存储的连接
+    pub async fn from_config(config: &Config, redis_storage: Option<Arc<RedisStorage>>) -> Result<Option<Self>> {
+        // 每次持有最多对应获取和释放两条记录
+        let capacity = config.history_size.max(1) * 2;
+        let store: Arc<dyn HistoryStore> = match config.history_store {
+            HistoryStoreType::Off => return Ok(None),
+            HistoryStoreType::Memory => Arc::new(memory::MemoryHistoryStore::new(capacity)),
+            HistoryStoreType::Redis => {
+                let redis_storage = redis_storage
+                    .ok_or_else(|| anyhow!("HISTORY_STORE=redis requires STORAGE_TYPE=redis"))?;
+                Arc::new(redis::RedisHistoryStore::new(redis_storage, capacity))
+            }
+            HistoryStoreType::Postgres => {
+                let url = config
+                    .history_postgres_url
+                    .as_deref()
+                    .ok_or_else(|| anyhow!("HISTORY_STORE=postgres requires HISTORY_POSTGRES_URL"))?;
+                Arc::new(
+                    postgres::PostgresHistoryStore::connect(url, capacity)
+                        .await
+                        .context("Failed to connect to PostgreSQL")?,
+                )
+            }
+        };
+        log::info!("Recording last {} holders per lock in {} history store", config.history_size, store.name());
+        Ok(Some(Self::new(store, config.history_size)))
+    }
+
+    /// 锁最近的持有记录，按获取时间从新到旧排列
+    pub async fn history(&self, lock_key: &str) -> Result<Vec<HistoryEntry>> {
+        let mut records = self.store.records(lock_key).await?;
+        if let Some(cipher) = &self.cipher {
+            for record in records.iter_mut() {
+                // 加密密钥已移除的记录保留密文
+                match cipher.decrypt("user_name", &record.user_name) {
+                    Ok(user_name) => record.user_name = user_name,
+                    Err(e) => log::warn!("Failed to decrypt history of lock {}: {}", record.lock_id, e),
+                }
+            }
+        }
+        Ok(fold(records, self.size))
+    }
+}
+
+/// 将获取和释放记录合并为持有记录。事件异步写入，记录按事件时间排序后按 lock_id 合并
+fn fold(mut records: Vec<HistoryRecord>, size: usize) -> Vec<HistoryEntry> {
+    records.sort_by_key(|record| record.at);
+    let mut entries: Vec<HistoryEntry> = Vec::new();
+    for record in records {
+        match entries.iter_mut().find(|entry| entry.lock_id == record.lock_id) {
+            Some(entry) => {
+                if entry.release_reason.is_none() && record.released.is_some() {
+                    entry.released_at = Some(record.at);
+                    entry.release_reason = record.released;
+                }
+                if entry.client.is_none() {
+                    entry.client = record.client;
+                }
+            }
+            None => entries.push(HistoryEntry {
+                released_at: record.released.map(|_| record.at),
+                release_reason: record.released,
+                lock_id: record.lock_id,
+                user_id: record.user_id,
+                user_name: record.user_name,
+                acquired_at: record.locked_at,
+                client: record.client,
+            }),
+        }
+    }
+
+    entries.sort_by_key(|entry| entry.acquired_at);
+    // 之后已有新的持有人但没有释放记录（如 Redis 键到期自动删除），视为过期
+    let superseded = entries.len().saturating_sub(1);
+    for entry in entries.iter_mut().take(superseded) {
+        if entry.release_reason.is_none() {
+            entry.release_reason = Some(ReleaseReason::Expired);
+        }
+    }
+    entries.reverse();
+    entries.truncate(size);
+    entries
+}
+
+#[async_trait]
+impl EventSink for LockHistory {
+    fn name(&self) -> &str {
+        "history"
+    }
+
+    fn reliable(&self) -> bool {
+        true
+    }
+
+    async fn publish(&self, event: &LockEvent) -> Result<()> {
+        match HistoryRecord::from_event(event) {
+            Some(mut record) => {
+                if let Some(cipher) = &self.cipher {
+                    record.user_name = cipher.encrypt("user_name", &record.user_name)?;
+                }
+                self.store.append(&event.lock.get_lock_key(), record).await
+            }
+            None => Ok(()),
+        }
+    }
+}
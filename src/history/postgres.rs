@@ -0,0 +1,76 @@
+use crate::history::{HistoryRecord, HistoryStore};
+use anyhow::Result;
+use async_trait::async_trait;
+use tokio_postgres::{Client, NoTls};
+
+/// PostgreSQL 表 `lock_history`，启动时自动建表，每次写入后删除该锁超出容量的记录
+pub struct PostgresHistoryStore {
+    client: Client,
+    capacity: i64,
+}
+
+impl PostgresHistoryStore {
+    pub async fn connect(url: &str, capacity: usize) -> Result<Self> {
+        let (client, connection) = tokio_postgres::connect(url, NoTls).await?;
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                log::error!("[HISTORY] PostgreSQL connection closed: {}", e);
+            }
+        });
+        client
+            .batch_execute(
+                "CREATE TABLE IF NOT EXISTS lock_history (
+                    id BIGSERIAL PRIMARY KEY,
+                    lock_key TEXT NOT NULL,
+                    record TEXT NOT NULL
+                );
+                CREATE INDEX IF NOT EXISTS lock_history_lock_key_idx ON lock_history (lock_key, id)",
+            )
+            .await?;
+        Ok(Self {
+            client,
+            capacity: capacity as i64,
+        })
+    }
+}
+
+#[async_trait]
+impl HistoryStore for PostgresHistoryStore {
+    fn name(&self) -> &str {
+        "postgres"
+    }
+
+    async fn append(&self, lock_key: &str, record: HistoryRecord) -> Result<()> {
+        let payload = serde_json::to_string(&record)?;
+        self.client
+            .execute(
+                "INSERT INTO lock_history (lock_key, record) VALUES ($1, $2)",
+                &[&lock_key, &payload],
+            )
+            .await?;
+        self.client
+            .execute(
+                "DELETE FROM lock_history WHERE lock_key = $1 AND id < (
+                    SELECT MIN(id) FROM (
+                        SELECT id FROM lock_history WHERE lock_key = $1 ORDER BY id DESC LIMIT $2
+                    ) AS kept
+                )",
+                &[&lock_key, &self.capacity],
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn records(&self, lock_key: &str) -> Result<Vec<HistoryRecord>> {
+        let rows = self
+            .client
+            .query(
+                "SELECT record FROM lock_history WHERE lock_key = $1 ORDER BY id DESC LIMIT $2",
+                &[&lock_key, &self.capacity],
+            )
+            .await?;
+        rows.iter()
+            .map(|row| Ok(serde_json::from_str(row.get::<_, &str>(0))?))
+            .collect()
+    }
+}
@@ -0,0 +1,38 @@
+use crate::history::{HistoryRecord, HistoryStore};
+use crate::storage::redis::RedisStorage;
+use anyhow::Result;
+use async_trait::async_trait;
+use std::sync::Arc;
+
+/// Redis Stream，键为 `<prefix>history:<lock_key>`，多个实例共享
+pub struct RedisHistoryStore {
+    storage: Arc<RedisStorage>,
+    capacity: usize,
+}
+
+impl RedisHistoryStore {
+    pub fn new(storage: Arc<RedisStorage>, capacity: usize) -> Self {
+        Self { storage, capacity }
+    }
+}
+
+#[async_trait]
+impl HistoryStore for RedisHistoryStore {
+    fn name(&self) -> &str {
+        "redis"
+    }
+
+    async fn append(&self, lock_key: &str, record: HistoryRecord) -> Result<()> {
+        let payload = serde_json::to_string(&record)?;
+        self.storage.append_history(lock_key, &payload, self.capacity).await
+    }
+
+    async fn records(&self, lock_key: &str) -> Result<Vec<HistoryRecord>> {
+        self.storage
+            .history(lock_key, self.capacity)
+            .await?
+            .iter()
+            .map(|payload| Ok(serde_json::from_str(payload)?))
+            .collect()
+    }
+}
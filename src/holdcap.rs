@@ -0,0 +1,169 @@
+//! 命名空间最长持有时间的提醒与统计
+//!
+//! 命名空间策略配置 `max_hold_seconds` 后，通过申请接口获取的锁最多存在该时间，心跳不能延长，由存储按锁的最长持有时间过期。
+//! 后台任务每 `LOCK_HOLD_CAP_CHECK_INTERVAL` 秒扫描一次配置了该限制的命名空间：锁距离上限不足 `hold_warning_seconds`
+//! （默认为上限的十分之一）时发布一次带有 `deadline` 的 `hold_limit_approaching` 事件；锁消失时若持有人仍在心跳
+//! （心跳截止晚于最长持有时间）且已到达上限，计为一次达到上限。各命名空间的累计数量通过管理接口和 `/metrics` 查看，
+//! 用于判断上限是否过紧。锁在两次扫描之间达到上限前释放时可能被计为达到上限，检测间隔越短越准确。
+//! 统计保存在本实例进程内，配置 `LEADER_ELECTION=true` 时只由当选的实例扫描，见 [`crate::leader`]。
+
+use crate::events::{EventBus, LockEvent, LockEventType};
+use crate::models::{HoldCapStats, NamespacePolicy};
+use crate::storage::LockStorage;
+use chrono::{DateTime, Duration, Utc};
+use dashmap::DashMap;
+use log::{error, info, warn};
+use std::collections::{BTreeMap, HashSet};
+
+/// 扫描到的受限制的锁
+struct Tracked {
+    namespace: String,
+    hold_deadline: DateTime<Utc>,
+    capped: bool, // 最近一次扫描时心跳截止不早于最长持有时间，即锁将因上限而过期
+    warned: bool,
+}
+
+/// 命名空间的累计数量
+#[derive(Default, Clone, Copy)]
+struct Counters {
+    locks: u64,
+    warned: u64,
+    reached: u64,
+}
+
+#[derive(Default)]
+pub struct HoldCapMonitor {
+    tracked: DashMap<String, Tracked>,          // lock_id -> 受限制的锁
+    counters: DashMap<String, Counters>,        // namespace -> 累计数量
+    policies: DashMap<String, NamespacePolicy>, // namespace -> 最近一次扫描时配置了上限的策略
+}
+
+impl HoldCapMonitor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 各命名空间的统计，包括当前配置了上限和曾经统计过的命名空间，按命名空间排序
+    pub fn stats(&self) -> Vec<HoldCapStats> {
+        let mut holding: BTreeMap<String, u64> = BTreeMap::new();
+        for entry in self.tracked.iter() {
+            *holding.entry(entry.namespace.clone()).or_default() += 1;
+        }
+        let mut namespaces: Vec<String> = self
+            .counters
+            .iter()
+            .map(|entry| entry.key().clone())
+            .chain(self.policies.iter().map(|entry| entry.key().clone()))
+            .collect();
+        namespaces.sort();
+        namespaces.dedup();
+        namespaces
+            .into_iter()
+            .map(|namespace| {
+                let counters = self.counters.get(&namespace).map(|entry| *entry).unwrap_or_default();
+                let policy = self.policies.get(&namespace);
+                HoldCapStats {
+                    max_hold_seconds: policy.as_ref().and_then(|policy| policy.max_hold_seconds),
+                    hold_warning_seconds: policy.as_ref().and_then(|policy| policy.hold_warning()),
+                    locks: counters.locks,
+                    warned: counters.warned,
+                    reached: counters.reached,
+                    holding: holding.get(&namespace).copied().unwrap_or(0),
+                    namespace,
+                }
+            })
+            .collect()
+    }
+
+    /// 清空正在跟踪的锁，本实例不再运行扫描时调用，累计数量保留
+    pub fn clear(&self) {
+        self.tracked.clear();
+    }
+
+    /// 扫描配置了上限的命名空间，发布到达提醒时间的事件，统计已结束的锁
+    pub async fn scan(&self, storage: &dyn LockStorage, events: &EventBus, now: DateTime<Utc>) {
+        let policies: Vec<NamespacePolicy> = match storage.list_namespaces().await {
+            Ok(policies) => policies
+                .into_iter()
+                .filter(|policy| policy.max_hold_seconds.is_some())
+                .collect(),
+            Err(e) => {
+                error!("[HOLD CAP] Failed to list namespace policies: {}", e);
+                return;
+            }
+        };
+        self.policies.clear();
+
+        let mut seen = HashSet::new();
+        for policy in policies {
+            let locks = match storage.list_prefix(&format!("{}:", policy.name)).await {
+                Ok(locks) => locks,
+                Err(e) => {
+                    error!("[HOLD CAP] Failed to list locks in namespace {}: {}", policy.name, e);
+                    // 读取失败的命名空间保留跟踪的锁，下一轮再判断
+                    seen.extend(
+                        self.tracked
+                            .iter()
+                            .filter(|entry| entry.namespace == policy.name)
+                            .map(|entry| entry.key().clone()),
+                    );
+                    self.policies.insert(policy.name.clone(), policy);
+                    continue;
+                }
+            };
+            let warning = Duration::seconds(policy.hold_warning().unwrap_or(0) as i64);
+            for lock_info in locks {
+                let Some(hold_deadline) = lock_info.hold_deadline() else {
+                    continue;
+                };
+                if lock_info.namespace != policy.name || lock_info.is_expired_at(now) {
+                    continue;
+                }
+                let mut tracked = self.tracked.entry(lock_info.lock_id.clone()).or_insert_with(|| {
+                    self.counters.entry(policy.name.clone()).or_default().locks += 1;
+                    Tracked {
+                        namespace: policy.name.clone(),
+                        hold_deadline,
+                        capped: false,
+                        warned: false,
+                    }
+                });
+                tracked.hold_deadline = hold_deadline;
+                tracked.capped = lock_info.expires_at() == Some(hold_deadline);
+                if !tracked.warned && now >= hold_deadline - warning {
+                    tracked.warned = true;
+                    self.counters.entry(policy.name.clone()).or_default().warned += 1;
+                    info!(
+                        "[HOLD CAP] Lock approaching max hold time - lock_id: {}, namespace: {}, business_id: {}, user_id: {}, deadline: {}",
+                        lock_info.lock_id, lock_info.namespace, lock_info.business_id, lock_info.user_id, hold_deadline
+                    );
+                    events.publish(
+                        LockEvent::new(LockEventType::HoldLimitApproaching, lock_info.clone()).with_deadline(hold_deadline),
+                    );
+                }
+                seen.insert(lock_info.lock_id);
+            }
+            self.policies.insert(policy.name.clone(), policy);
+        }
+
+        // 已释放、已过期或所在命名空间已取消上限的锁
+        let ended: Vec<String> = self
+            .tracked
+            .iter()
+            .filter(|entry| !seen.contains(entry.key()))
+            .map(|entry| entry.key().clone())
+            .collect();
+        for lock_id in ended {
+            let Some((_, tracked)) = self.tracked.remove(&lock_id) else {
+                continue;
+            };
+            if tracked.capped && now >= tracked.hold_deadline {
+                self.counters.entry(tracked.namespace.clone()).or_default().reached += 1;
+                warn!(
+                    "[HOLD CAP] Lock expired at max hold time while still heartbeating - lock_id: {}, namespace: {}, deadline: {}",
+                    lock_id, tracked.namespace, tracked.hold_deadline
+                );
+            }
+        }
+    }
+}
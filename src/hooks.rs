@@ -0,0 +1,116 @@
+//! 锁操作钩子
+//!
+//! 嵌入使用时通过 [`crate::LockService::with_hook`] 注册 [`LockHook`]，在申请锁前执行自定义策略
+//! （如发布冻结期间拒绝申请、为锁补充附加信息），在锁释放或过期后执行自定义处理。钩子按注册顺序调用。
+//!
+//! `before_acquire` 在 v1、v2 申请接口通过参数校验和配额检查后、写入锁前调用，可修改将写入的锁，
+//! 返回 [`HookRejection`] 时拒绝本次申请，错误码和信息原样返回给调用方。预检接口 `/api/lock/can-acquire`、
+//! 预约和抢占调度器代为申请的锁不调用钩子。`after_release` 和 `on_expire` 通过事件总线异步调用，
+//! 不阻塞释放请求，也不能阻止释放。
+
+use crate::events::{EventSink, LockEvent, LockEventType};
+use crate::models::{AcquireLockRequest, LockError, LockInfo};
+use anyhow::Result;
+use async_trait::async_trait;
+use parking_lot::RwLock;
+use std::sync::Arc;
+
+/// 钩子拒绝申请时默认使用的错误码，11000 到 11999 留给钩子使用，对应 HTTP 403
+pub const HOOK_REJECTED: i32 = LockError::HookRejected as i32;
+
+/// 钩子拒绝操作：返回给调用方的错误码和错误信息
+#[derive(Debug, Clone)]
+pub struct HookRejection {
+    pub code: i32,
+    pub message: String,
+}
+
+impl HookRejection {
+    pub fn new(code: i32, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+/// 锁操作钩子，所有方法都有默认的空实现，只需实现关心的方法
+#[async_trait]
+pub trait LockHook: Send + Sync {
+    /// 钩子名称（用于日志）
+    fn name(&self) -> &str;
+
+    /// 申请锁前调用，可修改 lock_info 的 metadata、tags 等附加信息（不应修改锁键和 lock_id），返回错误时拒绝申请；
+    /// 同一用户重入时同样调用，但重入保留已持有的锁，修改不生效
+    async fn before_acquire(&self, _req: &AcquireLockRequest, _lock_info: &mut LockInfo) -> Result<(), HookRejection> {
+        Ok(())
+    }
+
+    /// 锁被持有人释放或被管理员强制释放后调用
+    async fn after_release(&self, _lock_info: &LockInfo) {}
+
+    /// 锁过期被清理后调用
+    async fn on_expire(&self, _lock_info: &LockInfo) {}
+}
+
+/// 已注册的钩子
+#[derive(Default)]
+pub struct LockHooks {
+    hooks: RwLock<Vec<Arc<dyn LockHook>>>,
+}
+
+impl LockHooks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&self, hook: Arc<dyn LockHook>) {
+        log::info!("[HOOKS] Registered lock hook: {}", hook.name());
+        self.hooks.write().push(hook);
+    }
+
+    fn snapshot(&self) -> Vec<Arc<dyn LockHook>> {
+        self.hooks.read().clone()
+    }
+
+    /// 依次调用 before_acquire，第一个拒绝的钩子的结果即为申请结果，错误码为 0 时使用 [`HOOK_REJECTED`]
+    pub async fn before_acquire(&self, req: &AcquireLockRequest, lock_info: &mut LockInfo) -> Result<(), HookRejection> {
+        for hook in self.snapshot() {
+            if let Err(mut rejection) = hook.before_acquire(req, lock_info).await {
+                if rejection.code == 0 {
+                    rejection.code = HOOK_REJECTED;
+                }
+                log::info!(
+                    "[ACQUIRE REJECTED] Rejected by hook {} - namespace: {}, business_id: {}, user_id: {}, code: {}, reason: {}",
+                    hook.name(), req.namespace, req.business_id, req.user_id, rejection.code, rejection.message
+                );
+                return Err(rejection);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl EventSink for LockHooks {
+    fn name(&self) -> &str {
+        "hooks"
+    }
+
+    async fn publish(&self, event: &LockEvent) -> Result<()> {
+        match event.event {
+            LockEventType::Released | LockEventType::ForceReleased => {
+                for hook in self.snapshot() {
+                    hook.after_release(&event.lock).await;
+                }
+            }
+            LockEventType::Expired => {
+                for hook in self.snapshot() {
+                    hook.on_expire(&event.lock).await;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
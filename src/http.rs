@@ -0,0 +1,15 @@
+//! 服务端发起的回调请求
+//!
+//! 接管、卡住的锁、借出逾期、过期提醒和事件路由的回调使用同一个 HTTP 客户端配置：不跟随重定向，
+//! 避免回调地址经重定向转向内网地址。回调地址本身的限制由各功能负责，如过期提醒的允许主机列表。
+
+use std::time::Duration;
+
+/// 回调使用的 HTTP 客户端，请求超过 timeout 时失败
+pub fn client(timeout: Duration) -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(timeout)
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .expect("Failed to build HTTP client")
+}
@@ -0,0 +1,150 @@
+//! 相同请求的合并（singleflight）
+//!
+//! 同一 key 的请求并发到达时（如重复点击、客户端超时重试），只有第一个请求执行，其余请求等待并得到相同的结果。
+//! 执行中的请求被取消（如客户端断开连接）时，等待者各自重新执行。
+
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use std::future::Future;
+use tokio::sync::watch;
+
+pub struct InFlight<T> {
+    calls: DashMap<String, watch::Receiver<Option<T>>>,
+}
+
+impl<T: Clone> Default for InFlight<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Clone> InFlight<T> {
+    pub fn new() -> Self {
+        Self { calls: DashMap::new() }
+    }
+
+    /// 没有相同 key 的请求在执行时执行 call，否则等待其结果；返回结果和是否为合并的结果
+    pub async fn run<F, Fut>(&self, key: String, call: F) -> (T, bool)
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = T>,
+    {
+        let sender = match self.calls.entry(key.clone()) {
+            Entry::Occupied(entry) => {
+                let mut receiver = entry.get().clone();
+                drop(entry);
+                if let Ok(result) = receiver.wait_for(Option::is_some).await {
+                    if let Some(result) = (*result).clone() {
+                        return (result, true);
+                    }
+                }
+                // 执行中的请求被取消，不再合并
+                return (call().await, false);
+            }
+            Entry::Vacant(entry) => {
+                let (sender, receiver) = watch::channel(None);
+                entry.insert(receiver);
+                sender
+            }
+        };
+
+        let guard = CallGuard { calls: &self.calls, key };
+        let result = call().await;
+        let _ = sender.send(Some(result.clone()));
+        drop(guard);
+        (result, false)
+    }
+}
+
+/// 请求结束或被取消时移除 key
+struct CallGuard<'a, T> {
+    calls: &'a DashMap<String, watch::Receiver<Option<T>>>,
+    key: String,
+}
+
+impl<T> Drop for CallGuard<'_, T> {
+    fn drop(&mut self) {
+        self.calls.remove(&self.key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::sync::Notify;
+
+    /// 等待 release 后返回 value 的调用，记录执行次数
+    async fn call(calls: &AtomicUsize, release: &Notify, value: u32) -> u32 {
+        calls.fetch_add(1, Ordering::SeqCst);
+        release.notified().await;
+        value
+    }
+
+    #[tokio::test]
+    async fn concurrent_calls_with_same_key_run_once() {
+        let inflight = InFlight::new();
+        let (calls, release) = (AtomicUsize::new(0), Notify::new());
+
+        let first = inflight.run("k".to_string(), || call(&calls, &release, 1));
+        let second = inflight.run("k".to_string(), || call(&calls, &release, 2));
+        let releaser = async {
+            tokio::task::yield_now().await;
+            release.notify_waiters();
+        };
+        let (first, second, ()) = tokio::join!(first, second, releaser);
+
+        assert_eq!(first, (1, false));
+        assert_eq!(second, (1, true));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(inflight.calls.is_empty());
+    }
+
+    #[tokio::test]
+    async fn different_keys_are_not_coalesced() {
+        let inflight = InFlight::new();
+        let (calls, release) = (AtomicUsize::new(0), Notify::new());
+
+        let first = inflight.run("a".to_string(), || call(&calls, &release, 1));
+        let second = inflight.run("b".to_string(), || call(&calls, &release, 2));
+        let releaser = async {
+            tokio::task::yield_now().await;
+            release.notify_waiters();
+        };
+        let (first, second, ()) = tokio::join!(first, second, releaser);
+
+        assert_eq!((first, second), ((1, false), (2, false)));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn waiter_runs_again_when_leader_is_cancelled() {
+        let inflight = Arc::new(InFlight::new());
+        let started = Arc::new(Notify::new());
+
+        let leader = tokio::spawn({
+            let (inflight, started) = (inflight.clone(), started.clone());
+            async move {
+                inflight
+                    .run("k".to_string(), || async move {
+                        started.notify_one();
+                        std::future::pending::<u32>().await
+                    })
+                    .await
+            }
+        });
+        started.notified().await;
+        let waiter = tokio::spawn({
+            let inflight = inflight.clone();
+            async move { inflight.run("k".to_string(), || async { 2 }).await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!waiter.is_finished());
+
+        leader.abort();
+        assert_eq!(waiter.await.unwrap(), (2, false));
+        assert!(inflight.calls.is_empty());
+    }
+}
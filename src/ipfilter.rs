@@ -0,0 +1,121 @@
+//! 按来源地址限制访问
+//!
+//! 公共接口（`/api` 下除管理接口外的接口）和管理接口（`/api/admin`、`/metrics`）分别配置允许和禁止的地址（IP 或 CIDR）：
+//! 地址属于禁止列表时拒绝；允许列表非空时只接受属于允许列表的地址。被拒绝的请求返回 HTTP 403（错误码 7007），
+//! 不受 `HTTP_STATUS_MODE` 影响，并以 WARN 级别记录审计日志（接口类别、客户端地址、直连地址、方法和路径）。
+//! 客户端地址与锁信息中记录的相同，直连来源属于 `TRUSTED_PROXIES` 时按 `X-Forwarded-For` 识别；
+//! 无法确定地址的请求（Unix 域套接字上没有 `X-Forwarded-For` 的请求）视为来自本机，不受限制。
+//! `/readyz`、Raft 和集群 gossip 等节点间接口不受限制。
+
+use crate::config::Config;
+use crate::lockops::OpError;
+use crate::models::{accepts_problem_json, ApiResponse, LockError};
+use crate::peer;
+use crate::v2;
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{web, Error};
+use ipnet::IpNet;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// 一类接口的允许和禁止列表
+pub struct IpFilter {
+    allow: Vec<IpNet>, // 为空时不限制
+    deny: Vec<IpNet>,
+    rejected: AtomicU64,
+}
+
+impl IpFilter {
+    pub fn new(allow: Vec<IpNet>, deny: Vec<IpNet>) -> Self {
+        Self {
+            allow,
+            deny,
+            rejected: AtomicU64::new(0),
+        }
+    }
+
+    /// 是否配置了任一列表
+    pub fn enabled(&self) -> bool {
+        !self.allow.is_empty() || !self.deny.is_empty()
+    }
+
+    /// 禁止列表优先，允许列表为空时接受其余地址
+    pub fn allows(&self, ip: &IpAddr) -> bool {
+        !self.deny.iter().any(|net| net.contains(ip))
+            && (self.allow.is_empty() || self.allow.iter().any(|net| net.contains(ip)))
+    }
+
+    /// 累计拒绝的请求数
+    pub fn rejected(&self) -> u64 {
+        self.rejected.load(Ordering::Relaxed)
+    }
+}
+
+/// 公共接口和管理接口的地址限制
+pub struct IpFilters {
+    pub public: IpFilter,
+    pub admin: IpFilter,
+}
+
+impl IpFilters {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            public: IpFilter::new(config.public_ip_allowlist.clone(), config.public_ip_denylist.clone()),
+            admin: IpFilter::new(config.admin_ip_allowlist.clone(), config.admin_ip_denylist.clone()),
+        }
+    }
+
+    /// 请求路径所属的接口类别和对应的限制
+    fn for_path(&self, path: &str) -> (&'static str, &IpFilter) {
+        if path.starts_with("/api/admin") || path == "/metrics" {
+            ("admin", &self.admin)
+        } else {
+            ("public", &self.public)
+        }
+    }
+}
+
+/// 拒绝来源地址不被允许的请求
+pub async fn ip_guard(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let filters = req.app_data::<web::Data<Arc<IpFilters>>>().cloned();
+    let config = req.app_data::<web::Data<Config>>().cloned();
+    let (Some(filters), Some(config)) = (filters, config) else {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    };
+    let (surface, filter) = filters.for_path(req.path());
+    if !filter.enabled() {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    }
+    let Some(ip) = peer::client_ip(req.request(), &config.trusted_proxies) else {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    };
+    if filter.allows(&ip) {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    }
+
+    filter.rejected.fetch_add(1, Ordering::Relaxed);
+    log::warn!(
+        "[IP FILTER] Rejected {} request - client: {}, peer: {}, method: {}, path: {}",
+        surface,
+        ip,
+        req.peer_addr().map_or_else(|| "unix".to_string(), |addr| addr.ip().to_string()),
+        req.method(),
+        req.path()
+    );
+    let code = LockError::IpNotAllowed;
+    let error = ApiResponse::<()>::error(code, format!("Address {} is not allowed", ip));
+    let response = if v2::is_v2(req.request()) {
+        v2::error_response(req.request(), OpError::new(error.code, error.message))
+    } else if accepts_problem_json(req.request()) {
+        error.into_problem_response(req.request())
+    } else {
+        error.respond_with_status(req.request(), code.status())
+    };
+    Ok(req.into_response(response))
+}
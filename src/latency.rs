@@ -0,0 +1,249 @@
+//! 请求耗时分解
+//!
+//! `/api` 下每个请求的处理时间按阶段统计，用于判断延迟升高来自存储、排队还是服务本身：
+//! - `queue`：达到 `MAX_INFLIGHT_REQUESTS` 后排队等待空位的时间
+//! - `deserialize`：接收并解析请求体（JSON 或 MessagePack）
+//! - `storage`：存储调用耗时之和，包括 Redis 往返、内存存储的 DashMap 锁竞争、Raft 提交和字段加解密；
+//!   同一请求中并发的存储调用分别计入，可能超过总耗时
+//! - `serialize`：编码响应体
+//! - `other`：总耗时减去以上各阶段，主要是中间件、处理器逻辑和 actix 的调度
+//!
+//! 各阶段按路由模板记录到 `/metrics` 的 `fe_lock_request_stage_seconds` 直方图，总耗时记录到 `fe_lock_request_duration_seconds`。
+//! 配置 `SERVER_TIMING_HEADER=true` 时响应带 `Server-Timing` 头（毫秒），可在浏览器开发者工具或 `curl -i` 中查看单个请求的分解。
+//! 统计截至处理器返回响应，NDJSON 等流式响应在返回后逐块编码，这部分不计入；后台任务中的存储调用不计入任何请求。
+
+use crate::config::Config;
+use crate::models::Histogram;
+use crate::storage::stats::HistogramCounter;
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::middleware::Next;
+use actix_web::{web, Error};
+use dashmap::DashMap;
+use std::cell::Cell;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// 耗时直方图的桶上界（秒）
+pub const LATENCY_BUCKETS: &[f64] = &[
+    0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+const SERVER_TIMING: HeaderName = HeaderName::from_static("server-timing");
+
+/// 计时的处理阶段，`other` 由总耗时推算，不在其中
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Queue,
+    Deserialize,
+    Storage,
+    Serialize,
+}
+
+impl Stage {
+    const ALL: [Stage; 4] = [Stage::Queue, Stage::Deserialize, Stage::Storage, Stage::Serialize];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Stage::Queue => "queue",
+            Stage::Deserialize => "deserialize",
+            Stage::Storage => "storage",
+            Stage::Serialize => "serialize",
+        }
+    }
+}
+
+/// 当前请求各阶段的累计耗时
+#[derive(Default)]
+struct StageTimes {
+    elapsed: [Cell<Duration>; Stage::ALL.len()],
+    storage_calls: Cell<u32>,
+}
+
+tokio::task_local! {
+    static STAGES: StageTimes;
+}
+
+/// 将耗时计入当前请求的阶段，不在请求中（如后台任务）时忽略
+pub fn record(stage: Stage, elapsed: Duration) {
+    let _ = STAGES.try_with(|stages| {
+        let cell = &stages.elapsed[stage as usize];
+        cell.set(cell.get() + elapsed);
+        if stage == Stage::Storage {
+            stages.storage_calls.set(stages.storage_calls.get() + 1);
+        }
+    });
+}
+
+/// 等待 future 完成，并将耗时计入当前请求的阶段
+pub async fn time<T>(stage: Stage, future: impl Future<Output = T>) -> T {
+    let started = Instant::now();
+    let output = future.await;
+    record(stage, started.elapsed());
+    output
+}
+
+/// 执行 f，并将耗时计入当前请求的阶段
+pub fn time_sync<T>(stage: Stage, f: impl FnOnce() -> T) -> T {
+    let started = Instant::now();
+    let output = f();
+    record(stage, started.elapsed());
+    output
+}
+
+/// 一个请求的耗时分解
+#[derive(Debug, Clone)]
+pub struct Breakdown {
+    pub stages: [Duration; Stage::ALL.len()],
+    pub storage_calls: u32,
+    pub total: Duration,
+}
+
+impl Breakdown {
+    /// 总耗时减去各阶段
+    pub fn other(&self) -> Duration {
+        self.total.saturating_sub(self.stages.iter().sum())
+    }
+
+    /// `Server-Timing` 响应头，耗时为零的阶段省略
+    fn server_timing(&self) -> String {
+        let mut entries: Vec<String> = Stage::ALL
+            .iter()
+            .zip(&self.stages)
+            .filter(|(_, elapsed)| !elapsed.is_zero())
+            .map(|(stage, elapsed)| match stage {
+                Stage::Storage => format!(
+                    "storage;dur={:.3};desc=\"{} call{}\"",
+                    millis(*elapsed),
+                    self.storage_calls,
+                    if self.storage_calls == 1 { "" } else { "s" }
+                ),
+                stage => format!("{};dur={:.3}", stage.as_str(), millis(*elapsed)),
+            })
+            .collect();
+        entries.push(format!("other;dur={:.3}", millis(self.other())));
+        entries.push(format!("total;dur={:.3}", millis(self.total)));
+        entries.join(", ")
+    }
+}
+
+fn millis(elapsed: Duration) -> f64 {
+    elapsed.as_secs_f64() * 1000.0
+}
+
+/// 一个路由的耗时直方图
+struct RouteTimings {
+    stages: [HistogramCounter; Stage::ALL.len() + 1], // 各阶段和 other
+    total: HistogramCounter,
+}
+
+impl RouteTimings {
+    fn new() -> Self {
+        Self {
+            stages: std::array::from_fn(|_| HistogramCounter::new(LATENCY_BUCKETS)),
+            total: HistogramCounter::new(LATENCY_BUCKETS),
+        }
+    }
+}
+
+/// 一个路由的耗时直方图快照
+pub struct RouteLatency {
+    pub route: String,
+    pub stages: Vec<(&'static str, Histogram)>,
+    pub total: Histogram,
+}
+
+/// 按路由累计的请求耗时
+pub struct RequestTimings {
+    routes: DashMap<String, RouteTimings>, // 路由模板 -> 直方图
+    server_timing_header: bool,
+}
+
+impl RequestTimings {
+    pub fn new(server_timing_header: bool) -> Self {
+        Self {
+            routes: DashMap::new(),
+            server_timing_header,
+        }
+    }
+
+    pub fn from_config(config: &Config) -> Self {
+        Self::new(config.server_timing_header)
+    }
+
+    /// 记录一个请求的耗时分解
+    pub fn observe(&self, route: &str, breakdown: &Breakdown) {
+        let mut timings = match self.routes.get_mut(route) {
+            Some(timings) => timings,
+            None => self.routes.entry(route.to_string()).or_insert_with(RouteTimings::new),
+        };
+        let other = breakdown.other();
+        for (counter, elapsed) in timings.stages.iter_mut().zip(breakdown.stages.iter().chain([&other])) {
+            counter.observe(elapsed.as_secs_f64());
+        }
+        timings.total.observe(breakdown.total.as_secs_f64());
+    }
+
+    /// 各路由的直方图，按路由排序
+    pub fn snapshot(&self) -> Vec<RouteLatency> {
+        let names = Stage::ALL.iter().map(|stage| stage.as_str()).chain(["other"]);
+        let mut routes: Vec<RouteLatency> = self
+            .routes
+            .iter()
+            .map(|entry| RouteLatency {
+                route: entry.key().clone(),
+                stages: names
+                    .clone()
+                    .zip(&entry.stages)
+                    .map(|(name, counter)| (name, counter.to_histogram()))
+                    .collect(),
+                total: entry.total.to_histogram(),
+            })
+            .collect();
+        routes.sort_by(|a, b| a.route.cmp(&b.route));
+        routes
+    }
+}
+
+/// 为请求建立阶段计时的作用域，响应后按路由记录，配置时添加 `Server-Timing` 响应头
+pub async fn stage_timing(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let Some(timings) = req.app_data::<web::Data<Arc<RequestTimings>>>().cloned() else {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    };
+
+    let started = Instant::now();
+    let (response, stages, storage_calls) = STAGES
+        .scope(StageTimes::default(), async {
+            let response = next.call(req).await;
+            STAGES.with(|times| {
+                (
+                    response,
+                    times.elapsed.each_ref().map(Cell::get),
+                    times.storage_calls.get(),
+                )
+            })
+        })
+        .await;
+    let mut response = response?;
+    let breakdown = Breakdown {
+        stages,
+        storage_calls,
+        total: started.elapsed(),
+    };
+
+    // 未匹配路由的请求不记录，避免任意路径成为标签值
+    if let Some(route) = response.request().match_pattern() {
+        timings.observe(&route, &breakdown);
+    }
+    if timings.server_timing_header {
+        if let Ok(value) = HeaderValue::from_str(&breakdown.server_timing()) {
+            response.headers_mut().insert(SERVER_TIMING, value);
+        }
+    }
+    Ok(response.map_into_boxed_body())
+}
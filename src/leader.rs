@@ -0,0 +1,288 @@
+//! 后台任务的单实例选举
+//!
+//! 多个实例共享 Redis（或自定义的共享存储）时，过期锁清理、Redis 键对账、副本对账、卡住的锁检测、借出逾期检测、最长持有时间的提醒、
+//! 敏感字段的重新加密，以及授予到期的预约和结束超时的会话（两者保存在存储中）在每个实例上都运行会重复扫描存储，
+//! 回调和事件也会重复发送。配置 `LEADER_ELECTION=true` 后，
+//! 实例之间使用锁服务自身的锁选出一个实例运行这些任务：各实例在保留的命名空间 `__leader` 中申请同一把锁，
+//! 持有人为 `CLUSTER_NODE_ID`（Kubernetes 中默认为 Pod 名）加进程启动时生成的随机后缀，多个实例误配相同的节点 ID 时也只有一个当选。
+//! 获得锁的实例每 `LEADER_LEASE / 3` 秒心跳续期，
+//! 锁已不存在或距上次成功续期超过半个租期时放弃，在其他实例可能接替之前留出余量，避免两个实例同时运行这些任务；
+//! 持有人停止心跳后锁在租期结束时过期，由其他实例接替。
+//! 正常停止时主动释放锁，滚动更新时其他实例可立即接替。
+//!
+//! 等待队列、抢占、排空、接管和过期提醒等任务依赖本实例进程内的状态，仍在每个实例上运行。
+//! Redis 故障降级到本地内存存储期间，各实例在各自的内存中选举，可能同时运行这些任务。
+
+use crate::clock::Clock;
+use crate::config::Config;
+use crate::models::LockInfo;
+use crate::storage::LockStorage;
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use parking_lot::Mutex;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// 选举使用的保留命名空间，申请锁等接口不允许使用
+pub const LEADER_NAMESPACE: &str = "__leader";
+/// 选举锁的业务 ID
+const LEADER_BUSINESS_ID: &str = "background-tasks";
+
+/// 本实例持有的选举锁
+struct Leadership {
+    lock_id: String,
+    renewed_at: Instant, // 最近一次成功申请或续期的时间
+}
+
+pub struct LeaderElection {
+    enabled: bool,
+    node_id: String,
+    holder: String, // 选举锁的持有人，每个进程唯一
+    lease: u64,
+    storage: Arc<dyn LockStorage>,
+    leadership: Mutex<Option<Leadership>>,
+    is_leader: AtomicBool,
+}
+
+impl LeaderElection {
+    pub fn new(config: &Config, storage: Arc<dyn LockStorage>) -> Self {
+        Self {
+            enabled: config.leader_election,
+            node_id: config.cluster_node_id.clone(),
+            holder: format!("{}#{}", config.cluster_node_id, Uuid::new_v4().simple()),
+            lease: config.leader_lease.max(3),
+            storage,
+            leadership: Mutex::new(None),
+            is_leader: AtomicBool::new(false),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// 本实例是否应运行单实例任务，未启用选举时始终为 true
+    pub fn is_leader(&self) -> bool {
+        !self.enabled || self.is_leader.load(Ordering::Relaxed)
+    }
+
+    /// 按租期的三分之一参加选举或续期，直到进程退出；选举锁的加锁时间取自 clock
+    pub async fn run(self: Arc<Self>, clock: Arc<dyn Clock>) {
+        info!(
+            "[LEADER] Electing background task leader as {} with a {}s lease",
+            self.node_id, self.lease
+        );
+        let mut interval = tokio::time::interval(Duration::from_secs(self.lease / 3));
+        loop {
+            interval.tick().await;
+            self.campaign(clock.now()).await;
+        }
+    }
+
+    /// 持有锁时续期，否则尝试申请
+    async fn campaign(&self, now: DateTime<Utc>) {
+        let held = self
+            .leadership
+            .lock()
+            .as_ref()
+            .map(|leadership| (leadership.lock_id.clone(), leadership.renewed_at));
+        match held {
+            Some((lock_id, renewed_at)) => self.renew(&lock_id, renewed_at).await,
+            None => self.acquire(now).await,
+        }
+    }
+
+    async fn renew(&self, lock_id: &str, renewed_at: Instant) {
+        match self.storage.update_heartbeat(lock_id).await {
+            Ok(Some(_)) => {
+                if let Some(leadership) = self.leadership.lock().as_mut() {
+                    leadership.renewed_at = Instant::now();
+                }
+            }
+            Ok(None) => self.step_down("leader lock no longer exists"),
+            Err(e) if renewed_at.elapsed() >= self.renew_deadline() => {
+                self.step_down(&format!("failed to renew leader lock for {:?}: {}", self.renew_deadline(), e))
+            }
+            Err(e) => warn!("[LEADER] Failed to renew leader lock: {}", e),
+        }
+    }
+
+    /// 续期持续失败时放弃的时限：半个租期，在锁过期、其他实例接替之前放弃
+    fn renew_deadline(&self) -> Duration {
+        Duration::from_millis(self.lease * 500)
+    }
+
+    async fn acquire(&self, now: DateTime<Utc>) {
+        let lock_info = self.lock_info(now);
+        let lock_key = lock_info.get_lock_key();
+        match self.storage.try_acquire(lock_info).await {
+            Ok(acquired) if acquired.acquired => {
+                // 重新申请本进程仍持有的锁时存储保留原来的 lock_id，以存储中的为准
+                let lock_id = match self.storage.get_lock(&lock_key).await {
+                    Ok(Some(stored)) if stored.user_id == self.holder => stored.lock_id,
+                    Ok(_) => return,
+                    Err(e) => {
+                        warn!("[LEADER] Failed to read back leader lock: {}", e);
+                        return;
+                    }
+                };
+                *self.leadership.lock() = Some(Leadership {
+                    lock_id,
+                    renewed_at: Instant::now(),
+                });
+                self.is_leader.store(true, Ordering::Relaxed);
+                info!("[LEADER] {} is now the background task leader", self.node_id);
+            }
+            Ok(_) => {}
+            Err(e) => warn!("[LEADER] Failed to acquire leader lock: {}", e),
+        }
+    }
+
+    fn step_down(&self, reason: &str) {
+        *self.leadership.lock() = None;
+        self.is_leader.store(false, Ordering::Relaxed);
+        warn!("[LEADER] {} is no longer the background task leader: {}", self.node_id, reason);
+    }
+
+    /// 释放持有的选举锁，服务停止时调用
+    pub async fn resign(&self) {
+        let Some(leadership) = self.leadership.lock().take() else {
+            return;
+        };
+        self.is_leader.store(false, Ordering::Relaxed);
+        match self.storage.release(&leadership.lock_id).await {
+            Ok(_) => info!("[LEADER] {} resigned as background task leader", self.node_id),
+            Err(e) => warn!("[LEADER] Failed to release leader lock, it expires in {}s: {}", self.lease, e),
+        }
+    }
+
+    fn lock_info(&self, now: DateTime<Utc>) -> LockInfo {
+        LockInfo {
+            lock_id: Uuid::new_v4().to_string(),
+            namespace: LEADER_NAMESPACE.to_string(),
+            user_id: self.holder.clone(),
+            user_name: self.node_id.clone(),
+            business_id: LEADER_BUSINESS_ID.to_string(),
+            timeout: self.lease,
+            locked_at: now,
+            last_heartbeat: now,
+            metadata: BTreeMap::new(),
+            tags: Vec::new(),
+            max_hold_seconds: None,
+            grace_seconds: 0,
+            preemption: None,
+            version: 1,
+            reason: None,
+            client: None,
+            due_at: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockStorage;
+
+    fn election(storage: &Arc<MockStorage>) -> LeaderElection {
+        let mut config = Config::from_env();
+        config.leader_election = true;
+        config.leader_lease = 30;
+        // 误配相同的节点 ID 时也只有一个实例当选
+        config.cluster_node_id = "node-a".to_string();
+        LeaderElection::new(&config, storage.clone())
+    }
+
+    #[tokio::test]
+    async fn only_one_instance_is_elected() {
+        let storage = Arc::new(MockStorage::new());
+        let (a, b) = (election(&storage), election(&storage));
+
+        a.campaign(storage.clock().now()).await;
+        b.campaign(storage.clock().now()).await;
+        assert!(a.is_leader());
+        assert!(!b.is_leader());
+
+        // 续期后仍为 leader，锁的持有人不变
+        storage.advance(Duration::from_secs(10));
+        a.campaign(storage.clock().now()).await;
+        b.campaign(storage.clock().now()).await;
+        assert!(a.is_leader());
+        assert!(!b.is_leader());
+        assert_eq!(storage.calls("update_heartbeat"), 1);
+    }
+
+    #[tokio::test]
+    async fn another_instance_takes_over_after_lease_expires() {
+        let storage = Arc::new(MockStorage::new());
+        let (a, b) = (election(&storage), election(&storage));
+        a.campaign(storage.clock().now()).await;
+
+        // a 停止心跳，租期结束后由 b 接替
+        storage.advance(Duration::from_secs(31));
+        b.campaign(storage.clock().now()).await;
+        assert!(b.is_leader());
+
+        a.campaign(storage.clock().now()).await;
+        assert!(!a.is_leader());
+        assert!(b.is_leader());
+    }
+
+    #[tokio::test]
+    async fn transient_renew_failure_keeps_leadership() {
+        let storage = Arc::new(MockStorage::new());
+        let a = election(&storage);
+        a.campaign(storage.clock().now()).await;
+
+        storage.fail_next("update_heartbeat", 1);
+        a.campaign(storage.clock().now()).await;
+        assert!(a.is_leader());
+
+        a.campaign(storage.clock().now()).await;
+        assert!(a.is_leader());
+        assert_eq!(storage.calls("update_heartbeat"), 2);
+    }
+
+    #[tokio::test]
+    async fn steps_down_before_the_lease_can_expire() {
+        let storage = Arc::new(MockStorage::new());
+        let a = election(&storage);
+        a.campaign(storage.clock().now()).await;
+
+        // 上次续期在 14 秒前，未到半个租期
+        a.leadership.lock().as_mut().unwrap().renewed_at = Instant::now() - Duration::from_secs(14);
+        storage.fail_next("update_heartbeat", 1);
+        a.campaign(storage.clock().now()).await;
+        assert!(a.is_leader());
+
+        // 超过半个租期仍未续期成功，在锁过期前放弃
+        a.leadership.lock().as_mut().unwrap().renewed_at = Instant::now() - Duration::from_secs(15);
+        storage.fail_next("update_heartbeat", 1);
+        a.campaign(storage.clock().now()).await;
+        assert!(!a.is_leader());
+    }
+
+    #[tokio::test]
+    async fn resign_hands_over_immediately() {
+        let storage = Arc::new(MockStorage::new());
+        let (a, b) = (election(&storage), election(&storage));
+        a.campaign(storage.clock().now()).await;
+
+        a.resign().await;
+        assert!(!a.is_leader());
+        b.campaign(storage.clock().now()).await;
+        assert!(b.is_leader());
+    }
+
+    #[test]
+    fn disabled_election_always_leads() {
+        let mut config = Config::from_env();
+        config.leader_election = false;
+        let election = LeaderElection::new(&config, Arc::new(MockStorage::new()));
+
+        assert!(election.is_leader());
+    }
+}
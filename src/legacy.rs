@@ -0,0 +1,236 @@
+//! 从旧锁表迁移
+//!
+//! 旧系统以数据库行记录锁（命名空间、业务 ID、持有人），切换到本服务时通过 `POST /api/admin/import/legacy`
+//! 将这些行批量创建为锁，正在编辑的用户不会丢失锁。请求体为 JSON 数组，或 `Content-Type: text/csv` 的 CSV（首行为表头），
+//! 字段见 [`LegacyLockRecord`]，CSV 中的其他列忽略。每行单独校验，校验失败、已被其他用户持有的行跳过并在结果中列出，其余行照常导入。
+//! 创建的锁心跳时间为导入时间，持有人切换到本服务后以相同的 namespace、business_id 和 user_id 申请锁即重入得到 lock_id。
+
+use crate::config::Config;
+use crate::lockid::LockIdGenerator;
+use crate::models::{LegacyImportError, LegacyImportReport, LegacyLockRecord, LockInfo, NamespacePolicy};
+use crate::presence;
+use crate::storage::LockStorage;
+use crate::validation::{ValidationErrors, MAX_BUSINESS_ID_LEN, MAX_ID_LEN, MAX_USER_NAME_LEN};
+use chrono::{DateTime, Utc};
+use std::collections::{BTreeMap, HashMap};
+
+/// 解析结果，解析失败的行为错误信息
+pub type ParsedRecord = Result<LegacyLockRecord, String>;
+
+/// 按 Content-Type 解析请求体，text/csv 按 CSV 解析，其他按 JSON 数组解析；整体格式不正确时返回错误
+pub fn parse(content_type: Option<&str>, body: &[u8]) -> Result<Vec<ParsedRecord>, String> {
+    let is_csv = content_type
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|mime| mime.trim().eq_ignore_ascii_case("text/csv"));
+    if is_csv {
+        let text = std::str::from_utf8(body).map_err(|e| format!("CSV is not valid UTF-8: {}", e))?;
+        parse_csv(text)
+    } else {
+        let rows: Vec<serde_json::Value> =
+            serde_json::from_slice(body).map_err(|e| format!("Body must be a JSON array of records: {}", e))?;
+        Ok(rows
+            .into_iter()
+            .map(|row| serde_json::from_value(row).map_err(|e| e.to_string()))
+            .collect())
+    }
+}
+
+fn parse_csv(text: &str) -> Result<Vec<ParsedRecord>, String> {
+    let mut rows = split_csv(text.trim_start_matches('\u{feff}'))?.into_iter();
+    let header: Vec<String> = rows
+        .next()
+        .ok_or_else(|| "CSV must have a header row".to_string())?
+        .iter()
+        .map(|column| column.trim().to_lowercase())
+        .collect();
+    for required in ["namespace", "business_id", "user_id"] {
+        if !header.iter().any(|column| column == required) {
+            return Err(format!("CSV header must contain column {}", required));
+        }
+    }
+    Ok(rows
+        .map(|fields| {
+            if fields.len() != header.len() {
+                return Err(format!("expected {} fields, found {}", header.len(), fields.len()));
+            }
+            let row: BTreeMap<&str, &str> = header
+                .iter()
+                .map(String::as_str)
+                .zip(fields.iter().map(|field| field.trim()))
+                .filter(|(_, field)| !field.is_empty())
+                .collect();
+            Ok(LegacyLockRecord {
+                namespace: row.get("namespace").unwrap_or(&"").to_string(),
+                business_id: row.get("business_id").unwrap_or(&"").to_string(),
+                user_id: row.get("user_id").unwrap_or(&"").to_string(),
+                user_name: row.get("user_name").map(|value| value.to_string()),
+                timeout: row
+                    .get("timeout")
+                    .map(|value| value.parse().map_err(|_| format!("timeout {} is not a number", value)))
+                    .transpose()?,
+                locked_at: row
+                    .get("locked_at")
+                    .map(|value| {
+                        DateTime::parse_from_rfc3339(value)
+                            .map(|locked_at| locked_at.with_timezone(&Utc))
+                            .map_err(|_| format!("locked_at {} is not an RFC 3339 time", value))
+                    })
+                    .transpose()?,
+            })
+        })
+        .collect())
+}
+
+/// 按 RFC 4180 切分为行和字段，字段可用双引号包围，引号内的 `""` 为一个双引号，空行忽略
+fn split_csv(text: &str) -> Result<Vec<Vec<String>>, String> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' if quoted => quoted = false,
+            '"' if field.trim().is_empty() => {
+                field.clear();
+                quoted = true;
+            }
+            ',' if !quoted => row.push(std::mem::take(&mut field)),
+            '\r' if !quoted && chars.peek() == Some(&'\n') => {}
+            '\n' if !quoted => {
+                row.push(std::mem::take(&mut field));
+                if row.iter().any(|field| !field.trim().is_empty()) {
+                    rows.push(std::mem::take(&mut row));
+                } else {
+                    row.clear();
+                }
+            }
+            c => field.push(c),
+        }
+    }
+    if quoted {
+        return Err("CSV has an unterminated quoted field".to_string());
+    }
+    row.push(field);
+    if row.iter().any(|field| !field.trim().is_empty()) {
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+fn validate(record: &LegacyLockRecord, now: DateTime<Utc>) -> Result<(), String> {
+    let mut errors = ValidationErrors::default();
+    errors.scoped_namespace("namespace", &record.namespace);
+    if presence::is_presence_namespace(&record.namespace) {
+        errors.add("namespace", "is reserved for presence");
+    }
+    errors.required("business_id", &record.business_id, MAX_BUSINESS_ID_LEN);
+    errors.required("user_id", &record.user_id, MAX_ID_LEN);
+    errors.optional("user_name", record.user_name.as_deref(), MAX_USER_NAME_LEN);
+    if record.locked_at.is_some_and(|locked_at| locked_at > now) {
+        errors.add("locked_at", "must not be in the future");
+    }
+    errors.into_result().map_err(|errors| {
+        errors
+            .errors
+            .iter()
+            .map(|error| format!("{} {}", error.field, error.message))
+            .collect::<Vec<_>>()
+            .join("; ")
+    })
+}
+
+/// 将记录创建为锁，结果累计到 report，`report.dry_run` 为 true 时不写入；
+/// 超时时间依次取记录的 timeout、default_timeout、命名空间策略和全局配置，不能超过最大超时时间
+pub async fn import(
+    storage: &dyn LockStorage,
+    records: Vec<ParsedRecord>,
+    default_timeout: Option<u64>,
+    config: &Config,
+    id_generator: &dyn LockIdGenerator,
+    report: &mut LegacyImportReport,
+    now: DateTime<Utc>,
+) -> anyhow::Result<()> {
+    report.total = records.len();
+    let mut policies: HashMap<String, NamespacePolicy> = HashMap::new();
+    let mut rows: HashMap<String, usize> = HashMap::new(); // lock_key -> 第一次出现的行号
+    for (index, record) in records.into_iter().enumerate() {
+        let row = index + 1;
+        let record = match record.and_then(|record| validate(&record, now).map(|()| record)) {
+            Ok(record) => record,
+            Err(message) => {
+                report.invalid.push(LegacyImportError { row, message });
+                continue;
+            }
+        };
+        if !policies.contains_key(&record.namespace) {
+            let policy = storage
+                .get_namespace(&record.namespace)
+                .await?
+                .unwrap_or_else(|| NamespacePolicy::unrestricted(&record.namespace));
+            policies.insert(record.namespace.clone(), policy);
+        }
+        let timeout = match policies[&record.namespace].resolve_timeout(
+            record.timeout.or(default_timeout),
+            config.lock_default_timeout,
+            config.lock_max_timeout,
+        ) {
+            Ok(timeout) => timeout,
+            Err(message) => {
+                report.invalid.push(LegacyImportError { row, message });
+                continue;
+            }
+        };
+        let lock_key = format!("{}:{}", record.namespace, record.business_id);
+        if let Some(first) = rows.get(&lock_key) {
+            report.invalid.push(LegacyImportError {
+                row,
+                message: format!("duplicate of row {}", first),
+            });
+            continue;
+        }
+        rows.insert(lock_key.clone(), row);
+
+        match storage.get_lock(&lock_key).await? {
+            Some(existing_lock) if !existing_lock.is_expired_at(now) && existing_lock.user_id == record.user_id => {
+                report.unchanged += 1;
+                continue;
+            }
+            Some(existing_lock) if !existing_lock.is_expired_at(now) => {
+                report.conflicts.push(lock_key);
+                continue;
+            }
+            _ => {}
+        }
+        let lock_info = LockInfo {
+            lock_id: id_generator.next_id(),
+            namespace: record.namespace,
+            user_name: record.user_name.unwrap_or_else(|| record.user_id.clone()),
+            user_id: record.user_id,
+            business_id: record.business_id,
+            timeout,
+            locked_at: record.locked_at.unwrap_or(now),
+            last_heartbeat: now,
+            metadata: BTreeMap::new(),
+            tags: Vec::new(),
+            max_hold_seconds: None,
+            grace_seconds: config.heartbeat_grace(timeout),
+            preemption: None,
+            version: 1,
+            reason: None,
+            client: None,
+            due_at: None,
+        };
+        if report.dry_run || storage.restore(lock_info.clone()).await? {
+            report.imported += 1;
+            report.locks.push(lock_info);
+        } else {
+            report.conflicts.push(lock_key);
+        }
+    }
+    Ok(())
+}
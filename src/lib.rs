@@ -0,0 +1,67 @@
+//! fe-lock-service 分布式锁服务
+//!
+//! 除独立运行外，也可作为库嵌入其他 Rust 服务：`LockService` 按配置创建存储并提供 actix 路由，
+//! `LockStorage` 及 `storage` 下的内存、Redis、Raft 实现可单独使用。
+
+pub mod admin;
+pub mod advisory;
+pub mod auth;
+pub mod backpressure;
+pub mod chaos;
+pub mod checkout;
+pub mod clock;
+pub mod cluster;
+pub mod codec;
+pub mod config;
+pub mod contention;
+pub mod drain;
+pub mod events;
+pub mod expiry;
+pub mod handlers;
+pub mod handoff;
+pub mod health;
+pub mod hierarchy;
+pub mod hooks;
+pub mod history;
+pub mod heartbeats;
+pub mod holdcap;
+pub mod http;
+pub mod inflight;
+pub mod ipfilter;
+pub mod latency;
+pub mod leader;
+pub mod legacy;
+pub mod lockid;
+pub mod lockops;
+pub mod metrics;
+pub mod migrate;
+pub mod models;
+pub mod notices;
+pub mod notify;
+pub mod oidc;
+pub mod peer;
+pub mod preemption;
+pub mod presence;
+pub mod queue;
+pub mod recovery;
+pub mod reservation;
+pub mod server;
+pub mod service;
+pub mod session;
+pub mod sessions;
+pub mod storage;
+pub mod stuck;
+pub mod takeover;
+pub mod tenant;
+pub mod testing;
+pub mod timeout;
+pub mod timeseries;
+pub mod tombstone;
+pub mod uniqueness;
+pub mod usage;
+pub mod v2;
+pub mod validation;
+
+pub use config::Config;
+pub use service::LockService;
+pub use storage::LockStorage;
@@ -0,0 +1,107 @@
+//! 锁 ID 生成
+//!
+//! 通过 `LOCK_ID_SCHEME` 选择生成方式：`uuid_v4`（默认，随机）、`uuid_v7`（按时间有序）或 `snowflake`
+//! （毫秒时间戳 + 节点号 + 序号的 64 位整数）。集群部署时每个节点配置不同的 `LOCK_ID_NODE`，
+//! 雪花 ID 即可在不协调的情况下保证全局唯一；Raft 模式未配置时使用 `RAFT_NODE_ID`。
+
+use crate::config::{Config, LockIdScheme, StorageType};
+use anyhow::{bail, Result};
+use parking_lot::Mutex;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// 雪花 ID 的时间戳起点（2024-01-01T00:00:00Z，毫秒）
+const SNOWFLAKE_EPOCH_MS: i64 = 1_704_067_200_000;
+/// 节点号位数
+const NODE_BITS: u32 = 10;
+/// 毫秒内序号位数
+const SEQUENCE_BITS: u32 = 12;
+/// 最大节点号
+pub const MAX_NODE_ID: u16 = (1 << NODE_BITS) - 1;
+
+/// 锁 ID 生成器，同一服务内生成的 ID 必须唯一
+pub trait LockIdGenerator: Send + Sync {
+    fn next_id(&self) -> String;
+}
+
+/// 随机 UUID（v4）
+pub struct UuidV4Generator;
+
+impl LockIdGenerator for UuidV4Generator {
+    fn next_id(&self) -> String {
+        Uuid::new_v4().to_string()
+    }
+}
+
+/// 按时间有序的 UUID（v7），字典序即生成顺序
+pub struct UuidV7Generator;
+
+impl LockIdGenerator for UuidV7Generator {
+    fn next_id(&self) -> String {
+        Uuid::now_v7().to_string()
+    }
+}
+
+/// 雪花 ID：41 位毫秒时间戳 + 10 位节点号 + 12 位序号
+pub struct SnowflakeGenerator {
+    node_id: u16,
+    state: Mutex<(i64, u64)>, // 最近一次生成的时间戳和序号
+}
+
+impl SnowflakeGenerator {
+    pub fn new(node_id: u16) -> Result<Self> {
+        if node_id > MAX_NODE_ID {
+            bail!("lock id node {} exceeds the maximum of {}", node_id, MAX_NODE_ID);
+        }
+        Ok(Self {
+            node_id,
+            state: Mutex::new((0, 0)),
+        })
+    }
+}
+
+impl LockIdGenerator for SnowflakeGenerator {
+    fn next_id(&self) -> String {
+        let mut state = self.state.lock();
+        let (last, sequence) = *state;
+        let now = chrono::Utc::now().timestamp_millis() - SNOWFLAKE_EPOCH_MS;
+        // 时钟回拨或同一毫秒内序号用尽时沿用（借用）下一毫秒，保证单调递增
+        *state = if now > last {
+            (now, 0)
+        } else if sequence + 1 < (1 << SEQUENCE_BITS) {
+            (last, sequence + 1)
+        } else {
+            (last + 1, 0)
+        };
+        let (timestamp, sequence) = *state;
+        let id = ((timestamp as u64) << (NODE_BITS + SEQUENCE_BITS))
+            | ((self.node_id as u64) << SEQUENCE_BITS)
+            | sequence;
+        id.to_string()
+    }
+}
+
+/// 按配置创建锁 ID 生成器
+pub fn from_config(config: &Config) -> Result<Arc<dyn LockIdGenerator>> {
+    Ok(match config.lock_id_scheme {
+        LockIdScheme::UuidV4 => Arc::new(UuidV4Generator),
+        LockIdScheme::UuidV7 => Arc::new(UuidV7Generator),
+        LockIdScheme::Snowflake => {
+            let node_id = match config.lock_id_node {
+                Some(node_id) => node_id,
+                None if config.storage_type == StorageType::Raft => {
+                    if config.raft_node_id > MAX_NODE_ID as u64 {
+                        bail!(
+                            "RAFT_NODE_ID {} cannot be used as the snowflake node, set LOCK_ID_NODE (0-{})",
+                            config.raft_node_id,
+                            MAX_NODE_ID
+                        );
+                    }
+                    config.raft_node_id as u16
+                }
+                None => 0,
+            };
+            Arc::new(SnowflakeGenerator::new(node_id)?)
+        }
+    })
+}
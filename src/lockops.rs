@@ -0,0 +1,1640 @@
+//! 锁操作服务层
+//!
+//! 申请、心跳、释放和状态查询的业务逻辑，v1（`/api/lock`）和 v2（`/api/v2/lock`）接口共用。
+//! 操作结果为 [`OpResult`]，两个版本只在序列化时不同：v1 转换为 [`ApiResponse`]，v2 见 [`crate::v2`]。
+
+use crate::advisory;
+use crate::chaos::ChaosInjector;
+use crate::clock::Clock;
+use crate::config::Config;
+use crate::contention::ContentionTracker;
+use crate::drain::DrainScheduler;
+use crate::events::{EventBus, LockEvent, LockEventType};
+use crate::expiry::ExpiryWatcher;
+use crate::heartbeats::HeartbeatAdvisor;
+use crate::hierarchy;
+use crate::hooks::LockHooks;
+use crate::lockid::LockIdGenerator;
+use crate::models::{
+    AcquireCheck, AcquireConflict, AcquireHolder, AcquireLockFailure, AcquireLockRequest, AcquireLockSuccess, AcquireOutcome, AcquireQueued, AdvisoryHolder, ApiResponse, BusinessIdConflict, ClientContext, LockError, DeadlockDetected,
+    GracefulReleaseRequest, GracefulReleaseStarted, HeartbeatRequest, HeartbeatSuccess, LockConditionFailed, LockExpiry, LockInfo, LockStatus, NamespacePolicy, PathConflict,
+    LockVerification, PreemptionNotice, UserQuotaUsage, PreemptionPending, ReleaseLockRequest, SessionInfo, VerifyLockRequest,
+    storage_error_code,
+};
+use crate::inflight::InFlight;
+use crate::notices::PendingNotices;
+use crate::presence;
+use crate::preemption::PreemptionScheduler;
+use crate::queue::WaitQueue;
+use crate::recovery::RecoveryWindow;
+use crate::sessions::SessionRegistry;
+use crate::tenant::{self, Tenant, TenantRegistry};
+use crate::storage::memory::StorageFull;
+use crate::storage::{LockStorage, Takeover};
+use crate::tombstone::TombstoneTracker;
+use crate::uniqueness;
+use chrono::{DateTime, Utc};
+use log::{error, info, warn};
+use serde::Serialize;
+use std::sync::Arc;
+
+pub type OpResult<T> = Result<T, OpError>;
+
+/// 锁操作失败：错误码、错误信息和扩展字段，锁冲突时附带当前持有的锁
+#[derive(Debug, Clone)]
+pub struct OpError {
+    pub code: i32,
+    pub message: String,
+    pub extensions: serde_json::Map<String, serde_json::Value>,
+    pub holder: Option<Box<LockInfo>>,
+}
+
+impl OpError {
+    pub fn new(code: impl Into<i32>, message: String) -> Self {
+        Self {
+            code: code.into(),
+            message,
+            extensions: serde_json::Map::new(),
+            holder: None,
+        }
+    }
+
+    /// 附加错误扩展字段，`extensions` 需序列化为 JSON 对象
+    pub fn with_extensions<E: Serialize>(mut self, extensions: &E) -> Self {
+        if let Ok(serde_json::Value::Object(map)) = serde_json::to_value(extensions) {
+            self.extensions.extend(map);
+        }
+        self
+    }
+
+    /// 附加与申请冲突的锁，v1 响应只输出扩展字段中的持有人信息
+    pub fn with_holder(mut self, holder: &LockInfo) -> Self {
+        self.holder = Some(Box::new(holder.clone()));
+        self
+    }
+}
+
+impl<T> From<OpResult<T>> for ApiResponse<T> {
+    fn from(result: OpResult<T>) -> Self {
+        match result {
+            Ok(data) => ApiResponse::success(data),
+            Err(e) => {
+                let mut response = ApiResponse::error(e.code, e.message);
+                response.extensions = e.extensions;
+                response
+            }
+        }
+    }
+}
+
+/// 锁操作依赖的共享组件
+pub struct LockOps {
+    pub storage: Arc<dyn LockStorage>,
+    pub events: Arc<EventBus>,
+    pub config: Config,
+    pub queue: Arc<WaitQueue>,
+    pub expiry: Arc<ExpiryWatcher>,
+    pub preemption: Arc<PreemptionScheduler>,
+    pub drain: Arc<DrainScheduler>,
+    pub sessions: Arc<SessionRegistry>,
+    pub contention: Arc<ContentionTracker>,
+    pub tombstones: Arc<TombstoneTracker>,
+    pub notices: Arc<PendingNotices>,
+    pub recovery: Arc<RecoveryWindow>,
+    pub id_generator: Arc<dyn LockIdGenerator>,
+    pub clock: Arc<dyn Clock>,
+    pub chaos: Option<Arc<ChaosInjector>>,
+    pub inflight: Arc<InFlight<OpResult<AcquireLockSuccess>>>,
+    pub tenants: Arc<TenantRegistry>,
+    pub hooks: Arc<LockHooks>,
+    pub heartbeats: Arc<HeartbeatAdvisor>,
+}
+
+impl LockOps {
+    /// 申请锁，client 为申请人的网络信息
+    ///
+    /// 请求内容完全相同的申请并发到达时（重复点击、客户端重试）只执行一次，所有请求得到相同的结果；
+    /// 超时时间、元数据等任一参数不同的申请各自执行。抢占和条件获取不合并。
+    pub async fn acquire(&self, req: &AcquireLockRequest, client: ClientContext) -> OpResult<AcquireLockSuccess> {
+        let key = match req.preempt || req.condition().is_some() {
+            true => None,
+            false => serde_json::to_string(req).ok(),
+        };
+        let result = if let Some(key) = key {
+            let (result, coalesced) = self.inflight.run(key, || self.acquire_once(req, client)).await;
+            if coalesced {
+                info!(
+                    "[ACQUIRE COALESCED] Concurrent duplicate acquire - namespace: {}, business_id: {}, user_id: {}",
+                    req.namespace, req.business_id, req.user_id
+                );
+                return result;
+            }
+            result
+        } else {
+            self.acquire_once(req, client).await
+        };
+        if let Ok(success) = &result {
+            if !success.reentrant {
+                self.warn_user_quota(req, &success.lock_id).await;
+            }
+        }
+        result
+    }
+
+    /// 申请人持有的锁从低于提醒数量变为达到时发布 `user_quota_warning` 事件，见 [`crate::usage`]
+    async fn warn_user_quota(&self, req: &AcquireLockRequest, lock_id: &str) {
+        let Some(threshold) = self.config.user_quota_warning() else {
+            return;
+        };
+        if presence::is_presence_namespace(&req.namespace) {
+            return;
+        }
+        let held = match held_by_user(self.storage.as_ref(), &req.user_id, tenant::tenant_of(&req.namespace)).await {
+            Ok(held) => held,
+            Err(e) => {
+                warn!("[USER QUOTA] Failed to count locks held by {}: {}", req.user_id, e);
+                return;
+            }
+        };
+        if held.len() as u64 != threshold {
+            return;
+        }
+        let Some(lock_info) = held.into_iter().find(|held| held.lock_id == lock_id) else {
+            return;
+        };
+        let limit = (self.config.lock_max_per_user > 0).then_some(self.config.lock_max_per_user);
+        warn!(
+            "[USER QUOTA] User approaching lock quota - user_id: {}, held: {}, limit: {:?}, namespace: {}, business_id: {}",
+            req.user_id, threshold, limit, req.namespace, req.business_id
+        );
+        self.events.publish(
+            LockEvent::new(LockEventType::UserQuotaWarning, lock_info).with_quota(UserQuotaUsage {
+                held: threshold,
+                threshold,
+                limit,
+            }),
+        );
+    }
+
+    async fn acquire_once(&self, req: &AcquireLockRequest, client: ClientContext) -> OpResult<AcquireLockSuccess> {
+        let Self {
+            storage,
+            events,
+            config,
+            queue,
+            expiry,
+            drain,
+            sessions,
+            contention,
+            id_generator,
+            clock,
+            ..
+        } = self;
+
+        if req.preempt && !config.lock_preemptors.contains(&req.user_id) {
+            info!(
+                "[ACQUIRE REJECTED] Preemption not allowed - namespace: {}, business_id: {}, user_id: {}",
+                req.namespace, req.business_id, req.user_id
+            );
+            return Err(OpError::new(
+                LockError::PreemptionNotAllowed,
+                format!("User {} is not allowed to preempt locks", req.user_id),
+            ));
+        }
+
+        if let Some(callback_url) = &req.warning_callback_url {
+            if let Err(message) = expiry.check_callback(callback_url) {
+                info!(
+                    "[ACQUIRE REJECTED] Warning callback not allowed - namespace: {}, business_id: {}, user_id: {}, reason: {}",
+                    req.namespace, req.business_id, req.user_id, message
+                );
+                return Err(OpError::new(LockError::InvalidLockRequest, message));
+            }
+        }
+
+        let policy = match storage.get_namespace(&req.namespace).await {
+            Ok(policy) => policy.unwrap_or_else(|| NamespacePolicy::unrestricted(&req.namespace)),
+            Err(e) => {
+                error!("Failed to load namespace policy: {}", e);
+                return Err(OpError::new(
+                    storage_error_code(LockError::PolicyLookupFailed, &e),
+                    format!("Failed to load namespace policy: {}", e),
+                ));
+            }
+        };
+
+        match namespace_frozen(storage.as_ref(), &policy, req, clock.now()).await {
+            Ok(None) => {}
+            Ok(Some(frozen)) => {
+                info!(
+                    "[ACQUIRE REJECTED] Namespace frozen - namespace: {}, business_id: {}, user_id: {}",
+                    req.namespace, req.business_id, req.user_id
+                );
+                return Err(frozen);
+            }
+            Err(e) => {
+                error!("Failed to check namespace freeze: {}", e);
+                return Err(OpError::new(
+                    storage_error_code(LockError::PolicyLookupFailed, &e),
+                    format!("Failed to check namespace freeze: {}", e),
+                ));
+            }
+        }
+
+        let session = match &req.session_id {
+            Some(session_id) => match sessions.get(storage.as_ref(), session_id, &req.user_id, clock.now()).await {
+                Ok(Some(session)) => Some(session),
+                Ok(None) => {
+                    return Err(OpError::new(
+                        LockError::LockSessionNotFound,
+                        format!("Session not found or expired: {}", session_id),
+                    ))
+                }
+                Err(e) => {
+                    error!("Failed to load session {}: {}", session_id, e);
+                    return Err(OpError::new(
+                        storage_error_code(LockError::SessionFailed, &e),
+                        format!("Failed to load session: {}", e),
+                    ));
+                }
+            },
+            None => None,
+        };
+
+        let timeout = if req.permanent {
+            // 永久锁的超时时间记为 0，不受命名空间和全局最大超时限制
+            0
+        } else {
+            match policy.resolve_timeout(
+                session.as_ref().map(|session| session.timeout).or(req.timeout),
+                config.lock_default_timeout,
+                config.lock_max_timeout,
+            ) {
+                Ok(timeout) => timeout,
+                Err(message) => {
+                    info!(
+                        "[ACQUIRE REJECTED] Invalid timeout - namespace: {}, business_id: {}, user_id: {}, reason: {}",
+                        req.namespace, req.business_id, req.user_id, message
+                    );
+                    return Err(OpError::new(LockError::InvalidLockRequest, message));
+                }
+            }
+        };
+
+        info!(
+            "[ACQUIRE] Attempting to acquire lock - namespace: {}, business_id: {}, user_id: {}, user_name: {}, timeout: {}s",
+            req.namespace, req.business_id, req.user_id, req.user_name, timeout
+        );
+
+        let metadata_size = req.metadata_size();
+        if metadata_size > config.lock_metadata_max_bytes {
+            return Err(OpError::new(
+                LockError::InvalidLockRequest,
+                format!(
+                    "metadata size {} bytes exceeds the limit of {} bytes",
+                    metadata_size, config.lock_metadata_max_bytes
+                ),
+            ));
+        }
+
+        let mut lock_info = LockInfo::new(id_generator.next_id(), req, timeout, clock.now());
+        lock_info.grace_seconds = config.heartbeat_grace(timeout);
+        lock_info.max_hold_seconds = policy.cap_hold(req.max_hold_seconds, timeout);
+        lock_info.client = Some(client);
+        let lock_key = lock_info.get_lock_key();
+
+        if let Some(deadline) = drain.deadline(&lock_key, &req.user_id) {
+            info!(
+                "[ACQUIRE REJECTED] Lock draining - namespace: {}, business_id: {}, user_id: {}, deadline: {}",
+                req.namespace, req.business_id, req.user_id, deadline
+            );
+            return Err(OpError::new(
+                LockError::LockDraining,
+                format!("Lock is draining and will be released by {}", deadline),
+            ));
+        }
+
+        let max_locks = policy
+            .max_locks
+            .or((config.lock_max_per_namespace > 0).then_some(config.lock_max_per_namespace));
+        let max_per_tenant = tenant::tenant_of(&req.namespace)
+            .map(|tenant_id| self.tenants.max_locks(tenant_id))
+            .filter(|max_locks| *max_locks > 0);
+        match quota_exceeded(
+            storage.as_ref(),
+            &lock_info,
+            max_locks,
+            config.lock_max_per_user,
+            max_per_tenant,
+            clock.now(),
+        )
+        .await
+        {
+            Ok(None) => {}
+            Ok(Some(QuotaExceeded::Namespace(max_locks))) => {
+                info!(
+                    "[ACQUIRE REJECTED] Namespace lock limit reached - namespace: {}, max_locks: {}, business_id: {}, user_id: {}",
+                    req.namespace, max_locks, req.business_id, req.user_id
+                );
+                return Err(OpError::new(
+                    LockError::NamespaceLimitReached,
+                    format!(
+                        "Namespace {} has reached its limit of {} locks",
+                        req.namespace, max_locks
+                    ),
+                )
+                .with_extensions(&serde_json::json!({ "limit": max_locks })));
+            }
+            Ok(Some(QuotaExceeded::User(max_locks))) => {
+                info!(
+                    "[ACQUIRE REJECTED] User lock quota reached - user_id: {}, max_locks: {}, namespace: {}, business_id: {}",
+                    req.user_id, max_locks, req.namespace, req.business_id
+                );
+                return Err(OpError::new(
+                    LockError::UserQuotaReached,
+                    format!("User {} has reached its quota of {} locks", req.user_id, max_locks),
+                )
+                .with_extensions(&serde_json::json!({ "limit": max_locks })));
+            }
+            Ok(Some(QuotaExceeded::Tenant(max_locks))) => {
+                let tenant_id = tenant::tenant_of(&req.namespace).unwrap_or_default();
+                info!(
+                    "[ACQUIRE REJECTED] Tenant lock quota reached - tenant_id: {}, max_locks: {}, namespace: {}, business_id: {}, user_id: {}",
+                    tenant_id, max_locks, req.namespace, req.business_id, req.user_id
+                );
+                return Err(OpError::new(
+                    LockError::TenantQuotaReached,
+                    format!("Tenant {} has reached its quota of {} locks", tenant_id, max_locks),
+                )
+                .with_extensions(&serde_json::json!({ "limit": max_locks })));
+            }
+            Err(e) => {
+                error!("Failed to check lock quota: {}", e);
+                return Err(OpError::new(
+                    storage_error_code(LockError::PolicyLookupFailed, &e),
+                    format!("Failed to check lock quota: {}", e),
+                ));
+            }
+        }
+
+        if let Err(rejection) = self.hooks.before_acquire(req, &mut lock_info).await {
+            return Err(OpError::new(rejection.code, rejection.message));
+        }
+
+        if policy.advisory {
+            return self.acquire_advisory(req, lock_info, session).await;
+        }
+
+        if policy.hierarchical {
+            if !hierarchy::is_valid_path(&req.business_id) {
+                return Err(OpError::new(
+                    LockError::InvalidLockRequest,
+                    format!(
+                        "business_id must be a path of non-empty segments separated by '{}' in hierarchical namespace {}",
+                        hierarchy::SEPARATOR, req.namespace
+                    ),
+                ));
+            }
+            match hierarchy::find_conflict(storage.as_ref(), &lock_info, clock.now()).await {
+                Ok(None) => {}
+                Ok(Some(conflict)) => {
+                    info!(
+                        "[ACQUIRE FAILED] Path conflict - namespace: {}, business_id: {}, conflicting_path: {}, current_holder: {} (user_id: {}), requested_by: {} (user_id: {})",
+                        req.namespace, req.business_id, conflict.business_id, conflict.user_name,
+                        conflict.user_id, req.user_name, req.user_id
+                    );
+                    return Err(OpError::new(
+                        LockError::PathConflict,
+                        format!(
+                            "Path {} conflicts with {} held by {}",
+                            req.business_id, conflict.business_id, conflict.user_name
+                        ),
+                    )
+                    .with_holder(&conflict)
+                    .with_extensions(&PathConflict {
+                        remaining_seconds: conflict.deadline_secs_at(clock.now()),
+                        business_id: conflict.business_id,
+                        current_holder: conflict.user_name,
+                        locked_at: conflict.locked_at,
+                        reason: conflict.reason,
+                    }));
+                }
+                Err(e) => {
+                    error!("Failed to check path conflicts: {}", e);
+                    return Err(OpError::new(
+                        storage_error_code(LockError::LockLookupFailed, &e),
+                        format!("Failed to check path conflicts: {}", e),
+                    ));
+                }
+            }
+        }
+
+        match uniqueness::find_conflict(storage.as_ref(), &config.lock_unique_business_ids, &lock_info, clock.now()).await {
+            Ok(None) => {}
+            Ok(Some(conflict)) => {
+                info!(
+                    "[ACQUIRE FAILED] Business ID held in another namespace - namespace: {}, business_id: {}, conflicting_namespace: {}, current_holder: {} (user_id: {}), requested_by: {} (user_id: {})",
+                    req.namespace, req.business_id, conflict.namespace, conflict.user_name,
+                    conflict.user_id, req.user_name, req.user_id
+                );
+                return Err(business_id_conflict(req, conflict, clock.now()));
+            }
+            Err(e) => {
+                error!("Failed to check business ID uniqueness: {}", e);
+                return Err(OpError::new(
+                    storage_error_code(LockError::LockLookupFailed, &e),
+                    format!("Failed to check business ID uniqueness: {}", e),
+                ));
+            }
+        }
+
+        // 条件获取只接管满足条件的已过期的锁，不排队
+        if let Some(condition) = req.condition() {
+            return match storage.takeover(lock_info, &condition).await {
+                Ok(Takeover::Acquired { lock_info, replaced }) => {
+                    info!(
+                        "[ACQUIRE SUCCESS] Lock acquired by condition - lock_id: {}, namespace: {}, business_id: {}, user_id: {}, version: {}",
+                        lock_info.lock_id, lock_info.namespace, lock_info.business_id,
+                        lock_info.user_id, lock_info.version
+                    );
+                    queue.remove(&lock_key, &req.user_id);
+                    expiry.watch(&lock_info, req);
+                    if let Some(session) = &session {
+                        attach_to_session(sessions, storage.as_ref(), &session.session_id, &lock_info.lock_id).await;
+                    }
+                    if let Some(replaced) = replaced {
+                        events.publish(LockEvent::new(LockEventType::Expired, *replaced));
+                    }
+                    let mut success = AcquireLockSuccess::new(&lock_info, clock.now());
+                    success.heartbeat_interval_ms = self.heartbeats.acquired(&lock_info);
+                    events.publish(LockEvent::new(LockEventType::Acquired, lock_info));
+                    Ok(success)
+                }
+                Ok(Takeover::Rejected(current)) => {
+                    let now = clock.now();
+                    let message = if !current.is_expired_at(now) {
+                        format!("Lock is held by {} and has not expired", current.user_name)
+                    } else if condition.holder.as_ref().is_some_and(|holder| *holder != current.user_id) {
+                        format!("Lock is held by {}, not {}", current.user_id, req.if_holder_is.as_deref().unwrap_or_default())
+                    } else {
+                        format!("Lock version is {}, not {}", current.version, req.if_version.unwrap_or_default())
+                    };
+                    info!(
+                        "[ACQUIRE FAILED] Lock condition not met - namespace: {}, business_id: {}, user_id: {}, reason: {}",
+                        req.namespace, req.business_id, req.user_id, message
+                    );
+                    Err(OpError::new(LockError::AcquireConditionFailed, message).with_holder(&current).with_extensions(
+                        &LockConditionFailed {
+                            user_id: current.user_id.clone(),
+                            current_holder: current.user_name.clone(),
+                            version: current.version,
+                            remaining_seconds: current.deadline_secs_at(now),
+                        },
+                    ))
+                }
+                Err(e) => {
+                    error!("Failed to acquire lock by condition: {}", e);
+                    Err(acquire_failed(e))
+                }
+            };
+        }
+
+        // 锁空闲时由有效优先级更高的等待者先获取，锁被占用时按正常流程重入或排队
+        if policy.allow_queue {
+            let higher = queue.higher_priority_waiters(&lock_key, &req.user_id, req.priority, clock.now());
+            if higher > 0 {
+                match storage.get_lock(&lock_key).await {
+                    Ok(Some(existing_lock)) if !existing_lock.is_expired_at(clock.now()) => {}
+                    Ok(_) => {
+                        let waiters_ahead = queue.enqueue(&lock_key, &req.user_id, &req.user_name, req.priority, clock.now());
+                        info!(
+                            "[ACQUIRE REJECTED] Lock reserved for higher priority waiters - namespace: {}, business_id: {}, user_id: {}, priority: {}, waiters: {}",
+                            req.namespace, req.business_id, req.user_id, req.priority, higher
+                        );
+                        return Err(OpError::new(
+                            LockError::ReservedForWaiters,
+                            format!("Lock is reserved for {} higher priority waiters", higher),
+                        )
+                        .with_extensions(&serde_json::json!({ "waiters_ahead": waiters_ahead })));
+                    }
+                    Err(e) => {
+                        error!("Failed to get lock info: {}", e);
+                        return Err(OpError::new(
+                            storage_error_code(LockError::LockLookupFailed, &e),
+                            format!("Failed to get lock info: {}", e),
+                        ));
+                    }
+                }
+            }
+        }
+
+        match storage.try_acquire(lock_info.clone()).await {
+            Ok(acquired) => {
+                if let Some(replaced) = acquired.replaced {
+                    events.publish(LockEvent::new(LockEventType::Expired, *replaced));
+                }
+                if acquired.acquired {
+                    queue.remove(&lock_key, &req.user_id);
+                    // 检查是否是重复申请（返回现有锁ID）
+                    match storage.get_lock(&lock_key).await {
+                        Ok(Some(existing_lock)) => {
+                            info!(
+                                "[ACQUIRE SUCCESS] Lock acquired - lock_id: {}, namespace: {}, business_id: {}, user_id: {}, user_name: {}",
+                                existing_lock.lock_id, existing_lock.namespace, existing_lock.business_id, 
+                                existing_lock.user_id, existing_lock.user_name
+                            );
+                            expiry.watch(&existing_lock, req);
+                            if let Some(session) = &session {
+                                attach_to_session(sessions, storage.as_ref(), &session.session_id, &existing_lock.lock_id).await;
+                            }
+                            // 重入时返回的是已有锁，不重复发布事件
+                            if existing_lock.lock_id == lock_info.lock_id {
+                                events.publish(LockEvent::new(
+                                    LockEventType::Acquired,
+                                    existing_lock.clone(),
+                                ));
+                            }
+                            let mut success = AcquireLockSuccess::new(&existing_lock, clock.now());
+                            success.reentrant = existing_lock.lock_id != lock_info.lock_id;
+                            success.heartbeat_interval_ms = self.heartbeats.acquired(&existing_lock);
+                            Ok(success)
+                        }
+                        _ => {
+                            info!(
+                                "[ACQUIRE SUCCESS] Lock acquired - lock_id: {}, namespace: {}, business_id: {}, user_id: {}, user_name: {}",
+                                lock_info.lock_id, lock_info.namespace, lock_info.business_id, 
+                                lock_info.user_id, lock_info.user_name
+                            );
+                            expiry.watch(&lock_info, req);
+                            if let Some(session) = &session {
+                                attach_to_session(sessions, storage.as_ref(), &session.session_id, &lock_info.lock_id).await;
+                            }
+                            let mut success = AcquireLockSuccess::new(&lock_info, clock.now());
+                            success.heartbeat_interval_ms = self.heartbeats.acquired(&lock_info);
+                            events.publish(LockEvent::new(LockEventType::Acquired, lock_info));
+                            Ok(success)
+                        }
+                    }
+                } else {
+                    contention.record_conflict(&lock_key, &req.user_id);
+                    // 获取当前锁的持有人信息
+                    match storage.get_lock(&lock_key).await {
+                        Ok(Some(existing_lock)) => {
+                            info!(
+                                "[ACQUIRE FAILED] Lock already held - namespace: {}, business_id: {}, current_holder: {} (user_id: {}), locked_at: {}, requested_by: {} (user_id: {})",
+                                existing_lock.namespace, existing_lock.business_id, existing_lock.user_name, 
+                                existing_lock.user_id, existing_lock.locked_at, req.user_name, req.user_id
+                            );
+                            if req.preempt {
+                                return self.preempt(req, &existing_lock, lock_info).await;
+                            }
+                            if policy.allow_queue {
+                                if let Some(deadlock) = detect_deadlock(
+                                    storage.as_ref(),
+                                    queue,
+                                    &lock_key,
+                                    &req.user_id,
+                                    &existing_lock,
+                                    clock.now(),
+                                )
+                                .await
+                                {
+                                    return Err(deadlock);
+                                }
+                            }
+                            let message = match &existing_lock.reason {
+                                Some(reason) => format!("Lock already held by {}: {}", existing_lock.user_name, reason),
+                                None => format!("Lock already held by {}", existing_lock.user_name),
+                            };
+                            Err(OpError::new(LockError::LockHeld, message)
+                            .with_holder(&existing_lock)
+                            .with_extensions(&conflict_details(
+                                &existing_lock,
+                                policy
+                                    .allow_queue
+                                    .then(|| queue.enqueue(&lock_key, &req.user_id, &req.user_name, req.priority, clock.now())),
+                                clock.now(),
+                            )))
+                        }
+                        Ok(None) => {
+                            error!("Lock acquisition failed but no lock info found");
+                            Err(OpError::new(
+                                LockError::AcquireFailed,
+                                "Lock acquisition failed".to_string(),
+                            ))
+                        }
+                        Err(e) => {
+                            error!("Failed to get lock info: {}", e);
+                            Err(OpError::new(
+                                storage_error_code(LockError::LockLookupFailed, &e),
+                                format!("Failed to get lock info: {}", e),
+                            ))
+                        }
+                    }
+                }
+            }
+            Err(e) => {
+                error!("Failed to acquire lock: {}", e);
+                Err(acquire_failed(e))
+            }
+        }
+    }
+
+    /// 提示性命名空间中申请锁：每个申请人各自持有一把锁，总是成功并返回所有当前持有人
+    async fn acquire_advisory(
+        &self,
+        req: &AcquireLockRequest,
+        mut lock_info: LockInfo,
+        session: Option<SessionInfo>,
+    ) -> OpResult<AcquireLockSuccess> {
+        let Self {
+            storage,
+            events,
+            expiry,
+            sessions,
+            clock,
+            ..
+        } = self;
+
+        if req.preempt || req.condition().is_some() {
+            return Err(OpError::new(
+                LockError::InvalidLockRequest,
+                format!(
+                    "preempt and conditional acquire are not supported in advisory namespace {}",
+                    req.namespace
+                ),
+            ));
+        }
+        if !advisory::is_valid_business_id(&req.business_id) {
+            return Err(OpError::new(
+                LockError::InvalidLockRequest,
+                format!(
+                    "business_id must not contain '{}' in advisory namespace {}",
+                    advisory::SEPARATOR, req.namespace
+                ),
+            ));
+        }
+
+        lock_info.business_id = advisory::holder_business_id(&req.business_id, &req.user_id);
+        match storage.try_acquire(lock_info.clone()).await {
+            Ok(acquired) if acquired.acquired => {
+                if let Some(replaced) = acquired.replaced {
+                    events.publish(LockEvent::new(LockEventType::Expired, *replaced));
+                }
+            }
+            Ok(_) => {
+                error!("Advisory lock acquisition failed for {}", lock_info.get_lock_key());
+                return Err(OpError::new(LockError::AcquireFailed, "Lock acquisition failed".to_string()));
+            }
+            Err(e) => {
+                error!("Failed to acquire lock: {}", e);
+                return Err(acquire_failed(e));
+            }
+        }
+        // 重复申请时沿用已持有的锁
+        let held = match storage.get_lock(&lock_info.get_lock_key()).await {
+            Ok(Some(existing_lock)) => existing_lock,
+            _ => lock_info.clone(),
+        };
+        expiry.watch(&held, req);
+        if let Some(session) = &session {
+            attach_to_session(sessions, storage.as_ref(), &session.session_id, &held.lock_id).await;
+        }
+        if held.lock_id == lock_info.lock_id {
+            events.publish(LockEvent::new(LockEventType::Acquired, held.clone()));
+        }
+
+        let now = clock.now();
+        let holders = match advisory::holders(storage.as_ref(), &req.namespace, &req.business_id, now).await {
+            Ok(holders) => holders,
+            Err(e) => {
+                error!("Failed to list advisory lock holders: {}", e);
+                vec![held.clone()]
+            }
+        };
+        info!(
+            "[ACQUIRE SUCCESS] Advisory lock acquired - lock_id: {}, namespace: {}, business_id: {}, user_id: {}, holders: {}",
+            held.lock_id, req.namespace, req.business_id, req.user_id, holders.len()
+        );
+        let mut success = AcquireLockSuccess::new(&held, now);
+        success.reentrant = held.lock_id != lock_info.lock_id;
+        success.heartbeat_interval_ms = self.heartbeats.acquired(&held);
+        success.holders = Some(holders.iter().map(AdvisoryHolder::from).collect());
+        Ok(success)
+    }
+
+    /// 申请锁，将获取、重入、冲突和排队区分为不同的结果，其他失败仍返回错误
+    pub async fn acquire_outcome(&self, req: &AcquireLockRequest, client: ClientContext) -> OpResult<AcquireOutcome> {
+        let e = match self.acquire(req, client).await {
+            Ok(success) if success.reentrant => return Ok(AcquireOutcome::Reentrant(success)),
+            Ok(success) => return Ok(AcquireOutcome::Granted(success)),
+            Err(e) => e,
+        };
+        let now = self.clock.now();
+        let holder = e.holder.as_deref().map(|holder| AcquireHolder::new(holder, now));
+        let waiters_ahead = e.extensions.get("waiters_ahead").and_then(|value| value.as_u64());
+        Ok(match (LockError::from_code(e.code), holder, waiters_ahead) {
+            (Some(LockError::LockHeld | LockError::ReservedForWaiters), holder, Some(waiters_ahead)) => {
+                AcquireOutcome::Queued(AcquireQueued {
+                    code: e.code,
+                    message: e.message,
+                    estimated_wait_seconds: e.extensions.get("estimated_wait_seconds").and_then(|value| value.as_u64()),
+                    holder,
+                    waiters_ahead: waiters_ahead as usize,
+                })
+            }
+            (Some(LockError::LockHeld | LockError::PathConflict | LockError::BusinessIdConflict), Some(holder), _) => {
+                AcquireOutcome::Conflict(AcquireConflict {
+                    code: e.code,
+                    message: e.message,
+                    holder,
+                })
+            }
+            _ => return Err(e),
+        })
+    }
+
+    /// 检查申请是否会成功，不获取锁、不进入等待队列也不发布事件
+    ///
+    /// 依次检查与申请相同的条件：抢占权限、命名空间冻结、会话、超时时间、附加信息大小、配额、层级路径冲突、接管条件、
+    /// 更高优先级的等待者和当前持有人。结果只反映检查时的状态，随后的申请仍可能因并发的申请失败。
+    pub async fn check_acquire(&self, req: &AcquireLockRequest) -> OpResult<AcquireCheck> {
+        Ok(match self.check_acquire_once(req).await? {
+            Ok(reentrant) => AcquireCheck {
+                can_acquire: true,
+                reentrant,
+                code: None,
+                message: None,
+                holder: None,
+            },
+            Err(rejected) => AcquireCheck {
+                can_acquire: false,
+                reentrant: false,
+                code: Some(rejected.code),
+                message: Some(rejected.message),
+                holder: rejected.holder.map(|holder| *holder),
+            },
+        })
+    }
+
+    /// 外层错误为存储故障，内层错误为申请会被拒绝的原因，成功时返回是否为重入
+    async fn check_acquire_once(&self, req: &AcquireLockRequest) -> OpResult<Result<bool, OpError>> {
+        let Self {
+            storage,
+            config,
+            queue,
+            sessions,
+            clock,
+            ..
+        } = self;
+        let storage_error = |code: LockError, context: &str, e: anyhow::Error| {
+            error!("{}: {}", context, e);
+            OpError::new(storage_error_code(code, &e), format!("{}: {}", context, e))
+        };
+
+        if req.preempt && !config.lock_preemptors.contains(&req.user_id) {
+            return Ok(Err(OpError::new(
+                LockError::PreemptionNotAllowed,
+                format!("User {} is not allowed to preempt locks", req.user_id),
+            )));
+        }
+        let policy = storage
+            .get_namespace(&req.namespace)
+            .await
+            .map_err(|e| storage_error(LockError::PolicyLookupFailed, "Failed to load namespace policy", e))?
+            .unwrap_or_else(|| NamespacePolicy::unrestricted(&req.namespace));
+        if let Some(frozen) = namespace_frozen(storage.as_ref(), &policy, req, clock.now())
+            .await
+            .map_err(|e| storage_error(LockError::PolicyLookupFailed, "Failed to check namespace freeze", e))?
+        {
+            return Ok(Err(frozen));
+        }
+        let session_timeout = match &req.session_id {
+            Some(session_id) => match sessions
+                .get(storage.as_ref(), session_id, &req.user_id, clock.now())
+                .await
+                .map_err(|e| storage_error(LockError::SessionFailed, "Failed to load session", e))?
+            {
+                Some(session) => Some(session.timeout),
+                None => {
+                    return Ok(Err(OpError::new(
+                        LockError::LockSessionNotFound,
+                        format!("Session not found or expired: {}", session_id),
+                    )))
+                }
+            },
+            None => None,
+        };
+        let timeout = if req.permanent {
+            0
+        } else {
+            match policy.resolve_timeout(
+                session_timeout.or(req.timeout),
+                config.lock_default_timeout,
+                config.lock_max_timeout,
+            ) {
+                Ok(timeout) => timeout,
+                Err(message) => return Ok(Err(OpError::new(LockError::InvalidLockRequest, message))),
+            }
+        };
+        let metadata_size = req.metadata_size();
+        if metadata_size > config.lock_metadata_max_bytes {
+            return Ok(Err(OpError::new(
+                LockError::InvalidLockRequest,
+                format!(
+                    "metadata size {} bytes exceeds the limit of {} bytes",
+                    metadata_size, config.lock_metadata_max_bytes
+                ),
+            )));
+        }
+
+        let now = clock.now();
+        let mut lock_info = LockInfo::new(String::new(), req, timeout, now);
+        if policy.advisory {
+            if req.preempt || req.condition().is_some() || !advisory::is_valid_business_id(&req.business_id) {
+                return Ok(Err(OpError::new(
+                    LockError::InvalidLockRequest,
+                    format!("Invalid advisory lock request in namespace {}", req.namespace),
+                )));
+            }
+            lock_info.business_id = advisory::holder_business_id(&req.business_id, &req.user_id);
+        }
+        let lock_key = lock_info.get_lock_key();
+
+        if let Some(deadline) = self.drain.deadline(&lock_key, &req.user_id) {
+            return Ok(Err(OpError::new(
+                LockError::LockDraining,
+                format!("Lock is draining and will be released by {}", deadline),
+            )));
+        }
+
+        let max_locks = policy
+            .max_locks
+            .or((config.lock_max_per_namespace > 0).then_some(config.lock_max_per_namespace));
+        let max_per_tenant = tenant::tenant_of(&req.namespace)
+            .map(|tenant_id| self.tenants.max_locks(tenant_id))
+            .filter(|max_locks| *max_locks > 0);
+        let exceeded = quota_exceeded(storage.as_ref(), &lock_info, max_locks, config.lock_max_per_user, max_per_tenant, now)
+            .await
+            .map_err(|e| storage_error(LockError::PolicyLookupFailed, "Failed to check lock quota", e))?;
+        match exceeded {
+            None => {}
+            Some(QuotaExceeded::Namespace(max_locks)) => {
+                return Ok(Err(OpError::new(
+                    LockError::NamespaceLimitReached,
+                    format!("Namespace {} has reached its limit of {} locks", req.namespace, max_locks),
+                )))
+            }
+            Some(QuotaExceeded::User(max_locks)) => {
+                return Ok(Err(OpError::new(
+                    LockError::UserQuotaReached,
+                    format!("User {} has reached its quota of {} locks", req.user_id, max_locks),
+                )))
+            }
+            Some(QuotaExceeded::Tenant(max_locks)) => {
+                return Ok(Err(OpError::new(
+                    LockError::TenantQuotaReached,
+                    format!(
+                        "Tenant {} has reached its quota of {} locks",
+                        tenant::tenant_of(&req.namespace).unwrap_or_default(),
+                        max_locks
+                    ),
+                )))
+            }
+        }
+
+        let existing = storage
+            .get_lock(&lock_key)
+            .await
+            .map_err(|e| storage_error(LockError::LockLookupFailed, "Failed to get lock info", e))?;
+        let held = existing.as_ref().filter(|existing| !existing.is_expired_at(now));
+        let reentrant = held.is_some_and(|held| held.user_id == req.user_id);
+        if policy.advisory {
+            return Ok(Ok(reentrant));
+        }
+
+        if policy.hierarchical {
+            if !hierarchy::is_valid_path(&req.business_id) {
+                return Ok(Err(OpError::new(
+                    LockError::InvalidLockRequest,
+                    format!(
+                        "business_id must be a path of non-empty segments separated by '{}' in hierarchical namespace {}",
+                        hierarchy::SEPARATOR, req.namespace
+                    ),
+                )));
+            }
+            if let Some(conflict) = hierarchy::find_conflict(storage.as_ref(), &lock_info, now)
+                .await
+                .map_err(|e| storage_error(LockError::LockLookupFailed, "Failed to check path conflicts", e))?
+            {
+                return Ok(Err(OpError::new(
+                    LockError::PathConflict,
+                    format!(
+                        "Path {} conflicts with {} held by {}",
+                        req.business_id, conflict.business_id, conflict.user_name
+                    ),
+                )
+                .with_holder(&conflict)));
+            }
+        }
+        if let Some(conflict) =
+            uniqueness::find_conflict(storage.as_ref(), &config.lock_unique_business_ids, &lock_info, now)
+                .await
+                .map_err(|e| storage_error(LockError::LockLookupFailed, "Failed to check business ID uniqueness", e))?
+        {
+            return Ok(Err(business_id_conflict(req, conflict, now)));
+        }
+
+        // 条件获取在锁空闲时直接获取，否则只接管满足条件的已过期的锁，持有人本人也不按重入处理
+        if let Some(condition) = req.condition() {
+            return Ok(match existing {
+                Some(current) if !condition.matches(&current, now) => Err(OpError::new(
+                    LockError::AcquireConditionFailed,
+                    format!("Lock is held by {} and does not meet the takeover condition", current.user_name),
+                )
+                .with_holder(&current)),
+                _ => Ok(false),
+            });
+        }
+
+        match held {
+            Some(held) if held.user_id != req.user_id && req.preempt => Ok(Err(OpError::new(
+                LockError::PreemptionPending,
+                format!("Preemption of the lock held by {} would be scheduled", held.user_name),
+            )
+            .with_holder(held))),
+            Some(held) if held.user_id != req.user_id => {
+                let message = match &held.reason {
+                    Some(reason) => format!("Lock already held by {}: {}", held.user_name, reason),
+                    None => format!("Lock already held by {}", held.user_name),
+                };
+                Ok(Err(OpError::new(LockError::LockHeld, message).with_holder(held)))
+            }
+            Some(_) => Ok(Ok(true)),
+            None => {
+                let higher = if policy.allow_queue {
+                    queue.higher_priority_waiters(&lock_key, &req.user_id, req.priority, clock.now())
+                } else {
+                    0
+                };
+                if higher > 0 {
+                    return Ok(Err(OpError::new(
+                        LockError::ReservedForWaiters,
+                        format!("Lock is reserved for {} higher priority waiters", higher),
+                    )));
+                }
+                Ok(Ok(false))
+            }
+        }
+    }
+
+    /// 校验客户端声称持有的锁是否仍然有效，供资源服务在写入前确认
+    pub async fn verify(&self, req: &VerifyLockRequest) -> OpResult<LockVerification> {
+        let lock_info = match self.storage.get_lock_by_id(&req.lock_id).await {
+            Ok(lock_info) => lock_info,
+            Err(e) => {
+                error!("Failed to verify lock: {}", e);
+                return Err(OpError::new(
+                    storage_error_code(LockError::StatusFailed, &e),
+                    format!("Failed to verify lock: {}", e),
+                ));
+            }
+        };
+        let verification = LockVerification::check(req, lock_info.as_ref(), self.clock.now());
+        if let Some(reason) = verification.reason {
+            info!(
+                "[VERIFY FAILED] lock_id: {}, key: {}:{}, reason: {:?}",
+                req.lock_id, req.namespace, req.business_id, reason
+            );
+        }
+        Ok(verification)
+    }
+
+    /// 查询锁状态，锁空闲时返回最近一次持有的墓碑，提示性命名空间返回所有当前持有人
+    pub async fn status(&self, namespace: &str, business_id: &str) -> OpResult<LockStatus> {
+        let Self {
+            storage,
+            tombstones,
+            recovery,
+            clock,
+            ..
+        } = self;
+        let advisory = match storage.get_namespace(namespace).await {
+            Ok(policy) => policy.is_some_and(|policy| policy.advisory),
+            Err(e) => {
+                error!("Failed to load namespace policy: {}", e);
+                return Err(OpError::new(
+                    storage_error_code(LockError::StatusFailed, &e),
+                    format!("Failed to load namespace policy: {}", e),
+                ));
+            }
+        };
+        if advisory {
+            return match advisory::holders(storage.as_ref(), namespace, business_id, clock.now()).await {
+                Ok(holders) => Ok(LockStatus {
+                    locked: !holders.is_empty(),
+                    lock: None,
+                    tombstone: None,
+                    holders: Some(holders.iter().map(AdvisoryHolder::from).collect()),
+                    unconfirmed: false,
+                    confirm_before: None,
+                    expiry: None,
+                }),
+                Err(e) => {
+                    error!("Failed to get lock status: {}", e);
+                    Err(OpError::new(
+                        storage_error_code(LockError::StatusFailed, &e),
+                        format!("Failed to get lock status: {}", e),
+                    ))
+                }
+            };
+        }
+
+        let lock_key = format!("{}:{}", namespace, business_id);
+        match storage.get_lock(&lock_key).await {
+            Ok(Some(lock_info)) if !lock_info.is_expired_at(clock.now()) => {
+                let confirm_before = recovery.confirm_before(&lock_info, clock.now());
+                Ok(LockStatus {
+                    locked: true,
+                    expiry: Some(LockExpiry::from(&lock_info)),
+                    lock: Some(lock_info),
+                    tombstone: None,
+                    holders: None,
+                    unconfirmed: confirm_before.is_some(),
+                    confirm_before,
+                })
+            }
+            Ok(lock_info) => {
+                // 已过期但尚未清理的锁视为未持有，其过期时间比本实例记录的墓碑更新时以它为准
+                let expired = lock_info.and_then(|lock_info| tombstones.from_expired(&lock_info));
+                let tombstone = match (tombstones.get(&lock_key, clock.now()), expired) {
+                    (Some(recorded), Some(expired)) if recorded.ended_at >= expired.ended_at => Some(recorded),
+                    (recorded, expired) => expired.or(recorded),
+                };
+                Ok(LockStatus {
+                    locked: false,
+                    lock: None,
+                    tombstone,
+                    holders: None,
+                    unconfirmed: false,
+                    confirm_before: None,
+                    expiry: None,
+                })
+            }
+            Err(e) => {
+                error!("Failed to get lock status: {}", e);
+                Err(OpError::new(
+                    storage_error_code(LockError::StatusFailed, &e),
+                    format!("Failed to get lock status: {}", e),
+                ))
+            }
+        }
+    }
+
+    /// 续期锁，版本号不一致时返回错误码 2003，tenant 为请求所属的租户，其他租户的锁视为不存在
+    pub async fn heartbeat(&self, req: &HeartbeatRequest, tenant: Option<&Tenant>) -> OpResult<HeartbeatSuccess> {
+        let Self {
+            storage,
+            events,
+            notices,
+            chaos,
+            clock,
+            ..
+        } = self;
+        info!("Heartbeat request: lock_id={}", req.lock_id);
+        match tenant::may_access_lock(storage.as_ref(), tenant, &req.lock_id).await {
+            Ok(true) => {}
+            Ok(false) => {
+                info!("Lock not found or expired: {}", req.lock_id);
+                return Err(OpError::new(LockError::HeartbeatLockNotFound, "Lock not found or expired".to_string()));
+            }
+            Err(e) => {
+                error!("Failed to update heartbeat: {}", e);
+                return Err(OpError::new(
+                    storage_error_code(LockError::HeartbeatFailed, &e),
+                    format!("Failed to update heartbeat: {}", e),
+                ));
+            }
+        }
+
+        // 故障注入：心跳前使锁提前过期
+        if chaos.as_ref().is_some_and(|chaos| chaos.should_expire()) {
+            if let Ok(Some(lock_info)) = storage.get_lock_by_id(&req.lock_id).await {
+                if let Ok(Some(expired)) = storage.force_release(&lock_info.get_lock_key()).await {
+                    warn!("[CHAOS] Expiring lock prematurely: {}", req.lock_id);
+                    events.publish(LockEvent::new(LockEventType::Expired, expired));
+                }
+            }
+        }
+
+        let result = match req.if_version {
+            Some(version) => storage.update_heartbeat_version(&req.lock_id, version).await,
+            None => storage.update_heartbeat(&req.lock_id).await,
+        };
+        match result {
+            Ok(Some(lock_info)) => {
+                info!("Heartbeat updated successfully: {}", req.lock_id);
+                let mut success = HeartbeatSuccess::new(&lock_info, clock.now());
+                success.heartbeat_interval_ms = self.heartbeats.heartbeat(&lock_info);
+                success.notices = notices.take(&lock_info.lock_id);
+                Ok(success)
+            }
+            Ok(None) => {
+                if let Some(version) = req.if_version {
+                    if let Ok(Some(current)) = storage.get_lock_by_id(&req.lock_id).await {
+                        if current.version != version && !current.is_expired_at(clock.now()) {
+                            info!(
+                                "Heartbeat version mismatch: {}, version: {}, expected: {}",
+                                req.lock_id, current.version, version
+                            );
+                            return Err(OpError::new(
+                                LockError::HeartbeatConditionFailed,
+                                format!("Lock version is {}, not {}", current.version, version),
+                            )
+                            .with_extensions(&serde_json::json!({ "version": current.version })));
+                        }
+                    }
+                }
+                info!("Lock not found or expired: {}", req.lock_id);
+                Err(OpError::new(
+                    LockError::HeartbeatLockNotFound,
+                    "Lock not found or expired".to_string(),
+                ))
+            }
+            Err(e) => {
+                error!("Failed to update heartbeat: {}", e);
+                Err(OpError::new(
+                    storage_error_code(LockError::HeartbeatFailed, &e),
+                    format!("Failed to update heartbeat: {}", e),
+                ))
+            }
+        }
+    }
+
+    /// 释放锁，返回被释放的锁，tenant 为请求所属的租户，其他租户的锁视为不存在
+    pub async fn release(&self, req: &ReleaseLockRequest, tenant: Option<&Tenant>) -> OpResult<LockInfo> {
+        let Self { storage, events, .. } = self;
+        // 优先按 lock_id 释放；丢失 lock_id 时可按 namespace + business_id + user_id 释放
+        let (target, result) = match (&req.lock_id, &req.business_id, &req.user_id) {
+            (Some(lock_id), _, _) => {
+                let target = format!("lock_id: {}", lock_id);
+                info!("[RELEASE] Attempting to release lock - {}", target);
+                match tenant::may_access_lock(storage.as_ref(), tenant, lock_id).await {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        info!("[RELEASE FAILED] Lock not found or not owned - {}", target);
+                        return Err(OpError::new(LockError::ReleaseLockNotFound, "Lock not found or not owned".to_string()));
+                    }
+                    Err(e) => {
+                        error!("Failed to release lock: {}", e);
+                        return Err(OpError::new(
+                            storage_error_code(LockError::ReleaseFailed, &e),
+                            format!("Failed to release lock: {}", e),
+                        ));
+                    }
+                }
+                let result = match req.if_version {
+                    Some(version) => storage.release_version(lock_id, version).await,
+                    None => storage.release(lock_id).await,
+                };
+                (target, result)
+            }
+            (None, Some(business_id), Some(user_id)) => {
+                let lock_key = format!("{}:{}", req.namespace, business_id);
+                let target = format!("lock_key: {}, user_id: {}", lock_key, user_id);
+                info!("[RELEASE] Attempting to release lock by key - {}", target);
+                let result = match req.if_version {
+                    Some(version) => match release_target(storage.as_ref(), req).await {
+                        Ok(Some(lock_info)) => storage.release_version(&lock_info.lock_id, version).await,
+                        other => other,
+                    },
+                    None => storage.release_owned(&lock_key, user_id).await,
+                };
+                (target, result)
+            }
+            _ => {
+                return Err(OpError::new(
+                    LockError::InvalidReleaseRequest,
+                    "Either lock_id or business_id + user_id is required".to_string(),
+                ))
+            }
+        };
+
+        match result {
+            Ok(released) => {
+                if let Some(lock_info) = released {
+                    info!(
+                        "[RELEASE SUCCESS] Lock released - lock_id: {}, namespace: {}, business_id: {}",
+                        lock_info.lock_id, lock_info.namespace, lock_info.business_id
+                    );
+                    self.drain.finish(&lock_info);
+                    events.publish(LockEvent::new(LockEventType::Released, lock_info.clone()));
+                    Ok(lock_info)
+                } else {
+                    if let Some(version) = req.if_version {
+                        if let Ok(Some(current)) = release_target(storage.as_ref(), req).await {
+                            info!(
+                                "[RELEASE FAILED] Version mismatch - {}, version: {}, expected: {}",
+                                target, current.version, version
+                            );
+                            return Err(OpError::new(
+                                LockError::ReleaseConditionFailed,
+                                format!("Lock version is {}, not {}", current.version, version),
+                            )
+                            .with_extensions(&serde_json::json!({ "version": current.version })));
+                        }
+                    }
+                    info!("[RELEASE FAILED] Lock not found or not owned - {}", target);
+                    Err(OpError::new(
+                        LockError::ReleaseLockNotFound,
+                        "Lock not found or not owned".to_string(),
+                    ))
+                }
+            }
+            Err(e) => {
+                error!("Failed to release lock: {}", e);
+                Err(OpError::new(
+                    storage_error_code(LockError::ReleaseFailed, &e),
+                    format!("Failed to release lock: {}", e),
+                ))
+            }
+        }
+    }
+
+    /// 将用户（或其某个会话）持有的锁标记为排空并发布 `draining` 事件，截止时间到达时由 [`DrainScheduler`] 释放
+    pub async fn release_gracefully(
+        &self,
+        req: &GracefulReleaseRequest,
+        tenant: Option<&Tenant>,
+    ) -> OpResult<GracefulReleaseStarted> {
+        let Self {
+            storage,
+            events,
+            config,
+            drain,
+            sessions,
+            clock,
+            ..
+        } = self;
+        let now = clock.now();
+        let locks = match &req.session_id {
+            Some(session_id) => {
+                let session = match sessions.get(storage.as_ref(), session_id, &req.user_id, now).await {
+                    Ok(Some(session)) => session,
+                    Ok(None) => {
+                        return Err(OpError::new(
+                            LockError::SessionNotFound,
+                            format!("Session not found or expired: {}", session_id),
+                        ))
+                    }
+                    Err(e) => {
+                        error!("Failed to load session {}: {}", session_id, e);
+                        return Err(OpError::new(
+                            storage_error_code(LockError::SessionFailed, &e),
+                            format!("Failed to load session: {}", e),
+                        ));
+                    }
+                };
+                let mut locks = Vec::new();
+                for lock_id in &session.lock_ids {
+                    match storage.get_lock_by_id(lock_id).await {
+                        Ok(lock_info) => locks.extend(lock_info),
+                        Err(e) => {
+                            error!("Failed to load session locks: {}", e);
+                            return Err(OpError::new(
+                                storage_error_code(LockError::ReleaseFailed, &e),
+                                format!("Failed to load session locks: {}", e),
+                            ));
+                        }
+                    }
+                }
+                locks
+            }
+            None => match storage.list_by_user(&req.user_id).await {
+                Ok(locks) => locks,
+                Err(e) => {
+                    error!("Failed to list locks of user {}: {}", req.user_id, e);
+                    return Err(OpError::new(
+                        storage_error_code(LockError::ReleaseFailed, &e),
+                        format!("Failed to list locks: {}", e),
+                    ));
+                }
+            },
+        };
+        let locks: Vec<LockInfo> = locks
+            .into_iter()
+            .filter(|lock_info| lock_info.user_id == req.user_id && !lock_info.is_expired_at(now))
+            .filter(|lock_info| !presence::is_presence_namespace(&lock_info.namespace))
+            .filter(|lock_info| tenant.is_none_or(|tenant| tenant.owns_lock(lock_info)))
+            .collect();
+
+        let drain_seconds = req.drain_seconds.unwrap_or(config.lock_drain_timeout);
+        let deadline = now + chrono::Duration::seconds(drain_seconds as i64);
+        for lock_info in &locks {
+            if drain.start(lock_info, deadline) {
+                events.publish(LockEvent::new(LockEventType::Draining, lock_info.clone()).with_deadline(deadline));
+            }
+        }
+        info!(
+            "[DRAIN] Draining {} locks - user_id: {}, session_id: {}, deadline: {}",
+            locks.len(),
+            req.user_id,
+            req.session_id.as_deref().unwrap_or("-"),
+            deadline
+        );
+        Ok(GracefulReleaseStarted {
+            draining: locks.len(),
+            deadline,
+            locks,
+        })
+    }
+
+    /// 为被他人持有的锁登记抢占，宽限期结束后由 [`PreemptionScheduler`] 转移给申请人
+    async fn preempt(
+        &self,
+        req: &AcquireLockRequest,
+        holder: &LockInfo,
+        lock_info: LockInfo,
+    ) -> OpResult<AcquireLockSuccess> {
+        let Self {
+            storage,
+            events,
+            config,
+            preemption,
+            clock,
+            ..
+        } = self;
+        let now = clock.now();
+        let grace = req.grace_seconds.unwrap_or(config.lock_preempt_grace);
+        let notice = PreemptionNotice {
+            user_id: req.user_id.clone(),
+            user_name: req.user_name.clone(),
+            deadline: now + chrono::Duration::seconds(grace as i64),
+        };
+        let marked = match storage.mark_preempted(&holder.lock_id, notice).await {
+            Ok(Some(marked)) => marked,
+            Ok(None) => {
+                return Err(OpError::new(
+                    LockError::AcquireFailed,
+                    "Lock was released during preemption, please retry".to_string(),
+                ))
+            }
+            Err(e) => {
+                error!("Failed to mark lock as preempted: {}", e);
+                return Err(OpError::new(
+                    storage_error_code(LockError::LockLookupFailed, &e),
+                    format!("Failed to mark lock as preempted: {}", e),
+                ));
+            }
+        };
+        let Some(notice) = marked.preemption.clone() else {
+            return Err(OpError::new(
+                LockError::AcquireFailed,
+                "Lock acquisition failed".to_string(),
+            ));
+        };
+        if notice.user_id != req.user_id {
+            return Err(OpError::new(
+                LockError::LockHeld,
+                format!(
+                    "Lock already held by {} and being preempted by {}",
+                    marked.user_name, notice.user_name
+                ),
+            )
+            .with_holder(&marked)
+            .with_extensions(&conflict_details(&marked, None, now)));
+        }
+
+        let pending = preemption.schedule(&marked.lock_id, lock_info, notice.deadline);
+        if holder.preemption.is_none() {
+            warn!(
+                "[PREEMPT] Lock preemption pending - lock_id: {}, namespace: {}, business_id: {}, holder: {}, preemptor: {}, deadline: {}",
+                marked.lock_id, marked.namespace, marked.business_id, marked.user_id, req.user_id, notice.deadline
+            );
+            events.publish(LockEvent::new(LockEventType::PreemptionPending, marked.clone()));
+        }
+        Err(OpError::new(
+            LockError::PreemptionPending,
+            format!(
+                "Preemption pending, lock will be transferred from {} in {}s",
+                marked.user_name,
+                (notice.deadline - now).num_seconds().max(0)
+            ),
+        )
+        .with_holder(&marked)
+        .with_extensions(&PreemptionPending {
+            lock_id: pending.lock_id,
+            current_holder: marked.user_name,
+            deadline: notice.deadline,
+        }))
+    }
+
+    /// 清理已过期的锁并发布过期事件，返回被清理的锁（含在场记录）；Redis 存储由键过期自动删除，返回空
+    pub async fn cleanup_expired(&self) -> anyhow::Result<Vec<LockInfo>> {
+        let expired = self.storage.cleanup_expired().await?;
+        // 在场记录过期即离开，不是锁事件
+        for lock_info in expired
+            .iter()
+            .filter(|lock_info| !presence::is_presence_namespace(&lock_info.namespace))
+        {
+            self.events.publish(LockEvent::new(LockEventType::Expired, lock_info.clone()));
+        }
+        Ok(expired)
+    }
+}
+
+/// 存储写入锁失败，内存存储已满时返回错误码 7003，存储超时返回 7004
+fn acquire_failed(e: anyhow::Error) -> OpError {
+    if let Some(StorageFull(max_locks)) = e.downcast_ref::<StorageFull>() {
+        return OpError::new(
+            LockError::StorageFull,
+            format!("Lock storage is full ({} locks), retry later", max_locks),
+        )
+        .with_extensions(&serde_json::json!({ "limit": max_locks }));
+    }
+    OpError::new(storage_error_code(LockError::PolicyLookupFailed, &e), format!("Failed to acquire lock: {}", e))
+}
+
+/// 命名空间冻结时返回错误码 1019，申请人在冻结生效前已持有该锁（重入）时不受影响
+async fn namespace_frozen(
+    storage: &dyn LockStorage,
+    policy: &NamespacePolicy,
+    req: &AcquireLockRequest,
+    now: DateTime<Utc>,
+) -> anyhow::Result<Option<OpError>> {
+    let Some(freeze) = policy.freeze.as_ref().filter(|freeze| freeze.active_at(now)) else {
+        return Ok(None);
+    };
+    let lock_key = format!("{}:{}", req.namespace, req.business_id);
+    if storage
+        .get_lock(&lock_key)
+        .await?
+        .is_some_and(|held| held.user_id == req.user_id && !held.is_expired_at(now))
+    {
+        return Ok(None);
+    }
+    let message = match &freeze.reason {
+        Some(reason) => format!("Namespace {} is frozen: {}", req.namespace, reason),
+        None => format!("Namespace {} is frozen", req.namespace),
+    };
+    Ok(Some(OpError::new(LockError::NamespaceFrozen, message).with_extensions(&serde_json::json!({
+        "frozen_until": freeze.until,
+        "reason": freeze.reason,
+    }))))
+}
+
+/// 达到上限的锁配额及其上限
+enum QuotaExceeded {
+    Namespace(u64),
+    User(u64),
+    Tenant(u64),
+}
+
+/// 命名空间、用户或租户的锁数量是否已达上限，重入申请不占用新的名额，max_per_user 为 0 表示不限制。
+/// 用户和租户的锁数量不含在场记录，用户的锁数量只计同一租户中的锁
+async fn quota_exceeded(
+    storage: &dyn LockStorage,
+    lock_info: &LockInfo,
+    max_per_namespace: Option<u64>,
+    max_per_user: u64,
+    max_per_tenant: Option<u64>,
+    now: DateTime<Utc>,
+) -> anyhow::Result<Option<QuotaExceeded>> {
+    if max_per_namespace.is_none() && max_per_user == 0 && max_per_tenant.is_none() {
+        return Ok(None);
+    }
+    if let Some(existing_lock) = storage.get_lock(&lock_info.get_lock_key()).await? {
+        if existing_lock.user_id == lock_info.user_id && !existing_lock.is_expired_at(now) {
+            return Ok(None);
+        }
+    }
+    if let Some(max_locks) = max_per_namespace {
+        if storage.count_locks(&lock_info.namespace).await? >= max_locks {
+            return Ok(Some(QuotaExceeded::Namespace(max_locks)));
+        }
+    }
+    let tenant_id = tenant::tenant_of(&lock_info.namespace);
+    if max_per_user > 0 {
+        let held = held_by_user(storage, &lock_info.user_id, tenant_id).await?.len() as u64;
+        if held >= max_per_user {
+            return Ok(Some(QuotaExceeded::User(max_per_user)));
+        }
+    }
+    if let (Some(max_locks), Some(tenant_id)) = (max_per_tenant, tenant_id) {
+        let held = storage
+            .list_prefix(&format!("{}{}", tenant_id, tenant::SEPARATOR))
+            .await?
+            .iter()
+            .filter(|held| !held.is_expired_at(now) && !presence::is_presence_namespace(&held.namespace))
+            .count() as u64;
+        if held >= max_locks {
+            return Ok(Some(QuotaExceeded::Tenant(max_locks)));
+        }
+    }
+    Ok(None)
+}
+
+/// 将申请到的锁挂到会话上，失败时锁仍保留，按会话的超时时间过期
+async fn attach_to_session(sessions: &SessionRegistry, storage: &dyn LockStorage, session_id: &str, lock_id: &str) {
+    match sessions.attach(storage, session_id, lock_id).await {
+        Ok(true) => {}
+        Ok(false) => warn!("[CLIENT SESSION] Session {} ended before lock {} was attached", session_id, lock_id),
+        Err(e) => error!("[CLIENT SESSION] Failed to attach lock {} to session {}: {}", lock_id, session_id, e),
+    }
+}
+
+/// 用户在租户内持有的计入配额的锁，在场记录不计入
+async fn held_by_user(storage: &dyn LockStorage, user_id: &str, tenant_id: Option<&str>) -> anyhow::Result<Vec<LockInfo>> {
+    Ok(storage
+        .list_by_user(user_id)
+        .await?
+        .into_iter()
+        .filter(|held| tenant::tenant_of(&held.namespace) == tenant_id && !presence::is_presence_namespace(&held.namespace))
+        .collect())
+}
+
+/// 申请人排队等待当前持有人会形成死锁时，将其移出队列并返回错误码 1007
+async fn detect_deadlock(
+    storage: &dyn LockStorage,
+    queue: &WaitQueue,
+    lock_key: &str,
+    user_id: &str,
+    holder: &LockInfo,
+    now: DateTime<Utc>,
+) -> Option<OpError> {
+    let path = match queue.find_deadlock(storage, user_id, &holder.user_id, now).await {
+        Ok(path) => path?,
+        Err(e) => {
+            error!("Failed to run deadlock detection: {}", e);
+            return None;
+        }
+    };
+
+    queue.remove(lock_key, user_id);
+    let cycle: Vec<String> = std::iter::once(lock_key.to_string()).chain(path).collect();
+    warn!(
+        "[DEADLOCK] Acquisition rejected - lock_key: {}, user_id: {}, holder: {}, cycle: {}",
+        lock_key,
+        user_id,
+        holder.user_id,
+        cycle.join(" -> ")
+    );
+    Some(
+        OpError::new(
+            LockError::Deadlock,
+            format!(
+                "Waiting for {} held by {} would cause a deadlock",
+                lock_key, holder.user_name
+            ),
+        )
+        .with_holder(holder)
+        .with_extensions(&DeadlockDetected { cycle }),
+    )
+}
+
+/// 锁冲突时返回给申请人的持有人和截至 now 的排队信息
+fn business_id_conflict(req: &AcquireLockRequest, conflict: LockInfo, now: DateTime<Utc>) -> OpError {
+    OpError::new(
+        LockError::BusinessIdConflict,
+        format!(
+            "Business ID {} is already locked in namespace {} by {}",
+            req.business_id, conflict.namespace, conflict.user_name
+        ),
+    )
+    .with_holder(&conflict)
+    .with_extensions(&BusinessIdConflict {
+        remaining_seconds: conflict.deadline_secs_at(now),
+        namespace: conflict.namespace,
+        current_holder: conflict.user_name,
+        locked_at: conflict.locked_at,
+        reason: conflict.reason,
+    })
+}
+
+fn conflict_details(holder: &LockInfo, waiters_ahead: Option<usize>, now: DateTime<Utc>) -> AcquireLockFailure {
+    let remaining_seconds = holder.deadline_secs_at(now);
+    AcquireLockFailure {
+        current_holder: holder.user_name.clone(),
+        locked_at: holder.locked_at,
+        reason: holder.reason.clone(),
+        remaining_seconds,
+        waiters_ahead,
+        estimated_wait_seconds: remaining_seconds
+            .map(|remaining| remaining + waiters_ahead.unwrap_or(0) as u64 * holder.timeout),
+    }
+}
+
+/// 释放请求指向的锁：按 lock_id，或按业务键且持有人为 user_id
+async fn release_target(
+    storage: &dyn LockStorage,
+    req: &ReleaseLockRequest,
+) -> anyhow::Result<Option<LockInfo>> {
+    match (&req.lock_id, &req.business_id, &req.user_id) {
+        (Some(lock_id), _, _) => storage.get_lock_by_id(lock_id).await,
+        (None, Some(business_id), Some(user_id)) => Ok(storage
+            .get_lock(&format!("{}:{}", req.namespace, business_id))
+            .await?
+            .filter(|lock_info| &lock_info.user_id == user_id)),
+        _ => Ok(None),
+    }
+}
@@ -0,0 +1,434 @@
+//! Prometheus 指标接口 `GET /metrics`（文本格式 0.0.4）
+
+use crate::backpressure::Backpressure;
+use crate::checkout::OverdueTracker;
+use crate::clock;
+use crate::events::delivery::EventSinkStatus;
+use crate::events::EventBus;
+use crate::handoff::Handoff;
+use crate::heartbeats::{HeartbeatAdvisor, HeartbeatCounts};
+use crate::holdcap::HoldCapMonitor;
+use crate::ipfilter::IpFilters;
+use crate::latency::RequestTimings;
+use crate::leader::LeaderElection;
+use crate::models::{HoldCapStats, Histogram, LockStats};
+use crate::storage::failover::{FailoverStorage, StorageHealth};
+use crate::storage::memory::MemoryStorage;
+use crate::storage::replication::ReplicatedStorage;
+use crate::storage::sharded::ShardedStorage;
+use crate::storage::LockStorage;
+use crate::stuck::StuckLockDetector;
+use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::{DateTime, Utc};
+use log::error;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Arc;
+
+/// 按 lock_key 输出的指标数量，避免标签基数过高
+const METRICS_TOP_KEYS: usize = 20;
+
+#[allow(clippy::too_many_arguments)]
+pub async fn metrics(
+    storage: web::Data<Arc<dyn LockStorage>>,
+    failover: Option<web::Data<Arc<FailoverStorage>>>,
+    sharded: Option<web::Data<Arc<ShardedStorage>>>,
+    memory: Option<web::Data<Arc<MemoryStorage>>>,
+    backpressure: Option<web::Data<Arc<Backpressure>>>,
+    replication: Option<web::Data<Arc<ReplicatedStorage>>>,
+    events: Option<web::Data<Arc<EventBus>>>,
+    stuck_detector: Option<web::Data<Arc<StuckLockDetector>>>,
+    overdue_tracker: Option<web::Data<Arc<OverdueTracker>>>,
+    hold_cap_monitor: Option<web::Data<Arc<HoldCapMonitor>>>,
+    heartbeat_advisor: Option<web::Data<Arc<HeartbeatAdvisor>>>,
+    ip_filters: Option<web::Data<Arc<IpFilters>>>,
+    request_timings: Option<web::Data<Arc<RequestTimings>>>,
+    leader: Option<web::Data<Arc<LeaderElection>>>,
+    handoff: Option<web::Data<Arc<Handoff>>>,
+    req: HttpRequest,
+) -> HttpResponse {
+    // 分片模式下按分片输出，带 shard 标签
+    let health: Vec<(Option<String>, StorageHealth)> = match (failover, sharded) {
+        (Some(failover), _) => vec![(None, failover.health())],
+        (_, Some(sharded)) => sharded
+            .health()
+            .into_iter()
+            .map(|shard| (Some(shard.shard), shard.health))
+            .collect(),
+        _ => Vec::new(),
+    };
+    let mut out = match storage.stats(METRICS_TOP_KEYS).await {
+        Ok(stats) => render(&stats),
+        // 存储不可用时仍输出断路器状态
+        Err(e) if !health.is_empty() => {
+            error!("Failed to collect lock metrics: {}", e);
+            String::new()
+        }
+        Err(e) => {
+            error!("Failed to collect metrics: {}", e);
+            return HttpResponse::InternalServerError().body(format!("Failed to collect metrics: {}", e));
+        }
+    };
+    if !health.is_empty() {
+        render_storage(&mut out, &health);
+    }
+    if let Some(memory) = &memory {
+        render_memory(&mut out, memory);
+    }
+    if let Some(backpressure) = &backpressure {
+        render_backpressure(&mut out, backpressure);
+    }
+    if let Some(replication) = &replication {
+        render_replication(&mut out, replication);
+    }
+    if let Some(events) = &events {
+        render_events(&mut out, events, clock::now(&req));
+    }
+    if let Some(stuck_detector) = &stuck_detector {
+        render_stuck(&mut out, stuck_detector);
+    }
+    if let Some(overdue_tracker) = &overdue_tracker {
+        render_overdue(&mut out, overdue_tracker);
+    }
+    if let Some(hold_cap_monitor) = &hold_cap_monitor {
+        render_hold_caps(&mut out, hold_cap_monitor);
+    }
+    if let Some(heartbeat_advisor) = &heartbeat_advisor {
+        render_heartbeats(&mut out, heartbeat_advisor);
+    }
+    if let Some(ip_filters) = &ip_filters {
+        render_ip_filters(&mut out, ip_filters);
+    }
+    if let Some(request_timings) = &request_timings {
+        render_request_timings(&mut out, request_timings);
+    }
+    if let Some(leader) = leader.filter(|leader| leader.enabled()) {
+        header(&mut out, "fe_lock_leader", "gauge", "Whether this instance runs the single-instance background tasks");
+        let _ = writeln!(out, "fe_lock_leader {}", leader.is_leader() as u8);
+    }
+    if let Some(handoff) = &handoff {
+        let status = handoff.status();
+        for (name, help, value) in [
+            ("fe_lock_handoff_epoch", "Handoff epoch of this instance, incremented on each instance-to-instance handoff", status.epoch),
+            ("fe_lock_instance_fenced", "Whether this instance has been fenced by a successor and rejects lock requests", handoff.fenced() as u64),
+        ] {
+            header(&mut out, name, "gauge", help);
+            let _ = writeln!(out, "{} {}", name, value);
+        }
+    }
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4; charset=utf-8")
+        .body(out)
+}
+
+fn render(stats: &LockStats) -> String {
+    let mut out = String::new();
+
+    header(&mut out, "fe_lock_active_locks", "gauge", "Number of locks currently held");
+    for (namespace, count) in &stats.namespaces {
+        let _ = writeln!(
+            out,
+            "fe_lock_active_locks{{namespace=\"{}\"}} {}",
+            escape(namespace),
+            count
+        );
+    }
+
+    for (name, help, value) in [
+        ("fe_lock_acquired_total", "Successful lock acquisitions", stats.acquired),
+        ("fe_lock_conflicts_total", "Lock acquisitions rejected because the lock was held", stats.conflicts),
+        ("fe_lock_expired_total", "Locks removed after expiring", stats.expired),
+    ] {
+        header(&mut out, name, "counter", help);
+        let _ = writeln!(out, "{} {}", name, value);
+    }
+
+    histogram(
+        &mut out,
+        "fe_lock_hold_seconds",
+        "Time locks were held before release or expiry",
+        &stats.hold_time,
+    );
+    histogram(
+        &mut out,
+        "fe_lock_key_conflicts",
+        "Rejected acquisitions per lock key",
+        &stats.contention,
+    );
+
+    header(
+        &mut out,
+        "fe_lock_key_conflicts_total",
+        "counter",
+        "Rejected acquisitions for the most contended lock keys",
+    );
+    for lock in &stats.top_contended {
+        let _ = writeln!(
+            out,
+            "fe_lock_key_conflicts_total{{lock_key=\"{}\"}} {}",
+            escape(&lock.lock_key),
+            lock.conflicts
+        );
+    }
+
+    header(
+        &mut out,
+        "fe_lock_key_max_hold_seconds",
+        "gauge",
+        "Longest single hold for the longest held lock keys",
+    );
+    for lock in &stats.longest_held {
+        let _ = writeln!(
+            out,
+            "fe_lock_key_max_hold_seconds{{lock_key=\"{}\"}} {}",
+            escape(&lock.lock_key),
+            lock.max_hold_secs
+        );
+    }
+
+    out
+}
+
+/// 指标名、说明和取值
+type HealthMetric = (&'static str, &'static str, fn(&StorageHealth) -> u64);
+
+fn render_storage(out: &mut String, shards: &[(Option<String>, StorageHealth)]) {
+    let gauges: [HealthMetric; 3] = [
+        ("fe_lock_storage_circuit_open", "Whether the storage circuit breaker is open", |health| health.circuit_open as u64),
+        ("fe_lock_storage_degraded", "Whether requests are served by the local fallback storage", |health| health.degraded as u64),
+        ("fe_lock_storage_consecutive_failures", "Consecutive failed storage calls", |health| health.consecutive_failures as u64),
+    ];
+    let counters: [HealthMetric; 3] = [
+        ("fe_lock_storage_retries_total", "Storage calls retried after transient errors", |health| health.retries_total),
+        ("fe_lock_storage_failures_total", "Storage calls that failed after retries", |health| health.failures_total),
+        ("fe_lock_storage_circuit_opened_total", "Times the storage circuit breaker opened", |health| health.circuit_opened_total),
+    ];
+    for (kind, metrics) in [("gauge", gauges), ("counter", counters)] {
+        for (name, help, value) in metrics {
+            header(out, name, kind, help);
+            for (shard, health) in shards {
+                match shard {
+                    Some(shard) => {
+                        let _ = writeln!(out, "{}{{shard=\"{}\"}} {}", name, escape(shard), value(health));
+                    }
+                    None => {
+                        let _ = writeln!(out, "{} {}", name, value(health));
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn render_memory(out: &mut String, memory: &MemoryStorage) {
+    for (name, help, value) in [
+        ("fe_lock_memory_locks", "Locks held in memory, including expired locks not yet removed", memory.len() as u64),
+        ("fe_lock_memory_max_locks", "Maximum number of locks in memory, 0 if unlimited", memory.max_locks() as u64),
+    ] {
+        header(out, name, "gauge", help);
+        let _ = writeln!(out, "{} {}", name, value);
+    }
+    header(
+        out,
+        "fe_lock_memory_evicted_total",
+        "counter",
+        "Expired locks evicted to make room for new locks",
+    );
+    let _ = writeln!(out, "fe_lock_memory_evicted_total {}", memory.evicted());
+}
+
+fn render_backpressure(out: &mut String, backpressure: &Backpressure) {
+    for (name, help, value) in [
+        ("fe_lock_inflight_requests", "API requests currently being processed", backpressure.inflight()),
+        ("fe_lock_queued_requests", "API requests waiting for a free slot", backpressure.queued()),
+        ("fe_lock_max_inflight_requests", "Maximum concurrent API requests, 0 if unlimited", backpressure.max_inflight()),
+    ] {
+        header(out, name, "gauge", help);
+        let _ = writeln!(out, "{} {}", name, value);
+    }
+    header(
+        out,
+        "fe_lock_rejected_requests_total",
+        "counter",
+        "API requests rejected with 503 because the server was overloaded",
+    );
+    let _ = writeln!(out, "fe_lock_rejected_requests_total {}", backpressure.rejected());
+}
+
+fn render_replication(out: &mut String, replication: &ReplicatedStorage) {
+    header(
+        out,
+        "fe_lock_replication_queue_depth",
+        "gauge",
+        "Changes waiting to be written to the replica storage",
+    );
+    let _ = writeln!(out, "fe_lock_replication_queue_depth {}", replication.queue_depth());
+    for (name, help, value) in [
+        ("fe_lock_replication_writes_total", "Changes written to the replica storage", replication.replicated()),
+        ("fe_lock_replication_failures_total", "Changes that failed to be written to the replica storage", replication.failures()),
+        ("fe_lock_replication_dropped_total", "Changes dropped because the replication queue was full", replication.dropped()),
+        ("fe_lock_replication_repaired_total", "Locks and namespace policies repaired by reconciliation", replication.repaired()),
+    ] {
+        header(out, name, "counter", help);
+        let _ = writeln!(out, "{} {}", name, value);
+    }
+}
+
+fn render_events(out: &mut String, events: &EventBus, now: DateTime<Utc>) {
+    let sinks = events.delivery_status(now);
+    if sinks.is_empty() {
+        return;
+    }
+    header(out, "fe_lock_event_buffered", "gauge", "Lock events waiting to be delivered");
+    for sink in &sinks {
+        let _ = writeln!(out, "fe_lock_event_buffered{{sink=\"{}\"}} {}", sink.sink, sink.buffered);
+    }
+    header(
+        out,
+        "fe_lock_event_delivery_lag_seconds",
+        "gauge",
+        "Age of the lock event currently being delivered, 0 if idle",
+    );
+    for sink in &sinks {
+        let _ = writeln!(
+            out,
+            "fe_lock_event_delivery_lag_seconds{{sink=\"{}\"}} {}",
+            sink.sink,
+            sink.lag_ms as f64 / 1000.0
+        );
+    }
+    for (name, help, value) in [
+        ("fe_lock_event_delivered_total", "Lock events delivered", (|sink: &EventSinkStatus| sink.delivered) as fn(&EventSinkStatus) -> u64),
+        ("fe_lock_event_retries_total", "Lock event delivery retries", |sink| sink.retries),
+        ("fe_lock_event_dead_letters_total", "Lock events moved to the dead-letter queue", |sink| sink.dead_lettered),
+    ] {
+        header(out, name, "counter", help);
+        for sink in &sinks {
+            let _ = writeln!(out, "{}{{sink=\"{}\"}} {}", name, sink.sink, value(sink));
+        }
+    }
+}
+
+fn render_hold_caps(out: &mut String, hold_cap_monitor: &HoldCapMonitor) {
+    let stats = hold_cap_monitor.stats();
+    if stats.is_empty() {
+        return;
+    }
+    for (name, kind, help, value) in [
+        ("fe_lock_hold_cap_locks", "gauge", "Locks currently held under the namespace max hold time", (|entry: &HoldCapStats| entry.holding) as fn(&HoldCapStats) -> u64),
+        ("fe_lock_hold_cap_locks_total", "counter", "Locks acquired under the namespace max hold time", |entry| entry.locks),
+        ("fe_lock_hold_cap_warnings_total", "counter", "Locks warned that they are approaching the namespace max hold time", |entry| entry.warned),
+        ("fe_lock_hold_cap_reached_total", "counter", "Locks that expired at the namespace max hold time while still heartbeating", |entry| entry.reached),
+    ] {
+        header(out, name, kind, help);
+        for entry in &stats {
+            let _ = writeln!(out, "{}{{namespace=\"{}\"}} {}", name, escape(&entry.namespace), value(entry));
+        }
+    }
+}
+
+fn render_heartbeats(out: &mut String, heartbeat_advisor: &HeartbeatAdvisor) {
+    let counts = heartbeat_advisor.counts();
+    for (name, help, value) in [
+        ("fe_lock_heartbeats_total", "Successful lock heartbeats", (|counts: &HeartbeatCounts| counts.heartbeats) as fn(&HeartbeatCounts) -> u64),
+        ("fe_lock_heartbeats_too_frequent_total", "Heartbeats sent within a quarter of the recommended interval", |counts| counts.too_frequent),
+    ] {
+        header(out, name, "counter", help);
+        for (namespace, entry) in &counts {
+            let _ = writeln!(out, "{}{{namespace=\"{}\"}} {}", name, escape(namespace), value(entry));
+        }
+    }
+}
+
+fn render_stuck(out: &mut String, stuck_detector: &StuckLockDetector) {
+    let mut by_namespace: BTreeMap<String, u64> = BTreeMap::new();
+    for stuck in stuck_detector.list() {
+        *by_namespace.entry(stuck.lock.namespace).or_default() += 1;
+    }
+    header(out, "fe_lock_stuck_locks", "gauge", "Locks held longer than the namespace stuck threshold");
+    for (namespace, count) in &by_namespace {
+        let _ = writeln!(out, "fe_lock_stuck_locks{{namespace=\"{}\"}} {}", escape(namespace), count);
+    }
+    header(out, "fe_lock_stuck_locks_total", "counter", "Locks detected as stuck");
+    let _ = writeln!(out, "fe_lock_stuck_locks_total {}", stuck_detector.detected_total());
+}
+
+fn render_overdue(out: &mut String, overdue_tracker: &OverdueTracker) {
+    let mut by_namespace: BTreeMap<String, u64> = BTreeMap::new();
+    for overdue in overdue_tracker.list() {
+        *by_namespace.entry(overdue.lock.namespace).or_default() += 1;
+    }
+    header(out, "fe_lock_overdue_checkouts", "gauge", "Locks held past their expected return time");
+    for (namespace, count) in &by_namespace {
+        let _ = writeln!(out, "fe_lock_overdue_checkouts{{namespace=\"{}\"}} {}", escape(namespace), count);
+    }
+}
+
+fn render_ip_filters(out: &mut String, ip_filters: &IpFilters) {
+    header(out, "fe_lock_ip_rejected_total", "counter", "Requests rejected by the source address allow/deny lists");
+    for (surface, filter) in [("public", &ip_filters.public), ("admin", &ip_filters.admin)] {
+        let _ = writeln!(out, "fe_lock_ip_rejected_total{{surface=\"{}\"}} {}", surface, filter.rejected());
+    }
+}
+
+fn render_request_timings(out: &mut String, request_timings: &RequestTimings) {
+    let routes = request_timings.snapshot();
+    if routes.is_empty() {
+        return;
+    }
+    header(
+        out,
+        "fe_lock_request_duration_seconds",
+        "histogram",
+        "Time to handle API requests, by route",
+    );
+    for route in &routes {
+        let labels = format!("route=\"{}\"", escape(&route.route));
+        histogram_series(out, "fe_lock_request_duration_seconds", &labels, &route.total);
+    }
+    header(
+        out,
+        "fe_lock_request_stage_seconds",
+        "histogram",
+        "Time spent in each stage of handling API requests (queue, deserialize, storage, serialize, other), by route",
+    );
+    for route in &routes {
+        for (stage, histogram) in &route.stages {
+            let labels = format!("route=\"{}\",stage=\"{}\"", escape(&route.route), stage);
+            histogram_series(out, "fe_lock_request_stage_seconds", &labels, histogram);
+        }
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn histogram(out: &mut String, name: &str, help: &str, histogram: &Histogram) {
+    header(out, name, "histogram", help);
+    for bucket in &histogram.buckets {
+        let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, bucket.le, bucket.count);
+    }
+    let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, histogram.count);
+    let _ = writeln!(out, "{}_sum {}", name, histogram.sum);
+    let _ = writeln!(out, "{}_count {}", name, histogram.count);
+}
+
+/// 带标签的直方图序列，标签为已转义的 `name="value"` 列表
+fn histogram_series(out: &mut String, name: &str, labels: &str, histogram: &Histogram) {
+    for bucket in &histogram.buckets {
+        let _ = writeln!(out, "{}_bucket{{{},le=\"{}\"}} {}", name, labels, bucket.le, bucket.count);
+    }
+    let _ = writeln!(out, "{}_bucket{{{},le=\"+Inf\"}} {}", name, labels, histogram.count);
+    let _ = writeln!(out, "{}_sum{{{}}} {}", name, labels, histogram.sum);
+    let _ = writeln!(out, "{}_count{{{}}} {}", name, labels, histogram.count);
+}
+
+/// 转义标签值中的反斜杠、双引号和换行
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
@@ -0,0 +1,198 @@
+//! 存储迁移命令 `fe-lock-service migrate --from <后端> --to <后端> [--dry-run]`
+//!
+//! 从源后端读取全部锁和命名空间策略写入目标后端，锁保留 lock_id、加锁时间、心跳时间和版本号，剩余超时时间不变，
+//! 写入规则与管理接口的导入相同。后端有三种写法：
+//! - `file:<路径>`：内存存储的持久化文件，格式按 `MEMORY_PERSIST_FORMAT` 写入，读取时自动识别
+//! - `s3://bucket/prefix`：对象存储上的持久化快照，端点和凭证沿用 `MEMORY_PERSIST_S3_*`、`AWS_*` 环境变量
+//! - `shards:<目录或 s3://bucket/prefix>`：按命名空间分片的持久化快照
+//! - `redis://...`：Redis，`REDIS_USERNAME`、`REDIS_PASSWORD`、`REDIS_DB`、`REDIS_KEY_PREFIX`、`REDIS_KEY_TENANT` 沿用环境变量
+//! - `http://...`：运行中的服务，经管理接口导出和导入，`ADMIN_TOKEN` 沿用环境变量，Raft 集群通过该方式迁移
+
+use crate::admin;
+use crate::config::Config;
+use crate::models::{ImportReport, LockExport};
+use crate::storage::encryption::SnapshotCipher;
+use crate::storage::memory::MemoryStorage;
+use crate::storage::persist::PersistTarget;
+use crate::storage::redis::{RedisOptions, RedisStorage};
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::Utc;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+const USAGE: &str = "usage: fe-lock-service migrate --from <backend> --to <backend> [--dry-run]\n\
+backend: file:<path> | s3://bucket/prefix | shards:<dir|s3://bucket/prefix> | redis://... | http://<service>";
+
+enum Backend {
+    Snapshot { spec: String, sharded: bool }, // 内存存储的持久化位置，本地路径或 s3://
+    Redis(String),
+    Service(String),
+}
+
+impl Backend {
+    fn parse(spec: &str) -> Result<Self> {
+        if let Some(path) = spec.strip_prefix("file:") {
+            Ok(Backend::Snapshot {
+                spec: path.to_string(),
+                sharded: false,
+            })
+        } else if let Some(path) = spec.strip_prefix("shards:") {
+            Ok(Backend::Snapshot {
+                spec: path.to_string(),
+                sharded: true,
+            })
+        } else if spec.starts_with("s3://") {
+            Ok(Backend::Snapshot {
+                spec: spec.to_string(),
+                sharded: false,
+            })
+        } else if spec.starts_with("redis://") || spec.starts_with("rediss://") {
+            Ok(Backend::Redis(spec.to_string()))
+        } else if spec.starts_with("http://") || spec.starts_with("https://") {
+            Ok(Backend::Service(spec.trim_end_matches('/').to_string()))
+        } else {
+            bail!("unknown backend {}\n{}", spec, USAGE)
+        }
+    }
+}
+
+struct MigrateArgs {
+    from: Backend,
+    to: Backend,
+    dry_run: bool,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<MigrateArgs> {
+    let (mut from, mut to, mut dry_run) = (None, None, false);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--from" => from = args.next(),
+            "--to" => to = args.next(),
+            "--dry-run" => dry_run = true,
+            _ => bail!("unexpected argument {}\n{}", arg, USAGE),
+        }
+    }
+    let from = from.ok_or_else(|| anyhow!("--from is required\n{}", USAGE))?;
+    let to = to.ok_or_else(|| anyhow!("--to is required\n{}", USAGE))?;
+    Ok(MigrateArgs {
+        from: Backend::parse(&from)?,
+        to: Backend::parse(&to)?,
+        dry_run,
+    })
+}
+
+/// 执行迁移，args 为 `migrate` 之后的命令行参数
+pub async fn run(config: &Config, args: impl Iterator<Item = String>) -> Result<ImportReport> {
+    let args = parse_args(args)?;
+    let export = read(config, &args.from).await.context("failed to read source backend")?;
+    log::info!(
+        "[MIGRATE] Read {} locks and {} namespaces from source",
+        export.locks.len(),
+        export.namespaces.len()
+    );
+
+    let report = write(config, &args.to, export, args.dry_run)
+        .await
+        .context("failed to write destination backend")?;
+    log::info!(
+        "[MIGRATE] Migration finished - dry_run: {}, imported: {}, unchanged: {}, expired: {}, conflicts: {}, namespaces: {}",
+        report.dry_run,
+        report.imported,
+        report.unchanged,
+        report.expired,
+        report.conflicts.len(),
+        report.namespaces
+    );
+    Ok(report)
+}
+
+async fn read(config: &Config, backend: &Backend) -> Result<LockExport> {
+    match backend {
+        Backend::Snapshot { spec, sharded } => {
+            let storage = snapshot_storage(config, spec, *sharded)?;
+            if !storage.snapshot_exists().await? {
+                bail!("persistence file {} not found", spec);
+            }
+            storage.load_from_disk().await?;
+            admin::export(&storage).await
+        }
+        Backend::Redis(url) => admin::export(&redis_storage(config, url).await?).await,
+        Backend::Service(url) => {
+            let request = reqwest::Client::new().get(format!("{}/api/admin/export", url));
+            service_reply(http_client(config, request)).await
+        }
+    }
+}
+
+async fn write(
+    config: &Config,
+    backend: &Backend,
+    export: LockExport,
+    dry_run: bool,
+) -> Result<ImportReport> {
+    let mut report = ImportReport {
+        dry_run,
+        ..ImportReport::default()
+    };
+    match backend {
+        Backend::Snapshot { spec, sharded } => {
+            // 与文件中已有的锁合并
+            let storage = snapshot_storage(config, spec, *sharded)?;
+            storage.load_from_disk().await?;
+            admin::import(&storage, export.locks, export.namespaces, &mut report, Utc::now()).await?;
+            if !dry_run {
+                storage.persist_to_disk().await?;
+            }
+            Ok(report)
+        }
+        Backend::Redis(url) => {
+            let storage = redis_storage(config, url).await?;
+            admin::import(&storage, export.locks, export.namespaces, &mut report, Utc::now()).await?;
+            Ok(report)
+        }
+        Backend::Service(url) => {
+            let request = reqwest::Client::new()
+                .post(format!("{}/api/admin/import", url))
+                .query(&[("dry_run", dry_run)])
+                .json(&export);
+            service_reply(http_client(config, request)).await
+        }
+    }
+}
+
+fn snapshot_storage(config: &Config, spec: &str, sharded: bool) -> Result<MemoryStorage> {
+    let target = PersistTarget::parse(spec, config)?;
+    let cipher = SnapshotCipher::from_config(config)?;
+    let storage = MemoryStorage::with_persistence(target, config.memory_persist_format.clone(), 0);
+    Ok(storage.with_encryption(cipher).with_sharding(sharded))
+}
+
+async fn redis_storage(config: &Config, url: &str) -> Result<RedisStorage> {
+    RedisStorage::new(RedisOptions::from_config(config, url)).await
+}
+
+pub(crate) fn http_client(config: &Config, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+    match &config.admin_token {
+        Some(token) => request.bearer_auth(token),
+        None => request,
+    }
+}
+
+#[derive(Deserialize)]
+struct ServiceReply<T> {
+    code: i32,
+    message: String,
+    data: Option<T>,
+}
+
+pub(crate) async fn service_reply<T: DeserializeOwned>(request: reqwest::RequestBuilder) -> Result<T> {
+    let response = request.send().await?;
+    let status = response.status();
+    let reply: ServiceReply<T> = response
+        .json()
+        .await
+        .with_context(|| format!("unexpected response (HTTP {})", status))?;
+    reply
+        .data
+        .ok_or_else(|| anyhow!("service returned error {}: {}", reply.code, reply.message))
+}
@@ -1,7 +1,18 @@
+use crate::clock;
+use crate::advisory;
+use crate::codec;
+use crate::config::{AdminRole, Config, HttpStatusMode};
+use crate::events::LockEventType;
+use crate::oidc::AdminSession;
+use crate::presence;
+use crate::storage::timeout::StorageTimeout;
+use actix_web::body::BoxBody;
+use actix_web::http::{header, StatusCode};
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use utoipa::ToSchema;
-use uuid::Uuid;
+use std::collections::BTreeMap;
+use utoipa::{IntoParams, ToSchema};
 
 fn default_namespace() -> String {
     "default".to_string()
@@ -19,36 +30,2139 @@ pub struct AcquireLockRequest {
     pub user_name: String,
     #[schema(example = "order_001")]
     pub business_id: String,
+    /// 超时时间（秒），不传时使用命名空间或全局的默认超时
     #[schema(example = 60)]
-    pub timeout: u64, // 超时时间（秒）
+    pub timeout: Option<u64>,
+    /// 永久锁，不因心跳中断而过期，只能由持有人释放或管理员强制释放，不能与 `timeout` 同时指定
+    #[serde(default)]
+    pub permanent: bool,
+    /// 最长持有时间（秒），从获取锁开始计算，到期后即使仍在心跳也会过期，不传则不限制
+    #[schema(example = 3600)]
+    pub max_hold_seconds: Option<u64>,
+    /// 所属会话，锁随会话心跳续期、会话结束时释放，超时时间取会话的超时时间
+    #[schema(example = "3f2b8c1e-7a4d-4e1b-9c2f-5d6e7f8a9b0c")]
+    pub session_id: Option<String>,
+    /// 排队优先级（-100 到 100，默认 0），锁空闲时有效优先级更高的等待者先获取
+    #[serde(default)]
+    #[schema(example = 10)]
+    pub priority: i32,
+    /// 抢占被他人持有的锁，仅 `LOCK_PREEMPTORS` 中的用户可用，持有人在宽限期后失去锁
+    #[serde(default)]
+    pub preempt: bool,
+    /// 抢占的宽限期（秒），不传时使用 `LOCK_PREEMPT_GRACE`
+    #[schema(example = 30)]
+    pub grace_seconds: Option<u64>,
+    /// 条件获取：仅当锁由该用户持有且已过期时接管，锁空闲时直接获取
+    #[schema(example = "user456")]
+    pub if_holder_is: Option<String>,
+    /// 条件获取：仅当锁的版本号一致且已过期时接管，锁空闲时直接获取
+    #[schema(example = 1)]
+    pub if_version: Option<u64>,
+    /// 持有原因，申请被拒绝的用户会在冲突响应中看到，例如"月末结账中，请勿打断"
+    #[schema(example = "月末结账中，请勿打断")]
+    pub reason: Option<String>,
+    /// 客户端主机名，与服务端识别的客户端 IP 一起保存在锁信息中
+    #[schema(example = "batch-worker-03")]
+    pub hostname: Option<String>,
+    /// 附加信息，例如文档标题、页面地址、工单号
+    #[serde(default)]
+    #[schema(example = json!({"title": "2024 年度预算", "url": "https://example.com/doc/1"}))]
+    pub metadata: BTreeMap<String, String>,
+    /// 标签，用于按组查询和释放锁
+    #[serde(default)]
+    #[schema(example = json!(["release-freeze"]))]
+    pub tags: Vec<String>,
+    /// 剩余有效时间不足该秒数且没有心跳时发送一次过期提醒，不传则不提醒
+    #[schema(example = 10)]
+    pub warn_before_seconds: Option<u64>,
+    /// 过期提醒回调地址，服务端以 POST 发送 `expiring_soon` 锁事件；不传时只发布到事件流
+    #[schema(example = "https://example.com/lock-expiring")]
+    pub warning_callback_url: Option<String>,
+    /// 预计归还时间（秒）：获取锁后应在该时间内释放，超过后发布 `overdue` 锁事件并列入逾期报告，不影响锁的过期
+    #[schema(example = 86400)]
+    pub due_in_seconds: Option<u64>,
+}
+
+/// 每个锁最多的标签数量
+pub const MAX_TAGS: usize = 16;
+/// 单个标签的最大长度
+pub const MAX_TAG_LEN: usize = 64;
+
+impl AcquireLockRequest {
+    /// 附加信息的总字节数（键和值）
+    pub fn metadata_size(&self) -> usize {
+        self.metadata
+            .iter()
+            .map(|(key, value)| key.len() + value.len())
+            .sum()
+    }
+
+    /// 条件获取的条件，未指定 `if_holder_is` 和 `if_version` 时为 None
+    pub fn condition(&self) -> Option<LockCondition> {
+        (self.if_holder_is.is_some() || self.if_version.is_some()).then(|| LockCondition {
+            holder: self.if_holder_is.clone(),
+            version: self.if_version,
+        })
+    }
+}
+
+/// 条件获取的条件，锁已过期且持有人、版本号与条件一致时才能接管
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LockCondition {
+    pub holder: Option<String>,
+    pub version: Option<u64>,
+}
+
+impl LockCondition {
+    /// 截至 now 能否接管 lock_info
+    pub fn matches(&self, lock_info: &LockInfo, now: DateTime<Utc>) -> bool {
+        lock_info.is_expired_at(now)
+            && self.holder.as_ref().is_none_or(|holder| *holder == lock_info.user_id)
+            && self.version.is_none_or(|version| version == lock_info.version)
+    }
 }
 
 /// 申请锁成功响应
-#[derive(Debug, Deserialize, Serialize, ToSchema)]
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
 pub struct AcquireLockSuccess {
     #[schema(example = "550e8400-e29b-41d4-a716-446655440000")]
     pub lock_id: String,
+    /// 不再心跳时锁的过期时间（服务端时间），永久锁为 null
+    pub expires_at: Option<DateTime<Utc>>,
+    /// 距离过期的剩余秒数，永久锁为 null
+    #[schema(example = 60)]
+    pub remaining_seconds: Option<u64>,
+    /// 锁的版本号，用于条件获取和条件释放
+    #[schema(example = 1)]
+    pub version: u64,
+    /// 提示性命名空间中该业务键的所有当前持有人（包括申请人），按获取时间排列
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub holders: Option<Vec<AdvisoryHolder>>,
+    /// 预计归还时间，申请时指定了 `due_in_seconds` 时返回
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub due_at: Option<DateTime<Utc>>,
+    /// 建议的心跳间隔（毫秒），按超时时间计算并随机浮动，永久锁为 null
+    #[schema(example = 19800)]
+    pub heartbeat_interval_ms: Option<u64>,
+    /// 申请人已持有该锁，返回的是已有的锁，只用于区分申请结果，不输出
+    #[serde(skip)]
+    pub reentrant: bool,
+}
+
+impl AcquireLockSuccess {
+    /// 以 now 计算剩余有效时间
+    pub fn new(lock_info: &LockInfo, now: DateTime<Utc>) -> Self {
+        Self {
+            lock_id: lock_info.lock_id.clone(),
+            version: lock_info.version,
+            expires_at: lock_info.expires_at(),
+            remaining_seconds: lock_info.remaining_secs_at(now),
+            holders: None,
+            due_at: lock_info.due_at,
+            heartbeat_interval_ms: None,
+            reentrant: false,
+        }
+    }
+}
+
+/// 申请锁的结果 `POST /api/lock/acquire-outcome`，以 `outcome` 区分获取、重入、冲突和排队，
+/// 生成的客户端无需根据错误码判断；其他失败仍以错误码返回
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum AcquireOutcome {
+    /// 获取了新的锁
+    Granted(AcquireLockSuccess),
+    /// 申请人已持有该锁，返回已有的锁
+    Reentrant(AcquireLockSuccess),
+    /// 锁被他人持有，申请人未进入等待队列
+    Conflict(AcquireConflict),
+    /// 申请人已进入等待队列
+    Queued(AcquireQueued),
+}
+
+/// 与申请冲突的持有人
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AcquireHolder {
+    #[schema(example = "user123")]
+    pub user_id: String,
+    #[schema(example = "张三")]
+    pub user_name: String,
+    /// 冲突的锁，层级路径冲突和跨命名空间冲突时与申请的锁不同
+    #[schema(example = "order:order_001")]
+    pub lock_key: String,
+    pub locked_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// 持有人停止心跳后锁的剩余有效时间（秒，含宽限期），永久锁为 null
+    pub remaining_seconds: Option<u64>,
+}
+
+impl AcquireHolder {
+    pub fn new(lock_info: &LockInfo, now: DateTime<Utc>) -> Self {
+        Self {
+            user_id: lock_info.user_id.clone(),
+            user_name: lock_info.user_name.clone(),
+            lock_key: lock_info.get_lock_key(),
+            locked_at: lock_info.locked_at,
+            reason: lock_info.reason.clone(),
+            remaining_seconds: lock_info.deadline_secs_at(now),
+        }
+    }
+}
+
+/// 锁被他人持有
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AcquireConflict {
+    /// 申请接口返回的错误码：锁已被占用、层级路径冲突或业务 ID 在其他命名空间中被持有
+    #[schema(example = 1001)]
+    pub code: i32,
+    #[schema(example = "Lock already held by 张三")]
+    pub message: String,
+    pub holder: AcquireHolder,
+}
+
+/// 申请人已进入等待队列，锁释放后可重新申请
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AcquireQueued {
+    /// 申请接口返回的错误码：锁已被占用，或锁空闲但保留给优先级更高的等待者
+    #[schema(example = 1001)]
+    pub code: i32,
+    #[schema(example = "Lock already held by 张三")]
+    pub message: String,
+    /// 当前持有人，锁保留给优先级更高的等待者时为 null
+    pub holder: Option<AcquireHolder>,
+    /// 排在前面的等待人数
+    #[schema(example = 2)]
+    pub waiters_ahead: usize,
+    /// 预计等待时间（秒），无持有人或持有人为永久锁时为 null
+    pub estimated_wait_seconds: Option<u64>,
+}
+
+/// 提示性命名空间中业务键的一个持有人
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+pub struct AdvisoryHolder {
+    #[schema(example = "user123")]
+    pub user_id: String,
+    #[schema(example = "张三")]
+    pub user_name: String,
+    pub locked_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// 持有人申请时的附加信息，可用于区分查看和编辑等状态
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl From<&LockInfo> for AdvisoryHolder {
+    fn from(lock_info: &LockInfo) -> Self {
+        Self {
+            user_id: lock_info.user_id.clone(),
+            user_name: lock_info.user_name.clone(),
+            locked_at: lock_info.locked_at,
+            reason: lock_info.reason.clone(),
+            metadata: lock_info.metadata.clone(),
+        }
+    }
+}
+
+/// 申请锁失败时的持有人和排队信息（错误扩展字段）
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct AcquireLockFailure {
+    pub current_holder: String,
+    pub locked_at: DateTime<Utc>,
+    /// 持有人申请锁时填写的原因
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// 持有人停止心跳后锁的剩余有效时间（秒，含宽限期），永久锁为 null
+    pub remaining_seconds: Option<u64>,
+    /// 排在前面的等待人数，命名空间不允许排队时不返回
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub waiters_ahead: Option<usize>,
+    /// 预计等待时间（秒）：持有人的剩余时间加上前面每位等待者按持有人超时时间计，持有人为永久锁时为 null
+    pub estimated_wait_seconds: Option<u64>,
+}
+
+/// 申请锁预检结果
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AcquireCheck {
+    /// 此时以相同参数申请锁是否会成功
+    pub can_acquire: bool,
+    /// 申请人已持有该锁，申请按重入处理并返回已有的锁
+    pub reentrant: bool,
+    /// 申请不会成功时申请接口将返回的错误码
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = 1001)]
+    pub code: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "Lock already held by 张三")]
+    pub message: Option<String>,
+    /// 阻止申请的锁：当前持有人、层级路径上冲突的锁或不满足接管条件的锁
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub holder: Option<LockInfo>,
+}
+
+/// 申请锁会形成死锁时的等待环（错误扩展字段）
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct DeadlockDetected {
+    /// 环上依次等待的锁，第一个为本次申请的锁，最后一个由申请人持有
+    #[schema(example = json!(["order:order_001", "stock:sku_42"]))]
+    pub cycle: Vec<String>,
+}
+
+/// 抢占已登记、等待宽限期结束（错误扩展字段）
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct PreemptionPending {
+    /// 锁转移后抢占人持有的 lock_id，同一抢占重复申请时不变
+    #[schema(example = "550e8400-e29b-41d4-a716-446655440000")]
+    pub lock_id: String,
+    pub current_holder: String,
+    /// 锁转移给抢占人的时间，持有人提前释放时会更早获得锁
+    pub deadline: DateTime<Utc>,
+}
+
+/// 其他命名空间中持有的相同 business_id 的锁（错误扩展字段）
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct BusinessIdConflict {
+    /// 持有该 business_id 的命名空间
+    #[schema(example = "order-legacy")]
+    pub namespace: String,
+    pub current_holder: String,
+    pub locked_at: DateTime<Utc>,
+    /// 持有人申请锁时填写的原因
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// 持有人停止心跳后锁的剩余有效时间（秒，含宽限期），永久锁为 null
+    pub remaining_seconds: Option<u64>,
+}
+
+/// 层级路径上他人持有的锁（错误扩展字段）
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct PathConflict {
+    /// 冲突的锁所在路径，为申请路径的祖先或后代
+    #[schema(example = "project/42")]
+    pub business_id: String,
+    pub current_holder: String,
+    pub locked_at: DateTime<Utc>,
+    /// 持有人申请锁时填写的原因
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// 持有人停止心跳后锁的剩余有效时间（秒，含宽限期），永久锁为 null
+    pub remaining_seconds: Option<u64>,
+}
+
+/// 条件获取不满足条件时的当前锁（错误扩展字段）
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct LockConditionFailed {
+    /// 当前持有人的 user_id，可用作 `if_holder_is`
+    #[schema(example = "user456")]
+    pub user_id: String,
+    pub current_holder: String,
+    /// 当前锁的版本号，可用作 `if_version`
+    #[schema(example = 1)]
+    pub version: u64,
+    /// 持有人停止心跳后锁的剩余有效时间（秒，含宽限期），已过期时为 0，永久锁为 null
+    pub remaining_seconds: Option<u64>,
+}
+
+/// 心跳请求
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct HeartbeatRequest {
+    #[schema(example = "550e8400-e29b-41d4-a716-446655440000")]
+    pub lock_id: String,
+    /// 仅当锁的版本号一致时续期，用于发现锁已被他人修改
+    #[schema(example = 1)]
+    pub if_version: Option<u64>,
+}
+
+/// 心跳成功响应
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct HeartbeatSuccess {
+    pub updated: bool,
+    /// 锁被抢占时返回，持有人应在 deadline 前保存并释放锁
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preemption: Option<PreemptionNotice>,
+    /// 不再心跳时锁的过期时间（服务端时间），永久锁为 null
+    pub expires_at: Option<DateTime<Utc>>,
+    /// 距离过期的剩余秒数，永久锁为 null
+    #[schema(example = 60)]
+    pub remaining_seconds: Option<u64>,
+    /// 续期后锁的版本号
+    #[schema(example = 2)]
+    pub version: u64,
+    /// 建议的心跳间隔（毫秒），按超时时间计算并随机浮动，永久锁为 null
+    #[schema(example = 19800)]
+    pub heartbeat_interval_ms: Option<u64>,
+    /// 上次心跳以来的待处理通知，每条只返回一次
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub notices: Vec<LockNotice>,
+}
+
+impl HeartbeatSuccess {
+    /// 以 now 计算剩余有效时间
+    pub fn new(lock_info: &LockInfo, now: DateTime<Utc>) -> Self {
+        Self {
+            updated: true,
+            version: lock_info.version,
+            preemption: lock_info.preemption.clone(),
+            expires_at: lock_info.expires_at(),
+            remaining_seconds: lock_info.remaining_secs_at(now),
+            heartbeat_interval_ms: None,
+            notices: Vec::new(),
+        }
+    }
+}
+
+/// 心跳附带的通知类型
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LockNoticeType {
+    /// 锁被抢占，见 preemption
+    PreemptionPending,
+    /// 有人请求接管，见 takeover
+    TakeoverRequested,
+    /// 锁即将过期，见 expires_at
+    ExpiringSoon,
+    /// 命名空间被冻结，见 freeze
+    NamespaceFrozen,
+}
+
+/// 心跳附带的通知
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct LockNotice {
+    #[serde(rename = "type")]
+    pub notice_type: LockNoticeType,
+    /// 通知产生的时间
+    pub at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preemption: Option<PreemptionNotice>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub takeover: Option<TakeoverRequest>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub freeze: Option<NamespaceFreeze>,
+}
+
+impl LockNotice {
+    pub fn new(notice_type: LockNoticeType, at: DateTime<Utc>) -> Self {
+        Self {
+            notice_type,
+            at,
+            preemption: None,
+            takeover: None,
+            expires_at: None,
+            freeze: None,
+        }
+    }
+}
+
+/// 释放锁请求，lock_id 与 namespace + business_id + user_id 二选一
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct ReleaseLockRequest {
+    #[schema(example = "550e8400-e29b-41d4-a716-446655440000")]
+    pub lock_id: Option<String>,
+    #[serde(default = "default_namespace")]
+    #[schema(example = "default")]
+    pub namespace: String,
+    #[schema(example = "order_001")]
+    pub business_id: Option<String>,
+    /// 按业务键释放时校验的持有人
+    #[schema(example = "user123")]
+    pub user_id: Option<String>,
+    /// 条件释放：仅当锁的版本号一致时释放
+    #[schema(example = 1)]
+    pub if_version: Option<u64>,
+}
+
+/// 借出请求：获取锁并登记预计归还时间，默认获取永久锁，不需要心跳，归还时调用 `/api/lock/checkin`
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct CheckoutRequest {
+    #[serde(default = "default_namespace")]
+    #[schema(example = "assets")]
+    pub namespace: String,
+    #[schema(example = "user123")]
+    pub user_id: String,
+    #[schema(example = "张三")]
+    pub user_name: String,
+    #[schema(example = "banner_2024.psd")]
+    pub business_id: String,
+    /// 预计归还时间（秒），超过后仍未归还视为逾期
+    #[schema(example = 86400)]
+    pub due_in_seconds: u64,
+    /// 超时时间（秒），指定时借出的锁需要心跳，不传则为永久锁
+    #[schema(example = 600)]
+    pub timeout: Option<u64>,
+    /// 借出原因，申请被拒绝的用户会在冲突响应中看到
+    #[schema(example = "修改活动横幅")]
+    pub reason: Option<String>,
+    /// 客户端主机名
+    pub hostname: Option<String>,
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl CheckoutRequest {
+    /// 对应的申请锁请求
+    pub fn acquire_request(&self) -> AcquireLockRequest {
+        AcquireLockRequest {
+            namespace: self.namespace.clone(),
+            user_id: self.user_id.clone(),
+            user_name: self.user_name.clone(),
+            business_id: self.business_id.clone(),
+            timeout: self.timeout,
+            permanent: self.timeout.is_none(),
+            max_hold_seconds: None,
+            session_id: None,
+            priority: 0,
+            preempt: false,
+            grace_seconds: None,
+            if_holder_is: None,
+            if_version: None,
+            reason: self.reason.clone(),
+            hostname: self.hostname.clone(),
+            metadata: self.metadata.clone(),
+            tags: self.tags.clone(),
+            warn_before_seconds: None,
+            warning_callback_url: None,
+            due_in_seconds: Some(self.due_in_seconds),
+        }
+    }
+}
+
+/// 归还结果
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CheckinSuccess {
+    pub released: bool,
+    #[schema(example = 1)]
+    pub version: u64,
+    /// 借出时间
+    pub locked_at: DateTime<Utc>,
+    /// 预计归还时间，借出时未登记时为 null
+    pub due_at: Option<DateTime<Utc>>,
+    /// 归还时超过预计归还时间的秒数，按时归还为 0
+    #[schema(example = 0)]
+    pub overdue_seconds: u64,
+}
+
+impl CheckinSuccess {
+    /// 在 now 归还 lock_info
+    pub fn new(lock_info: &LockInfo, now: DateTime<Utc>) -> Self {
+        Self {
+            released: true,
+            version: lock_info.version,
+            locked_at: lock_info.locked_at,
+            due_at: lock_info.due_at,
+            overdue_seconds: lock_info.overdue_secs_at(now),
+        }
+    }
+}
+
+/// 锁校验请求：资源服务在写入前向锁服务确认客户端声称持有的锁。lock_id 每次获取锁时重新生成，可用作防护令牌（fencing token）
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct VerifyLockRequest {
+    #[schema(example = "550e8400-e29b-41d4-a716-446655440000")]
+    pub lock_id: String,
+    #[serde(default = "default_namespace")]
+    #[schema(example = "default")]
+    pub namespace: String,
+    #[schema(example = "order_001")]
+    pub business_id: String,
+    /// 客户端声称的持有人，不指定时不校验持有人
+    #[schema(example = "user123")]
+    pub user_id: Option<String>,
+}
+
+/// 锁校验未通过的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum VerifyFailure {
+    /// lock_id 不存在：锁已释放、已被清理或从未获取
+    NotFound,
+    /// 锁存在，但不是请求中 namespace + business_id 的锁
+    KeyMismatch,
+    /// 锁的持有人不是请求中的 user_id
+    UserMismatch,
+    /// 锁已过期（含宽限期），等待清理
+    Expired,
+}
+
+/// 锁校验结果，未通过时不返回锁的其他信息
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LockVerification {
+    /// 锁当前有效且由声称的持有人持有
+    pub valid: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<VerifyFailure>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "user123")]
+    pub user_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = 1)]
+    pub version: Option<u64>,
+    /// 持有人应在此前心跳，永久锁不返回
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+    /// 距离心跳截止的剩余秒数，永久锁不返回
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = 25)]
+    pub remaining_seconds: Option<u64>,
+    /// 锁已被抢占，持有人即将失去锁，写入方可据此拒绝耗时较长的操作
+    pub preemption_pending: bool,
+}
+
+impl LockVerification {
+    /// 在 now 以 lock_info（按 lock_id 查到的锁）校验 req
+    pub fn check(req: &VerifyLockRequest, lock_info: Option<&LockInfo>, now: DateTime<Utc>) -> Self {
+        let failure = match lock_info {
+            None => Some(VerifyFailure::NotFound),
+            Some(lock_info) if !lock_info.matches_key(&req.namespace, &req.business_id) => {
+                Some(VerifyFailure::KeyMismatch)
+            }
+            Some(lock_info) if req.user_id.as_ref().is_some_and(|user_id| *user_id != lock_info.user_id) => {
+                Some(VerifyFailure::UserMismatch)
+            }
+            Some(lock_info) if lock_info.is_expired_at(now) => Some(VerifyFailure::Expired),
+            Some(_) => None,
+        };
+        match (failure, lock_info) {
+            (None, Some(lock_info)) => Self {
+                valid: true,
+                reason: None,
+                user_id: Some(lock_info.user_id.clone()),
+                version: Some(lock_info.version),
+                expires_at: lock_info.expires_at(),
+                remaining_seconds: lock_info.remaining_secs_at(now),
+                preemption_pending: lock_info.preemption.is_some(),
+            },
+            (failure, _) => Self {
+                valid: false,
+                reason: failure,
+                user_id: None,
+                version: None,
+                expires_at: None,
+                remaining_seconds: None,
+                preemption_pending: false,
+            },
+        }
+    }
+}
+
+/// 排空请求：将用户（或其某个会话）的锁标记为排空，持有人完成工作后逐个释放，截止时间到达时由服务端释放
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct GracefulReleaseRequest {
+    #[schema(example = "worker-7")]
+    pub user_id: String,
+    /// 只排空该会话中的锁，不指定时排空用户持有的所有锁
+    #[schema(example = "b3f1c2d4-5e6f-4a7b-8c9d-0e1f2a3b4c5d")]
+    pub session_id: Option<String>,
+    /// 排空时长（秒），不传时使用 `LOCK_DRAIN_TIMEOUT`
+    #[schema(example = 30)]
+    pub drain_seconds: Option<u64>,
+}
+
+/// 排空结果
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GracefulReleaseStarted {
+    /// 排空中的锁数量
+    #[schema(example = 3)]
+    pub draining: usize,
+    /// 截止时间，届时仍未释放的锁由服务端释放
+    pub deadline: DateTime<Utc>,
+    pub locks: Vec<LockInfo>,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct ReleaseAllRequest {
+    #[schema(example = "user123")]
+    pub user_id: String,
+}
+
+/// 强制释放锁请求，lock_id 与 namespace + business_id 二选一
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct ForceReleaseRequest {
+    #[schema(example = "550e8400-e29b-41d4-a716-446655440000")]
+    pub lock_id: Option<String>,
+    #[serde(default = "default_namespace")]
+    #[schema(example = "default")]
+    pub namespace: String,
+    #[schema(example = "order_001")]
+    pub business_id: Option<String>,
+}
+
+/// 预约锁请求，在 `acquire_at` 开始的 `hold_for` 秒时间窗内独占锁
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct ReserveLockRequest {
+    #[serde(default = "default_namespace")]
+    #[schema(example = "default")]
+    pub namespace: String,
+    #[schema(example = "user123")]
+    pub user_id: String,
+    #[schema(example = "张三")]
+    pub user_name: String,
+    #[schema(example = "order_001")]
+    pub business_id: String,
+    /// 开始时间（服务端时间）
+    #[schema(example = "2024-01-01T02:00:00Z")]
+    pub acquire_at: DateTime<Utc>,
+    /// 持有时长（秒），同时作为锁的超时时间，受命名空间和全局最大超时限制
+    #[schema(example = 3600)]
+    pub hold_for: u64,
+    /// 持有原因，授予后展示给申请被拒绝的用户
+    #[schema(example = "数据库维护窗口")]
+    pub reason: Option<String>,
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl ReserveLockRequest {
+    /// 附加信息的总字节数（键和值）
+    pub fn metadata_size(&self) -> usize {
+        self.metadata
+            .iter()
+            .map(|(key, value)| key.len() + value.len())
+            .sum()
+    }
+}
+
+/// 锁预约
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Reservation {
+    /// 授予后锁的 lock_id，也用于取消预约
+    #[schema(example = "550e8400-e29b-41d4-a716-446655440000")]
+    pub lock_id: String,
+    pub namespace: String,
+    pub user_id: String,
+    pub user_name: String,
+    pub business_id: String,
+    pub acquire_at: DateTime<Utc>,
+    /// 时间窗结束时间，到期仍未授予则放弃预约
+    pub ends_at: DateTime<Utc>,
+    pub reason: Option<String>,
+    pub metadata: BTreeMap<String, String>,
+    pub tags: Vec<String>,
+}
+
+impl Reservation {
+    pub fn new(lock_id: String, request: &ReserveLockRequest) -> Self {
+        Self {
+            lock_id,
+            namespace: request.namespace.clone(),
+            user_id: request.user_id.clone(),
+            user_name: request.user_name.clone(),
+            business_id: request.business_id.clone(),
+            acquire_at: request.acquire_at,
+            ends_at: seconds_after(request.acquire_at, request.hold_for),
+            reason: request.reason.clone(),
+            metadata: request.metadata.clone(),
+            tags: dedup_tags(&request.tags),
+        }
+    }
+
+    pub fn get_lock_key(&self) -> String {
+        format!("{}:{}", self.namespace, self.business_id)
+    }
+
+    /// 时间窗是否与另一个预约重叠
+    pub fn overlaps(&self, other: &Reservation) -> bool {
+        self.acquire_at < other.ends_at && other.acquire_at < self.ends_at
+    }
+
+    /// 在 now 授予时的锁，超时时间为时间窗剩余秒数
+    pub fn to_lock_info(&self, now: DateTime<Utc>) -> LockInfo {
+        let start = now.max(self.acquire_at);
+        let remaining_ms = (self.ends_at - start).num_milliseconds().max(0) as u64;
+        LockInfo {
+            lock_id: self.lock_id.clone(),
+            namespace: self.namespace.clone(),
+            user_id: self.user_id.clone(),
+            user_name: self.user_name.clone(),
+            business_id: self.business_id.clone(),
+            timeout: remaining_ms.div_ceil(1000).max(1),
+            locked_at: start,
+            last_heartbeat: start,
+            metadata: self.metadata.clone(),
+            tags: self.tags.clone(),
+            max_hold_seconds: None,
+            grace_seconds: 0,
+            preemption: None,
+            version: 1,
+            reason: self.reason.clone(),
+            client: None,
+            due_at: None,
+        }
+    }
+}
+
+/// 取消预约请求
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct CancelReservationRequest {
+    #[schema(example = "550e8400-e29b-41d4-a716-446655440000")]
+    pub lock_id: String,
+    /// 预约人，只能取消自己的预约
+    #[schema(example = "user123")]
+    pub user_id: String,
+}
+
+/// 预约列表查询参数
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ListReservationsQuery {
+    /// 按命名空间过滤
+    pub namespace: Option<String>,
+}
+
+/// 请求接管他人持有的锁
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct RequestTakeoverRequest {
+    #[serde(default = "default_namespace")]
+    #[schema(example = "default")]
+    pub namespace: String,
+    #[schema(example = "user456")]
+    pub user_id: String,
+    #[schema(example = "李四")]
+    pub user_name: String,
+    #[schema(example = "order_001")]
+    pub business_id: String,
+    /// 接管后锁的超时时间（秒），不传时按命名空间策略和全局配置取默认值
+    #[schema(example = 60)]
+    pub timeout: Option<u64>,
+    /// 给持有人的留言
+    #[schema(example = "需要修改第三章，可以先让给我吗？")]
+    pub message: Option<String>,
+    /// 客户端主机名
+    #[schema(example = "editor-pc-07")]
+    pub hostname: Option<String>,
+}
+
+impl RequestTakeoverRequest {
+    /// 批准后申请人持有的锁
+    pub fn to_lock_info(&self, lock_id: String, timeout: u64, client: ClientContext, now: DateTime<Utc>) -> LockInfo {
+        LockInfo {
+            lock_id,
+            namespace: self.namespace.clone(),
+            user_id: self.user_id.clone(),
+            user_name: self.user_name.clone(),
+            business_id: self.business_id.clone(),
+            timeout,
+            locked_at: now,
+            last_heartbeat: now,
+            metadata: BTreeMap::new(),
+            tags: Vec::new(),
+            max_hold_seconds: None,
+            grace_seconds: 0,
+            preemption: None,
+            version: 1,
+            reason: None,
+            client: Some(client),
+            due_at: None,
+        }
+    }
+}
+
+/// 持有人答复接管请求
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct RespondTakeoverRequest {
+    #[schema(example = "7c9e6679-7425-40de-944b-e07fc1f90ae7")]
+    pub request_id: String,
+    /// 持有人的 lock_id
+    #[schema(example = "550e8400-e29b-41d4-a716-446655440000")]
+    pub lock_id: String,
+    /// true 表示同意并将锁转移给申请人，false 表示拒绝
+    pub approve: bool,
+}
+
+/// 接管请求查询参数
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct TakeoverStatusQuery {
+    pub request_id: String,
+    /// 申请人，只能查询自己的请求
+    pub user_id: String,
+}
+
+/// 待答复的接管请求查询参数
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct PendingTakeoversQuery {
+    /// 持有人的 lock_id
+    pub lock_id: String,
+}
+
+/// 接管请求状态
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TakeoverState {
+    /// 等待持有人答复
+    Pending,
+    /// 持有人已同意，锁已转移给申请人
+    Approved,
+    /// 持有人已拒绝
+    Declined,
+    /// 超时未答复，或持有人已释放锁
+    Expired,
+}
+
+/// 接管请求
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct TakeoverRequest {
+    #[schema(example = "7c9e6679-7425-40de-944b-e07fc1f90ae7")]
+    pub request_id: String,
+    pub namespace: String,
+    pub business_id: String,
+    /// 当前持有人
+    pub holder_user_id: String,
+    pub holder_user_name: String,
+    /// 申请人
+    pub user_id: String,
+    pub user_name: String,
+    pub message: Option<String>,
+    pub state: TakeoverState,
+    pub created_at: DateTime<Utc>,
+    /// 超过该时间未答复则请求过期
+    pub expires_at: DateTime<Utc>,
+    /// 批准后申请人持有的锁
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lock_id: Option<String>,
+}
+
+impl TakeoverRequest {
+    pub fn get_lock_key(&self) -> String {
+        format!("{}:{}", self.namespace, self.business_id)
+    }
+}
+
+/// 登记在场请求，同一用户再次登记即刷新心跳
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct PresenceJoinRequest {
+    #[serde(default = "default_namespace")]
+    #[schema(example = "doc")]
+    pub namespace: String,
+    #[schema(example = "doc_001")]
+    pub business_id: String,
+    #[schema(example = "user456")]
+    pub user_id: String,
+    #[schema(example = "李四")]
+    pub user_name: String,
+    /// 超过该时间（秒）未再次登记即离开，不传时使用 `PRESENCE_TIMEOUT`，不能超过 `LOCK_MAX_TIMEOUT`
+    #[schema(example = 30)]
+    pub timeout: Option<u64>,
+    /// 附加信息，如光标位置，首次登记时保存
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl PresenceJoinRequest {
+    /// 在场记录
+    pub fn to_lock_info(&self, lock_id: String, timeout: u64, now: DateTime<Utc>) -> LockInfo {
+        LockInfo {
+            lock_id,
+            namespace: presence::namespace(&self.namespace),
+            user_id: self.user_id.clone(),
+            user_name: self.user_name.clone(),
+            business_id: advisory::holder_business_id(&self.business_id, &self.user_id),
+            timeout,
+            locked_at: now,
+            last_heartbeat: now,
+            metadata: self.metadata.clone(),
+            tags: Vec::new(),
+            max_hold_seconds: None,
+            grace_seconds: 0,
+            preemption: None,
+            version: 1,
+            reason: None,
+            client: None,
+            due_at: None,
+        }
+    }
+}
+
+/// 离开请求
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct PresenceLeaveRequest {
+    #[serde(default = "default_namespace")]
+    #[schema(example = "doc")]
+    pub namespace: String,
+    #[schema(example = "doc_001")]
+    pub business_id: String,
+    #[schema(example = "user456")]
+    pub user_id: String,
+}
+
+/// 在场者查询参数
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct PresenceListQuery {
+    /// 命名空间，默认 default
+    #[serde(default = "default_namespace")]
+    pub namespace: String,
+    /// 业务 ID
+    pub business_id: String,
+}
+
+/// 一个在场者
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+pub struct PresenceMember {
+    #[schema(example = "user456")]
+    pub user_id: String,
+    #[schema(example = "李四")]
+    pub user_name: String,
+    pub joined_at: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl From<&LockInfo> for PresenceMember {
+    fn from(lock_info: &LockInfo) -> Self {
+        Self {
+            user_id: lock_info.user_id.clone(),
+            user_name: lock_info.user_name.clone(),
+            joined_at: lock_info.locked_at,
+            last_seen: lock_info.last_heartbeat,
+            metadata: lock_info.metadata.clone(),
+        }
+    }
+}
+
+/// business_id 的在场者和当前持有的锁
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PresenceList {
+    /// 在场者，按登记时间排列
+    pub members: Vec<PresenceMember>,
+    /// 当前持有的锁，未被持有时为 null
+    pub lock: Option<LockInfo>,
+}
+
+/// 登记在场成功
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PresenceJoinSuccess {
+    /// 在场记录的 ID，也可用于 `/api/lock/heartbeat` 续期
+    #[schema(example = "550e8400-e29b-41d4-a716-446655440000")]
+    pub presence_id: String,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub members: Vec<PresenceMember>,
+    pub lock: Option<LockInfo>,
+}
+
+/// 创建会话请求
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct CreateSessionRequest {
+    #[schema(example = "user123")]
+    pub user_id: String,
+    /// 会话超时时间（秒），超过该时间未收到会话心跳则结束会话，不传时使用 `LOCK_DEFAULT_TIMEOUT`
+    #[schema(example = 60)]
+    pub timeout: Option<u64>,
+}
+
+/// 会话心跳或关闭请求
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct SessionRequest {
+    #[schema(example = "3f2b8c1e-7a4d-4e1b-9c2f-5d6e7f8a9b0c")]
+    pub session_id: String,
+}
+
+/// 会话信息
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct SessionInfo {
+    #[schema(example = "3f2b8c1e-7a4d-4e1b-9c2f-5d6e7f8a9b0c")]
+    pub session_id: String,
+    pub user_id: String,
+    pub timeout: u64,
+    pub created_at: DateTime<Utc>,
+    pub last_heartbeat: DateTime<Utc>,
+    /// 不再心跳时会话的结束时间
+    pub expires_at: DateTime<Utc>,
+    /// 会话中的锁
+    pub lock_ids: Vec<String>,
+}
+
+/// 锁状态查询参数
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct LockStatusQuery {
+    /// 命名空间，默认 default
+    #[serde(default = "default_namespace")]
+    pub namespace: String,
+    /// 业务 ID
+    pub business_id: String,
+}
+
+/// 锁等待队列中的一位等待者
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct QueuedWaiter {
+    /// 排队位置，从 1 开始，锁空闲时位置 1 的等待者优先获取
+    pub position: usize,
+    pub user_id: String,
+    pub user_name: String,
+    /// 申请时的优先级
+    pub priority: i32,
+    /// 有效优先级，为 priority 加上老化加成
+    pub effective_priority: i64,
+    /// 首次申请失败的时间
+    pub enqueued_at: DateTime<Utc>,
+    /// 最近一次申请的时间，超过 LOCK_WAITER_TTL 未再申请则移出队列
+    pub last_seen: DateTime<Utc>,
+}
+
+/// 放弃排队的查询参数
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct LeaveQueueQuery {
+    /// 命名空间，默认 default
+    #[serde(default = "default_namespace")]
+    pub namespace: String,
+    /// 业务 ID
+    pub business_id: String,
+    /// 放弃排队的用户
+    pub user_id: String,
+}
+
+/// 等待锁释放的查询参数
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct WaitReleaseQuery {
+    /// 命名空间，默认 default
+    #[serde(default = "default_namespace")]
+    pub namespace: String,
+    /// 业务 ID
+    pub business_id: String,
+    /// 最长等待时间（秒），默认 30，最大 300
+    #[serde(default = "default_wait_release_timeout")]
+    pub timeout: u64,
+}
+
+/// 锁状态
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LockStatus {
+    pub locked: bool,
+    pub lock: Option<LockInfo>,
+    /// 锁空闲时最近一次持有的结束记录，保留 `LOCK_TOMBSTONE_TTL` 秒
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tombstone: Option<Tombstone>,
+    /// 提示性命名空间中的所有当前持有人，此时 lock 始终为 null
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub holders: Option<Vec<AdvisoryHolder>>,
+    /// 锁在服务启动前获取且启动后尚未心跳，须在 confirm_before 前心跳确认，否则将被释放，见 `LOCK_RECOVERY_WINDOW`
+    pub unconfirmed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub confirm_before: Option<DateTime<Utc>>,
+    /// 锁的过期策略，锁被持有时返回
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expiry: Option<LockExpiry>,
+}
+
+/// 锁的过期策略：持有人应在 heartbeat_due 前心跳，锁在 expires_at 才过期、可被他人获取
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct LockExpiry {
+    /// 心跳超时（秒），永久锁为 0
+    #[schema(example = 60)]
+    pub timeout: u64,
+    /// 心跳超时后的宽限期（秒），宽限期内心跳仍然有效
+    #[schema(example = 6)]
+    pub grace_seconds: u64,
+    /// 心跳截止时间，取心跳超时和最长持有时间中较早的一个，永久锁为 null
+    pub heartbeat_due: Option<DateTime<Utc>>,
+    /// 最长持有时间的到期时间，不受宽限期影响
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hold_deadline: Option<DateTime<Utc>>,
+    /// 不再心跳时锁的过期时间（含宽限期），永久锁为 null
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl From<&LockInfo> for LockExpiry {
+    fn from(lock_info: &LockInfo) -> Self {
+        Self {
+            timeout: lock_info.timeout,
+            grace_seconds: lock_info.grace_seconds,
+            heartbeat_due: lock_info.expires_at(),
+            hold_deadline: lock_info.hold_deadline(),
+            expires_at: lock_info.expiry_deadline(),
+        }
+    }
+}
+
+/// 持有时间超过阈值的锁
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct StuckLock {
+    pub lock: LockInfo,
+    /// 截至检测时的持有时长（秒）
+    #[schema(example = 90000)]
+    pub held_seconds: u64,
+    /// 命名空间的卡住阈值（秒）
+    #[schema(example = 7200)]
+    pub threshold_seconds: u64,
+    /// 第一次检测到卡住的时间
+    pub detected_at: DateTime<Utc>,
+}
+
+/// 超过预计归还时间仍未释放的锁
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct OverdueCheckout {
+    pub lock: LockInfo,
+    pub due_at: DateTime<Utc>,
+    /// 截至检测时超过预计归还时间的秒数
+    #[schema(example = 3600)]
+    pub overdue_seconds: u64,
+    /// 第一次检测到逾期的时间
+    pub detected_at: DateTime<Utc>,
+}
+
+/// 命名空间最长持有时间的执行统计，用于调整 `max_hold_seconds`
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct HoldCapStats {
+    #[schema(example = "order")]
+    pub namespace: String,
+    /// 当前策略的最长持有时间，策略已删除或取消限制时为 null
+    #[schema(example = 7200)]
+    pub max_hold_seconds: Option<u64>,
+    /// 当前策略的提醒提前量
+    #[schema(example = 600)]
+    pub hold_warning_seconds: Option<u64>,
+    /// 受最长持有时间限制的锁的累计数量
+    #[schema(example = 120)]
+    pub locks: u64,
+    /// 发布了 `hold_limit_approaching` 提醒的锁的累计数量
+    #[schema(example = 9)]
+    pub warned: u64,
+    /// 持有人仍在心跳、因达到最长持有时间而过期的锁的累计数量
+    #[schema(example = 2)]
+    pub reached: u64,
+    /// 当前持有中、受限制的锁数量
+    #[schema(example = 14)]
+    pub holding: u64,
+}
+
+/// 锁释放或过期后保留的墓碑，记录最后的持有人和结束原因
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Tombstone {
+    #[schema(example = "550e8400-e29b-41d4-a716-446655440000")]
+    pub lock_id: String,
+    #[schema(example = "user123")]
+    pub user_id: String,
+    #[schema(example = "张三")]
+    pub user_name: String,
+    pub locked_at: DateTime<Utc>,
+    /// 持有结束时间
+    pub ended_at: DateTime<Utc>,
+    pub reason: ReleaseReason,
+    /// 墓碑的删除时间
+    pub retained_until: DateTime<Utc>,
+}
+
+/// 锁的释放原因
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReleaseReason {
+    /// 持有人主动释放
+    Explicit,
+    /// 超时未心跳
+    Expired,
+    /// 管理员强制释放
+    Forced,
+    /// 被抢占
+    Preempted,
+    /// 持有人同意接管请求，锁已转移给申请人
+    TakenOver,
+}
+
+/// 锁的一次持有记录
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct HistoryEntry {
+    pub lock_id: String,
+    pub user_id: String,
+    pub user_name: String,
+    pub acquired_at: DateTime<Utc>,
+    /// 释放时间，仍在持有或过期时间未知时为空
+    pub released_at: Option<DateTime<Utc>>,
+    /// 释放原因，仍在持有时为空
+    pub release_reason: Option<ReleaseReason>,
+    /// 申请锁的客户端网络信息
+    pub client: Option<ClientContext>,
+}
+
+/// 分配序列值的查询参数
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct SequenceQuery {
+    /// 序列名
+    pub name: String,
+    /// 一次分配的值数量，默认 1，最大 10000
+    #[serde(default = "default_sequence_batch")]
+    pub batch: u64,
+}
+
+/// 分配到的序列值，`first` 到 `last` 的连续区间（含两端）
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SequenceRange {
+    #[schema(example = "invoice")]
+    pub name: String,
+    pub first: u64,
+    pub last: u64,
+}
+
+/// 令牌桶参数
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TokenBucket {
+    /// 桶容量，即允许的最大突发量
+    pub capacity: u64,
+    /// 每秒补充的令牌数
+    pub refill_per_sec: f64,
+}
+
+/// 限流检查请求，同一 key 的所有请求共享一个令牌桶
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct RateLimitRequest {
+    #[schema(example = "api:user123")]
+    pub key: String,
+    /// 桶容量，即允许的最大突发量
+    #[schema(example = 100)]
+    pub capacity: u64,
+    /// 每秒补充的令牌数
+    #[schema(example = 10.0)]
+    pub refill_per_sec: f64,
+    /// 本次消耗的令牌数，默认 1
+    #[serde(default = "default_rate_limit_cost")]
+    pub cost: u64,
+}
+
+impl RateLimitRequest {
+    pub fn bucket(&self) -> TokenBucket {
+        TokenBucket {
+            capacity: self.capacity,
+            refill_per_sec: self.refill_per_sec,
+        }
+    }
+}
+
+/// 限流检查结果
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RateLimitDecision {
+    /// 是否放行，拒绝时不扣减令牌
+    pub allowed: bool,
+    /// 检查后桶中剩余的令牌数
+    pub remaining: f64,
+    /// 拒绝时令牌补足 cost 所需的秒数，放行时为 0
+    pub retry_after_secs: f64,
+}
+
+/// 服务端时间
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ServerTime {
+    /// 当前服务端时间（UTC）
+    pub now: DateTime<Utc>,
+    /// 当前服务端时间的 Unix 毫秒时间戳
+    pub unix_ms: i64,
+}
+
+/// 锁统计查询参数
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct StatsQuery {
+    /// 返回竞争最激烈的锁数量，默认 10，最大 100
+    #[serde(default = "default_stats_top")]
+    pub top: usize,
+}
+
+fn default_stats_top() -> usize {
+    10
+}
+
+fn default_wait_release_timeout() -> u64 {
+    30
+}
+
+fn default_sequence_batch() -> u64 {
+    1
+}
+
+fn default_rate_limit_cost() -> u64 {
+    1
+}
+
+/// 锁统计信息，计数自 `since` 起累计
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct LockStats {
+    /// 当前未过期的锁数量
+    pub active_locks: u64,
+    /// 各命名空间未过期的锁数量
+    pub namespaces: BTreeMap<String, u64>,
+    /// 申请成功次数（含重入）
+    pub acquired: u64,
+    /// 因锁被占用而申请失败的次数
+    pub conflicts: u64,
+    /// 过期被清理的锁数量
+    pub expired: u64,
+    /// 申请失败次数最多的锁
+    pub top_contended: Vec<ContendedLock>,
+    /// 单次持有时长最长的锁（含当前仍持有的锁）
+    pub longest_held: Vec<HeldLock>,
+    /// 锁持有时长分布（秒），释放或过期时记录
+    pub hold_time: Histogram,
+    /// 每个锁的申请失败次数分布
+    pub contention: Histogram,
+    pub since: DateTime<Utc>,
+}
+
+/// 滑动窗口内的锁竞争分析
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ContentionStats {
+    /// 统计窗口（秒）
+    pub window_secs: u64,
+    /// 窗口内申请冲突次数最多的锁
+    pub top_contended: Vec<ContentionEntry>,
+}
+
+/// 用户用量查询参数
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct UserStatsQuery {
+    /// 返回的用户数量，默认 20，最大 100
+    #[serde(default = "default_user_stats_top")]
+    pub top: usize,
+    /// 排序方式，默认按当前持有的锁数量
+    #[serde(default)]
+    pub sort: UserUsageSort,
+}
+
+fn default_user_stats_top() -> usize {
+    20
+}
+
+/// 用户用量的排序方式，均为从大到小
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum UserUsageSort {
+    /// 当前持有的锁数量
+    #[default]
+    Held,
+    /// 累计持有时长
+    HoldSeconds,
+    /// 获取次数
+    Acquired,
+}
+
+/// 按用户的用量
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct UserUsageStats {
+    /// 发布 `user_quota_warning` 事件的锁数量，未配置软配额和 `LOCK_MAX_PER_USER` 时为 null
+    pub warning_threshold: Option<u64>,
+    /// `LOCK_MAX_PER_USER`，不限制时为 null
+    pub max_per_user: Option<u64>,
+    /// 按 sort 排序的用户
+    pub users: Vec<UserUsage>,
+}
+
+/// 单个用户的用量
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct UserUsage {
+    #[schema(example = "user123")]
+    pub user_id: String,
+    /// 当前持有的锁数量（所有实例）
+    pub held: u64,
+    /// 本实例记录的获取次数（不含重入）
+    pub acquired: u64,
+    /// 本实例记录的已结束（释放、过期、强制释放、被抢占或被接管）的持有次数
+    pub completed: u64,
+    /// 累计持有时长（秒）：本实例记录的已结束持有与当前持有的锁已持有的时长之和
+    pub hold_seconds: f64,
+    /// 本实例发布的 `user_quota_warning` 事件数
+    pub warnings: u64,
+}
+
+/// 用户持有的锁接近配额，见 `user_quota_warning` 事件
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct UserQuotaUsage {
+    /// 获取该锁后用户持有的锁数量
+    pub held: u64,
+    /// 发布提醒的锁数量
+    pub threshold: u64,
+    /// `LOCK_MAX_PER_USER`，不限制时为 null
+    pub limit: Option<u64>,
+}
+
+/// 时间序列的指标
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeSeriesMetric {
+    /// 未过期的锁数量，每个时间点取该时间段内最后一次采样
+    ActiveLocks,
+    /// 时间段内申请成功次数（含重入）
+    Acquired,
+    /// 时间段内因锁被占用而申请失败的次数
+    Conflicts,
+    /// 时间段内过期被清理的锁数量
+    Expired,
+}
+
+/// 时间序列查询参数
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct TimeSeriesQuery {
+    /// 指标：active_locks、acquired、conflicts 或 expired
+    pub metric: TimeSeriesMetric,
+    /// 只统计该命名空间，仅 active_locks 支持
+    pub namespace: Option<String>,
+    /// 时间点间隔（秒），默认为采样间隔
+    pub step: Option<u64>,
+    /// 起始时间（Unix 毫秒），默认为最早的采样
+    pub from: Option<i64>,
+    /// 结束时间（Unix 毫秒），默认为当前时间
+    pub to: Option<i64>,
+}
+
+/// 时间序列中的一个点
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TimeSeriesPoint {
+    /// 时间段的起始时间，按 step 对齐
+    pub time: DateTime<Utc>,
+    pub value: u64,
+}
+
+/// 采样统计的时间序列，没有采样的时间段不输出
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TimeSeries {
+    pub metric: TimeSeriesMetric,
+    pub namespace: Option<String>,
+    /// 时间点间隔（秒）
+    pub step: u64,
+    pub points: Vec<TimeSeriesPoint>,
+}
+
+/// 单个锁在统计窗口内的竞争情况
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ContentionEntry {
+    #[schema(example = "order:order_001")]
+    pub lock_key: String,
+    /// 因锁被占用而申请失败的次数
+    pub conflicts: u64,
+    /// 申请失败后最终获取到锁的次数
+    pub waits: u64,
+    /// 从首次申请失败到获取到锁的平均时长（秒），没有样本时为空
+    pub avg_wait_secs: Option<f64>,
+    /// 释放、过期、强制释放或被抢占的次数
+    pub holds: u64,
+    /// 平均持有时长（秒），没有样本时为空
+    pub avg_hold_secs: Option<f64>,
+}
+
+/// 锁持有时长统计
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct HeldLock {
+    #[schema(example = "order:order_001")]
+    pub lock_key: String,
+    /// 单次持有的最长时长（秒）
+    pub max_hold_secs: f64,
+}
+
+/// 直方图，桶计数为累计值（小于等于 `le` 的样本数）
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct Histogram {
+    pub buckets: Vec<HistogramBucket>,
+    pub count: u64,
+    pub sum: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct HistogramBucket {
+    pub le: f64,
+    pub count: u64,
+}
+
+/// 锁竞争统计
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ContendedLock {
+    #[schema(example = "order:order_001")]
+    pub lock_key: String,
+    pub conflicts: u64,
 }
 
-/// 申请锁失败响应
+/// 锁列表查询参数
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ListLocksQuery {
+    /// 按命名空间过滤
+    pub namespace: Option<String>,
+    /// 按标签过滤
+    pub tag: Option<String>,
+}
+
+impl ListLocksQuery {
+    pub fn matches(&self, lock_info: &LockInfo) -> bool {
+        self.namespace
+            .as_ref()
+            .is_none_or(|namespace| &lock_info.namespace == namespace)
+            && self
+                .tag
+                .as_ref()
+                .is_none_or(|tag| lock_info.tags.contains(tag))
+    }
+}
+
+/// 按标签批量释放锁请求
 #[derive(Debug, Deserialize, Serialize, ToSchema)]
-pub struct AcquireLockFailure {
-    pub current_holder: String,
-    pub locked_at: DateTime<Utc>,
+pub struct ReleaseByTagRequest {
+    #[schema(example = "release-freeze")]
+    pub tag: String,
+    /// 只释放指定命名空间内的锁
+    #[schema(example = "order")]
+    pub namespace: Option<String>,
 }
 
-/// 心跳请求
+/// 死信查询参数
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct DeadLetterQuery {
+    /// 投递目标名称，如 nats、history，不传则为所有投递目标
+    pub sink: Option<String>,
+}
+
+/// 审计 Stream 查询参数
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct AuditQuery {
+    /// 只返回该记录 ID 之后的记录，用于从上次读到的位置继续；不传则返回最新的记录
+    #[param(example = "1704067200000-0")]
+    pub after: Option<String>,
+    /// 返回的记录数，默认 100，最大 1000
+    #[serde(default = "default_audit_count")]
+    pub count: usize,
+}
+
+fn default_audit_count() -> usize {
+    100
+}
+
+/// 审计 Stream 中的一条记录
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AuditEntry {
+    /// Stream 记录 ID，按写入顺序递增
+    #[schema(example = "1704067200000-0")]
+    pub id: String,
+    /// 锁事件，格式与 NATS 事件相同
+    #[schema(value_type = Object)]
+    pub event: serde_json::Value,
+}
+
+/// 实例的交接状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HandoffState {
+    /// 正常处理锁请求
+    Serving,
+    /// 已被新实例隔离，拒绝锁请求，新实例失败时可解除
+    Fenced,
+    /// 新实例已完成交接，不再处理锁请求，等待停止
+    Retired,
+}
+
+/// 实例的交接状态和纪元，每次交接后新实例的纪元比旧实例大 1
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct HandoffStatus {
+    pub state: HandoffState,
+    #[schema(example = 1)]
+    pub epoch: u64,
+    /// 隔离本实例的新实例
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "fe-lock-service-7d9f8-x2k4q")]
+    pub successor: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fenced_at: Option<DateTime<Utc>>,
+    /// 本实例启动时交出状态的旧实例地址
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "http://fe-lock-service-old:8080")]
+    pub predecessor: Option<String>,
+}
+
+/// 隔离或完成交接请求
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct HandoffRequest {
+    /// 接收状态的新实例的标识（其 `CLUSTER_NODE_ID`）
+    #[schema(example = "fe-lock-service-7d9f8-x2k4q")]
+    pub successor: String,
+}
+
+/// 重新投递死信的结果
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ReplayReport {
+    /// 重新放入缓冲队列的事件数
+    pub replayed: usize,
+}
+
+/// 按持有时长或心跳间隔批量强制过期锁请求，指定的条件需全部满足
 #[derive(Debug, Deserialize, Serialize, ToSchema)]
-pub struct HeartbeatRequest {
-    #[schema(example = "550e8400-e29b-41d4-a716-446655440000")]
-    pub lock_id: String,
+pub struct ExpireLocksRequest {
+    /// 加锁时间早于该秒数之前的锁
+    #[schema(example = 86400)]
+    pub older_than_seconds: Option<u64>,
+    /// 超过该秒数没有心跳的锁
+    #[schema(example = 600)]
+    pub no_heartbeat_for: Option<u64>,
+    /// 只处理指定命名空间内的锁
+    #[schema(example = "order")]
+    pub namespace: Option<String>,
+    /// 只返回会被过期的锁，不释放
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+impl ExpireLocksRequest {
+    /// 截至 now 锁是否满足全部条件
+    pub fn matches(&self, lock_info: &LockInfo, now: DateTime<Utc>) -> bool {
+        let elapsed = |since: DateTime<Utc>| (now - since).num_seconds().max(0) as u64;
+        self.namespace
+            .as_ref()
+            .is_none_or(|namespace| &lock_info.namespace == namespace)
+            && self
+                .older_than_seconds
+                .is_none_or(|older_than| elapsed(lock_info.locked_at) >= older_than)
+            && self
+                .no_heartbeat_for
+                .is_none_or(|no_heartbeat_for| elapsed(lock_info.last_heartbeat) >= no_heartbeat_for)
+    }
+}
+
+/// 锁状态导出数据，可通过导入接口恢复到任意存储后端
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct LockExport {
+    pub exported_at: DateTime<Utc>,
+    pub locks: Vec<LockInfo>,
+    #[serde(default)]
+    pub namespaces: Vec<NamespacePolicy>,
+    /// 预约等保存在存储中的记录，记录类别 -> id -> 内容（JSON），实例交接时一并复制，导入接口忽略
+    #[serde(default)]
+    pub records: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+/// NDJSON 格式导出的一行，命名空间策略在前、锁在后，`type` 区分记录类型
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ExportRecord {
+    Namespace(NamespacePolicy),
+    Lock(Box<LockInfo>),
+}
+
+/// 导入参数
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ImportQuery {
+    /// 只检查导入结果，不写入存储
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// 旧锁表迁移的一行记录
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct LegacyLockRecord {
+    #[schema(example = "order")]
+    pub namespace: String,
+    #[schema(example = "order_001")]
+    pub business_id: String,
+    #[schema(example = "user_123")]
+    pub user_id: String,
+    /// 不传时使用 user_id
+    #[schema(example = "张三")]
+    pub user_name: Option<String>,
+    /// 超时时间（秒），不传时使用参数 `timeout`，再按命名空间策略和全局配置确定
+    #[schema(example = 300)]
+    pub timeout: Option<u64>,
+    /// 旧系统中的加锁时间，不传时为导入时间；心跳时间总是导入时间
+    pub locked_at: Option<DateTime<Utc>>,
+}
+
+/// 旧锁表迁移参数
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct LegacyImportQuery {
+    /// 只校验并返回迁移结果，不写入存储
+    #[serde(default)]
+    pub dry_run: bool,
+    /// 记录未指定超时时间时使用的超时时间（秒）
+    #[param(example = 600)]
+    pub timeout: Option<u64>,
+}
+
+/// 旧锁表中不能迁移的记录
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LegacyImportError {
+    /// 记录的行号，CSV 从表头之后的第一行数据起为 1，JSON 为数组下标加 1
+    #[schema(example = 3)]
+    pub row: usize,
+    #[schema(example = "user_id must not be empty")]
+    pub message: String,
+}
+
+/// 旧锁表迁移结果，演练时为将要产生的结果
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct LegacyImportReport {
+    pub dry_run: bool,
+    /// 记录总数
+    pub total: usize,
+    /// 创建的锁数量
+    pub imported: usize,
+    /// 同一用户已持有该锁而跳过的数量
+    pub unchanged: usize,
+    /// 已被其他用户持有而跳过的锁
+    #[schema(example = json!(["order:order_001"]))]
+    pub conflicts: Vec<String>,
+    /// 校验失败而跳过的记录
+    pub invalid: Vec<LegacyImportError>,
+    /// 创建（演练时为将要创建）的锁
+    pub locks: Vec<LockInfo>,
+}
+
+/// 发起管理接口登录的参数
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct AdminLoginQuery {
+    /// 登录完成后跳转的本站路径，不传时回调返回会话信息
+    #[param(example = "/admin/")]
+    pub return_to: Option<String>,
+}
+
+/// 身份提供方回调的参数
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct AdminCallbackQuery {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    /// 身份提供方拒绝登录时的错误
+    pub error: Option<String>,
+    pub error_description: Option<String>,
+}
+
+/// 当前的管理登录会话
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AdminSessionInfo {
+    /// ID Token 的 sub 声明
+    pub subject: String,
+    pub name: Option<String>,
+    pub role: AdminRole,
+    pub expires_at: DateTime<Utc>,
+    /// 修改类请求需放入 X-CSRF-Token 请求头的令牌
+    pub csrf_token: String,
+}
+
+impl From<&AdminSession> for AdminSessionInfo {
+    fn from(session: &AdminSession) -> Self {
+        Self {
+            subject: session.subject.clone(),
+            name: session.name.clone(),
+            role: session.role,
+            expires_at: session.expires_at,
+            csrf_token: session.csrf_token.clone(),
+        }
+    }
+}
+
+/// 导入结果，演练时为将要产生的结果
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    /// 导入的锁数量
+    pub imported: usize,
+    /// 已存在相同 lock_id 的锁而跳过的数量
+    pub unchanged: usize,
+    /// 已过期而跳过的数量
+    pub expired: usize,
+    /// lock_key 已被其他未过期的锁占用而跳过的锁
+    #[schema(example = json!(["order:order_001"]))]
+    pub conflicts: Vec<String>,
+    /// 导入（覆盖）的命名空间策略数量
+    pub namespaces: usize,
+}
+
+/// 故障注入设置，概率取值 0 到 1
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct ChaosSettings {
+    /// 请求注入延迟的概率
+    #[serde(default)]
+    pub latency_rate: f64,
+    /// 注入的延迟（毫秒）
+    #[serde(default)]
+    pub latency_ms: u64,
+    /// 请求直接返回 HTTP 500（错误码 7002）的概率
+    #[serde(default)]
+    pub error_rate: f64,
+    /// 心跳前使锁提前过期的概率
+    #[serde(default)]
+    pub expire_rate: f64,
+}
+
+fn default_allow_queue() -> bool {
+    true
+}
+
+/// 命名空间策略
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct NamespacePolicy {
+    #[schema(example = "order")]
+    pub name: String,
+    /// 申请锁未传 timeout 时使用的超时时间（秒）
+    #[schema(example = 60)]
+    pub default_timeout: Option<u64>,
+    /// 允许的最大超时时间（秒）
+    #[schema(example = 3600)]
+    pub max_timeout: Option<u64>,
+    /// 命名空间内同时持有的最大锁数量
+    #[schema(example = 1000)]
+    pub max_locks: Option<u64>,
+    /// 是否允许排队等待锁
+    #[serde(default = "default_allow_queue")]
+    pub allow_queue: bool,
+    /// business_id 是否为以 `/` 分隔的层级路径，锁定路径时与祖先和后代路径上他人持有的锁冲突
+    #[serde(default)]
+    pub hierarchical: bool,
+    /// 是否为提示性命名空间：申请总是成功，多人可同时持有，响应中返回所有当前持有人
+    #[serde(default)]
+    pub advisory: bool,
+    /// 事件路由规则，命名空间的锁事件额外投递到规则中的回调地址或 NATS 主题
+    #[serde(default)]
+    pub event_routes: Vec<EventRoute>,
+    /// 锁持有超过该时间（秒）视为卡住，未配置时使用 `LOCK_STUCK_THRESHOLD`，0 表示不检测
+    #[serde(default)]
+    #[schema(example = 7200)]
+    pub stuck_after: Option<u64>,
+    /// 冻结设置，冻结期间拒绝新的申请，已持有的锁不受影响
+    #[serde(default)]
+    pub freeze: Option<NamespaceFreeze>,
+    /// 锁从获取起最多存在的时间（秒），心跳不能延长，申请时的 `max_hold_seconds` 不能超过该值，永久锁不受限制
+    #[serde(default)]
+    #[schema(example = 7200)]
+    pub max_hold_seconds: Option<u64>,
+    /// 锁距离最长持有时间不足该秒数时发布 `hold_limit_approaching` 事件，未配置时为最长持有时间的十分之一
+    #[serde(default)]
+    #[schema(example = 600)]
+    pub hold_warning_seconds: Option<u64>,
+}
+
+/// 命名空间冻结，用于迁移数据等维护操作前停止编辑；from 和 until 均未指定时立即冻结直到解除
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct NamespaceFreeze {
+    /// 拒绝申请时返回的原因
+    #[schema(example = "Data migration in progress")]
+    pub reason: Option<String>,
+    /// 冻结开始时间，未指定时立即开始
+    pub from: Option<DateTime<Utc>>,
+    /// 冻结结束时间，未指定时持续到解除冻结
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl NamespaceFreeze {
+    /// 冻结在 now 时是否生效
+    pub fn active_at(&self, now: DateTime<Utc>) -> bool {
+        self.from.is_none_or(|from| from <= now) && self.until.is_none_or(|until| now < until)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self
+            .reason
+            .as_ref()
+            .is_some_and(|reason| reason.is_empty() || reason.len() > crate::validation::MAX_REASON_LEN)
+        {
+            return Err(format!(
+                "freeze reason must be 1-{} bytes",
+                crate::validation::MAX_REASON_LEN
+            ));
+        }
+        if let (Some(from), Some(until)) = (self.from, self.until) {
+            if from >= until {
+                return Err("freeze from must be earlier than until".to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 每个命名空间最多的事件路由规则数量
+pub const MAX_EVENT_ROUTES: usize = 10;
+
+/// 事件路由规则，回调地址和 NATS 主题二选一
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct EventRoute {
+    /// 回调地址，事件以 JSON POST 到该地址
+    #[schema(example = "https://ops.example.com/hooks/locks")]
+    pub webhook_url: Option<String>,
+    /// NATS 主题，需配置 `NATS_URL`
+    #[schema(example = "ops.locks.order")]
+    pub nats_subject: Option<String>,
+    /// 投递的事件类型，为空时投递全部事件
+    #[serde(default)]
+    #[schema(example = json!(["force_released", "expired"]))]
+    pub events: Vec<LockEventType>,
+}
+
+impl EventRoute {
+    /// 事件是否按本规则投递
+    pub fn matches(&self, event: LockEventType) -> bool {
+        self.events.is_empty() || self.events.contains(&event)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        match (&self.webhook_url, &self.nats_subject) {
+            (Some(url), None) => {
+                if !(url.starts_with("http://") || url.starts_with("https://")) {
+                    return Err(format!("event route webhook_url must be an http(s) URL: {}", url));
+                }
+            }
+            (None, Some(subject)) => {
+                if subject.is_empty()
+                    || subject.starts_with('.')
+                    || subject.ends_with('.')
+                    || subject.chars().any(|c| c.is_whitespace() || c == '*' || c == '>')
+                {
+                    return Err(format!("event route nats_subject is not a valid subject: {}", subject));
+                }
+            }
+            _ => return Err("event route must set exactly one of webhook_url and nats_subject".to_string()),
+        }
+        Ok(())
+    }
+}
+
+impl NamespacePolicy {
+    /// 未配置策略的命名空间使用的默认策略（仅受全局配置限制）
+    pub fn unrestricted(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            default_timeout: None,
+            max_timeout: None,
+            max_locks: None,
+            allow_queue: true,
+            hierarchical: false,
+            advisory: false,
+            event_routes: Vec::new(),
+            stuck_after: None,
+            freeze: None,
+            max_hold_seconds: None,
+            hold_warning_seconds: None,
+        }
+    }
+
+    /// 按策略的最长持有时间限制申请的最长持有时间，永久锁（timeout 为 0）不受限制
+    pub fn cap_hold(&self, requested: Option<u64>, timeout: u64) -> Option<u64> {
+        match self.max_hold_seconds {
+            Some(max_hold) if timeout > 0 => Some(requested.map_or(max_hold, |secs| secs.min(max_hold))),
+            _ => requested,
+        }
+    }
+
+    /// 到达最长持有时间前发布提醒的提前量（秒），未配置最长持有时间时为 None
+    pub fn hold_warning(&self) -> Option<u64> {
+        self.max_hold_seconds
+            .map(|max_hold| self.hold_warning_seconds.unwrap_or(max_hold / 10))
+    }
+
+    /// 根据策略确定本次申请的超时时间，策略未配置的部分使用全局默认值和上限
+    pub fn resolve_timeout(
+        &self,
+        requested: Option<u64>,
+        global_default: u64,
+        global_max: u64,
+    ) -> Result<u64, String> {
+        let timeout = requested
+            .or(self.default_timeout)
+            .unwrap_or(global_default);
+        if timeout == 0 {
+            return Err("timeout must be greater than 0".to_string());
+        }
+        let max_timeout = self
+            .max_timeout
+            .map_or(global_max, |max_timeout| max_timeout.min(global_max));
+        if timeout > max_timeout {
+            return Err(format!(
+                "timeout {}s exceeds the maximum of {}s for namespace {}",
+                timeout, max_timeout, self.name
+            ));
+        }
+        Ok(timeout)
+    }
+
+    /// 校验策略本身是否合法，超时时间不能超过全局上限
+    pub fn validate(&self, global_max: u64) -> Result<(), String> {
+        // 租户的命名空间带有 `<tenant_id>/` 前缀，两部分分别校验
+        if !crate::validation::is_valid_scoped_namespace(&self.name)
+            || self
+                .name
+                .split(crate::tenant::SEPARATOR)
+                .any(|part| part.len() > crate::validation::MAX_NAMESPACE_LEN)
+        {
+            return Err(format!(
+                "namespace name must be 1-{} bytes of letters, digits, '-', '_' and '.', optionally prefixed by '<tenant_id>/'",
+                crate::validation::MAX_NAMESPACE_LEN
+            ));
+        }
+        if self.default_timeout == Some(0) || self.max_timeout == Some(0) {
+            return Err("timeout must be greater than 0".to_string());
+        }
+        if let (Some(default_timeout), Some(max_timeout)) = (self.default_timeout, self.max_timeout)
+        {
+            if default_timeout > max_timeout {
+                return Err("default_timeout must not exceed max_timeout".to_string());
+            }
+        }
+        if self.advisory && self.hierarchical {
+            return Err("advisory and hierarchical cannot both be enabled".to_string());
+        }
+        if self.event_routes.len() > MAX_EVENT_ROUTES {
+            return Err(format!("at most {} event routes are allowed", MAX_EVENT_ROUTES));
+        }
+        for route in &self.event_routes {
+            route.validate()?;
+        }
+        if let Some(freeze) = &self.freeze {
+            freeze.validate()?;
+        }
+        if self.max_hold_seconds == Some(0) {
+            return Err("max_hold_seconds must be greater than 0".to_string());
+        }
+        if self.hold_warning_seconds.is_some() && self.max_hold_seconds.is_none() {
+            return Err("hold_warning_seconds requires max_hold_seconds".to_string());
+        }
+        if let (Some(warning), Some(max_hold)) = (self.hold_warning_seconds, self.max_hold_seconds) {
+            if warning >= max_hold {
+                return Err("hold_warning_seconds must be less than max_hold_seconds".to_string());
+            }
+        }
+        if self.default_timeout.max(self.max_timeout).unwrap_or(0) > global_max {
+            return Err(format!(
+                "timeout must not exceed the global maximum of {}s",
+                global_max
+            ));
+        }
+        Ok(())
+    }
 }
 
-/// 释放锁请求
+/// 创建或更新命名空间策略请求
 #[derive(Debug, Deserialize, Serialize, ToSchema)]
-pub struct ReleaseLockRequest {
-    #[schema(example = "550e8400-e29b-41d4-a716-446655440000")]
-    pub lock_id: String,
+pub struct NamespacePolicyRequest {
+    #[schema(example = 60)]
+    pub default_timeout: Option<u64>,
+    #[schema(example = 3600)]
+    pub max_timeout: Option<u64>,
+    #[schema(example = 1000)]
+    pub max_locks: Option<u64>,
+    #[serde(default = "default_allow_queue")]
+    pub allow_queue: bool,
+    #[serde(default)]
+    pub hierarchical: bool,
+    #[serde(default)]
+    pub advisory: bool,
+    #[serde(default)]
+    pub event_routes: Vec<EventRoute>,
+    #[schema(example = 7200)]
+    pub stuck_after: Option<u64>,
+    #[serde(default)]
+    pub freeze: Option<NamespaceFreeze>,
+    #[schema(example = 7200)]
+    pub max_hold_seconds: Option<u64>,
+    #[schema(example = 600)]
+    pub hold_warning_seconds: Option<u64>,
+}
+
+impl NamespacePolicyRequest {
+    pub fn into_policy(self, name: String) -> NamespacePolicy {
+        NamespacePolicy {
+            name,
+            default_timeout: self.default_timeout,
+            max_timeout: self.max_timeout,
+            max_locks: self.max_locks,
+            allow_queue: self.allow_queue,
+            hierarchical: self.hierarchical,
+            advisory: self.advisory,
+            event_routes: self.event_routes,
+            stuck_after: self.stuck_after,
+            freeze: self.freeze,
+            max_hold_seconds: self.max_hold_seconds,
+            hold_warning_seconds: self.hold_warning_seconds,
+        }
+    }
 }
 
 /// 锁信息
@@ -62,32 +2176,182 @@ pub struct LockInfo {
     pub timeout: u64,
     pub locked_at: DateTime<Utc>,
     pub last_heartbeat: DateTime<Utc>,
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// 最长持有时间（秒），到期后即使仍在心跳也会过期
+    #[serde(default)]
+    pub max_hold_seconds: Option<u64>,
+    /// 心跳超时后的宽限期（秒），获取锁时按 `LOCK_HEARTBEAT_GRACE` 确定，宽限期内心跳仍然有效，他人不能获取锁
+    #[serde(default)]
+    pub grace_seconds: u64,
+    /// 待执行的抢占，持有人应在 deadline 前保存并释放锁
+    #[serde(default)]
+    pub preemption: Option<PreemptionNotice>,
+    /// 版本号，新获取的锁为 1，之后每次变更（心跳、重入、登记抢占、转移、接管）加 1
+    #[serde(default = "default_version")]
+    pub version: u64,
+    /// 持有原因，展示给申请被拒绝的用户
+    #[serde(default)]
+    pub reason: Option<String>,
+    /// 申请锁的客户端网络信息，预约授予和导入的锁为 null
+    #[serde(default)]
+    pub client: Option<ClientContext>,
+    /// 预计归还时间，超过后仍未释放的锁视为逾期
+    #[serde(default)]
+    pub due_at: Option<DateTime<Utc>>,
+}
+
+/// 申请锁的客户端网络信息
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ClientContext {
+    /// 客户端 IP，经受信任的代理转发时取自 X-Forwarded-For
+    #[schema(example = "10.0.3.17")]
+    pub ip: Option<String>,
+    #[schema(example = "fe-lock-client/0.1")]
+    pub user_agent: Option<String>,
+    /// 客户端上报的主机名
+    #[schema(example = "batch-worker-03")]
+    pub hostname: Option<String>,
+}
+
+fn default_version() -> u64 {
+    1
+}
+
+/// at 之后 secs 秒的时刻，超出可表示的范围时取最大时刻
+pub fn seconds_after(at: DateTime<Utc>, secs: u64) -> DateTime<Utc> {
+    i64::try_from(secs)
+        .ok()
+        .and_then(chrono::Duration::try_seconds)
+        .and_then(|duration| at.checked_add_signed(duration))
+        .unwrap_or(DateTime::<Utc>::MAX_UTC)
+}
+
+/// 抢占通知
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PreemptionNotice {
+    #[schema(example = "admin")]
+    pub user_id: String,
+    #[schema(example = "管理员")]
+    pub user_name: String,
+    /// 锁转移给抢占人的时间
+    pub deadline: DateTime<Utc>,
 }
 
 impl LockInfo {
-    pub fn new(request: &AcquireLockRequest) -> Self {
-        let now = Utc::now();
+    /// 以 now 作为加锁时间和首次心跳时间创建锁
+    pub fn new(lock_id: String, request: &AcquireLockRequest, timeout: u64, now: DateTime<Utc>) -> Self {
         Self {
-            lock_id: Uuid::new_v4().to_string(),
+            lock_id,
             namespace: request.namespace.clone(),
             user_id: request.user_id.clone(),
             user_name: request.user_name.clone(),
             business_id: request.business_id.clone(),
-            timeout: request.timeout,
+            timeout,
             locked_at: now,
             last_heartbeat: now,
+            metadata: request.metadata.clone(),
+            tags: dedup_tags(&request.tags),
+            max_hold_seconds: request.max_hold_seconds,
+            grace_seconds: 0,
+            preemption: None,
+            version: 1,
+            reason: request.reason.clone(),
+            client: None,
+            due_at: request
+                .due_in_seconds
+                .map(|due_in| seconds_after(now, due_in)),
+        }
+    }
+
+    /// 超时时间为 0 的永久锁，不会过期
+    pub fn is_permanent(&self) -> bool {
+        self.timeout == 0
+    }
+
+    /// 达到最长持有时间的时刻，未设置 max_hold_seconds 时为 None
+    pub fn hold_deadline(&self) -> Option<DateTime<Utc>> {
+        self.max_hold_seconds
+            .map(|max_hold| seconds_after(self.locked_at, max_hold))
+    }
+
+    /// 截至 now 超过预计归还时间的秒数，未逾期或未登记归还时间时为 0
+    pub fn overdue_secs_at(&self, now: DateTime<Utc>) -> u64 {
+        self.due_at
+            .map(|due_at| (now - due_at).num_seconds().max(0) as u64)
+            .unwrap_or(0)
+    }
+
+    /// 持有人应在此前心跳：不再心跳时的过期时间，取心跳超时和最长持有时间中较早的一个，永久锁为 None
+    pub fn expires_at(&self) -> Option<DateTime<Utc>> {
+        let heartbeat_expiry = (!self.is_permanent())
+            .then(|| seconds_after(self.last_heartbeat, self.timeout));
+        match (heartbeat_expiry, self.hold_deadline()) {
+            (Some(heartbeat_expiry), Some(deadline)) => Some(heartbeat_expiry.min(deadline)),
+            (heartbeat_expiry, deadline) => heartbeat_expiry.or(deadline),
         }
     }
 
-    pub fn is_expired(&self) -> bool {
-        let now = Utc::now();
-        let elapsed = now.signed_duration_since(self.last_heartbeat);
-        elapsed.num_seconds() as u64 >= self.timeout
+    /// 锁真正过期、可被他人获取的时间：心跳超时后再经过宽限期，最长持有时间不因宽限期延长，永久锁为 None
+    pub fn expiry_deadline(&self) -> Option<DateTime<Utc>> {
+        let heartbeat_expiry = (!self.is_permanent())
+            .then(|| seconds_after(self.last_heartbeat, self.timeout.saturating_add(self.grace_seconds)));
+        match (heartbeat_expiry, self.hold_deadline()) {
+            (Some(heartbeat_expiry), Some(deadline)) => Some(heartbeat_expiry.min(deadline)),
+            (heartbeat_expiry, deadline) => heartbeat_expiry.or(deadline),
+        }
+    }
+
+    /// 截至 now 距离心跳截止的剩余时间（秒，向上取整），已过期时为 0，永久锁为 None
+    pub fn remaining_secs_at(&self, now: DateTime<Utc>) -> Option<u64> {
+        self.expires_at().map(|expires_at| secs_until(expires_at, now))
+    }
+
+    /// 截至 now 距离锁真正过期的剩余时间（秒，向上取整），含宽限期，永久锁为 None
+    pub fn deadline_secs_at(&self, now: DateTime<Utc>) -> Option<u64> {
+        self.expiry_deadline().map(|deadline| secs_until(deadline, now))
+    }
+
+    /// 截至 now 的持有时长（秒），已过期的锁按过期时间计算
+    pub fn held_secs_at(&self, now: DateTime<Utc>) -> f64 {
+        let end = self.expiry_deadline().map_or(now, |deadline| now.min(deadline));
+        (end - self.locked_at).num_milliseconds().max(0) as f64 / 1000.0
+    }
+
+    /// 以指定时间判断锁是否过期（含宽限期），所有存储的心跳、获取和清理都以此为准。
+    /// 只与服务端记录的截止时间比较，其他实例时钟略快写入的心跳时间晚于 now 时不会被误判为过期
+    pub fn is_expired_at(&self, now: DateTime<Utc>) -> bool {
+        self.expiry_deadline().is_some_and(|deadline| now >= deadline)
     }
 
     pub fn get_lock_key(&self) -> String {
         format!("{}:{}", self.namespace, self.business_id)
     }
+
+    /// 是否为 namespace + business_id 的锁，提示性命名空间中的锁按 `<business_id>#<user_id>` 保存
+    pub fn matches_key(&self, namespace: &str, business_id: &str) -> bool {
+        self.namespace == namespace
+            && (self.business_id == business_id
+                || self.business_id == advisory::holder_business_id(business_id, &self.user_id))
+    }
+}
+
+/// 距离 deadline 的秒数，向上取整，已过时为 0
+fn secs_until(deadline: DateTime<Utc>, now: DateTime<Utc>) -> u64 {
+    ((deadline - now).num_milliseconds().max(0) as u64).div_ceil(1000)
+}
+
+/// 去除重复标签，保留首次出现的顺序
+fn dedup_tags(tags: &[String]) -> Vec<String> {
+    let mut unique: Vec<String> = Vec::with_capacity(tags.len());
+    for tag in tags {
+        if !unique.contains(tag) {
+            unique.push(tag.clone());
+        }
+    }
+    unique
 }
 
 /// 统一响应结构
@@ -97,6 +2361,12 @@ pub struct ApiResponse<T> {
     pub message: String,
     pub data: Option<T>,
     pub success: bool,
+    /// 生成响应时的服务端时间，客户端可据此校准本地时钟；输出响应时按服务的时钟填写
+    pub server_time: DateTime<Utc>,
+    /// 错误的扩展字段，与其他字段同级输出
+    #[serde(flatten)]
+    #[schema(value_type = Object)]
+    pub extensions: serde_json::Map<String, serde_json::Value>,
 }
 
 impl<T> ApiResponse<T> {
@@ -106,15 +2376,558 @@ impl<T> ApiResponse<T> {
             message: "success".to_string(),
             data: Some(data),
             success: true,
+            server_time: DateTime::<Utc>::UNIX_EPOCH,
+            extensions: serde_json::Map::new(),
         }
     }
 
-    pub fn error(code: i32, message: String) -> Self {
+    pub fn error(code: impl Into<i32>, message: String) -> Self {
         Self {
-            code,
+            code: code.into(),
             message,
             data: None,
             success: false,
+            server_time: DateTime::<Utc>::UNIX_EPOCH,
+            extensions: serde_json::Map::new(),
+        }
+    }
+
+    /// 附加错误扩展字段，`extensions` 需序列化为 JSON 对象
+    pub fn with_extensions<E: Serialize>(mut self, extensions: &E) -> Self {
+        if let Ok(serde_json::Value::Object(map)) = serde_json::to_value(extensions) {
+            self.extensions.extend(map);
+        }
+        self
+    }
+}
+
+impl<T: Serialize> ApiResponse<T> {
+    /// 以指定的状态码输出响应，server_time 取服务的时钟
+    pub fn respond_with_status(mut self, req: &HttpRequest, status: StatusCode) -> HttpResponse {
+        self.server_time = clock::now(req);
+        codec::respond(req, status, &self)
+    }
+
+    /// 成功响应附带按 data 计算的弱 ETag（与 server_time 无关），请求头 If-None-Match 与之相同时返回 304，不带响应体；
+    /// 锁的任何变更（心跳、重入等）都会递增版本号，ETag 随之变化。错误响应不带 ETag
+    pub fn respond_with_etag(self, req: &HttpRequest) -> HttpResponse {
+        let etag = match &self.data {
+            Some(data) if self.success => serde_json::to_vec(data).ok().map(|bytes| etag_of(&bytes)),
+            _ => None,
+        };
+        let Some(etag) = etag else {
+            return self.respond_to(req);
+        };
+        if if_none_match(req, &etag) {
+            return HttpResponse::NotModified()
+                .insert_header((header::ETAG, etag))
+                .insert_header((header::CACHE_CONTROL, "no-cache"))
+                .finish();
+        }
+        let mut response = self.respond_to(req);
+        if let Ok(value) = header::HeaderValue::from_str(&etag) {
+            response.headers_mut().insert(header::ETAG, value);
+            response
+                .headers_mut()
+                .insert(header::CACHE_CONTROL, header::HeaderValue::from_static("no-cache"));
+        }
+        response
+    }
+}
+
+/// 数据的弱 ETag，取 SHA-256 的前 16 字节，同一数据在所有实例上相同
+fn etag_of(bytes: &[u8]) -> String {
+    let digest = ring::digest::digest(&ring::digest::SHA256, bytes);
+    let hash: String = digest.as_ref()[..16].iter().map(|b| format!("{:02x}", b)).collect();
+    format!("W/\"{}\"", hash)
+}
+
+/// If-None-Match 是否包含 etag（弱比较）或为 `*`
+fn if_none_match(req: &HttpRequest, etag: &str) -> bool {
+    let Some(value) = req.headers().get(header::IF_NONE_MATCH).and_then(|value| value.to_str().ok()) else {
+        return false;
+    };
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    value
+        .split(',')
+        .any(|tag| tag.trim() == "*" || opaque(tag) == opaque(etag))
+}
+
+/// RFC 7807 错误响应
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ProblemDetails {
+    #[serde(rename = "type")]
+    #[schema(example = "urn:fe-lock:error:1001")]
+    pub problem_type: String,
+    #[schema(example = "Lock already held")]
+    pub title: String,
+    #[schema(example = 409)]
+    pub status: u16,
+    #[schema(example = "Lock already held by 张三")]
+    pub detail: String,
+    #[schema(example = "/api/lock/acquire")]
+    pub instance: String,
+    #[schema(example = 1001)]
+    pub code: i32,
+    pub server_time: DateTime<Utc>,
+    #[serde(flatten)]
+    pub extensions: serde_json::Map<String, serde_json::Value>,
+}
+
+/// problem+json 的媒体类型
+pub const PROBLEM_JSON: &str = "application/problem+json";
+
+/// 请求是否通过 Accept 头要求 problem+json 格式的错误响应
+pub fn accepts_problem_json(req: &HttpRequest) -> bool {
+    req.headers()
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|accept| accept.contains(PROBLEM_JSON))
+}
+
+/// 错误码目录：每个错误码对应一个变体，新接口在此登记错误码，避免与已有错误码重叠。
+/// 11000 到 11999 留给嵌入方的钩子使用，目录中只登记 11000
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(i32)]
+pub enum LockError {
+    /// 请求参数校验失败
+    ValidationFailed = 1000,
+    /// 锁已被他人持有
+    LockHeld = 1001,
+    /// 申请锁失败（抢占期间锁被释放等），可重试
+    AcquireFailed = 1002,
+    /// 申请锁时读取锁失败
+    LockLookupFailed = 1003,
+    /// 申请锁时读取命名空间策略或配额失败
+    PolicyLookupFailed = 1004,
+    /// 超时时间、附加信息等申请参数不合法
+    InvalidLockRequest = 1005,
+    /// 命名空间锁数量达到上限
+    NamespaceLimitReached = 1006,
+    /// 等待会形成死锁
+    Deadlock = 1007,
+    /// 预约时间窗重叠
+    ReservationConflict = 1008,
+    /// 预约不存在
+    ReservationNotFound = 1009,
+    /// 锁留给更高优先级的等待者
+    ReservedForWaiters = 1010,
+    /// 抢占已登记，等待宽限期结束
+    PreemptionPending = 1011,
+    /// 无权抢占锁
+    PreemptionNotAllowed = 1012,
+    /// 申请锁时指定的会话不存在
+    LockSessionNotFound = 1013,
+    /// 层级路径上的祖先或后代已被他人锁定
+    PathConflict = 1014,
+    /// 条件获取的条件不满足
+    AcquireConditionFailed = 1015,
+    /// 用户持有的锁数量达到配额
+    UserQuotaReached = 1016,
+    /// 租户持有的锁数量达到配额
+    TenantQuotaReached = 1017,
+    /// 用户不在等待队列中
+    NotInWaitQueue = 1018,
+    /// 命名空间已冻结
+    NamespaceFrozen = 1019,
+    /// business_id 已在其他命名空间中被锁定
+    BusinessIdConflict = 1020,
+    /// 持有人的锁正在排空，不能重入申请
+    LockDraining = 1021,
+    /// 预约的存储操作失败
+    ReservationFailed = 1022,
+    /// 心跳的锁不存在或已过期
+    HeartbeatLockNotFound = 2001,
+    /// 更新心跳失败
+    HeartbeatFailed = 2002,
+    /// 条件心跳的版本号不一致
+    HeartbeatConditionFailed = 2003,
+    /// 释放的锁不存在或不属于该用户
+    ReleaseLockNotFound = 3001,
+    /// 释放锁失败
+    ReleaseFailed = 3002,
+    /// 释放参数不合法
+    InvalidReleaseRequest = 3003,
+    /// 条件释放的版本号不一致
+    ReleaseConditionFailed = 3004,
+    /// 未授权
+    Unauthorized = 4001,
+    /// 管理接口操作的资源不存在或功能未启用
+    AdminNotFound = 4002,
+    /// 管理接口参数不合法
+    InvalidAdminRequest = 4003,
+    /// 管理接口存储操作失败
+    AdminStorageError = 4004,
+    /// 管理角色无权访问或 CSRF 令牌无效
+    Forbidden = 4005,
+    /// 管理接口登录失败
+    LoginFailed = 4006,
+    /// 身份提供方不可用
+    IdentityProviderUnavailable = 4007,
+    /// 查询锁状态失败
+    StatusFailed = 5001,
+    /// 查询锁列表失败
+    ListFailed = 5002,
+    /// 查询锁统计失败
+    StatsFailed = 5003,
+    /// 查询锁历史失败
+    HistoryFailed = 5004,
+    /// 未启用统计采样
+    StatsSamplingDisabled = 5005,
+    /// 会话不存在或已过期
+    SessionNotFound = 6001,
+    /// 续期或释放会话的锁失败
+    SessionFailed = 6002,
+    /// 会话参数不合法
+    InvalidSessionRequest = 6003,
+    /// 存储不可用（断路器打开）
+    StorageUnavailable = 7001,
+    /// 故障注入的错误
+    InjectedFault = 7002,
+    /// 内存存储已达到最大锁数量
+    StorageFull = 7003,
+    /// 存储操作超时
+    StorageTimeout = 7004,
+    /// 请求处理超时
+    RequestTimeout = 7005,
+    /// 服务过载，请按 Retry-After 重试
+    Overloaded = 7006,
+    /// 来源地址不在允许访问的范围内
+    IpNotAllowed = 7007,
+    /// 实例已被隔离，状态已交接或正在交接给新实例
+    InstanceFenced = 7008,
+    /// 分配序列失败
+    SequenceFailed = 8001,
+    /// 限流检查失败
+    RateLimitFailed = 8002,
+    /// 接管请求不存在或已答复
+    TakeoverNotFound = 9001,
+    /// 锁未被他人持有，无法接管
+    TakeoverNotPossible = 9002,
+    /// 接管请求的存储操作失败
+    TakeoverFailed = 9003,
+    /// 在场记录操作失败
+    PresenceFailed = 10001,
+    /// 申请被嵌入方注册的钩子拒绝（11000–11999 留给钩子使用）
+    HookRejected = 11000,
+}
+
+impl LockError {
+    /// 目录中的全部错误码，按错误码升序
+    pub const ALL: &'static [LockError] = &[
+        LockError::ValidationFailed,
+        LockError::LockHeld,
+        LockError::AcquireFailed,
+        LockError::LockLookupFailed,
+        LockError::PolicyLookupFailed,
+        LockError::InvalidLockRequest,
+        LockError::NamespaceLimitReached,
+        LockError::Deadlock,
+        LockError::ReservationConflict,
+        LockError::ReservationNotFound,
+        LockError::ReservedForWaiters,
+        LockError::PreemptionPending,
+        LockError::PreemptionNotAllowed,
+        LockError::LockSessionNotFound,
+        LockError::PathConflict,
+        LockError::AcquireConditionFailed,
+        LockError::UserQuotaReached,
+        LockError::TenantQuotaReached,
+        LockError::NotInWaitQueue,
+        LockError::NamespaceFrozen,
+        LockError::BusinessIdConflict,
+        LockError::LockDraining,
+        LockError::ReservationFailed,
+        LockError::HeartbeatLockNotFound,
+        LockError::HeartbeatFailed,
+        LockError::HeartbeatConditionFailed,
+        LockError::ReleaseLockNotFound,
+        LockError::ReleaseFailed,
+        LockError::InvalidReleaseRequest,
+        LockError::ReleaseConditionFailed,
+        LockError::Unauthorized,
+        LockError::AdminNotFound,
+        LockError::InvalidAdminRequest,
+        LockError::AdminStorageError,
+        LockError::Forbidden,
+        LockError::LoginFailed,
+        LockError::IdentityProviderUnavailable,
+        LockError::StatusFailed,
+        LockError::ListFailed,
+        LockError::StatsFailed,
+        LockError::HistoryFailed,
+        LockError::StatsSamplingDisabled,
+        LockError::SessionNotFound,
+        LockError::SessionFailed,
+        LockError::InvalidSessionRequest,
+        LockError::StorageUnavailable,
+        LockError::InjectedFault,
+        LockError::StorageFull,
+        LockError::StorageTimeout,
+        LockError::RequestTimeout,
+        LockError::Overloaded,
+        LockError::IpNotAllowed,
+        LockError::InstanceFenced,
+        LockError::SequenceFailed,
+        LockError::RateLimitFailed,
+        LockError::TakeoverNotFound,
+        LockError::TakeoverNotPossible,
+        LockError::TakeoverFailed,
+        LockError::PresenceFailed,
+        LockError::HookRejected,
+    ];
+
+    pub fn code(self) -> i32 {
+        self as i32
+    }
+
+    pub fn from_code(code: i32) -> Option<Self> {
+        Self::ALL.iter().copied().find(|error| error.code() == code)
+    }
+
+    /// 错误码对应的 HTTP 状态码
+    pub fn status(self) -> StatusCode {
+        match self {
+            LockError::ValidationFailed | LockError::InvalidLockRequest | LockError::InvalidReleaseRequest | LockError::InvalidAdminRequest | LockError::InvalidSessionRequest => StatusCode::BAD_REQUEST,
+            LockError::LockHeld | LockError::AcquireFailed | LockError::Deadlock | LockError::ReservationConflict | LockError::ReservedForWaiters | LockError::PreemptionPending | LockError::PathConflict | LockError::BusinessIdConflict | LockError::LockDraining | LockError::TakeoverNotPossible => StatusCode::CONFLICT,
+            LockError::LockLookupFailed | LockError::PolicyLookupFailed | LockError::HeartbeatFailed | LockError::ReleaseFailed | LockError::AdminStorageError | LockError::StatusFailed | LockError::ListFailed | LockError::StatsFailed | LockError::HistoryFailed | LockError::SessionFailed | LockError::ReservationFailed | LockError::InjectedFault | LockError::SequenceFailed | LockError::RateLimitFailed | LockError::TakeoverFailed | LockError::PresenceFailed => StatusCode::INTERNAL_SERVER_ERROR,
+            LockError::NamespaceLimitReached | LockError::UserQuotaReached | LockError::TenantQuotaReached => StatusCode::TOO_MANY_REQUESTS,
+            LockError::ReservationNotFound | LockError::LockSessionNotFound | LockError::NotInWaitQueue | LockError::HeartbeatLockNotFound | LockError::ReleaseLockNotFound | LockError::AdminNotFound | LockError::StatsSamplingDisabled | LockError::SessionNotFound | LockError::TakeoverNotFound => StatusCode::NOT_FOUND,
+            LockError::PreemptionNotAllowed | LockError::Forbidden | LockError::IpNotAllowed | LockError::HookRejected => StatusCode::FORBIDDEN,
+            LockError::AcquireConditionFailed | LockError::HeartbeatConditionFailed | LockError::ReleaseConditionFailed => StatusCode::PRECONDITION_FAILED,
+            LockError::NamespaceFrozen => StatusCode::LOCKED,
+            LockError::Unauthorized | LockError::LoginFailed => StatusCode::UNAUTHORIZED,
+            LockError::IdentityProviderUnavailable => StatusCode::BAD_GATEWAY,
+            LockError::StorageUnavailable | LockError::StorageFull | LockError::Overloaded | LockError::InstanceFenced => StatusCode::SERVICE_UNAVAILABLE,
+            LockError::StorageTimeout | LockError::RequestTimeout => StatusCode::GATEWAY_TIMEOUT,
+        }
+    }
+
+    /// 错误码对应的简短标题（problem+json 的 title）
+    pub fn title(self) -> &'static str {
+        match self {
+            LockError::ValidationFailed | LockError::InvalidLockRequest | LockError::InvalidReleaseRequest | LockError::InvalidAdminRequest | LockError::InvalidSessionRequest => "Invalid request",
+            LockError::LockHeld | LockError::AcquireFailed => "Lock already held",
+            LockError::LockLookupFailed | LockError::PolicyLookupFailed | LockError::HeartbeatFailed | LockError::ReleaseFailed | LockError::AdminStorageError | LockError::StatusFailed | LockError::ListFailed | LockError::StatsFailed | LockError::HistoryFailed | LockError::SessionFailed | LockError::ReservationFailed | LockError::TakeoverFailed => "Storage error",
+            LockError::NamespaceLimitReached => "Namespace lock limit reached",
+            LockError::Deadlock => "Deadlock detected",
+            LockError::ReservationConflict => "Reservation conflict",
+            LockError::ReservationNotFound => "Reservation not found",
+            LockError::ReservedForWaiters => "Lock reserved for higher priority waiters",
+            LockError::PreemptionPending => "Preemption pending",
+            LockError::PreemptionNotAllowed => "Preemption not allowed",
+            LockError::LockSessionNotFound | LockError::SessionNotFound => "Session not found",
+            LockError::PathConflict => "Path conflict",
+            LockError::AcquireConditionFailed | LockError::HeartbeatConditionFailed | LockError::ReleaseConditionFailed => "Precondition failed",
+            LockError::UserQuotaReached => "User lock quota reached",
+            LockError::TenantQuotaReached => "Tenant lock quota reached",
+            LockError::NotInWaitQueue => "Not in wait queue",
+            LockError::NamespaceFrozen => "Namespace frozen",
+            LockError::BusinessIdConflict => "Business ID locked in another namespace",
+            LockError::LockDraining => "Lock draining",
+            LockError::HeartbeatLockNotFound | LockError::ReleaseLockNotFound => "Lock not found",
+            LockError::Unauthorized => "Unauthorized",
+            LockError::AdminNotFound => "Resource not found",
+            LockError::Forbidden => "Forbidden",
+            LockError::LoginFailed => "Login failed",
+            LockError::IdentityProviderUnavailable => "Identity provider unavailable",
+            LockError::StatsSamplingDisabled => "Stats sampling disabled",
+            LockError::StorageUnavailable => "Storage unavailable",
+            LockError::InjectedFault => "Injected fault",
+            LockError::StorageFull => "Storage full",
+            LockError::StorageTimeout => "Storage timeout",
+            LockError::RequestTimeout => "Request timeout",
+            LockError::Overloaded => "Server overloaded",
+            LockError::IpNotAllowed => "Source address not allowed",
+            LockError::InstanceFenced => "Instance fenced",
+            LockError::SequenceFailed => "Sequence allocation failed",
+            LockError::RateLimitFailed => "Rate limit check failed",
+            LockError::TakeoverNotFound => "Takeover request not found",
+            LockError::TakeoverNotPossible => "Takeover not possible",
+            LockError::PresenceFailed => "Presence operation failed",
+            LockError::HookRejected => "Rejected by hook",
+        }
+    }
+
+    /// 错误码的含义
+    pub fn description(self) -> &'static str {
+        match self {
+            LockError::ValidationFailed => "请求参数校验失败",
+            LockError::LockHeld => "锁已被他人持有",
+            LockError::AcquireFailed => "申请锁失败（抢占期间锁被释放等），可重试",
+            LockError::LockLookupFailed => "申请锁时读取锁失败",
+            LockError::PolicyLookupFailed => "申请锁时读取命名空间策略或配额失败",
+            LockError::InvalidLockRequest => "超时时间、附加信息等申请参数不合法",
+            LockError::NamespaceLimitReached => "命名空间锁数量达到上限",
+            LockError::Deadlock => "等待会形成死锁",
+            LockError::ReservationConflict => "预约时间窗重叠",
+            LockError::ReservationNotFound => "预约不存在",
+            LockError::ReservedForWaiters => "锁留给更高优先级的等待者",
+            LockError::PreemptionPending => "抢占已登记，等待宽限期结束",
+            LockError::PreemptionNotAllowed => "无权抢占锁",
+            LockError::LockSessionNotFound => "申请锁时指定的会话不存在",
+            LockError::PathConflict => "层级路径上的祖先或后代已被他人锁定",
+            LockError::AcquireConditionFailed => "条件获取的条件不满足",
+            LockError::UserQuotaReached => "用户持有的锁数量达到配额",
+            LockError::TenantQuotaReached => "租户持有的锁数量达到配额",
+            LockError::NotInWaitQueue => "用户不在等待队列中",
+            LockError::NamespaceFrozen => "命名空间已冻结",
+            LockError::BusinessIdConflict => "business_id 已在其他命名空间中被锁定",
+            LockError::LockDraining => "持有人的锁正在排空，不能重入申请",
+            LockError::ReservationFailed => "预约的存储操作失败",
+            LockError::HeartbeatLockNotFound => "心跳的锁不存在或已过期",
+            LockError::HeartbeatFailed => "更新心跳失败",
+            LockError::HeartbeatConditionFailed => "条件心跳的版本号不一致",
+            LockError::ReleaseLockNotFound => "释放的锁不存在或不属于该用户",
+            LockError::ReleaseFailed => "释放锁失败",
+            LockError::InvalidReleaseRequest => "释放参数不合法",
+            LockError::ReleaseConditionFailed => "条件释放的版本号不一致",
+            LockError::Unauthorized => "未授权",
+            LockError::AdminNotFound => "管理接口操作的资源不存在或功能未启用",
+            LockError::InvalidAdminRequest => "管理接口参数不合法",
+            LockError::AdminStorageError => "管理接口存储操作失败",
+            LockError::Forbidden => "管理角色无权访问或 CSRF 令牌无效",
+            LockError::LoginFailed => "管理接口登录失败",
+            LockError::IdentityProviderUnavailable => "身份提供方不可用",
+            LockError::StatusFailed => "查询锁状态失败",
+            LockError::ListFailed => "查询锁列表失败",
+            LockError::StatsFailed => "查询锁统计失败",
+            LockError::HistoryFailed => "查询锁历史失败",
+            LockError::StatsSamplingDisabled => "未启用统计采样",
+            LockError::SessionNotFound => "会话不存在或已过期",
+            LockError::SessionFailed => "读写会话或续期、释放会话的锁失败",
+            LockError::InvalidSessionRequest => "会话参数不合法",
+            LockError::StorageUnavailable => "存储不可用（断路器打开）",
+            LockError::InjectedFault => "故障注入的错误",
+            LockError::StorageFull => "内存存储已达到最大锁数量",
+            LockError::StorageTimeout => "存储操作超时",
+            LockError::RequestTimeout => "请求处理超时",
+            LockError::Overloaded => "服务过载，请按 Retry-After 重试",
+            LockError::IpNotAllowed => "来源地址不在允许访问的范围内",
+            LockError::InstanceFenced => "实例已被隔离，请求应发往交接后的新实例",
+            LockError::SequenceFailed => "分配序列失败",
+            LockError::RateLimitFailed => "限流检查失败",
+            LockError::TakeoverNotFound => "接管请求不存在或已答复",
+            LockError::TakeoverNotPossible => "锁未被他人持有，无法接管",
+            LockError::TakeoverFailed => "接管请求的存储操作失败",
+            LockError::PresenceFailed => "在场记录操作失败",
+            LockError::HookRejected => "申请被嵌入方注册的钩子拒绝（11000–11999 留给钩子使用）",
+        }
+    }
+}
+
+impl From<LockError> for i32 {
+    fn from(error: LockError) -> Self {
+        error.code()
+    }
+}
+
+/// 错误码目录中的一项（`GET /api/errors`）
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ErrorCodeInfo {
+    #[schema(example = 1001)]
+    pub code: i32,
+    /// 错误名称，供客户端生成常量
+    #[schema(example = "LockHeld")]
+    pub name: String,
+    /// 使用 strict 状态码模式或 problem+json 时的 HTTP 状态码
+    #[schema(example = 409)]
+    pub status: u16,
+    #[schema(example = "Lock already held")]
+    pub title: String,
+    pub description: String,
+}
+
+impl From<LockError> for ErrorCodeInfo {
+    fn from(error: LockError) -> Self {
+        Self {
+            code: error.code(),
+            name: format!("{:?}", error),
+            status: error.status().as_u16(),
+            title: error.title().to_string(),
+            description: error.description().to_string(),
+        }
+    }
+}
+
+/// 存储操作失败时返回的错误码：存储超时为 7004，其他错误为 code
+pub fn storage_error_code(code: impl Into<i32>, e: &anyhow::Error) -> i32 {
+    if e.is::<StorageTimeout>() {
+        LockError::StorageTimeout.code()
+    } else {
+        code.into()
+    }
+}
+
+/// 错误码对应的 HTTP 状态码，钩子使用的错误码为 403，目录外的错误码为 500
+pub fn error_status(code: i32) -> StatusCode {
+    match LockError::from_code(code) {
+        Some(error) => error.status(),
+        None if code == 0 => StatusCode::OK,
+        None if is_hook_code(code) => StatusCode::FORBIDDEN,
+        None => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/// 错误码对应的简短标题
+pub fn error_title(code: i32) -> &'static str {
+    match LockError::from_code(code) {
+        Some(error) => error.title(),
+        None if is_hook_code(code) => LockError::HookRejected.title(),
+        None => "Storage error",
+    }
+}
+
+fn is_hook_code(code: i32) -> bool {
+    (11000..=11999).contains(&code)
+}
+
+impl<T> ApiResponse<T> {
+    /// 错误码对应的 HTTP 状态码（strict 模式使用）
+    pub fn http_status(&self) -> StatusCode {
+        error_status(self.code)
+    }
+
+    /// 错误码对应的简短标题
+    pub fn error_title(&self) -> &'static str {
+        error_title(self.code)
+    }
+
+    /// 转换为 RFC 7807 错误响应
+    pub fn into_problem_response(self, req: &HttpRequest) -> HttpResponse {
+        let status = self.http_status();
+        let problem = ProblemDetails {
+            problem_type: format!("urn:fe-lock:error:{}", self.code),
+            title: self.error_title().to_string(),
+            status: status.as_u16(),
+            detail: self.message,
+            instance: req.path().to_string(),
+            code: self.code,
+            server_time: clock::now(req),
+            extensions: self.extensions,
+        };
+        HttpResponse::build(status)
+            .content_type(PROBLEM_JSON)
+            .json(problem)
+    }
+}
+
+impl<T: Serialize> Responder for ApiResponse<T> {
+    type Body = BoxBody;
+
+    fn respond_to(self, req: &HttpRequest) -> HttpResponse<Self::Body> {
+        if !self.success && accepts_problem_json(req) {
+            return self.into_problem_response(req);
         }
+
+        let strict = req
+            .app_data::<web::Data<Config>>()
+            .is_some_and(|config| config.http_status_mode == HttpStatusMode::Strict);
+        let status = if strict {
+            self.http_status()
+        } else {
+            StatusCode::OK
+        };
+        self.respond_with_status(req, status)
     }
 }
@@ -0,0 +1,102 @@
+//! 心跳附带的待处理通知
+//!
+//! 不能保持 WebSocket 连接的客户端通过心跳响应的 `notices` 得知需要处理的情况：锁被抢占、有人请求接管、
+//! 锁即将过期（登记了过期提醒的锁）以及命名空间被冻结。`PendingNotices` 订阅锁事件，按 lock_id 排队，
+//! 持有人下一次心跳时取出并清空，每条通知只返回一次；锁释放、过期或转移给他人后丢弃。
+//! 队列保存在本实例进程内，只包含本实例处理的事件，多实例部署时心跳落到其他实例上取不到这些通知。
+
+use crate::events::{EventSink, LockEvent, LockEventType};
+use crate::models::{LockInfo, LockNotice, LockNoticeType, NamespaceFreeze};
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use dashmap::DashMap;
+
+/// 每把锁最多保留的通知数量，超过时丢弃最早的通知
+const MAX_NOTICES: usize = 16;
+
+/// 超过该时间（秒）未被心跳取出的通知丢弃
+const NOTICE_TTL: i64 = 3600;
+
+#[derive(Default)]
+pub struct PendingNotices {
+    notices: DashMap<String, Vec<LockNotice>>, // lock_id -> 待返回的通知
+}
+
+impl PendingNotices {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn push(&self, lock_id: &str, notice: LockNotice) {
+        let mut notices = self.notices.entry(lock_id.to_string()).or_default();
+        if notices.len() == MAX_NOTICES {
+            notices.remove(0);
+        }
+        notices.push(notice);
+    }
+
+    /// 取出并清空锁的待处理通知
+    pub fn take(&self, lock_id: &str) -> Vec<LockNotice> {
+        self.notices
+            .remove(lock_id)
+            .map(|(_, notices)| notices)
+            .unwrap_or_default()
+    }
+
+    /// 命名空间被冻结时通知当前持有的锁
+    pub fn notify_frozen(&self, locks: &[LockInfo], freeze: &NamespaceFreeze, now: DateTime<Utc>) {
+        for lock_info in locks {
+            let mut notice = LockNotice::new(LockNoticeType::NamespaceFrozen, now);
+            notice.freeze = Some(freeze.clone());
+            self.push(&lock_info.lock_id, notice);
+        }
+    }
+
+    /// 丢弃超过保留时间未被取出的通知
+    pub fn prune(&self, now: DateTime<Utc>) {
+        let cutoff = now - Duration::seconds(NOTICE_TTL);
+        self.notices.retain(|_, notices| {
+            notices.retain(|notice| notice.at > cutoff);
+            !notices.is_empty()
+        });
+    }
+}
+
+#[async_trait]
+impl EventSink for PendingNotices {
+    fn name(&self) -> &str {
+        "notices"
+    }
+
+    async fn publish(&self, event: &LockEvent) -> Result<()> {
+        let lock_id = &event.lock.lock_id;
+        match event.event {
+            LockEventType::PreemptionPending => {
+                let mut notice = LockNotice::new(LockNoticeType::PreemptionPending, event.timestamp);
+                notice.preemption = event.lock.preemption.clone();
+                self.push(lock_id, notice);
+            }
+            LockEventType::TakeoverRequested => {
+                let mut notice = LockNotice::new(LockNoticeType::TakeoverRequested, event.timestamp);
+                notice.takeover = event.takeover.clone();
+                self.push(lock_id, notice);
+            }
+            LockEventType::ExpiringSoon => {
+                let mut notice = LockNotice::new(LockNoticeType::ExpiringSoon, event.timestamp);
+                notice.expires_at = event.lock.expires_at();
+                self.push(lock_id, notice);
+            }
+            // 锁已不再由该 lock_id 持有
+            LockEventType::Released
+            | LockEventType::Expired
+            | LockEventType::ForceReleased
+            | LockEventType::Preempted
+            | LockEventType::TakeoverApproved => {
+                self.notices.remove(lock_id);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
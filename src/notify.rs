@@ -0,0 +1,102 @@
+//! 锁释放通知
+//!
+//! `GET /api/lock/wait-release` 长轮询等待锁被释放。本实例处理的释放、过期和强制释放事件会立即唤醒等待者，
+//! 其他实例的释放（共享 Redis 存储时）和 Redis 自动过期通过每秒查询一次存储发现。
+
+use crate::clock::Clock;
+use crate::events::{EventSink, LockEvent, LockEventType};
+use crate::models::LockInfo;
+use crate::storage::LockStorage;
+use anyhow::Result;
+use async_trait::async_trait;
+use dashmap::DashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::time::Instant;
+
+/// 查询存储的间隔
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Default)]
+pub struct ReleaseNotifier {
+    waiters: DashMap<String, Arc<Notify>>, // lock_key -> 等待该锁释放的请求
+}
+
+impl ReleaseNotifier {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 等待锁被释放或过期，锁空闲时立即返回 None，超时仍被持有时返回当前的锁，是否过期按 clock 判断
+    pub async fn wait(
+        &self,
+        storage: &dyn LockStorage,
+        clock: &dyn Clock,
+        lock_key: &str,
+        timeout: Duration,
+    ) -> Result<Option<LockInfo>> {
+        let deadline = Instant::now() + timeout;
+        let notify = self
+            .waiters
+            .entry(lock_key.to_string())
+            .or_insert_with(|| Arc::new(Notify::new()))
+            .clone();
+        let result = loop {
+            // 先登记再查询，避免错过查询与等待之间的释放事件
+            let notified = notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            let current = match storage.get_lock(lock_key).await {
+                Ok(lock_info) => lock_info.filter(|lock_info| !lock_info.is_expired_at(clock.now())),
+                Err(e) => break Err(e),
+            };
+            let Some(current) = current else {
+                break Ok(None);
+            };
+            let now = Instant::now();
+            if now >= deadline {
+                break Ok(Some(current));
+            }
+
+            // 锁到期前不必等满一个查询间隔
+            let mut wake_at = deadline.min(now + POLL_INTERVAL);
+            if let Some(remaining) = current.expiry_deadline().map(|deadline| deadline - clock.now()) {
+                let remaining = remaining.to_std().unwrap_or_default() + Duration::from_millis(10);
+                wake_at = wake_at.min(now + remaining);
+            }
+            tokio::select! {
+                _ = notified => {}
+                _ = tokio::time::sleep_until(wake_at) => {}
+            }
+        };
+        drop(notify);
+        self.waiters
+            .remove_if(lock_key, |_, notify| Arc::strong_count(notify) == 1);
+        result
+    }
+}
+
+#[async_trait]
+impl EventSink for ReleaseNotifier {
+    fn name(&self) -> &str {
+        "release-notifier"
+    }
+
+    async fn publish(&self, event: &LockEvent) -> Result<()> {
+        if matches!(
+            event.event,
+            LockEventType::Released
+                | LockEventType::Expired
+                | LockEventType::ForceReleased
+                | LockEventType::Preempted
+                | LockEventType::TakeoverApproved
+        ) {
+            if let Some(notify) = self.waiters.get(&event.lock.get_lock_key()) {
+                notify.notify_waiters();
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,502 @@
+//! 管理接口的 OpenID Connect 登录
+//!
+//! 配置 `OIDC_ISSUER` 后，运维人员通过 `/api/admin/auth/login` 跳转到身份提供方登录（授权码模式），回调时用授权码换取 ID Token，
+//! 按身份提供方 JWKS 中的公钥校验签名（RS256）以及 `iss`、`aud`、`exp` 和 `nonce`，再将用户组声明映射为管理角色。
+//! 登录成功后签发 HttpOnly 的会话 Cookie，修改类请求还需在 `X-CSRF-Token` 请求头中携带会话的 CSRF 令牌。
+//! 发起登录的状态和登录会话以记录的形式保存在锁存储中，多实例部署时回调和后续请求可以发往任意实例，实例重启后会话仍然有效。
+//! 会话记录以会话 ID 的 SHA-256 为键，导出的记录不能直接用作会话 Cookie。
+//! 未完成的登录最多保存 [`MAX_PENDING_LOGINS`] 个，发起登录时先清理已过期的，仍然已满时拒绝；过期的会话在新的登录完成时清理。
+
+use crate::config::{AdminRole, Config};
+use crate::lockops::OpError;
+use crate::models::LockError;
+use crate::storage::LockStorage;
+use anyhow::{anyhow, bail, Context, Result};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Duration, Utc};
+use parking_lot::RwLock;
+use reqwest::Url;
+use ring::rand::{SecureRandom, SystemRandom};
+use ring::signature::{RsaPublicKeyComponents, RSA_PKCS1_2048_8192_SHA256};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::OnceCell;
+
+/// 会话 Cookie，HttpOnly
+pub const SESSION_COOKIE: &str = "fe_lock_admin_session";
+/// CSRF 令牌 Cookie，页面脚本读取后放入 `X-CSRF-Token` 请求头
+pub const CSRF_COOKIE: &str = "fe_lock_admin_csrf";
+/// 登录状态 Cookie，将回调绑定到发起登录的浏览器
+pub const LOGIN_COOKIE: &str = "fe_lock_admin_login";
+pub const CSRF_HEADER: &str = "X-CSRF-Token";
+
+/// 发起登录后完成登录的时限（秒）
+pub const LOGIN_TTL: i64 = 600;
+/// 同时保存的未完成登录的最大数量
+pub const MAX_PENDING_LOGINS: usize = 1000;
+
+/// 未完成的登录在存储中的记录类别，以 state 为键
+pub const PENDING_RECORD_KIND: &str = "oidc_login";
+/// 登录会话在存储中的记录类别，以会话 ID 的 SHA-256 为键
+pub const SESSION_RECORD_KIND: &str = "admin_session";
+
+/// 身份提供方的元数据，取自 `<issuer>/.well-known/openid-configuration`
+#[derive(Deserialize)]
+struct Discovery {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Deserialize)]
+struct Jwks {
+    keys: Vec<Jwk>,
+}
+
+#[derive(Deserialize)]
+struct Jwk {
+    kty: String,
+    kid: Option<String>,
+    n: Option<String>,
+    e: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct JwtHeader {
+    alg: String,
+    kid: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+/// JWKS 中的 RSA 公钥及其 kid
+type SigningKey = (Option<String>, RsaPublicKeyComponents<Vec<u8>>);
+
+#[derive(Serialize, Deserialize)]
+struct PendingLogin {
+    nonce: String,
+    return_to: Option<String>,
+    expires_at: DateTime<Utc>,
+}
+
+/// 存储中的会话记录，不含会话 ID
+#[derive(Serialize, Deserialize)]
+struct StoredSession {
+    subject: String,
+    name: Option<String>,
+    role: AdminRole,
+    csrf_token: String,
+    expires_at: DateTime<Utc>,
+}
+
+/// 登录后的管理会话
+#[derive(Debug, Clone)]
+pub struct AdminSession {
+    pub id: String,
+    pub subject: String,
+    pub name: Option<String>,
+    pub role: AdminRole,
+    pub csrf_token: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl AdminRole {
+    /// 是否允许以 method 访问管理接口 path（`/api/admin/` 之后的部分）
+    pub fn allows(self, method: &str, path: &str) -> bool {
+        let read_only = matches!(method, "GET" | "HEAD" | "OPTIONS") || path == "auth/logout";
+        match self {
+            AdminRole::Viewer => read_only,
+            AdminRole::Operator => read_only || path.starts_with("lock/"),
+            AdminRole::Admin => true,
+        }
+    }
+}
+
+/// OIDC 登录和管理会话
+pub struct OidcLogin {
+    issuer: String,
+    client_id: String,
+    client_secret: Option<String>,
+    redirect_url: String,
+    scopes: String,
+    groups_claim: String,
+    role_mapping: Vec<(String, AdminRole)>,
+    session_ttl: Duration,
+    secure_cookies: bool,
+    http: reqwest::Client,
+    discovery: OnceCell<Discovery>,
+    keys: RwLock<Vec<SigningKey>>,
+    rng: SystemRandom,
+}
+
+impl OidcLogin {
+    /// 未配置 OIDC_ISSUER 时返回 None；缺少客户端配置时仍然启用，管理接口只接受令牌，登录返回错误
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let issuer = config.oidc_issuer.clone()?;
+        if config.oidc_client_id.is_empty() || config.oidc_redirect_url.is_none() {
+            log::error!("[OIDC] OIDC_ISSUER requires OIDC_CLIENT_ID and OIDC_REDIRECT_URL, admin login is unavailable");
+        }
+        if config.oidc_role_mapping.is_empty() {
+            log::warn!("[OIDC] OIDC_ROLE_MAPPING is empty, no user can log in to the admin API");
+        }
+        let redirect_url = config.oidc_redirect_url.clone().unwrap_or_default();
+        Some(Self {
+            issuer,
+            client_id: config.oidc_client_id.clone(),
+            client_secret: config.oidc_client_secret.clone(),
+            secure_cookies: redirect_url.starts_with("https://"),
+            redirect_url,
+            scopes: config.oidc_scopes.clone(),
+            groups_claim: config.oidc_groups_claim.clone(),
+            role_mapping: config.oidc_role_mapping.clone(),
+            session_ttl: Duration::seconds(config.oidc_session_ttl as i64),
+            http: reqwest::Client::builder()
+                .timeout(std::time::Duration::from_secs(10))
+                .build()
+                .unwrap_or_default(),
+            discovery: OnceCell::new(),
+            keys: RwLock::new(Vec::new()),
+            rng: SystemRandom::new(),
+        })
+    }
+
+    /// 回调地址为 https 时 Cookie 带 Secure 属性
+    pub fn secure_cookies(&self) -> bool {
+        self.secure_cookies
+    }
+
+    /// 发起登录，返回身份提供方的授权地址和 state
+    pub async fn begin(
+        &self,
+        storage: &dyn LockStorage,
+        return_to: Option<String>,
+        now: DateTime<Utc>,
+    ) -> Result<(String, String), OpError> {
+        if self.client_id.is_empty() || self.redirect_url.is_empty() {
+            return Err(OpError::new(LockError::IdentityProviderUnavailable, "OIDC client is not configured".to_string()));
+        }
+        let discovery = self.discovery().await.map_err(provider_error)?;
+        let pending = purge_expired::<PendingLogin>(storage, PENDING_RECORD_KIND, now, |login| login.expires_at)
+            .await
+            .map_err(storage_error)?;
+        if pending >= MAX_PENDING_LOGINS {
+            log::warn!("[OIDC] {} logins are pending, rejecting new login", pending);
+            return Err(OpError::new(LockError::Overloaded, "Too many pending logins, retry later".to_string()));
+        }
+
+        let state = self.random_token();
+        let nonce = self.random_token();
+        let url = Url::parse_with_params(
+            &discovery.authorization_endpoint,
+            [
+                ("response_type", "code"),
+                ("client_id", self.client_id.as_str()),
+                ("redirect_uri", self.redirect_url.as_str()),
+                ("scope", self.scopes.as_str()),
+                ("state", state.as_str()),
+                ("nonce", nonce.as_str()),
+            ],
+        )
+        .map_err(|e| provider_error(anyhow!("Invalid authorization endpoint: {}", e)))?;
+        let login = PendingLogin {
+            nonce,
+            return_to,
+            expires_at: now + Duration::seconds(LOGIN_TTL),
+        };
+        let value = serde_json::to_string(&login).map_err(|e| storage_error(e.into()))?;
+        if !storage
+            .swap_record(PENDING_RECORD_KIND, &state, None, Some(&value))
+            .await
+            .map_err(storage_error)?
+        {
+            return Err(storage_error(anyhow!("login state {} already exists", state)));
+        }
+        Ok((url.to_string(), state))
+    }
+
+    /// 用授权码完成登录，校验 ID Token 并按用户组映射角色，返回新的会话和发起登录时指定的跳转地址
+    pub async fn complete(
+        &self,
+        storage: &dyn LockStorage,
+        state: &str,
+        code: &str,
+        now: DateTime<Utc>,
+    ) -> Result<(AdminSession, Option<String>), OpError> {
+        // 删除成功才能使用，同一 state 只能完成一次登录
+        let value = storage
+            .get_record(PENDING_RECORD_KIND, state)
+            .await
+            .map_err(storage_error)?;
+        let consumed = match &value {
+            Some(value) => storage
+                .swap_record(PENDING_RECORD_KIND, state, Some(value), None)
+                .await
+                .map_err(storage_error)?,
+            None => false,
+        };
+        let login = value
+            .filter(|_| consumed)
+            .and_then(|value| serde_json::from_str::<PendingLogin>(&value).ok())
+            .filter(|login| login.expires_at > now)
+            .ok_or_else(|| OpError::new(LockError::LoginFailed, "Login state is invalid or expired".to_string()))?;
+
+        let id_token = self.exchange_code(code).await.map_err(provider_error)?;
+        let claims = self
+            .verify_id_token(&id_token, &login.nonce, now)
+            .await
+            .map_err(|e| OpError::new(LockError::LoginFailed, format!("Invalid ID token: {}", e)))?;
+
+        let subject = claims["sub"]
+            .as_str()
+            .ok_or_else(|| OpError::new(LockError::LoginFailed, "Invalid ID token: missing sub".to_string()))?
+            .to_string();
+        let groups = match &claims[self.groups_claim.as_str()] {
+            Value::Array(groups) => groups.iter().filter_map(Value::as_str).collect(),
+            Value::String(group) => vec![group.as_str()],
+            _ => Vec::new(),
+        };
+        let role = self
+            .role_mapping
+            .iter()
+            .filter(|(group, _)| groups.contains(&group.as_str()))
+            .map(|(_, role)| *role)
+            .max()
+            .ok_or_else(|| OpError::new(LockError::Forbidden, format!("User {} has no admin role", subject)))?;
+        let name = ["name", "preferred_username", "email"]
+            .iter()
+            .find_map(|claim| claims[*claim].as_str())
+            .map(str::to_string);
+
+        let session = AdminSession {
+            id: self.random_token(),
+            subject,
+            name,
+            role,
+            csrf_token: self.random_token(),
+            expires_at: now + self.session_ttl,
+        };
+        purge_expired::<StoredSession>(storage, SESSION_RECORD_KIND, now, |session| session.expires_at)
+            .await
+            .map_err(storage_error)?;
+        let stored = StoredSession {
+            subject: session.subject.clone(),
+            name: session.name.clone(),
+            role: session.role,
+            csrf_token: session.csrf_token.clone(),
+            expires_at: session.expires_at,
+        };
+        let value = serde_json::to_string(&stored).map_err(|e| storage_error(e.into()))?;
+        if !storage
+            .swap_record(SESSION_RECORD_KIND, &session_key(&session.id), None, Some(&value))
+            .await
+            .map_err(storage_error)?
+        {
+            return Err(storage_error(anyhow!("session {} already exists", session.subject)));
+        }
+        log::info!("[OIDC] {} logged in as {:?}", session.subject, session.role);
+        Ok((session, login.return_to))
+    }
+
+    /// 会话 ID 对应的未过期会话
+    pub async fn session(&self, storage: &dyn LockStorage, id: &str, now: DateTime<Utc>) -> Result<Option<AdminSession>> {
+        let key = session_key(id);
+        let Some(value) = storage.get_record(SESSION_RECORD_KIND, &key).await? else {
+            return Ok(None);
+        };
+        let stored: StoredSession = serde_json::from_str(&value)?;
+        if stored.expires_at <= now {
+            storage.swap_record(SESSION_RECORD_KIND, &key, Some(&value), None).await?;
+            return Ok(None);
+        }
+        Ok(Some(AdminSession {
+            id: id.to_string(),
+            subject: stored.subject,
+            name: stored.name,
+            role: stored.role,
+            csrf_token: stored.csrf_token,
+            expires_at: stored.expires_at,
+        }))
+    }
+
+    /// 注销会话，返回会话是否存在
+    pub async fn logout(&self, storage: &dyn LockStorage, id: &str) -> Result<bool> {
+        let key = session_key(id);
+        loop {
+            let Some(value) = storage.get_record(SESSION_RECORD_KIND, &key).await? else {
+                return Ok(false);
+            };
+            if storage.swap_record(SESSION_RECORD_KIND, &key, Some(&value), None).await? {
+                return Ok(true);
+            }
+        }
+    }
+
+    async fn discovery(&self) -> Result<&Discovery> {
+        self.discovery
+            .get_or_try_init(|| async {
+                let url = format!("{}/.well-known/openid-configuration", self.issuer);
+                let discovery: Discovery = self
+                    .http
+                    .get(&url)
+                    .send()
+                    .await
+                    .and_then(|response| response.error_for_status())
+                    .with_context(|| format!("Failed to fetch {}", url))?
+                    .json()
+                    .await
+                    .with_context(|| format!("Invalid OpenID configuration from {}", url))?;
+                Ok(discovery)
+            })
+            .await
+    }
+
+    async fn exchange_code(&self, code: &str) -> Result<String> {
+        let discovery = self.discovery().await?;
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", self.redirect_url.as_str()),
+            ("client_id", self.client_id.as_str()),
+        ];
+        if let Some(secret) = &self.client_secret {
+            form.push(("client_secret", secret.as_str()));
+        }
+        let response = self
+            .http
+            .post(&discovery.token_endpoint)
+            .form(&form)
+            .send()
+            .await
+            .context("Token request failed")?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            bail!("Token endpoint returned {}: {}", status, body);
+        }
+        let token: TokenResponse = response.json().await.context("Invalid token response")?;
+        Ok(token.id_token)
+    }
+
+    async fn verify_id_token(&self, token: &str, nonce: &str, now: DateTime<Utc>) -> Result<Value> {
+        let mut parts = token.split('.');
+        let (header, claims, signature) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(header), Some(claims), Some(signature), None) => (header, claims, signature),
+            _ => bail!("malformed JWT"),
+        };
+        let signed = &token[..header.len() + 1 + claims.len()];
+        let header: JwtHeader = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(header)?)?;
+        if header.alg != "RS256" {
+            bail!("unsupported algorithm {}", header.alg);
+        }
+        let signature = URL_SAFE_NO_PAD.decode(signature)?;
+
+        // 身份提供方轮换密钥后 kid 可能不在缓存的 JWKS 中，重新获取一次
+        if !self.verify_signature(header.kid.as_deref(), signed.as_bytes(), &signature) {
+            self.refresh_keys().await?;
+            if !self.verify_signature(header.kid.as_deref(), signed.as_bytes(), &signature) {
+                bail!("signature verification failed");
+            }
+        }
+
+        let claims: Value = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(claims)?)?;
+        let issuer = &self.discovery().await?.issuer;
+        if claims["iss"].as_str() != Some(issuer.as_str()) {
+            bail!("unexpected issuer");
+        }
+        let audience_ok = match &claims["aud"] {
+            Value::String(audience) => *audience == self.client_id,
+            Value::Array(audiences) => audiences.iter().any(|audience| audience.as_str() == Some(&self.client_id)),
+            _ => false,
+        };
+        if !audience_ok {
+            bail!("unexpected audience");
+        }
+        if claims["exp"].as_i64().is_none_or(|exp| now.timestamp() >= exp) {
+            bail!("token expired");
+        }
+        if claims["nonce"].as_str() != Some(nonce) {
+            bail!("nonce mismatch");
+        }
+        Ok(claims)
+    }
+
+    fn verify_signature(&self, kid: Option<&str>, message: &[u8], signature: &[u8]) -> bool {
+        self.keys
+            .read()
+            .iter()
+            .filter(|(key_id, _)| kid.is_none() || key_id.as_deref() == kid)
+            .any(|(_, key)| key.verify(&RSA_PKCS1_2048_8192_SHA256, message, signature).is_ok())
+    }
+
+    async fn refresh_keys(&self) -> Result<()> {
+        let jwks_uri = &self.discovery().await?.jwks_uri;
+        let jwks: Jwks = self
+            .http
+            .get(jwks_uri)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .with_context(|| format!("Failed to fetch {}", jwks_uri))?
+            .json()
+            .await
+            .context("Invalid JWKS")?;
+        let keys = jwks
+            .keys
+            .into_iter()
+            .filter(|key| key.kty == "RSA")
+            .filter_map(|key| {
+                let n = URL_SAFE_NO_PAD.decode(key.n?).ok()?;
+                let e = URL_SAFE_NO_PAD.decode(key.e?).ok()?;
+                Some((key.kid, RsaPublicKeyComponents { n, e }))
+            })
+            .collect();
+        *self.keys.write() = keys;
+        Ok(())
+    }
+
+    fn random_token(&self) -> String {
+        let mut bytes = [0u8; 32];
+        self.rng.fill(&mut bytes).expect("system random number generator failed");
+        URL_SAFE_NO_PAD.encode(bytes)
+    }
+}
+
+/// 会话记录的键：会话 ID 的 SHA-256（十六进制）
+fn session_key(id: &str) -> String {
+    let digest = ring::digest::digest(&ring::digest::SHA256, id.as_bytes());
+    digest.as_ref().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// 删除 kind 中已过期的记录，返回剩余的记录数量；无法解析的记录视为已过期
+async fn purge_expired<T: serde::de::DeserializeOwned>(
+    storage: &dyn LockStorage,
+    kind: &str,
+    now: DateTime<Utc>,
+    expires_at: impl Fn(&T) -> DateTime<Utc>,
+) -> Result<usize> {
+    let records = storage.list_records(kind).await?;
+    let mut remaining = 0;
+    for (id, value) in records {
+        let expired = !serde_json::from_str::<T>(&value).is_ok_and(|record| expires_at(&record) > now);
+        // 期间被其他请求修改或删除的记录留到下次清理
+        if !expired || !storage.swap_record(kind, &id, Some(&value), None).await? {
+            remaining += 1;
+        }
+    }
+    Ok(remaining)
+}
+
+fn storage_error(e: anyhow::Error) -> OpError {
+    log::error!("[OIDC] Failed to access login state: {:#}", e);
+    OpError::new(LockError::StorageUnavailable, "Failed to access login state".to_string())
+}
+
+fn provider_error(e: anyhow::Error) -> OpError {
+    log::error!("[OIDC] Identity provider request failed: {:#}", e);
+    OpError::new(LockError::IdentityProviderUnavailable, "Identity provider unavailable".to_string())
+}
@@ -0,0 +1,61 @@
+//! 客户端网络信息
+//!
+//! 申请锁时记录客户端 IP、User-Agent 和客户端上报的主机名，锁卡住时可据此找到持有锁的机器。
+//! 直连来源属于 `TRUSTED_PROXIES` 时按 `X-Forwarded-For` 从右向左跳过受信任的代理，第一个不受信任的地址即为客户端 IP；
+//! 否则使用直连来源地址，避免客户端伪造请求头。Unix 域套接字上的连接视为来自本机的反向代理。
+
+use crate::models::ClientContext;
+use actix_web::http::header;
+use actix_web::HttpRequest;
+use ipnet::IpNet;
+use std::net::IpAddr;
+
+/// User-Agent 的最大保存长度
+const MAX_USER_AGENT_LEN: usize = 256;
+
+/// 请求的客户端网络信息，hostname 为客户端在请求体中上报的主机名
+pub fn client_context(req: &HttpRequest, hostname: Option<String>, trusted_proxies: &[IpNet]) -> ClientContext {
+    let user_agent = req
+        .headers()
+        .get(header::USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| truncate(value, MAX_USER_AGENT_LEN));
+    ClientContext {
+        ip: client_ip(req, trusted_proxies).map(|ip| ip.to_string()),
+        user_agent,
+        hostname,
+    }
+}
+
+/// 客户端 IP，无法确定时为 None
+pub fn client_ip(req: &HttpRequest, trusted_proxies: &[IpNet]) -> Option<IpAddr> {
+    let peer = req.peer_addr().map(|addr| addr.ip());
+    let is_trusted = |ip: &IpAddr| trusted_proxies.iter().any(|net| net.contains(ip));
+    if peer.as_ref().is_some_and(|ip| !is_trusted(ip)) {
+        return peer;
+    }
+
+    // 多个 X-Forwarded-For 头按出现顺序拼接
+    let forwarded: Vec<IpAddr> = req
+        .headers()
+        .get_all("x-forwarded-for")
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|addr| addr.trim().parse().ok())
+        .collect();
+    forwarded
+        .iter()
+        .rev()
+        .find(|ip| !is_trusted(ip))
+        .or(forwarded.first())
+        .copied()
+        .or(peer)
+}
+
+fn truncate(value: &str, max_len: usize) -> String {
+    let mut end = value.len().min(max_len);
+    while !value.is_char_boundary(end) {
+        end -= 1;
+    }
+    value[..end].to_string()
+}
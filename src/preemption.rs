@@ -0,0 +1,134 @@
+//! 锁抢占
+//!
+//! `LOCK_PREEMPTORS` 中的用户以 `preempt: true` 申请被他人持有的锁时，服务端在锁上登记抢占通知并发布 `preemption_pending` 事件，
+//! 持有人可通过事件或心跳响应得知，需要在宽限期内保存并释放锁。宽限期内持有人释放或锁过期时立即授予抢占人，
+//! 宽限期结束仍未释放则将锁转移给抢占人，并为原持有人发布 `preempted` 事件。
+//! 待转移的抢占保存在本实例进程内，多实例部署时由接受抢占的实例负责转移，实例重启后需抢占人重新申请。
+
+use crate::clock::Clock;
+use crate::events::{EventBus, LockEvent, LockEventType};
+use crate::models::LockInfo;
+use crate::storage::LockStorage;
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+
+#[derive(Clone)]
+struct PendingTransfer {
+    holder_lock_id: String,
+    lock_info: LockInfo, // 转移后抢占人持有的锁
+    deadline: DateTime<Utc>,
+}
+
+#[derive(Default)]
+pub struct PreemptionScheduler {
+    pending: DashMap<String, PendingTransfer>, // lock_key -> 待转移的抢占
+}
+
+impl PreemptionScheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 登记待转移的抢占，同一抢占人对同一持有人重复申请时沿用已登记的锁，返回转移后抢占人持有的锁
+    pub fn schedule(
+        &self,
+        holder_lock_id: &str,
+        lock_info: LockInfo,
+        deadline: DateTime<Utc>,
+    ) -> LockInfo {
+        let mut entry = self
+            .pending
+            .entry(lock_info.get_lock_key())
+            .or_insert_with(|| PendingTransfer {
+                holder_lock_id: holder_lock_id.to_string(),
+                lock_info: lock_info.clone(),
+                deadline,
+            });
+        if entry.holder_lock_id != holder_lock_id || entry.lock_info.user_id != lock_info.user_id {
+            *entry = PendingTransfer {
+                holder_lock_id: holder_lock_id.to_string(),
+                lock_info,
+                deadline,
+            };
+        }
+        entry.lock_info.clone()
+    }
+
+    /// 授予持有人已释放的锁，转移宽限期已结束的锁
+    pub async fn transfer_due(&self, storage: &dyn LockStorage, events: &EventBus, clock: &dyn Clock) {
+        let pending: Vec<(String, PendingTransfer)> = self
+            .pending
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect();
+
+        for (lock_key, transfer) in pending {
+            let now = clock.now();
+            let holder_active = match storage.get_lock(&lock_key).await {
+                Ok(Some(lock_info)) => {
+                    lock_info.lock_id == transfer.holder_lock_id && !lock_info.is_expired_at(now)
+                }
+                Ok(None) => false,
+                Err(e) => {
+                    log::error!("[PREEMPT] Failed to load lock {}: {}", lock_key, e);
+                    continue;
+                }
+            };
+            if holder_active && now < transfer.deadline {
+                continue;
+            }
+
+            let mut lock_info = transfer.lock_info.clone();
+            lock_info.locked_at = now;
+            lock_info.last_heartbeat = now;
+
+            if holder_active {
+                match storage.transfer(&transfer.holder_lock_id, lock_info.clone()).await {
+                    Ok(Some(previous)) => {
+                        lock_info.version = previous.version + 1;
+                        log::info!(
+                            "[PREEMPT] Grace period ended, lock transferred - lock_key: {}, from: {}, to: {}",
+                            lock_key,
+                            previous.user_id,
+                            lock_info.user_id
+                        );
+                        events.publish(LockEvent::new(LockEventType::Preempted, previous));
+                        events.publish(LockEvent::new(LockEventType::Acquired, lock_info));
+                    }
+                    // 持有人恰好释放，下一轮按锁已空闲处理
+                    Ok(None) => continue,
+                    Err(e) => {
+                        log::error!("[PREEMPT] Failed to transfer lock {}: {}", lock_key, e);
+                        continue;
+                    }
+                }
+            } else {
+                match storage.try_acquire(lock_info.clone()).await {
+                    Ok(acquired) if acquired.acquired => {
+                        if let Some(replaced) = acquired.replaced {
+                            events.publish(LockEvent::new(LockEventType::Expired, *replaced));
+                        }
+                        log::info!(
+                            "[PREEMPT] Holder released, lock granted to preemptor - lock_key: {}, user_id: {}",
+                            lock_key,
+                            lock_info.user_id
+                        );
+                        events.publish(LockEvent::new(LockEventType::Acquired, lock_info));
+                    }
+                    // 锁已被他人获取，抢占作废，抢占人可重新申请
+                    Ok(_) => log::info!(
+                        "[PREEMPT] Lock taken by another user before transfer - lock_key: {}",
+                        lock_key
+                    ),
+                    Err(e) => {
+                        log::error!("[PREEMPT] Failed to acquire preempted lock {}: {}", lock_key, e);
+                        continue;
+                    }
+                }
+            }
+            self.pending.remove_if(&lock_key, |_, entry| {
+                entry.lock_info.lock_id == transfer.lock_info.lock_id
+            });
+        }
+    }
+}
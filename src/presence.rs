@@ -0,0 +1,80 @@
+//! 在线状态（presence）
+//!
+//! 协作场景中除了"谁持有编辑锁"，还需要知道"谁正在查看"。客户端通过 `/api/presence/join` 登记为某个 business_id 的在场者，
+//! 在场记录就是保留命名空间 `__presence.<namespace>` 中业务键为 `<business_id>#<user_id>` 的锁，
+//! 复用锁的存储、心跳和过期清理：超时未再次登记（或未按 presence_id 心跳）即离开，过期清理时不发布锁事件。
+
+use crate::advisory;
+use crate::models::{LockInfo, PresenceList, PresenceMember};
+use crate::storage::LockStorage;
+use crate::tenant;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+
+/// 在场记录所在命名空间的前缀，申请锁等接口不允许使用
+pub const NAMESPACE_PREFIX: &str = "__presence.";
+
+/// namespace 的在场记录所在的命名空间，租户的命名空间在租户前缀之后加前缀，使在场记录同样属于该租户
+pub fn namespace(namespace: &str) -> String {
+    match namespace.split_once(tenant::SEPARATOR) {
+        Some((tenant_id, namespace)) => format!("{}{}{}{}", tenant_id, tenant::SEPARATOR, NAMESPACE_PREFIX, namespace),
+        None => format!("{}{}", NAMESPACE_PREFIX, namespace),
+    }
+}
+
+pub fn is_presence_namespace(namespace: &str) -> bool {
+    namespace
+        .split_once(tenant::SEPARATOR)
+        .map_or(namespace, |(_, namespace)| namespace)
+        .starts_with(NAMESPACE_PREFIX)
+}
+
+/// 登记在场，已在场时刷新心跳并返回已有的记录；lock_info 的命名空间和业务键需已转换为在场记录的形式
+pub async fn join(storage: &dyn LockStorage, lock_info: LockInfo) -> Result<LockInfo> {
+    let lock_key = lock_info.get_lock_key();
+    if !storage.try_acquire(lock_info).await?.acquired {
+        return Err(anyhow!("Presence entry {} is held by another user", lock_key));
+    }
+    storage
+        .get_lock(&lock_key)
+        .await?
+        .ok_or_else(|| anyhow!("Presence entry {} disappeared after join", lock_key))
+}
+
+/// 离开，返回是否曾在场
+pub async fn leave(storage: &dyn LockStorage, namespace: &str, business_id: &str, user_id: &str) -> Result<bool> {
+    let lock_key = format!(
+        "{}:{}",
+        self::namespace(namespace),
+        advisory::holder_business_id(business_id, user_id)
+    );
+    Ok(storage.release_owned(&lock_key, user_id).await?.is_some())
+}
+
+/// business_id 截至 now 的在场者，按登记时间排列
+pub async fn members(
+    storage: &dyn LockStorage,
+    namespace: &str,
+    business_id: &str,
+    now: DateTime<Utc>,
+) -> Result<Vec<LockInfo>> {
+    advisory::holders(storage, &self::namespace(namespace), business_id, now).await
+}
+
+/// business_id 截至 now 的在场者和当前持有的锁
+pub async fn list(
+    storage: &dyn LockStorage,
+    namespace: &str,
+    business_id: &str,
+    now: DateTime<Utc>,
+) -> Result<PresenceList> {
+    let members = members(storage, namespace, business_id, now).await?;
+    let lock = storage
+        .get_lock(&format!("{}:{}", namespace, business_id))
+        .await?
+        .filter(|lock_info| !lock_info.is_expired_at(now));
+    Ok(PresenceList {
+        members: members.iter().map(PresenceMember::from).collect(),
+        lock,
+    })
+}
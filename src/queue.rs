@@ -0,0 +1,237 @@
+//! 锁等待队列
+//!
+//! 申请锁失败的用户按有效优先级从高到低、同优先级按首次失败时间排队，在 `ttl` 内再次申请视为仍在等待，超时未重试则移出队列。
+//! 有效优先级为申请时的 `priority` 加上老化加成（每等待 `aging` 时长加 1），避免低优先级的等待者一直被后来的高优先级申请插队。
+//! 队列保存在本实例进程内，多实例部署时只统计经由本实例申请的等待者。
+
+use crate::models::QueuedWaiter;
+use crate::storage::LockStorage;
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use dashmap::DashMap;
+use std::collections::HashSet;
+
+/// 死锁检测时最多遍历的持有人数量
+const MAX_DEADLOCK_SEARCH: usize = 256;
+
+struct Waiter {
+    user_id: String,
+    user_name: String,
+    priority: i32,
+    enqueued_at: DateTime<Utc>,
+    last_seen: DateTime<Utc>,
+}
+
+pub struct WaitQueue {
+    waiters: DashMap<String, Vec<Waiter>>, // lock_key -> 按排队先后排列的等待者
+    ttl: Duration,
+    aging: Option<Duration>,
+}
+
+impl WaitQueue {
+    /// `aging_secs` 为 0 时不做老化
+    pub fn new(ttl_secs: u64, aging_secs: u64) -> Self {
+        Self {
+            waiters: DashMap::new(),
+            ttl: Duration::seconds(ttl_secs as i64),
+            aging: (aging_secs > 0).then(|| Duration::seconds(aging_secs as i64)),
+        }
+    }
+
+    /// 截至 now 的有效优先级
+    fn effective_priority(&self, priority: i32, waited: Duration) -> i64 {
+        let bonus = self.aging.map_or(0, |aging| waited.num_seconds() / aging.num_seconds());
+        priority as i64 + bonus
+    }
+
+    /// 排在 (priority, enqueued_at) 前面的等待者人数，不含 user_id 本人
+    fn count_ahead(
+        &self,
+        waiters: &[Waiter],
+        user_id: &str,
+        priority: i32,
+        enqueued_at: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> usize {
+        let rank = self.effective_priority(priority, now - enqueued_at);
+        waiters
+            .iter()
+            .filter(|waiter| waiter.user_id != user_id)
+            .filter(|waiter| {
+                let waiter_rank = self.effective_priority(waiter.priority, now - waiter.enqueued_at);
+                waiter_rank > rank || (waiter_rank == rank && waiter.enqueued_at < enqueued_at)
+            })
+            .count()
+    }
+
+    /// 登记等待者（已在队列中则刷新优先级和最近申请时间），返回排在其前面的人数
+    pub fn enqueue(
+        &self,
+        lock_key: &str,
+        user_id: &str,
+        user_name: &str,
+        priority: i32,
+        now: DateTime<Utc>,
+    ) -> usize {
+        let mut waiters = self.waiters.entry(lock_key.to_string()).or_default();
+        waiters.retain(|waiter| now - waiter.last_seen < self.ttl);
+        let enqueued_at = match waiters.iter_mut().find(|waiter| waiter.user_id == user_id) {
+            Some(waiter) => {
+                waiter.user_name = user_name.to_string();
+                waiter.priority = priority;
+                waiter.last_seen = now;
+                waiter.enqueued_at
+            }
+            None => {
+                waiters.push(Waiter {
+                    user_id: user_id.to_string(),
+                    user_name: user_name.to_string(),
+                    priority,
+                    enqueued_at: now,
+                    last_seen: now,
+                });
+                now
+            }
+        };
+        self.count_ahead(&waiters, user_id, priority, enqueued_at, now)
+    }
+
+    /// 有效优先级高于申请人的等待者人数，锁空闲时这些等待者优先获取
+    pub fn higher_priority_waiters(
+        &self,
+        lock_key: &str,
+        user_id: &str,
+        priority: i32,
+        now: DateTime<Utc>,
+    ) -> usize {
+        let Some(waiters) = self.waiters.get(lock_key) else {
+            return 0;
+        };
+        let enqueued_at = waiters
+            .iter()
+            .find(|waiter| waiter.user_id == user_id)
+            .map_or(now, |waiter| waiter.enqueued_at);
+        let rank = self.effective_priority(priority, now - enqueued_at);
+        waiters
+            .iter()
+            .filter(|waiter| waiter.user_id != user_id && now - waiter.last_seen < self.ttl)
+            .filter(|waiter| self.effective_priority(waiter.priority, now - waiter.enqueued_at) > rank)
+            .count()
+    }
+
+    /// 锁的等待者，按获取顺序（有效优先级从高到低、同优先级按排队先后）排列
+    pub fn list(&self, lock_key: &str, now: DateTime<Utc>) -> Vec<QueuedWaiter> {
+        let Some(waiters) = self.waiters.get(lock_key) else {
+            return Vec::new();
+        };
+        let mut queued: Vec<QueuedWaiter> = waiters
+            .iter()
+            .filter(|waiter| now - waiter.last_seen < self.ttl)
+            .map(|waiter| QueuedWaiter {
+                position: 0,
+                user_id: waiter.user_id.clone(),
+                user_name: waiter.user_name.clone(),
+                priority: waiter.priority,
+                effective_priority: self.effective_priority(waiter.priority, now - waiter.enqueued_at),
+                enqueued_at: waiter.enqueued_at,
+                last_seen: waiter.last_seen,
+            })
+            .collect();
+        queued.sort_by(|a, b| {
+            b.effective_priority
+                .cmp(&a.effective_priority)
+                .then(a.enqueued_at.cmp(&b.enqueued_at))
+        });
+        for (index, waiter) in queued.iter_mut().enumerate() {
+            waiter.position = index + 1;
+        }
+        queued
+    }
+
+    /// 等待者放弃排队，返回其是否在队列中
+    pub fn cancel(&self, lock_key: &str, user_id: &str, now: DateTime<Utc>) -> bool {
+        let mut cancelled = false;
+        self.waiters.remove_if_mut(lock_key, |_, waiters| {
+            waiters.retain(|waiter| {
+                let found = waiter.user_id == user_id && now - waiter.last_seen < self.ttl;
+                cancelled |= found;
+                waiter.user_id != user_id
+            });
+            waiters.is_empty()
+        });
+        cancelled
+    }
+
+    /// 用户获得锁后移出队列
+    pub fn remove(&self, lock_key: &str, user_id: &str) {
+        self.waiters
+            .remove_if_mut(lock_key, |_, waiters| {
+                waiters.retain(|waiter| waiter.user_id != user_id);
+                waiters.is_empty()
+            });
+    }
+
+    /// 用户正在等待的锁
+    pub fn waiting_for(&self, user_id: &str, now: DateTime<Utc>) -> Vec<String> {
+        self.waiters
+            .iter()
+            .filter(|entry| {
+                entry
+                    .value()
+                    .iter()
+                    .any(|waiter| waiter.user_id == user_id && now - waiter.last_seen < self.ttl)
+            })
+            .map(|entry| entry.key().clone())
+            .collect()
+    }
+
+    /// 检查 requester 等待 holder 持有的锁是否会形成死锁
+    ///
+    /// 以用户为节点构建等待图（等待者 -> 所等待锁的持有人），从 holder 出发沿等待关系查找能否回到 requester，
+    /// 找到时返回环上依次等待的锁（不含 requester 正在申请的锁），截至 now 已过期的锁不构成等待关系。
+    pub async fn find_deadlock(
+        &self,
+        storage: &dyn LockStorage,
+        requester: &str,
+        holder: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Option<Vec<String>>> {
+        let mut visited: HashSet<String> = HashSet::from([holder.to_string()]);
+        let mut stack: Vec<(String, Vec<String>)> = vec![(holder.to_string(), Vec::new())];
+
+        while let Some((user_id, path)) = stack.pop() {
+            for lock_key in self.waiting_for(&user_id, now) {
+                let Some(lock_info) = storage.get_lock(&lock_key).await? else {
+                    continue;
+                };
+                if lock_info.is_expired_at(now) {
+                    continue;
+                }
+                let mut next_path = path.clone();
+                next_path.push(lock_key);
+                if lock_info.user_id == requester {
+                    return Ok(Some(next_path));
+                }
+                if visited.len() >= MAX_DEADLOCK_SEARCH {
+                    log::warn!(
+                        "[DEADLOCK] Search limit of {} holders reached, skipping detection",
+                        MAX_DEADLOCK_SEARCH
+                    );
+                    return Ok(None);
+                }
+                if visited.insert(lock_info.user_id.clone()) {
+                    stack.push((lock_info.user_id, next_path));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// 清理超时未重试的等待者
+    pub fn prune(&self, now: DateTime<Utc>) {
+        self.waiters.retain(|_, waiters| {
+            waiters.retain(|waiter| now - waiter.last_seen < self.ttl);
+            !waiters.is_empty()
+        });
+    }
+}
@@ -0,0 +1,99 @@
+//! 启动恢复窗口
+//!
+//! 服务停机期间死掉的持有人不会再心跳，但从快照或存储中恢复的锁仍会占用资源直到完整的超时时间。
+//! 配置 `LOCK_RECOVERY_WINDOW` 后，启动时已存在、启动后尚未心跳的锁视为未确认（状态查询中 `unconfirmed` 为 true），
+//! 持有人须在窗口结束前心跳一次，窗口结束时仍未确认的锁按过期释放并发布 `expired` 事件。
+//! 是否确认以锁的最近心跳时间是否晚于启动时间判断，经其他实例的心跳同样有效。窗口应大于客户端的心跳间隔。
+
+use crate::events::{EventBus, LockEvent, LockEventType};
+use crate::models::LockInfo;
+use crate::presence;
+use crate::storage::LockStorage;
+use chrono::{DateTime, Duration, Utc};
+use log::{error, info};
+use std::sync::OnceLock;
+
+pub struct RecoveryWindow {
+    window_secs: u64,
+    started_at: OnceLock<DateTime<Utc>>, // 启动后台任务时记录
+}
+
+impl RecoveryWindow {
+    pub fn new(window_secs: u64) -> Self {
+        Self {
+            window_secs,
+            started_at: OnceLock::new(),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.window_secs > 0
+    }
+
+    /// 记录启动时间，返回窗口结束时间，未启用或已启动时返回 None
+    pub fn start(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        if !self.enabled() || self.started_at.set(now).is_err() {
+            return None;
+        }
+        Some(now + Duration::seconds(self.window_secs as i64))
+    }
+
+    /// 锁未确认时返回须心跳确认的截止时间
+    pub fn confirm_before(&self, lock_info: &LockInfo, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let started_at = *self.started_at.get()?;
+        let deadline = started_at + Duration::seconds(self.window_secs as i64);
+        (now < deadline && lock_info.last_heartbeat < started_at).then_some(deadline)
+    }
+
+    /// 启动时已存在的锁的数量，仅用于日志
+    pub async fn pending(&self, storage: &dyn LockStorage, now: DateTime<Utc>) -> usize {
+        let Some(started_at) = self.started_at.get() else {
+            return 0;
+        };
+        match storage.list_locks().await {
+            Ok(locks) => locks
+                .iter()
+                .filter(|lock_info| !lock_info.is_expired_at(now) && lock_info.last_heartbeat < *started_at)
+                .count(),
+            Err(e) => {
+                error!("[RECOVERY] Failed to list restored locks: {}", e);
+                0
+            }
+        }
+    }
+
+    /// 窗口结束时释放仍未确认的锁，按版本号释放，与并发的心跳竞争时以心跳为准
+    pub async fn expire_unconfirmed(&self, storage: &dyn LockStorage, events: &EventBus, now: DateTime<Utc>) {
+        let Some(started_at) = self.started_at.get() else {
+            return;
+        };
+        let locks = match storage.list_locks().await {
+            Ok(locks) => locks,
+            Err(e) => {
+                error!("[RECOVERY] Failed to list unconfirmed locks: {}", e);
+                return;
+            }
+        };
+        let mut expired = 0;
+        for lock_info in locks
+            .into_iter()
+            .filter(|lock_info| !lock_info.is_expired_at(now) && lock_info.last_heartbeat < *started_at)
+        {
+            match storage.release_version(&lock_info.lock_id, lock_info.version).await {
+                Ok(Some(lock_info)) => {
+                    expired += 1;
+                    info!(
+                        "[RECOVERY] Unconfirmed lock expired - lock_id: {}, namespace: {}, business_id: {}, user_id: {}",
+                        lock_info.lock_id, lock_info.namespace, lock_info.business_id, lock_info.user_id
+                    );
+                    if !presence::is_presence_namespace(&lock_info.namespace) {
+                        events.publish(LockEvent::new(LockEventType::Expired, lock_info));
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => error!("[RECOVERY] Failed to expire lock {}: {}", lock_info.lock_id, e),
+            }
+        }
+        info!("[RECOVERY] Recovery window closed, {} unconfirmed locks expired", expired);
+    }
+}
@@ -0,0 +1,244 @@
+//! 锁预约
+//!
+//! 预约在 `acquire_at` 到达后由后台任务自动申请，锁空闲时授予预约人并发布 `reservation_granted` 事件；
+//! 锁仍被占用时每秒重试，直到时间窗结束仍未授予则放弃并发布 `reservation_missed` 事件。
+//! 预约以记录的形式保存在锁存储中（每个 lock_key 一条记录，内容为该锁上按开始时间排列的预约），
+//! 多个实例共享同一份预约，实例重启后仍然保留；授予由 leader 实例负责，申请前先从记录中移出预约，
+//! 选举切换期间两个实例同时处理时只有一个实例申请，申请期间取消预约返回预约不存在。
+
+use crate::events::{EventBus, LockEvent, LockEventType};
+use crate::hierarchy;
+use crate::models::{LockInfo, Reservation};
+use crate::storage::LockStorage;
+use crate::tenant::Tenant;
+use crate::uniqueness;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+
+/// 预约在存储中的记录类别
+pub const RECORD_KIND: &str = "reservation";
+
+#[derive(Default)]
+pub struct ReservationScheduler;
+
+impl ReservationScheduler {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// 登记预约，同一锁上时间窗重叠的预约已存在时返回该预约
+    pub async fn reserve(
+        &self,
+        storage: &dyn LockStorage,
+        reservation: Reservation,
+    ) -> Result<Result<(), Box<Reservation>>> {
+        let lock_key = reservation.get_lock_key();
+        update(storage, &lock_key, |reservations| {
+            if let Some(existing) = reservations.iter().find(|existing| existing.overlaps(&reservation)) {
+                return Err(Box::new(existing.clone()));
+            }
+            reservations.push(reservation.clone());
+            reservations.sort_by(|a, b| a.acquire_at.cmp(&b.acquire_at).then_with(|| a.lock_id.cmp(&b.lock_id)));
+            Ok(())
+        })
+        .await
+    }
+
+    /// 取消 user_id 自己的预约，tenant 为请求所属的租户
+    pub async fn cancel(
+        &self,
+        storage: &dyn LockStorage,
+        lock_id: &str,
+        user_id: &str,
+        tenant: Option<&Tenant>,
+    ) -> Result<Option<Reservation>> {
+        let Some(lock_key) = self
+            .list(storage, None)
+            .await?
+            .into_iter()
+            .find(|reservation| reservation.lock_id == lock_id)
+            .map(|reservation| reservation.get_lock_key())
+        else {
+            return Ok(None);
+        };
+        update(storage, &lock_key, |reservations| {
+            let index = reservations.iter().position(|reservation| {
+                reservation.lock_id == lock_id
+                    && reservation.user_id == user_id
+                    && tenant.is_none_or(|tenant| tenant.owns(&reservation.namespace))
+            })?;
+            Some(reservations.remove(index))
+        })
+        .await
+    }
+
+    /// 按开始时间排列的预约
+    pub async fn list(&self, storage: &dyn LockStorage, namespace: Option<&str>) -> Result<Vec<Reservation>> {
+        let mut reservations = Vec::new();
+        for (lock_key, value) in storage.list_records(RECORD_KIND).await? {
+            match serde_json::from_str::<Vec<Reservation>>(&value) {
+                Ok(records) => reservations.extend(
+                    records
+                        .into_iter()
+                        .filter(|reservation| namespace.is_none_or(|namespace| reservation.namespace == namespace)),
+                ),
+                Err(e) => log::warn!("[RESERVATION] Skipping malformed reservations of {}: {}", lock_key, e),
+            }
+        }
+        reservations.sort_by(|a, b| {
+            a.acquire_at
+                .cmp(&b.acquire_at)
+                .then_with(|| a.lock_id.cmp(&b.lock_id))
+        });
+        Ok(reservations)
+    }
+
+    /// 申请已到开始时间的预约，unique_business_ids 为 `LOCK_UNIQUE_BUSINESS_IDS` 的模式
+    pub async fn grant_due(
+        &self,
+        storage: &dyn LockStorage,
+        events: &EventBus,
+        unique_business_ids: &[String],
+        now: DateTime<Utc>,
+    ) -> Result<()> {
+        let due: Vec<Reservation> = self
+            .list(storage, None)
+            .await?
+            .into_iter()
+            .filter(|reservation| reservation.acquire_at <= now)
+            .collect();
+
+        for reservation in due {
+            if now >= reservation.ends_at {
+                if remove(storage, &reservation).await? {
+                    log::info!(
+                        "[RESERVATION] Reservation missed, lock was held for the whole window - lock_id: {}, lock_key: {}",
+                        reservation.lock_id,
+                        reservation.get_lock_key()
+                    );
+                    events.publish(LockEvent::new(
+                        LockEventType::ReservationMissed,
+                        reservation.to_lock_info(reservation.acquire_at),
+                    ));
+                }
+                continue;
+            }
+
+            let lock_info = reservation.to_lock_info(now);
+            // 层级命名空间中祖先或后代路径被他人锁定，或 business_id 在其他命名空间中被锁定时，等下一轮再授予
+            match path_conflict(storage, &lock_info, unique_business_ids, now).await {
+                Ok(false) => {}
+                Ok(true) => continue,
+                Err(e) => {
+                    log::error!(
+                        "[RESERVATION] Failed to check conflicts for {}: {}",
+                        reservation.lock_id,
+                        e
+                    );
+                    continue;
+                }
+            }
+            // 先移出预约再申请，预约已被取消或由其他实例处理时跳过；未能授予时放回，下一轮重试
+            if !remove(storage, &reservation).await? {
+                continue;
+            }
+            match storage.try_acquire(lock_info.clone()).await {
+                Ok(acquired) if acquired.acquired => {
+                    if let Some(replaced) = acquired.replaced {
+                        events.publish(LockEvent::new(LockEventType::Expired, *replaced));
+                    }
+                    // 预约人已持有该锁时沿用已有的锁
+                    let granted = match storage.get_lock(&reservation.get_lock_key()).await {
+                        Ok(Some(existing)) => existing,
+                        _ => lock_info,
+                    };
+                    log::info!(
+                        "[RESERVATION] Reservation granted - lock_id: {}, lock_key: {}, user_id: {}",
+                        granted.lock_id,
+                        reservation.get_lock_key(),
+                        reservation.user_id
+                    );
+                    events.publish(LockEvent::new(LockEventType::ReservationGranted, granted));
+                }
+                Ok(_) => put_back(storage, reservation).await?,
+                Err(e) => {
+                    log::error!(
+                        "[RESERVATION] Failed to acquire reserved lock {}: {}",
+                        reservation.lock_id,
+                        e
+                    );
+                    put_back(storage, reservation).await?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 读取 lock_key 上的预约，由 apply 修改后写回，期间记录被其他实例修改时重新读取，apply 可能执行多次
+async fn update<T>(
+    storage: &dyn LockStorage,
+    lock_key: &str,
+    mut apply: impl FnMut(&mut Vec<Reservation>) -> T,
+) -> Result<T> {
+    loop {
+        let current = storage.get_record(RECORD_KIND, lock_key).await?;
+        let mut reservations: Vec<Reservation> = match &current {
+            Some(value) => serde_json::from_str(value)?,
+            None => Vec::new(),
+        };
+        let result = apply(&mut reservations);
+        let value = match reservations.is_empty() {
+            true => None,
+            false => Some(serde_json::to_string(&reservations)?),
+        };
+        if value == current {
+            return Ok(result);
+        }
+        if storage
+            .swap_record(RECORD_KIND, lock_key, current.as_deref(), value.as_deref())
+            .await?
+        {
+            return Ok(result);
+        }
+    }
+}
+
+/// 将未能授予的预约放回
+async fn put_back(storage: &dyn LockStorage, reservation: Reservation) -> Result<()> {
+    update(storage, &reservation.get_lock_key(), |reservations| {
+        reservations.push(reservation.clone());
+        reservations.sort_by(|a, b| a.acquire_at.cmp(&b.acquire_at).then_with(|| a.lock_id.cmp(&b.lock_id)));
+    })
+    .await
+}
+
+/// 移出预约，返回预约是否仍在（未被取消或由其他实例处理）
+async fn remove(storage: &dyn LockStorage, reservation: &Reservation) -> Result<bool> {
+    update(storage, &reservation.get_lock_key(), |reservations| {
+        let before = reservations.len();
+        reservations.retain(|existing| existing.lock_id != reservation.lock_id);
+        reservations.len() != before
+    })
+    .await
+}
+
+/// 锁所在命名空间为层级命名空间且路径与他人持有的锁冲突，或 business_id 须唯一且已在其他命名空间中被锁定
+async fn path_conflict(
+    storage: &dyn LockStorage,
+    lock_info: &LockInfo,
+    unique_business_ids: &[String],
+    now: DateTime<Utc>,
+) -> Result<bool> {
+    if uniqueness::find_conflict(storage, unique_business_ids, lock_info, now).await?.is_some() {
+        return Ok(true);
+    }
+    let hierarchical = storage
+        .get_namespace(&lock_info.namespace)
+        .await?
+        .is_some_and(|policy| policy.hierarchical);
+    if !hierarchical {
+        return Ok(false);
+    }
+    Ok(hierarchy::find_conflict(storage, lock_info, now).await?.is_some())
+}
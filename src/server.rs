@@ -0,0 +1,165 @@
+//! 独立运行的 HTTP 服务
+//!
+//! 按配置监听公共地址和管理端口，`main` 使用内置存储启动；嵌入使用时可传入注册了自定义存储的 [`StorageRegistry`]，
+//! 以同样的方式运行服务而无需修改 `main`；需要在启动前定制 [`LockService`]（如注册钩子）时使用 [`serve`]。
+
+use crate::storage::registry::StorageRegistry;
+use crate::{Config, LockService};
+use actix_web::http::KeepAlive;
+use actix_web::middleware::Logger;
+use actix_web::{App, HttpServer};
+use log::info;
+use std::io;
+use std::net::TcpListener;
+#[cfg(unix)]
+use std::os::unix::net::UnixListener;
+use std::time::Duration;
+
+/// 创建存储、启动定时任务并运行 HTTP 服务，直到服务停止
+pub async fn run(config: Config, registry: &StorageRegistry) -> io::Result<()> {
+    info!("Starting fe-lock-service with config: {:?}", config);
+    check_listen(&config)?;
+
+    // 创建存储
+    let service = LockService::from_config_with(config, registry)
+        .await
+        .map_err(|e| io::Error::other(format!("{:#}", e)))?;
+    serve(service).await
+}
+
+/// 使用已创建的锁服务启动定时任务并运行 HTTP 服务，直到服务停止（停止后释放后台任务的选举锁），监听地址等取自服务的配置；
+/// 配置了 `HANDOFF_FROM` 时先从旧实例接收锁状态
+pub async fn serve(service: LockService) -> io::Result<()> {
+    let config = service.config().clone();
+    check_listen(&config)?;
+    // 滚动更新时先从旧实例接收锁状态，接收失败时不启动
+    service
+        .receive_handoff()
+        .await
+        .map_err(|e| io::Error::other(format!("{:#}", e)))?;
+    service.spawn_background_tasks();
+
+    // 启动 HTTP 服务，配置了管理端口时管理接口和指标单独监听
+    let separate_management = config.separate_management();
+    let public_service = service.clone();
+    let mut server = HttpServer::new(move || {
+        App::new().wrap(Logger::default()).configure(|cfg| {
+            if separate_management {
+                public_service.configure_public(cfg)
+            } else {
+                public_service.configure(cfg)
+            }
+        })
+    })
+    .keep_alive(match config.server_keep_alive {
+        0 => KeepAlive::Disabled,
+        secs => KeepAlive::Timeout(Duration::from_secs(secs)),
+    })
+    .max_connections(config.server_max_connections)
+    .client_request_timeout(Duration::from_millis(config.server_client_request_timeout_ms));
+    for listener in listen(
+        &config.server_listen,
+        config.server_uds_path.as_deref(),
+        config.server_uds_mode,
+    )? {
+        server = match listener {
+            Listener::Tcp(addr, listener) => {
+                info!("Server listening on http://{}", addr);
+                server.listen(listener)?
+            }
+            #[cfg(unix)]
+            Listener::Unix(path, listener) => {
+                info!("Server listening on unix:{}", path);
+                server.listen_uds(listener)?
+            }
+        };
+    }
+    if let Some(addr) = config.server_listen.first() {
+        info!("Swagger UI available at http://{}/api/swagger-ui/", addr);
+    }
+
+    if !separate_management {
+        let result = server.run().await;
+        service.shutdown().await;
+        return result;
+    }
+
+    let management_service = service.clone();
+    let mut management = HttpServer::new(move || {
+        App::new()
+            .wrap(Logger::default())
+            .configure(|cfg| management_service.configure_management(cfg))
+    })
+    .workers(1);
+    for listener in listen(
+        &config.management_listen,
+        config.management_uds_path.as_deref(),
+        config.server_uds_mode,
+    )? {
+        management = match listener {
+            Listener::Tcp(addr, listener) => {
+                info!("Management API listening on http://{}", addr);
+                management.listen(listener)?
+            }
+            #[cfg(unix)]
+            Listener::Unix(path, listener) => {
+                info!("Management API listening on unix:{}", path);
+                management.listen_uds(listener)?
+            }
+        };
+    }
+
+    let result = futures_util::future::try_join(server.run(), management.run()).await;
+    service.shutdown().await;
+    result.map(|_| ())
+}
+
+/// 至少需要一个 TCP 地址或 Unix 域套接字
+fn check_listen(config: &Config) -> io::Result<()> {
+    if config.server_listen.is_empty() && config.server_uds_path.is_none() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "no listen address, set SERVER_LISTEN or SERVER_UDS_PATH",
+        ));
+    }
+    Ok(())
+}
+
+enum Listener {
+    Tcp(String, TcpListener),
+    #[cfg(unix)]
+    Unix(String, UnixListener),
+}
+
+/// 绑定 TCP 地址和 Unix 域套接字，uds_mode 为套接字文件权限（八进制）
+fn listen(addrs: &[String], uds_path: Option<&str>, uds_mode: Option<u32>) -> io::Result<Vec<Listener>> {
+    let mut listeners = Vec::new();
+    for addr in addrs {
+        let listener = TcpListener::bind(addr)
+            .map_err(|e| io::Error::new(e.kind(), format!("failed to bind {}: {}", addr, e)))?;
+        listeners.push(Listener::Tcp(addr.clone(), listener));
+    }
+    if let Some(path) = uds_path {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+            // 清理上次运行遗留的套接字文件
+            if std::fs::metadata(path).is_ok_and(|metadata| metadata.file_type().is_socket()) {
+                std::fs::remove_file(path)?;
+            }
+            let listener = UnixListener::bind(path)
+                .map_err(|e| io::Error::new(e.kind(), format!("failed to bind {}: {}", path, e)))?;
+            if let Some(mode) = uds_mode {
+                std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))?;
+            }
+            listeners.push(Listener::Unix(path.to_string(), listener));
+        }
+        #[cfg(not(unix))]
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("Unix domain socket {} is not supported on this platform", path),
+        ));
+    }
+    Ok(listeners)
+}
@@ -0,0 +1,1350 @@
+//! 锁服务
+//!
+//! `LockService` 持有存储、事件总线和各调度器，其他 Rust 服务可以在进程内嵌入锁服务：
+//! 通过 `storage()` 直接加锁，通过 `configure` 将 HTTP 接口挂载到自己的 actix `App`。
+//! 创建后需调用一次 `spawn_background_tasks` 启动过期清理、持久化、预约和抢占等定时任务。
+
+use crate::admin;
+use crate::backpressure::{self, Backpressure};
+use crate::chaos::{self, ChaosInjector};
+use crate::checkout::OverdueTracker;
+use crate::clock::{self, Clock};
+use crate::cluster::{self, Cluster};
+use crate::config::{ClusterDiscovery, Config, FailoverMode, ReplicationMode, StorageType};
+use crate::contention::ContentionTracker;
+use crate::usage::UsageTracker;
+use crate::events::delivery::DeliveryOptions;
+use crate::events::audit::RedisAuditSink;
+use crate::events::nats::NatsSink;
+use crate::events::routing::EventRouter;
+use crate::events::{EventBus, LockEvent, LockEventType};
+use crate::expiry::ExpiryWatcher;
+use crate::handlers;
+use crate::handoff::{self, Handoff};
+use crate::health::{self, SelfTest};
+use crate::history::LockHistory;
+use crate::heartbeats::HeartbeatAdvisor;
+use crate::holdcap::HoldCapMonitor;
+use crate::hooks::{LockHook, LockHooks};
+use crate::lockid::{self, LockIdGenerator, UuidV4Generator};
+use crate::inflight::InFlight;
+use crate::ipfilter::{self, IpFilters};
+use crate::latency::{self, RequestTimings};
+use crate::leader::LeaderElection;
+use crate::lockops::{LockOps, OpResult};
+use crate::models::AcquireLockSuccess;
+use crate::metrics;
+use crate::notices::PendingNotices;
+use crate::notify::ReleaseNotifier;
+use crate::oidc::OidcLogin;
+use crate::drain::DrainScheduler;
+use crate::preemption::PreemptionScheduler;
+use crate::presence;
+use crate::queue::WaitQueue;
+use crate::recovery::RecoveryWindow;
+use crate::reservation::ReservationScheduler;
+use crate::session;
+use crate::sessions::SessionRegistry;
+use crate::storage::cache::CachedStorage;
+use crate::storage::encryption::{SealError, SnapshotCipher};
+use crate::storage::failover::{self, FailoverStorage};
+use crate::storage::fieldcrypt::{EncryptedStorage, FieldCipher};
+use crate::storage::memory::MemoryStorage;
+use crate::storage::persist::PersistTarget;
+use crate::storage::raft::{self, RaftOptions, RaftStorage};
+use crate::storage::redis::{RedisOptions, RedisStorage};
+use crate::storage::registry::StorageRegistry;
+use crate::storage::replication::{self, Replica, ReplicatedStorage};
+use crate::storage::sharded::ShardedStorage;
+use crate::storage::timeout::TimeoutStorage;
+use crate::storage::timing::TimedStorage;
+use crate::timeout;
+use crate::storage::snapshot::SnapshotCorrupt;
+use crate::storage::LockStorage;
+use crate::stuck::StuckLockDetector;
+use crate::timeseries::StatsSampler;
+use crate::takeover::TakeoverBroker;
+use crate::tenant::{self, TenantRegistry};
+use crate::tombstone::TombstoneTracker;
+use crate::v2;
+use actix_web::middleware::{from_fn, Compress};
+use actix_web::web;
+use anyhow::{anyhow, bail, Context, Result};
+use log::info;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use utoipa::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
+
+#[derive(Clone)]
+pub struct LockService {
+    config: Config,
+    storage: Arc<dyn LockStorage>,
+    event_bus: Arc<EventBus>,
+    wait_queue: Arc<WaitQueue>,
+    expiry_watcher: Arc<ExpiryWatcher>,
+    reservation_scheduler: Arc<ReservationScheduler>,
+    preemption_scheduler: Arc<PreemptionScheduler>,
+    drain_scheduler: Arc<DrainScheduler>,
+    takeover_broker: Arc<TakeoverBroker>,
+    tenant_registry: Arc<TenantRegistry>, // 租户认证和配额，未配置租户时不区分租户
+    admin_login: Option<Arc<OidcLogin>>,  // 管理接口的 OIDC 登录，未配置 OIDC_ISSUER 时为 None
+    session_registry: Arc<SessionRegistry>,
+    backpressure: Arc<Backpressure>, // 公共接口的并发请求计数和上限
+    ip_filters: Arc<IpFilters>,      // 公共接口和管理接口的来源地址限制
+    request_timings: Arc<RequestTimings>, // 按路由的请求耗时分解
+    leader: Arc<LeaderElection>,     // 单实例后台任务的选举
+    contention: Arc<ContentionTracker>,
+    usage: Arc<UsageTracker>, // 按用户的用量统计
+    release_notifier: Arc<ReleaseNotifier>,
+    tombstones: Arc<TombstoneTracker>,
+    notices: Arc<PendingNotices>, // 心跳附带的待处理通知
+    recovery: Arc<RecoveryWindow>, // 启动恢复窗口，LOCK_RECOVERY_WINDOW=0 时不要求确认
+    stuck_detector: Arc<StuckLockDetector>,
+    overdue_tracker: Arc<OverdueTracker>, // 逾期未归还的借出
+    hold_cap_monitor: Arc<HoldCapMonitor>, // 命名空间最长持有时间的提醒和统计
+    heartbeat_advisor: Arc<HeartbeatAdvisor>, // 建议的心跳间隔和过于频繁的心跳统计
+    stats_sampler: Arc<StatsSampler>, // 锁统计时间序列的采样
+    hooks: Arc<LockHooks>,            // 嵌入使用时注册的锁操作钩子
+    self_test: Arc<SelfTest>,
+    id_generator: Arc<dyn LockIdGenerator>,
+    inflight: Arc<InFlight<OpResult<AcquireLockSuccess>>>, // 执行中的申请，合并并发的重复申请
+    clock: Arc<dyn Clock>, // 判断锁是否过期的时钟，默认为系统时钟
+    chaos: Option<Arc<ChaosInjector>>, // 故障注入，仅 CHAOS_ENABLED=true 时启用
+    history: Option<Arc<LockHistory>>, // 锁历史，HISTORY_STORE=off 时为 None
+    audit: Option<Arc<RedisAuditSink>>, // 审计 Stream，AUDIT_STREAM_ENABLED=false 时为 None
+    memory_storage: Option<Arc<MemoryStorage>>,
+    handoff: Option<Arc<Handoff>>, // 实例间的状态交接，仅内存存储
+    redis_storages: Vec<Arc<RedisStorage>>, // Redis 存储，分片模式下每个分片一个
+    failover_storage: Option<Arc<FailoverStorage>>,
+    sharded_storage: Option<Arc<ShardedStorage>>,
+    cached_storage: Option<Arc<CachedStorage>>,
+    raft_storage: Option<Arc<RaftStorage>>,
+    replicated_storage: Option<Arc<ReplicatedStorage>>, // 双写复制，未配置 REPLICA_STORAGE_TYPE 时为 None
+    encrypted_storage: Option<Arc<EncryptedStorage>>,   // 锁信息字段加密，未配置 STORAGE_FIELD_KEYS 时为 None
+    replica_memory: Option<Arc<MemoryStorage>>,         // memory 副本，定时持久化
+    cluster: Option<Arc<Cluster>>, // 集群成员，CLUSTER_DISCOVERY=off 时为 None
+}
+
+impl LockService {
+    /// 使用自定义的存储和事件总线创建锁服务
+    pub fn new(config: Config, storage: Arc<dyn LockStorage>, mut event_bus: EventBus) -> Self {
+        if config.chaos_enabled {
+            log::warn!("Fault injection enabled, do not use in production");
+        }
+        if config.admin_token.is_none() && config.management_token.is_none() && config.oidc_issuer.is_none() {
+            log::warn!("ADMIN_TOKEN and OIDC_ISSUER are not configured, /api/admin is disabled");
+        }
+        let contention = Arc::new(ContentionTracker::new(config.stats_contention_window));
+        event_bus.register(contention.clone());
+        let usage = Arc::new(UsageTracker::new());
+        event_bus.register(usage.clone());
+        let release_notifier = Arc::new(ReleaseNotifier::new());
+        event_bus.register(release_notifier.clone());
+        event_bus.register(Arc::new(EventRouter::new(storage.clone(), config.nats_url.clone())));
+        let hooks = Arc::new(LockHooks::new());
+        event_bus.register(hooks.clone());
+        let tombstones = Arc::new(TombstoneTracker::new(config.lock_tombstone_ttl));
+        if tombstones.enabled() {
+            event_bus.register(tombstones.clone());
+        }
+        let notices = Arc::new(PendingNotices::new());
+        event_bus.register(notices.clone());
+        let id_generator = lockid::from_config(&config).unwrap_or_else(|e| {
+            log::warn!("{}, falling back to UUIDv4 lock ids", e);
+            Arc::new(UuidV4Generator)
+        });
+        let backend = match config.storage_type {
+            StorageType::Memory => "memory",
+            StorageType::Redis => "redis",
+            StorageType::Raft => "raft",
+            StorageType::Custom(_) => "custom",
+        };
+        let self_test = Arc::new(SelfTest::new(&config, vec![(backend, storage.clone())]));
+        Self {
+            chaos: config.chaos_enabled.then(|| Arc::new(ChaosInjector::from_config(&config))),
+            cluster: Cluster::from_config(&config).map(Arc::new),
+            wait_queue: Arc::new(WaitQueue::new(config.lock_waiter_ttl, config.lock_queue_aging)),
+            takeover_broker: Arc::new(TakeoverBroker::new(config.lock_takeover_webhook_url.clone())),
+            tenant_registry: Arc::new(TenantRegistry::new(&config)),
+            backpressure: Arc::new(Backpressure::new(&config)),
+            ip_filters: Arc::new(IpFilters::from_config(&config)),
+            request_timings: Arc::new(RequestTimings::from_config(&config)),
+            leader: Arc::new(LeaderElection::new(&config, storage.clone())),
+            recovery: Arc::new(RecoveryWindow::new(config.lock_recovery_window)),
+            stuck_detector: Arc::new(StuckLockDetector::new(
+                config.lock_stuck_threshold,
+                config.lock_stuck_webhook_url.clone(),
+            )),
+            overdue_tracker: Arc::new(OverdueTracker::new(config.lock_checkout_webhook_url.clone())),
+            hold_cap_monitor: Arc::new(HoldCapMonitor::new()),
+            expiry_watcher: Arc::new(ExpiryWatcher::new(config.lock_warning_callback_hosts.clone())),
+            heartbeat_advisor: Arc::new(HeartbeatAdvisor::new(&config)),
+            stats_sampler: Arc::new(StatsSampler::new(
+                config.stats_sample_interval,
+                config.stats_sample_retention,
+            )),
+            admin_login: OidcLogin::from_config(&config).map(Arc::new),
+            config,
+            storage,
+            event_bus: Arc::new(event_bus),
+            reservation_scheduler: Arc::new(ReservationScheduler::new()),
+            preemption_scheduler: Arc::new(PreemptionScheduler::new()),
+            drain_scheduler: Arc::new(DrainScheduler::new()),
+            session_registry: Arc::new(SessionRegistry::new()),
+            contention,
+            usage,
+            release_notifier,
+            tombstones,
+            notices,
+            hooks,
+            self_test,
+            id_generator,
+            inflight: Arc::new(InFlight::new()),
+            clock: clock::system(),
+            history: None,
+            audit: None,
+            memory_storage: None,
+            handoff: None,
+            redis_storages: Vec::new(),
+            failover_storage: None,
+            sharded_storage: None,
+            cached_storage: None,
+            raft_storage: None,
+            replicated_storage: None,
+            encrypted_storage: None,
+            replica_memory: None,
+        }
+    }
+
+    /// 按配置创建存储和事件总线
+    pub async fn from_config(config: Config) -> Result<Self> {
+        Self::from_config_with(config, &StorageRegistry::new()).await
+    }
+
+    /// 按配置创建存储和事件总线，`STORAGE_TYPE` 不是内置存储时使用 registry 中注册的工厂创建存储
+    pub async fn from_config_with(config: Config, registry: &StorageRegistry) -> Result<Self> {
+        let cipher = FieldCipher::from_config(&config)?.map(Arc::new);
+        let service = Self::open(config, registry, cipher.clone()).await?;
+        let service = match replication::connect_replica(&service.config, registry).await? {
+            Some(replica) => service.with_replica(replica),
+            None => service,
+        };
+        match cipher {
+            Some(cipher) => Ok(service.with_field_encryption(cipher)),
+            None => Ok(service),
+        }
+    }
+
+    async fn open(config: Config, registry: &StorageRegistry, cipher: Option<Arc<FieldCipher>>) -> Result<Self> {
+        // 提前校验 lock_id 生成方式，避免雪花 ID 节点号无效时静默回退
+        lockid::from_config(&config)?;
+        info!("Lock id scheme: {:?}", config.lock_id_scheme);
+
+        let mut event_bus = EventBus::with_delivery(DeliveryOptions::from_config(&config));
+        if let Some(nats_url) = &config.nats_url {
+            info!("Publishing lock events to NATS: {}", nats_url);
+            let sink = NatsSink::new(nats_url, config.nats_subject_prefix.clone())
+                .await
+                .context("Failed to connect to NATS")?;
+            event_bus.register(Arc::new(sink));
+        }
+        if config.audit_stream_enabled && !matches!(config.storage_type, StorageType::Redis) {
+            bail!("AUDIT_STREAM_ENABLED requires STORAGE_TYPE=redis");
+        }
+        if config.handoff_from.is_some() && !matches!(config.storage_type, StorageType::Memory) {
+            bail!("HANDOFF_FROM requires STORAGE_TYPE=memory");
+        }
+        if config.cluster_discovery != ClusterDiscovery::Off {
+            if config.cluster_peer_token.is_none() {
+                bail!("CLUSTER_PEER_TOKEN is required when CLUSTER_DISCOVERY is enabled");
+            }
+            if config.separate_management() && config.management_listen.is_empty() {
+                bail!("CLUSTER_DISCOVERY serves /cluster/gossip on the management port, MANAGEMENT_LISTEN is required");
+            }
+        }
+
+        match config.storage_type {
+            StorageType::Memory => {
+                info!("Using memory storage");
+                if config.memory_max_locks > 0 {
+                    info!(
+                        "Memory storage limited to {} locks, eviction policy: {:?}",
+                        config.memory_max_locks, config.memory_eviction
+                    );
+                }
+                let memory_storage = if config.memory_persist_enabled {
+                    info!("Memory persistence enabled: {}", config.memory_persist_path);
+                    info!("Persistence interval: {} seconds", config.memory_persist_interval);
+                    info!("Persistence format: {:?}", config.memory_persist_format);
+                    if config.memory_persist_sharded {
+                        info!("Persisting one snapshot per namespace");
+                        if config.memory_persist_incremental {
+                            log::warn!("MEMORY_PERSIST_INCREMENTAL is ignored with sharded persistence");
+                        }
+                    } else if config.memory_persist_incremental {
+                        info!(
+                            "Persisting incremental snapshots, full snapshot every {} seconds",
+                            config.memory_persist_compact_interval
+                        );
+                    }
+                    if config.memory_persist_change_threshold > 0 {
+                        info!(
+                            "Persisting immediately after {} changes",
+                            config.memory_persist_change_threshold
+                        );
+                    }
+                    MemoryStorage::with_persistence(
+                        PersistTarget::parse(&config.memory_persist_path, &config)?,
+                        config.memory_persist_format.clone(),
+                        config.memory_persist_change_threshold,
+                    )
+                } else {
+                    info!("Memory persistence disabled");
+                    MemoryStorage::new()
+                };
+                let memory_storage = Arc::new(
+                    memory_storage
+                        .with_max_locks(config.memory_max_locks, config.memory_eviction.clone())
+                        .with_encryption(SnapshotCipher::from_config(&config)?)
+                        .with_sharding(config.memory_persist_sharded)
+                        .with_incremental(config.memory_persist_incremental, config.memory_persist_compact_interval),
+                );
+
+                // 尝试从磁盘加载数据
+                if config.memory_persist_enabled {
+                    match memory_storage.load_from_disk().await {
+                        Ok(count) => {
+                            if count > 0 {
+                                info!("Successfully restored {} locks from disk", count);
+                            }
+                        }
+                        // 无法解密或所有快照都损坏时拒绝启动，避免空状态覆盖原文件
+                        Err(e) if e.is::<SealError>() || e.is::<SnapshotCorrupt>() => {
+                            return Err(e.context("Failed to load persistence file"));
+                        }
+                        Err(e) => {
+                            log::warn!("Failed to load from disk: {}", e);
+                        }
+                    }
+                }
+
+                let history = open_history(&config, &mut event_bus, None, cipher.clone()).await?;
+                Ok(Self {
+                    history,
+                    memory_storage: Some(memory_storage.clone()),
+                    handoff: Some(Arc::new(Handoff::new(&config))),
+                    ..Self::new(config, memory_storage, event_bus)
+                })
+            }
+            StorageType::Redis if !config.redis_shards.is_empty() => {
+                info!(
+                    "Using sharded Redis storage - {} shards, {} virtual nodes each, key prefix: {}",
+                    config.redis_shards.len(),
+                    config.redis_shard_vnodes,
+                    config.redis_key_prefix
+                );
+                let sharded_storage = Arc::new(ShardedStorage::connect(&config).await?);
+                let redis_storages: Vec<_> = sharded_storage.shards().iter().map(|shard| shard.redis.clone()).collect();
+                let cached_storage = (config.storage_cache_ttl_ms > 0).then(|| {
+                    info!("Caching lock status reads for {}ms", config.storage_cache_ttl_ms);
+                    Arc::new(CachedStorage::new(
+                        sharded_storage.clone(),
+                        Duration::from_millis(config.storage_cache_ttl_ms),
+                    ))
+                });
+                let storage: Arc<dyn LockStorage> = match &cached_storage {
+                    Some(cache) => cache.clone(),
+                    None => sharded_storage.clone(),
+                };
+
+                // 锁历史和审计 Stream 写入第一个分片
+                let history = open_history(&config, &mut event_bus, Some(redis_storages[0].clone()), cipher.clone()).await?;
+                let audit = open_audit(&config, &mut event_bus, &redis_storages[0], cipher.clone());
+                let self_test = Arc::new(SelfTest::new(&config, vec![("redis", sharded_storage.clone())]));
+                Ok(Self {
+                    history,
+                    audit,
+                    self_test,
+                    redis_storages,
+                    sharded_storage: Some(sharded_storage),
+                    cached_storage,
+                    ..Self::new(config, storage, event_bus)
+                })
+            }
+            StorageType::Redis => {
+                info!("Using Redis storage - key prefix: {}", config.redis_key_prefix);
+                let redis_url = config
+                    .redis_url
+                    .as_ref()
+                    .ok_or_else(|| anyhow!("Redis URL not configured"))?;
+                let redis_storage = Arc::new(
+                    RedisStorage::new(RedisOptions::from_config(&config, redis_url))
+                        .await
+                        .context("Failed to connect to Redis")?,
+                );
+                info!(
+                    "Redis failover: {:?}, failure threshold: {}, retry interval: {}s, retries: {}",
+                    config.storage_failover,
+                    config.storage_failure_threshold,
+                    config.storage_retry_interval,
+                    config.storage_retries
+                );
+                let failover_storage = Arc::new(FailoverStorage::new(
+                    TimeoutStorage::wrap(redis_storage.clone(), config.storage_op_timeout_ms),
+                    &config,
+                ));
+                let cached_storage = (config.storage_cache_ttl_ms > 0).then(|| {
+                    info!("Caching lock status reads for {}ms", config.storage_cache_ttl_ms);
+                    Arc::new(CachedStorage::new(
+                        failover_storage.clone(),
+                        Duration::from_millis(config.storage_cache_ttl_ms),
+                    ))
+                });
+                let storage: Arc<dyn LockStorage> = match &cached_storage {
+                    Some(cache) => cache.clone(),
+                    None => failover_storage.clone(),
+                };
+
+                let history = open_history(&config, &mut event_bus, Some(redis_storage.clone()), cipher.clone()).await?;
+                let audit = open_audit(&config, &mut event_bus, &redis_storage, cipher.clone());
+                // 自检直接访问 Redis，不经过断路器和降级存储
+                let self_test = Arc::new(SelfTest::new(&config, vec![("redis", redis_storage.clone())]));
+                Ok(Self {
+                    history,
+                    audit,
+                    self_test,
+                    redis_storages: vec![redis_storage],
+                    failover_storage: Some(failover_storage),
+                    cached_storage,
+                    ..Self::new(config, storage, event_bus)
+                })
+            }
+            StorageType::Raft => {
+                info!(
+                    "Using Raft storage - node_id: {}, members: {:?}",
+                    config.raft_node_id, config.raft_members
+                );
+                if !config.raft_members.contains_key(&config.raft_node_id) {
+                    bail!("RAFT_MEMBERS must contain RAFT_NODE_ID {}", config.raft_node_id);
+                }
+                let Some(peer_token) = config.raft_peer_token.clone() else {
+                    bail!("RAFT_PEER_TOKEN is required for STORAGE_TYPE=raft");
+                };
+                if config.separate_management() && config.management_listen.is_empty() {
+                    bail!("STORAGE_TYPE=raft serves /raft on the management port, MANAGEMENT_LISTEN is required");
+                }
+                let raft_storage = Arc::new(
+                    RaftStorage::new(RaftOptions {
+                        node_id: config.raft_node_id,
+                        members: config.raft_members.clone(),
+                        heartbeat_interval_ms: config.raft_heartbeat_interval_ms,
+                        election_timeout_ms: config.raft_election_timeout_ms,
+                        snapshot_logs: config.raft_snapshot_logs,
+                        data_dir: PathBuf::from(&config.raft_data_dir),
+                        peer_token,
+                    })
+                    .await
+                    .context("Failed to start Raft node")?,
+                );
+
+                let storage = TimeoutStorage::wrap(raft_storage.clone(), config.storage_op_timeout_ms);
+                let history = open_history(&config, &mut event_bus, None, cipher.clone()).await?;
+                Ok(Self {
+                    history,
+                    raft_storage: Some(raft_storage),
+                    ..Self::new(config, storage, event_bus)
+                })
+            }
+            StorageType::Custom(ref name) => {
+                let factory = registry.get(name).ok_or_else(|| {
+                    anyhow!(
+                        "Unknown STORAGE_TYPE '{}', available storage types: {}",
+                        name,
+                        registry.names().join(", ")
+                    )
+                })?;
+                info!("Using {} storage", name);
+                let storage = factory
+                    .create(&config)
+                    .await
+                    .with_context(|| format!("Failed to create {} storage", name))?;
+                let storage = TimeoutStorage::wrap(storage, config.storage_op_timeout_ms);
+                let history = open_history(&config, &mut event_bus, None, cipher.clone()).await?;
+                Ok(Self {
+                    history,
+                    ..Self::new(config, storage, event_bus)
+                })
+            }
+        }
+    }
+
+    /// 变更在写入主存储后复制到副本存储，读取仍只访问主存储
+    fn with_replica(mut self, replica: Replica) -> Self {
+        let replicated_storage = Arc::new(ReplicatedStorage::new(self.storage.clone(), replica.storage, &self.config));
+        self.storage = replicated_storage.clone();
+        self.replicated_storage = Some(replicated_storage);
+        self.replica_memory = replica.memory;
+        self
+    }
+
+    /// 锁信息的敏感字段在写入存储（包括副本）前加密，读取时解密
+    fn with_field_encryption(mut self, cipher: Arc<FieldCipher>) -> Self {
+        info!("Encrypting lock user names and metadata with key {}", cipher.primary_key_id());
+        let encrypted_storage = Arc::new(EncryptedStorage::new(self.storage.clone(), cipher));
+        self.storage = encrypted_storage.clone();
+        self.encrypted_storage = Some(encrypted_storage);
+        self
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn storage(&self) -> &Arc<dyn LockStorage> {
+        &self.storage
+    }
+
+    pub fn events(&self) -> &Arc<EventBus> {
+        &self.event_bus
+    }
+
+    /// 使用自定义的 lock_id 生成器，替换按 `LOCK_ID_SCHEME` 创建的生成器
+    pub fn with_id_generator(mut self, id_generator: Arc<dyn LockIdGenerator>) -> Self {
+        self.id_generator = id_generator;
+        self
+    }
+
+    /// 注册锁操作钩子，见 [`crate::hooks`]
+    pub fn with_hook(self, hook: Arc<dyn LockHook>) -> Self {
+        self.hooks.register(hook);
+        self
+    }
+
+    /// 使用指定的时钟判断锁是否过期，自定义的存储需通过其 `with_clock` 使用同一个时钟
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// 配置了 `HANDOFF_FROM` 时从旧实例接收锁状态并隔离旧实例，需在开始处理请求之前调用，见 [`crate::handoff`]
+    pub async fn receive_handoff(&self) -> Result<()> {
+        let (Some(handoff), Some(from)) = (&self.handoff, &self.config.handoff_from) else {
+            return Ok(());
+        };
+        info!("Receiving lock state from {}", from);
+        handoff.receive(&self.config, from, self.storage.as_ref(), self.clock.as_ref()).await
+    }
+
+    /// 服务停止时调用，释放后台任务的选举锁以便其他实例立即接替
+    pub async fn shutdown(&self) {
+        self.leader.resign().await;
+    }
+
+    /// 启动定时任务，需在 tokio 运行时中调用
+    pub fn spawn_background_tasks(&self) {
+        // 多实例选举，清理、对账和检测等任务只在当选的实例上运行
+        if self.leader.enabled() {
+            tokio::spawn(self.leader.clone().run(self.clock.clone()));
+        }
+
+        // 等待队列，定时清理超时未重试的等待者
+        {
+            let wait_queue = self.wait_queue.clone();
+            let clock = self.clock.clone();
+            let prune_interval = self.config.lock_waiter_ttl.max(1);
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(Duration::from_secs(prune_interval));
+                loop {
+                    interval.tick().await;
+                    wait_queue.prune(clock.now());
+                }
+            });
+        }
+
+        // 过期提醒，每秒检查一次
+        {
+            let expiry_watcher = self.expiry_watcher.clone();
+            let storage = self.storage.clone();
+            let event_bus = self.event_bus.clone();
+            let clock = self.clock.clone();
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(Duration::from_secs(1));
+                loop {
+                    interval.tick().await;
+                    expiry_watcher.check(storage.as_ref(), &event_bus, clock.now()).await;
+                }
+            });
+        }
+
+        // 锁预约，每秒检查一次到期的预约，预约保存在存储中，由 leader 实例授予
+        {
+            let reservation_scheduler = self.reservation_scheduler.clone();
+            let storage = self.storage.clone();
+            let event_bus = self.event_bus.clone();
+            let unique_business_ids = self.config.lock_unique_business_ids.clone();
+            let clock = self.clock.clone();
+            let handoff = self.handoff.clone();
+            let leader = self.leader.clone();
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(Duration::from_secs(1));
+                loop {
+                    interval.tick().await;
+                    if !leader.is_leader() {
+                        continue;
+                    }
+                    let Some(_in_flight) = handoff::admit(handoff.as_ref()) else {
+                        continue;
+                    };
+                    if let Err(e) = reservation_scheduler
+                        .grant_due(storage.as_ref(), &event_bus, &unique_business_ids, clock.now())
+                        .await
+                    {
+                        log::error!("[RESERVATION] Failed to grant due reservations: {}", e);
+                    }
+                }
+            });
+        }
+
+        // 锁抢占，每秒检查一次可授予或宽限期已结束的抢占
+        {
+            let preemption_scheduler = self.preemption_scheduler.clone();
+            let storage = self.storage.clone();
+            let event_bus = self.event_bus.clone();
+            let clock = self.clock.clone();
+            let handoff = self.handoff.clone();
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(Duration::from_secs(1));
+                loop {
+                    interval.tick().await;
+                    let Some(_in_flight) = handoff::admit(handoff.as_ref()) else {
+                        continue;
+                    };
+                    preemption_scheduler.transfer_due(storage.as_ref(), &event_bus, clock.as_ref()).await;
+                }
+            });
+        }
+
+        // 锁排空，每秒检查一次已释放或截止时间已到的锁
+        {
+            let drain_scheduler = self.drain_scheduler.clone();
+            let storage = self.storage.clone();
+            let event_bus = self.event_bus.clone();
+            let clock = self.clock.clone();
+            let handoff = self.handoff.clone();
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(Duration::from_secs(1));
+                loop {
+                    interval.tick().await;
+                    let Some(_in_flight) = handoff::admit(handoff.as_ref()) else {
+                        continue;
+                    };
+                    drain_scheduler.release_due(storage.as_ref(), &event_bus, clock.as_ref()).await;
+                }
+            });
+        }
+
+        // 心跳间隔统计，每分钟移出已释放或已过期的锁
+        {
+            let heartbeat_advisor = self.heartbeat_advisor.clone();
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(Duration::from_secs(60));
+                loop {
+                    interval.tick().await;
+                    heartbeat_advisor.prune();
+                }
+            });
+        }
+
+        // 接管请求，每秒检查一次超时未答复或持有人已释放锁的请求
+        {
+            let takeover_broker = self.takeover_broker.clone();
+            let storage = self.storage.clone();
+            let event_bus = self.event_bus.clone();
+            let clock = self.clock.clone();
+            let handoff = self.handoff.clone();
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(Duration::from_secs(1));
+                loop {
+                    interval.tick().await;
+                    let Some(_in_flight) = handoff::admit(handoff.as_ref()) else {
+                        continue;
+                    };
+                    takeover_broker.expire_due(storage.as_ref(), &event_bus, clock.now()).await;
+                }
+            });
+        }
+
+        // 客户端会话，每秒检查一次超时未心跳的会话，会话保存在存储中，由 leader 实例结束
+        {
+            let session_registry = self.session_registry.clone();
+            let storage = self.storage.clone();
+            let event_bus = self.event_bus.clone();
+            let clock = self.clock.clone();
+            let handoff = self.handoff.clone();
+            let leader = self.leader.clone();
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(Duration::from_secs(1));
+                loop {
+                    interval.tick().await;
+                    if !leader.is_leader() {
+                        continue;
+                    }
+                    let Some(_in_flight) = handoff::admit(handoff.as_ref()) else {
+                        continue;
+                    };
+                    session_registry.expire_due(storage.as_ref(), &event_bus, clock.now()).await;
+                }
+            });
+        }
+
+        // 启动恢复窗口，结束时释放启动前已存在且仍未心跳确认的锁
+        if let Some(deadline) = self.recovery.start(self.clock.now()) {
+            let recovery = self.recovery.clone();
+            let storage = self.storage.clone();
+            let event_bus = self.event_bus.clone();
+            let clock = self.clock.clone();
+            let window = self.config.lock_recovery_window;
+            let handoff = self.handoff.clone();
+            tokio::spawn(async move {
+                let pending = recovery.pending(storage.as_ref(), clock.now()).await;
+                info!(
+                    "[RECOVERY] {} restored locks must heartbeat within {}s (before {})",
+                    pending, window, deadline
+                );
+                tokio::time::sleep(Duration::from_secs(window)).await;
+                // 已隔离的实例不再释放锁，由新实例的恢复窗口处理
+                let Some(_in_flight) = handoff::admit(handoff.as_ref()) else {
+                    return;
+                };
+                recovery.expire_unconfirmed(storage.as_ref(), &event_bus, clock.now()).await;
+            });
+        }
+
+        // 锁竞争分析，每分钟清理窗口外的统计
+        {
+            let contention = self.contention.clone();
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(Duration::from_secs(60));
+                loop {
+                    interval.tick().await;
+                    contention.prune();
+                }
+            });
+        }
+
+        // 锁统计时间序列采样
+        if self.stats_sampler.interval() > 0 {
+            let stats_sampler = self.stats_sampler.clone();
+            let storage = self.storage.clone();
+            let clock = self.clock.clone();
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(Duration::from_secs(stats_sampler.interval()));
+                loop {
+                    interval.tick().await;
+                    match storage.stats(0).await {
+                        Ok(stats) => stats_sampler.record(&stats, clock.now()),
+                        Err(e) => log::error!("[STATS] Failed to sample lock stats: {}", e),
+                    }
+                }
+            });
+        }
+
+        // 卡住的锁检测，未配置阈值的命名空间跳过，阈值可随命名空间策略修改，因此始终启动
+        {
+            let stuck_detector = self.stuck_detector.clone();
+            let storage = self.storage.clone();
+            let clock = self.clock.clone();
+            let leader = self.leader.clone();
+            let check_interval = self.config.lock_stuck_check_interval;
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(Duration::from_secs(check_interval));
+                loop {
+                    interval.tick().await;
+                    if !leader.is_leader() {
+                        stuck_detector.clear();
+                        continue;
+                    }
+                    stuck_detector.scan(storage.as_ref(), clock.now()).await;
+                }
+            });
+        }
+
+        // 借出逾期检测，预计归还时间随锁保存，因此始终启动
+        {
+            let overdue_tracker = self.overdue_tracker.clone();
+            let storage = self.storage.clone();
+            let event_bus = self.event_bus.clone();
+            let clock = self.clock.clone();
+            let leader = self.leader.clone();
+            let check_interval = self.config.lock_checkout_check_interval;
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(Duration::from_secs(check_interval));
+                loop {
+                    interval.tick().await;
+                    if !leader.is_leader() {
+                        overdue_tracker.clear();
+                        continue;
+                    }
+                    overdue_tracker.scan(storage.as_ref(), &event_bus, clock.now()).await;
+                }
+            });
+        }
+
+        // 命名空间最长持有时间的提醒和统计，上限可随命名空间策略修改，因此始终启动
+        {
+            let hold_cap_monitor = self.hold_cap_monitor.clone();
+            let storage = self.storage.clone();
+            let event_bus = self.event_bus.clone();
+            let clock = self.clock.clone();
+            let leader = self.leader.clone();
+            let check_interval = self.config.lock_hold_cap_check_interval;
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(Duration::from_secs(check_interval));
+                loop {
+                    interval.tick().await;
+                    if !leader.is_leader() {
+                        hold_cap_monitor.clear();
+                        continue;
+                    }
+                    hold_cap_monitor.scan(storage.as_ref(), &event_bus, clock.now()).await;
+                }
+            });
+        }
+
+        // 锁墓碑，每分钟清理超过保留时间的墓碑
+        if self.tombstones.enabled() {
+            let tombstones = self.tombstones.clone();
+            let clock = self.clock.clone();
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(Duration::from_secs(60));
+                loop {
+                    interval.tick().await;
+                    tombstones.prune(clock.now());
+                }
+            });
+        }
+
+        // 心跳通知，每分钟清理长时间未被取出的通知
+        let notices = self.notices.clone();
+        let clock = self.clock.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(60));
+            loop {
+                interval.tick().await;
+                notices.prune(clock.now());
+            }
+        });
+
+        // 存储故障转移，定时探测 Redis 是否恢复
+        if let Some(failover_storage) = self
+            .failover_storage
+            .clone()
+            .filter(|_| self.config.storage_failover != FailoverMode::Off)
+        {
+            let retry_interval = self.config.storage_retry_interval;
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(Duration::from_secs(retry_interval));
+                loop {
+                    interval.tick().await;
+                    failover_storage.probe().await;
+                }
+            });
+        }
+
+        // 集群成员发现与 gossip
+        if let Some(cluster) = &self.cluster {
+            cluster.spawn(self.failover_storage.clone(), self.sharded_storage.clone());
+        }
+
+        // 分片模式下各分片独立探测
+        if let Some(sharded_storage) = self
+            .sharded_storage
+            .clone()
+            .filter(|_| self.config.storage_failover != FailoverMode::Off)
+        {
+            let retry_interval = self.config.storage_retry_interval;
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(Duration::from_secs(retry_interval));
+                loop {
+                    interval.tick().await;
+                    sharded_storage.probe().await;
+                }
+            });
+        }
+
+        // 读缓存，按 Redis 键空间通知使其他实例写入的锁失效，订阅中断时清空缓存并重新订阅
+        for redis_storage in &self.redis_storages {
+            let Some(cache) = self.cached_storage.clone() else {
+                break;
+            };
+            let redis_storage = redis_storage.clone();
+            tokio::spawn(async move {
+                loop {
+                    cache.clear();
+                    if let Err(e) = redis_storage.watch_lock_keys(|lock_key| cache.invalidate(lock_key)).await {
+                        log::warn!("[CACHE] Keyspace notifications unavailable, cache relies on TTL: {}", e);
+                    }
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                }
+            });
+        }
+
+        // Redis 锁过期，按截止键的过期通知删除过期的锁并发布过期事件，订阅中断时重新订阅
+        for redis_storage in &self.redis_storages {
+            let redis_storage = redis_storage.clone();
+            let event_bus = self.event_bus.clone();
+            let encrypted_storage = self.encrypted_storage.clone();
+            tokio::spawn(async move {
+                loop {
+                    let published = redis_storage.watch_expired(|lock_info| {
+                        // 在场记录过期即离开，不是锁事件
+                        if presence::is_presence_namespace(&lock_info.namespace) {
+                            return;
+                        }
+                        let lock_info = match &encrypted_storage {
+                            Some(encrypted_storage) => match encrypted_storage.open_lock(lock_info) {
+                                Ok(lock_info) => lock_info,
+                                Err(e) => {
+                                    log::warn!("[EXPIRED] Failed to decrypt expired lock: {}", e);
+                                    return;
+                                }
+                            },
+                            None => lock_info,
+                        };
+                        event_bus.publish(LockEvent::new(LockEventType::Expired, lock_info));
+                    });
+                    if let Err(e) = published.await {
+                        log::warn!("[EXPIRED] Keyspace notifications unavailable, expired events are not published: {}", e);
+                    }
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                }
+            });
+        }
+
+        // Redis 键对账，迁移旧布局的 id 键，清理失效的 id 索引并补齐缺失的 id 索引和过期时间
+        for redis_storage in self
+            .redis_storages
+            .iter()
+            .filter(|_| self.config.redis_reconcile_interval > 0)
+        {
+            let redis_storage = redis_storage.clone();
+            let leader = self.leader.clone();
+            let reconcile_interval = self.config.redis_reconcile_interval;
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(Duration::from_secs(reconcile_interval));
+                loop {
+                    interval.tick().await;
+                    if !leader.is_leader() {
+                        continue;
+                    }
+                    match redis_storage.reconcile().await {
+                        Ok(report) => {
+                            if report.removed_id_keys
+                                + report.migrated_id_keys
+                                + report.repaired_id_keys
+                                + report.repaired_ttls
+                                > 0
+                            {
+                                info!(
+                                    "[RECONCILE] Removed {} dangling id entries, migrated {} id keys, repaired {} id entries and {} TTLs",
+                                    report.removed_id_keys,
+                                    report.migrated_id_keys,
+                                    report.repaired_id_keys,
+                                    report.repaired_ttls
+                                );
+                            }
+                        }
+                        Err(e) => log::error!("[RECONCILE] Failed to reconcile Redis keys: {}", e),
+                    }
+                }
+            });
+        }
+
+        // 启动清理任务（Redis 自动过期，无需清理）
+        if self.redis_storages.is_empty() {
+            let ops = self.lock_ops(&self.config, self.storage.clone());
+            let leader = self.leader.clone();
+            let handoff = self.handoff.clone();
+            let cleanup_interval = self.config.cleanup_interval_seconds;
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(Duration::from_secs(cleanup_interval));
+                loop {
+                    interval.tick().await;
+                    if !leader.is_leader() {
+                        continue;
+                    }
+                    // 已隔离的实例不再修改锁，过期的锁由新实例清理
+                    let Some(_in_flight) = handoff::admit(handoff.as_ref()) else {
+                        continue;
+                    };
+                    if let Err(e) = ops.cleanup_expired().await {
+                        log::error!("Failed to cleanup expired locks: {}", e);
+                    }
+                }
+            });
+        }
+
+        // 双写复制，异步模式下按顺序写入副本，并定时以主存储为准对账
+        if let Some(replicated_storage) = &self.replicated_storage {
+            if replicated_storage.mode() == ReplicationMode::Async {
+                tokio::spawn(replicated_storage.clone().run());
+            }
+            if self.config.replication_reconcile_interval > 0 {
+                let replicated_storage = replicated_storage.clone();
+                let leader = self.leader.clone();
+                let clock = self.clock.clone();
+                let reconcile_interval = self.config.replication_reconcile_interval;
+                tokio::spawn(async move {
+                    let mut interval = tokio::time::interval(Duration::from_secs(reconcile_interval));
+                    loop {
+                        interval.tick().await;
+                        if !leader.is_leader() {
+                            continue;
+                        }
+                        match replicated_storage.reconcile(clock.now()).await {
+                            Ok(report) => {
+                                if report.upserted + report.removed + report.namespaces > 0 {
+                                    info!(
+                                        "[REPLICATION] Repaired {} locks, removed {} stale locks and synced {} namespaces",
+                                        report.upserted, report.removed, report.namespaces
+                                    );
+                                }
+                            }
+                            Err(e) => log::error!("[REPLICATION] Failed to reconcile replica: {}", e),
+                        }
+                    }
+                });
+            }
+        }
+
+        // 字段密钥轮换后将锁重新以主密钥加密
+        if let Some(encrypted_storage) = self.encrypted_storage.clone() {
+            if self.config.storage_field_rewrap_interval > 0 {
+                let leader = self.leader.clone();
+                let handoff = self.handoff.clone();
+                let rewrap_interval = self.config.storage_field_rewrap_interval;
+                tokio::spawn(async move {
+                    let mut interval = tokio::time::interval(Duration::from_secs(rewrap_interval));
+                    loop {
+                        interval.tick().await;
+                        if !leader.is_leader() {
+                            continue;
+                        }
+                        let Some(_in_flight) = handoff::admit(handoff.as_ref()) else {
+                            continue;
+                        };
+                        match encrypted_storage.rewrap().await {
+                            Ok(0) => {}
+                            Ok(count) => info!("[FIELDCRYPT] Re-encrypted {} locks and records with the primary key", count),
+                            Err(e) => log::error!("[FIELDCRYPT] Failed to rewrap locks: {}", e),
+                        }
+                    }
+                });
+            }
+        }
+
+        // 内存副本的持久化
+        if let Some(replica_memory) = self.replica_memory.clone() {
+            let persist_interval = self.config.memory_persist_interval;
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(Duration::from_secs(persist_interval));
+                loop {
+                    interval.tick().await;
+                    if let Err(e) = replica_memory.persist_to_disk().await {
+                        log::error!("[REPLICATION] Failed to persist replica: {}", e);
+                    }
+                }
+            });
+        }
+
+        // 启动持久化任务
+        if let Some(memory_storage) = self
+            .memory_storage
+            .clone()
+            .filter(|memory_storage| memory_storage.persist_enabled())
+        {
+            let persist_interval = self.config.memory_persist_interval;
+            let handoff = self.handoff.clone();
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(Duration::from_secs(persist_interval));
+                loop {
+                    // 定时持久化，或变更次数达到阈值时立即持久化
+                    tokio::select! {
+                        _ = interval.tick() => {}
+                        _ = memory_storage.changes_pending() => {}
+                    }
+
+                    // 已隔离的实例不再覆盖新实例写入的持久化文件
+                    if handoff.as_ref().is_some_and(|handoff| handoff.fenced()) {
+                        continue;
+                    }
+
+                    if let Err(e) = memory_storage.persist_to_disk().await {
+                        log::error!("[PERSISTENCE] Failed to persist to disk: {}", e);
+                    }
+                }
+            });
+        }
+    }
+
+    /// 注册共享状态和全部 HTTP 接口：`/api`（含管理接口）、`/metrics`、`/readyz`，Raft 存储另有节点间的 `/raft`，
+    /// 启用集群发现时另有 `/cluster/gossip`
+    pub fn configure(&self, cfg: &mut web::ServiceConfig) {
+        self.register_data(cfg, self.config.clone());
+        self.register_peer_routes(cfg);
+        cfg.service(
+            web::resource("/metrics")
+                .wrap(from_fn(ipfilter::ip_guard))
+                .route(web::get().to(metrics::metrics)),
+        )
+            .route("/readyz", web::get().to(health::readyz))
+            .service(
+                web::scope("/api")
+                    .wrap(from_fn(handoff::fence_guard))
+                    .wrap(from_fn(failover::storage_guard))
+                    .wrap(from_fn(chaos::chaos_guard))
+                    .wrap(from_fn(timeout::request_timeout))
+                    .wrap(from_fn(tenant::tenant_guard))
+                    .wrap(from_fn(backpressure::backpressure_guard))
+                    .wrap(from_fn(latency::stage_timing))
+                    .wrap(from_fn(ipfilter::ip_guard))
+                    .configure(lock_routes)
+                    .configure(|cfg| self.register_admin(cfg, &self.config)),
+            );
+    }
+
+    /// 注册共享状态和公共接口：`/api` 下的锁和会话接口、`/readyz`，不含管理接口、`/metrics` 和节点间的 `/raft`、`/cluster/gossip`
+    pub fn configure_public(&self, cfg: &mut web::ServiceConfig) {
+        self.register_data(cfg, self.config.clone());
+        cfg.route("/readyz", web::get().to(health::readyz)).service(
+            web::scope("/api")
+                .wrap(from_fn(handoff::fence_guard))
+                .wrap(from_fn(failover::storage_guard))
+                .wrap(from_fn(chaos::chaos_guard))
+                .wrap(from_fn(timeout::request_timeout))
+                .wrap(from_fn(tenant::tenant_guard))
+                .wrap(from_fn(backpressure::backpressure_guard))
+                .wrap(from_fn(latency::stage_timing))
+                .wrap(from_fn(ipfilter::ip_guard))
+                .configure(lock_routes),
+        );
+    }
+
+    /// 注册共享状态和管理接口：`/api/admin`、`/metrics`、`/readyz`，管理接口使用 `MANAGEMENT_TOKEN` 认证；
+    /// Raft 存储另有节点间的 `/raft`，启用集群发现时另有 `/cluster/gossip`，不暴露在公共端口上
+    pub fn configure_management(&self, cfg: &mut web::ServiceConfig) {
+        let config = Config {
+            admin_token: self.config.management_token.clone(),
+            ..self.config.clone()
+        };
+        self.register_data(cfg, config.clone());
+        self.register_peer_routes(cfg);
+        cfg.service(
+            web::resource("/metrics")
+                .wrap(from_fn(ipfilter::ip_guard))
+                .route(web::get().to(metrics::metrics)),
+        )
+            .route("/readyz", web::get().to(health::readyz))
+            .service(
+                web::scope("/api")
+                    .wrap(from_fn(handoff::fence_guard))
+                    .wrap(from_fn(failover::storage_guard))
+                    .wrap(from_fn(timeout::request_timeout))
+                    .wrap(from_fn(latency::stage_timing))
+                    .wrap(from_fn(ipfilter::ip_guard))
+                    .configure(|cfg| self.register_admin(cfg, &config)),
+            );
+    }
+
+    /// 配置了管理令牌或 OIDC 登录时注册管理接口，都未配置时不提供管理接口
+    fn register_admin(&self, cfg: &mut web::ServiceConfig, config: &Config) {
+        if config.admin_token.is_some() || self.admin_login.is_some() {
+            admin::configure(cfg);
+        }
+    }
+
+    fn register_data(&self, cfg: &mut web::ServiceConfig, config: Config) {
+        // 接口中的存储调用计入请求耗时分解
+        let storage: Arc<dyn LockStorage> = Arc::new(TimedStorage::new(self.storage.clone()));
+        cfg.app_data(web::Data::new(storage.clone()))
+            .app_data(web::Data::new(self.event_bus.clone()))
+            .app_data(web::Data::new(self.wait_queue.clone()))
+            .app_data(web::Data::new(self.expiry_watcher.clone()))
+            .app_data(web::Data::new(self.reservation_scheduler.clone()))
+            .app_data(web::Data::new(self.preemption_scheduler.clone()))
+            .app_data(web::Data::new(self.drain_scheduler.clone()))
+            .app_data(web::Data::new(self.takeover_broker.clone()))
+            .app_data(web::Data::new(self.tenant_registry.clone()))
+            .app_data(web::Data::new(self.session_registry.clone()))
+            .app_data(web::Data::new(self.backpressure.clone()))
+            .app_data(web::Data::new(self.ip_filters.clone()))
+            .app_data(web::Data::new(self.request_timings.clone()))
+            .app_data(web::Data::new(self.leader.clone()))
+            .app_data(web::Data::new(self.contention.clone()))
+            .app_data(web::Data::new(self.usage.clone()))
+            .app_data(web::Data::new(self.release_notifier.clone()))
+            .app_data(web::Data::new(self.tombstones.clone()))
+            .app_data(web::Data::new(self.notices.clone()))
+            .app_data(web::Data::new(self.stuck_detector.clone()))
+            .app_data(web::Data::new(self.overdue_tracker.clone()))
+            .app_data(web::Data::new(self.hold_cap_monitor.clone()))
+            .app_data(web::Data::new(self.heartbeat_advisor.clone()))
+            .app_data(web::Data::new(self.stats_sampler.clone()))
+            .app_data(web::Data::new(self.self_test.clone()))
+            .app_data(web::Data::new(self.id_generator.clone()))
+            .app_data(web::Data::new(self.clock.clone()))
+            .app_data(web::Data::new(self.lock_ops(&config, storage)))
+            .app_data(web::Data::new(config));
+        if let Some(admin_login) = &self.admin_login {
+            cfg.app_data(web::Data::new(admin_login.clone()));
+        }
+        if let Some(failover_storage) = &self.failover_storage {
+            cfg.app_data(web::Data::new(failover_storage.clone()));
+        }
+        if let Some(sharded_storage) = &self.sharded_storage {
+            cfg.app_data(web::Data::new(sharded_storage.clone()));
+        }
+        if let Some(memory_storage) = &self.memory_storage {
+            cfg.app_data(web::Data::new(memory_storage.clone()));
+        }
+        if let Some(handoff) = &self.handoff {
+            cfg.app_data(web::Data::new(handoff.clone()));
+        }
+        if let Some(replicated_storage) = &self.replicated_storage {
+            cfg.app_data(web::Data::new(replicated_storage.clone()));
+        }
+        if let Some(chaos) = &self.chaos {
+            cfg.app_data(web::Data::new(chaos.clone()));
+        }
+        if let Some(cluster) = &self.cluster {
+            cfg.app_data(web::Data::new(cluster.clone()));
+        }
+        if let Some(history) = &self.history {
+            cfg.app_data(web::Data::new(history.clone()));
+        }
+        if let Some(audit) = &self.audit {
+            cfg.app_data(web::Data::new(audit.clone()));
+        }
+    }
+
+    /// v1 和 v2 锁接口共用的锁操作服务层
+    fn lock_ops(&self, config: &Config, storage: Arc<dyn LockStorage>) -> LockOps {
+        LockOps {
+            storage,
+            events: self.event_bus.clone(),
+            config: config.clone(),
+            queue: self.wait_queue.clone(),
+            expiry: self.expiry_watcher.clone(),
+            preemption: self.preemption_scheduler.clone(),
+            drain: self.drain_scheduler.clone(),
+            sessions: self.session_registry.clone(),
+            contention: self.contention.clone(),
+            tombstones: self.tombstones.clone(),
+            notices: self.notices.clone(),
+            recovery: self.recovery.clone(),
+            id_generator: self.id_generator.clone(),
+            clock: self.clock.clone(),
+            chaos: self.chaos.clone(),
+            inflight: self.inflight.clone(),
+            tenants: self.tenant_registry.clone(),
+            hooks: self.hooks.clone(),
+            heartbeats: self.heartbeat_advisor.clone(),
+        }
+    }
+
+    fn register_peer_routes(&self, cfg: &mut web::ServiceConfig) {
+        if let Some(raft_storage) = &self.raft_storage {
+            raft::configure_routes(cfg, raft_storage.clone());
+        }
+        if let Some(cluster) = &self.cluster {
+            cluster::configure_routes(cfg, cluster.clone());
+        }
+    }
+}
+
+/// 按配置创建锁历史并订阅锁事件
+async fn open_history(
+    config: &Config,
+    event_bus: &mut EventBus,
+    redis_storage: Option<Arc<RedisStorage>>,
+    cipher: Option<Arc<FieldCipher>>,
+) -> Result<Option<Arc<LockHistory>>> {
+    let Some(history) = LockHistory::from_config(config, redis_storage).await? else {
+        return Ok(None);
+    };
+    let history = Arc::new(history.with_cipher(cipher));
+    event_bus.register(history.clone());
+    Ok(Some(history))
+}
+
+/// 按配置将锁事件追加到审计 Stream，`AUDIT_STREAM_ENABLED=false` 时返回 None
+fn open_audit(
+    config: &Config,
+    event_bus: &mut EventBus,
+    redis_storage: &Arc<RedisStorage>,
+    cipher: Option<Arc<FieldCipher>>,
+) -> Option<Arc<RedisAuditSink>> {
+    if !config.audit_stream_enabled {
+        return None;
+    }
+    info!(
+        "Appending lock events to Redis Stream {}audit, max length: {}",
+        config.redis_key_prefix, config.audit_stream_max_len
+    );
+    let audit = Arc::new(RedisAuditSink::new(redis_storage.clone(), config.audit_stream_max_len).with_cipher(cipher));
+    event_bus.register(audit.clone());
+    Some(audit)
+}
+
+/// `/api` 下的锁和会话接口
+fn lock_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        SwaggerUi::new("/swagger-ui/{_:.*}").url("/api-docs/openapi.json", handlers::ApiDoc::openapi()),
+    )
+    .route("/lock/acquire", web::post().to(handlers::acquire_lock))
+    .route("/lock/acquire-outcome", web::post().to(handlers::acquire_lock_outcome))
+    .route("/lock/can-acquire", web::post().to(handlers::can_acquire))
+    .route("/lock/status", web::get().to(handlers::lock_status))
+    .service(
+        web::resource("/lock/list")
+            .wrap(Compress::default())
+            .route(web::get().to(handlers::list_locks)),
+    )
+    .route("/lock/history", web::get().to(handlers::lock_history))
+    .route("/lock/queue", web::get().to(handlers::lock_queue))
+    .route("/lock/queue", web::delete().to(handlers::leave_queue))
+    .route("/lock/wait-release", web::get().to(handlers::wait_release))
+    .route("/lock/heartbeat", web::post().to(handlers::heartbeat))
+    .route("/lock/release", web::post().to(handlers::release_lock))
+    .route("/lock/release-all", web::post().to(handlers::release_all))
+    .route("/lock/release-session-graceful", web::post().to(handlers::release_session_graceful))
+    .route("/lock/checkout", web::post().to(handlers::checkout))
+    .route("/lock/checkin", web::post().to(handlers::checkin))
+    .route("/lock/verify", web::post().to(handlers::verify_lock))
+    .route("/lock/by-user/{user_id}", web::get().to(handlers::list_user_locks))
+    .route("/lock/session", web::get().to(session::session))
+    .route("/lock/reserve", web::post().to(handlers::reserve_lock))
+    .route("/lock/reserve/cancel", web::post().to(handlers::cancel_reservation))
+    .route("/lock/reservations", web::get().to(handlers::list_reservations))
+    .route("/lock/request-takeover", web::post().to(handlers::request_takeover))
+    .route("/lock/takeover/respond", web::post().to(handlers::respond_takeover))
+    .route("/lock/takeover/status", web::get().to(handlers::takeover_status))
+    .route("/lock/takeover/pending", web::get().to(handlers::pending_takeovers))
+    .route("/presence/join", web::post().to(handlers::presence_join))
+    .route("/presence/leave", web::post().to(handlers::presence_leave))
+    .route("/presence/list", web::get().to(handlers::presence_list))
+    .route("/session/create", web::post().to(handlers::create_session))
+    .route("/session/heartbeat", web::post().to(handlers::session_heartbeat))
+    .route("/session/close", web::post().to(handlers::close_session))
+    .route("/sequence/next", web::post().to(handlers::next_sequence))
+    .route("/ratelimit/check", web::post().to(handlers::check_rate_limit))
+    .route("/stats", web::get().to(handlers::stats))
+    .route("/stats/contention", web::get().to(handlers::contention_stats))
+    .route("/stats/users", web::get().to(handlers::user_stats))
+    .route("/stats/timeseries", web::get().to(handlers::stats_timeseries))
+    .route("/time", web::get().to(handlers::server_time))
+    .route("/errors", web::get().to(handlers::error_codes))
+    .configure(v2::configure);
+}
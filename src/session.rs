@@ -0,0 +1,297 @@
+//! 会话自动续期 `GET /api/lock/session`（WebSocket）
+//!
+//! 客户端建立连接后通过 `attach` 将已获取的锁挂到会话上，连接保持期间由服务端定时续期；
+//! 连接断开（正常关闭、出错或超过 [`CLIENT_TIMEOUT`] 未收到任何消息）时立即释放会话中的所有锁。
+//! `detach` 只将锁移出会话、不释放，之后由客户端自行心跳或释放。
+//!
+//! 消息均为 JSON 文本帧，以 `type` 字段区分：
+//! - 客户端：`{"type":"attach","lock_id":"..."}`、`{"type":"detach","lock_id":"..."}`
+//! - 服务端：`attached`（含 `expires_at`、`remaining_seconds`）、`detached`、`lost`（续期时锁已不存在）、`error`
+//!
+//! 实例被交接隔离（见 [`crate::handoff`]）时发送错误码 7008 的 `error` 帧后断开，会话中的锁已交接给新实例，不释放，
+//! 客户端重新连接后重新 `attach` 即可。
+
+use crate::clock::Clock;
+use crate::events::{EventBus, LockEvent, LockEventType};
+use crate::handoff::{self, Handoff};
+use crate::models::{LockError, LockInfo};
+use crate::storage::LockStorage;
+use crate::tenant::{self, Tenant};
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse};
+use actix_ws::{Message, MessageStream, Session};
+use chrono::{DateTime, Utc};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// 检查续期的间隔
+const TICK_INTERVAL: Duration = Duration::from_secs(1);
+/// 服务端发送 Ping 的间隔
+const PING_INTERVAL: Duration = Duration::from_secs(10);
+/// 超过该时长未收到客户端任何消息视为断开
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientMessage {
+    Attach { lock_id: String },
+    Detach { lock_id: String },
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ServerMessage {
+    Attached {
+        lock_id: String,
+        expires_at: Option<DateTime<Utc>>,
+        remaining_seconds: Option<u64>,
+    },
+    Detached {
+        lock_id: String,
+    },
+    Lost {
+        lock_id: String,
+    },
+    Error {
+        code: i32,
+        message: String,
+    },
+}
+
+impl ServerMessage {
+    fn attached(lock_info: &LockInfo, now: DateTime<Utc>) -> Self {
+        ServerMessage::Attached {
+            lock_id: lock_info.lock_id.clone(),
+            expires_at: lock_info.expires_at(),
+            remaining_seconds: lock_info.remaining_secs_at(now),
+        }
+    }
+}
+
+pub async fn session(
+    req: HttpRequest,
+    body: web::Payload,
+    storage: web::Data<Arc<dyn LockStorage>>,
+    events: web::Data<Arc<EventBus>>,
+    clock: web::Data<Arc<dyn Clock>>,
+    handoff: Option<web::Data<Arc<Handoff>>>,
+) -> actix_web::Result<HttpResponse> {
+    let (response, ws, stream) = actix_ws::handle(&req, body)?;
+    let session = LockSession {
+        ws,
+        tenant: req.extensions().get::<Tenant>().cloned(),
+        storage: storage.get_ref().clone(),
+        events: events.get_ref().clone(),
+        clock: clock.get_ref().clone(),
+        handoff: handoff.map(|handoff| handoff.get_ref().clone()),
+        locks: HashMap::new(),
+    };
+    actix_web::rt::spawn(session.run(stream));
+    Ok(response)
+}
+
+struct LockSession {
+    ws: Session,
+    tenant: Option<Tenant>, // 启用多租户时只能挂上本租户的锁
+    storage: Arc<dyn LockStorage>,
+    events: Arc<EventBus>,
+    clock: Arc<dyn Clock>,
+    handoff: Option<Arc<Handoff>>,
+    locks: HashMap<String, LockInfo>, // lock_id -> 最近一次续期后的锁信息
+}
+
+impl LockSession {
+    async fn run(mut self, mut stream: MessageStream) {
+        info!("[SESSION] Session opened");
+        let mut tick = tokio::time::interval(TICK_INTERVAL);
+        let mut last_ping = Instant::now();
+        let mut last_seen = Instant::now();
+        let mut fenced = false;
+
+        let reason = loop {
+            tokio::select! {
+                message = stream.recv() => {
+                    let message = match message {
+                        Some(Ok(message)) => message,
+                        Some(Err(e)) => break format!("protocol error: {}", e),
+                        None => break "connection closed".to_string(),
+                    };
+                    last_seen = Instant::now();
+                    let Some(_in_flight) = handoff::admit(self.handoff.as_ref()) else {
+                        fenced = true;
+                        break "instance fenced".to_string();
+                    };
+                    let open = match message {
+                        Message::Text(text) => self.handle_text(&text).await,
+                        Message::Binary(_) => {
+                            self.send(&ServerMessage::Error {
+                                code: LockError::ValidationFailed.code(),
+                                message: "Binary messages are not supported".to_string(),
+                            })
+                            .await
+                        }
+                        Message::Ping(bytes) => self.ws.pong(&bytes).await.is_ok(),
+                        Message::Close(_) => break "closed by client".to_string(),
+                        _ => true,
+                    };
+                    if !open {
+                        break "connection closed".to_string();
+                    }
+                }
+                _ = tick.tick() => {
+                    let Some(_in_flight) = handoff::admit(self.handoff.as_ref()) else {
+                        fenced = true;
+                        break "instance fenced".to_string();
+                    };
+                    if last_seen.elapsed() > CLIENT_TIMEOUT {
+                        break "client timed out".to_string();
+                    }
+                    if last_ping.elapsed() >= PING_INTERVAL {
+                        last_ping = Instant::now();
+                        if self.ws.ping(b"").await.is_err() {
+                            break "connection closed".to_string();
+                        }
+                    }
+                    if !self.renew_due().await {
+                        break "connection closed".to_string();
+                    }
+                }
+            }
+        };
+
+        if fenced {
+            // 锁已交接给新实例，客户端重新连接后重新挂上
+            info!("[SESSION] Session ended ({}), keeping {} locks", reason, self.locks.len());
+            self.send(&ServerMessage::Error {
+                code: LockError::InstanceFenced.code(),
+                message: "Instance handed off, reconnect and attach the locks again".to_string(),
+            })
+            .await;
+            let _ = self.ws.close(None).await;
+            return;
+        }
+
+        info!(
+            "[SESSION] Session ended ({}), releasing {} locks",
+            reason,
+            self.locks.len()
+        );
+        self.release_all().await;
+        let _ = self.ws.close(None).await;
+    }
+
+    /// 续期并返回要挂上的锁，其他租户的锁视为不存在
+    async fn attach(&self, lock_id: &str) -> anyhow::Result<Option<LockInfo>> {
+        if !tenant::may_access_lock(self.storage.as_ref(), self.tenant.as_ref(), lock_id).await? {
+            return Ok(None);
+        }
+        self.storage.update_heartbeat(lock_id).await
+    }
+
+    /// 处理客户端消息，返回连接是否仍然可用
+    async fn handle_text(&mut self, text: &str) -> bool {
+        let message = match serde_json::from_str::<ClientMessage>(text) {
+            Ok(message) => message,
+            Err(e) => {
+                return self
+                    .send(&ServerMessage::Error {
+                        code: LockError::ValidationFailed.code(),
+                        message: format!("Invalid message: {}", e),
+                    })
+                    .await
+            }
+        };
+
+        match message {
+            ClientMessage::Attach { lock_id } => match self.attach(&lock_id).await {
+                Ok(Some(lock_info)) => {
+                    info!("[SESSION] Lock attached - lock_id: {}", lock_id);
+                    let reply = ServerMessage::attached(&lock_info, self.clock.now());
+                    self.locks.insert(lock_id, lock_info);
+                    self.send(&reply).await
+                }
+                Ok(None) => {
+                    self.send(&ServerMessage::Error {
+                        code: LockError::HeartbeatLockNotFound.code(),
+                        message: format!("Lock not found or expired: {}", lock_id),
+                    })
+                    .await
+                }
+                Err(e) => {
+                    error!("[SESSION] Failed to attach lock {}: {}", lock_id, e);
+                    self.send(&ServerMessage::Error {
+                        code: LockError::HeartbeatFailed.code(),
+                        message: format!("Failed to update heartbeat: {}", e),
+                    })
+                    .await
+                }
+            },
+            ClientMessage::Detach { lock_id } => {
+                if self.locks.remove(&lock_id).is_some() {
+                    info!("[SESSION] Lock detached - lock_id: {}", lock_id);
+                }
+                self.send(&ServerMessage::Detached { lock_id }).await
+            }
+        }
+    }
+
+    /// 为已过去三分之一超时时间的锁续期，续期时锁已不存在则通知客户端并移出会话
+    async fn renew_due(&mut self) -> bool {
+        let now = self.clock.now();
+        let due: Vec<String> = self
+            .locks
+            .values()
+            .filter(|lock_info| !lock_info.is_permanent())
+            .filter(|lock_info| {
+                let elapsed = (now - lock_info.last_heartbeat).num_seconds().max(0) as u64;
+                elapsed * 3 >= lock_info.timeout
+            })
+            .map(|lock_info| lock_info.lock_id.clone())
+            .collect();
+
+        for lock_id in due {
+            match self.storage.update_heartbeat(&lock_id).await {
+                Ok(Some(lock_info)) => {
+                    self.locks.insert(lock_id, lock_info);
+                }
+                Ok(None) => {
+                    warn!("[SESSION] Lock lost before renewal - lock_id: {}", lock_id);
+                    self.locks.remove(&lock_id);
+                    if !self.send(&ServerMessage::Lost { lock_id }).await {
+                        return false;
+                    }
+                }
+                // 存储暂时不可用时保留在会话中，下一轮继续重试
+                Err(e) => error!("[SESSION] Failed to renew lock {}: {}", lock_id, e),
+            }
+        }
+        true
+    }
+
+    async fn release_all(&mut self) {
+        for lock_id in std::mem::take(&mut self.locks).into_keys() {
+            match self.storage.release(&lock_id).await {
+                Ok(Some(lock_info)) => {
+                    info!("[SESSION] Lock released on disconnect - lock_id: {}", lock_id);
+                    self.events
+                        .publish(LockEvent::new(LockEventType::Released, lock_info));
+                }
+                Ok(None) => {}
+                Err(e) => error!("[SESSION] Failed to release lock {}: {}", lock_id, e),
+            }
+        }
+    }
+
+    /// 发送消息，返回连接是否仍然可用
+    async fn send(&mut self, message: &ServerMessage) -> bool {
+        match serde_json::to_string(message) {
+            Ok(text) => self.ws.text(text).await.is_ok(),
+            Err(e) => {
+                error!("[SESSION] Failed to serialize message: {}", e);
+                true
+            }
+        }
+    }
+}
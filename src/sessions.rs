@@ -0,0 +1,257 @@
+//! 客户端会话 `/api/session/*`
+//!
+//! 客户端创建会话后，申请锁时传入 `session_id` 即将锁挂到会话上：锁的超时时间取会话的超时时间，
+//! 每次会话心跳为会话中的所有锁续期，客户端只需维持一个心跳。会话关闭或超过超时时间未收到心跳时释放会话中的所有锁。
+//! 与 WebSocket 会话（`session.rs`）不同，会话基于 HTTP 心跳，不需要保持连接。
+//! 会话以记录的形式保存在锁存储中（每个会话一条记录），多实例部署时会话的请求可以发往任意实例，实例重启后会话仍然保留。
+//! 关闭和超时结束会话时先删除记录，多个实例同时处理同一会话时只有删除成功的实例释放其中的锁。
+
+use crate::events::{EventBus, LockEvent, LockEventType};
+use crate::models::{LockInfo, SessionInfo};
+use crate::storage::LockStorage;
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use uuid::Uuid;
+
+/// 会话在存储中的记录类别
+pub const RECORD_KIND: &str = "session";
+
+#[derive(Serialize, Deserialize)]
+struct Session {
+    user_id: String,
+    timeout: u64,
+    created_at: DateTime<Utc>,
+    last_heartbeat: DateTime<Utc>,
+    lock_ids: BTreeSet<String>,
+}
+
+impl Session {
+    fn expires_at(&self) -> DateTime<Utc> {
+        self.last_heartbeat + Duration::seconds(self.timeout as i64)
+    }
+
+    fn info(&self, session_id: &str) -> SessionInfo {
+        SessionInfo {
+            session_id: session_id.to_string(),
+            user_id: self.user_id.clone(),
+            timeout: self.timeout,
+            created_at: self.created_at,
+            last_heartbeat: self.last_heartbeat,
+            expires_at: self.expires_at(),
+            lock_ids: self.lock_ids.iter().cloned().collect(),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct SessionRegistry;
+
+impl SessionRegistry {
+    pub fn new() -> Self {
+        Self
+    }
+
+    pub async fn create(
+        &self,
+        storage: &dyn LockStorage,
+        user_id: &str,
+        timeout: u64,
+        now: DateTime<Utc>,
+    ) -> Result<SessionInfo> {
+        let session_id = Uuid::new_v4().to_string();
+        let session = Session {
+            user_id: user_id.to_string(),
+            timeout,
+            created_at: now,
+            last_heartbeat: now,
+            lock_ids: BTreeSet::new(),
+        };
+        let value = serde_json::to_string(&session)?;
+        if !storage.swap_record(RECORD_KIND, &session_id, None, Some(&value)).await? {
+            anyhow::bail!("session id {} already exists", session_id);
+        }
+        Ok(session.info(&session_id))
+    }
+
+    /// 属于 user_id 且未结束的会话
+    pub async fn get(
+        &self,
+        storage: &dyn LockStorage,
+        session_id: &str,
+        user_id: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Option<SessionInfo>> {
+        Ok(load(storage, session_id)
+            .await?
+            .map(|(_, session)| session)
+            .filter(|session| session.user_id == user_id && session.expires_at() > now)
+            .map(|session| session.info(session_id)))
+    }
+
+    /// 将锁挂到会话上，会话已结束时返回 false
+    pub async fn attach(&self, storage: &dyn LockStorage, session_id: &str, lock_id: &str) -> Result<bool> {
+        update(storage, session_id, |session| match session {
+            Some(session) => {
+                session.lock_ids.insert(lock_id.to_string());
+                true
+            }
+            None => false,
+        })
+        .await
+    }
+
+    /// 会话心跳，为会话中的所有锁续期并移出已释放或已过期的锁，会话不存在或已结束时返回 None
+    pub async fn heartbeat(
+        &self,
+        session_id: &str,
+        storage: &dyn LockStorage,
+        now: DateTime<Utc>,
+    ) -> Result<Option<SessionInfo>> {
+        let lock_ids = update(storage, session_id, |session| {
+            let session = session.as_mut().filter(|session| session.expires_at() > now)?;
+            session.last_heartbeat = now;
+            Some(session.lock_ids.clone())
+        })
+        .await?;
+        let Some(lock_ids) = lock_ids else {
+            return Ok(None);
+        };
+
+        let mut lost = Vec::new();
+        for lock_id in lock_ids {
+            if storage.update_heartbeat(&lock_id).await?.is_none() {
+                lost.push(lock_id);
+            }
+        }
+
+        update(storage, session_id, |session| {
+            let session = session.as_mut()?;
+            for lock_id in &lost {
+                session.lock_ids.remove(lock_id);
+            }
+            Some(session.info(session_id))
+        })
+        .await
+    }
+
+    /// 关闭会话并释放其中的锁，会话不存在时返回 None
+    pub async fn close(
+        &self,
+        session_id: &str,
+        storage: &dyn LockStorage,
+        events: &EventBus,
+    ) -> Result<Option<Vec<LockInfo>>> {
+        loop {
+            let Some((current, session)) = load(storage, session_id).await? else {
+                return Ok(None);
+            };
+            if storage.swap_record(RECORD_KIND, session_id, Some(&current), None).await? {
+                return Ok(Some(release(session, storage, events).await?));
+            }
+        }
+    }
+
+    /// 结束超时未心跳的会话并释放其中的锁
+    pub async fn expire_due(&self, storage: &dyn LockStorage, events: &EventBus, now: DateTime<Utc>) {
+        let records = match storage.list_records(RECORD_KIND).await {
+            Ok(records) => records,
+            Err(e) => {
+                log::error!("[CLIENT SESSION] Failed to list sessions: {}", e);
+                return;
+            }
+        };
+
+        for (session_id, value) in records {
+            let session: Session = match serde_json::from_str(&value) {
+                Ok(session) => session,
+                Err(e) => {
+                    log::warn!("[CLIENT SESSION] Skipping malformed session {}: {}", session_id, e);
+                    continue;
+                }
+            };
+            if session.expires_at() > now {
+                continue;
+            }
+            // 会话已被心跳、关闭或由其他实例结束时跳过
+            match storage.swap_record(RECORD_KIND, &session_id, Some(&value), None).await {
+                Ok(true) => {}
+                Ok(false) => continue,
+                Err(e) => {
+                    log::error!("[CLIENT SESSION] Failed to expire session {}: {}", session_id, e);
+                    continue;
+                }
+            }
+            log::info!(
+                "[CLIENT SESSION] Session expired - session_id: {}, user_id: {}, locks: {}",
+                session_id,
+                session.user_id,
+                session.lock_ids.len()
+            );
+            if let Err(e) = release(session, storage, events).await {
+                log::error!(
+                    "[CLIENT SESSION] Failed to release locks of expired session {}: {}",
+                    session_id,
+                    e
+                );
+            }
+        }
+    }
+}
+
+/// 读取会话记录，返回记录原文和会话
+async fn load(storage: &dyn LockStorage, session_id: &str) -> Result<Option<(String, Session)>> {
+    match storage.get_record(RECORD_KIND, session_id).await? {
+        Some(value) => {
+            let session = serde_json::from_str(&value)?;
+            Ok(Some((value, session)))
+        }
+        None => Ok(None),
+    }
+}
+
+/// 读取会话，由 apply 修改后写回，期间记录被其他实例修改时重新读取，apply 可能执行多次；apply 不会创建或删除会话
+async fn update<T>(
+    storage: &dyn LockStorage,
+    session_id: &str,
+    mut apply: impl FnMut(&mut Option<Session>) -> T,
+) -> Result<T> {
+    loop {
+        let current = load(storage, session_id).await?;
+        let (value, mut session) = match current {
+            Some((value, session)) => (Some(value), Some(session)),
+            None => (None, None),
+        };
+        let result = apply(&mut session);
+        let Some(session) = session else {
+            return Ok(result);
+        };
+        let updated = serde_json::to_string(&session)?;
+        if value.as_deref() == Some(updated.as_str()) {
+            return Ok(result);
+        }
+        if storage
+            .swap_record(RECORD_KIND, session_id, value.as_deref(), Some(&updated))
+            .await?
+        {
+            return Ok(result);
+        }
+    }
+}
+
+/// 释放会话中仍然存在的锁
+async fn release(
+    session: Session,
+    storage: &dyn LockStorage,
+    events: &EventBus,
+) -> Result<Vec<LockInfo>> {
+    let mut released = Vec::with_capacity(session.lock_ids.len());
+    for lock_id in &session.lock_ids {
+        if let Some(lock_info) = storage.release(lock_id).await? {
+            events.publish(LockEvent::new(LockEventType::Released, lock_info.clone()));
+            released.push(lock_info);
+        }
+    }
+    Ok(released)
+}
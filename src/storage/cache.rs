@@ -0,0 +1,237 @@
+//! 锁状态读缓存
+//!
+//! 包装存储，`get_lock`（锁状态查询）的结果在本地缓存 `STORAGE_CACHE_TTL_MS` 毫秒，写操作仍直接访问存储并使相关的缓存失效。
+//! 其他实例的写入通过 Redis 键空间通知使缓存失效，通知不可用时缓存的锁状态最多滞后一个缓存时长。
+//! 锁的互斥由存储的原子操作保证，不受缓存影响。
+
+use crate::models::{
+    LockCondition, LockInfo, LockStats, NamespacePolicy, PreemptionNotice, RateLimitDecision, TokenBucket,
+};
+use crate::storage::{Acquire, LockStorage, Takeover};
+use anyhow::Result;
+use async_trait::async_trait;
+use dashmap::DashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// 缓存条目数超过该值时清理过期条目
+const MAX_ENTRIES: usize = 10_000;
+
+struct CacheEntry {
+    cached_at: Instant,
+    lock_info: Option<LockInfo>,
+}
+
+pub struct CachedStorage {
+    inner: Arc<dyn LockStorage>,
+    ttl: Duration,
+    entries: DashMap<String, CacheEntry>, // lock_key -> 缓存的锁状态，None 表示锁空闲
+}
+
+impl CachedStorage {
+    pub fn new(inner: Arc<dyn LockStorage>, ttl: Duration) -> Self {
+        Self {
+            inner,
+            ttl,
+            entries: DashMap::new(),
+        }
+    }
+
+    /// 使 lock_key 的缓存失效
+    pub fn invalidate(&self, lock_key: &str) {
+        self.entries.remove(lock_key);
+    }
+
+    /// 清空缓存（键空间通知中断期间可能错过失效）
+    pub fn clear(&self) {
+        self.entries.clear();
+    }
+
+    fn invalidate_lock(&self, lock_info: &LockInfo) {
+        self.invalidate(&lock_info.get_lock_key());
+    }
+
+    /// 写操作完成后使返回的锁的缓存失效
+    fn invalidate_result<T>(&self, result: Result<T>, locks: impl Fn(&T) -> Vec<&LockInfo>) -> Result<T> {
+        if let Ok(value) = &result {
+            for lock_info in locks(value) {
+                self.invalidate_lock(lock_info);
+            }
+        }
+        result
+    }
+}
+
+#[async_trait]
+impl LockStorage for CachedStorage {
+    async fn try_acquire(&self, lock_info: LockInfo) -> Result<Acquire> {
+        let lock_key = lock_info.get_lock_key();
+        let result = self.inner.try_acquire(lock_info).await;
+        self.invalidate(&lock_key);
+        result
+    }
+
+    async fn takeover(&self, lock_info: LockInfo, condition: &LockCondition) -> Result<Takeover> {
+        let lock_key = lock_info.get_lock_key();
+        let result = self.inner.takeover(lock_info, condition).await;
+        self.invalidate(&lock_key);
+        result
+    }
+
+    async fn restore(&self, lock_info: LockInfo) -> Result<bool> {
+        let lock_key = lock_info.get_lock_key();
+        let result = self.inner.restore(lock_info).await;
+        self.invalidate(&lock_key);
+        result
+    }
+
+    async fn get_lock(&self, lock_key: &str) -> Result<Option<LockInfo>> {
+        if let Some(entry) = self.entries.get(lock_key) {
+            if entry.cached_at.elapsed() < self.ttl {
+                return Ok(entry.lock_info.clone());
+            }
+        }
+
+        let lock_info = self.inner.get_lock(lock_key).await?;
+        if self.entries.len() >= MAX_ENTRIES {
+            self.entries.retain(|_, entry| entry.cached_at.elapsed() < self.ttl);
+        }
+        self.entries.insert(
+            lock_key.to_string(),
+            CacheEntry {
+                cached_at: Instant::now(),
+                lock_info: lock_info.clone(),
+            },
+        );
+        Ok(lock_info)
+    }
+
+    async fn update_heartbeat(&self, lock_id: &str) -> Result<Option<LockInfo>> {
+        let result = self.inner.update_heartbeat(lock_id).await;
+        self.invalidate_result(result, |lock_info| lock_info.iter().collect())
+    }
+
+    async fn update_heartbeat_version(&self, lock_id: &str, version: u64) -> Result<Option<LockInfo>> {
+        let result = self.inner.update_heartbeat_version(lock_id, version).await;
+        self.invalidate_result(result, |lock_info| lock_info.iter().collect())
+    }
+
+    async fn release(&self, lock_id: &str) -> Result<Option<LockInfo>> {
+        let result = self.inner.release(lock_id).await;
+        self.invalidate_result(result, |lock_info| lock_info.iter().collect())
+    }
+
+    async fn release_version(&self, lock_id: &str, version: u64) -> Result<Option<LockInfo>> {
+        let result = self.inner.release_version(lock_id, version).await;
+        self.invalidate_result(result, |lock_info| lock_info.iter().collect())
+    }
+
+    async fn release_owned(&self, lock_key: &str, user_id: &str) -> Result<Option<LockInfo>> {
+        let result = self.inner.release_owned(lock_key, user_id).await;
+        self.invalidate(lock_key);
+        result
+    }
+
+    async fn cleanup_expired(&self) -> Result<Vec<LockInfo>> {
+        let result = self.inner.cleanup_expired().await;
+        self.invalidate_result(result, |locks| locks.iter().collect())
+    }
+
+    async fn list_locks(&self) -> Result<Vec<LockInfo>> {
+        self.inner.list_locks().await
+    }
+
+    async fn list_prefix(&self, key_prefix: &str) -> Result<Vec<LockInfo>> {
+        self.inner.list_prefix(key_prefix).await
+    }
+
+    async fn get_lock_by_id(&self, lock_id: &str) -> Result<Option<LockInfo>> {
+        self.inner.get_lock_by_id(lock_id).await
+    }
+
+    async fn force_release(&self, lock_key: &str) -> Result<Option<LockInfo>> {
+        let result = self.inner.force_release(lock_key).await;
+        self.invalidate(lock_key);
+        result
+    }
+
+    async fn mark_preempted(
+        &self,
+        lock_id: &str,
+        notice: PreemptionNotice,
+    ) -> Result<Option<LockInfo>> {
+        let result = self.inner.mark_preempted(lock_id, notice).await;
+        self.invalidate_result(result, |lock_info| lock_info.iter().collect())
+    }
+
+    async fn transfer(&self, lock_id: &str, new_lock: LockInfo) -> Result<Option<LockInfo>> {
+        let lock_key = new_lock.get_lock_key();
+        let result = self.inner.transfer(lock_id, new_lock).await;
+        self.invalidate(&lock_key);
+        result
+    }
+
+    async fn rewrite_lock(&self, lock_info: LockInfo) -> Result<bool> {
+        let lock_key = lock_info.get_lock_key();
+        let result = self.inner.rewrite_lock(lock_info).await;
+        self.invalidate(&lock_key);
+        result
+    }
+
+    async fn list_by_user(&self, user_id: &str) -> Result<Vec<LockInfo>> {
+        self.inner.list_by_user(user_id).await
+    }
+
+    async fn list_by_business_id(&self, business_id: &str) -> Result<Vec<LockInfo>> {
+        self.inner.list_by_business_id(business_id).await
+    }
+
+    async fn release_all_by_user(&self, user_id: &str) -> Result<Vec<LockInfo>> {
+        let result = self.inner.release_all_by_user(user_id).await;
+        self.invalidate_result(result, |locks| locks.iter().collect())
+    }
+
+    async fn count_locks(&self, namespace: &str) -> Result<u64> {
+        self.inner.count_locks(namespace).await
+    }
+
+    async fn stats(&self, top_n: usize) -> Result<LockStats> {
+        self.inner.stats(top_n).await
+    }
+
+    async fn list_namespaces(&self) -> Result<Vec<NamespacePolicy>> {
+        self.inner.list_namespaces().await
+    }
+
+    async fn get_namespace(&self, name: &str) -> Result<Option<NamespacePolicy>> {
+        self.inner.get_namespace(name).await
+    }
+
+    async fn put_namespace(&self, policy: NamespacePolicy) -> Result<()> {
+        self.inner.put_namespace(policy).await
+    }
+
+    async fn delete_namespace(&self, name: &str) -> Result<bool> {
+        self.inner.delete_namespace(name).await
+    }
+
+    async fn next_sequence(&self, name: &str, count: u64) -> Result<u64> {
+        self.inner.next_sequence(name, count).await
+    }
+
+    async fn take_tokens(&self, key: &str, bucket: TokenBucket, cost: u64) -> Result<RateLimitDecision> {
+        self.inner.take_tokens(key, bucket, cost).await
+    }
+
+    async fn get_record(&self, kind: &str, id: &str) -> Result<Option<String>> {
+        self.inner.get_record(kind, id).await
+    }
+
+    async fn list_records(&self, kind: &str) -> Result<Vec<(String, String)>> {
+        self.inner.list_records(kind).await
+    }
+
+    async fn swap_record(&self, kind: &str, id: &str, expected: Option<&str>, value: Option<&str>) -> Result<bool> {
+        self.inner.swap_record(kind, id, expected, value).await
+    }
+}
@@ -0,0 +1,129 @@
+//! 持久化文件加密
+//!
+//! 配置了密钥时，内存存储的持久化文件使用 AES-256-GCM 加密，加载时校验认证标签，密钥错误或文件被篡改都会拒绝加载。
+//! 文件格式：魔数 `FLCE` + 版本号 + 12 字节随机 nonce + 密文（含 16 字节认证标签），魔数和版本号作为附加认证数据。
+
+use crate::config::Config;
+use anyhow::{anyhow, bail, Context, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use thiserror::Error;
+
+/// 加密文件头魔数
+const MAGIC: &[u8; 4] = b"FLCE";
+/// 加密格式版本
+const VERSION: u8 = 1;
+/// 密钥长度（字节）
+const KEY_LEN: usize = 32;
+
+/// 加密持久化文件无法解密
+#[derive(Debug, Error)]
+pub enum SealError {
+    #[error("persistence file is encrypted but no MEMORY_PERSIST_KEY is configured")]
+    KeyMissing,
+    #[error("persistence file failed integrity verification (wrong key or corrupted file)")]
+    Integrity,
+}
+
+pub struct SnapshotCipher {
+    key: LessSafeKey,
+    rng: SystemRandom,
+}
+
+impl SnapshotCipher {
+    /// 使用 32 字节的原始密钥
+    pub fn new(key: &[u8]) -> Result<Self> {
+        if key.len() != KEY_LEN {
+            bail!("persistence key must be {} bytes, got {}", KEY_LEN, key.len());
+        }
+        let key = UnboundKey::new(&AES_256_GCM, key).map_err(|_| anyhow!("invalid persistence key"))?;
+        Ok(Self {
+            key: LessSafeKey::new(key),
+            rng: SystemRandom::new(),
+        })
+    }
+
+    /// 按 `MEMORY_PERSIST_KEY`、`MEMORY_PERSIST_KEY_FILE`、`MEMORY_PERSIST_KEY_COMMAND` 的顺序读取密钥，均未配置时不加密
+    pub fn from_config(config: &Config) -> Result<Option<Self>> {
+        let encoded = if let Some(key) = &config.memory_persist_key {
+            key.clone()
+        } else if let Some(path) = &config.memory_persist_key_file {
+            std::fs::read_to_string(path)
+                .with_context(|| format!("failed to read persistence key file {}", path))?
+        } else if let Some(command) = &config.memory_persist_key_command {
+            run_key_command(command)?
+        } else {
+            return Ok(None);
+        };
+        let key = STANDARD
+            .decode(encoded.trim())
+            .context("persistence key must be base64 encoded")?;
+        Self::new(&key).map(Some)
+    }
+
+    /// 加密快照
+    pub fn seal(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let mut nonce = [0u8; NONCE_LEN];
+        self.rng
+            .fill(&mut nonce)
+            .map_err(|_| anyhow!("failed to generate nonce"))?;
+
+        let mut bytes = Vec::with_capacity(MAGIC.len() + 1 + NONCE_LEN + plaintext.len() + 16);
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+        bytes.extend_from_slice(&nonce);
+        let mut in_out = plaintext.to_vec();
+        self.key
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(&bytes[..MAGIC.len() + 1]),
+                &mut in_out,
+            )
+            .map_err(|_| anyhow!("failed to encrypt snapshot"))?;
+        bytes.extend_from_slice(&in_out);
+        Ok(bytes)
+    }
+
+    /// 解密并校验快照
+    pub fn open(&self, bytes: &[u8]) -> Result<Vec<u8>> {
+        let header_len = MAGIC.len() + 1;
+        if bytes.len() < header_len + NONCE_LEN {
+            return Err(SealError::Integrity.into());
+        }
+        if bytes[MAGIC.len()] != VERSION {
+            bail!("unsupported encrypted snapshot version {}", bytes[MAGIC.len()]);
+        }
+        let nonce = Nonce::try_assume_unique_for_key(&bytes[header_len..header_len + NONCE_LEN])
+            .map_err(|_| SealError::Integrity)?;
+        let mut in_out = bytes[header_len + NONCE_LEN..].to_vec();
+        let plaintext = self
+            .key
+            .open_in_place(nonce, Aad::from(&bytes[..header_len]), &mut in_out)
+            .map_err(|_| SealError::Integrity)?;
+        Ok(plaintext.to_vec())
+    }
+}
+
+/// 是否为加密的持久化文件
+pub fn is_sealed(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+/// 执行密钥命令（如调用 KMS 解密数据密钥），标准输出为 base64 编码的密钥
+fn run_key_command(command: &str) -> Result<String> {
+    let output = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .output()
+        .context("failed to run persistence key command")?;
+    if !output.status.success() {
+        bail!(
+            "persistence key command exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    String::from_utf8(output.stdout).context("persistence key command output is not UTF-8")
+}
@@ -0,0 +1,452 @@
+//! 存储故障转移
+//!
+//! 包装 Redis 存储，连接中断、超时等暂时性错误按 `STORAGE_RETRIES` 重试，第 n 次重试前随机等待不超过 `STORAGE_RETRY_BACKOFF_MS * 2^n` 毫秒。
+//! 重试后仍失败时计入断路器：连续 `STORAGE_FAILURE_THRESHOLD` 次操作失败后断路器打开，之后的操作不再访问 Redis，
+//! 按 `STORAGE_FAILOVER` 立即失败（接口返回 HTTP 503，错误码 7001）或转到本地内存存储继续服务（降级模式）。
+//! 每隔 `STORAGE_RETRY_INTERVAL` 秒探测一次 Redis，恢复后关闭断路器，并将降级期间在本地获取的锁写回 Redis。
+//!
+//! 降级模式下各实例各自在本地加锁，互斥只在单个实例内成立；故障前获取的锁在本地不可见，其心跳和释放会失败，
+//! 到期后由 Redis 自动过期。写回时 Redis 中已有未过期的锁以 Redis 为准。命名空间策略在 Redis 正常时定期同步到本地，
+//! 降级期间只读，修改会失败。
+
+use crate::clock::{self, Clock};
+use crate::config::{Config, FailoverMode};
+use crate::lockops::OpError;
+use crate::models::{accepts_problem_json, ApiResponse, LockCondition, LockError, LockInfo, LockStats, NamespacePolicy, PreemptionNotice, RateLimitDecision, TokenBucket};
+use crate::storage::memory::MemoryStorage;
+use crate::storage::{Acquire, LockStorage, Takeover};
+use crate::v2;
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{self, HeaderValue};
+use actix_web::http::StatusCode;
+use actix_web::middleware::Next;
+use actix_web::{web, Error};
+use anyhow::Result;
+use async_trait::async_trait;
+use rand::Rng;
+use redis::{ErrorKind, RedisError};
+use serde::Serialize;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 降级模式下附加到响应的请求头
+pub const DEGRADED_HEADER: &str = "x-storage-degraded";
+
+/// 断路器打开且未启用降级时存储操作返回的错误
+#[derive(Debug, thiserror::Error)]
+#[error("storage unavailable (circuit breaker open)")]
+pub struct StorageUnavailable;
+
+type StorageFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T>> + Send + 'a>>;
+
+/// 存储的健康状态，用于 `/readyz` 和 `/metrics`
+#[derive(Debug, Serialize)]
+pub struct StorageHealth {
+    pub circuit_open: bool,
+    pub degraded: bool,
+    pub consecutive_failures: u32,
+    pub retries_total: u64,
+    pub failures_total: u64,
+    pub circuit_opened_total: u64,
+}
+
+pub struct FailoverStorage {
+    primary: Arc<dyn LockStorage>,
+    fallback: Option<MemoryStorage>, // 降级模式使用的本地存储，None 表示立即失败
+    breaker: bool,                   // 是否启用断路器
+    failure_threshold: u32,
+    retries: u32,
+    retry_backoff_ms: u64,
+    failures: AtomicU32, // 连续失败次数
+    open: AtomicBool,
+    retries_total: AtomicU64,
+    failures_total: AtomicU64,
+    circuit_opened_total: AtomicU64,
+    clock: Arc<dyn Clock>,
+}
+
+impl FailoverStorage {
+    pub fn new(primary: Arc<dyn LockStorage>, config: &Config) -> Self {
+        Self {
+            primary,
+            fallback: (config.storage_failover == FailoverMode::Memory).then(MemoryStorage::new),
+            breaker: config.storage_failover != FailoverMode::Off,
+            failure_threshold: config.storage_failure_threshold.max(1),
+            retries: config.storage_retries,
+            retry_backoff_ms: config.storage_retry_backoff_ms,
+            failures: AtomicU32::new(0),
+            open: AtomicBool::new(false),
+            retries_total: AtomicU64::new(0),
+            failures_total: AtomicU64::new(0),
+            circuit_opened_total: AtomicU64::new(0),
+            clock: clock::system(),
+        }
+    }
+
+    /// 使用指定的时钟判断降级期间的锁是否过期，默认为系统时钟
+    pub fn with_clock(self, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            fallback: self.fallback.map(|fallback| fallback.with_clock(clock.clone())),
+            clock,
+            ..self
+        }
+    }
+
+    pub fn health(&self) -> StorageHealth {
+        StorageHealth {
+            circuit_open: self.is_open(),
+            degraded: self.is_degraded(),
+            consecutive_failures: self.failures.load(Ordering::Acquire),
+            retries_total: self.retries_total.load(Ordering::Relaxed),
+            failures_total: self.failures_total.load(Ordering::Relaxed),
+            circuit_opened_total: self.circuit_opened_total.load(Ordering::Relaxed),
+        }
+    }
+
+    /// 断路器是否打开
+    pub fn is_open(&self) -> bool {
+        self.open.load(Ordering::Acquire)
+    }
+
+    /// 是否处于降级模式（断路器打开且由本地存储服务）
+    pub fn is_degraded(&self) -> bool {
+        self.is_open() && self.fallback.is_some()
+    }
+
+    fn record<T>(&self, result: &Result<T>) {
+        match result {
+            Ok(_) => self.failures.store(0, Ordering::Release),
+            Err(e) => {
+                self.failures_total.fetch_add(1, Ordering::Relaxed);
+                let failures = self.failures.fetch_add(1, Ordering::AcqRel) + 1;
+                if self.breaker
+                    && failures >= self.failure_threshold
+                    && !self.open.swap(true, Ordering::AcqRel)
+                {
+                    self.circuit_opened_total.fetch_add(1, Ordering::Relaxed);
+                    if self.fallback.is_some() {
+                        log::warn!(
+                            "[FAILOVER] Storage failed {} times in a row, entering DEGRADED mode with local memory storage - mutual exclusion only holds within this instance: {}",
+                            failures,
+                            e
+                        );
+                    } else {
+                        log::warn!(
+                            "[FAILOVER] Storage failed {} times in a row, circuit breaker open - failing requests fast: {}",
+                            failures,
+                            e
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// 访问主存储，暂时性错误按配置重试
+    async fn call_primary<'a, T>(&'a self, op: impl Fn(&'a dyn LockStorage) -> StorageFuture<'a, T>) -> Result<T> {
+        let mut attempt = 0;
+        loop {
+            let result = op(self.primary.as_ref()).await;
+            match &result {
+                Err(e) if attempt < self.retries && is_transient(e) => {
+                    self.retries_total.fetch_add(1, Ordering::Relaxed);
+                    let max_delay = self.retry_backoff_ms.saturating_mul(1 << attempt.min(16));
+                    let delay = rand::thread_rng().gen_range(0..=max_delay);
+                    log::debug!("[FAILOVER] Transient storage error, retrying in {}ms: {}", delay, e);
+                    tokio::time::sleep(Duration::from_millis(delay)).await;
+                    attempt += 1;
+                }
+                _ => {
+                    self.record(&result);
+                    return result;
+                }
+            }
+        }
+    }
+
+    /// 断路器关闭时访问主存储，打开时转到本地存储或立即失败
+    async fn route<'a, T>(&'a self, op: impl Fn(&'a dyn LockStorage) -> StorageFuture<'a, T>) -> Result<T> {
+        if self.is_open() {
+            return match &self.fallback {
+                Some(fallback) => op(fallback).await,
+                None => Err(StorageUnavailable.into()),
+            };
+        }
+        self.call_primary(op).await
+    }
+
+    /// 修改命名空间策略和分配序列值只访问主存储，降级期间失败
+    async fn route_primary<'a, T>(&'a self, op: impl Fn(&'a dyn LockStorage) -> StorageFuture<'a, T>) -> Result<T> {
+        if self.is_open() {
+            return Err(StorageUnavailable.into());
+        }
+        self.call_primary(op).await
+    }
+
+    /// 定时探测主存储：断路器关闭时同步命名空间策略到本地，打开时探测主存储是否恢复
+    pub async fn probe(&self) {
+        if !self.is_open() {
+            let result = self.primary.list_namespaces().await;
+            self.record(&result);
+            if let (Ok(namespaces), Some(fallback)) = (result, &self.fallback) {
+                if let Err(e) = mirror_namespaces(fallback, namespaces).await {
+                    log::warn!("[FAILOVER] Failed to mirror namespace policies: {}", e);
+                }
+            }
+            return;
+        }
+
+        if let Err(e) = self.primary.get_lock("").await {
+            log::warn!("[FAILOVER] Storage still unavailable: {}", e);
+            return;
+        }
+        self.failures.store(0, Ordering::Release);
+        self.open.store(false, Ordering::Release);
+        log::info!("[FAILOVER] Storage recovered, circuit breaker closed");
+        if let Some(fallback) = &self.fallback {
+            self.resync(fallback).await;
+        }
+    }
+
+    /// 将降级期间本地获取的锁写回主存储并从本地移除
+    async fn resync(&self, fallback: &MemoryStorage) {
+        let locks = match fallback.list_locks().await {
+            Ok(locks) => locks,
+            Err(e) => {
+                log::error!("[FAILOVER] Failed to list degraded-mode locks: {}", e);
+                return;
+            }
+        };
+
+        let (mut synced, mut conflicts) = (0, 0);
+        for lock_info in locks {
+            if !lock_info.is_expired_at(self.clock.now()) {
+                match self.primary.restore(lock_info.clone()).await {
+                    Ok(true) => synced += 1,
+                    Ok(false) => {
+                        conflicts += 1;
+                        log::warn!(
+                            "[FAILOVER] Degraded-mode lock conflicts with storage, keeping the stored lock - lock_key: {}, user_id: {}",
+                            lock_info.get_lock_key(),
+                            lock_info.user_id
+                        );
+                    }
+                    Err(e) => {
+                        log::error!("[FAILOVER] Failed to re-sync lock {}: {}", lock_info.lock_id, e);
+                        continue;
+                    }
+                }
+            }
+            let _ = fallback.release(&lock_info.lock_id).await;
+        }
+        log::info!(
+            "[FAILOVER] Re-synced degraded-mode locks - synced: {}, conflicts: {}",
+            synced,
+            conflicts
+        );
+    }
+}
+
+/// 连接中断、超时、Redis 正在加载数据或主从切换等可重试的错误
+fn is_transient(error: &anyhow::Error) -> bool {
+    error.downcast_ref::<RedisError>().is_some_and(|e| {
+        e.is_io_error()
+            || matches!(
+                e.kind(),
+                ErrorKind::BusyLoadingError
+                    | ErrorKind::TryAgain
+                    | ErrorKind::MasterDown
+                    | ErrorKind::ReadOnly
+                    | ErrorKind::ClusterDown
+            )
+    })
+}
+
+/// 以主存储的命名空间策略覆盖本地的策略
+async fn mirror_namespaces(fallback: &MemoryStorage, namespaces: Vec<NamespacePolicy>) -> Result<()> {
+    for policy in fallback.list_namespaces().await? {
+        if !namespaces.iter().any(|other| other.name == policy.name) {
+            fallback.delete_namespace(&policy.name).await?;
+        }
+    }
+    for policy in namespaces {
+        fallback.put_namespace(policy).await?;
+    }
+    Ok(())
+}
+
+/// 断路器打开且未启用降级时，接口直接返回 HTTP 503；降级模式下在响应中附加 `X-Storage-Degraded: true`
+pub async fn storage_guard(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    // Swagger UI 不访问存储
+    let failover = req
+        .app_data::<web::Data<Arc<FailoverStorage>>>()
+        .filter(|_| !req.path().starts_with("/api/swagger-ui"))
+        .cloned();
+    let Some(failover) = failover.filter(|failover| failover.is_open()) else {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    };
+
+    if failover.is_degraded() {
+        let mut response = next.call(req).await?.map_into_boxed_body();
+        response
+            .headers_mut()
+            .insert(header::HeaderName::from_static(DEGRADED_HEADER), HeaderValue::from_static("true"));
+        return Ok(response);
+    }
+
+    let retry_after = req
+        .app_data::<web::Data<Config>>()
+        .map_or(1, |config| config.storage_retry_interval);
+    let error = ApiResponse::<()>::error(LockError::StorageUnavailable, "Storage unavailable, retry later".to_string());
+    let mut response = if v2::is_v2(req.request()) {
+        v2::error_response(req.request(), OpError::new(error.code, error.message))
+    } else if accepts_problem_json(req.request()) {
+        error.into_problem_response(req.request())
+    } else {
+        error.respond_with_status(req.request(), StatusCode::SERVICE_UNAVAILABLE)
+    };
+    response
+        .headers_mut()
+        .insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+    Ok(req.into_response(response))
+}
+
+#[async_trait]
+impl LockStorage for FailoverStorage {
+    async fn try_acquire(&self, lock_info: LockInfo) -> Result<Acquire> {
+        self.route(|storage| storage.try_acquire(lock_info.clone())).await
+    }
+
+    async fn takeover(&self, lock_info: LockInfo, condition: &LockCondition) -> Result<Takeover> {
+        self.route(|storage| storage.takeover(lock_info.clone(), condition)).await
+    }
+
+    async fn restore(&self, lock_info: LockInfo) -> Result<bool> {
+        self.route(|storage| storage.restore(lock_info.clone())).await
+    }
+
+    async fn get_lock(&self, lock_key: &str) -> Result<Option<LockInfo>> {
+        self.route(|storage| storage.get_lock(lock_key)).await
+    }
+
+    async fn update_heartbeat(&self, lock_id: &str) -> Result<Option<LockInfo>> {
+        self.route(|storage| storage.update_heartbeat(lock_id)).await
+    }
+
+    async fn update_heartbeat_version(&self, lock_id: &str, version: u64) -> Result<Option<LockInfo>> {
+        self.route(|storage| storage.update_heartbeat_version(lock_id, version)).await
+    }
+
+    async fn release(&self, lock_id: &str) -> Result<Option<LockInfo>> {
+        self.route(|storage| storage.release(lock_id)).await
+    }
+
+    async fn release_version(&self, lock_id: &str, version: u64) -> Result<Option<LockInfo>> {
+        self.route(|storage| storage.release_version(lock_id, version)).await
+    }
+
+    async fn release_owned(&self, lock_key: &str, user_id: &str) -> Result<Option<LockInfo>> {
+        self.route(|storage| storage.release_owned(lock_key, user_id)).await
+    }
+
+    async fn cleanup_expired(&self) -> Result<Vec<LockInfo>> {
+        self.route(|storage| storage.cleanup_expired()).await
+    }
+
+    async fn list_locks(&self) -> Result<Vec<LockInfo>> {
+        self.route(|storage| storage.list_locks()).await
+    }
+
+    async fn list_prefix(&self, key_prefix: &str) -> Result<Vec<LockInfo>> {
+        self.route(|storage| storage.list_prefix(key_prefix)).await
+    }
+
+    async fn get_lock_by_id(&self, lock_id: &str) -> Result<Option<LockInfo>> {
+        self.route(|storage| storage.get_lock_by_id(lock_id)).await
+    }
+
+    async fn force_release(&self, lock_key: &str) -> Result<Option<LockInfo>> {
+        self.route(|storage| storage.force_release(lock_key)).await
+    }
+
+    async fn mark_preempted(
+        &self,
+        lock_id: &str,
+        notice: PreemptionNotice,
+    ) -> Result<Option<LockInfo>> {
+        self.route(|storage| storage.mark_preempted(lock_id, notice.clone())).await
+    }
+
+    async fn transfer(&self, lock_id: &str, new_lock: LockInfo) -> Result<Option<LockInfo>> {
+        self.route(|storage| storage.transfer(lock_id, new_lock.clone())).await
+    }
+
+    async fn rewrite_lock(&self, lock_info: LockInfo) -> Result<bool> {
+        self.route(|storage| storage.rewrite_lock(lock_info.clone())).await
+    }
+
+    async fn list_by_user(&self, user_id: &str) -> Result<Vec<LockInfo>> {
+        self.route(|storage| storage.list_by_user(user_id)).await
+    }
+
+    async fn list_by_business_id(&self, business_id: &str) -> Result<Vec<LockInfo>> {
+        self.route(|storage| storage.list_by_business_id(business_id)).await
+    }
+
+    async fn release_all_by_user(&self, user_id: &str) -> Result<Vec<LockInfo>> {
+        self.route(|storage| storage.release_all_by_user(user_id)).await
+    }
+
+    async fn count_locks(&self, namespace: &str) -> Result<u64> {
+        self.route(|storage| storage.count_locks(namespace)).await
+    }
+
+    async fn stats(&self, top_n: usize) -> Result<LockStats> {
+        self.route(|storage| storage.stats(top_n)).await
+    }
+
+    async fn list_namespaces(&self) -> Result<Vec<NamespacePolicy>> {
+        self.route(|storage| storage.list_namespaces()).await
+    }
+
+    async fn get_namespace(&self, name: &str) -> Result<Option<NamespacePolicy>> {
+        self.route(|storage| storage.get_namespace(name)).await
+    }
+
+    async fn put_namespace(&self, policy: NamespacePolicy) -> Result<()> {
+        self.route_primary(|storage| storage.put_namespace(policy.clone())).await
+    }
+
+    async fn delete_namespace(&self, name: &str) -> Result<bool> {
+        self.route_primary(|storage| storage.delete_namespace(name)).await
+    }
+
+    // 本地存储的序列与主存储不连续，降级期间分配会产生重复的值
+    async fn next_sequence(&self, name: &str, count: u64) -> Result<u64> {
+        self.route_primary(|storage| storage.next_sequence(name, count)).await
+    }
+
+    // 降级期间由本地存储限流，各实例分别计数
+    async fn take_tokens(&self, key: &str, bucket: TokenBucket, cost: u64) -> Result<RateLimitDecision> {
+        self.route(|storage| storage.take_tokens(key, bucket, cost)).await
+    }
+
+    async fn get_record(&self, kind: &str, id: &str) -> Result<Option<String>> {
+        self.route(|storage| storage.get_record(kind, id)).await
+    }
+
+    async fn list_records(&self, kind: &str) -> Result<Vec<(String, String)>> {
+        self.route(|storage| storage.list_records(kind)).await
+    }
+
+    async fn swap_record(&self, kind: &str, id: &str, expected: Option<&str>, value: Option<&str>) -> Result<bool> {
+        self.route(|storage| storage.swap_record(kind, id, expected, value)).await
+    }
+}
@@ -0,0 +1,465 @@
+//! 锁信息敏感字段加密
+//!
+//! 配置 `STORAGE_FIELD_KEYS` 后，锁的 `user_name`、`metadata` 的值和抢占人的 `user_name` 在写入存储前使用 AES-256-GCM 加密，
+//! 读取时解密，Redis、Raft 日志、副本和持久化文件中都不再保存明文。锁历史的 `user_name`，以及 Redis 审计 Stream 中
+//! 事件的锁信息和接管请求双方的 `user_name` 同样加密保存，预约等记录整体加密。其他字段（user_id、business_id 等）用于索引和匹配，不加密。
+//!
+//! 密文格式为 `enc:v1:<key_id>:<base64(nonce + 密文 + 认证标签)>`，密钥 ID 和字段名作为附加认证数据。
+//! 列表中的第一个密钥用于加密，其余密钥只用于解密已有的密文。没有密文前缀的值按明文读取，已有的锁在启用加密后仍可读取。
+//! 轮换时将新密钥放在最前面，`EncryptedStorage::rewrap` 定时将明文和旧密钥加密的锁和记录重新以新密钥加密，
+//! 完成后旧密钥即可移除；历史和审计记录不改写，旧密钥移除后无法解密的记录保留密文。
+
+use crate::config::Config;
+use crate::events::LockEvent;
+use crate::models::{
+    LockCondition, LockInfo, LockStats, NamespacePolicy, PreemptionNotice, RateLimitDecision, TokenBucket,
+};
+use crate::storage::{Acquire, LockStorage, Takeover, RECORD_KINDS};
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use std::sync::Arc;
+
+/// 密文前缀
+const PREFIX: &str = "enc:v1:";
+/// 密钥长度（字节）
+const KEY_LEN: usize = 32;
+
+pub struct FieldCipher {
+    keys: Vec<(String, LessSafeKey)>, // 第一个密钥用于加密
+    rng: SystemRandom,
+}
+
+impl FieldCipher {
+    /// keys 为（密钥 ID，32 字节原始密钥），第一个用于加密
+    pub fn new(keys: Vec<(String, Vec<u8>)>) -> Result<Self> {
+        if keys.is_empty() {
+            bail!("at least one field encryption key is required");
+        }
+        let keys = keys
+            .into_iter()
+            .map(|(key_id, key)| {
+                if key_id.is_empty() || key_id.contains(':') {
+                    bail!("invalid field encryption key id {:?}", key_id);
+                }
+                if key.len() != KEY_LEN {
+                    bail!("field encryption key {} must be {} bytes, got {}", key_id, KEY_LEN, key.len());
+                }
+                let key = UnboundKey::new(&AES_256_GCM, &key)
+                    .map_err(|_| anyhow!("invalid field encryption key {}", key_id))?;
+                Ok((key_id, LessSafeKey::new(key)))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            keys,
+            rng: SystemRandom::new(),
+        })
+    }
+
+    /// 按 `STORAGE_FIELD_KEYS`、`STORAGE_FIELD_KEYS_FILE` 的顺序读取密钥（`key_id:base64密钥`，逗号或换行分隔），均未配置时不加密
+    pub fn from_config(config: &Config) -> Result<Option<Self>> {
+        let value = if let Some(keys) = &config.storage_field_keys {
+            keys.clone()
+        } else if let Some(path) = &config.storage_field_keys_file {
+            std::fs::read_to_string(path)
+                .with_context(|| format!("failed to read field encryption key file {}", path))?
+        } else {
+            return Ok(None);
+        };
+        let keys = value
+            .split([',', '\n'])
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                let (key_id, encoded) = entry
+                    .split_once(':')
+                    .with_context(|| format!("field encryption key must be key_id:base64, got {:?}", entry))?;
+                let key = STANDARD
+                    .decode(encoded.trim())
+                    .with_context(|| format!("field encryption key {} must be base64 encoded", key_id))?;
+                Ok((key_id.trim().to_string(), key))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Self::new(keys).map(Some)
+    }
+
+    /// 加密用的密钥 ID
+    pub fn primary_key_id(&self) -> &str {
+        &self.keys[0].0
+    }
+
+    /// 值已用主密钥加密，明文和旧密钥的密文需要重新加密
+    fn is_current(&self, value: &str) -> bool {
+        value
+            .strip_prefix(PREFIX)
+            .and_then(|sealed| sealed.split_once(':'))
+            .is_some_and(|(key_id, _)| key_id == self.primary_key_id())
+    }
+
+    fn aad(key_id: &str, field: &str) -> Vec<u8> {
+        [key_id.as_bytes(), b"\0", field.as_bytes()].concat()
+    }
+
+    /// 以主密钥加密字段值
+    pub fn encrypt(&self, field: &str, plaintext: &str) -> Result<String> {
+        let (key_id, key) = &self.keys[0];
+        let mut nonce = [0u8; NONCE_LEN];
+        self.rng
+            .fill(&mut nonce)
+            .map_err(|_| anyhow!("failed to generate nonce"))?;
+        let mut in_out = plaintext.as_bytes().to_vec();
+        key.seal_in_place_append_tag(
+            Nonce::assume_unique_for_key(nonce),
+            Aad::from(Self::aad(key_id, field)),
+            &mut in_out,
+        )
+        .map_err(|_| anyhow!("failed to encrypt {}", field))?;
+        let mut bytes = nonce.to_vec();
+        bytes.extend_from_slice(&in_out);
+        Ok(format!("{}{}:{}", PREFIX, key_id, URL_SAFE_NO_PAD.encode(bytes)))
+    }
+
+    /// 解密字段值，没有密文前缀的值原样返回
+    pub fn decrypt(&self, field: &str, value: &str) -> Result<String> {
+        let Some(sealed) = value.strip_prefix(PREFIX) else {
+            return Ok(value.to_string());
+        };
+        let (key_id, encoded) = sealed
+            .split_once(':')
+            .with_context(|| format!("malformed encrypted {}", field))?;
+        let key = self
+            .keys
+            .iter()
+            .find(|(id, _)| id == key_id)
+            .map(|(_, key)| key)
+            .with_context(|| format!("{} is encrypted with unknown key {}", field, key_id))?;
+        let bytes = URL_SAFE_NO_PAD
+            .decode(encoded)
+            .with_context(|| format!("malformed encrypted {}", field))?;
+        if bytes.len() < NONCE_LEN {
+            bail!("malformed encrypted {}", field);
+        }
+        let nonce = Nonce::try_assume_unique_for_key(&bytes[..NONCE_LEN])
+            .map_err(|_| anyhow!("malformed encrypted {}", field))?;
+        let mut in_out = bytes[NONCE_LEN..].to_vec();
+        let plaintext = key
+            .open_in_place(nonce, Aad::from(Self::aad(key_id, field)), &mut in_out)
+            .map_err(|_| anyhow!("failed to decrypt {} with key {} (wrong key or corrupted value)", field, key_id))?;
+        String::from_utf8(plaintext.to_vec()).with_context(|| format!("decrypted {} is not UTF-8", field))
+    }
+
+    pub fn seal_lock(&self, mut lock_info: LockInfo) -> Result<LockInfo> {
+        lock_info.user_name = self.encrypt("user_name", &lock_info.user_name)?;
+        for (key, value) in lock_info.metadata.iter_mut() {
+            *value = self.encrypt(&format!("metadata.{}", key), value)?;
+        }
+        if let Some(notice) = lock_info.preemption.take() {
+            lock_info.preemption = Some(self.seal_notice(notice)?);
+        }
+        Ok(lock_info)
+    }
+
+    pub fn open_lock(&self, mut lock_info: LockInfo) -> Result<LockInfo> {
+        lock_info.user_name = self.decrypt("user_name", &lock_info.user_name)?;
+        for (key, value) in lock_info.metadata.iter_mut() {
+            *value = self.decrypt(&format!("metadata.{}", key), value)?;
+        }
+        if let Some(mut notice) = lock_info.preemption.take() {
+            notice.user_name = self.decrypt("preemption.user_name", &notice.user_name)?;
+            lock_info.preemption = Some(notice);
+        }
+        Ok(lock_info)
+    }
+
+    fn seal_notice(&self, mut notice: PreemptionNotice) -> Result<PreemptionNotice> {
+        notice.user_name = self.encrypt("preemption.user_name", &notice.user_name)?;
+        Ok(notice)
+    }
+
+    /// 锁中有明文或旧密钥加密的字段
+    fn needs_rewrap(&self, lock_info: &LockInfo) -> bool {
+        !self.is_current(&lock_info.user_name)
+            || lock_info.metadata.values().any(|value| !self.is_current(value))
+            || lock_info
+                .preemption
+                .as_ref()
+                .is_some_and(|notice| !self.is_current(&notice.user_name))
+    }
+
+    /// 加密事件中的锁信息和接管请求双方的 user_name
+    pub fn seal_event(&self, event: &LockEvent) -> Result<LockEvent> {
+        let mut event = event.clone();
+        event.lock = self.seal_lock(event.lock)?;
+        if let Some(takeover) = event.takeover.as_mut() {
+            takeover.holder_user_name = self.encrypt("takeover.holder_user_name", &takeover.holder_user_name)?;
+            takeover.user_name = self.encrypt("takeover.user_name", &takeover.user_name)?;
+        }
+        Ok(event)
+    }
+
+    /// 解密 JSON 形式的事件中由 seal_event 加密的字段
+    pub fn open_event(&self, event: &mut serde_json::Value) -> Result<()> {
+        let open = |field: &str, value: Option<&mut serde_json::Value>| -> Result<()> {
+            if let Some(value) = value {
+                if let Some(sealed) = value.as_str() {
+                    *value = serde_json::Value::String(self.decrypt(field, sealed)?);
+                }
+            }
+            Ok(())
+        };
+        if let Some(lock) = event.get_mut("lock") {
+            open("user_name", lock.get_mut("user_name"))?;
+            if let Some(metadata) = lock.get_mut("metadata").and_then(|metadata| metadata.as_object_mut()) {
+                for (key, value) in metadata.iter_mut() {
+                    open(&format!("metadata.{}", key), Some(value))?;
+                }
+            }
+            if let Some(notice) = lock.get_mut("preemption") {
+                open("preemption.user_name", notice.get_mut("user_name"))?;
+            }
+        }
+        if let Some(takeover) = event.get_mut("takeover") {
+            open("takeover.holder_user_name", takeover.get_mut("holder_user_name"))?;
+            open("takeover.user_name", takeover.get_mut("user_name"))?;
+        }
+        Ok(())
+    }
+}
+
+/// 写入前加密、读取后解密敏感字段的存储
+pub struct EncryptedStorage {
+    inner: Arc<dyn LockStorage>,
+    cipher: Arc<FieldCipher>,
+}
+
+impl EncryptedStorage {
+    pub fn new(inner: Arc<dyn LockStorage>, cipher: Arc<FieldCipher>) -> Self {
+        Self { inner, cipher }
+    }
+
+    /// 解密直接从内层存储读到的锁
+    pub fn open_lock(&self, lock_info: LockInfo) -> Result<LockInfo> {
+        self.cipher.open_lock(lock_info)
+    }
+
+    /// 将明文和旧密钥加密的锁和记录以主密钥重新加密（锁的版本号不变），返回改写的数量。
+    /// 改写期间锁被续期或释放时跳过，下一轮再处理；无法解密的锁记录日志后跳过
+    pub async fn rewrap(&self) -> Result<usize> {
+        let mut rewrapped = 0;
+        for lock_info in self.inner.list_locks().await? {
+            if !self.cipher.needs_rewrap(&lock_info) {
+                continue;
+            }
+            let lock_id = lock_info.lock_id.clone();
+            let sealed = match self.cipher.open_lock(lock_info).and_then(|lock_info| self.cipher.seal_lock(lock_info)) {
+                Ok(sealed) => sealed,
+                Err(e) => {
+                    log::warn!("[FIELDCRYPT] Failed to rewrap lock {}: {}", lock_id, e);
+                    continue;
+                }
+            };
+            if self.inner.rewrite_lock(sealed).await? {
+                rewrapped += 1;
+            }
+        }
+        for kind in RECORD_KINDS {
+            let field = format!("record.{}", kind);
+            for (id, value) in self.inner.list_records(kind).await? {
+                if self.cipher.is_current(&value) {
+                    continue;
+                }
+                let sealed = match self
+                    .cipher
+                    .decrypt(&field, &value)
+                    .and_then(|opened| self.cipher.encrypt(&field, &opened))
+                {
+                    Ok(sealed) => sealed,
+                    Err(e) => {
+                        log::warn!("[FIELDCRYPT] Failed to rewrap {} {}: {}", kind, id, e);
+                        continue;
+                    }
+                };
+                if self.inner.swap_record(kind, &id, Some(&value), Some(&sealed)).await? {
+                    rewrapped += 1;
+                }
+            }
+        }
+        Ok(rewrapped)
+    }
+
+    fn open(&self, lock_info: Option<LockInfo>) -> Result<Option<LockInfo>> {
+        lock_info.map(|lock_info| self.cipher.open_lock(lock_info)).transpose()
+    }
+
+    fn open_all(&self, locks: Vec<LockInfo>) -> Result<Vec<LockInfo>> {
+        locks.into_iter().map(|lock_info| self.cipher.open_lock(lock_info)).collect()
+    }
+}
+
+#[async_trait]
+impl LockStorage for EncryptedStorage {
+    async fn try_acquire(&self, lock_info: LockInfo) -> Result<Acquire> {
+        let acquired = self.inner.try_acquire(self.cipher.seal_lock(lock_info)?).await?;
+        Ok(Acquire {
+            acquired: acquired.acquired,
+            replaced: acquired
+                .replaced
+                .map(|replaced| self.cipher.open_lock(*replaced).map(Box::new))
+                .transpose()?,
+        })
+    }
+
+    async fn takeover(&self, lock_info: LockInfo, condition: &LockCondition) -> Result<Takeover> {
+        match self.inner.takeover(self.cipher.seal_lock(lock_info)?, condition).await? {
+            Takeover::Acquired { lock_info, replaced } => Ok(Takeover::Acquired {
+                lock_info: self.cipher.open_lock(lock_info)?,
+                replaced: replaced
+                    .map(|replaced| self.cipher.open_lock(*replaced).map(Box::new))
+                    .transpose()?,
+            }),
+            Takeover::Rejected(current) => Ok(Takeover::Rejected(self.cipher.open_lock(current)?)),
+        }
+    }
+
+    async fn restore(&self, lock_info: LockInfo) -> Result<bool> {
+        self.inner.restore(self.cipher.seal_lock(lock_info)?).await
+    }
+
+    async fn get_lock(&self, lock_key: &str) -> Result<Option<LockInfo>> {
+        self.open(self.inner.get_lock(lock_key).await?)
+    }
+
+    async fn update_heartbeat(&self, lock_id: &str) -> Result<Option<LockInfo>> {
+        self.open(self.inner.update_heartbeat(lock_id).await?)
+    }
+
+    async fn update_heartbeat_version(&self, lock_id: &str, version: u64) -> Result<Option<LockInfo>> {
+        self.open(self.inner.update_heartbeat_version(lock_id, version).await?)
+    }
+
+    async fn release(&self, lock_id: &str) -> Result<Option<LockInfo>> {
+        self.open(self.inner.release(lock_id).await?)
+    }
+
+    async fn release_version(&self, lock_id: &str, version: u64) -> Result<Option<LockInfo>> {
+        self.open(self.inner.release_version(lock_id, version).await?)
+    }
+
+    async fn release_owned(&self, lock_key: &str, user_id: &str) -> Result<Option<LockInfo>> {
+        self.open(self.inner.release_owned(lock_key, user_id).await?)
+    }
+
+    async fn cleanup_expired(&self) -> Result<Vec<LockInfo>> {
+        self.open_all(self.inner.cleanup_expired().await?)
+    }
+
+    async fn list_locks(&self) -> Result<Vec<LockInfo>> {
+        self.open_all(self.inner.list_locks().await?)
+    }
+
+    async fn list_prefix(&self, key_prefix: &str) -> Result<Vec<LockInfo>> {
+        self.open_all(self.inner.list_prefix(key_prefix).await?)
+    }
+
+    async fn get_lock_by_id(&self, lock_id: &str) -> Result<Option<LockInfo>> {
+        self.open(self.inner.get_lock_by_id(lock_id).await?)
+    }
+
+    async fn force_release(&self, lock_key: &str) -> Result<Option<LockInfo>> {
+        self.open(self.inner.force_release(lock_key).await?)
+    }
+
+    async fn mark_preempted(&self, lock_id: &str, notice: PreemptionNotice) -> Result<Option<LockInfo>> {
+        self.open(self.inner.mark_preempted(lock_id, self.cipher.seal_notice(notice)?).await?)
+    }
+
+    async fn transfer(&self, lock_id: &str, new_lock: LockInfo) -> Result<Option<LockInfo>> {
+        self.open(self.inner.transfer(lock_id, self.cipher.seal_lock(new_lock)?).await?)
+    }
+
+    async fn rewrite_lock(&self, lock_info: LockInfo) -> Result<bool> {
+        self.inner.rewrite_lock(self.cipher.seal_lock(lock_info)?).await
+    }
+
+    async fn list_by_user(&self, user_id: &str) -> Result<Vec<LockInfo>> {
+        self.open_all(self.inner.list_by_user(user_id).await?)
+    }
+
+    async fn list_by_business_id(&self, business_id: &str) -> Result<Vec<LockInfo>> {
+        self.open_all(self.inner.list_by_business_id(business_id).await?)
+    }
+
+    async fn release_all_by_user(&self, user_id: &str) -> Result<Vec<LockInfo>> {
+        self.open_all(self.inner.release_all_by_user(user_id).await?)
+    }
+
+    async fn count_locks(&self, namespace: &str) -> Result<u64> {
+        self.inner.count_locks(namespace).await
+    }
+
+    async fn stats(&self, top_n: usize) -> Result<LockStats> {
+        self.inner.stats(top_n).await
+    }
+
+    async fn list_namespaces(&self) -> Result<Vec<NamespacePolicy>> {
+        self.inner.list_namespaces().await
+    }
+
+    async fn get_namespace(&self, name: &str) -> Result<Option<NamespacePolicy>> {
+        self.inner.get_namespace(name).await
+    }
+
+    async fn put_namespace(&self, policy: NamespacePolicy) -> Result<()> {
+        self.inner.put_namespace(policy).await
+    }
+
+    async fn delete_namespace(&self, name: &str) -> Result<bool> {
+        self.inner.delete_namespace(name).await
+    }
+
+    async fn next_sequence(&self, name: &str, count: u64) -> Result<u64> {
+        self.inner.next_sequence(name, count).await
+    }
+
+    async fn take_tokens(&self, key: &str, bucket: TokenBucket, cost: u64) -> Result<RateLimitDecision> {
+        self.inner.take_tokens(key, bucket, cost).await
+    }
+
+    // 记录内容（如预约中的 user_name 和 metadata）整体加密，比较 expected 时先解密当前内容
+    async fn get_record(&self, kind: &str, id: &str) -> Result<Option<String>> {
+        self.inner
+            .get_record(kind, id)
+            .await?
+            .map(|value| self.cipher.decrypt(&format!("record.{}", kind), &value))
+            .transpose()
+    }
+
+    async fn list_records(&self, kind: &str) -> Result<Vec<(String, String)>> {
+        let field = format!("record.{}", kind);
+        self.inner
+            .list_records(kind)
+            .await?
+            .into_iter()
+            .map(|(id, value)| Ok((id, self.cipher.decrypt(&field, &value)?)))
+            .collect()
+    }
+
+    async fn swap_record(&self, kind: &str, id: &str, expected: Option<&str>, value: Option<&str>) -> Result<bool> {
+        let field = format!("record.{}", kind);
+        let current = self.inner.get_record(kind, id).await?;
+        let opened = current
+            .as_deref()
+            .map(|current| self.cipher.decrypt(&field, current))
+            .transpose()?;
+        if opened.as_deref() != expected {
+            return Ok(false);
+        }
+        let sealed = value.map(|value| self.cipher.encrypt(&field, value)).transpose()?;
+        self.inner
+            .swap_record(kind, id, current.as_deref(), sealed.as_deref())
+            .await
+    }
+}
@@ -1,17 +1,76 @@
-use crate::models::LockInfo;
-use crate::storage::LockStorage;
+use crate::clock::{self, Clock};
+use crate::config::{MemoryEviction, PersistFormat};
+use crate::models::{
+    LockCondition, LockInfo, LockStats, NamespacePolicy, PreemptionNotice, RateLimitDecision, TokenBucket,
+};
+use crate::storage::encryption::{self, SealError, SnapshotCipher};
+use crate::storage::persist::PersistTarget;
+use crate::storage::ratelimit::BucketState;
+use crate::storage::snapshot::{self, ShardManifest, SnapshotCorrupt, SnapshotData, SnapshotDelta};
+use crate::storage::stats::StatsCounters;
+use crate::storage::{Acquire, LockStorage, Takeover};
 use anyhow::Result;
 use async_trait::async_trait;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
+use dashmap::mapref::entry::Entry;
 use dashmap::DashMap;
-use std::path::PathBuf;
-use tokio::fs;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use parking_lot::Mutex;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tokio::sync::Notify;
+
+/// 分片持久化时清单在持久化目录下的名称
+const MANIFEST_NAME: &str = "manifest";
+/// 已满时为腾出空间扫描过期锁的最小间隔，期间的新增锁直接拒绝，避免每次申请都扫描全部锁
+const EVICTION_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 最近一次写入或加载的全量快照
+struct BaseSnapshot {
+    checksum: String,  // 快照文件的 SHA-256
+    since: Instant,    // 写入或加载的时间，用于判断是否需要压缩
+    deltas: u64,       // 此后写入增量快照的次数
+}
+
+/// 内存存储已达到最大锁数量
+#[derive(Debug, Error)]
+#[error("Memory storage is full ({0} locks)")]
+pub struct StorageFull(pub usize);
 
 pub struct MemoryStorage {
     locks: DashMap<String, LockInfo>, // lock_key -> LockInfo
     lock_by_id: DashMap<String, String>, // lock_id -> lock_key
-    persist_path: Option<PathBuf>,
+    locks_by_user: DashMap<String, HashSet<String>>, // user_id -> lock_key
+    locks_by_business_id: DashMap<String, HashSet<String>>, // business_id -> lock_key
+    namespaces: DashMap<String, NamespacePolicy>,
+    sequences: DashMap<String, u64>, // 序列名 -> 已分配的最后一个值
+    records: Mutex<BTreeMap<String, BTreeMap<String, String>>>, // 记录类别 -> id -> 内容
+    rate_limits: DashMap<String, BucketState>, // 限流 key -> 令牌桶，不持久化
+    persist_target: Option<PersistTarget>, // 持久化文件或对象存储位置
+    persist_format: PersistFormat,
+    cipher: Option<SnapshotCipher>, // 持久化文件的加密密钥，None 表示不加密
+    sharded: bool,                  // 是否按命名空间分片持久化
+    dirty_namespaces: Mutex<HashSet<String>>, // 分片持久化时自上次持久化以来有变更的命名空间
+    shard_targets: DashMap<String, Arc<PersistTarget>>, // 分片名 -> 分片快照的位置
+    delta_target: Option<PersistTarget>, // 增量快照的位置
+    incremental: bool,                   // 是否在两次全量快照之间只写入有变更的锁
+    compact_interval: Duration,          // 增量持久化时全量快照的间隔
+    changed_keys: Mutex<HashSet<String>>, // 增量持久化时自上次全量快照以来有变更的 lock_key
+    base: Mutex<Option<BaseSnapshot>>,   // 增量快照所基于的全量快照，None 时下次写入全量快照
+    delta_present: AtomicBool,           // 持久化位置可能存在增量快照，写入全量快照后删除
+    dirty: AtomicBool,           // 自上次持久化以来是否有变更
+    pending_changes: AtomicU64,  // 自上次持久化以来的变更次数
+    heartbeat_due: Mutex<Option<DateTime<Utc>>>, // 心跳不计入变更，持久化的锁最早需要写入新心跳的时间
+    change_threshold: u64,       // 变更次数达到该值时立即持久化，0 表示不启用
+    persist_notify: Notify,
+    stats: StatsCounters,
+    max_locks: usize, // 最大锁数量，0 表示不限制
+    eviction: MemoryEviction,
+    evicted: AtomicU64, // 为腾出空间清理的过期锁数量
+    last_eviction: Mutex<Option<Instant>>, // 最近一次为腾出空间扫描过期锁的时间
+    clock: Arc<dyn Clock>,
 }
 
 impl MemoryStorage {
@@ -19,61 +78,616 @@ impl MemoryStorage {
         Self {
             locks: DashMap::new(),
             lock_by_id: DashMap::new(),
-            persist_path: None,
+            locks_by_user: DashMap::new(),
+            locks_by_business_id: DashMap::new(),
+            namespaces: DashMap::new(),
+            sequences: DashMap::new(),
+            records: Mutex::new(BTreeMap::new()),
+            rate_limits: DashMap::new(),
+            persist_target: None,
+            persist_format: PersistFormat::Json,
+            cipher: None,
+            sharded: false,
+            dirty_namespaces: Mutex::new(HashSet::new()),
+            shard_targets: DashMap::new(),
+            delta_target: None,
+            incremental: false,
+            compact_interval: Duration::ZERO,
+            changed_keys: Mutex::new(HashSet::new()),
+            base: Mutex::new(None),
+            delta_present: AtomicBool::new(false),
+            dirty: AtomicBool::new(false),
+            pending_changes: AtomicU64::new(0),
+            heartbeat_due: Mutex::new(None),
+            change_threshold: 0,
+            persist_notify: Notify::new(),
+            stats: StatsCounters::new(),
+            max_locks: 0,
+            eviction: MemoryEviction::EvictExpired,
+            evicted: AtomicU64::new(0),
+            last_eviction: Mutex::new(None),
+            clock: clock::system(),
         }
     }
 
-    pub fn with_persistence(persist_path: PathBuf) -> Self {
+    pub fn with_persistence(
+        persist_target: PersistTarget,
+        persist_format: PersistFormat,
+        change_threshold: u64,
+    ) -> Self {
         Self {
-            locks: DashMap::new(),
-            lock_by_id: DashMap::new(),
-            persist_path: Some(persist_path),
+            delta_target: Some(persist_target.delta()),
+            persist_target: Some(persist_target),
+            persist_format,
+            change_threshold,
+            ..Self::new()
+        }
+    }
+
+    /// 限制最大锁数量，max_locks 为 0 表示不限制
+    pub fn with_max_locks(self, max_locks: usize, eviction: MemoryEviction) -> Self {
+        Self {
+            max_locks,
+            eviction,
+            ..self
+        }
+    }
+
+    /// 加密持久化文件
+    pub fn with_encryption(self, cipher: Option<SnapshotCipher>) -> Self {
+        Self { cipher, ..self }
+    }
+
+    /// 按命名空间分片持久化，持久化位置为目录（或对象存储前缀）
+    pub fn with_sharding(self, sharded: bool) -> Self {
+        Self { sharded, ..self }
+    }
+
+    /// 两次全量快照之间只写入有变更的锁，每 compact_interval 秒写入一次全量快照；分片持久化时不使用
+    pub fn with_incremental(self, incremental: bool, compact_interval: u64) -> Self {
+        Self {
+            incremental,
+            compact_interval: Duration::from_secs(compact_interval),
+            ..self
+        }
+    }
+
+    /// 使用指定的时钟判断锁是否过期，默认为系统时钟
+    pub fn with_clock(self, clock: Arc<dyn Clock>) -> Self {
+        Self { clock, ..self }
+    }
+
+    pub fn persist_enabled(&self) -> bool {
+        self.persist_target.is_some()
+    }
+
+    /// 当前的锁数量，包括已过期但尚未回收的锁
+    pub fn len(&self) -> usize {
+        self.locks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.locks.is_empty()
+    }
+
+    pub fn max_locks(&self) -> usize {
+        self.max_locks
+    }
+
+    pub fn evicted(&self) -> u64 {
+        self.evicted.load(Ordering::Relaxed)
+    }
+
+    /// 新增锁前检查容量，已满时按策略清理过期的锁或返回 StorageFull；
+    /// 清理每 [`EVICTION_INTERVAL`] 最多执行一次
+    async fn ensure_capacity(&self) -> Result<()> {
+        if self.max_locks == 0 || self.locks.len() < self.max_locks {
+            return Ok(());
+        }
+        if self.eviction == MemoryEviction::EvictExpired && self.eviction_due() {
+            let evicted = self.cleanup_expired().await?;
+            if !evicted.is_empty() {
+                log::warn!(
+                    "[CAPACITY] Memory storage full, evicted {} expired locks",
+                    evicted.len()
+                );
+                self.evicted.fetch_add(evicted.len() as u64, Ordering::Relaxed);
+            }
+            if self.locks.len() < self.max_locks {
+                return Ok(());
+            }
         }
+        log::warn!("[CAPACITY] Memory storage full ({} locks), rejecting new lock", self.max_locks);
+        Err(StorageFull(self.max_locks).into())
     }
 
-    /// 从磁盘加载数据
+    /// 距上次为腾出空间扫描已超过 [`EVICTION_INTERVAL`]，返回 true 时记为本次扫描
+    fn eviction_due(&self) -> bool {
+        let mut last_eviction = self.last_eviction.lock();
+        if last_eviction.is_some_and(|at| at.elapsed() < EVICTION_INTERVAL) {
+            return false;
+        }
+        *last_eviction = Some(Instant::now());
+        true
+    }
+
+    /// 标记数据已变更，达到阈值时通知持久化任务
+    fn mark_dirty(&self) {
+        self.dirty.store(true, Ordering::Release);
+        let changes = self.pending_changes.fetch_add(1, Ordering::AcqRel) + 1;
+        if self.change_threshold > 0 && changes == self.change_threshold {
+            self.persist_notify.notify_one();
+        }
+    }
+
+    /// 标记 namespace 的锁已变更，分片持久化时只重写有变更的命名空间
+    fn mark_namespace_dirty(&self, namespace: &str) {
+        if self.sharded {
+            self.dirty_namespaces.lock().insert(namespace.to_string());
+        }
+        self.mark_dirty();
+    }
+
+    /// 标记锁已变更，增量持久化时记录 lock_key
+    fn mark_lock_dirty(&self, lock_info: &LockInfo) {
+        if self.incremental_enabled() {
+            self.changed_keys.lock().insert(lock_info.get_lock_key());
+        }
+        self.mark_namespace_dirty(&lock_info.namespace);
+    }
+
+    /// 记录心跳，不标记数据已变更：持久化的锁的最近心跳时间落后超过有效期的一半时才需要写入，
+    /// 持有人持续心跳的实例不会每次都重写快照。previous 为心跳前的锁
+    fn mark_heartbeat(&self, previous: &LockInfo) {
+        if self.incremental_enabled() {
+            self.changed_keys.lock().insert(previous.get_lock_key());
+        }
+        if self.sharded {
+            self.dirty_namespaces.lock().insert(previous.namespace.clone());
+        }
+        let Some(deadline) = previous.expiry_deadline() else {
+            return;
+        };
+        let due = previous.last_heartbeat + (deadline - previous.last_heartbeat) / 2;
+        let mut heartbeat_due = self.heartbeat_due.lock();
+        if heartbeat_due.is_none_or(|current| due < current) {
+            *heartbeat_due = Some(due);
+        }
+    }
+
+    fn incremental_enabled(&self) -> bool {
+        self.incremental && !self.sharded
+    }
+
+    /// 更新心跳并递增版本号，version 不为 None 时要求版本号一致
+    fn heartbeat(&self, lock_id: &str, version: Option<u64>) -> Option<LockInfo> {
+        let lock_key = self.lock_by_id.get(lock_id)?.value().clone();
+        let mut lock_info = self.locks.get_mut(&lock_key)?;
+        // 已过期（含达到最长持有时间）的锁不再续期，与获取时的判断一致
+        if lock_info.lock_id != lock_id
+            || lock_info.is_expired_at(self.clock.now())
+            || version.is_some_and(|version| version != lock_info.version)
+        {
+            return None;
+        }
+        let previous = lock_info.clone();
+        lock_info.last_heartbeat = self.clock.now();
+        lock_info.version += 1;
+        let updated = lock_info.clone();
+        drop(lock_info);
+        self.mark_heartbeat(&previous);
+        Some(updated)
+    }
+
+    /// 将锁加入持有人索引和 business_id 索引
+    fn index_lock(&self, lock_info: &LockInfo) {
+        self.locks_by_user
+            .entry(lock_info.user_id.clone())
+            .or_default()
+            .insert(lock_info.get_lock_key());
+        self.locks_by_business_id
+            .entry(lock_info.business_id.clone())
+            .or_default()
+            .insert(lock_info.get_lock_key());
+    }
+
+    /// 将锁移出持有人索引和 business_id 索引
+    fn unindex_lock(&self, lock_info: &LockInfo) {
+        self.locks_by_user
+            .remove_if_mut(&lock_info.user_id, |_, lock_keys| {
+                lock_keys.remove(&lock_info.get_lock_key());
+                lock_keys.is_empty()
+            });
+        self.locks_by_business_id
+            .remove_if_mut(&lock_info.business_id, |_, lock_keys| {
+                lock_keys.remove(&lock_info.get_lock_key());
+                lock_keys.is_empty()
+            });
+    }
+
+    /// 等待变更次数达到阈值（供持久化任务使用）
+    pub async fn changes_pending(&self) {
+        self.persist_notify.notified().await
+    }
+
+    /// 持久化位置是否已有快照，分片持久化时检查分片清单
+    pub async fn snapshot_exists(&self) -> Result<bool> {
+        let Some(target) = &self.persist_target else {
+            return Ok(false);
+        };
+        let contents = if self.sharded {
+            self.shard_target(target, MANIFEST_NAME).read().await?
+        } else {
+            target.read().await?
+        };
+        Ok(contents.is_some())
+    }
+
+    /// 从持久化文件或对象存储加载数据
     pub async fn load_from_disk(&self) -> Result<usize> {
-        let path = match &self.persist_path {
-            Some(p) => p,
+        let target = match &self.persist_target {
+            Some(target) => target,
             None => return Ok(0),
         };
+        if self.sharded {
+            return self.load_shards(target).await;
+        }
 
-        if !path.exists() {
-            log::info!("[PERSISTENCE] No persistence file found at {:?}", path);
+        let Some((generation, ((data, file_format, sealed), checksum))) = self
+            .read_latest(target, |contents| {
+                Ok((self.decode_snapshot(contents)?, snapshot::checksum(contents)))
+            })
+            .await?
+        else {
+            log::info!("[PERSISTENCE] No persistence file found at {}", target);
             return Ok(0);
+        };
+        let delta = self.read_delta(&checksum).await?;
+        let (data, deltas) = match delta {
+            Some(delta) => {
+                let deltas = delta.sequence;
+                log::info!(
+                    "[PERSISTENCE] Merging incremental snapshot #{} with {} changed and {} removed locks",
+                    delta.sequence,
+                    delta.data.locks.len(),
+                    delta.removed.len()
+                );
+                if self.incremental_enabled() {
+                    let mut changed_keys = self.changed_keys.lock();
+                    changed_keys.extend(delta.removed.iter().cloned());
+                    changed_keys.extend(delta.data.locks.iter().map(LockInfo::get_lock_key));
+                }
+                (snapshot::merge(data, delta), deltas)
+            }
+            None => (data, 0),
+        };
+        self.load_global(data.namespaces, data.sequences, data.records);
+        let loaded_count = self.load_locks(data.locks);
+
+        log::info!(
+            "[PERSISTENCE] Loaded {} locks from disk (file: {}, format: {:?})",
+            loaded_count, target.describe(generation), file_format
+        );
+        if self.needs_rewrite(generation, &file_format, sealed) {
+            self.mark_dirty();
+        } else {
+            *self.base.lock() = Some(BaseSnapshot {
+                checksum,
+                since: Instant::now(),
+                deltas,
+            });
         }
+        Ok(loaded_count)
+    }
 
-        let mut file = fs::File::open(path).await?;
-        let mut contents = String::new();
-        file.read_to_string(&mut contents).await?;
+    /// 读取基于 checksum 对应的全量快照的增量快照；增量快照损坏或属于其他全量快照时忽略，其中的变更丢失
+    async fn read_delta(&self, checksum: &str) -> Result<Option<SnapshotDelta>> {
+        let Some(delta_target) = &self.delta_target else {
+            return Ok(None);
+        };
+        let Some(contents) = delta_target.read().await? else {
+            return Ok(None);
+        };
+        self.delta_present.store(true, Ordering::Release);
+        let delta = match self.decode_delta(&contents) {
+            Ok(delta) => delta,
+            Err(e) if matches!(e.downcast_ref::<SealError>(), Some(SealError::KeyMissing)) => return Err(e),
+            Err(e) => {
+                log::error!(
+                    "[PERSISTENCE] Incremental snapshot {} is corrupt, changes after the full snapshot are lost: {:#}",
+                    delta_target, e
+                );
+                return Ok(None);
+            }
+        };
+        if delta.base != checksum {
+            log::warn!(
+                "[PERSISTENCE] Incremental snapshot {} does not belong to the loaded full snapshot, ignoring it",
+                delta_target
+            );
+            return Ok(None);
+        }
+        Ok(Some(delta))
+    }
+
+    /// 按分片清单加载命名空间策略、序列和各命名空间的锁
+    async fn load_shards(&self, target: &PersistTarget) -> Result<usize> {
+        let manifest_target = self.shard_target(target, MANIFEST_NAME);
+        let Some((generation, (manifest, sealed))) = self
+            .read_latest(&manifest_target, |contents| self.decode_manifest(contents))
+            .await?
+        else {
+            log::info!("[PERSISTENCE] No shard manifest found at {}", manifest_target);
+            return Ok(0);
+        };
+        self.load_global(manifest.namespaces, manifest.sequences, manifest.records);
+        if self.needs_rewrite(generation, &self.persist_format, sealed) {
+            self.mark_dirty();
+        }
 
-        let data: Vec<LockInfo> = serde_json::from_str(&contents)?;
         let mut loaded_count = 0;
+        for namespace in &manifest.shards {
+            let shard = self.shard_target(target, &shard_name(namespace));
+            let Some((generation, (data, file_format, sealed))) =
+                self.read_latest(&shard, |contents| self.decode_snapshot(contents)).await?
+            else {
+                log::warn!("[PERSISTENCE] Shard {} listed in manifest is missing", shard);
+                continue;
+            };
+            loaded_count += self.load_locks(data.locks);
+            if self.needs_rewrite(generation, &file_format, sealed) {
+                self.mark_namespace_dirty(namespace);
+            }
+        }
+
+        log::info!(
+            "[PERSISTENCE] Loaded {} locks from {} shards (manifest: {})",
+            loaded_count,
+            manifest.shards.len(),
+            manifest_target
+        );
+        Ok(loaded_count)
+    }
 
-        for lock_info in data {
+    /// 从最新的快照开始依次尝试，跳过损坏的副本，返回成功加载的副本序号和解码结果
+    async fn read_latest<T>(
+        &self,
+        target: &PersistTarget,
+        decode: impl Fn(&[u8]) -> Result<T>,
+    ) -> Result<Option<(usize, T)>> {
+        let mut first_error = None;
+        for generation in 0..=target.rotations() {
+            let Some(contents) = target.read_generation(generation).await? else {
+                continue;
+            };
+            match decode(&contents) {
+                Ok(decoded) => {
+                    if generation > 0 {
+                        log::warn!(
+                            "[PERSISTENCE] Recovered from older snapshot {}, changes after it are lost",
+                            target.describe(generation)
+                        );
+                    }
+                    return Ok(Some((generation, decoded)));
+                }
+                // 未配置密钥时所有副本都无法读取
+                Err(e) if matches!(e.downcast_ref::<SealError>(), Some(SealError::KeyMissing)) => return Err(e),
+                Err(e) => {
+                    log::error!(
+                        "[PERSISTENCE] Snapshot {} is corrupt: {:#}",
+                        target.describe(generation),
+                        e
+                    );
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        match first_error {
+            Some(e) => Err(e.context(SnapshotCorrupt(target.to_string()))),
+            None => Ok(None),
+        }
+    }
+
+    /// 恢复命名空间策略、序列和记录
+    fn load_global(
+        &self,
+        namespaces: Vec<NamespacePolicy>,
+        sequences: BTreeMap<String, u64>,
+        records: BTreeMap<String, BTreeMap<String, String>>,
+    ) {
+        for policy in namespaces {
+            self.namespaces.insert(policy.name.clone(), policy);
+        }
+        for (name, value) in sequences {
+            self.sequences.insert(name, value);
+        }
+        *self.records.lock() = records;
+    }
+
+    /// 恢复未过期的锁，返回加载的数量
+    fn load_locks(&self, locks: Vec<LockInfo>) -> usize {
+        let mut loaded_count = 0;
+        for lock_info in locks {
             // 只加载未过期的锁
-            if !lock_info.is_expired() {
+            if !lock_info.is_expired_at(self.clock.now()) {
                 let lock_key = lock_info.get_lock_key();
                 self.lock_by_id.insert(lock_info.lock_id.clone(), lock_key.clone());
+                self.index_lock(&lock_info);
                 self.locks.insert(lock_key, lock_info);
                 loaded_count += 1;
             }
         }
+        loaded_count
+    }
+
+    /// 加载的快照是否需要在下次持久化时重写：从旧副本恢复、格式与配置不一致或新配置了密钥
+    fn needs_rewrite(&self, generation: usize, file_format: &PersistFormat, sealed: bool) -> bool {
+        if *file_format != self.persist_format {
+            log::info!(
+                "[PERSISTENCE] Migrating persistence file from {:?} to {:?} on next persist",
+                file_format, self.persist_format
+            );
+        }
+        if !sealed && self.cipher.is_some() {
+            log::info!("[PERSISTENCE] Encrypting persistence file on next persist");
+        }
+        generation > 0 || *file_format != self.persist_format || (!sealed && self.cipher.is_some())
+    }
 
-        log::info!(
-            "[PERSISTENCE] Loaded {} locks from disk (file: {:?})",
-            loaded_count, path
-        );
-        Ok(loaded_count)
+    /// 校验并解密快照，返回内容和是否加密
+    fn open_bytes(&self, contents: &[u8]) -> Result<(Vec<u8>, bool)> {
+        let contents = snapshot::verify_checksum(contents)?;
+        if !encryption::is_sealed(contents) {
+            return Ok((contents.to_vec(), false));
+        }
+        let cipher = self.cipher.as_ref().ok_or(SealError::KeyMissing)?;
+        Ok((cipher.open(contents)?, true))
+    }
+
+    /// 加密（配置了密钥时）并追加校验和
+    fn seal_bytes(&self, mut data: Vec<u8>) -> Result<Vec<u8>> {
+        if let Some(cipher) = &self.cipher {
+            data = cipher.seal(&data)?;
+        }
+        snapshot::append_checksum(&mut data);
+        Ok(data)
+    }
+
+    /// 校验、解密并解码快照，返回快照数据、格式和是否加密
+    fn decode_snapshot(&self, contents: &[u8]) -> Result<(SnapshotData, PersistFormat, bool)> {
+        let (contents, sealed) = self.open_bytes(contents)?;
+        let (data, format) = snapshot::decode(&contents)?;
+        Ok((data, format, sealed))
     }
 
-    /// 持久化数据到磁盘
+    /// 校验、解密并解码增量快照
+    fn decode_delta(&self, contents: &[u8]) -> Result<SnapshotDelta> {
+        let (contents, _) = self.open_bytes(contents)?;
+        snapshot::decode_delta(&contents)
+    }
+
+    /// 校验、解密并解析分片清单，返回清单和是否加密
+    fn decode_manifest(&self, contents: &[u8]) -> Result<(ShardManifest, bool)> {
+        let (contents, sealed) = self.open_bytes(contents)?;
+        Ok((serde_json::from_slice(&contents)?, sealed))
+    }
+
+    /// 分片快照的位置，缓存以保留对象存储的 ETag
+    fn shard_target(&self, target: &PersistTarget, name: &str) -> Arc<PersistTarget> {
+        self.shard_targets
+            .entry(name.to_string())
+            .or_insert_with(|| Arc::new(target.child(name)))
+            .clone()
+    }
+
+    /// 持久化数据到文件或对象存储
     pub async fn persist_to_disk(&self) -> Result<usize> {
-        let path = match &self.persist_path {
-            Some(p) => p,
+        let target = match &self.persist_target {
+            Some(target) => target,
             None => return Ok(0),
         };
 
+        // 没有变更且无需写入新心跳则跳过写入
+        let heartbeat_due = self
+            .heartbeat_due
+            .lock()
+            .take_if(|due| *due <= self.clock.now())
+            .is_some();
+        if !self.dirty.swap(false, Ordering::AcqRel) && !heartbeat_due {
+            log::debug!("[PERSISTENCE] No changes since last persist, skipping");
+            return Ok(0);
+        }
+        self.pending_changes.store(0, Ordering::Release);
+
+        let result = if self.sharded {
+            self.write_shards(target).await
+        } else if let Some(sequence) = self.next_delta() {
+            self.write_delta(sequence).await
+        } else {
+            self.write_snapshot(target).await
+        };
+        match result {
+            Ok(count) => Ok(count),
+            Err(e) => {
+                // 写入失败，保留脏标记以便下次重试
+                self.dirty.store(true, Ordering::Release);
+                Err(e)
+            }
+        }
+    }
+
+    /// 下一次写入增量快照时返回其序号，需要写入全量快照时为 None：未启用增量持久化、尚无基础快照、
+    /// 距离上次全量快照已超过压缩间隔，或有变更的锁达到全部锁的一半
+    fn next_delta(&self) -> Option<u64> {
+        if !self.incremental_enabled() {
+            return None;
+        }
+        let base = self.base.lock();
+        let base = base.as_ref()?;
+        if base.since.elapsed() >= self.compact_interval || self.changed_keys.lock().len() * 2 >= self.locks.len() {
+            return None;
+        }
+        Some(base.deltas + 1)
+    }
+
+    /// 写入自上次全量快照以来有变更的锁
+    async fn write_delta(&self, sequence: u64) -> Result<usize> {
+        let (Some(delta_target), Some(checksum)) = (
+            &self.delta_target,
+            self.base.lock().as_ref().map(|base| base.checksum.clone()),
+        ) else {
+            return Ok(0);
+        };
+        let changed_keys: Vec<String> = self.changed_keys.lock().iter().cloned().collect();
+        let mut locks = Vec::new();
+        let mut removed = Vec::new();
+        for lock_key in changed_keys {
+            match self.locks.get(&lock_key) {
+                Some(lock_info) => locks.push(lock_info.value().clone()),
+                None => removed.push(lock_key),
+            }
+        }
+
+        let count = locks.len();
+        let removed_count = removed.len();
+        let data = snapshot::encode_delta(
+            &SnapshotDelta {
+                base: checksum,
+                sequence,
+                removed,
+                data: SnapshotData {
+                    locks,
+                    namespaces: self.namespace_policies(),
+                    sequences: self.sequence_values(),
+                    records: self.records.lock().clone(),
+                },
+            },
+            &self.persist_format,
+        )?;
+        self.delta_present.store(true, Ordering::Release);
+        delta_target.write(self.seal_bytes(data)?).await?;
+        if let Some(base) = self.base.lock().as_mut() {
+            base.deltas = sequence;
+        }
+
+        log::debug!(
+            "[PERSISTENCE] Persisted incremental snapshot #{} with {} changed and {} removed locks (file: {})",
+            sequence, count, removed_count, delta_target
+        );
+        Ok(count)
+    }
+
+    /// 写入全量快照，增量持久化时作为新的基础快照并删除旧的增量快照
+    async fn write_snapshot(&self, target: &PersistTarget) -> Result<usize> {
+        let changed_keys = std::mem::take(&mut *self.changed_keys.lock());
+        let result = self.write_full_snapshot(target).await;
+        if result.is_err() {
+            self.changed_keys.lock().extend(changed_keys);
+        }
+        result
+    }
+
+    async fn write_full_snapshot(&self, target: &PersistTarget) -> Result<usize> {
         // 收集所有锁数据
         let locks: Vec<LockInfo> = self
             .locks
@@ -82,96 +696,276 @@ impl MemoryStorage {
             .collect();
 
         let count = locks.len();
-        let json = serde_json::to_string_pretty(&locks)?;
+        let data = snapshot::encode(
+            &SnapshotData {
+                locks,
+                namespaces: self.namespace_policies(),
+                sequences: self.sequence_values(),
+                records: self.records.lock().clone(),
+            },
+            &self.persist_format,
+        )?;
+        let data = self.seal_bytes(data)?;
+        let checksum = snapshot::checksum(&data);
+        target.write(data).await?;
+        *self.base.lock() = Some(BaseSnapshot {
+            checksum,
+            since: Instant::now(),
+            deltas: 0,
+        });
 
-        // 确保目录存在
-        if let Some(parent) = path.parent() {
-            fs::create_dir_all(parent).await?;
+        log::debug!(
+            "[PERSISTENCE] Persisted {} locks to disk (file: {})",
+            count, target
+        );
+
+        // 旧的增量快照基于上一份全量快照，删除失败时加载也会因校验和不一致而忽略
+        if let Some(delta_target) = &self.delta_target {
+            if self.delta_present.swap(false, Ordering::AcqRel) {
+                if let Err(e) = delta_target.remove().await {
+                    self.delta_present.store(true, Ordering::Release);
+                    log::warn!("[PERSISTENCE] Failed to remove incremental snapshot {}: {}", delta_target, e);
+                }
+            }
         }
+        Ok(count)
+    }
 
-        // 写入临时文件，然后重命名（原子操作）
-        let temp_path = path.with_extension("tmp");
-        let mut file = fs::File::create(&temp_path).await?;
-        file.write_all(json.as_bytes()).await?;
-        file.sync_all().await?;
-        fs::rename(temp_path, path).await?;
+    /// 只重写有变更的命名空间分片，再重写分片清单
+    async fn write_shards(&self, target: &PersistTarget) -> Result<usize> {
+        let dirty = std::mem::take(&mut *self.dirty_namespaces.lock());
+        let result = self.write_dirty_shards(target, &dirty).await;
+        if result.is_err() {
+            self.dirty_namespaces.lock().extend(dirty);
+        }
+        result
+    }
+
+    async fn write_dirty_shards(&self, target: &PersistTarget, dirty: &HashSet<String>) -> Result<usize> {
+        let mut shards: BTreeSet<String> = BTreeSet::new();
+        let mut dirty_locks: HashMap<&str, Vec<LockInfo>> = HashMap::new();
+        for entry in self.locks.iter() {
+            let namespace = entry.value().namespace.as_str();
+            if !shards.contains(namespace) {
+                shards.insert(namespace.to_string());
+            }
+            if let Some(namespace) = dirty.get(namespace) {
+                dirty_locks.entry(namespace).or_default().push(entry.value().clone());
+            }
+        }
+
+        let mut count = 0;
+        for namespace in dirty {
+            let shard = self.shard_target(target, &shard_name(namespace));
+            match dirty_locks.remove(namespace.as_str()) {
+                Some(locks) => {
+                    count += locks.len();
+                    let data = snapshot::encode(
+                        &SnapshotData {
+                            locks,
+                            ..SnapshotData::default()
+                        },
+                        &self.persist_format,
+                    )?;
+                    shard.write(self.seal_bytes(data)?).await?;
+                }
+                // 命名空间已没有锁
+                None => shard.remove().await?,
+            }
+        }
+
+        let manifest = ShardManifest {
+            shards: shards.into_iter().collect(),
+            namespaces: self.namespace_policies(),
+            sequences: self.sequence_values(),
+            records: self.records.lock().clone(),
+        };
+        let data = serde_json::to_vec_pretty(&manifest)?;
+        let manifest_target = self.shard_target(target, MANIFEST_NAME);
+        manifest_target.write(self.seal_bytes(data)?).await?;
 
         log::debug!(
-            "[PERSISTENCE] Persisted {} locks to disk (file: {:?})",
-            count, path
+            "[PERSISTENCE] Persisted {} locks in {} dirty shards (manifest: {})",
+            count,
+            dirty.len(),
+            manifest_target
         );
         Ok(count)
     }
+
+    fn namespace_policies(&self) -> Vec<NamespacePolicy> {
+        self.namespaces
+            .iter()
+            .map(|entry| entry.value().clone())
+            .collect()
+    }
+
+    fn sequence_values(&self) -> BTreeMap<String, u64> {
+        self.sequences
+            .iter()
+            .map(|entry| (entry.key().clone(), *entry.value()))
+            .collect()
+    }
+}
+
+/// 命名空间分片在持久化目录下的名称
+fn shard_name(namespace: &str) -> String {
+    format!("namespaces/{}.snapshot", namespace)
+}
+
+impl Default for MemoryStorage {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[async_trait]
 impl LockStorage for MemoryStorage {
-    async fn try_acquire(&self, lock_info: LockInfo) -> Result<bool> {
+    async fn try_acquire(&self, lock_info: LockInfo) -> Result<Acquire> {
         let lock_key = lock_info.get_lock_key();
+        let now = self.clock.now();
 
-        // 检查是否已存在锁
-        if let Some(existing_lock) = self.locks.get(&lock_key) {
-            // 如果锁过期，则移除旧锁
-            if existing_lock.is_expired() {
-                let old_lock_id = existing_lock.lock_id.clone();
-                let old_user_name = existing_lock.user_name.clone();
-                let old_user_id = existing_lock.user_id.clone();
-                let namespace = existing_lock.namespace.clone();
-                let business_id = existing_lock.business_id.clone();
-                drop(existing_lock); // 释放读锁
-                self.lock_by_id.remove(&old_lock_id);
-                self.locks.remove(&lock_key);
-                log::info!(
-                    "[EXPIRED] Lock expired and removed - lock_id: {}, namespace: {}, business_id: {}, user_id: {}, user_name: {}",
-                    old_lock_id, namespace, business_id, old_user_id, old_user_name
-                );
-            } else if existing_lock.user_id == lock_info.user_id {
-                // 同一个用户重复申请，更新心跳时间并返回现有锁ID
-                let existing_lock_id = existing_lock.lock_id.clone();
-                drop(existing_lock); // 释放读锁
-                if let Some(mut lock) = self.locks.get_mut(&lock_key) {
-                    lock.last_heartbeat = chrono::Utc::now();
+        // 新增锁之前检查容量，检查和写入之间不等待，已有的锁在下面的 entry 中原子地判断
+        if !self.locks.contains_key(&lock_key) {
+            self.ensure_capacity().await?;
+        }
+        let replaced = match self.locks.entry(lock_key.clone()) {
+            Entry::Occupied(mut entry) => {
+                let existing_lock = entry.get_mut();
+                if !existing_lock.is_expired_at(now) {
+                    if existing_lock.user_id != lock_info.user_id {
+                        // 锁仍然有效且被其他用户持有，获取失败
+                        drop(entry);
+                        self.stats.record_acquire(&lock_key, false);
+                        return Ok(Acquire::plain(false));
+                    }
+                    // 同一个用户重复申请，更新心跳时间并保留现有锁
+                    existing_lock.last_heartbeat = now;
+                    existing_lock.version += 1;
                     log::info!(
                         "[REENTRANT] Same user re-acquiring lock - lock_id: {}, namespace: {}, business_id: {}, user_id: {}, user_name: {}",
-                        existing_lock_id, lock.namespace, lock.business_id, lock.user_id, lock.user_name
+                        existing_lock.lock_id, existing_lock.namespace, existing_lock.business_id,
+                        existing_lock.user_id, existing_lock.user_name
                     );
+                    drop(entry);
+                    self.mark_lock_dirty(&lock_info);
+                    self.stats.record_acquire(&lock_key, true);
+                    return Ok(Acquire::plain(true));
                 }
-                return Ok(true);
-            } else {
-                // 锁仍然有效且被其他用户持有，获取失败
-                return Ok(false);
+                // 锁已过期，替换为新锁
+                Some(entry.insert(lock_info.clone()))
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(lock_info.clone());
+                None
             }
+        };
+
+        if let Some(expired_lock) = &replaced {
+            log::info!(
+                "[EXPIRED] Lock expired and removed - lock_id: {}, namespace: {}, business_id: {}, user_id: {}, user_name: {}",
+                expired_lock.lock_id, expired_lock.namespace, expired_lock.business_id,
+                expired_lock.user_id, expired_lock.user_name
+            );
+            self.lock_by_id.remove(&expired_lock.lock_id);
+            self.unindex_lock(expired_lock);
+            self.stats.record_expired(expired_lock, now);
+        }
+        self.stats.record_acquire(&lock_key, true);
+        self.lock_by_id.insert(lock_info.lock_id.clone(), lock_key);
+        self.index_lock(&lock_info);
+        self.mark_lock_dirty(&lock_info);
+        Ok(Acquire {
+            acquired: true,
+            replaced: replaced.map(Box::new),
+        })
+    }
+
+    async fn takeover(&self, mut lock_info: LockInfo, condition: &LockCondition) -> Result<Takeover> {
+        let lock_key = lock_info.get_lock_key();
+        if !self.locks.contains_key(&lock_key) {
+            self.ensure_capacity().await?;
         }
+        let replaced = match self.locks.entry(lock_key.clone()) {
+            Entry::Occupied(mut entry) => {
+                if !condition.matches(entry.get(), self.clock.now()) {
+                    let current = entry.get().clone();
+                    drop(entry);
+                    self.stats.record_acquire(&lock_key, false);
+                    return Ok(Takeover::Rejected(current));
+                }
+                lock_info.version = entry.get().version + 1;
+                Some(entry.insert(lock_info.clone()))
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(lock_info.clone());
+                None
+            }
+        };
 
-        // 获取锁
         self.lock_by_id.insert(lock_info.lock_id.clone(), lock_key.clone());
-        self.locks.insert(lock_key, lock_info);
-        Ok(true)
+        if let Some(replaced) = &replaced {
+            log::info!(
+                "[TAKEOVER] Expired lock taken over - lock_id: {}, namespace: {}, business_id: {}, from: {}, to: {}, version: {}",
+                lock_info.lock_id, lock_info.namespace, lock_info.business_id,
+                replaced.user_id, lock_info.user_id, lock_info.version
+            );
+            self.lock_by_id.remove(&replaced.lock_id);
+            self.unindex_lock(replaced);
+            self.stats.record_expired(replaced, self.clock.now());
+        }
+        self.index_lock(&lock_info);
+        self.mark_lock_dirty(&lock_info);
+        self.stats.record_acquire(&lock_key, true);
+        Ok(Takeover::Acquired {
+            lock_info,
+            replaced: replaced.map(Box::new),
+        })
     }
 
     async fn get_lock(&self, lock_key: &str) -> Result<Option<LockInfo>> {
         Ok(self.locks.get(lock_key).map(|entry| entry.value().clone()))
     }
 
-    async fn update_heartbeat(&self, lock_id: &str) -> Result<bool> {
-        let lock_key = match self.lock_by_id.get(lock_id) {
-            Some(entry) => entry.value().clone(),
-            None => return Ok(false),
-        };
-
-        if let Some(mut lock_info) = self.locks.get_mut(&lock_key) {
-            if lock_info.lock_id == lock_id {
-                lock_info.last_heartbeat = Utc::now();
-                return Ok(true);
+    async fn restore(&self, lock_info: LockInfo) -> Result<bool> {
+        let lock_key = lock_info.get_lock_key();
+        if !self.locks.contains_key(&lock_key) {
+            self.ensure_capacity().await?;
+        }
+        let replaced = match self.locks.entry(lock_key.clone()) {
+            Entry::Occupied(mut entry) => {
+                if !entry.get().is_expired_at(self.clock.now()) {
+                    return Ok(false);
+                }
+                Some(entry.insert(lock_info.clone()))
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(lock_info.clone());
+                None
             }
+        };
+        if let Some(replaced) = &replaced {
+            self.lock_by_id.remove(&replaced.lock_id);
+            self.unindex_lock(replaced);
         }
-        Ok(false)
+        self.lock_by_id.insert(lock_info.lock_id.clone(), lock_key);
+        self.index_lock(&lock_info);
+        self.mark_lock_dirty(&lock_info);
+        Ok(true)
     }
 
-    async fn release(&self, lock_id: &str) -> Result<bool> {
+    async fn update_heartbeat(&self, lock_id: &str) -> Result<Option<LockInfo>> {
+        Ok(self.heartbeat(lock_id, None))
+    }
+
+    async fn update_heartbeat_version(&self, lock_id: &str, version: u64) -> Result<Option<LockInfo>> {
+        Ok(self.heartbeat(lock_id, Some(version)))
+    }
+
+    async fn release(&self, lock_id: &str) -> Result<Option<LockInfo>> {
         let lock_key = match self.lock_by_id.remove(lock_id) {
             Some((_, key)) => key,
-            None => return Ok(false),
+            None => return Ok(None),
         };
 
         if let Some((_, lock_info)) = self.locks.remove(&lock_key) {
@@ -181,21 +975,67 @@ impl LockStorage for MemoryStorage {
                     lock_info.lock_id, lock_info.namespace, lock_info.business_id, 
                     lock_info.user_id, lock_info.user_name
                 );
-                return Ok(true);
+                self.unindex_lock(&lock_info);
+                self.mark_lock_dirty(&lock_info);
+                self.stats.record_released(&lock_info, self.clock.now());
+                return Ok(Some(lock_info));
             } else {
                 // 如果 lock_id 不匹配，恢复锁
                 self.locks.insert(lock_key, lock_info);
             }
         }
-        Ok(false)
+        Ok(None)
     }
 
-    async fn cleanup_expired(&self) -> Result<()> {
+    async fn release_version(&self, lock_id: &str, version: u64) -> Result<Option<LockInfo>> {
+        let lock_key = match self.lock_by_id.get(lock_id) {
+            Some(entry) => entry.value().clone(),
+            None => return Ok(None),
+        };
+        let lock_info = match self.locks.remove_if(&lock_key, |_, lock_info| {
+            lock_info.lock_id == lock_id && lock_info.version == version
+        }) {
+            Some((_, lock_info)) => lock_info,
+            None => return Ok(None),
+        };
+        log::info!(
+            "[RELEASE] Releasing lock - lock_id: {}, namespace: {}, business_id: {}, user_id: {}, user_name: {}, version: {}",
+            lock_info.lock_id, lock_info.namespace, lock_info.business_id,
+            lock_info.user_id, lock_info.user_name, lock_info.version
+        );
+        self.lock_by_id.remove(lock_id);
+        self.unindex_lock(&lock_info);
+        self.mark_lock_dirty(&lock_info);
+        self.stats.record_released(&lock_info, self.clock.now());
+        Ok(Some(lock_info))
+    }
+
+    async fn release_owned(&self, lock_key: &str, user_id: &str) -> Result<Option<LockInfo>> {
+        let lock_info = match self
+            .locks
+            .remove_if(lock_key, |_, lock_info| lock_info.user_id == user_id)
+        {
+            Some((_, lock_info)) => lock_info,
+            None => return Ok(None),
+        };
+        self.lock_by_id.remove(&lock_info.lock_id);
+        self.unindex_lock(&lock_info);
+        self.mark_lock_dirty(&lock_info);
+        self.stats.record_released(&lock_info, self.clock.now());
+        log::info!(
+            "[RELEASE] Releasing lock by key - lock_id: {}, namespace: {}, business_id: {}, user_id: {}, user_name: {}",
+            lock_info.lock_id, lock_info.namespace, lock_info.business_id,
+            lock_info.user_id, lock_info.user_name
+        );
+        Ok(Some(lock_info))
+    }
+
+    async fn cleanup_expired(&self) -> Result<Vec<LockInfo>> {
         // 收集过期的锁
         let expired: Vec<(String, String)> = self
             .locks
             .iter()
-            .filter(|entry| entry.value().is_expired())
+            .filter(|entry| entry.value().is_expired_at(self.clock.now()))
             .map(|entry| (entry.key().clone(), entry.value().lock_id.clone()))
             .collect();
 
@@ -204,6 +1044,7 @@ impl LockStorage for MemoryStorage {
         }
 
         // 删除过期的锁
+        let mut removed = Vec::with_capacity(expired.len());
         for (lock_key, lock_id) in expired {
             if let Some((_, lock_info)) = self.locks.remove(&lock_key) {
                 log::info!(
@@ -211,10 +1052,268 @@ impl LockStorage for MemoryStorage {
                     lock_info.lock_id, lock_info.namespace, lock_info.business_id,
                     lock_info.user_id, lock_info.user_name, lock_info.locked_at
                 );
+                self.unindex_lock(&lock_info);
+                self.mark_lock_dirty(&lock_info);
+                self.stats.record_expired(&lock_info, self.clock.now());
+                removed.push(lock_info);
             }
             self.lock_by_id.remove(&lock_id);
         }
 
+        // 已补满的令牌桶与不存在等价
+        let now = self.clock.now();
+        self.rate_limits.retain(|_, state| !state.is_full_at(now));
+
+        Ok(removed)
+    }
+
+    async fn list_locks(&self) -> Result<Vec<LockInfo>> {
+        Ok(self.locks.iter().map(|entry| entry.value().clone()).collect())
+    }
+
+    async fn list_prefix(&self, key_prefix: &str) -> Result<Vec<LockInfo>> {
+        Ok(self
+            .locks
+            .iter()
+            .filter(|entry| entry.key().starts_with(key_prefix))
+            .map(|entry| entry.value().clone())
+            .collect())
+    }
+
+    async fn get_lock_by_id(&self, lock_id: &str) -> Result<Option<LockInfo>> {
+        let lock_key = match self.lock_by_id.get(lock_id) {
+            Some(entry) => entry.value().clone(),
+            None => return Ok(None),
+        };
+        Ok(self
+            .locks
+            .get(&lock_key)
+            .filter(|entry| entry.lock_id == lock_id)
+            .map(|entry| entry.value().clone()))
+    }
+
+    async fn force_release(&self, lock_key: &str) -> Result<Option<LockInfo>> {
+        let lock_info = match self.locks.remove(lock_key) {
+            Some((_, lock_info)) => lock_info,
+            None => return Ok(None),
+        };
+        self.lock_by_id.remove(&lock_info.lock_id);
+        self.unindex_lock(&lock_info);
+        self.mark_lock_dirty(&lock_info);
+        self.stats.record_released(&lock_info, self.clock.now());
+        log::warn!(
+            "[FORCE RELEASE] Lock force released - lock_id: {}, namespace: {}, business_id: {}, user_id: {}, user_name: {}",
+            lock_info.lock_id, lock_info.namespace, lock_info.business_id,
+            lock_info.user_id, lock_info.user_name
+        );
+        Ok(Some(lock_info))
+    }
+
+    async fn mark_preempted(
+        &self,
+        lock_id: &str,
+        notice: PreemptionNotice,
+    ) -> Result<Option<LockInfo>> {
+        let lock_key = match self.lock_by_id.get(lock_id) {
+            Some(entry) => entry.value().clone(),
+            None => return Ok(None),
+        };
+
+        if let Some(mut lock_info) = self.locks.get_mut(&lock_key) {
+            if lock_info.lock_id == lock_id && !lock_info.is_expired_at(self.clock.now()) {
+                if lock_info.preemption.is_none() {
+                    lock_info.preemption = Some(notice);
+                    lock_info.version += 1;
+                    self.mark_lock_dirty(&lock_info);
+                }
+                return Ok(Some(lock_info.clone()));
+            }
+        }
+        Ok(None)
+    }
+
+    async fn transfer(&self, lock_id: &str, mut new_lock: LockInfo) -> Result<Option<LockInfo>> {
+        let lock_key = new_lock.get_lock_key();
+        let old_lock = {
+            let Some(mut lock_info) = self.locks.get_mut(&lock_key) else {
+                return Ok(None);
+            };
+            if lock_info.lock_id != lock_id {
+                return Ok(None);
+            }
+            new_lock.version = lock_info.version + 1;
+            self.lock_by_id
+                .insert(new_lock.lock_id.clone(), lock_key.clone());
+            std::mem::replace(&mut *lock_info, new_lock)
+        };
+        self.lock_by_id.remove(&old_lock.lock_id);
+        self.unindex_lock(&old_lock);
+        if let Some(lock_info) = self.locks.get(&lock_key) {
+            self.index_lock(&lock_info);
+        }
+        self.mark_lock_dirty(&old_lock);
+        self.stats.record_released(&old_lock, self.clock.now());
+        self.stats.record_acquire(&lock_key, true);
+        log::warn!(
+            "[PREEMPT] Lock transferred - lock_id: {}, namespace: {}, business_id: {}, user_id: {}, user_name: {}",
+            old_lock.lock_id, old_lock.namespace, old_lock.business_id,
+            old_lock.user_id, old_lock.user_name
+        );
+        Ok(Some(old_lock))
+    }
+
+    async fn rewrite_lock(&self, lock_info: LockInfo) -> Result<bool> {
+        let Some(mut current) = self.locks.get_mut(&lock_info.get_lock_key()) else {
+            return Ok(false);
+        };
+        if current.lock_id != lock_info.lock_id || current.version != lock_info.version {
+            return Ok(false);
+        }
+        *current = lock_info;
+        let updated = current.clone();
+        drop(current);
+        self.mark_lock_dirty(&updated);
+        Ok(true)
+    }
+
+    async fn list_by_user(&self, user_id: &str) -> Result<Vec<LockInfo>> {
+        let lock_keys: Vec<String> = match self.locks_by_user.get(user_id) {
+            Some(entry) => entry.value().iter().cloned().collect(),
+            None => return Ok(Vec::new()),
+        };
+        Ok(lock_keys
+            .iter()
+            .filter_map(|lock_key| self.locks.get(lock_key))
+            .filter(|entry| entry.user_id == user_id && !entry.is_expired_at(self.clock.now()))
+            .map(|entry| entry.value().clone())
+            .collect())
+    }
+
+    async fn list_by_business_id(&self, business_id: &str) -> Result<Vec<LockInfo>> {
+        let lock_keys: Vec<String> = match self.locks_by_business_id.get(business_id) {
+            Some(entry) => entry.value().iter().cloned().collect(),
+            None => return Ok(Vec::new()),
+        };
+        Ok(lock_keys
+            .iter()
+            .filter_map(|lock_key| self.locks.get(lock_key))
+            .filter(|entry| entry.business_id == business_id && !entry.is_expired_at(self.clock.now()))
+            .map(|entry| entry.value().clone())
+            .collect())
+    }
+
+    async fn release_all_by_user(&self, user_id: &str) -> Result<Vec<LockInfo>> {
+        let lock_keys: Vec<String> = match self.locks_by_user.get(user_id) {
+            Some(entry) => entry.value().iter().cloned().collect(),
+            None => return Ok(Vec::new()),
+        };
+        let mut released = Vec::with_capacity(lock_keys.len());
+        for lock_key in lock_keys {
+            if let Some(lock_info) = self.release_owned(&lock_key, user_id).await? {
+                released.push(lock_info);
+            }
+        }
+        Ok(released)
+    }
+
+    async fn count_locks(&self, namespace: &str) -> Result<u64> {
+        Ok(self
+            .locks
+            .iter()
+            .filter(|entry| entry.namespace == namespace && !entry.is_expired_at(self.clock.now()))
+            .count() as u64)
+    }
+
+    async fn stats(&self, top_n: usize) -> Result<LockStats> {
+        let locks: Vec<LockInfo> = self.locks.iter().map(|entry| entry.value().clone()).collect();
+        Ok(self.stats.snapshot(&locks, top_n, self.clock.now()))
+    }
+
+    async fn list_namespaces(&self) -> Result<Vec<NamespacePolicy>> {
+        Ok(self
+            .namespaces
+            .iter()
+            .map(|entry| entry.value().clone())
+            .collect())
+    }
+
+    async fn get_namespace(&self, name: &str) -> Result<Option<NamespacePolicy>> {
+        Ok(self.namespaces.get(name).map(|entry| entry.value().clone()))
+    }
+
+    async fn put_namespace(&self, policy: NamespacePolicy) -> Result<()> {
+        self.namespaces.insert(policy.name.clone(), policy);
+        self.mark_dirty();
         Ok(())
     }
+
+    async fn delete_namespace(&self, name: &str) -> Result<bool> {
+        let removed = self.namespaces.remove(name).is_some();
+        if removed {
+            self.mark_dirty();
+        }
+        Ok(removed)
+    }
+
+    async fn next_sequence(&self, name: &str, count: u64) -> Result<u64> {
+        let last = {
+            let mut value = self.sequences.entry(name.to_string()).or_insert(0);
+            *value = value
+                .checked_add(count)
+                .ok_or_else(|| anyhow::anyhow!("Sequence {} overflowed", name))?;
+            *value
+        };
+        self.mark_dirty();
+        Ok(last)
+    }
+
+    async fn take_tokens(&self, key: &str, bucket: TokenBucket, cost: u64) -> Result<RateLimitDecision> {
+        let now = self.clock.now();
+        Ok(self
+            .rate_limits
+            .entry(key.to_string())
+            .or_insert_with(|| BucketState::full(bucket, now))
+            .take(bucket, cost, now))
+    }
+
+    async fn get_record(&self, kind: &str, id: &str) -> Result<Option<String>> {
+        Ok(self.records.lock().get(kind).and_then(|records| records.get(id)).cloned())
+    }
+
+    async fn list_records(&self, kind: &str) -> Result<Vec<(String, String)>> {
+        Ok(self
+            .records
+            .lock()
+            .get(kind)
+            .map(|records| records.iter().map(|(id, value)| (id.clone(), value.clone())).collect())
+            .unwrap_or_default())
+    }
+
+    async fn swap_record(&self, kind: &str, id: &str, expected: Option<&str>, value: Option<&str>) -> Result<bool> {
+        {
+            let mut records = self.records.lock();
+            let current = records.get(kind).and_then(|records| records.get(id));
+            if current.map(String::as_str) != expected {
+                return Ok(false);
+            }
+            match value {
+                Some(value) => {
+                    records
+                        .entry(kind.to_string())
+                        .or_default()
+                        .insert(id.to_string(), value.to_string());
+                }
+                None => {
+                    if let Some(kind_records) = records.get_mut(kind) {
+                        kind_records.remove(id);
+                        if kind_records.is_empty() {
+                            records.remove(kind);
+                        }
+                    }
+                }
+            }
+        }
+        self.mark_dirty();
+        Ok(true)
+    }
 }
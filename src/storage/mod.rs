@@ -1,24 +1,160 @@
+pub mod cache;
+pub mod encryption;
+pub mod failover;
+pub mod fieldcrypt;
 pub mod memory;
+pub mod persist;
+pub mod raft;
+pub mod ratelimit;
 pub mod redis;
+pub mod registry;
+pub mod replication;
+pub mod s3;
+pub mod sharded;
+pub mod snapshot;
+pub mod stats;
+pub mod timeout;
+pub mod timing;
 
-use crate::models::LockInfo;
+use crate::models::{
+    LockCondition, LockInfo, LockStats, NamespacePolicy, PreemptionNotice, RateLimitDecision, TokenBucket,
+};
 use anyhow::Result;
 use async_trait::async_trait;
 
+/// 尝试获取的结果
+#[derive(Debug, Default)]
+pub struct Acquire {
+    pub acquired: bool,
+    /// 获取时替换的已过期的锁，由调用方发布 `expired` 事件
+    pub replaced: Option<Box<LockInfo>>,
+}
+
+impl Acquire {
+    /// 没有替换其他锁的结果
+    pub fn plain(acquired: bool) -> Self {
+        Self {
+            acquired,
+            replaced: None,
+        }
+    }
+}
+
+/// 条件获取的结果
+pub enum Takeover {
+    /// 获取成功，replaced 为被接管的已过期的锁，锁空闲时为 None
+    Acquired {
+        lock_info: LockInfo,
+        replaced: Option<Box<LockInfo>>,
+    },
+    /// 条件不满足，附带当前的锁
+    Rejected(LockInfo),
+}
+
+/// 保存在存储中的记录类别，导出和实例交接时逐类复制
+pub const RECORD_KINDS: &[&str] = &[
+    crate::reservation::RECORD_KIND,
+    crate::sessions::RECORD_KIND,
+    crate::oidc::PENDING_RECORD_KIND,
+    crate::oidc::SESSION_RECORD_KIND,
+];
+
 #[async_trait]
 pub trait LockStorage: Send + Sync {
-    /// 尝试获取锁
-    async fn try_acquire(&self, lock_info: LockInfo) -> Result<bool>;
+    /// 尝试获取锁，锁已过期时替换
+    async fn try_acquire(&self, lock_info: LockInfo) -> Result<Acquire>;
+
+    /// 条件获取：锁空闲时直接获取，锁满足 condition 时以 lock_info 接管
+    async fn takeover(&self, lock_info: LockInfo, condition: &LockCondition) -> Result<Takeover>;
+
+    /// 原样写入锁（保留 lock_id、时间戳和版本号），用于导入和迁移，lock_key 上已有未过期的锁时不写入，返回是否写入
+    async fn restore(&self, lock_info: LockInfo) -> Result<bool>;
 
     /// 获取锁信息
     async fn get_lock(&self, lock_key: &str) -> Result<Option<LockInfo>>;
 
-    /// 更新心跳
-    async fn update_heartbeat(&self, lock_id: &str) -> Result<bool>;
+    /// 更新心跳，返回更新后的锁信息
+    async fn update_heartbeat(&self, lock_id: &str) -> Result<Option<LockInfo>>;
+
+    /// 仅当锁的版本号为 version 时更新心跳，返回更新后的锁信息
+    async fn update_heartbeat_version(&self, lock_id: &str, version: u64) -> Result<Option<LockInfo>>;
+
+    /// 释放锁，返回被释放的锁信息
+    async fn release(&self, lock_id: &str) -> Result<Option<LockInfo>>;
+
+    /// 仅当锁的版本号为 version 时释放，返回被释放的锁信息
+    async fn release_version(&self, lock_id: &str, version: u64) -> Result<Option<LockInfo>>;
+
+    /// 按 lock_key 释放锁，仅当持有人为 user_id 时删除，返回被释放的锁信息
+    async fn release_owned(&self, lock_key: &str, user_id: &str) -> Result<Option<LockInfo>>;
+
+    /// 清理过期锁，返回被清理的锁信息
+    async fn cleanup_expired(&self) -> Result<Vec<LockInfo>>;
+
+    /// 列出所有锁
+    async fn list_locks(&self) -> Result<Vec<LockInfo>>;
+
+    /// 列出 lock_key 以 key_prefix 开头的锁
+    async fn list_prefix(&self, key_prefix: &str) -> Result<Vec<LockInfo>>;
+
+    /// 按 lock_id 获取锁信息
+    async fn get_lock_by_id(&self, lock_id: &str) -> Result<Option<LockInfo>>;
+
+    /// 强制释放锁（不校验持有人），返回被释放的锁信息
+    async fn force_release(&self, lock_key: &str) -> Result<Option<LockInfo>>;
+
+    /// 为未过期的锁登记抢占通知，已有通知时保留原通知，返回登记后的锁信息
+    async fn mark_preempted(
+        &self,
+        lock_id: &str,
+        notice: PreemptionNotice,
+    ) -> Result<Option<LockInfo>>;
+
+    /// 将 lock_id 对应的锁转移给 new_lock（同一 lock_key），返回被替换的锁信息
+    async fn transfer(&self, lock_id: &str, new_lock: LockInfo) -> Result<Option<LockInfo>>;
+
+    /// lock_key 上的锁 lock_id 和版本号与 lock_info 相同时原样覆盖（版本号不变），用于字段密钥轮换后重新加密，返回是否写入
+    async fn rewrite_lock(&self, lock_info: LockInfo) -> Result<bool>;
+
+    /// 列出用户持有的未过期的锁
+    async fn list_by_user(&self, user_id: &str) -> Result<Vec<LockInfo>>;
+
+    /// 列出各命名空间中 business_id 相同的未过期的锁
+    async fn list_by_business_id(&self, business_id: &str) -> Result<Vec<LockInfo>>;
+
+    /// 释放用户持有的所有锁，返回被释放的锁信息
+    async fn release_all_by_user(&self, user_id: &str) -> Result<Vec<LockInfo>>;
+
+    /// 统计命名空间内未过期的锁数量
+    async fn count_locks(&self, namespace: &str) -> Result<u64>;
+
+    /// 锁统计信息，包含竞争最激烈的 top_n 个锁
+    async fn stats(&self, top_n: usize) -> Result<LockStats>;
+
+    /// 列出所有命名空间策略
+    async fn list_namespaces(&self) -> Result<Vec<NamespacePolicy>>;
+
+    /// 获取命名空间策略
+    async fn get_namespace(&self, name: &str) -> Result<Option<NamespacePolicy>>;
+
+    /// 创建或更新命名空间策略
+    async fn put_namespace(&self, policy: NamespacePolicy) -> Result<()>;
+
+    /// 删除命名空间策略，返回是否存在
+    async fn delete_namespace(&self, name: &str) -> Result<bool>;
+
+    /// 从序列 name 分配 count 个连续的值，返回其中最后一个（序列从 1 开始）
+    async fn next_sequence(&self, name: &str, count: u64) -> Result<u64>;
+
+    /// 从令牌桶 key 中取出 cost 个令牌，令牌不足时不扣减
+    async fn take_tokens(&self, key: &str, bucket: TokenBucket, cost: u64) -> Result<RateLimitDecision>;
+
+    /// 读取 kind 类的记录 id。记录保存预约、客户端会话等需要跨实例共享和重启后保留的状态，内容为 JSON
+    async fn get_record(&self, kind: &str, id: &str) -> Result<Option<String>>;
 
-    /// 释放锁
-    async fn release(&self, lock_id: &str) -> Result<bool>;
+    /// 列出 kind 类的全部记录（id，内容）
+    async fn list_records(&self, kind: &str) -> Result<Vec<(String, String)>>;
 
-    /// 清理过期锁
-    async fn cleanup_expired(&self) -> Result<()>;
+    /// 记录的当前内容为 expected（None 表示不存在）时写入 value（None 表示删除），返回是否写入
+    async fn swap_record(&self, kind: &str, id: &str, expected: Option<&str>, value: Option<&str>) -> Result<bool>;
 }
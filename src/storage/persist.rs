@@ -0,0 +1,157 @@
+//! 内存存储快照的保存位置：本地文件或 S3 兼容对象存储
+//!
+//! 本地文件每次写入前将上一份快照轮转为 `<路径>.1`、`<路径>.2`……，最多保留 `MEMORY_PERSIST_KEEP` 份，
+//! 加载时最新的快照损坏则依次尝试旧副本。对象存储只保存一份快照，历史版本可使用存储桶的版本控制。
+//! 增量快照保存在 `<路径>.delta`（对象存储为同一前缀下的 `<对象名>.delta`），不轮转。
+
+use crate::config::Config;
+use crate::storage::s3::S3Object;
+use anyhow::Result;
+use std::ffi::OsString;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+
+pub enum PersistTarget {
+    File { path: PathBuf, keep: usize }, // keep 为保留的旧快照份数
+    S3(Box<S3Object>),
+}
+
+impl PersistTarget {
+    /// `s3://bucket/prefix` 为对象存储，其他为本地文件路径
+    pub fn parse(spec: &str, config: &Config) -> Result<Self> {
+        if spec.starts_with("s3://") {
+            Ok(PersistTarget::S3(Box::new(S3Object::from_url(spec, config)?)))
+        } else {
+            Ok(PersistTarget::File {
+                path: PathBuf::from(spec),
+                keep: config.memory_persist_keep,
+            })
+        }
+    }
+
+    /// 分片保存时位于同一目录（前缀）下名为 name 的快照
+    pub fn child(&self, name: &str) -> Self {
+        match self {
+            PersistTarget::File { path, keep } => PersistTarget::File {
+                path: path.join(name),
+                keep: *keep,
+            },
+            PersistTarget::S3(object) => PersistTarget::S3(Box::new(object.child(name))),
+        }
+    }
+
+    /// 增量快照的位置
+    pub fn delta(&self) -> Self {
+        match self {
+            PersistTarget::File { path, .. } => PersistTarget::File {
+                path: suffixed_path(path, ".delta"),
+                keep: 0,
+            },
+            PersistTarget::S3(object) => PersistTarget::S3(Box::new(object.suffixed(".delta"))),
+        }
+    }
+
+    /// 可供加载的旧快照份数
+    pub fn rotations(&self) -> usize {
+        match self {
+            PersistTarget::File { keep, .. } => *keep,
+            PersistTarget::S3(_) => 0,
+        }
+    }
+
+    /// 读取最新的快照，不存在时为 None
+    pub async fn read(&self) -> Result<Option<Vec<u8>>> {
+        self.read_generation(0).await
+    }
+
+    /// 读取第 generation 份快照（0 为最新），不存在时为 None
+    pub async fn read_generation(&self, generation: usize) -> Result<Option<Vec<u8>>> {
+        match self {
+            PersistTarget::File { path, .. } => {
+                let path = generation_path(path, generation);
+                if !path.exists() {
+                    return Ok(None);
+                }
+                Ok(Some(fs::read(path).await?))
+            }
+            PersistTarget::S3(object) if generation == 0 => object.get().await,
+            PersistTarget::S3(_) => Ok(None),
+        }
+    }
+
+    /// 写入快照
+    pub async fn write(&self, data: Vec<u8>) -> Result<()> {
+        match self {
+            PersistTarget::File { path, keep } => {
+                // 确保目录存在
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent).await?;
+                }
+
+                // 写入临时文件
+                let temp_path = path.with_extension("tmp");
+                let mut file = fs::File::create(&temp_path).await?;
+                file.write_all(&data).await?;
+                file.sync_all().await?;
+
+                // 轮转旧快照，中途崩溃时加载会回退到已轮转的副本
+                for generation in (1..=*keep).rev() {
+                    let from = generation_path(path, generation - 1);
+                    if from.exists() {
+                        fs::rename(from, generation_path(path, generation)).await?;
+                    }
+                }
+
+                // 重命名为正式文件（原子操作）
+                fs::rename(temp_path, path).await?;
+                Ok(())
+            }
+            PersistTarget::S3(object) => object.put(data).await,
+        }
+    }
+
+    /// 删除快照，保留已轮转的旧副本
+    pub async fn remove(&self) -> Result<()> {
+        match self {
+            PersistTarget::File { path, .. } => match fs::remove_file(path).await {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+                _ => Ok(()),
+            },
+            PersistTarget::S3(object) => object.delete().await,
+        }
+    }
+
+    /// 第 generation 份快照的描述，用于日志
+    pub fn describe(&self, generation: usize) -> String {
+        match self {
+            PersistTarget::File { path, .. } => generation_path(path, generation).display().to_string(),
+            PersistTarget::S3(object) => object.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for PersistTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PersistTarget::File { path, .. } => write!(f, "{}", path.display()),
+            PersistTarget::S3(object) => object.fmt(f),
+        }
+    }
+}
+
+/// 第 generation 份快照的路径，0 为 path 本身，其余为 `<path>.<generation>`
+fn generation_path(path: &Path, generation: usize) -> PathBuf {
+    if generation == 0 {
+        return path.to_path_buf();
+    }
+    suffixed_path(path, &format!(".{}", generation))
+}
+
+/// 在 path 的文件名后追加 suffix
+fn suffixed_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(suffix);
+    PathBuf::from(name)
+}
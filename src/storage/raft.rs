@@ -0,0 +1,2031 @@
+//! 基于 Raft（openraft）复制的内存存储
+//!
+//! 所有写操作作为日志条目提交到 Raft，由各节点的状态机按相同顺序应用；
+//! 非 leader 节点收到的请求会转发给 leader 执行。节点间通信经 HTTP 接口 `/raft/*`，请求需携带 `RAFT_PEER_TOKEN`。
+//! 投票、已提交位置、日志和快照写入 `RAFT_DATA_DIR` 并 fsync 后才确认，节点重启后从磁盘恢复，
+//! 不会在同一任期重复投票，多数节点同时重启也不会丢失已提交的锁。
+
+use crate::auth;
+use crate::clock::{self, Clock};
+use crate::models::{
+    LockCondition, LockInfo, LockStats, NamespacePolicy, PreemptionNotice, RateLimitDecision, TokenBucket,
+};
+use crate::storage::ratelimit::BucketState;
+use crate::storage::stats::StatsCounters;
+use crate::storage::{Acquire, LockStorage, Takeover};
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::{from_fn, Next};
+use actix_web::{web, HttpResponse};
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use openraft::error::{
+    InstallSnapshotError, NetworkError, RPCError, RaftError, RemoteError, Unreachable,
+};
+use openraft::network::{RPCOption, RaftNetwork, RaftNetworkFactory};
+use openraft::raft::{
+    AppendEntriesRequest, AppendEntriesResponse, InstallSnapshotRequest, InstallSnapshotResponse,
+    VoteRequest, VoteResponse,
+};
+use openraft::storage::{LogFlushed, RaftLogStorage, RaftStateMachine};
+use openraft::{
+    BasicNode, Entry, EntryPayload, LogId, LogState, Raft, RaftLogReader, RaftSnapshotBuilder,
+    Snapshot, SnapshotMeta, SnapshotPolicy, StorageError, StorageIOError, StoredMembership, Vote,
+};
+use parking_lot::{Mutex, RwLock};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Debug;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Cursor, Write};
+use std::ops::RangeBounds;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+pub type NodeId = u64;
+
+openraft::declare_raft_types!(
+    pub TypeConfig:
+        D = Command,
+        R = CommandResult,
+);
+
+/// 写入 Raft 日志的命令，时间戳在提交前确定，保证各节点应用结果一致
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Command {
+    TryAcquire { lock_info: LockInfo, now: DateTime<Utc> },
+    Takeover { lock_info: LockInfo, condition: LockCondition, now: DateTime<Utc> },
+    Restore { lock_info: LockInfo, now: DateTime<Utc> },
+    UpdateHeartbeat {
+        lock_id: String,
+        now: DateTime<Utc>,
+        #[serde(default)]
+        version: Option<u64>,
+    },
+    Release { lock_id: String },
+    ReleaseVersion { lock_id: String, version: u64 },
+    ReleaseOwned { lock_key: String, user_id: String },
+    CleanupExpired { now: DateTime<Utc> },
+    ForceRelease { lock_key: String },
+    MarkPreempted { lock_id: String, notice: PreemptionNotice, now: DateTime<Utc> },
+    Transfer { lock_id: String, new_lock: LockInfo },
+    RewriteLock { lock_info: LockInfo },
+    ReleaseAllByUser { user_id: String },
+    PutNamespace { policy: NamespacePolicy },
+    DeleteNamespace { name: String },
+    NextSequence { name: String, count: u64 },
+    TakeTokens { key: String, bucket: TokenBucket, cost: u64, now: DateTime<Utc> },
+    SwapRecord { kind: String, id: String, expected: Option<String>, value: Option<String> },
+}
+
+/// 命令在状态机上的执行结果
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CommandResult {
+    pub ok: bool,
+    pub locks: Vec<LockInfo>, // 被释放或清理的锁，心跳时为更新后的锁
+    #[serde(default)]
+    pub output: Option<CommandOutput>, // 序列和限流命令的返回值
+}
+
+/// 序列和限流命令的返回值
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum CommandOutput {
+    Sequence(u64), // 分配的最后一个值
+    RateLimit(RateLimitDecision),
+}
+
+/// 只读查询，由 leader 在确认线性一致后读取本地状态机
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Query {
+    GetLock(String),
+    GetLockById(String),
+    ListLocks,
+    ListPrefix(String),
+    ListByUser { user_id: String, now: DateTime<Utc> },
+    ListByBusinessId { business_id: String, now: DateTime<Utc> },
+    CountLocks { namespace: String, now: DateTime<Utc> },
+    GetNamespace(String),
+    ListNamespaces,
+    GetRecord { kind: String, id: String },
+    ListRecords(String),
+}
+
+/// 查询结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum QueryResult {
+    Lock(Option<Box<LockInfo>>),
+    Locks(Vec<LockInfo>),
+    Count(u64),
+    Namespace(Option<NamespacePolicy>),
+    Namespaces(Vec<NamespacePolicy>),
+    Record(Option<String>),
+    Records(Vec<(String, String)>),
+}
+
+/// 客户端请求（用于转发给 leader）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ClientRequest {
+    Write(Box<Command>),
+    Read(Query),
+}
+
+/// 客户端请求的响应
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ClientResponse {
+    Write(CommandResult),
+    Read(Box<QueryResult>),
+}
+
+/// 状态机中的锁数据
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LockState {
+    locks: BTreeMap<String, LockInfo>,     // lock_key -> LockInfo
+    lock_by_id: BTreeMap<String, String>, // lock_id -> lock_key
+    #[serde(default)]
+    namespaces: BTreeMap<String, NamespacePolicy>,
+    #[serde(default)]
+    sequences: BTreeMap<String, u64>, // 序列名 -> 已分配的最后一个值
+    #[serde(default)]
+    rate_limits: BTreeMap<String, BucketState>, // 限流 key -> 令牌桶
+    #[serde(default)]
+    records: BTreeMap<String, BTreeMap<String, String>>, // 记录类别 -> id -> 内容
+    #[serde(skip)]
+    locks_by_user: BTreeMap<String, BTreeSet<String>>, // user_id -> lock_key，安装快照后重建
+    #[serde(skip)]
+    locks_by_business_id: BTreeMap<String, BTreeSet<String>>, // business_id -> lock_key，安装快照后重建
+}
+
+impl LockState {
+    fn apply(&mut self, command: Command) -> CommandResult {
+        match command {
+            Command::TryAcquire { lock_info, now } => {
+                let (ok, expired) = self.try_acquire(lock_info, now);
+                CommandResult {
+                    ok,
+                    locks: expired.into_iter().collect(),
+                    output: None,
+                }
+            }
+            // 成功时 locks 依次为获取的锁和被接管的锁，失败时为当前的锁
+            Command::Takeover {
+                lock_info,
+                condition,
+                now,
+            } => match self.takeover(lock_info, &condition, now) {
+                Takeover::Acquired { lock_info, replaced } => CommandResult {
+                    ok: true,
+                    locks: std::iter::once(lock_info)
+                        .chain(replaced.map(|replaced| *replaced))
+                        .collect(),
+                    output: None,
+                },
+                Takeover::Rejected(current) => CommandResult {
+                    ok: false,
+                    locks: vec![current],
+                    output: None,
+                },
+            },
+            Command::Restore { lock_info, now } => CommandResult {
+                ok: self.restore(lock_info, now),
+                locks: Vec::new(),
+                output: None,
+            },
+            Command::UpdateHeartbeat {
+                lock_id,
+                now,
+                version,
+            } => {
+                let updated = self.update_heartbeat(&lock_id, version, now);
+                CommandResult {
+                    ok: updated.is_some(),
+                    locks: updated.into_iter().collect(),
+                    output: None,
+                }
+            }
+            Command::Release { lock_id } => {
+                let released = self.release(&lock_id);
+                CommandResult {
+                    ok: released.is_some(),
+                    locks: released.into_iter().collect(),
+                    output: None,
+                }
+            }
+            Command::ReleaseVersion { lock_id, version } => {
+                let released = self.release_version(&lock_id, version);
+                CommandResult {
+                    ok: released.is_some(),
+                    locks: released.into_iter().collect(),
+                    output: None,
+                }
+            }
+            Command::ReleaseOwned { lock_key, user_id } => {
+                let released = self.release_owned(&lock_key, &user_id);
+                CommandResult {
+                    ok: released.is_some(),
+                    locks: released.into_iter().collect(),
+                    output: None,
+                }
+            }
+            Command::CleanupExpired { now } => CommandResult {
+                ok: true,
+                locks: self.cleanup_expired(now),
+                output: None,
+            },
+            Command::ForceRelease { lock_key } => {
+                let released = self.force_release(&lock_key);
+                CommandResult {
+                    ok: released.is_some(),
+                    locks: released.into_iter().collect(),
+                    output: None,
+                }
+            }
+            Command::MarkPreempted {
+                lock_id,
+                notice,
+                now,
+            } => {
+                let marked = self.mark_preempted(&lock_id, notice, now);
+                CommandResult {
+                    ok: marked.is_some(),
+                    locks: marked.into_iter().collect(),
+                    output: None,
+                }
+            }
+            Command::Transfer { lock_id, new_lock } => {
+                let replaced = self.transfer(&lock_id, new_lock);
+                CommandResult {
+                    ok: replaced.is_some(),
+                    locks: replaced.into_iter().collect(),
+                    output: None,
+                }
+            }
+            Command::SwapRecord { kind, id, expected, value } => CommandResult {
+                ok: self.swap_record(kind, id, expected, value),
+                locks: Vec::new(),
+                output: None,
+            },
+            Command::RewriteLock { lock_info } => CommandResult {
+                ok: self.rewrite_lock(lock_info),
+                locks: Vec::new(),
+                output: None,
+            },
+            Command::ReleaseAllByUser { user_id } => CommandResult {
+                ok: true,
+                locks: self.release_all_by_user(&user_id),
+                output: None,
+            },
+            Command::PutNamespace { policy } => {
+                self.namespaces.insert(policy.name.clone(), policy);
+                CommandResult {
+                    ok: true,
+                    locks: Vec::new(),
+                    output: None,
+                }
+            }
+            Command::DeleteNamespace { name } => CommandResult {
+                ok: self.namespaces.remove(&name).is_some(),
+                locks: Vec::new(),
+                output: None,
+            },
+            // 溢出时 ok 为 false，序列保持不变
+            Command::NextSequence { name, count } => {
+                let value = self.sequences.entry(name).or_insert(0);
+                let next = value.checked_add(count);
+                if let Some(next) = next {
+                    *value = next;
+                }
+                CommandResult {
+                    ok: next.is_some(),
+                    locks: Vec::new(),
+                    output: next.map(CommandOutput::Sequence),
+                }
+            }
+            Command::TakeTokens {
+                key,
+                bucket,
+                cost,
+                now,
+            } => {
+                let decision = self
+                    .rate_limits
+                    .entry(key)
+                    .or_insert_with(|| BucketState::full(bucket, now))
+                    .take(bucket, cost, now);
+                CommandResult {
+                    ok: decision.allowed,
+                    locks: Vec::new(),
+                    output: Some(CommandOutput::RateLimit(decision)),
+                }
+            }
+        }
+    }
+
+    fn query(&self, query: &Query) -> QueryResult {
+        match query {
+            Query::GetLock(lock_key) => {
+                QueryResult::Lock(self.locks.get(lock_key).cloned().map(Box::new))
+            }
+            Query::GetLockById(lock_id) => QueryResult::Lock(
+                self.lock_by_id
+                    .get(lock_id)
+                    .and_then(|lock_key| self.locks.get(lock_key))
+                    .filter(|lock_info| &lock_info.lock_id == lock_id)
+                    .cloned()
+                    .map(Box::new),
+            ),
+            Query::ListLocks => QueryResult::Locks(self.locks.values().cloned().collect()),
+            Query::ListPrefix(key_prefix) => QueryResult::Locks(
+                self.locks
+                    .range(key_prefix.clone()..)
+                    .take_while(|(lock_key, _)| lock_key.starts_with(key_prefix.as_str()))
+                    .map(|(_, lock_info)| lock_info.clone())
+                    .collect(),
+            ),
+            Query::ListByUser { user_id, now } => {
+                QueryResult::Locks(
+                    self.locks_by_user
+                        .get(user_id)
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|lock_key| self.locks.get(lock_key))
+                        .filter(|lock_info| !lock_info.is_expired_at(*now))
+                        .cloned()
+                        .collect(),
+                )
+            }
+            Query::ListByBusinessId { business_id, now } => {
+                QueryResult::Locks(
+                    self.locks_by_business_id
+                        .get(business_id)
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|lock_key| self.locks.get(lock_key))
+                        .filter(|lock_info| !lock_info.is_expired_at(*now))
+                        .cloned()
+                        .collect(),
+                )
+            }
+            Query::CountLocks { namespace, now } => {
+                QueryResult::Count(
+                    self.locks
+                        .values()
+                        .filter(|lock_info| {
+                            &lock_info.namespace == namespace && !lock_info.is_expired_at(*now)
+                        })
+                        .count() as u64,
+                )
+            }
+            Query::GetNamespace(name) => QueryResult::Namespace(self.namespaces.get(name).cloned()),
+            Query::ListNamespaces => {
+                QueryResult::Namespaces(self.namespaces.values().cloned().collect())
+            }
+            Query::GetRecord { kind, id } => {
+                QueryResult::Record(self.records.get(kind).and_then(|records| records.get(id)).cloned())
+            }
+            Query::ListRecords(kind) => QueryResult::Records(
+                self.records
+                    .get(kind)
+                    .map(|records| records.iter().map(|(id, value)| (id.clone(), value.clone())).collect())
+                    .unwrap_or_default(),
+            ),
+        }
+    }
+
+    fn swap_record(&mut self, kind: String, id: String, expected: Option<String>, value: Option<String>) -> bool {
+        let current = self.records.get(&kind).and_then(|records| records.get(&id));
+        if current != expected.as_ref() {
+            return false;
+        }
+        match value {
+            Some(value) => {
+                self.records.entry(kind).or_default().insert(id, value);
+            }
+            None => {
+                if let Some(records) = self.records.get_mut(&kind) {
+                    records.remove(&id);
+                    if records.is_empty() {
+                        self.records.remove(&kind);
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    /// 根据锁数据重建持有人索引和 business_id 索引
+    fn rebuild_indexes(&mut self) {
+        self.locks_by_user.clear();
+        self.locks_by_business_id.clear();
+        for (lock_key, lock_info) in &self.locks {
+            self.locks_by_user
+                .entry(lock_info.user_id.clone())
+                .or_default()
+                .insert(lock_key.clone());
+            self.locks_by_business_id
+                .entry(lock_info.business_id.clone())
+                .or_default()
+                .insert(lock_key.clone());
+        }
+    }
+
+    fn index_lock(&mut self, lock_info: &LockInfo) {
+        self.locks_by_user
+            .entry(lock_info.user_id.clone())
+            .or_default()
+            .insert(lock_info.get_lock_key());
+        self.locks_by_business_id
+            .entry(lock_info.business_id.clone())
+            .or_default()
+            .insert(lock_info.get_lock_key());
+    }
+
+    fn unindex_lock(&mut self, lock_info: &LockInfo) {
+        if let Some(lock_keys) = self.locks_by_user.get_mut(&lock_info.user_id) {
+            lock_keys.remove(&lock_info.get_lock_key());
+            if lock_keys.is_empty() {
+                self.locks_by_user.remove(&lock_info.user_id);
+            }
+        }
+        if let Some(lock_keys) = self.locks_by_business_id.get_mut(&lock_info.business_id) {
+            lock_keys.remove(&lock_info.get_lock_key());
+            if lock_keys.is_empty() {
+                self.locks_by_business_id.remove(&lock_info.business_id);
+            }
+        }
+    }
+
+    /// 尝试获取锁，返回是否成功以及被替换的过期锁
+    fn try_acquire(&mut self, lock_info: LockInfo, now: DateTime<Utc>) -> (bool, Option<LockInfo>) {
+        let lock_key = lock_info.get_lock_key();
+        let mut expired = None;
+
+        if let Some(existing_lock) = self.locks.get_mut(&lock_key) {
+            if existing_lock.is_expired_at(now) {
+                log::info!(
+                    "[EXPIRED] Lock expired and removed - lock_id: {}, namespace: {}, business_id: {}, user_id: {}, user_name: {}",
+                    existing_lock.lock_id, existing_lock.namespace, existing_lock.business_id,
+                    existing_lock.user_id, existing_lock.user_name
+                );
+                let old_lock_id = existing_lock.lock_id.clone();
+                self.lock_by_id.remove(&old_lock_id);
+                expired = self.locks.remove(&lock_key);
+                if let Some(expired_lock) = &expired {
+                    self.unindex_lock(expired_lock);
+                }
+            } else if existing_lock.user_id == lock_info.user_id {
+                // 同一个用户重复申请，更新心跳时间
+                existing_lock.last_heartbeat = now;
+                existing_lock.version += 1;
+                log::info!(
+                    "[REENTRANT] Same user re-acquiring lock - lock_id: {}, namespace: {}, business_id: {}, user_id: {}, user_name: {}",
+                    existing_lock.lock_id, existing_lock.namespace, existing_lock.business_id,
+                    existing_lock.user_id, existing_lock.user_name
+                );
+                return (true, None);
+            } else {
+                return (false, None);
+            }
+        }
+
+        self.lock_by_id
+            .insert(lock_info.lock_id.clone(), lock_key.clone());
+        self.index_lock(&lock_info);
+        self.locks.insert(lock_key, lock_info);
+        (true, expired)
+    }
+
+    fn takeover(
+        &mut self,
+        mut lock_info: LockInfo,
+        condition: &LockCondition,
+        now: DateTime<Utc>,
+    ) -> Takeover {
+        let lock_key = lock_info.get_lock_key();
+        let replaced = match self.locks.get(&lock_key) {
+            Some(current) if !condition.matches(current, now) => {
+                return Takeover::Rejected(current.clone())
+            }
+            Some(current) => {
+                lock_info.version = current.version + 1;
+                self.locks.remove(&lock_key)
+            }
+            None => None,
+        };
+        if let Some(replaced) = &replaced {
+            log::info!(
+                "[TAKEOVER] Expired lock taken over - lock_id: {}, namespace: {}, business_id: {}, from: {}, to: {}, version: {}",
+                lock_info.lock_id, lock_info.namespace, lock_info.business_id,
+                replaced.user_id, lock_info.user_id, lock_info.version
+            );
+            self.lock_by_id.remove(&replaced.lock_id);
+            self.unindex_lock(replaced);
+        }
+        self.lock_by_id
+            .insert(lock_info.lock_id.clone(), lock_key.clone());
+        self.index_lock(&lock_info);
+        self.locks.insert(lock_key, lock_info.clone());
+        Takeover::Acquired {
+            lock_info,
+            replaced: replaced.map(Box::new),
+        }
+    }
+
+    fn restore(&mut self, lock_info: LockInfo, now: DateTime<Utc>) -> bool {
+        let lock_key = lock_info.get_lock_key();
+        if let Some(existing_lock) = self.locks.get(&lock_key) {
+            if !existing_lock.is_expired_at(now) {
+                return false;
+            }
+        }
+        if let Some(replaced) = self.locks.remove(&lock_key) {
+            self.lock_by_id.remove(&replaced.lock_id);
+            self.unindex_lock(&replaced);
+        }
+        self.lock_by_id
+            .insert(lock_info.lock_id.clone(), lock_key.clone());
+        self.index_lock(&lock_info);
+        self.locks.insert(lock_key, lock_info);
+        true
+    }
+
+    fn update_heartbeat(
+        &mut self,
+        lock_id: &str,
+        version: Option<u64>,
+        now: DateTime<Utc>,
+    ) -> Option<LockInfo> {
+        let lock_key = self.lock_by_id.get(lock_id)?;
+
+        match self.locks.get_mut(lock_key) {
+            // 已过期（含达到最长持有时间）的锁不再续期，与获取时的判断一致
+            Some(lock_info)
+                if lock_info.lock_id == lock_id
+                    && !lock_info.is_expired_at(now)
+                    && version.is_none_or(|version| version == lock_info.version) =>
+            {
+                lock_info.last_heartbeat = now;
+                lock_info.version += 1;
+                Some(lock_info.clone())
+            }
+            _ => None,
+        }
+    }
+
+    fn release(&mut self, lock_id: &str) -> Option<LockInfo> {
+        let lock_key = self.lock_by_id.get(lock_id)?.clone();
+
+        match self.locks.get(&lock_key) {
+            Some(lock_info) if lock_info.lock_id == lock_id => {
+                log::info!(
+                    "[RELEASE] Releasing lock - lock_id: {}, namespace: {}, business_id: {}, user_id: {}, user_name: {}",
+                    lock_info.lock_id, lock_info.namespace, lock_info.business_id,
+                    lock_info.user_id, lock_info.user_name
+                );
+                self.lock_by_id.remove(lock_id);
+                let lock_info = self.locks.remove(&lock_key)?;
+                self.unindex_lock(&lock_info);
+                Some(lock_info)
+            }
+            _ => None,
+        }
+    }
+
+    fn release_version(&mut self, lock_id: &str, version: u64) -> Option<LockInfo> {
+        let lock_key = self.lock_by_id.get(lock_id)?;
+        match self.locks.get(lock_key) {
+            Some(lock_info) if lock_info.version == version => self.release(lock_id),
+            _ => None,
+        }
+    }
+
+    fn release_owned(&mut self, lock_key: &str, user_id: &str) -> Option<LockInfo> {
+        match self.locks.get(lock_key) {
+            Some(lock_info) if lock_info.user_id == user_id => {
+                log::info!(
+                    "[RELEASE] Releasing lock by key - lock_id: {}, namespace: {}, business_id: {}, user_id: {}, user_name: {}",
+                    lock_info.lock_id, lock_info.namespace, lock_info.business_id,
+                    lock_info.user_id, lock_info.user_name
+                );
+                let lock_info = self.locks.remove(lock_key)?;
+                self.lock_by_id.remove(&lock_info.lock_id);
+                self.unindex_lock(&lock_info);
+                Some(lock_info)
+            }
+            _ => None,
+        }
+    }
+
+    fn cleanup_expired(&mut self, now: DateTime<Utc>) -> Vec<LockInfo> {
+        let expired: Vec<String> = self
+            .locks
+            .iter()
+            .filter(|(_, lock_info)| lock_info.is_expired_at(now))
+            .map(|(lock_key, _)| lock_key.clone())
+            .collect();
+
+        let mut removed = Vec::with_capacity(expired.len());
+        for lock_key in expired {
+            if let Some(lock_info) = self.locks.remove(&lock_key) {
+                log::info!(
+                    "[EXPIRED CLEANUP] Removed expired lock - lock_id: {}, namespace: {}, business_id: {}, user_id: {}, user_name: {}, locked_at: {}",
+                    lock_info.lock_id, lock_info.namespace, lock_info.business_id,
+                    lock_info.user_id, lock_info.user_name, lock_info.locked_at
+                );
+                self.lock_by_id.remove(&lock_info.lock_id);
+                self.unindex_lock(&lock_info);
+                removed.push(lock_info);
+            }
+        }
+        // 已补满的令牌桶与不存在等价
+        self.rate_limits.retain(|_, state| !state.is_full_at(now));
+        removed
+    }
+
+    fn force_release(&mut self, lock_key: &str) -> Option<LockInfo> {
+        let lock_info = self.locks.remove(lock_key)?;
+        self.lock_by_id.remove(&lock_info.lock_id);
+        self.unindex_lock(&lock_info);
+        log::warn!(
+            "[FORCE RELEASE] Lock force released - lock_id: {}, namespace: {}, business_id: {}, user_id: {}, user_name: {}",
+            lock_info.lock_id, lock_info.namespace, lock_info.business_id,
+            lock_info.user_id, lock_info.user_name
+        );
+        Some(lock_info)
+    }
+
+    fn release_all_by_user(&mut self, user_id: &str) -> Vec<LockInfo> {
+        let lock_keys: Vec<String> = self
+            .locks_by_user
+            .get(user_id)
+            .map(|lock_keys| lock_keys.iter().cloned().collect())
+            .unwrap_or_default();
+        lock_keys
+            .iter()
+            .filter_map(|lock_key| self.release_owned(lock_key, user_id))
+            .collect()
+    }
+
+    fn mark_preempted(
+        &mut self,
+        lock_id: &str,
+        notice: PreemptionNotice,
+        now: DateTime<Utc>,
+    ) -> Option<LockInfo> {
+        let lock_key = self.lock_by_id.get(lock_id)?;
+
+        match self.locks.get_mut(lock_key) {
+            Some(lock_info) if lock_info.lock_id == lock_id && !lock_info.is_expired_at(now) => {
+                if lock_info.preemption.is_none() {
+                    lock_info.preemption = Some(notice);
+                    lock_info.version += 1;
+                }
+                Some(lock_info.clone())
+            }
+            _ => None,
+        }
+    }
+
+    fn transfer(&mut self, lock_id: &str, mut new_lock: LockInfo) -> Option<LockInfo> {
+        let lock_key = new_lock.get_lock_key();
+        let lock_info = self.locks.get_mut(&lock_key)?;
+        if lock_info.lock_id != lock_id {
+            return None;
+        }
+        new_lock.version = lock_info.version + 1;
+        self.lock_by_id
+            .insert(new_lock.lock_id.clone(), lock_key);
+        let old_lock = std::mem::replace(lock_info, new_lock.clone());
+        self.lock_by_id.remove(&old_lock.lock_id);
+        self.unindex_lock(&old_lock);
+        self.index_lock(&new_lock);
+        log::warn!(
+            "[PREEMPT] Lock transferred - lock_id: {}, namespace: {}, business_id: {}, user_id: {}, user_name: {}",
+            old_lock.lock_id, old_lock.namespace, old_lock.business_id,
+            old_lock.user_id, old_lock.user_name
+        );
+        Some(old_lock)
+    }
+
+    fn rewrite_lock(&mut self, lock_info: LockInfo) -> bool {
+        match self.locks.get_mut(&lock_info.get_lock_key()) {
+            Some(current) if current.lock_id == lock_info.lock_id && current.version == lock_info.version => {
+                *current = lock_info;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// 日志存储
+// ---------------------------------------------------------------------------
+
+/// Raft 持久化目录：投票、已提交位置、日志和快照，写入并 fsync 后才返回
+#[derive(Debug, Clone)]
+struct RaftDir {
+    path: PathBuf,
+}
+
+impl RaftDir {
+    const VOTE: &'static str = "vote.json";
+    const COMMITTED: &'static str = "committed.json";
+    const PURGED: &'static str = "purged.json";
+    const LOG: &'static str = "log.jsonl";
+    const SNAPSHOT: &'static str = "snapshot";
+
+    fn open(path: &Path) -> io::Result<Self> {
+        fs::create_dir_all(path)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+        })
+    }
+
+    fn read(&self, name: &str) -> io::Result<Option<Vec<u8>>> {
+        match fs::read(self.path.join(name)) {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn read_json<T: DeserializeOwned>(&self, name: &str) -> io::Result<Option<T>> {
+        self.read(name)?
+            .map(|data| serde_json::from_slice(&data).map_err(io::Error::other))
+            .transpose()
+    }
+
+    /// 写入临时文件后重命名，中途崩溃时保留旧文件
+    fn write(&self, name: &str, data: &[u8]) -> io::Result<()> {
+        let path = self.path.join(name);
+        let temp_path = path.with_extension("tmp");
+        let mut file = File::create(&temp_path)?;
+        file.write_all(data)?;
+        file.sync_all()?;
+        fs::rename(&temp_path, &path)?;
+        // 重命名本身也需落盘
+        File::open(&self.path)?.sync_all()
+    }
+
+    fn write_json<T: Serialize>(&self, name: &str, value: &T) -> io::Result<()> {
+        self.write(name, &serde_json::to_vec(value).map_err(io::Error::other)?)
+    }
+
+    fn append_log(&self, entries: &[Entry<TypeConfig>]) -> io::Result<()> {
+        let mut data = Vec::new();
+        for entry in entries {
+            serde_json::to_writer(&mut data, entry).map_err(io::Error::other)?;
+            data.push(b'\n');
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(self.path.join(Self::LOG))?;
+        file.write_all(&data)?;
+        file.sync_data()
+    }
+
+    fn rewrite_log<'a>(&self, entries: impl Iterator<Item = &'a Entry<TypeConfig>>) -> io::Result<()> {
+        let mut data = Vec::new();
+        for entry in entries {
+            serde_json::to_writer(&mut data, entry).map_err(io::Error::other)?;
+            data.push(b'\n');
+        }
+        self.write(Self::LOG, &data)
+    }
+
+    /// 读取日志，末尾写到一半的条目（追加时崩溃）视为未写入
+    fn read_log(&self) -> io::Result<Vec<Entry<TypeConfig>>> {
+        let Some(data) = self.read(Self::LOG)? else {
+            return Ok(Vec::new());
+        };
+        let lines: Vec<&[u8]> = data.split(|byte| *byte == b'\n').filter(|line| !line.is_empty()).collect();
+        let mut entries = Vec::with_capacity(lines.len());
+        for (i, line) in lines.iter().enumerate() {
+            match serde_json::from_slice(line) {
+                Ok(entry) => entries.push(entry),
+                Err(e) if i + 1 == lines.len() => {
+                    log::warn!("[RAFT] Discarding torn log entry at the end of {}: {}", Self::LOG, e);
+                }
+                Err(e) => return Err(io::Error::other(e)),
+            }
+        }
+        Ok(entries)
+    }
+
+    /// 快照文件：第一行为元数据，其后为状态机数据
+    fn read_snapshot(&self) -> io::Result<Option<StoredSnapshot>> {
+        let Some(data) = self.read(Self::SNAPSHOT)? else {
+            return Ok(None);
+        };
+        let split = data
+            .iter()
+            .position(|byte| *byte == b'\n')
+            .ok_or_else(|| io::Error::other("Snapshot file has no metadata line"))?;
+        Ok(Some(StoredSnapshot {
+            meta: serde_json::from_slice(&data[..split]).map_err(io::Error::other)?,
+            data: data[split + 1..].to_vec(),
+        }))
+    }
+
+    fn write_snapshot(&self, snapshot: &StoredSnapshot) -> io::Result<()> {
+        let mut data = serde_json::to_vec(&snapshot.meta).map_err(io::Error::other)?;
+        data.push(b'\n');
+        data.extend_from_slice(&snapshot.data);
+        self.write(Self::SNAPSHOT, &data)
+    }
+}
+
+#[derive(Debug, Default)]
+struct LogStoreInner {
+    last_purged_log_id: Option<LogId<NodeId>>,
+    log: BTreeMap<u64, Entry<TypeConfig>>,
+    committed: Option<LogId<NodeId>>,
+    vote: Option<Vote<NodeId>>,
+}
+
+/// 日志存储，内存中保留未清除的日志，每次修改先写入磁盘再确认
+#[derive(Debug, Clone)]
+pub struct LogStore {
+    inner: Arc<Mutex<LogStoreInner>>,
+    dir: RaftDir,
+}
+
+impl LogStore {
+    /// 从持久化目录加载投票、已提交位置和日志
+    fn open(dir: RaftDir) -> io::Result<Self> {
+        let last_purged_log_id: Option<LogId<NodeId>> = dir.read_json(RaftDir::PURGED)?;
+        let purged_index = last_purged_log_id.map(|log_id| log_id.index);
+        let log: BTreeMap<u64, Entry<TypeConfig>> = dir
+            .read_log()?
+            .into_iter()
+            .filter(|entry| purged_index.is_none_or(|index| entry.log_id.index > index))
+            .map(|entry| (entry.log_id.index, entry))
+            .collect();
+        // 去掉被截断或已清除的条目，之后从干净的文件末尾追加
+        dir.rewrite_log(log.values())?;
+
+        let inner = LogStoreInner {
+            last_purged_log_id,
+            committed: dir.read_json(RaftDir::COMMITTED)?,
+            vote: dir.read_json(RaftDir::VOTE)?,
+            log,
+        };
+        log::info!(
+            "[RAFT] Loaded {} log entries from {} - vote: {:?}, committed: {:?}",
+            inner.log.len(),
+            dir.path.display(),
+            inner.vote,
+            inner.committed
+        );
+        Ok(Self {
+            inner: Arc::new(Mutex::new(inner)),
+            dir,
+        })
+    }
+}
+
+impl RaftLogReader<TypeConfig> for LogStore {
+    async fn try_get_log_entries<RB: RangeBounds<u64> + Clone + Debug + Send>(
+        &mut self,
+        range: RB,
+    ) -> Result<Vec<Entry<TypeConfig>>, StorageError<NodeId>> {
+        let inner = self.inner.lock();
+        Ok(inner.log.range(range).map(|(_, entry)| entry.clone()).collect())
+    }
+}
+
+impl RaftLogStorage<TypeConfig> for LogStore {
+    type LogReader = Self;
+
+    async fn get_log_state(&mut self) -> Result<LogState<TypeConfig>, StorageError<NodeId>> {
+        let inner = self.inner.lock();
+        let last_log_id = inner
+            .log
+            .values()
+            .next_back()
+            .map(|entry| entry.log_id)
+            .or(inner.last_purged_log_id);
+
+        Ok(LogState {
+            last_purged_log_id: inner.last_purged_log_id,
+            last_log_id,
+        })
+    }
+
+    async fn get_log_reader(&mut self) -> Self::LogReader {
+        self.clone()
+    }
+
+    async fn save_vote(&mut self, vote: &Vote<NodeId>) -> Result<(), StorageError<NodeId>> {
+        let mut inner = self.inner.lock();
+        self.dir
+            .write_json(RaftDir::VOTE, vote)
+            .map_err(|e| StorageIOError::write_vote(&e))?;
+        inner.vote = Some(*vote);
+        Ok(())
+    }
+
+    async fn read_vote(&mut self) -> Result<Option<Vote<NodeId>>, StorageError<NodeId>> {
+        Ok(self.inner.lock().vote)
+    }
+
+    async fn save_committed(
+        &mut self,
+        committed: Option<LogId<NodeId>>,
+    ) -> Result<(), StorageError<NodeId>> {
+        let mut inner = self.inner.lock();
+        self.dir
+            .write_json(RaftDir::COMMITTED, &committed)
+            .map_err(|e| StorageIOError::write(&e))?;
+        inner.committed = committed;
+        Ok(())
+    }
+
+    async fn read_committed(&mut self) -> Result<Option<LogId<NodeId>>, StorageError<NodeId>> {
+        Ok(self.inner.lock().committed)
+    }
+
+    async fn append<I>(
+        &mut self,
+        entries: I,
+        callback: LogFlushed<TypeConfig>,
+    ) -> Result<(), StorageError<NodeId>>
+    where
+        I: IntoIterator<Item = Entry<TypeConfig>> + Send,
+        I::IntoIter: Send,
+    {
+        let entries: Vec<Entry<TypeConfig>> = entries.into_iter().collect();
+        {
+            let mut inner = self.inner.lock();
+            self.dir
+                .append_log(&entries)
+                .map_err(|e| StorageIOError::write_logs(&e))?;
+            for entry in entries {
+                inner.log.insert(entry.log_id.index, entry);
+            }
+        }
+        // 已写入磁盘，通知 openraft 可以确认
+        callback.log_io_completed(Ok(()));
+        Ok(())
+    }
+
+    async fn truncate(&mut self, log_id: LogId<NodeId>) -> Result<(), StorageError<NodeId>> {
+        let mut inner = self.inner.lock();
+        inner.log.split_off(&log_id.index);
+        self.dir
+            .rewrite_log(inner.log.values())
+            .map_err(|e| StorageIOError::write_logs(&e))?;
+        Ok(())
+    }
+
+    async fn purge(&mut self, log_id: LogId<NodeId>) -> Result<(), StorageError<NodeId>> {
+        let mut inner = self.inner.lock();
+        // 先记录清除位置，重写日志前崩溃时加载会跳过已清除的条目
+        self.dir
+            .write_json(RaftDir::PURGED, &log_id)
+            .map_err(|e| StorageIOError::write_logs(&e))?;
+        inner.log = inner.log.split_off(&(log_id.index + 1));
+        inner.last_purged_log_id = Some(log_id);
+        self.dir
+            .rewrite_log(inner.log.values())
+            .map_err(|e| StorageIOError::write_logs(&e))?;
+        Ok(())
+    }
+}
+
+// ---------------------------------------------------------------------------
+// 状态机
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Default)]
+struct StateMachineData {
+    last_applied: Option<LogId<NodeId>>,
+    last_membership: StoredMembership<NodeId, BasicNode>,
+    state: LockState,
+}
+
+#[derive(Debug, Clone)]
+struct StoredSnapshot {
+    meta: SnapshotMeta<NodeId, BasicNode>,
+    data: Vec<u8>,
+}
+
+#[derive(Debug, Default)]
+struct StateMachineInner {
+    data: RwLock<StateMachineData>,
+    current_snapshot: Mutex<Option<StoredSnapshot>>,
+    snapshot_idx: AtomicU64,
+}
+
+/// 锁状态机，状态保存在内存中，快照写入磁盘；重启时从快照恢复，再由 openraft 重放已提交的日志
+#[derive(Debug, Clone)]
+pub struct StateMachineStore {
+    inner: Arc<StateMachineInner>,
+    dir: RaftDir,
+}
+
+impl StateMachineStore {
+    /// 从持久化目录中的快照恢复状态机
+    fn open(dir: RaftDir) -> io::Result<Self> {
+        let inner = StateMachineInner::default();
+        if let Some(snapshot) = dir.read_snapshot()? {
+            let mut state: LockState = serde_json::from_slice(&snapshot.data).map_err(io::Error::other)?;
+            state.rebuild_indexes();
+            log::info!(
+                "[RAFT] Loaded snapshot {} with {} locks",
+                snapshot.meta.snapshot_id,
+                state.locks.len()
+            );
+            *inner.data.write() = StateMachineData {
+                last_applied: snapshot.meta.last_log_id,
+                last_membership: snapshot.meta.last_membership.clone(),
+                state,
+            };
+            *inner.current_snapshot.lock() = Some(snapshot);
+        }
+        Ok(Self {
+            inner: Arc::new(inner),
+            dir,
+        })
+    }
+
+    /// 在本地状态机上执行查询
+    fn query(&self, query: &Query) -> QueryResult {
+        self.inner.data.read().state.query(query)
+    }
+}
+
+impl RaftSnapshotBuilder<TypeConfig> for StateMachineStore {
+    async fn build_snapshot(&mut self) -> Result<Snapshot<TypeConfig>, StorageError<NodeId>> {
+        let (data, last_applied, last_membership) = {
+            let sm = self.inner.data.read();
+            let data = serde_json::to_vec(&sm.state)
+                .map_err(|e| StorageIOError::read_state_machine(&e))?;
+            (data, sm.last_applied, sm.last_membership.clone())
+        };
+
+        let snapshot_idx = self.inner.snapshot_idx.fetch_add(1, Ordering::Relaxed) + 1;
+        let snapshot_id = match last_applied {
+            Some(log_id) => format!("{}-{}-{}", log_id.leader_id, log_id.index, snapshot_idx),
+            None => format!("--{}", snapshot_idx),
+        };
+
+        let meta = SnapshotMeta {
+            last_log_id: last_applied,
+            last_membership,
+            snapshot_id,
+        };
+
+        let stored = StoredSnapshot {
+            meta: meta.clone(),
+            data: data.clone(),
+        };
+        // 快照写入磁盘后 openraft 才会清除其中的日志
+        self.dir
+            .write_snapshot(&stored)
+            .map_err(|e| StorageIOError::write_snapshot(Some(meta.signature()), &e))?;
+        *self.inner.current_snapshot.lock() = Some(stored);
+
+        Ok(Snapshot {
+            meta,
+            snapshot: Box::new(Cursor::new(data)),
+        })
+    }
+}
+
+impl RaftStateMachine<TypeConfig> for StateMachineStore {
+    type SnapshotBuilder = Self;
+
+    async fn applied_state(
+        &mut self,
+    ) -> Result<(Option<LogId<NodeId>>, StoredMembership<NodeId, BasicNode>), StorageError<NodeId>>
+    {
+        let sm = self.inner.data.read();
+        Ok((sm.last_applied, sm.last_membership.clone()))
+    }
+
+    async fn apply<I>(&mut self, entries: I) -> Result<Vec<CommandResult>, StorageError<NodeId>>
+    where
+        I: IntoIterator<Item = Entry<TypeConfig>> + Send,
+        I::IntoIter: Send,
+    {
+        let mut sm = self.inner.data.write();
+        let mut results = Vec::new();
+
+        for entry in entries {
+            sm.last_applied = Some(entry.log_id);
+            match entry.payload {
+                EntryPayload::Blank => results.push(CommandResult::default()),
+                EntryPayload::Normal(command) => results.push(sm.state.apply(command)),
+                EntryPayload::Membership(membership) => {
+                    sm.last_membership = StoredMembership::new(Some(entry.log_id), membership);
+                    results.push(CommandResult::default());
+                }
+            }
+        }
+        Ok(results)
+    }
+
+    async fn get_snapshot_builder(&mut self) -> Self::SnapshotBuilder {
+        self.clone()
+    }
+
+    async fn begin_receiving_snapshot(
+        &mut self,
+    ) -> Result<Box<Cursor<Vec<u8>>>, StorageError<NodeId>> {
+        Ok(Box::new(Cursor::new(Vec::new())))
+    }
+
+    async fn install_snapshot(
+        &mut self,
+        meta: &SnapshotMeta<NodeId, BasicNode>,
+        snapshot: Box<Cursor<Vec<u8>>>,
+    ) -> Result<(), StorageError<NodeId>> {
+        let data = snapshot.into_inner();
+        let mut state: LockState = serde_json::from_slice(&data)
+            .map_err(|e| StorageIOError::read_snapshot(Some(meta.signature()), &e))?;
+        state.rebuild_indexes();
+
+        log::info!(
+            "[RAFT] Installing snapshot {} with {} locks",
+            meta.snapshot_id,
+            state.locks.len()
+        );
+
+        let stored = StoredSnapshot {
+            meta: meta.clone(),
+            data,
+        };
+        self.dir
+            .write_snapshot(&stored)
+            .map_err(|e| StorageIOError::write_snapshot(Some(meta.signature()), &e))?;
+
+        {
+            let mut sm = self.inner.data.write();
+            sm.last_applied = meta.last_log_id;
+            sm.last_membership = meta.last_membership.clone();
+            sm.state = state;
+        }
+
+        *self.inner.current_snapshot.lock() = Some(stored);
+        Ok(())
+    }
+
+    async fn get_current_snapshot(
+        &mut self,
+    ) -> Result<Option<Snapshot<TypeConfig>>, StorageError<NodeId>> {
+        Ok(self
+            .inner
+            .current_snapshot
+            .lock()
+            .clone()
+            .map(|stored| Snapshot {
+                meta: stored.meta,
+                snapshot: Box::new(Cursor::new(stored.data)),
+            }))
+    }
+}
+
+// ---------------------------------------------------------------------------
+// 节点间网络
+// ---------------------------------------------------------------------------
+
+/// 基于 HTTP 的 Raft 网络
+pub struct Network {
+    client: reqwest::Client,
+    peer_token: String,
+}
+
+impl RaftNetworkFactory<TypeConfig> for Network {
+    type Network = NetworkConnection;
+
+    async fn new_client(&mut self, target: NodeId, node: &BasicNode) -> Self::Network {
+        NetworkConnection {
+            client: self.client.clone(),
+            peer_token: self.peer_token.clone(),
+            target,
+            addr: node.addr.clone(),
+        }
+    }
+}
+
+pub struct NetworkConnection {
+    client: reqwest::Client,
+    peer_token: String,
+    target: NodeId,
+    addr: String,
+}
+
+impl NetworkConnection {
+    async fn send<Req, Resp, Err>(
+        &self,
+        path: &str,
+        req: Req,
+        option: RPCOption,
+    ) -> Result<Resp, RPCError<NodeId, BasicNode, Err>>
+    where
+        Req: Serialize,
+        Resp: DeserializeOwned,
+        Err: std::error::Error + DeserializeOwned,
+    {
+        let url = format!("http://{}/raft/{}", self.addr, path);
+        let response = self
+            .client
+            .post(url)
+            .timeout(option.hard_ttl())
+            .bearer_auth(&self.peer_token)
+            .json(&req)
+            .send()
+            .await
+            .map_err(|e| {
+                if e.is_connect() {
+                    RPCError::Unreachable(Unreachable::new(&e))
+                } else {
+                    RPCError::Network(NetworkError::new(&e))
+                }
+            })?;
+
+        let result: Result<Resp, Err> = response
+            .json()
+            .await
+            .map_err(|e| RPCError::Network(NetworkError::new(&e)))?;
+
+        result.map_err(|e| RPCError::RemoteError(RemoteError::new(self.target, e)))
+    }
+}
+
+impl RaftNetwork<TypeConfig> for NetworkConnection {
+    async fn append_entries(
+        &mut self,
+        rpc: AppendEntriesRequest<TypeConfig>,
+        option: RPCOption,
+    ) -> Result<AppendEntriesResponse<NodeId>, RPCError<NodeId, BasicNode, RaftError<NodeId>>> {
+        self.send("append", rpc, option).await
+    }
+
+    async fn install_snapshot(
+        &mut self,
+        rpc: InstallSnapshotRequest<TypeConfig>,
+        option: RPCOption,
+    ) -> Result<
+        InstallSnapshotResponse<NodeId>,
+        RPCError<NodeId, BasicNode, RaftError<NodeId, InstallSnapshotError>>,
+    > {
+        self.send("snapshot", rpc, option).await
+    }
+
+    async fn vote(
+        &mut self,
+        rpc: VoteRequest<NodeId>,
+        option: RPCOption,
+    ) -> Result<VoteResponse<NodeId>, RPCError<NodeId, BasicNode, RaftError<NodeId>>> {
+        self.send("vote", rpc, option).await
+    }
+}
+
+// ---------------------------------------------------------------------------
+// LockStorage 实现
+// ---------------------------------------------------------------------------
+
+/// Raft 集群参数
+#[derive(Debug, Clone)]
+pub struct RaftOptions {
+    pub node_id: NodeId,
+    pub members: BTreeMap<NodeId, String>, // node_id -> host:port
+    pub heartbeat_interval_ms: u64,
+    pub election_timeout_ms: u64,
+    pub snapshot_logs: u64,
+    pub data_dir: PathBuf,  // 投票、日志和快照的保存目录
+    pub peer_token: String, // 节点间 `/raft/*` 接口的认证令牌
+}
+
+pub struct RaftStorage {
+    node_id: NodeId,
+    raft: Raft<TypeConfig>,
+    state_machine: StateMachineStore,
+    client: reqwest::Client,
+    stats: StatsCounters, // 本节点处理的请求计数
+    clock: Arc<dyn Clock>,
+    peer_token: String,
+}
+
+impl RaftStorage {
+    pub async fn new(options: RaftOptions) -> Result<Self> {
+        let config = openraft::Config {
+            cluster_name: "fe-lock-service".to_string(),
+            heartbeat_interval: options.heartbeat_interval_ms,
+            election_timeout_min: options.election_timeout_ms,
+            election_timeout_max: options.election_timeout_ms * 2,
+            snapshot_policy: SnapshotPolicy::LogsSinceLast(options.snapshot_logs),
+            snapshot_max_chunk_size: 1024 * 1024,
+            ..Default::default()
+        }
+        .validate()?;
+
+        let client = reqwest::Client::new();
+        let dir = RaftDir::open(&options.data_dir)?;
+        let log_store = LogStore::open(dir.clone())?;
+        let state_machine = StateMachineStore::open(dir)?;
+        let network = Network {
+            client: client.clone(),
+            peer_token: options.peer_token.clone(),
+        };
+
+        let raft = Raft::new(
+            options.node_id,
+            Arc::new(config),
+            network,
+            log_store,
+            state_machine.clone(),
+        )
+        .await?;
+
+        // 由 ID 最小的节点负责初始化集群成员
+        if options.members.keys().next() == Some(&options.node_id)
+            && !raft.is_initialized().await?
+        {
+            let members: BTreeMap<NodeId, BasicNode> = options
+                .members
+                .iter()
+                .map(|(id, addr)| (*id, BasicNode { addr: addr.clone() }))
+                .collect();
+            log::info!("[RAFT] Initializing cluster with members: {:?}", members);
+            if let Err(e) = raft.initialize(members).await {
+                log::warn!("[RAFT] Cluster initialization skipped: {}", e);
+            }
+        }
+
+        Ok(Self {
+            node_id: options.node_id,
+            raft,
+            state_machine,
+            client,
+            stats: StatsCounters::new(),
+            clock: clock::system(),
+            peer_token: options.peer_token,
+        })
+    }
+
+    /// 使用指定的时钟生成命令和查询的当前时间，默认为系统时钟
+    pub fn with_clock(self, clock: Arc<dyn Clock>) -> Self {
+        Self { clock, ..self }
+    }
+
+    /// 执行请求，本节点不是 leader 时转发给 leader
+    async fn execute(&self, request: ClientRequest) -> Result<ClientResponse> {
+        match self.execute_local(&request).await? {
+            Ok(response) => Ok(response),
+            Err(leader) => {
+                let leader = leader.ok_or_else(|| anyhow!("Raft cluster has no leader"))?;
+                self.forward(&leader, request).await
+            }
+        }
+    }
+
+    /// 在本节点执行请求；本节点不是 leader 时返回 leader 节点
+    async fn execute_local(
+        &self,
+        request: &ClientRequest,
+    ) -> Result<Result<ClientResponse, Option<BasicNode>>> {
+        match request {
+            ClientRequest::Write(command) => match self.raft.client_write((**command).clone()).await {
+                Ok(response) => Ok(Ok(ClientResponse::Write(response.data))),
+                Err(e) => match e.forward_to_leader() {
+                    Some(forward) => Ok(Err(forward.leader_node.clone())),
+                    None => Err(anyhow!("Raft write failed: {}", e)),
+                },
+            },
+            ClientRequest::Read(query) => match self.raft.ensure_linearizable().await {
+                Ok(_) => Ok(Ok(ClientResponse::Read(Box::new(self.state_machine.query(query))))),
+                Err(e) => match e.forward_to_leader() {
+                    Some(forward) => Ok(Err(forward.leader_node.clone())),
+                    None => Err(anyhow!("Raft read failed: {}", e)),
+                },
+            },
+        }
+    }
+
+    async fn forward(&self, leader: &BasicNode, request: ClientRequest) -> Result<ClientResponse> {
+        log::debug!(
+            "[RAFT] Node {} forwarding request to leader {}",
+            self.node_id,
+            leader.addr
+        );
+        let url = format!("http://{}/raft/client", leader.addr);
+        let result: Result<ClientResponse, String> = self
+            .client
+            .post(url)
+            .bearer_auth(&self.peer_token)
+            .json(&request)
+            .send()
+            .await?
+            .json()
+            .await?;
+        result.map_err(|e| anyhow!("Leader {} rejected request: {}", leader.addr, e))
+    }
+
+    async fn write(&self, command: Command) -> Result<CommandResult> {
+        match self.execute(ClientRequest::Write(Box::new(command))).await? {
+            ClientResponse::Write(result) => Ok(result),
+            ClientResponse::Read(_) => Err(anyhow!("Unexpected response for write request")),
+        }
+    }
+
+    async fn read(&self, query: Query) -> Result<QueryResult> {
+        match self.execute(ClientRequest::Read(query)).await? {
+            ClientResponse::Read(result) => Ok(*result),
+            ClientResponse::Write(_) => Err(anyhow!("Unexpected response for read request")),
+        }
+    }
+
+    /// 取出释放命令返回的锁并记录持有时长
+    fn released(&self, result: CommandResult) -> Option<LockInfo> {
+        let lock_info = result.locks.into_iter().next()?;
+        self.stats.record_released(&lock_info, self.clock.now());
+        Some(lock_info)
+    }
+
+    async fn read_lock(&self, query: Query) -> Result<Option<LockInfo>> {
+        match self.read(query).await? {
+            QueryResult::Lock(lock_info) => Ok(lock_info.map(|lock_info| *lock_info)),
+            _ => Err(anyhow!("Unexpected response for lock query")),
+        }
+    }
+}
+
+#[async_trait]
+impl LockStorage for RaftStorage {
+    async fn try_acquire(&self, lock_info: LockInfo) -> Result<Acquire> {
+        let lock_key = lock_info.get_lock_key();
+        let result = self
+            .write(Command::TryAcquire {
+                lock_info,
+                now: self.clock.now(),
+            })
+            .await?;
+        self.stats.record_acquire(&lock_key, result.ok);
+        for lock_info in &result.locks {
+            self.stats.record_expired(lock_info, self.clock.now());
+        }
+        Ok(Acquire {
+            acquired: result.ok,
+            replaced: result.locks.into_iter().next().map(Box::new),
+        })
+    }
+
+    async fn takeover(&self, lock_info: LockInfo, condition: &LockCondition) -> Result<Takeover> {
+        let lock_key = lock_info.get_lock_key();
+        let result = self
+            .write(Command::Takeover {
+                lock_info,
+                condition: condition.clone(),
+                now: self.clock.now(),
+            })
+            .await?;
+        self.stats.record_acquire(&lock_key, result.ok);
+        let mut locks = result.locks.into_iter();
+        let lock_info = locks
+            .next()
+            .ok_or_else(|| anyhow!("Unexpected response for takeover command"))?;
+        if !result.ok {
+            return Ok(Takeover::Rejected(lock_info));
+        }
+        let replaced = locks.next();
+        if let Some(replaced) = &replaced {
+            self.stats.record_expired(replaced, self.clock.now());
+        }
+        Ok(Takeover::Acquired {
+            lock_info,
+            replaced: replaced.map(Box::new),
+        })
+    }
+
+    async fn restore(&self, lock_info: LockInfo) -> Result<bool> {
+        let result = self
+            .write(Command::Restore {
+                lock_info,
+                now: self.clock.now(),
+            })
+            .await?;
+        Ok(result.ok)
+    }
+
+    async fn get_lock(&self, lock_key: &str) -> Result<Option<LockInfo>> {
+        self.read_lock(Query::GetLock(lock_key.to_string())).await
+    }
+
+    async fn update_heartbeat(&self, lock_id: &str) -> Result<Option<LockInfo>> {
+        let result = self
+            .write(Command::UpdateHeartbeat {
+                lock_id: lock_id.to_string(),
+                now: self.clock.now(),
+                version: None,
+            })
+            .await?;
+        Ok(result.locks.into_iter().next())
+    }
+
+    async fn update_heartbeat_version(&self, lock_id: &str, version: u64) -> Result<Option<LockInfo>> {
+        let result = self
+            .write(Command::UpdateHeartbeat {
+                lock_id: lock_id.to_string(),
+                now: self.clock.now(),
+                version: Some(version),
+            })
+            .await?;
+        Ok(result.locks.into_iter().next())
+    }
+
+    async fn release(&self, lock_id: &str) -> Result<Option<LockInfo>> {
+        let result = self
+            .write(Command::Release {
+                lock_id: lock_id.to_string(),
+            })
+            .await?;
+        Ok(self.released(result))
+    }
+
+    async fn release_version(&self, lock_id: &str, version: u64) -> Result<Option<LockInfo>> {
+        let result = self
+            .write(Command::ReleaseVersion {
+                lock_id: lock_id.to_string(),
+                version,
+            })
+            .await?;
+        Ok(self.released(result))
+    }
+
+    async fn release_owned(&self, lock_key: &str, user_id: &str) -> Result<Option<LockInfo>> {
+        let result = self
+            .write(Command::ReleaseOwned {
+                lock_key: lock_key.to_string(),
+                user_id: user_id.to_string(),
+            })
+            .await?;
+        Ok(self.released(result))
+    }
+
+    async fn cleanup_expired(&self) -> Result<Vec<LockInfo>> {
+        // 只由 leader 发起清理，避免重复写入日志
+        if self.raft.current_leader().await != Some(self.node_id) {
+            return Ok(Vec::new());
+        }
+        let result = self
+            .write(Command::CleanupExpired { now: self.clock.now() })
+            .await?;
+        for lock_info in &result.locks {
+            self.stats.record_expired(lock_info, self.clock.now());
+        }
+        Ok(result.locks)
+    }
+
+    async fn list_locks(&self) -> Result<Vec<LockInfo>> {
+        match self.read(Query::ListLocks).await? {
+            QueryResult::Locks(locks) => Ok(locks),
+            _ => Err(anyhow!("Unexpected response for list query")),
+        }
+    }
+
+    async fn list_prefix(&self, key_prefix: &str) -> Result<Vec<LockInfo>> {
+        match self.read(Query::ListPrefix(key_prefix.to_string())).await? {
+            QueryResult::Locks(locks) => Ok(locks),
+            _ => Err(anyhow!("Unexpected response for list query")),
+        }
+    }
+
+    async fn get_lock_by_id(&self, lock_id: &str) -> Result<Option<LockInfo>> {
+        self.read_lock(Query::GetLockById(lock_id.to_string())).await
+    }
+
+    async fn force_release(&self, lock_key: &str) -> Result<Option<LockInfo>> {
+        let result = self
+            .write(Command::ForceRelease {
+                lock_key: lock_key.to_string(),
+            })
+            .await?;
+        Ok(self.released(result))
+    }
+
+    async fn mark_preempted(
+        &self,
+        lock_id: &str,
+        notice: PreemptionNotice,
+    ) -> Result<Option<LockInfo>> {
+        let result = self
+            .write(Command::MarkPreempted {
+                lock_id: lock_id.to_string(),
+                notice,
+                now: self.clock.now(),
+            })
+            .await?;
+        Ok(result.locks.into_iter().next())
+    }
+
+    async fn transfer(&self, lock_id: &str, new_lock: LockInfo) -> Result<Option<LockInfo>> {
+        let lock_key = new_lock.get_lock_key();
+        let result = self
+            .write(Command::Transfer {
+                lock_id: lock_id.to_string(),
+                new_lock,
+            })
+            .await?;
+        if result.ok {
+            self.stats.record_acquire(&lock_key, true);
+        }
+        Ok(self.released(result))
+    }
+
+    async fn rewrite_lock(&self, lock_info: LockInfo) -> Result<bool> {
+        Ok(self.write(Command::RewriteLock { lock_info }).await?.ok)
+    }
+
+    async fn list_by_user(&self, user_id: &str) -> Result<Vec<LockInfo>> {
+        match self
+            .read(Query::ListByUser {
+                user_id: user_id.to_string(),
+                now: self.clock.now(),
+            }).await? {
+            QueryResult::Locks(locks) => Ok(locks),
+            _ => Err(anyhow!("Unexpected response for list query")),
+        }
+    }
+
+    async fn list_by_business_id(&self, business_id: &str) -> Result<Vec<LockInfo>> {
+        match self
+            .read(Query::ListByBusinessId {
+                business_id: business_id.to_string(),
+                now: self.clock.now(),
+            }).await? {
+            QueryResult::Locks(locks) => Ok(locks),
+            _ => Err(anyhow!("Unexpected response for list query")),
+        }
+    }
+
+    async fn release_all_by_user(&self, user_id: &str) -> Result<Vec<LockInfo>> {
+        let result = self
+            .write(Command::ReleaseAllByUser {
+                user_id: user_id.to_string(),
+            })
+            .await?;
+        for lock_info in &result.locks {
+            self.stats.record_released(lock_info, self.clock.now());
+        }
+        Ok(result.locks)
+    }
+
+    async fn count_locks(&self, namespace: &str) -> Result<u64> {
+        match self
+            .read(Query::CountLocks {
+                namespace: namespace.to_string(),
+                now: self.clock.now(),
+            }).await? {
+            QueryResult::Count(count) => Ok(count),
+            _ => Err(anyhow!("Unexpected response for count query")),
+        }
+    }
+
+    async fn stats(&self, top_n: usize) -> Result<LockStats> {
+        let locks = self.list_locks().await?;
+        Ok(self.stats.snapshot(&locks, top_n, self.clock.now()))
+    }
+
+    async fn list_namespaces(&self) -> Result<Vec<NamespacePolicy>> {
+        match self.read(Query::ListNamespaces).await? {
+            QueryResult::Namespaces(namespaces) => Ok(namespaces),
+            _ => Err(anyhow!("Unexpected response for namespace list query")),
+        }
+    }
+
+    async fn get_namespace(&self, name: &str) -> Result<Option<NamespacePolicy>> {
+        match self.read(Query::GetNamespace(name.to_string())).await? {
+            QueryResult::Namespace(policy) => Ok(policy),
+            _ => Err(anyhow!("Unexpected response for namespace query")),
+        }
+    }
+
+    async fn put_namespace(&self, policy: NamespacePolicy) -> Result<()> {
+        self.write(Command::PutNamespace { policy }).await?;
+        Ok(())
+    }
+
+    async fn delete_namespace(&self, name: &str) -> Result<bool> {
+        let result = self
+            .write(Command::DeleteNamespace {
+                name: name.to_string(),
+            })
+            .await?;
+        Ok(result.ok)
+    }
+
+    async fn next_sequence(&self, name: &str, count: u64) -> Result<u64> {
+        let result = self
+            .write(Command::NextSequence {
+                name: name.to_string(),
+                count,
+            })
+            .await?;
+        match result.output {
+            Some(CommandOutput::Sequence(last)) => Ok(last),
+            _ => Err(anyhow!("Sequence {} overflowed", name)),
+        }
+    }
+
+    async fn take_tokens(&self, key: &str, bucket: TokenBucket, cost: u64) -> Result<RateLimitDecision> {
+        let result = self
+            .write(Command::TakeTokens {
+                key: key.to_string(),
+                bucket,
+                cost,
+                now: self.clock.now(),
+            })
+            .await?;
+        match result.output {
+            Some(CommandOutput::RateLimit(decision)) => Ok(decision),
+            _ => Err(anyhow!("Unexpected response for rate limit command")),
+        }
+    }
+
+    async fn get_record(&self, kind: &str, id: &str) -> Result<Option<String>> {
+        match self
+            .read(Query::GetRecord {
+                kind: kind.to_string(),
+                id: id.to_string(),
+            })
+            .await?
+        {
+            QueryResult::Record(value) => Ok(value),
+            _ => Err(anyhow!("Unexpected response for record query")),
+        }
+    }
+
+    async fn list_records(&self, kind: &str) -> Result<Vec<(String, String)>> {
+        match self.read(Query::ListRecords(kind.to_string())).await? {
+            QueryResult::Records(records) => Ok(records),
+            _ => Err(anyhow!("Unexpected response for record list query")),
+        }
+    }
+
+    async fn swap_record(&self, kind: &str, id: &str, expected: Option<&str>, value: Option<&str>) -> Result<bool> {
+        let result = self
+            .write(Command::SwapRecord {
+                kind: kind.to_string(),
+                id: id.to_string(),
+                expected: expected.map(str::to_string),
+                value: value.map(str::to_string),
+            })
+            .await?;
+        Ok(result.ok)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// 节点间 HTTP 接口
+// ---------------------------------------------------------------------------
+
+/// 注册 Raft 内部接口
+pub fn configure_routes(cfg: &mut web::ServiceConfig, storage: Arc<RaftStorage>) {
+    cfg.app_data(web::Data::new(storage)).service(
+        web::scope("/raft")
+            .wrap(from_fn(peer_guard))
+            .app_data(web::JsonConfig::default().limit(16 * 1024 * 1024))
+            .route("/append", web::post().to(append_entries))
+            .route("/snapshot", web::post().to(install_snapshot))
+            .route("/vote", web::post().to(vote))
+            .route("/client", web::post().to(client_request)),
+    );
+}
+
+/// 要求请求携带 `Authorization: Bearer <RAFT_PEER_TOKEN>`，在解析请求体之前检查
+async fn peer_guard(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let authorized = req
+        .app_data::<web::Data<Arc<RaftStorage>>>()
+        .is_some_and(|storage| auth::bearer_matches(req.headers(), &storage.peer_token));
+    if !authorized {
+        log::warn!("[RAFT] Unauthorized peer request: {} {}", req.method(), req.path());
+        return Ok(req.into_response(HttpResponse::Unauthorized().finish()));
+    }
+    Ok(next.call(req).await?.map_into_boxed_body())
+}
+
+async fn append_entries(
+    storage: web::Data<Arc<RaftStorage>>,
+    req: web::Json<AppendEntriesRequest<TypeConfig>>,
+) -> HttpResponse {
+    let result = storage.raft.append_entries(req.into_inner()).await;
+    HttpResponse::Ok().json(result)
+}
+
+async fn install_snapshot(
+    storage: web::Data<Arc<RaftStorage>>,
+    req: web::Json<InstallSnapshotRequest<TypeConfig>>,
+) -> HttpResponse {
+    let result = storage.raft.install_snapshot(req.into_inner()).await;
+    HttpResponse::Ok().json(result)
+}
+
+async fn vote(
+    storage: web::Data<Arc<RaftStorage>>,
+    req: web::Json<VoteRequest<NodeId>>,
+) -> HttpResponse {
+    let result = storage.raft.vote(req.into_inner()).await;
+    HttpResponse::Ok().json(result)
+}
+
+/// 处理其他节点转发来的客户端请求（只处理一跳，不再继续转发）
+async fn client_request(
+    storage: web::Data<Arc<RaftStorage>>,
+    req: web::Json<ClientRequest>,
+) -> HttpResponse {
+    let result: Result<ClientResponse, String> = match storage.execute_local(&req).await {
+        Ok(Ok(response)) => Ok(response),
+        Ok(Err(_)) => Err(format!("Node {} is not the leader", storage.node_id)),
+        Err(e) => Err(e.to_string()),
+    };
+    HttpResponse::Ok().json(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use openraft::CommittedLeaderId;
+
+    fn entry(index: u64, command: Command) -> Entry<TypeConfig> {
+        Entry {
+            log_id: LogId::new(CommittedLeaderId::new(1, 1), index),
+            payload: EntryPayload::Normal(command),
+        }
+    }
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("fe-lock-raft-{}", uuid::Uuid::new_v4().simple()))
+    }
+
+    fn user_locks(store: &StateMachineStore, user_id: &str, now: DateTime<Utc>) -> Vec<LockInfo> {
+        match store.query(&Query::ListByUser {
+            user_id: user_id.to_string(),
+            now,
+        }) {
+            QueryResult::Locks(locks) => locks,
+            other => panic!("unexpected query result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn acquire_is_exclusive_until_expiry() {
+        let now = Utc::now();
+        let mut state = LockState::default();
+        let first = testing::lock_info("order", "1001", "u1", 30, now);
+        let second = testing::lock_info("order", "1001", "u2", 30, now);
+
+        let acquired = state.apply(Command::TryAcquire { lock_info: first.clone(), now });
+        assert!(acquired.ok && acquired.locks.is_empty());
+        let rejected = state.apply(Command::TryAcquire {
+            lock_info: second.clone(),
+            now: now + chrono::Duration::seconds(10),
+        });
+        assert!(!rejected.ok);
+
+        // 同一用户重入时保留原锁并递增版本号
+        let reentrant = state.apply(Command::TryAcquire {
+            lock_info: testing::lock_info("order", "1001", "u1", 30, now),
+            now,
+        });
+        assert!(reentrant.ok);
+        assert_eq!(state.locks[&first.get_lock_key()].lock_id, first.lock_id);
+        assert_eq!(state.locks[&first.get_lock_key()].version, 2);
+
+        // 过期后被他人获取，结果中带回被替换的锁
+        let replaced = state.apply(Command::TryAcquire {
+            lock_info: second.clone(),
+            now: now + chrono::Duration::seconds(31),
+        });
+        assert!(replaced.ok);
+        assert_eq!(replaced.locks.len(), 1);
+        assert_eq!(replaced.locks[0].lock_id, first.lock_id);
+        assert!(!state.lock_by_id.contains_key(&first.lock_id));
+        assert!(!state.locks_by_user.contains_key("u1"));
+        assert_eq!(state.locks_by_user["u2"].len(), 1);
+    }
+
+    #[test]
+    fn heartbeat_and_release_are_fenced() {
+        let now = Utc::now();
+        let mut state = LockState::default();
+        let lock_info = testing::lock_info("order", "1001", "u1", 30, now);
+        let (lock_id, lock_key) = (lock_info.lock_id.clone(), lock_info.get_lock_key());
+        state.apply(Command::TryAcquire { lock_info, now });
+
+        let stale = state.apply(Command::UpdateHeartbeat {
+            lock_id: lock_id.clone(),
+            now,
+            version: Some(5),
+        });
+        assert!(!stale.ok);
+        let renewed = state.apply(Command::UpdateHeartbeat {
+            lock_id: lock_id.clone(),
+            now: now + chrono::Duration::seconds(20),
+            version: Some(1),
+        });
+        assert!(renewed.ok);
+        assert_eq!(renewed.locks[0].version, 2);
+
+        let expired = state.apply(Command::UpdateHeartbeat {
+            lock_id: lock_id.clone(),
+            now: now + chrono::Duration::seconds(60),
+            version: None,
+        });
+        assert!(!expired.ok);
+
+        assert!(!state.apply(Command::ReleaseVersion { lock_id: lock_id.clone(), version: 1 }).ok);
+        assert!(!state
+            .apply(Command::ReleaseOwned {
+                lock_key,
+                user_id: "u2".to_string(),
+            })
+            .ok);
+        assert!(state.apply(Command::ReleaseVersion { lock_id: lock_id.clone(), version: 2 }).ok);
+        assert!(state.locks.is_empty() && state.lock_by_id.is_empty() && state.locks_by_user.is_empty());
+    }
+
+    #[test]
+    fn records_and_sequences() {
+        let mut state = LockState::default();
+        let swap = |expected: Option<&str>, value: Option<&str>| Command::SwapRecord {
+            kind: "session".to_string(),
+            id: "s1".to_string(),
+            expected: expected.map(str::to_string),
+            value: value.map(str::to_string),
+        };
+
+        assert!(state.apply(swap(None, Some("a"))).ok);
+        assert!(!state.apply(swap(None, Some("b"))).ok);
+        assert!(state.apply(swap(Some("a"), Some("b"))).ok);
+        assert!(!state.apply(swap(Some("a"), None)).ok);
+        assert!(state.apply(swap(Some("b"), None)).ok);
+        assert!(state.records.is_empty());
+
+        let next = |count| Command::NextSequence {
+            name: "invoice".to_string(),
+            count,
+        };
+        assert!(matches!(state.apply(next(1)).output, Some(CommandOutput::Sequence(1))));
+        assert!(matches!(state.apply(next(10)).output, Some(CommandOutput::Sequence(11))));
+    }
+
+    #[tokio::test]
+    async fn snapshot_restores_state_and_indexes() {
+        let now = Utc::now();
+        let (path, other_path) = (temp_dir(), temp_dir());
+        let mut store = StateMachineStore::open(RaftDir::open(&path).unwrap()).unwrap();
+        let lock_info = testing::lock_info("order", "1001", "u1", 300, now);
+        let results = store
+            .apply(vec![
+                entry(1, Command::TryAcquire { lock_info: lock_info.clone(), now }),
+                entry(2, Command::NextSequence { name: "invoice".to_string(), count: 3 }),
+                entry(
+                    3,
+                    Command::SwapRecord {
+                        kind: "reservation".to_string(),
+                        id: "r1".to_string(),
+                        expected: None,
+                        value: Some("{}".to_string()),
+                    },
+                ),
+            ])
+            .await
+            .unwrap();
+        assert!(results.iter().all(|result| result.ok));
+
+        let snapshot = store.build_snapshot().await.unwrap();
+        assert_eq!(snapshot.meta.last_log_id.map(|log_id| log_id.index), Some(3));
+
+        // 重启时从磁盘上的快照恢复，持有人索引随之重建
+        let mut reopened = StateMachineStore::open(RaftDir::open(&path).unwrap()).unwrap();
+        let (last_applied, _) = reopened.applied_state().await.unwrap();
+        assert_eq!(last_applied, snapshot.meta.last_log_id);
+        assert_eq!(user_locks(&reopened, "u1", now)[0].lock_id, lock_info.lock_id);
+
+        // 落后的节点安装 leader 发送的快照
+        let mut other = StateMachineStore::open(RaftDir::open(&other_path).unwrap()).unwrap();
+        let data = snapshot.snapshot.into_inner();
+        other.install_snapshot(&snapshot.meta, Box::new(Cursor::new(data))).await.unwrap();
+        assert_eq!(user_locks(&other, "u1", now)[0].lock_id, lock_info.lock_id);
+        assert!(matches!(
+            other.query(&Query::GetRecord {
+                kind: "reservation".to_string(),
+                id: "r1".to_string(),
+            }),
+            QueryResult::Record(Some(_))
+        ));
+        let next = other.apply(vec![entry(4, Command::NextSequence { name: "invoice".to_string(), count: 1 })]);
+        assert!(matches!(next.await.unwrap()[0].output, Some(CommandOutput::Sequence(4))));
+
+        fs::remove_dir_all(path).unwrap();
+        fs::remove_dir_all(other_path).unwrap();
+    }
+}
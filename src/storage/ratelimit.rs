@@ -0,0 +1,57 @@
+//! 令牌桶限流：内存存储和 Raft 存储共用的令牌计算，Redis 存储在 Lua 脚本中实现相同的规则
+
+use crate::models::{RateLimitDecision, TokenBucket};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// 令牌桶状态
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BucketState {
+    pub bucket: TokenBucket,
+    pub tokens: f64,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl BucketState {
+    /// 新建满桶
+    pub fn full(bucket: TokenBucket, now: DateTime<Utc>) -> Self {
+        Self {
+            bucket,
+            tokens: bucket.capacity as f64,
+            updated_at: now,
+        }
+    }
+
+    /// 按经过的时间补充令牌后尝试取出 cost 个。参数变化时按新参数计算，令牌数不超过新容量
+    pub fn take(&mut self, bucket: TokenBucket, cost: u64, now: DateTime<Utc>) -> RateLimitDecision {
+        self.bucket = bucket;
+        self.tokens = self.tokens_at(now);
+        self.updated_at = now;
+
+        let cost = cost as f64;
+        if self.tokens >= cost {
+            self.tokens -= cost;
+            RateLimitDecision {
+                allowed: true,
+                remaining: self.tokens,
+                retry_after_secs: 0.0,
+            }
+        } else {
+            RateLimitDecision {
+                allowed: false,
+                remaining: self.tokens,
+                retry_after_secs: (cost - self.tokens) / bucket.refill_per_sec,
+            }
+        }
+    }
+
+    /// 是否已补满，补满的桶与不存在的桶等价，可以删除
+    pub fn is_full_at(&self, now: DateTime<Utc>) -> bool {
+        self.tokens_at(now) >= self.bucket.capacity as f64
+    }
+
+    fn tokens_at(&self, now: DateTime<Utc>) -> f64 {
+        let elapsed = (now - self.updated_at).num_milliseconds().max(0) as f64 / 1000.0;
+        (self.tokens + elapsed * self.bucket.refill_per_sec).min(self.bucket.capacity as f64)
+    }
+}
@@ -1,76 +1,984 @@
-use crate::models::LockInfo;
-use crate::storage::LockStorage;
-use anyhow::Result;
+use crate::clock::{self, Clock};
+use crate::config::{Config, RedisIdIndex};
+use crate::models::{
+    ContendedLock, LockCondition, LockInfo, LockStats, NamespacePolicy, PreemptionNotice, RateLimitDecision,
+    TokenBucket,
+};
+use crate::storage::stats::{
+    active_lock_stats, bucket_index, cumulative_histogram, longest_held, CONTENTION_BUCKETS,
+    HOLD_TIME_BUCKETS,
+};
+use crate::storage::{Acquire, LockStorage, Takeover};
+use anyhow::{anyhow, Result};
 use async_trait::async_trait;
-use chrono::Utc;
-use redis::aio::ConnectionManager;
-use redis::{AsyncCommands, RedisError};
+use chrono::{DateTime, Utc};
+use futures_util::StreamExt;
+use redis::aio::{ConnectionLike, ConnectionManager};
+use redis::{AsyncCommands, ClientTlsConfig, Cmd, Pipeline, RedisFuture, TlsCertificates, Value};
+use std::collections::HashMap;
 use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 锁数据（KEYS[1]）仍为 ARGV[1]（空字符串表示锁不存在）时写入 ARGV[2]，ARGV[3] 为过期秒数（0 表示不过期），
+/// 并在同一脚本中更新 lock_id（ARGV[5]）到锁键（ARGV[6]）的索引、持有人索引（KEYS[4]）和 business_id 索引（KEYS[7]），返回是否写入。
+/// ARGV[4] 为索引布局：hash 写入索引哈希（KEYS[2]）并删除旧布局的 id 键（KEYS[3]），keys 写入带过期时间的 id 键并删除哈希条目；
+/// ARGV[7] 为被替换的锁的 lock_id，与 ARGV[5] 不同时先移除其 id 索引（KEYS[2]、KEYS[5]）和持有人索引（KEYS[6]）；
+/// 截止键（KEYS[8]）按 ARGV[8] 秒过期（0 表示删除），过期时触发键空间通知
+const WRITE_LOCK: &str = r"
+if (redis.call('GET', KEYS[1]) or '') ~= ARGV[1] then
+    return 0
+end
+if ARGV[7] ~= ARGV[5] then
+    redis.call('HDEL', KEYS[2], ARGV[7])
+    redis.call('DEL', KEYS[5])
+    redis.call('SREM', KEYS[6], ARGV[6])
+end
+if ARGV[3] == '0' then
+    redis.call('SET', KEYS[1], ARGV[2])
+else
+    redis.call('SET', KEYS[1], ARGV[2], 'EX', ARGV[3])
+end
+if ARGV[4] == 'hash' then
+    redis.call('HSET', KEYS[2], ARGV[5], ARGV[6])
+    redis.call('DEL', KEYS[3])
+else
+    if ARGV[3] == '0' then
+        redis.call('SET', KEYS[3], ARGV[6])
+    else
+        redis.call('SET', KEYS[3], ARGV[6], 'EX', ARGV[3])
+    end
+    redis.call('HDEL', KEYS[2], ARGV[5])
+end
+redis.call('SADD', KEYS[4], ARGV[6])
+redis.call('SADD', KEYS[7], ARGV[6])
+if ARGV[8] == '0' then
+    redis.call('DEL', KEYS[8])
+else
+    redis.call('SET', KEYS[8], ARGV[5], 'EX', ARGV[8])
+end
+return 1
+";
+
+/// 按 lock_id（ARGV[2]）读取锁数据，先查索引哈希（KEYS[1]），再查旧布局的 id 键（KEYS[2]），ARGV[1] 为锁数据键前缀，
+/// 返回 {锁数据键, 锁数据}，不存在时返回 nil 并删除失效的哈希条目。
+/// 锁数据键由索引的值拼出，未在 KEYS 中声明，只适用于单个 Redis（不支持 Cluster）
+const GET_BY_ID: &str = r"
+local lock_key = redis.call('HGET', KEYS[1], ARGV[2])
+local indexed = lock_key
+if not lock_key then
+    lock_key = redis.call('GET', KEYS[2])
+end
+if not lock_key then
+    return false
+end
+local full_key = ARGV[1] .. lock_key
+local data = redis.call('GET', full_key)
+if not data or cjson.decode(data).lock_id ~= ARGV[2] then
+    if indexed then
+        redis.call('HDEL', KEYS[1], ARGV[2])
+    end
+    return false
+end
+return {full_key, data}
+";
+
+/// 锁数据（KEYS[1]）仍为 ARGV[1] 时删除锁数据、id 键（KEYS[3]）、截止键（KEYS[8]）和索引哈希（KEYS[2]）中的 ARGV[5]，
+/// 将 ARGV[2] 移出持有人索引（KEYS[4]）和 business_id 索引（KEYS[7]），并记录持有时长：KEYS[5] 为持有时长直方图，
+/// ARGV[3] 为直方图桶，ARGV[4] 为持有秒数，KEYS[6] 为最长持有时间；返回是否删除
+const RELEASE: &str = r"
+if redis.call('GET', KEYS[1]) ~= ARGV[1] then
+    return 0
+end
+redis.call('DEL', KEYS[1], KEYS[3], KEYS[8])
+redis.call('HDEL', KEYS[2], ARGV[5])
+redis.call('SREM', KEYS[4], ARGV[2])
+redis.call('SREM', KEYS[7], ARGV[2])
+redis.call('HINCRBY', KEYS[5], ARGV[3], 1)
+redis.call('HINCRBY', KEYS[5], 'count', 1)
+redis.call('HINCRBYFLOAT', KEYS[5], 'sum', ARGV[4])
+redis.call('ZADD', KEYS[6], 'GT', ARGV[4], ARGV[2])
+return 1
+";
+
+/// 令牌桶限流，使用 Redis 服务器时间计算补充的令牌，多个实例共享同一时钟
+const TAKE_TOKENS: &str = r"
+redis.replicate_commands()
+local capacity = tonumber(ARGV[1])
+local rate = tonumber(ARGV[2])
+local cost = tonumber(ARGV[3])
+local time = redis.call('TIME')
+local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+local state = redis.call('HMGET', KEYS[1], 'tokens', 'ts')
+local tokens = tonumber(state[1]) or capacity
+local ts = tonumber(state[2]) or now
+tokens = math.min(capacity, tokens + math.max(0, now - ts) * rate / 1000)
+local allowed = 0
+local retry_after = 0
+if tokens >= cost then
+    tokens = tokens - cost
+    allowed = 1
+else
+    retry_after = (cost - tokens) / rate
+end
+redis.call('HSET', KEYS[1], 'tokens', tostring(tokens), 'ts', now)
+redis.call('PEXPIRE', KEYS[1], math.ceil(capacity / rate * 1000) + 1000)
+return {allowed, tostring(tokens), tostring(retry_after)}
+";
+
+/// 哈希（KEYS[1]）中字段 ARGV[1] 的当前值与期望一致时写入或删除，返回是否写入。
+/// ARGV[2] 为 1 时期望值为 ARGV[3]，为 0 时期望字段不存在；ARGV[4] 为 1 时写入 ARGV[5]，为 0 时删除字段
+const SWAP_RECORD: &str = r"
+local current = redis.call('HGET', KEYS[1], ARGV[1])
+if ARGV[2] == '1' then
+    if current ~= ARGV[3] then
+        return 0
+    end
+elseif current then
+    return 0
+end
+if ARGV[4] == '1' then
+    redis.call('HSET', KEYS[1], ARGV[1], ARGV[5])
+else
+    redis.call('HDEL', KEYS[1], ARGV[1])
+end
+return 1
+";
+
+/// 旧布局的 id 键（KEYS[1]）仍指向 ARGV[2] 时对账：锁键（KEYS[2]）上是 ARGV[1] 对应的锁时，
+/// ARGV[3] 为 hash 则迁移到索引哈希（KEYS[3]）并删除 id 键，返回 2，为 keys 则保留，返回 0；否则删除失效的 id 键，返回 1
+const RECONCILE_ID_KEY: &str = r"
+if redis.call('GET', KEYS[1]) ~= ARGV[2] then
+    return 0
+end
+local data = redis.call('GET', KEYS[2])
+if data and cjson.decode(data).lock_id == ARGV[1] then
+    if ARGV[3] ~= 'hash' then
+        return 0
+    end
+    redis.call('HSET', KEYS[3], ARGV[1], ARGV[2])
+    redis.call('DEL', KEYS[1])
+    return 2
+end
+redis.call('DEL', KEYS[1])
+return 1
+";
+
+/// 索引哈希（KEYS[1]）中 ARGV[1] 仍指向 ARGV[2] 且该锁键（KEYS[2]）上不是 ARGV[1] 对应的锁时删除该条目，返回是否删除
+const REMOVE_DANGLING_INDEX: &str = r"
+if redis.call('HGET', KEYS[1], ARGV[1]) ~= ARGV[2] then
+    return 0
+end
+local data = redis.call('GET', KEYS[2])
+if data and cjson.decode(data).lock_id == ARGV[1] then
+    return 0
+end
+redis.call('HDEL', KEYS[1], ARGV[1])
+return 1
+";
+
+/// 锁数据（KEYS[1]）仍为 ARGV[1] 时补齐缺失的过期时间和 id 索引，ARGV[3] 为过期秒数（0 表示不过期），
+/// ARGV[4] 为索引布局（hash 检查索引哈希 KEYS[2] 中的 ARGV[5]，keys 检查 id 键 KEYS[3]），返回 {是否补齐 id 索引, 是否补齐过期时间}
+const REPAIR_LOCK: &str = r"
+if redis.call('GET', KEYS[1]) ~= ARGV[1] then
+    return {0, 0}
+end
+local ttl_repaired = 0
+if ARGV[3] ~= '0' and redis.call('TTL', KEYS[1]) == -1 then
+    redis.call('EXPIRE', KEYS[1], ARGV[3])
+    ttl_repaired = 1
+end
+local id_repaired = 0
+if ARGV[4] == 'hash' then
+    if redis.call('HGET', KEYS[2], ARGV[5]) ~= ARGV[2] then
+        redis.call('HSET', KEYS[2], ARGV[5], ARGV[2])
+        id_repaired = 1
+    end
+elseif redis.call('EXISTS', KEYS[3]) == 0 then
+    if ARGV[3] == '0' then
+        redis.call('SET', KEYS[3], ARGV[2])
+    else
+        redis.call('SET', KEYS[3], ARGV[2], 'EX', ARGV[3])
+    end
+    id_repaired = 1
+end
+return {id_repaired, ttl_repaired}
+";
+
+/// 锁被并发修改导致比较失败时的最大重试次数
+const CAS_RETRIES: usize = 3;
+
+/// 锁过期后锁数据键保留的秒数，截止键过期通知到达时仍可读到锁数据并发布过期事件
+const EXPIRED_RETENTION_SECS: u64 = 60;
+
+pub struct RedisOptions {
+    pub url: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub db: Option<i64>,
+    pub prefix: String,
+    pub tls_ca_cert: Option<String>,
+    pub tls_client_cert: Option<String>,
+    pub tls_client_key: Option<String>,
+    pub connect_timeout: Duration,
+    pub response_timeout: Option<Duration>,
+    pub reconnect_retries: usize,
+    pub reconnect_backoff_ms: u64,
+    pub id_index: RedisIdIndex,
+}
+
+impl RedisOptions {
+    pub fn from_config(config: &Config, url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            username: config.redis_username.clone(),
+            password: config.redis_password.clone(),
+            db: config.redis_db,
+            prefix: config.redis_key_prefix.clone(),
+            tls_ca_cert: config.redis_tls_ca_cert.clone(),
+            tls_client_cert: config.redis_tls_client_cert.clone(),
+            tls_client_key: config.redis_tls_client_key.clone(),
+            connect_timeout: Duration::from_millis(config.redis_connect_timeout_ms),
+            response_timeout: (config.redis_response_timeout_ms > 0)
+                .then(|| Duration::from_millis(config.redis_response_timeout_ms)),
+            reconnect_retries: config.redis_reconnect_retries,
+            reconnect_backoff_ms: config.redis_reconnect_backoff_ms,
+            id_index: config.redis_id_index.clone(),
+        }
+    }
+
+    /// 配置了证书时读取证书文件，rediss:// 未配置证书时使用系统根证书
+    fn tls_certificates(&self) -> Result<Option<TlsCertificates>> {
+        if self.tls_ca_cert.is_none() && self.tls_client_cert.is_none() {
+            return Ok(None);
+        }
+        let read = |path: &str| {
+            std::fs::read(path).map_err(|e| anyhow!("Failed to read TLS file {}: {}", path, e))
+        };
+        let client_tls = match (&self.tls_client_cert, &self.tls_client_key) {
+            (Some(cert), Some(key)) => Some(ClientTlsConfig {
+                client_cert: read(cert)?,
+                client_key: read(key)?,
+            }),
+            (None, None) => None,
+            _ => return Err(anyhow!("REDIS_TLS_CLIENT_CERT and REDIS_TLS_CLIENT_KEY must be set together")),
+        };
+        Ok(Some(TlsCertificates {
+            client_tls,
+            root_cert: self.tls_ca_cert.as_deref().map(read).transpose()?,
+        }))
+    }
+}
+
+/// 为每条命令加上响应超时的连接，Redis 无响应时请求及时失败而不是一直等待
+#[derive(Clone)]
+struct TimeoutConnection {
+    inner: ConnectionManager,
+    response_timeout: Option<Duration>,
+}
+
+impl TimeoutConnection {
+    async fn with_timeout<T>(
+        response_timeout: Option<Duration>,
+        request: RedisFuture<'_, T>,
+    ) -> redis::RedisResult<T> {
+        match response_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, request).await.unwrap_or_else(|_| {
+                Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "Redis response timed out").into())
+            }),
+            None => request.await,
+        }
+    }
+}
+
+impl ConnectionLike for TimeoutConnection {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+        let response_timeout = self.response_timeout;
+        Box::pin(Self::with_timeout(response_timeout, self.inner.req_packed_command(cmd)))
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        cmd: &'a Pipeline,
+        offset: usize,
+        count: usize,
+    ) -> RedisFuture<'a, Vec<Value>> {
+        let response_timeout = self.response_timeout;
+        Box::pin(Self::with_timeout(
+            response_timeout,
+            self.inner.req_packed_commands(cmd, offset, count),
+        ))
+    }
+
+    fn get_db(&self) -> i64 {
+        self.inner.get_db()
+    }
+}
+
+/// 一轮键对账的结果
+#[derive(Debug, Default)]
+pub struct ReconcileReport {
+    /// 删除的失效 id 键和索引哈希条目（锁已释放、过期或已被他人重新获取）
+    pub removed_id_keys: usize,
+    /// 迁移到索引哈希的旧布局 id 键
+    pub migrated_id_keys: usize,
+    /// 补齐的 id 键或索引哈希条目
+    pub repaired_id_keys: usize,
+    /// 补齐的过期时间（申请锁时写入锁数据后未能设置过期时间）
+    pub repaired_ttls: usize,
+}
 
 pub struct RedisStorage {
-    client: ConnectionManager,
+    client: TimeoutConnection,
+    redis_client: redis::Client, // 用于订阅键空间通知
     prefix: String,
+    id_index: RedisIdIndex,
+    clock: Arc<dyn Clock>,
 }
 
 impl RedisStorage {
-    pub async fn new(
-        redis_url: &str,
-        username: Option<String>,
-        password: Option<String>,
-        db: Option<i64>,
-    ) -> Result<Self> {
+    pub async fn new(options: RedisOptions) -> Result<Self> {
         // 构建连接信息
-        let mut connection_info = redis::ConnectionInfo::from_str(redis_url)?;
+        let mut connection_info = redis::ConnectionInfo::from_str(&options.url)?;
         
         // 设置认证信息
-        if let Some(pwd) = password {
+        if let Some(pwd) = options.password.clone() {
             connection_info.redis.password = Some(pwd);
         }
-        if let Some(user) = username {
+        if let Some(user) = options.username.clone() {
             connection_info.redis.username = Some(user);
         }
-        if let Some(database) = db {
+        if let Some(database) = options.db {
             connection_info.redis.db = database;
         }
 
-        let client = redis::Client::open(connection_info)?;
-        let connection = ConnectionManager::new(client).await?;
-        Ok(Self {
-            client: connection,
-            prefix: "lock:".to_string(),
-        })
+        let client = match options.tls_certificates()? {
+            Some(certificates) => {
+                if !matches!(connection_info.addr, redis::ConnectionAddr::TcpTls { .. }) {
+                    return Err(anyhow!("TLS certificates require a rediss:// URL"));
+                }
+                redis::Client::build_with_tls(connection_info, certificates)?
+            }
+            None => redis::Client::open(connection_info)?,
+        };
+        // 重连第 n 次前随机等待 0 到 reconnect_backoff_ms * 2^n 毫秒
+        let connection = tokio::time::timeout(
+            options.connect_timeout,
+            ConnectionManager::new_with_backoff(
+                client.clone(),
+                2,
+                options.reconnect_backoff_ms,
+                options.reconnect_retries,
+            ),
+        )
+        .await
+        .map_err(|_| anyhow!("Timed out connecting to Redis after {:?}", options.connect_timeout))??;
+        let storage = Self {
+            client: TimeoutConnection {
+                inner: connection,
+                response_timeout: options.response_timeout,
+            },
+            redis_client: client,
+            prefix: options.prefix,
+            id_index: options.id_index,
+            clock: clock::system(),
+        };
+
+        // 统计计数由所有实例共享，只在首次启动时记录起始时间
+        let mut conn = storage.client.clone();
+        let _: bool = conn
+            .set_nx(storage.get_stats_key("since"), Utc::now().to_rfc3339())
+            .await?;
+        Ok(storage)
+    }
+
+    /// 使用指定的时钟判断锁是否过期，默认为系统时钟
+    pub fn with_clock(self, clock: Arc<dyn Clock>) -> Self {
+        Self { clock, ..self }
+    }
+
+    /// 截止键的过期秒数，取锁真正过期的时间（含宽限期）并向上取整，永久锁为 0（不过期）
+    fn deadline_ttl(&self, lock_info: &LockInfo) -> u64 {
+        lock_info
+            .deadline_secs_at(self.clock.now())
+            .map_or(0, |remaining| remaining.max(1))
+    }
+
+    /// 锁相关键的过期秒数，在截止时间之后再保留 [`EXPIRED_RETENTION_SECS`] 秒，永久锁为 0（不过期）。
+    /// 键不会早于 [`LockInfo::is_expired_at`] 消失，锁是否过期始终以锁数据中的截止时间判断
+    fn key_ttl(&self, lock_info: &LockInfo) -> u64 {
+        match self.deadline_ttl(lock_info) {
+            0 => 0,
+            ttl => ttl + EXPIRED_RETENTION_SECS,
+        }
     }
 
     fn get_lock_key(&self, lock_key: &str) -> String {
         format!("{}data:{}", self.prefix, lock_key)
     }
 
+    /// 锁的截止键，随锁的截止时间过期
+    fn get_deadline_key(&self, lock_key: &str) -> String {
+        format!("{}deadline:{}", self.prefix, lock_key)
+    }
+
+    /// 锁数据键的 SCAN 模式，pattern 为已转义的 lock_key 模式
+    fn get_lock_pattern(&self, pattern: &str) -> String {
+        format!("{}data:{}", escape_pattern(&self.prefix), pattern)
+    }
+
+    /// 旧布局中 lock_id 对应的 id 键
     fn get_lock_id_key(&self, lock_id: &str) -> String {
         format!("{}id:{}", self.prefix, lock_id)
     }
+
+    /// lock_id 到锁键的索引哈希
+    fn get_id_index_key(&self) -> String {
+        format!("{}ids", self.prefix)
+    }
+
+    /// 传给脚本的索引布局
+    fn id_index_mode(&self) -> &'static str {
+        match self.id_index {
+            RedisIdIndex::Hash => "hash",
+            RedisIdIndex::Keys => "keys",
+        }
+    }
+
+    fn get_user_key(&self, user_id: &str) -> String {
+        format!("{}user:{}", self.prefix, user_id)
+    }
+
+    fn get_business_id_key(&self, business_id: &str) -> String {
+        format!("{}bid:{}", self.prefix, business_id)
+    }
+
+    fn get_namespaces_key(&self) -> String {
+        format!("{}namespaces", self.prefix)
+    }
+
+    fn get_records_key(&self, kind: &str) -> String {
+        format!("{}records:{}", self.prefix, kind)
+    }
+
+    fn get_stats_key(&self, name: &str) -> String {
+        format!("{}stats:{}", self.prefix, name)
+    }
+
+    fn get_sequence_key(&self, name: &str) -> String {
+        format!("{}seq:{}", self.prefix, name)
+    }
+
+    fn get_rate_limit_key(&self, key: &str) -> String {
+        format!("{}ratelimit:{}", self.prefix, key)
+    }
+
+    fn get_history_key(&self, lock_key: &str) -> String {
+        format!("{}history:{}", self.prefix, lock_key)
+    }
+
+    /// 向锁的历史 Stream 追加一条记录，Stream 长度约为 max_len
+    pub async fn append_history(&self, lock_key: &str, payload: &str, max_len: usize) -> Result<()> {
+        let mut conn = self.client.clone();
+        let _: String = redis::cmd("XADD")
+            .arg(self.get_history_key(lock_key))
+            .arg("MAXLEN")
+            .arg("~")
+            .arg(max_len)
+            .arg("*")
+            .arg("record")
+            .arg(payload)
+            .query_async(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    /// 锁的历史 Stream 中最新的 count 条记录，从新到旧
+    pub async fn history(&self, lock_key: &str, count: usize) -> Result<Vec<String>> {
+        let mut conn = self.client.clone();
+        let entries: Vec<(String, HashMap<String, String>)> = redis::cmd("XREVRANGE")
+            .arg(self.get_history_key(lock_key))
+            .arg("+")
+            .arg("-")
+            .arg("COUNT")
+            .arg(count)
+            .query_async(&mut conn)
+            .await?;
+        Ok(entries
+            .into_iter()
+            .filter_map(|(_, mut fields)| fields.remove("record"))
+            .collect())
+    }
+
+    fn get_audit_key(&self) -> String {
+        format!("{}audit", self.prefix)
+    }
+
+    /// 向审计 Stream 追加一条记录，Stream 长度约为 max_len，返回记录 ID
+    pub async fn append_audit(&self, fields: &[(&str, &str)], max_len: usize) -> Result<String> {
+        let mut conn = self.client.clone();
+        let mut cmd = redis::cmd("XADD");
+        cmd.arg(self.get_audit_key()).arg("MAXLEN").arg("~").arg(max_len).arg("*");
+        for (field, value) in fields {
+            cmd.arg(*field).arg(*value);
+        }
+        Ok(cmd.query_async(&mut conn).await?)
+    }
+
+    /// 审计 Stream 中的记录，从旧到新：指定 after 时为该 ID 之后的 count 条，否则为最新的 count 条
+    pub async fn audit(&self, after: Option<&str>, count: usize) -> Result<Vec<(String, HashMap<String, String>)>> {
+        let mut conn = self.client.clone();
+        let entries: Vec<(String, HashMap<String, String>)> = match after {
+            Some(after) => {
+                redis::cmd("XRANGE")
+                    .arg(self.get_audit_key())
+                    .arg(format!("({}", after))
+                    .arg("+")
+                    .arg("COUNT")
+                    .arg(count)
+                    .query_async(&mut conn)
+                    .await?
+            }
+            None => {
+                let mut entries: Vec<(String, HashMap<String, String>)> = redis::cmd("XREVRANGE")
+                    .arg(self.get_audit_key())
+                    .arg("+")
+                    .arg("-")
+                    .arg("COUNT")
+                    .arg(count)
+                    .query_async(&mut conn)
+                    .await?;
+                entries.reverse();
+                entries
+            }
+        };
+        Ok(entries)
+    }
+
+    /// 订阅锁数据键的键空间通知，锁数据被写入、删除或过期时以 lock_key 调用 on_change，连接断开时返回
+    ///
+    /// 需要 Redis 开启键空间通知（`notify-keyspace-events` 至少包含 `K$gx`）。
+    pub async fn watch_lock_keys(&self, on_change: impl Fn(&str)) -> Result<()> {
+        let db = self.redis_client.get_connection_info().redis.db;
+        let channel_prefix = format!("__keyspace@{}__:{}", db, self.get_lock_key(""));
+        let mut pubsub = self.redis_client.get_async_connection().await?.into_pubsub();
+        pubsub
+            .psubscribe(format!("__keyspace@{}__:{}*", db, self.get_lock_pattern("")))
+            .await?;
+
+        let mut messages = pubsub.on_message();
+        while let Some(message) = messages.next().await {
+            if let Some(lock_key) = message.get_channel_name().strip_prefix(&channel_prefix) {
+                on_change(lock_key);
+            }
+        }
+        Err(anyhow!("Keyspace notification connection closed"))
+    }
+
+    /// 订阅截止键的过期通知，锁到达截止时间仍未续期时删除锁并以被删除的锁调用 on_expired，连接断开时返回。
+    /// 多个实例同时收到通知时只有删除成功的实例调用 on_expired
+    ///
+    /// 需要 Redis 开启键空间通知（`notify-keyspace-events` 至少包含 `Kx`）。
+    pub async fn watch_expired(&self, on_expired: impl Fn(LockInfo)) -> Result<()> {
+        let db = self.redis_client.get_connection_info().redis.db;
+        let channel_prefix = format!("__keyspace@{}__:{}", db, self.get_deadline_key(""));
+        let mut pubsub = self.redis_client.get_async_connection().await?.into_pubsub();
+        pubsub
+            .psubscribe(format!(
+                "__keyspace@{}__:{}deadline:*",
+                db,
+                escape_pattern(&self.prefix)
+            ))
+            .await?;
+
+        let mut messages = pubsub.on_message();
+        while let Some(message) = messages.next().await {
+            let Some(lock_key) = message.get_channel_name().strip_prefix(&channel_prefix) else {
+                continue;
+            };
+            if message.get_payload::<String>().ok().as_deref() != Some("expired") {
+                continue;
+            }
+            match self.remove_expired(lock_key).await {
+                Ok(Some(lock_info)) => on_expired(lock_info),
+                Ok(None) => {}
+                Err(e) => log::warn!("Failed to remove expired lock {}: {}", lock_key, e),
+            }
+        }
+        Err(anyhow!("Keyspace notification connection closed"))
+    }
+
+    /// lock_key 上的锁已过期时删除锁及其索引，返回被删除的锁；锁已续期、被替换或已删除时返回 None
+    async fn remove_expired(&self, lock_key: &str) -> Result<Option<LockInfo>> {
+        let full_lock_key = self.get_lock_key(lock_key);
+        let Some((data, lock_info)) = self.get_raw(&full_lock_key).await? else {
+            return Ok(None);
+        };
+        if !lock_info.is_expired_at(self.clock.now())
+            || !self.compare_and_release(&full_lock_key, &data, &lock_info).await?
+        {
+            return Ok(None);
+        }
+        let mut conn = self.client.clone();
+        let _: () = conn.incr(self.get_stats_key("expired"), 1).await?;
+        log::info!(
+            "[EXPIRED] Lock expired and removed - lock_id: {}, namespace: {}, business_id: {}, user_id: {}, user_name: {}",
+            lock_info.lock_id, lock_info.namespace, lock_info.business_id,
+            lock_info.user_id, lock_info.user_name
+        );
+        Ok(Some(lock_info))
+    }
+
+    /// 对账 id 索引与锁数据键：迁移或删除旧布局的 id 键，删除失效的索引哈希条目，为锁补齐缺失的 id 索引和过期时间
+    ///
+    /// 写入锁时 id 索引在同一脚本中更新，但旧版本分步写入留下的键、随 TTL 过期的锁在索引哈希中的条目不会自行清理。
+    /// 每个键的检查与修复在 Lua 脚本中原子执行，与并发的申请、释放互不干扰。
+    pub async fn reconcile(&self) -> Result<ReconcileReport> {
+        let mut conn = self.client.clone();
+        let mut report = ReconcileReport::default();
+        let index_key = self.get_id_index_key();
+
+        let id_prefix = self.get_lock_id_key("");
+        let id_keys = self
+            .scan_keys(&format!("{}*", escape_pattern(&id_prefix)))
+            .await?;
+        for chunk in id_keys.chunks(500) {
+            let values: Vec<Option<String>> = redis::cmd("MGET")
+                .arg(chunk)
+                .query_async(&mut conn)
+                .await?;
+            for (id_key, lock_key) in chunk.iter().zip(values) {
+                let (Some(lock_key), Some(lock_id)) = (lock_key, id_key.strip_prefix(&id_prefix)) else {
+                    continue;
+                };
+                let result: i32 = redis::Script::new(RECONCILE_ID_KEY)
+                    .key(id_key)
+                    .key(self.get_lock_key(&lock_key))
+                    .key(&index_key)
+                    .arg(lock_id)
+                    .arg(&lock_key)
+                    .arg(self.id_index_mode())
+                    .invoke_async(&mut conn)
+                    .await?;
+                match result {
+                    1 => report.removed_id_keys += 1,
+                    2 => report.migrated_id_keys += 1,
+                    _ => {}
+                }
+            }
+        }
+
+        for (lock_id, lock_key) in self.scan_id_index().await? {
+            let removed: i32 = redis::Script::new(REMOVE_DANGLING_INDEX)
+                .key(&index_key)
+                .key(self.get_lock_key(&lock_key))
+                .arg(&lock_id)
+                .arg(&lock_key)
+                .invoke_async(&mut conn)
+                .await?;
+            report.removed_id_keys += removed as usize;
+        }
+
+        let data_keys = self.scan_keys(&self.get_lock_pattern("*")).await?;
+        for chunk in data_keys.chunks(500) {
+            let values: Vec<Option<String>> = redis::cmd("MGET")
+                .arg(chunk)
+                .query_async(&mut conn)
+                .await?;
+            for (data_key, data) in chunk.iter().zip(values) {
+                let Some(data) = data else {
+                    continue;
+                };
+                let lock_info = match serde_json::from_str::<LockInfo>(&data) {
+                    Ok(lock_info) => lock_info,
+                    Err(e) => {
+                        log::warn!("Skipping malformed lock data {}: {}", data_key, e);
+                        continue;
+                    }
+                };
+                let (id_repaired, ttl_repaired): (i32, i32) = redis::Script::new(REPAIR_LOCK)
+                    .key(data_key)
+                    .key(&index_key)
+                    .key(self.get_lock_id_key(&lock_info.lock_id))
+                    .arg(&data)
+                    .arg(lock_info.get_lock_key())
+                    .arg(self.key_ttl(&lock_info))
+                    .arg(self.id_index_mode())
+                    .arg(&lock_info.lock_id)
+                    .invoke_async(&mut conn)
+                    .await?;
+                report.repaired_id_keys += id_repaired as usize;
+                report.repaired_ttls += ttl_repaired as usize;
+            }
+        }
+        Ok(report)
+    }
+
+    /// 使用 HSCAN 遍历索引哈希，返回 (lock_id, lock_key)
+    async fn scan_id_index(&self) -> Result<Vec<(String, String)>> {
+        let mut conn = self.client.clone();
+        let index_key = self.get_id_index_key();
+        let mut entries = Vec::new();
+        let mut cursor: u64 = 0;
+        loop {
+            let (next_cursor, batch): (u64, Vec<(String, String)>) = redis::cmd("HSCAN")
+                .arg(&index_key)
+                .arg(cursor)
+                .arg("COUNT")
+                .arg(500)
+                .query_async(&mut conn)
+                .await?;
+            entries.extend(batch);
+            cursor = next_cursor;
+            if cursor == 0 {
+                break;
+            }
+        }
+        Ok(entries)
+    }
+
+    /// 记录一次申请结果，失败时累加该锁的竞争次数
+    async fn record_acquire(&self, lock_key: &str, acquired: bool) -> Result<()> {
+        let mut conn = self.client.clone();
+        if acquired {
+            let _: () = conn.incr(self.get_stats_key("acquired"), 1).await?;
+        } else {
+            let _: () = redis::pipe()
+                .incr(self.get_stats_key("conflicts"), 1)
+                .ignore()
+                .zincr(self.get_stats_key("contention"), lock_key, 1)
+                .ignore()
+                .query_async(&mut conn)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// 记录被释放或过期的锁的持有时长
+    async fn record_released(&self, lock_info: &LockInfo, expired: bool) -> Result<()> {
+        let held_secs = lock_info.held_secs_at(self.clock.now());
+        let hold_time_key = self.get_stats_key("hold_time");
+        let mut pipe = redis::pipe();
+        pipe.hincr(
+            &hold_time_key,
+            format!("bucket:{}", bucket_index(HOLD_TIME_BUCKETS, held_secs)),
+            1,
+        )
+        .ignore()
+        .hincr(&hold_time_key, "count", 1)
+        .ignore()
+        .hincr(&hold_time_key, "sum", held_secs)
+        .ignore()
+        .cmd("ZADD")
+        .arg(self.get_stats_key("max_hold"))
+        .arg("GT")
+        .arg(held_secs)
+        .arg(lock_info.get_lock_key())
+        .ignore();
+        if expired {
+            pipe.incr(self.get_stats_key("expired"), 1).ignore();
+        }
+        let mut conn = self.client.clone();
+        let _: () = pipe.query_async(&mut conn).await?;
+        Ok(())
+    }
+
+    /// 使用 SCAN 遍历匹配的键，避免 KEYS 阻塞 Redis
+    async fn scan_keys(&self, pattern: &str) -> Result<Vec<String>> {
+        let mut conn = self.client.clone();
+        let mut keys: Vec<String> = Vec::new();
+        let mut cursor: u64 = 0;
+        loop {
+            let (next_cursor, batch): (u64, Vec<String>) = redis::cmd("SCAN")
+                .arg(cursor)
+                .arg("MATCH")
+                .arg(pattern)
+                .arg("COUNT")
+                .arg(500)
+                .query_async(&mut conn)
+                .await?;
+            keys.extend(batch);
+            cursor = next_cursor;
+            if cursor == 0 {
+                break;
+            }
+        }
+        Ok(keys)
+    }
+
+    /// 读取锁数据，返回原始 JSON（用于比较后写入或删除）和解析后的锁
+    async fn get_raw(&self, full_lock_key: &str) -> Result<Option<(String, LockInfo)>> {
+        let mut conn = self.client.clone();
+        let data: Option<String> = conn.get(full_lock_key).await?;
+        match data {
+            Some(data) => {
+                let lock_info = serde_json::from_str(&data)?;
+                Ok(Some((data, lock_info)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// 按 lock_id 读取锁数据，一次往返返回锁数据的键、原始 JSON 和解析后的锁
+    async fn get_raw_by_id(&self, lock_id: &str) -> Result<Option<(String, String, LockInfo)>> {
+        let mut conn = self.client.clone();
+        let found: Option<(String, String)> = redis::Script::new(GET_BY_ID)
+            .key(self.get_id_index_key())
+            .key(self.get_lock_id_key(lock_id))
+            .arg(self.get_lock_key(""))
+            .arg(lock_id)
+            .invoke_async(&mut conn)
+            .await?;
+        match found {
+            Some((full_lock_key, data)) => {
+                let lock_info = serde_json::from_str(&data)?;
+                Ok(Some((full_lock_key, data, lock_info)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// 锁数据仍为 expected（空字符串表示锁不存在）时写入 lock_info 并按其剩余时间设置过期时间，
+    /// 同时更新 id 索引、持有人索引和 business_id 索引，replaced 为被替换的锁时移除其索引；一次往返完成，返回是否写入
+    async fn compare_and_set(
+        &self,
+        full_lock_key: &str,
+        expected: &str,
+        lock_info: &LockInfo,
+        replaced: Option<&LockInfo>,
+    ) -> Result<bool> {
+        let replaced = replaced.unwrap_or(lock_info);
+        let mut conn = self.client.clone();
+        let written: i32 = redis::Script::new(WRITE_LOCK)
+            .key(full_lock_key)
+            .key(self.get_id_index_key())
+            .key(self.get_lock_id_key(&lock_info.lock_id))
+            .key(self.get_user_key(&lock_info.user_id))
+            .key(self.get_lock_id_key(&replaced.lock_id))
+            .key(self.get_user_key(&replaced.user_id))
+            .key(self.get_business_id_key(&lock_info.business_id))
+            .key(self.get_deadline_key(&lock_info.get_lock_key()))
+            .arg(expected)
+            .arg(serde_json::to_string(lock_info)?)
+            .arg(self.key_ttl(lock_info))
+            .arg(self.id_index_mode())
+            .arg(&lock_info.lock_id)
+            .arg(lock_info.get_lock_key())
+            .arg(&replaced.lock_id)
+            .arg(self.deadline_ttl(lock_info))
+            .invoke_async(&mut conn)
+            .await?;
+        Ok(written == 1)
+    }
+
+    /// 锁数据仍为 expected 时删除锁及其 id 索引、持有人索引和 business_id 索引并记录持有时长，一次往返完成，返回是否删除
+    async fn compare_and_release(&self, full_lock_key: &str, expected: &str, lock_info: &LockInfo) -> Result<bool> {
+        let held_secs = lock_info.held_secs_at(self.clock.now());
+        let mut conn = self.client.clone();
+        let released: i32 = redis::Script::new(RELEASE)
+            .key(full_lock_key)
+            .key(self.get_id_index_key())
+            .key(self.get_lock_id_key(&lock_info.lock_id))
+            .key(self.get_user_key(&lock_info.user_id))
+            .key(self.get_stats_key("hold_time"))
+            .key(self.get_stats_key("max_hold"))
+            .key(self.get_business_id_key(&lock_info.business_id))
+            .key(self.get_deadline_key(&lock_info.get_lock_key()))
+            .arg(expected)
+            .arg(lock_info.get_lock_key())
+            .arg(format!("bucket:{}", bucket_index(HOLD_TIME_BUCKETS, held_secs)))
+            .arg(held_secs)
+            .arg(&lock_info.lock_id)
+            .invoke_async(&mut conn)
+            .await?;
+        Ok(released == 1)
+    }
+
+    /// 更新心跳并递增版本号，version 不为 None 时要求版本号一致；无冲突时两次往返（读取、写入锁数据和 id 索引）
+    async fn heartbeat(&self, lock_id: &str, version: Option<u64>) -> Result<Option<LockInfo>> {
+        for _ in 0..CAS_RETRIES {
+            let Some((full_lock_key, data, mut lock_info)) = self.get_raw_by_id(lock_id).await? else {
+                return Ok(None);
+            };
+            // 已过期（含达到最长持有时间）的锁不再续期，与获取时的判断一致
+            if lock_info.lock_id != lock_id
+                || lock_info.is_expired_at(self.clock.now())
+                || version.is_some_and(|version| version != lock_info.version)
+            {
+                return Ok(None);
+            }
+
+            lock_info.last_heartbeat = self.clock.now();
+            lock_info.version += 1;
+            if self.compare_and_set(&full_lock_key, &data, &lock_info, None).await? {
+                return Ok(Some(lock_info));
+            }
+        }
+        Err(anyhow!("Lock {} is being modified concurrently", lock_id))
+    }
+
+    /// 按 lock_id 释放锁，version 不为 None 时要求版本号一致；无冲突时两次往返（读取、删除并记录统计）
+    async fn release_matching(&self, lock_id: &str, version: Option<u64>) -> Result<Option<LockInfo>> {
+        for _ in 0..CAS_RETRIES {
+            // 验证锁所有权
+            let (full_lock_key, data, lock_info) = match self.get_raw_by_id(lock_id).await? {
+                Some((full_lock_key, data, lock_info))
+                    if lock_info.lock_id == lock_id
+                        && version.is_none_or(|version| version == lock_info.version) =>
+                {
+                    (full_lock_key, data, lock_info)
+                }
+                _ => return Ok(None),
+            };
+            if !self.compare_and_release(&full_lock_key, &data, &lock_info).await? {
+                continue;
+            }
+
+            log::info!(
+                "[RELEASE] Releasing lock - lock_id: {}, namespace: {}, business_id: {}, user_id: {}, user_name: {}",
+                lock_info.lock_id, lock_info.namespace, lock_info.business_id,
+                lock_info.user_id, lock_info.user_name
+            );
+            return Ok(Some(lock_info));
+        }
+        Err(anyhow!("Lock {} is being modified concurrently", lock_id))
+    }
+}
+
+/// 转义 SCAN MATCH 模式中的通配符
+fn escape_pattern(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
 }
 
 #[async_trait]
 impl LockStorage for RedisStorage {
-    async fn try_acquire(&self, lock_info: LockInfo) -> Result<bool> {
+    async fn try_acquire(&self, lock_info: LockInfo) -> Result<Acquire> {
         let lock_key = self.get_lock_key(&lock_info.get_lock_key());
-        let lock_id_key = self.get_lock_id_key(&lock_info.lock_id);
         let mut conn = self.client.clone();
 
-        // 检查锁是否存在
+        // 检查锁是否存在，锁数据无法解析时按锁被持有处理
+        let mut expected = String::new();
+        let mut expired_lock = None;
         let existing: Option<String> = conn.get(&lock_key).await?;
         if let Some(existing_data) = existing {
+            expected = existing_data;
             // 解析现有锁信息
-            if let Ok(existing_lock) = serde_json::from_str::<LockInfo>(&existing_data) {
-                if existing_lock.is_expired() {
-                    // 锁已过期，删除旧锁
+            if let Ok(existing_lock) = serde_json::from_str::<LockInfo>(&expected) {
+                if existing_lock.is_expired_at(self.clock.now()) {
+                    // 锁已过期，与写入新锁在同一脚本中替换旧锁及其索引
                     log::info!(
                         "[EXPIRED] Lock expired - lock_id: {}, namespace: {}, business_id: {}, user_id: {}, user_name: {}",
                         existing_lock.lock_id, existing_lock.namespace, existing_lock.business_id,
                         existing_lock.user_id, existing_lock.user_name
                     );
-                    let old_lock_id_key = self.get_lock_id_key(&existing_lock.lock_id);
-                    let _: Result<(), RedisError> = conn.del(&old_lock_id_key).await;
+                    expired_lock = Some(existing_lock);
                 } else if existing_lock.user_id == lock_info.user_id {
                     // 同一个用户重复申请，更新心跳时间
                     log::info!(
@@ -79,38 +987,84 @@ impl LockStorage for RedisStorage {
                         existing_lock.user_id, existing_lock.user_name
                     );
                     let mut updated_lock = existing_lock;
-                    updated_lock.last_heartbeat = Utc::now();
-                    let lock_data = serde_json::to_string(&updated_lock)?;
-                    let ttl = updated_lock.timeout as u64;
-                    let _: () = conn.set_ex(&lock_key, &lock_data, ttl).await?;
-                    return Ok(true);
+                    updated_lock.last_heartbeat = self.clock.now();
+                    updated_lock.version += 1;
+                    // 锁在此期间被释放或接管时按申请失败处理，由调用方重新读取当前的锁
+                    let updated = self
+                        .compare_and_set(&lock_key, &expected, &updated_lock, None)
+                        .await?;
+                    self.record_acquire(&lock_info.get_lock_key(), updated).await?;
+                    return Ok(Acquire::plain(updated));
                 } else {
                     // 锁被其他用户持有
-                    return Ok(false);
+                    self.record_acquire(&lock_info.get_lock_key(), false).await?;
+                    return Ok(Acquire::plain(false));
                 }
             }
         }
 
-        // 设置锁
-        let lock_data = serde_json::to_string(&lock_info)?;
-        let ttl = lock_info.timeout as usize;
-
-        // 使用 SET NX 确保原子性
-        let result: bool = conn
-            .set_nx(&lock_key, &lock_data)
+        // 锁数据、lock_id 索引和持有人索引在同一脚本中写入，锁在此期间被他人获取时写入失败
+        let result = self
+            .compare_and_set(&lock_key, &expected, &lock_info, expired_lock.as_ref())
             .await?;
+        let replaced = expired_lock.filter(|_| result);
+        if let Some(expired_lock) = &replaced {
+            if let Err(e) = self.record_released(expired_lock, true).await {
+                log::warn!("Failed to record lock stats: {}", e);
+            }
+        }
+        self.record_acquire(&lock_info.get_lock_key(), result).await?;
+        Ok(Acquire {
+            acquired: result,
+            replaced: replaced.map(Box::new),
+        })
+    }
 
-        if result {
-            // 设置过期时间
-            let _: () = conn.expire(&lock_key, ttl as i64).await?;
-            // 保存 lock_id -> lock_key 映射
-            let _: () = conn
-                .set_ex(&lock_id_key, lock_info.get_lock_key(), ttl as u64)
-                .await?;
-            Ok(true)
-        } else {
-            Ok(false)
+    async fn takeover(&self, mut lock_info: LockInfo, condition: &LockCondition) -> Result<Takeover> {
+        let lock_key = lock_info.get_lock_key();
+        let full_lock_key = self.get_lock_key(&lock_key);
+        let (data, current) = match self.get_raw(&full_lock_key).await? {
+            Some(current) => current,
+            // 锁空闲（Redis 中过期的锁随键过期删除），按普通申请处理
+            None => {
+                if self.try_acquire(lock_info.clone()).await?.acquired {
+                    let lock_info = self.get_lock(&lock_key).await?.unwrap_or(lock_info);
+                    return Ok(Takeover::Acquired {
+                        lock_info,
+                        replaced: None,
+                    });
+                }
+                return match self.get_lock(&lock_key).await? {
+                    Some(current) => Ok(Takeover::Rejected(current)),
+                    None => Err(anyhow!("Lock {} changed during takeover", lock_key)),
+                };
+            }
+        };
+        if !condition.matches(&current, self.clock.now()) {
+            self.record_acquire(&lock_key, false).await?;
+            return Ok(Takeover::Rejected(current));
+        }
+
+        lock_info.version = current.version + 1;
+        if !self.compare_and_set(&full_lock_key, &data, &lock_info, Some(&current)).await? {
+            // 锁在比较前被修改，以修改后的锁作为当前的锁
+            self.record_acquire(&lock_key, false).await?;
+            return match self.get_lock(&lock_key).await? {
+                Some(current) => Ok(Takeover::Rejected(current)),
+                None => Err(anyhow!("Lock {} changed during takeover", lock_key)),
+            };
         }
+        log::info!(
+            "[TAKEOVER] Expired lock taken over - lock_id: {}, namespace: {}, business_id: {}, from: {}, to: {}, version: {}",
+            lock_info.lock_id, lock_info.namespace, lock_info.business_id,
+            current.user_id, lock_info.user_id, lock_info.version
+        );
+        self.record_released(&current, true).await?;
+        self.record_acquire(&lock_key, true).await?;
+        Ok(Takeover::Acquired {
+            lock_info,
+            replaced: Some(Box::new(current)),
+        })
     }
 
     async fn get_lock(&self, lock_key: &str) -> Result<Option<LockInfo>> {
@@ -127,82 +1081,409 @@ impl LockStorage for RedisStorage {
         }
     }
 
-    async fn update_heartbeat(&self, lock_id: &str) -> Result<bool> {
-        let lock_id_key = self.get_lock_id_key(lock_id);
-        let mut conn = self.client.clone();
+    async fn restore(&self, lock_info: LockInfo) -> Result<bool> {
+        let full_lock_key = self.get_lock_key(&lock_info.get_lock_key());
+        match self.get_raw(&full_lock_key).await? {
+            Some((_, existing_lock)) if !existing_lock.is_expired_at(self.clock.now()) => Ok(false),
+            Some((data, existing_lock)) => {
+                self.compare_and_set(&full_lock_key, &data, &lock_info, Some(&existing_lock))
+                    .await
+            }
+            None => self.compare_and_set(&full_lock_key, "", &lock_info, None).await,
+        }
+    }
 
-        // 获取 lock_key
-        let lock_key: Option<String> = conn.get(&lock_id_key).await?;
-        let lock_key = match lock_key {
-            Some(key) => key,
-            None => return Ok(false),
-        };
+    async fn update_heartbeat(&self, lock_id: &str) -> Result<Option<LockInfo>> {
+        self.heartbeat(lock_id, None).await
+    }
 
-        let full_lock_key = self.get_lock_key(&lock_key);
+    async fn update_heartbeat_version(&self, lock_id: &str, version: u64) -> Result<Option<LockInfo>> {
+        self.heartbeat(lock_id, Some(version)).await
+    }
 
-        // 获取锁信息
-        let data: Option<String> = conn.get(&full_lock_key).await?;
-        let data = match data {
-            Some(d) => d,
-            None => return Ok(false),
-        };
+    async fn release(&self, lock_id: &str) -> Result<Option<LockInfo>> {
+        self.release_matching(lock_id, None).await
+    }
 
-        let mut lock_info: LockInfo = serde_json::from_str(&data)?;
-        if lock_info.lock_id != lock_id {
-            return Ok(false);
-        }
+    async fn release_version(&self, lock_id: &str, version: u64) -> Result<Option<LockInfo>> {
+        self.release_matching(lock_id, Some(version)).await
+    }
 
-        // 更新心跳时间
-        lock_info.last_heartbeat = Utc::now();
-        let lock_data = serde_json::to_string(&lock_info)?;
-        let ttl = lock_info.timeout as usize;
+    async fn release_owned(&self, lock_key: &str, user_id: &str) -> Result<Option<LockInfo>> {
+        let full_lock_key = self.get_lock_key(lock_key);
 
-        // 更新锁数据和过期时间
-        let _: () = conn.set_ex(&full_lock_key, &lock_data, ttl as u64).await?;
-        let _: () = conn.expire(&lock_id_key, ttl as i64).await?;
+        for _ in 0..CAS_RETRIES {
+            // 验证锁所有权
+            let (data, lock_info) = match self.get_raw(&full_lock_key).await? {
+                Some((data, lock_info)) if lock_info.user_id == user_id => (data, lock_info),
+                _ => return Ok(None),
+            };
+            if !self.compare_and_release(&full_lock_key, &data, &lock_info).await? {
+                continue;
+            }
 
-        Ok(true)
+            log::info!(
+                "[RELEASE] Releasing lock by key - lock_id: {}, namespace: {}, business_id: {}, user_id: {}, user_name: {}",
+                lock_info.lock_id, lock_info.namespace, lock_info.business_id,
+                lock_info.user_id, lock_info.user_name
+            );
+            return Ok(Some(lock_info));
+        }
+        Err(anyhow!("Lock {} is being modified concurrently", lock_key))
+    }
+
+    async fn cleanup_expired(&self) -> Result<Vec<LockInfo>> {
+        // 过期的锁按截止键的过期通知删除（见 watch_expired），未开启通知时随键过期删除
+        Ok(Vec::new())
+    }
+
+    async fn list_locks(&self) -> Result<Vec<LockInfo>> {
+        self.list_prefix("").await
     }
 
-    async fn release(&self, lock_id: &str) -> Result<bool> {
-        let lock_id_key = self.get_lock_id_key(lock_id);
+    async fn list_prefix(&self, key_prefix: &str) -> Result<Vec<LockInfo>> {
         let mut conn = self.client.clone();
+        let pattern = format!("{}*", escape_pattern(key_prefix));
+        let keys = self.scan_keys(&self.get_lock_pattern(&pattern)).await?;
 
-        // 获取 lock_key
-        let lock_key: Option<String> = conn.get(&lock_id_key).await?;
-        let lock_key = match lock_key {
-            Some(key) => key,
-            None => return Ok(false),
-        };
+        let mut locks = Vec::with_capacity(keys.len());
+        for chunk in keys.chunks(500) {
+            let values: Vec<Option<String>> = redis::cmd("MGET")
+                .arg(chunk)
+                .query_async(&mut conn)
+                .await?;
+            for data in values.into_iter().flatten() {
+                match serde_json::from_str::<LockInfo>(&data) {
+                    Ok(lock_info) => locks.push(lock_info),
+                    Err(e) => log::warn!("Skipping malformed lock data: {}", e),
+                }
+            }
+        }
+        Ok(locks)
+    }
 
-        let full_lock_key = self.get_lock_key(&lock_key);
+    async fn get_lock_by_id(&self, lock_id: &str) -> Result<Option<LockInfo>> {
+        Ok(self
+            .get_raw_by_id(lock_id)
+            .await?
+            .map(|(_, _, lock_info)| lock_info)
+            .filter(|lock_info| lock_info.lock_id == lock_id))
+    }
+
+    async fn force_release(&self, lock_key: &str) -> Result<Option<LockInfo>> {
+        let full_lock_key = self.get_lock_key(lock_key);
 
-        // 验证锁所有权
-        let data: Option<String> = conn.get(&full_lock_key).await?;
-        if let Some(data) = data {
-            let lock_info: LockInfo = serde_json::from_str(&data)?;
-            if lock_info.lock_id != lock_id {
-                return Ok(false);
+        for _ in 0..CAS_RETRIES {
+            let Some((data, lock_info)) = self.get_raw(&full_lock_key).await? else {
+                return Ok(None);
+            };
+            if !self.compare_and_release(&full_lock_key, &data, &lock_info).await? {
+                continue;
             }
-            
-            log::info!(
-                "[RELEASE] Releasing lock - lock_id: {}, namespace: {}, business_id: {}, user_id: {}, user_name: {}",
+
+            log::warn!(
+                "[FORCE RELEASE] Lock force released - lock_id: {}, namespace: {}, business_id: {}, user_id: {}, user_name: {}",
                 lock_info.lock_id, lock_info.namespace, lock_info.business_id,
                 lock_info.user_id, lock_info.user_name
             );
-        } else {
-            return Ok(false);
+            return Ok(Some(lock_info));
+        }
+        Err(anyhow!("Lock {} is being modified concurrently", lock_key))
+    }
+
+    async fn mark_preempted(
+        &self,
+        lock_id: &str,
+        notice: PreemptionNotice,
+    ) -> Result<Option<LockInfo>> {
+        for _ in 0..CAS_RETRIES {
+            let now = self.clock.now();
+            let (full_lock_key, data, mut lock_info) = match self.get_raw_by_id(lock_id).await? {
+                Some((full_lock_key, data, lock_info)) if lock_info.lock_id == lock_id && !lock_info.is_expired_at(now) => {
+                    (full_lock_key, data, lock_info)
+                }
+                _ => return Ok(None),
+            };
+            if lock_info.preemption.is_some() {
+                return Ok(Some(lock_info));
+            }
+
+            lock_info.preemption = Some(notice.clone());
+            lock_info.version += 1;
+            if self.compare_and_set(&full_lock_key, &data, &lock_info, None).await? {
+                return Ok(Some(lock_info));
+            }
+        }
+        Err(anyhow!("Lock {} is being modified concurrently", lock_id))
+    }
+
+    async fn transfer(&self, lock_id: &str, mut new_lock: LockInfo) -> Result<Option<LockInfo>> {
+        let full_lock_key = self.get_lock_key(&new_lock.get_lock_key());
+        let (data, old_lock) = match self.get_raw(&full_lock_key).await? {
+            Some((data, lock_info)) if lock_info.lock_id == lock_id => (data, lock_info),
+            _ => return Ok(None),
+        };
+        new_lock.version = old_lock.version + 1;
+        // 持有人恰好释放或续期时放弃本次转移，由调用方重新判断
+        if !self.compare_and_set(&full_lock_key, &data, &new_lock, Some(&old_lock)).await? {
+            return Ok(None);
+        }
+
+        log::warn!(
+            "[PREEMPT] Lock transferred - lock_id: {}, namespace: {}, business_id: {}, user_id: {}, user_name: {}",
+            old_lock.lock_id, old_lock.namespace, old_lock.business_id,
+            old_lock.user_id, old_lock.user_name
+        );
+
+        self.record_released(&old_lock, false).await?;
+        self.record_acquire(&new_lock.get_lock_key(), true).await?;
+        Ok(Some(old_lock))
+    }
+
+    async fn rewrite_lock(&self, lock_info: LockInfo) -> Result<bool> {
+        let full_lock_key = self.get_lock_key(&lock_info.get_lock_key());
+        let data = match self.get_raw(&full_lock_key).await? {
+            Some((data, current)) if current.lock_id == lock_info.lock_id && current.version == lock_info.version => data,
+            _ => return Ok(false),
+        };
+        self.compare_and_set(&full_lock_key, &data, &lock_info, None).await
+    }
+
+    async fn list_by_user(&self, user_id: &str) -> Result<Vec<LockInfo>> {
+        let user_key = self.get_user_key(user_id);
+        let mut conn = self.client.clone();
+        let lock_keys: Vec<String> = conn.smembers(&user_key).await?;
+
+        let mut locks = Vec::with_capacity(lock_keys.len());
+        let mut stale = Vec::new();
+        for chunk in lock_keys.chunks(500) {
+            let keys: Vec<String> = chunk.iter().map(|lock_key| self.get_lock_key(lock_key)).collect();
+            let values: Vec<Option<String>> = redis::cmd("MGET")
+                .arg(&keys)
+                .query_async(&mut conn)
+                .await?;
+            for (lock_key, data) in chunk.iter().zip(values) {
+                match data.map(|data| serde_json::from_str::<LockInfo>(&data)) {
+                    Some(Ok(lock_info)) if lock_info.user_id == user_id => locks.push(lock_info),
+                    Some(Err(e)) => log::warn!("Skipping malformed lock data: {}", e),
+                    // 锁已过期或已被他人持有
+                    _ => stale.push(lock_key.clone()),
+                }
+            }
+        }
+        if !stale.is_empty() {
+            let _: () = conn.srem(&user_key, stale).await?;
+        }
+        Ok(locks)
+    }
+
+    async fn list_by_business_id(&self, business_id: &str) -> Result<Vec<LockInfo>> {
+        let business_id_key = self.get_business_id_key(business_id);
+        let mut conn = self.client.clone();
+        let lock_keys: Vec<String> = conn.smembers(&business_id_key).await?;
+
+        let mut locks = Vec::with_capacity(lock_keys.len());
+        let mut stale = Vec::new();
+        for chunk in lock_keys.chunks(500) {
+            let keys: Vec<String> = chunk.iter().map(|lock_key| self.get_lock_key(lock_key)).collect();
+            let values: Vec<Option<String>> = redis::cmd("MGET")
+                .arg(&keys)
+                .query_async(&mut conn)
+                .await?;
+            for (lock_key, data) in chunk.iter().zip(values) {
+                match data.map(|data| serde_json::from_str::<LockInfo>(&data)) {
+                    Some(Ok(lock_info)) if lock_info.business_id == business_id => locks.push(lock_info),
+                    Some(Err(e)) => log::warn!("Skipping malformed lock data: {}", e),
+                    // 锁已过期或已被释放
+                    _ => stale.push(lock_key.clone()),
+                }
+            }
+        }
+        if !stale.is_empty() {
+            let _: () = conn.srem(&business_id_key, stale).await?;
+        }
+        Ok(locks)
+    }
+
+    async fn release_all_by_user(&self, user_id: &str) -> Result<Vec<LockInfo>> {
+        let mut conn = self.client.clone();
+        let lock_keys: Vec<String> = conn.smembers(self.get_user_key(user_id)).await?;
+        let mut released = Vec::with_capacity(lock_keys.len());
+        for lock_key in lock_keys {
+            if let Some(lock_info) = self.release_owned(&lock_key, user_id).await? {
+                released.push(lock_info);
+            }
+        }
+        Ok(released)
+    }
+
+    async fn count_locks(&self, namespace: &str) -> Result<u64> {
+        // 锁键随 TTL 自动过期，键的数量即为未过期的锁数量
+        let pattern = self.get_lock_pattern(&format!("{}:*", escape_pattern(namespace)));
+        Ok(self.scan_keys(&pattern).await?.len() as u64)
+    }
+
+    async fn stats(&self, top_n: usize) -> Result<LockStats> {
+        let locks = self.list_locks().await?;
+        let now = self.clock.now();
+        let mut conn = self.client.clone();
+
+        let (since, acquired, conflicts, expired): (
+            Option<String>,
+            Option<u64>,
+            Option<u64>,
+            Option<u64>,
+        ) = redis::cmd("MGET")
+            .arg(self.get_stats_key("since"))
+            .arg(self.get_stats_key("acquired"))
+            .arg(self.get_stats_key("conflicts"))
+            .arg(self.get_stats_key("expired"))
+            .query_async(&mut conn)
+            .await?;
+        let since = since
+            .and_then(|since| DateTime::parse_from_rfc3339(&since).ok())
+            .map_or(now, |since| since.with_timezone(&Utc));
+
+        let contention_key = self.get_stats_key("contention");
+        let (top_contended, longest_held_released): (Vec<(String, u64)>, Vec<(String, f64)>) =
+            if top_n == 0 {
+                (Vec::new(), Vec::new())
+            } else {
+                redis::pipe()
+                    .zrevrange_withscores(&contention_key, 0, top_n as isize - 1)
+                    .zrevrange_withscores(self.get_stats_key("max_hold"), 0, top_n as isize - 1)
+                    .query_async(&mut conn)
+                    .await?
+            };
+
+        // 每个锁的失败次数分布：按各桶上界 ZCOUNT，最后补上 +Inf 桶
+        let mut pipe = redis::pipe();
+        for bound in CONTENTION_BUCKETS {
+            pipe.zcount(&contention_key, "-inf", *bound);
         }
+        pipe.zcard(&contention_key);
+        let contention_cumulative: Vec<u64> = pipe.query_async(&mut conn).await?;
+        let contention_counts: Vec<u64> = contention_cumulative
+            .iter()
+            .enumerate()
+            .map(|(i, count)| count - if i == 0 { 0 } else { contention_cumulative[i - 1] })
+            .collect();
+        let contention_keys = contention_cumulative.last().copied().unwrap_or(0);
 
-        // 删除锁
-        let _: () = conn.del(&full_lock_key).await?;
-        let _: () = conn.del(&lock_id_key).await?;
+        let hold_time: HashMap<String, f64> =
+            conn.hgetall(self.get_stats_key("hold_time")).await?;
+        let hold_time_counts: Vec<u64> = (0..=HOLD_TIME_BUCKETS.len())
+            .map(|i| hold_time.get(&format!("bucket:{}", i)).copied().unwrap_or(0.0) as u64)
+            .collect();
 
-        Ok(true)
+        let mut stats = active_lock_stats(&locks, since, now);
+        stats.acquired = acquired.unwrap_or(0);
+        stats.conflicts = conflicts.unwrap_or(0);
+        stats.expired = expired.unwrap_or(0);
+        stats.top_contended = top_contended
+            .into_iter()
+            .map(|(lock_key, conflicts)| ContendedLock {
+                lock_key,
+                conflicts,
+            })
+            .collect();
+        stats.longest_held = longest_held(longest_held_released.into_iter(), &locks, top_n, now);
+        stats.hold_time = cumulative_histogram(
+            HOLD_TIME_BUCKETS,
+            &hold_time_counts,
+            hold_time.get("count").copied().unwrap_or(0.0) as u64,
+            hold_time.get("sum").copied().unwrap_or(0.0),
+        );
+        stats.contention = cumulative_histogram(
+            CONTENTION_BUCKETS,
+            &contention_counts,
+            contention_keys,
+            stats.conflicts as f64,
+        );
+        Ok(stats)
     }
 
-    async fn cleanup_expired(&self) -> Result<()> {
-        // Redis 会自动清理过期的键，无需手动清理
+    async fn list_namespaces(&self) -> Result<Vec<NamespacePolicy>> {
+        let mut conn = self.client.clone();
+        let values: Vec<String> = conn.hvals(self.get_namespaces_key()).await?;
+        let mut namespaces = Vec::with_capacity(values.len());
+        for data in values {
+            match serde_json::from_str::<NamespacePolicy>(&data) {
+                Ok(policy) => namespaces.push(policy),
+                Err(e) => log::warn!("Skipping malformed namespace data: {}", e),
+            }
+        }
+        Ok(namespaces)
+    }
+
+    async fn get_namespace(&self, name: &str) -> Result<Option<NamespacePolicy>> {
+        let mut conn = self.client.clone();
+        let data: Option<String> = conn.hget(self.get_namespaces_key(), name).await?;
+        match data {
+            Some(data) => Ok(Some(serde_json::from_str(&data)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn put_namespace(&self, policy: NamespacePolicy) -> Result<()> {
+        let mut conn = self.client.clone();
+        let data = serde_json::to_string(&policy)?;
+        let _: () = conn
+            .hset(self.get_namespaces_key(), &policy.name, data)
+            .await?;
         Ok(())
     }
+
+    async fn delete_namespace(&self, name: &str) -> Result<bool> {
+        let mut conn = self.client.clone();
+        let removed: u64 = conn.hdel(self.get_namespaces_key(), name).await?;
+        Ok(removed > 0)
+    }
+
+    async fn next_sequence(&self, name: &str, count: u64) -> Result<u64> {
+        let mut conn = self.client.clone();
+        let last: u64 = conn.incr(self.get_sequence_key(name), count).await?;
+        Ok(last)
+    }
+
+    async fn take_tokens(&self, key: &str, bucket: TokenBucket, cost: u64) -> Result<RateLimitDecision> {
+        let mut conn = self.client.clone();
+        let (allowed, remaining, retry_after): (i32, f64, f64) = redis::Script::new(TAKE_TOKENS)
+            .key(self.get_rate_limit_key(key))
+            .arg(bucket.capacity)
+            .arg(bucket.refill_per_sec)
+            .arg(cost)
+            .invoke_async(&mut conn)
+            .await?;
+        Ok(RateLimitDecision {
+            allowed: allowed == 1,
+            remaining,
+            retry_after_secs: retry_after,
+        })
+    }
+
+    async fn get_record(&self, kind: &str, id: &str) -> Result<Option<String>> {
+        let mut conn = self.client.clone();
+        Ok(conn.hget(self.get_records_key(kind), id).await?)
+    }
+
+    async fn list_records(&self, kind: &str) -> Result<Vec<(String, String)>> {
+        let mut conn = self.client.clone();
+        let records: Vec<(String, String)> = conn.hgetall(self.get_records_key(kind)).await?;
+        Ok(records)
+    }
+
+    async fn swap_record(&self, kind: &str, id: &str, expected: Option<&str>, value: Option<&str>) -> Result<bool> {
+        let mut conn = self.client.clone();
+        let written: i32 = redis::Script::new(SWAP_RECORD)
+            .key(self.get_records_key(kind))
+            .arg(id)
+            .arg(expected.is_some() as i32)
+            .arg(expected.unwrap_or_default())
+            .arg(value.is_some() as i32)
+            .arg(value.unwrap_or_default())
+            .invoke_async(&mut conn)
+            .await?;
+        Ok(written == 1)
+    }
 }
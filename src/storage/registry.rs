@@ -0,0 +1,60 @@
+//! 存储后端注册表
+//!
+//! 内置的 memory、redis、raft 之外，嵌入使用时可实现 [`StorageFactory`] 并以名称注册，
+//! `STORAGE_TYPE` 为该名称时 [`LockService::from_config_with`](crate::LockService::from_config_with) 调用工厂创建存储。
+//! 自定义存储与内置存储一样受 `STORAGE_OP_TIMEOUT_MS` 限制，过期锁由定时任务调用 `cleanup_expired` 清理。
+
+use super::LockStorage;
+use crate::config::Config;
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// 内置存储的名称，不能被注册的工厂覆盖
+pub const BUILTIN: [&str; 3] = ["memory", "redis", "raft"];
+
+/// 按配置创建存储后端
+#[async_trait]
+pub trait StorageFactory: Send + Sync {
+    /// 创建存储，自定义的连接参数可从环境变量读取
+    async fn create(&self, config: &Config) -> Result<Arc<dyn LockStorage>>;
+}
+
+/// 名称到存储工厂的映射，名称不区分大小写
+#[derive(Clone, Default)]
+pub struct StorageRegistry {
+    factories: BTreeMap<String, Arc<dyn StorageFactory>>,
+}
+
+impl StorageRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 以 name 注册工厂，name 为内置存储或已注册时返回错误
+    pub fn register(&mut self, name: &str, factory: impl StorageFactory + 'static) -> Result<&mut Self> {
+        let name = name.trim().to_lowercase();
+        if name.is_empty() || BUILTIN.contains(&name.as_str()) {
+            bail!("Storage type '{}' is reserved", name);
+        }
+        if self.factories.contains_key(&name) {
+            bail!("Storage type '{}' is already registered", name);
+        }
+        self.factories.insert(name, Arc::new(factory));
+        Ok(self)
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn StorageFactory>> {
+        self.factories.get(&name.to_lowercase()).cloned()
+    }
+
+    /// 可用的存储名称，含内置存储
+    pub fn names(&self) -> Vec<&str> {
+        BUILTIN
+            .iter()
+            .copied()
+            .chain(self.factories.keys().map(String::as_str))
+            .collect()
+    }
+}
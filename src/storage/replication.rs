@@ -0,0 +1,531 @@
+//! 双写复制存储
+//!
+//! 配置 `REPLICA_STORAGE_TYPE` 后，每次变更在主存储成功后写入副本存储，作为锁状态的持久审计副本，
+//! 读取和条件判断始终只走主存储，不改变热路径上的存储后端。副本写入的是变更后的锁状态而不是操作本身
+//! （新增或覆盖锁、按 lock_id 删除锁、写入或删除命名空间策略），重复写入或乱序到达不会使副本偏离主存储。
+//!
+//! `REPLICATION_MODE=sync` 时等待副本写入完成再返回；`async`（默认）时放入长度为 `REPLICATION_QUEUE_SIZE` 的队列，
+//! 由后台任务按顺序写入，队列满时丢弃。副本写入失败或被丢弃不影响请求结果，由每 `REPLICATION_RECONCILE_INTERVAL`
+//! 秒一次的对账修复：以主存储为准补齐或覆盖副本中不一致的锁，删除副本中多余的锁和命名空间策略。
+//! 序列和限流令牌桶不复制。
+
+use super::encryption::SnapshotCipher;
+use super::memory::MemoryStorage;
+use super::persist::PersistTarget;
+use super::redis::{RedisOptions, RedisStorage};
+use super::registry::StorageRegistry;
+use super::timeout::TimeoutStorage;
+use super::{Acquire, LockStorage, Takeover};
+use crate::config::{Config, ReplicationMode};
+use crate::models::{
+    LockCondition, LockInfo, LockStats, NamespacePolicy, PreemptionNotice, RateLimitDecision, TokenBucket,
+};
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use log::info;
+use parking_lot::Mutex;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+/// 副本存储，memory 副本另外保留内存存储以便定时持久化
+pub struct Replica {
+    pub storage: Arc<dyn LockStorage>,
+    pub memory: Option<Arc<MemoryStorage>>,
+}
+
+/// 按 `REPLICA_STORAGE_TYPE` 连接副本存储，未配置时返回 None
+pub async fn connect_replica(config: &Config, registry: &StorageRegistry) -> Result<Option<Replica>> {
+    let Some(name) = &config.replica_storage_type else {
+        return Ok(None);
+    };
+    info!(
+        "Replicating lock state to {} storage, mode: {:?}, reconcile interval: {}s",
+        name, config.replication_mode, config.replication_reconcile_interval
+    );
+    let replica = match name.as_str() {
+        "redis" => {
+            let url = config
+                .replica_redis_url
+                .as_ref()
+                .ok_or_else(|| anyhow!("REPLICA_REDIS_URL not configured"))?;
+            // 副本的认证信息和数据库编号取自 URL，不沿用主存储的配置
+            let options = RedisOptions {
+                username: None,
+                password: None,
+                db: None,
+                prefix: config.replica_redis_key_prefix.clone(),
+                ..RedisOptions::from_config(config, url)
+            };
+            let redis_storage = RedisStorage::new(options)
+                .await
+                .context("Failed to connect to replica Redis")?;
+            Replica {
+                storage: Arc::new(redis_storage),
+                memory: None,
+            }
+        }
+        "memory" => {
+            let path = config
+                .replica_persist_path
+                .as_ref()
+                .ok_or_else(|| anyhow!("REPLICA_PERSIST_PATH not configured"))?;
+            let memory_storage = Arc::new(
+                MemoryStorage::with_persistence(
+                    PersistTarget::parse(path, config)?,
+                    config.memory_persist_format.clone(),
+                    0,
+                )
+                .with_encryption(SnapshotCipher::from_config(config)?),
+            );
+            let count = memory_storage
+                .load_from_disk()
+                .await
+                .context("Failed to load replica snapshot")?;
+            info!("Loaded {} locks from replica snapshot {}", count, path);
+            Replica {
+                storage: memory_storage.clone(),
+                memory: Some(memory_storage),
+            }
+        }
+        "raft" => bail!("Raft storage cannot be used as a replica"),
+        name => {
+            let factory = registry.get(name).ok_or_else(|| {
+                anyhow!(
+                    "Unknown REPLICA_STORAGE_TYPE '{}', available storage types: {}",
+                    name,
+                    registry.names().join(", ")
+                )
+            })?;
+            let storage = factory
+                .create(config)
+                .await
+                .with_context(|| format!("Failed to create {} replica storage", name))?;
+            Replica { storage, memory: None }
+        }
+    };
+    Ok(Some(Replica {
+        storage: TimeoutStorage::wrap(replica.storage, config.storage_op_timeout_ms),
+        ..replica
+    }))
+}
+
+/// 写入副本的变更
+enum Mutation {
+    /// 新增或覆盖锁
+    Upsert(Box<LockInfo>),
+    /// 删除 lock_key 上 lock_id 对应的锁
+    Remove { lock_key: String, lock_id: String },
+    /// 按主存储中 lock_key 的当前状态写入
+    Resync(String),
+    PutNamespace(NamespacePolicy),
+    DeleteNamespace(String),
+}
+
+/// 一次对账的结果
+#[derive(Debug, Default)]
+pub struct ReconcileReport {
+    pub upserted: usize,
+    pub removed: usize,
+    pub namespaces: usize,
+}
+
+pub struct ReplicatedStorage {
+    primary: Arc<dyn LockStorage>,
+    replica: Arc<dyn LockStorage>,
+    mode: ReplicationMode,
+    queue: mpsc::Sender<Mutation>,
+    receiver: Mutex<Option<mpsc::Receiver<Mutation>>>, // 异步复制任务启动时取走
+    queue_size: usize,
+    replicated: AtomicU64,
+    failures: AtomicU64,
+    dropped: AtomicU64,
+    repaired: AtomicU64,
+}
+
+impl ReplicatedStorage {
+    pub fn new(primary: Arc<dyn LockStorage>, replica: Arc<dyn LockStorage>, config: &Config) -> Self {
+        let (queue, receiver) = mpsc::channel(config.replication_queue_size);
+        Self {
+            primary,
+            replica,
+            mode: config.replication_mode,
+            queue,
+            receiver: Mutex::new(Some(receiver)),
+            queue_size: config.replication_queue_size,
+            replicated: AtomicU64::new(0),
+            failures: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+            repaired: AtomicU64::new(0),
+        }
+    }
+
+    pub fn mode(&self) -> ReplicationMode {
+        self.mode
+    }
+
+    /// 等待写入副本的变更数
+    pub fn queue_depth(&self) -> usize {
+        self.queue_size - self.queue.capacity()
+    }
+
+    /// 已写入副本的变更总数
+    pub fn replicated(&self) -> u64 {
+        self.replicated.load(Ordering::Relaxed)
+    }
+
+    /// 写入副本失败的变更总数
+    pub fn failures(&self) -> u64 {
+        self.failures.load(Ordering::Relaxed)
+    }
+
+    /// 因队列已满丢弃的变更总数
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// 对账修复的锁和命名空间策略总数
+    pub fn repaired(&self) -> u64 {
+        self.repaired.load(Ordering::Relaxed)
+    }
+
+    /// 异步复制任务，按入队顺序写入副本，只能启动一次
+    pub async fn run(self: Arc<Self>) {
+        let Some(mut receiver) = self.receiver.lock().take() else {
+            return;
+        };
+        while let Some(mutation) = receiver.recv().await {
+            self.write(mutation).await;
+        }
+    }
+
+    async fn replicate(&self, mutations: Vec<Mutation>) {
+        for mutation in mutations {
+            match self.mode {
+                ReplicationMode::Sync => self.write(mutation).await,
+                ReplicationMode::Async => {
+                    if self.queue.try_send(mutation).is_err() {
+                        let dropped = self.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+                        if dropped % 1000 == 1 {
+                            log::warn!(
+                                "[REPLICATION] Queue full, {} changes dropped so far, reconciliation will repair the replica",
+                                dropped
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    async fn write(&self, mutation: Mutation) {
+        match self.apply(mutation).await {
+            Ok(()) => {
+                self.replicated.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(e) => {
+                self.failures.fetch_add(1, Ordering::Relaxed);
+                log::warn!("[REPLICATION] Failed to write to replica: {}", e);
+            }
+        }
+    }
+
+    async fn apply(&self, mutation: Mutation) -> Result<()> {
+        match mutation {
+            Mutation::Upsert(lock_info) => self.upsert(*lock_info).await.map(|_| ()),
+            Mutation::Remove { lock_key, lock_id } => self.remove(&lock_key, Some(&lock_id)).await.map(|_| ()),
+            Mutation::Resync(lock_key) => match self.primary.get_lock(&lock_key).await? {
+                Some(lock_info) => self.upsert(lock_info).await.map(|_| ()),
+                None => self.remove(&lock_key, None).await.map(|_| ()),
+            },
+            Mutation::PutNamespace(policy) => self.replica.put_namespace(policy).await,
+            Mutation::DeleteNamespace(name) => self.replica.delete_namespace(&name).await.map(|_| ()),
+        }
+    }
+
+    /// 副本中 lock_key 的锁与 lock_info 不同时覆盖，返回是否写入
+    async fn upsert(&self, lock_info: LockInfo) -> Result<bool> {
+        let lock_key = lock_info.get_lock_key();
+        if let Some(existing) = self.replica.get_lock(&lock_key).await? {
+            if same_state(&existing, &lock_info) {
+                return Ok(false);
+            }
+            self.replica.force_release(&lock_key).await?;
+        }
+        self.replica.restore(lock_info).await
+    }
+
+    /// 删除副本中 lock_key 的锁，lock_id 不为 None 时仅删除该锁，返回是否删除
+    async fn remove(&self, lock_key: &str, lock_id: Option<&str>) -> Result<bool> {
+        match self.replica.get_lock(lock_key).await? {
+            Some(existing) if lock_id.is_none_or(|lock_id| existing.lock_id == lock_id) => {
+                Ok(self.replica.force_release(lock_key).await?.is_some())
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// 以主存储为准修复副本中不一致的锁和命名空间策略，副本中主存储截至 now 已过期的锁同样删除
+    pub async fn reconcile(&self, now: DateTime<Utc>) -> Result<ReconcileReport> {
+        let mut report = ReconcileReport::default();
+
+        let primary: HashMap<String, LockInfo> = self
+            .primary
+            .list_locks()
+            .await?
+            .into_iter()
+            .filter(|lock_info| !lock_info.is_expired_at(now))
+            .map(|lock_info| (lock_info.get_lock_key(), lock_info))
+            .collect();
+        let replica: HashMap<String, LockInfo> = self
+            .replica
+            .list_locks()
+            .await?
+            .into_iter()
+            .map(|lock_info| (lock_info.get_lock_key(), lock_info))
+            .collect();
+        for (lock_key, lock_info) in &primary {
+            if replica
+                .get(lock_key)
+                .is_none_or(|existing| !same_state(existing, lock_info))
+                && self.upsert(lock_info.clone()).await?
+            {
+                report.upserted += 1;
+            }
+        }
+        for lock_key in replica.keys().filter(|lock_key| !primary.contains_key(*lock_key)) {
+            if self.remove(lock_key, None).await? {
+                report.removed += 1;
+            }
+        }
+
+        let policies = self.primary.list_namespaces().await?;
+        let names: HashSet<&str> = policies.iter().map(|policy| policy.name.as_str()).collect();
+        let existing: HashMap<String, NamespacePolicy> = self
+            .replica
+            .list_namespaces()
+            .await?
+            .into_iter()
+            .map(|policy| (policy.name.clone(), policy))
+            .collect();
+        for policy in &policies {
+            if existing.get(&policy.name) != Some(policy) {
+                self.replica.put_namespace(policy.clone()).await?;
+                report.namespaces += 1;
+            }
+        }
+        for name in existing.keys().filter(|name| !names.contains(name.as_str())) {
+            if self.replica.delete_namespace(name).await? {
+                report.namespaces += 1;
+            }
+        }
+
+        self.repaired.fetch_add(
+            (report.upserted + report.removed + report.namespaces) as u64,
+            Ordering::Relaxed,
+        );
+        Ok(report)
+    }
+}
+
+/// 副本中的锁是否已是 lock_info 的状态
+fn same_state(existing: &LockInfo, lock_info: &LockInfo) -> bool {
+    existing.lock_id == lock_info.lock_id
+        && existing.version == lock_info.version
+        && existing.last_heartbeat == lock_info.last_heartbeat
+}
+
+fn upserted(lock_info: &Option<LockInfo>) -> Vec<Mutation> {
+    lock_info
+        .iter()
+        .map(|lock_info| Mutation::Upsert(Box::new(lock_info.clone())))
+        .collect()
+}
+
+fn removed<'a>(lock_infos: impl IntoIterator<Item = &'a LockInfo>) -> Vec<Mutation> {
+    lock_infos
+        .into_iter()
+        .map(|lock_info| Mutation::Remove {
+            lock_key: lock_info.get_lock_key(),
+            lock_id: lock_info.lock_id.clone(),
+        })
+        .collect()
+}
+
+#[async_trait]
+impl LockStorage for ReplicatedStorage {
+    async fn try_acquire(&self, lock_info: LockInfo) -> Result<Acquire> {
+        // 重入时只延长已有的锁，写入副本的是主存储中的锁
+        let lock_key = lock_info.get_lock_key();
+        let acquired = self.primary.try_acquire(lock_info).await?;
+        if acquired.acquired {
+            self.replicate(vec![Mutation::Resync(lock_key)]).await;
+        }
+        Ok(acquired)
+    }
+
+    async fn takeover(&self, lock_info: LockInfo, condition: &LockCondition) -> Result<Takeover> {
+        let takeover = self.primary.takeover(lock_info, condition).await?;
+        if let Takeover::Acquired { lock_info, .. } = &takeover {
+            self.replicate(vec![Mutation::Upsert(Box::new(lock_info.clone()))]).await;
+        }
+        Ok(takeover)
+    }
+
+    async fn restore(&self, lock_info: LockInfo) -> Result<bool> {
+        let restored = self.primary.restore(lock_info.clone()).await?;
+        if restored {
+            self.replicate(vec![Mutation::Upsert(Box::new(lock_info))]).await;
+        }
+        Ok(restored)
+    }
+
+    async fn get_lock(&self, lock_key: &str) -> Result<Option<LockInfo>> {
+        self.primary.get_lock(lock_key).await
+    }
+
+    async fn update_heartbeat(&self, lock_id: &str) -> Result<Option<LockInfo>> {
+        let lock_info = self.primary.update_heartbeat(lock_id).await?;
+        self.replicate(upserted(&lock_info)).await;
+        Ok(lock_info)
+    }
+
+    async fn update_heartbeat_version(&self, lock_id: &str, version: u64) -> Result<Option<LockInfo>> {
+        let lock_info = self.primary.update_heartbeat_version(lock_id, version).await?;
+        self.replicate(upserted(&lock_info)).await;
+        Ok(lock_info)
+    }
+
+    async fn release(&self, lock_id: &str) -> Result<Option<LockInfo>> {
+        let lock_info = self.primary.release(lock_id).await?;
+        self.replicate(removed(&lock_info)).await;
+        Ok(lock_info)
+    }
+
+    async fn release_version(&self, lock_id: &str, version: u64) -> Result<Option<LockInfo>> {
+        let lock_info = self.primary.release_version(lock_id, version).await?;
+        self.replicate(removed(&lock_info)).await;
+        Ok(lock_info)
+    }
+
+    async fn release_owned(&self, lock_key: &str, user_id: &str) -> Result<Option<LockInfo>> {
+        let lock_info = self.primary.release_owned(lock_key, user_id).await?;
+        self.replicate(removed(&lock_info)).await;
+        Ok(lock_info)
+    }
+
+    async fn cleanup_expired(&self) -> Result<Vec<LockInfo>> {
+        let expired = self.primary.cleanup_expired().await?;
+        self.replicate(removed(&expired)).await;
+        Ok(expired)
+    }
+
+    async fn list_locks(&self) -> Result<Vec<LockInfo>> {
+        self.primary.list_locks().await
+    }
+
+    async fn list_prefix(&self, key_prefix: &str) -> Result<Vec<LockInfo>> {
+        self.primary.list_prefix(key_prefix).await
+    }
+
+    async fn get_lock_by_id(&self, lock_id: &str) -> Result<Option<LockInfo>> {
+        self.primary.get_lock_by_id(lock_id).await
+    }
+
+    async fn force_release(&self, lock_key: &str) -> Result<Option<LockInfo>> {
+        let lock_info = self.primary.force_release(lock_key).await?;
+        self.replicate(removed(&lock_info)).await;
+        Ok(lock_info)
+    }
+
+    async fn mark_preempted(&self, lock_id: &str, notice: PreemptionNotice) -> Result<Option<LockInfo>> {
+        let lock_info = self.primary.mark_preempted(lock_id, notice).await?;
+        self.replicate(upserted(&lock_info)).await;
+        Ok(lock_info)
+    }
+
+    async fn transfer(&self, lock_id: &str, new_lock: LockInfo) -> Result<Option<LockInfo>> {
+        let lock_key = new_lock.get_lock_key();
+        let old_lock = self.primary.transfer(lock_id, new_lock).await?;
+        if old_lock.is_some() {
+            self.replicate(vec![Mutation::Resync(lock_key)]).await;
+        }
+        Ok(old_lock)
+    }
+
+    async fn rewrite_lock(&self, lock_info: LockInfo) -> Result<bool> {
+        let lock_key = lock_info.get_lock_key();
+        let written = self.primary.rewrite_lock(lock_info).await?;
+        if written {
+            self.replicate(vec![Mutation::Resync(lock_key)]).await;
+        }
+        Ok(written)
+    }
+
+    async fn list_by_user(&self, user_id: &str) -> Result<Vec<LockInfo>> {
+        self.primary.list_by_user(user_id).await
+    }
+
+    async fn list_by_business_id(&self, business_id: &str) -> Result<Vec<LockInfo>> {
+        self.primary.list_by_business_id(business_id).await
+    }
+
+    async fn release_all_by_user(&self, user_id: &str) -> Result<Vec<LockInfo>> {
+        let released = self.primary.release_all_by_user(user_id).await?;
+        self.replicate(removed(&released)).await;
+        Ok(released)
+    }
+
+    async fn count_locks(&self, namespace: &str) -> Result<u64> {
+        self.primary.count_locks(namespace).await
+    }
+
+    async fn stats(&self, top_n: usize) -> Result<LockStats> {
+        self.primary.stats(top_n).await
+    }
+
+    async fn list_namespaces(&self) -> Result<Vec<NamespacePolicy>> {
+        self.primary.list_namespaces().await
+    }
+
+    async fn get_namespace(&self, name: &str) -> Result<Option<NamespacePolicy>> {
+        self.primary.get_namespace(name).await
+    }
+
+    async fn put_namespace(&self, policy: NamespacePolicy) -> Result<()> {
+        self.primary.put_namespace(policy.clone()).await?;
+        self.replicate(vec![Mutation::PutNamespace(policy)]).await;
+        Ok(())
+    }
+
+    async fn delete_namespace(&self, name: &str) -> Result<bool> {
+        let deleted = self.primary.delete_namespace(name).await?;
+        if deleted {
+            self.replicate(vec![Mutation::DeleteNamespace(name.to_string())]).await;
+        }
+        Ok(deleted)
+    }
+
+    async fn next_sequence(&self, name: &str, count: u64) -> Result<u64> {
+        self.primary.next_sequence(name, count).await
+    }
+
+    async fn take_tokens(&self, key: &str, bucket: TokenBucket, cost: u64) -> Result<RateLimitDecision> {
+        self.primary.take_tokens(key, bucket, cost).await
+    }
+
+    // 记录与序列一样只保存在主存储
+    async fn get_record(&self, kind: &str, id: &str) -> Result<Option<String>> {
+        self.primary.get_record(kind, id).await
+    }
+
+    async fn list_records(&self, kind: &str) -> Result<Vec<(String, String)>> {
+        self.primary.list_records(kind).await
+    }
+
+    async fn swap_record(&self, kind: &str, id: &str, expected: Option<&str>, value: Option<&str>) -> Result<bool> {
+        self.primary.swap_record(kind, id, expected, value).await
+    }
+}
@@ -0,0 +1,269 @@
+//! S3 兼容对象存储上的快照对象
+//!
+//! 使用 AWS Signature V4 签名访问 AWS S3 或 MinIO 等兼容服务。写入时带条件头：已读取过对象时使用 `If-Match: <ETag>`，
+//! 对象尚不存在时使用 `If-None-Match: *`，对象在此期间被其他实例改写时返回 412，不会覆盖对方的快照。
+
+use crate::config::Config;
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use reqwest::{Method, StatusCode};
+use ring::{digest, hmac};
+use std::fmt;
+use std::sync::Arc;
+use thiserror::Error;
+
+/// 快照对象的文件名，位于 `s3://bucket/prefix` 的前缀下
+const OBJECT_NAME: &str = "locks.snapshot";
+
+/// 快照对象已被其他实例改写
+#[derive(Debug, Error)]
+#[error("snapshot {0} was modified by another instance (ETag mismatch), refusing to overwrite")]
+pub struct SnapshotConflict(pub String);
+
+/// 存储桶的访问信息，同一前缀下的对象共享
+struct S3Bucket {
+    client: reqwest::Client,
+    name: String,
+    base: String,     // 端点地址
+    path_style: bool, // 是否使用路径风格（对象路径以存储桶名开头）
+    host: String,
+    region: String,
+    access_key: String,
+    secret_key: String,
+    session_token: Option<String>,
+}
+
+pub struct S3Object {
+    bucket: Arc<S3Bucket>,
+    dir: String,      // 前缀，不含首尾的 `/`
+    name: String,     // 前缀下的对象名
+    location: String, // s3://bucket/key，用于日志
+    url: String,
+    canonical_uri: String,
+    etag: Mutex<Option<String>>, // 最近一次读取或写入时对象的 ETag，None 表示对象不存在
+}
+
+impl S3Object {
+    /// 解析 `s3://bucket/prefix`，快照对象为 `<prefix>/locks.snapshot`
+    pub fn from_url(spec: &str, config: &Config) -> Result<Self> {
+        let rest = spec
+            .strip_prefix("s3://")
+            .ok_or_else(|| anyhow!("invalid S3 location {}, expected s3://bucket/prefix", spec))?;
+        let (bucket, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+        if bucket.is_empty() {
+            bail!("invalid S3 location {}, bucket is empty", spec);
+        }
+
+        let access_key = config
+            .memory_persist_s3_access_key
+            .clone()
+            .ok_or_else(|| anyhow!("AWS_ACCESS_KEY_ID is required for S3 persistence"))?;
+        let secret_key = config
+            .memory_persist_s3_secret_key
+            .clone()
+            .ok_or_else(|| anyhow!("AWS_SECRET_ACCESS_KEY is required for S3 persistence"))?;
+        let region = config.memory_persist_s3_region.clone();
+
+        // 自定义端点（MinIO 等）使用路径风格，AWS 使用虚拟主机风格
+        let (base, path_style) = match &config.memory_persist_s3_endpoint {
+            Some(endpoint) => (endpoint.trim_end_matches('/').to_string(), true),
+            None => (format!("https://{}.s3.{}.amazonaws.com", bucket, region), false),
+        };
+        let endpoint = reqwest::Url::parse(&base).with_context(|| format!("invalid S3 endpoint {}", base))?;
+        let host = match (endpoint.host_str(), endpoint.port()) {
+            (Some(host), Some(port)) => format!("{}:{}", host, port),
+            (Some(host), None) => host.to_string(),
+            (None, _) => bail!("invalid S3 endpoint {}", base),
+        };
+
+        let bucket = Arc::new(S3Bucket {
+            client: reqwest::Client::new(),
+            name: bucket.to_string(),
+            base,
+            path_style,
+            host,
+            region,
+            access_key,
+            secret_key,
+            session_token: config.memory_persist_s3_session_token.clone(),
+        });
+        Ok(Self::at(bucket, prefix.trim_matches('/').to_string(), OBJECT_NAME))
+    }
+
+    /// 同一前缀下名为 name 的对象，name 可包含 `/`
+    pub fn child(&self, name: &str) -> Self {
+        Self::at(self.bucket.clone(), self.dir.clone(), name)
+    }
+
+    /// 同一前缀下对象名追加 suffix 的对象
+    pub fn suffixed(&self, suffix: &str) -> Self {
+        Self::at(self.bucket.clone(), self.dir.clone(), &format!("{}{}", self.name, suffix))
+    }
+
+    fn at(bucket: Arc<S3Bucket>, dir: String, name: &str) -> Self {
+        let key = if dir.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}/{}", dir, name)
+        };
+        let canonical_uri = if bucket.path_style {
+            format!("/{}/{}", uri_encode(&bucket.name), uri_encode(&key))
+        } else {
+            format!("/{}", uri_encode(&key))
+        };
+        Self {
+            location: format!("s3://{}/{}", bucket.name, key),
+            url: format!("{}{}", bucket.base, canonical_uri),
+            canonical_uri,
+            dir,
+            name: name.to_string(),
+            bucket,
+            etag: Mutex::new(None),
+        }
+    }
+
+    /// 读取快照对象，对象不存在时为 None
+    pub async fn get(&self) -> Result<Option<Vec<u8>>> {
+        let response = self.request(Method::GET, Vec::new()).send().await?;
+        match response.status() {
+            StatusCode::NOT_FOUND => {
+                *self.etag.lock() = None;
+                Ok(None)
+            }
+            status if status.is_success() => {
+                let etag = header_value(&response, "etag");
+                let bytes = response.bytes().await?.to_vec();
+                *self.etag.lock() = etag;
+                Ok(Some(bytes))
+            }
+            status => bail!("GET {} failed: {} {}", self.location, status, response.text().await?),
+        }
+    }
+
+    /// 条件写入快照对象，对象的 ETag 与上次读取或写入时不一致时返回 SnapshotConflict
+    pub async fn put(&self, bytes: Vec<u8>) -> Result<()> {
+        let expected = self.etag.lock().clone();
+        let request = self.request(Method::PUT, bytes);
+        let request = match &expected {
+            Some(etag) => request.header("if-match", etag),
+            None => request.header("if-none-match", "*"),
+        };
+        let response = request.send().await?;
+        match response.status() {
+            // 409 为并发的条件写入冲突
+            StatusCode::PRECONDITION_FAILED | StatusCode::CONFLICT => {
+                Err(SnapshotConflict(self.location.clone()).into())
+            }
+            status if status.is_success() => {
+                *self.etag.lock() = header_value(&response, "etag");
+                Ok(())
+            }
+            status => bail!("PUT {} failed: {} {}", self.location, status, response.text().await?),
+        }
+    }
+
+    /// 删除对象，对象不存在时忽略
+    pub async fn delete(&self) -> Result<()> {
+        let response = self.request(Method::DELETE, Vec::new()).send().await?;
+        match response.status() {
+            status if status.is_success() || status == StatusCode::NOT_FOUND => {
+                *self.etag.lock() = None;
+                Ok(())
+            }
+            status => bail!("DELETE {} failed: {} {}", self.location, status, response.text().await?),
+        }
+    }
+
+    fn request(&self, method: Method, body: Vec<u8>) -> reqwest::RequestBuilder {
+        let mut request = self.bucket.client.request(method.clone(), &self.url);
+        for (name, value) in self.sign(method.as_str(), &body, Utc::now()) {
+            request = request.header(name, value);
+        }
+        request.body(body)
+    }
+
+    /// 生成 Signature V4 签名所需的请求头
+    fn sign(&self, method: &str, payload: &[u8], now: DateTime<Utc>) -> Vec<(&'static str, String)> {
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let payload_hash = hex(digest::digest(&digest::SHA256, payload).as_ref());
+
+        let mut headers = vec![
+            ("host", self.bucket.host.clone()),
+            ("x-amz-content-sha256", payload_hash.clone()),
+            ("x-amz-date", amz_date.clone()),
+        ];
+        if let Some(token) = &self.bucket.session_token {
+            headers.push(("x-amz-security-token", token.clone()));
+        }
+        let signed_headers = headers.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(";");
+        let canonical_headers: String = headers
+            .iter()
+            .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+            .collect();
+        let canonical_request = format!(
+            "{}\n{}\n\n{}\n{}\n{}",
+            method, self.canonical_uri, canonical_headers, signed_headers, payload_hash
+        );
+
+        let scope = format!("{}/{}/s3/aws4_request", date, self.bucket.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex(digest::digest(&digest::SHA256, canonical_request.as_bytes()).as_ref())
+        );
+        let mut key = format!("AWS4{}", self.bucket.secret_key).into_bytes();
+        for part in [date.as_str(), self.bucket.region.as_str(), "s3", "aws4_request"] {
+            key = hmac_sha256(&key, part.as_bytes());
+        }
+        let signature = hex(&hmac_sha256(&key, string_to_sign.as_bytes()));
+
+        headers.retain(|(name, _)| *name != "host");
+        headers.push((
+            "authorization",
+            format!(
+                "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+                self.bucket.access_key, scope, signed_headers, signature
+            ),
+        ));
+        headers
+    }
+}
+
+impl fmt::Display for S3Object {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.location)
+    }
+}
+
+fn header_value(response: &reqwest::Response, name: &str) -> Option<String> {
+    response
+        .headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, key), data).as_ref().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// 按 Signature V4 的规则编码路径，保留 `/`
+fn uri_encode(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
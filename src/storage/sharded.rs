@@ -0,0 +1,533 @@
+//! 分片 Redis 存储
+//!
+//! 配置 `REDIS_SHARDS` 后，锁按 lock_key 以一致性哈希分布到多个相互独立的 Redis，每个 Redis 在哈希环上有
+//! `REDIS_SHARD_VNODES` 个虚拟节点。命名空间策略、序列和令牌桶同样按名称分布到各分片。
+//!
+//! - 按 lock_key 的操作只访问所在分片；按 lock_id 的操作优先使用本实例记录的 lock_id 所在分片，
+//!   未记录时（如锁由其他实例申请）并发查询所有分片
+//! - 列表、按用户释放、计数和统计访问所有分片后合并结果
+//! - 每个分片各自经过 [`FailoverStorage`] 的重试、断路器和降级，单个分片不可用只影响落在该分片上的锁
+//!
+//! 增减分片会使约 1/N 的 lock_key 改变所在分片，已持有的锁在新分片上不可见，应在锁全部过期或释放后调整。
+
+use crate::clock::{self, Clock};
+use crate::config::Config;
+use crate::models::{
+    ContendedLock, HeldLock, Histogram, LockCondition, LockInfo, LockStats, NamespacePolicy, PreemptionNotice,
+    RateLimitDecision, TokenBucket,
+};
+use crate::storage::failover::{FailoverStorage, StorageHealth};
+use crate::storage::redis::{RedisOptions, RedisStorage};
+use crate::storage::timeout::TimeoutStorage;
+use crate::storage::{Acquire, LockStorage, Takeover};
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use futures_util::future::join_all;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// lock_id 记录超过上次清理时的两倍后清理已过期的记录
+const MIN_PRUNE_LEN: usize = 1024;
+
+/// 单个分片
+pub struct Shard {
+    pub name: String, // host:port/db，不含认证信息，决定分片在哈希环上的位置
+    pub redis: Arc<RedisStorage>,
+    pub storage: Arc<FailoverStorage>,
+}
+
+/// 分片的健康状态，用于 `/readyz` 和 `/metrics`
+#[derive(Debug, Serialize)]
+pub struct ShardHealth {
+    pub shard: String,
+    #[serde(flatten)]
+    pub health: StorageHealth,
+}
+
+pub struct ShardedStorage {
+    shards: Vec<Shard>,
+    ring: Vec<(u64, usize)>,                                 // 按哈希值排序的虚拟节点 -> 分片下标
+    lock_ids: DashMap<String, (usize, Option<DateTime<Utc>>)>, // lock_id -> (分片下标, 锁的过期时间)
+    pruned_len: AtomicUsize,
+    clock: Arc<dyn Clock>,
+}
+
+impl ShardedStorage {
+    /// 连接 `REDIS_SHARDS` 中的所有 Redis
+    pub async fn connect(config: &Config) -> Result<Self> {
+        let mut shards = Vec::with_capacity(config.redis_shards.len());
+        for url in &config.redis_shards {
+            let name = shard_name(url)?;
+            let redis = Arc::new(
+                RedisStorage::new(RedisOptions::from_config(config, url))
+                    .await
+                    .with_context(|| format!("Failed to connect to Redis shard {}", name))?,
+            );
+            let storage = Arc::new(FailoverStorage::new(
+                TimeoutStorage::wrap(redis.clone(), config.storage_op_timeout_ms),
+                config,
+            ));
+            shards.push(Shard { name, redis, storage });
+        }
+        Self::new(shards, config.redis_shard_vnodes)
+    }
+
+    pub fn new(shards: Vec<Shard>, vnodes: usize) -> Result<Self> {
+        if shards.is_empty() {
+            bail!("At least one Redis shard is required");
+        }
+        let mut ring = Vec::with_capacity(shards.len() * vnodes.max(1));
+        for (index, shard) in shards.iter().enumerate() {
+            if shards[..index].iter().any(|other| other.name == shard.name) {
+                bail!("Duplicate Redis shard {}", shard.name);
+            }
+            for vnode in 0..vnodes.max(1) {
+                ring.push((hash(&format!("{}#{}", shard.name, vnode)), index));
+            }
+        }
+        ring.sort_unstable();
+        Ok(Self {
+            shards,
+            ring,
+            lock_ids: DashMap::new(),
+            pruned_len: AtomicUsize::new(MIN_PRUNE_LEN),
+            clock: clock::system(),
+        })
+    }
+
+    /// 使用指定的时钟清理已过期的 lock_id 记录，默认为系统时钟
+    pub fn with_clock(self, clock: Arc<dyn Clock>) -> Self {
+        Self { clock, ..self }
+    }
+
+    pub fn shards(&self) -> &[Shard] {
+        &self.shards
+    }
+
+    pub fn health(&self) -> Vec<ShardHealth> {
+        self.shards
+            .iter()
+            .map(|shard| ShardHealth {
+                shard: shard.name.clone(),
+                health: shard.storage.health(),
+            })
+            .collect()
+    }
+
+    /// 定时探测各分片，断路器打开的分片恢复后关闭断路器
+    pub async fn probe(&self) {
+        join_all(self.shards.iter().map(|shard| shard.storage.probe())).await;
+    }
+
+    /// 哈希环上顺时针方向第一个虚拟节点所在的分片
+    fn index_of(&self, key: &str) -> usize {
+        let hash = hash(key);
+        let position = self.ring.partition_point(|(vnode, _)| *vnode < hash);
+        self.ring[position % self.ring.len()].1
+    }
+
+    fn shard_of_key(&self, lock_key: &str) -> &dyn LockStorage {
+        self.shards[self.index_of(lock_key)].storage.as_ref()
+    }
+
+    /// 命名空间策略、序列和令牌桶按名称分布，与 lock_key 使用不同的前缀
+    fn shard_of(&self, kind: &str, name: &str) -> &dyn LockStorage {
+        self.shards[self.index_of(&format!("{}\0{}", kind, name))].storage.as_ref()
+    }
+
+    /// 记录锁所在的分片
+    fn remember(&self, lock_info: &LockInfo) {
+        let index = self.index_of(&lock_info.get_lock_key());
+        self.lock_ids
+            .insert(lock_info.lock_id.clone(), (index, lock_info.expiry_deadline()));
+
+        let len = self.lock_ids.len();
+        if len > self.pruned_len.load(Ordering::Relaxed) * 2 {
+            let now = self.clock.now();
+            self.lock_ids
+                .retain(|_, (_, expires_at)| expires_at.is_none_or(|expires_at| expires_at > now));
+            self.pruned_len
+                .store(self.lock_ids.len().max(MIN_PRUNE_LEN), Ordering::Relaxed);
+        }
+    }
+
+    fn forget(&self, lock_id: &str) {
+        self.lock_ids.remove(lock_id);
+    }
+
+    /// 按操作结果更新 lock_id 记录：返回锁时记录，锁不存在时移除
+    fn track(&self, lock_id: &str, result: Result<Option<LockInfo>>) -> Result<Option<LockInfo>> {
+        match &result {
+            Ok(Some(lock_info)) => self.remember(lock_info),
+            Ok(None) => self.forget(lock_id),
+            Err(_) => {}
+        }
+        result
+    }
+
+    /// 锁所在的分片，本实例未记录时查询所有分片
+    async fn index_of_id(&self, lock_id: &str) -> Result<Option<usize>> {
+        if let Some(entry) = self.lock_ids.get(lock_id) {
+            return Ok(Some(entry.0));
+        }
+        let results = join_all(self.shards.iter().map(|shard| shard.storage.get_lock_by_id(lock_id))).await;
+        let mut error = None;
+        for (index, result) in results.into_iter().enumerate() {
+            match result {
+                Ok(Some(lock_info)) => {
+                    self.remember(&lock_info);
+                    return Ok(Some(index));
+                }
+                Ok(None) => {}
+                Err(e) => error = Some(e.context(format!("Redis shard {}", self.shards[index].name))),
+            }
+        }
+        // 有分片查询失败时无法确认锁不存在
+        error.map_or(Ok(None), Err)
+    }
+
+    /// 在所有分片上执行操作并合并结果
+    async fn all<'a, T, F, Fut>(&'a self, op: F) -> Result<Vec<T>>
+    where
+        F: Fn(&'a dyn LockStorage) -> Fut,
+        Fut: std::future::Future<Output = Result<T>>,
+    {
+        let results = join_all(self.shards.iter().map(|shard| op(shard.storage.as_ref()))).await;
+        results
+            .into_iter()
+            .zip(&self.shards)
+            .map(|(result, shard)| result.with_context(|| format!("Redis shard {}", shard.name)))
+            .collect()
+    }
+}
+
+/// 分片名称 host:port/db，不含认证信息，修改密码不会改变分片位置
+fn shard_name(url: &str) -> Result<String> {
+    let info = redis::ConnectionInfo::from_str(url).map_err(|e| anyhow!("Invalid Redis shard URL: {}", e))?;
+    Ok(format!("{}/{}", info.addr, info.redis.db))
+}
+
+/// 跨进程稳定的 64 位哈希（FNV-1a，末尾做一次雪崩混合使相近的键分布均匀）
+fn hash(key: &str) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in key.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    hash ^ (hash >> 33)
+}
+
+/// 合并各分片的统计，lock_key 只属于一个分片，排行直接合并
+fn merge_stats(shard_stats: Vec<LockStats>, top_n: usize) -> LockStats {
+    let mut merged = LockStats {
+        active_locks: 0,
+        namespaces: BTreeMap::new(),
+        acquired: 0,
+        conflicts: 0,
+        expired: 0,
+        top_contended: Vec::new(),
+        longest_held: Vec::new(),
+        hold_time: Histogram::default(),
+        contention: Histogram::default(),
+        since: Utc::now(),
+    };
+    for stats in shard_stats {
+        merged.active_locks += stats.active_locks;
+        for (namespace, count) in stats.namespaces {
+            *merged.namespaces.entry(namespace).or_default() += count;
+        }
+        merged.acquired += stats.acquired;
+        merged.conflicts += stats.conflicts;
+        merged.expired += stats.expired;
+        merged.top_contended.extend(stats.top_contended);
+        merged.longest_held.extend(stats.longest_held);
+        merge_histogram(&mut merged.hold_time, stats.hold_time);
+        merge_histogram(&mut merged.contention, stats.contention);
+        merged.since = merged.since.min(stats.since);
+    }
+    merged.top_contended.sort_by(|a: &ContendedLock, b: &ContendedLock| {
+        b.conflicts.cmp(&a.conflicts).then_with(|| a.lock_key.cmp(&b.lock_key))
+    });
+    merged.top_contended.truncate(top_n);
+    merged.longest_held.sort_by(|a: &HeldLock, b: &HeldLock| {
+        b.max_hold_secs
+            .total_cmp(&a.max_hold_secs)
+            .then_with(|| a.lock_key.cmp(&b.lock_key))
+    });
+    merged.longest_held.truncate(top_n);
+    merged
+}
+
+/// 累加桶上界相同的累计直方图
+fn merge_histogram(merged: &mut Histogram, histogram: Histogram) {
+    if merged.buckets.is_empty() {
+        merged.buckets = histogram.buckets;
+    } else {
+        for (bucket, other) in merged.buckets.iter_mut().zip(&histogram.buckets) {
+            bucket.count += other.count;
+        }
+    }
+    merged.count += histogram.count;
+    merged.sum += histogram.sum;
+}
+
+#[async_trait]
+impl LockStorage for ShardedStorage {
+    async fn try_acquire(&self, lock_info: LockInfo) -> Result<Acquire> {
+        let acquired = self
+            .shard_of_key(&lock_info.get_lock_key())
+            .try_acquire(lock_info.clone())
+            .await?;
+        if acquired.acquired {
+            if let Some(replaced) = &acquired.replaced {
+                self.forget(&replaced.lock_id);
+            }
+            self.remember(&lock_info);
+        }
+        Ok(acquired)
+    }
+
+    async fn takeover(&self, lock_info: LockInfo, condition: &LockCondition) -> Result<Takeover> {
+        let shard = self.shard_of_key(&lock_info.get_lock_key());
+        let takeover = shard.takeover(lock_info, condition).await?;
+        if let Takeover::Acquired { lock_info, replaced } = &takeover {
+            if let Some(replaced) = replaced {
+                self.forget(&replaced.lock_id);
+            }
+            self.remember(lock_info);
+        }
+        Ok(takeover)
+    }
+
+    async fn restore(&self, lock_info: LockInfo) -> Result<bool> {
+        let restored = self
+            .shard_of_key(&lock_info.get_lock_key())
+            .restore(lock_info.clone())
+            .await?;
+        if restored {
+            self.remember(&lock_info);
+        }
+        Ok(restored)
+    }
+
+    async fn get_lock(&self, lock_key: &str) -> Result<Option<LockInfo>> {
+        self.shard_of_key(lock_key).get_lock(lock_key).await
+    }
+
+    async fn update_heartbeat(&self, lock_id: &str) -> Result<Option<LockInfo>> {
+        let Some(index) = self.index_of_id(lock_id).await? else {
+            return Ok(None);
+        };
+        let result = self.shards[index].storage.update_heartbeat(lock_id).await;
+        self.track(lock_id, result)
+    }
+
+    async fn update_heartbeat_version(&self, lock_id: &str, version: u64) -> Result<Option<LockInfo>> {
+        let Some(index) = self.index_of_id(lock_id).await? else {
+            return Ok(None);
+        };
+        self.shards[index]
+            .storage
+            .update_heartbeat_version(lock_id, version)
+            .await
+    }
+
+    async fn release(&self, lock_id: &str) -> Result<Option<LockInfo>> {
+        let Some(index) = self.index_of_id(lock_id).await? else {
+            return Ok(None);
+        };
+        let released = self.shards[index].storage.release(lock_id).await?;
+        self.forget(lock_id);
+        Ok(released)
+    }
+
+    async fn release_version(&self, lock_id: &str, version: u64) -> Result<Option<LockInfo>> {
+        let Some(index) = self.index_of_id(lock_id).await? else {
+            return Ok(None);
+        };
+        let released = self.shards[index].storage.release_version(lock_id, version).await?;
+        if released.is_some() {
+            self.forget(lock_id);
+        }
+        Ok(released)
+    }
+
+    async fn release_owned(&self, lock_key: &str, user_id: &str) -> Result<Option<LockInfo>> {
+        let released = self.shard_of_key(lock_key).release_owned(lock_key, user_id).await?;
+        if let Some(lock_info) = &released {
+            self.forget(&lock_info.lock_id);
+        }
+        Ok(released)
+    }
+
+    async fn cleanup_expired(&self) -> Result<Vec<LockInfo>> {
+        let expired: Vec<LockInfo> = self
+            .all(|storage| storage.cleanup_expired())
+            .await?
+            .into_iter()
+            .flatten()
+            .collect();
+        for lock_info in &expired {
+            self.forget(&lock_info.lock_id);
+        }
+        Ok(expired)
+    }
+
+    async fn list_locks(&self) -> Result<Vec<LockInfo>> {
+        Ok(self
+            .all(|storage| storage.list_locks())
+            .await?
+            .into_iter()
+            .flatten()
+            .collect())
+    }
+
+    async fn list_prefix(&self, key_prefix: &str) -> Result<Vec<LockInfo>> {
+        Ok(self
+            .all(|storage| storage.list_prefix(key_prefix))
+            .await?
+            .into_iter()
+            .flatten()
+            .collect())
+    }
+
+    async fn get_lock_by_id(&self, lock_id: &str) -> Result<Option<LockInfo>> {
+        let Some(index) = self.index_of_id(lock_id).await? else {
+            return Ok(None);
+        };
+        let result = self.shards[index].storage.get_lock_by_id(lock_id).await;
+        self.track(lock_id, result)
+    }
+
+    async fn force_release(&self, lock_key: &str) -> Result<Option<LockInfo>> {
+        let released = self.shard_of_key(lock_key).force_release(lock_key).await?;
+        if let Some(lock_info) = &released {
+            self.forget(&lock_info.lock_id);
+        }
+        Ok(released)
+    }
+
+    async fn mark_preempted(&self, lock_id: &str, notice: PreemptionNotice) -> Result<Option<LockInfo>> {
+        let Some(index) = self.index_of_id(lock_id).await? else {
+            return Ok(None);
+        };
+        self.shards[index].storage.mark_preempted(lock_id, notice).await
+    }
+
+    async fn transfer(&self, lock_id: &str, new_lock: LockInfo) -> Result<Option<LockInfo>> {
+        // 转移前后 lock_key 相同，位于同一分片
+        let shard = self.shard_of_key(&new_lock.get_lock_key());
+        let replaced = shard.transfer(lock_id, new_lock.clone()).await?;
+        if replaced.is_some() {
+            self.forget(lock_id);
+            self.remember(&new_lock);
+        }
+        Ok(replaced)
+    }
+
+    async fn rewrite_lock(&self, lock_info: LockInfo) -> Result<bool> {
+        self.shard_of_key(&lock_info.get_lock_key()).rewrite_lock(lock_info).await
+    }
+
+    async fn list_by_user(&self, user_id: &str) -> Result<Vec<LockInfo>> {
+        Ok(self
+            .all(|storage| storage.list_by_user(user_id))
+            .await?
+            .into_iter()
+            .flatten()
+            .collect())
+    }
+
+    async fn list_by_business_id(&self, business_id: &str) -> Result<Vec<LockInfo>> {
+        Ok(self
+            .all(|storage| storage.list_by_business_id(business_id))
+            .await?
+            .into_iter()
+            .flatten()
+            .collect())
+    }
+
+    async fn release_all_by_user(&self, user_id: &str) -> Result<Vec<LockInfo>> {
+        let released: Vec<LockInfo> = self
+            .all(|storage| storage.release_all_by_user(user_id))
+            .await?
+            .into_iter()
+            .flatten()
+            .collect();
+        for lock_info in &released {
+            self.forget(&lock_info.lock_id);
+        }
+        Ok(released)
+    }
+
+    async fn count_locks(&self, namespace: &str) -> Result<u64> {
+        Ok(self
+            .all(|storage| storage.count_locks(namespace))
+            .await?
+            .into_iter()
+            .sum())
+    }
+
+    async fn stats(&self, top_n: usize) -> Result<LockStats> {
+        Ok(merge_stats(self.all(|storage| storage.stats(top_n)).await?, top_n))
+    }
+
+    async fn list_namespaces(&self) -> Result<Vec<NamespacePolicy>> {
+        let mut namespaces: Vec<NamespacePolicy> = self
+            .all(|storage| storage.list_namespaces())
+            .await?
+            .into_iter()
+            .flatten()
+            .collect();
+        namespaces.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(namespaces)
+    }
+
+    async fn get_namespace(&self, name: &str) -> Result<Option<NamespacePolicy>> {
+        self.shard_of("namespace", name).get_namespace(name).await
+    }
+
+    async fn put_namespace(&self, policy: NamespacePolicy) -> Result<()> {
+        self.shard_of("namespace", &policy.name).put_namespace(policy).await
+    }
+
+    async fn delete_namespace(&self, name: &str) -> Result<bool> {
+        self.shard_of("namespace", name).delete_namespace(name).await
+    }
+
+    async fn next_sequence(&self, name: &str, count: u64) -> Result<u64> {
+        self.shard_of("sequence", name).next_sequence(name, count).await
+    }
+
+    async fn take_tokens(&self, key: &str, bucket: TokenBucket, cost: u64) -> Result<RateLimitDecision> {
+        self.shard_of("ratelimit", key).take_tokens(key, bucket, cost).await
+    }
+
+    async fn get_record(&self, kind: &str, id: &str) -> Result<Option<String>> {
+        self.shard_of(kind, id).get_record(kind, id).await
+    }
+
+    async fn list_records(&self, kind: &str) -> Result<Vec<(String, String)>> {
+        let mut records: Vec<(String, String)> = self
+            .all(|storage| storage.list_records(kind))
+            .await?
+            .into_iter()
+            .flatten()
+            .collect();
+        records.sort();
+        Ok(records)
+    }
+
+    async fn swap_record(&self, kind: &str, id: &str, expected: Option<&str>, value: Option<&str>) -> Result<bool> {
+        self.shard_of(kind, id).swap_record(kind, id, expected, value).await
+    }
+}
@@ -0,0 +1,615 @@
+use crate::config::PersistFormat;
+use crate::models::{LockInfo, NamespacePolicy};
+use anyhow::{bail, Result};
+use ring::digest;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+/// 二进制快照文件头魔数
+const MAGIC: &[u8; 4] = b"FLCK";
+/// 二进制增量快照文件头魔数，其后为 4 字节（小端）的头部长度、bincode 编码的 [`DeltaHeader`] 和完整格式的快照
+const DELTA_MAGIC: &[u8; 4] = b"FLCI";
+/// 二进制快照格式版本，快照或 LockInfo 结构变化时需要递增，并在 legacy 中保留旧结构
+const FORMAT_VERSION: u8 = 14;
+/// zstd 压缩级别
+const ZSTD_LEVEL: i32 = 3;
+/// 校验和尾部的前缀，尾部为 `\n# sha256:<64 位十六进制>\n`，位于文件末尾（加密文件在密文之后）
+const CHECKSUM_PREFIX: &[u8] = b"\n# sha256:";
+/// 校验和尾部的总长度
+const CHECKSUM_LEN: usize = CHECKSUM_PREFIX.len() + 64 + 1;
+
+/// 快照内容与尾部的校验和不一致
+#[derive(Debug, Error)]
+#[error("snapshot checksum mismatch")]
+pub struct ChecksumMismatch;
+
+/// 所有快照副本都无法加载
+#[derive(Debug, Error)]
+#[error("no valid snapshot found at {0}")]
+pub struct SnapshotCorrupt(pub String);
+
+/// 持久化的快照数据
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SnapshotData {
+    pub locks: Vec<LockInfo>,
+    #[serde(default)]
+    pub namespaces: Vec<NamespacePolicy>,
+    #[serde(default)]
+    pub sequences: BTreeMap<String, u64>, // 序列名 -> 已分配的最后一个值
+    #[serde(default)]
+    pub records: BTreeMap<String, BTreeMap<String, String>>, // 记录类别 -> id -> 内容
+}
+
+/// 分片持久化的清单，保存命名空间策略、序列、记录和有锁的命名空间（各自一个分片）
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ShardManifest {
+    pub shards: Vec<String>,
+    pub namespaces: Vec<NamespacePolicy>,
+    pub sequences: BTreeMap<String, u64>,
+    #[serde(default)]
+    pub records: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+/// 增量快照：自基础（全量）快照以来新增、更新和删除的锁，以及当前全部的命名空间策略、序列和记录
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SnapshotDelta {
+    pub base: String,         // 基础快照文件（加密和校验和之后）的 SHA-256，与加载的全量快照不一致时不合并
+    pub sequence: u64,        // 自基础快照以来第几次写入增量
+    pub removed: Vec<String>, // 已删除的锁的 lock_key
+    #[serde(flatten)]
+    pub data: SnapshotData, // 新增或更新的锁
+}
+
+/// 二进制增量快照的头部，锁数据沿用全量快照的编码和版本升级
+#[derive(Serialize, Deserialize)]
+struct DeltaHeader {
+    base: String,
+    sequence: u64,
+    removed: Vec<String>,
+}
+
+/// JSON 快照，兼容只保存锁列表的旧格式
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonSnapshot {
+    Current(SnapshotData),
+    LocksOnly(Vec<LockInfo>),
+}
+
+/// 将快照数据编码为指定格式
+pub fn encode(data: &SnapshotData, format: &PersistFormat) -> Result<Vec<u8>> {
+    match format {
+        PersistFormat::Json => Ok(serde_json::to_vec_pretty(data)?),
+        PersistFormat::Bincode => {
+            let raw = bincode::serialize(data)?;
+            let compressed = zstd::encode_all(raw.as_slice(), ZSTD_LEVEL)?;
+
+            let mut bytes = Vec::with_capacity(MAGIC.len() + 1 + compressed.len());
+            bytes.extend_from_slice(MAGIC);
+            bytes.push(FORMAT_VERSION);
+            bytes.extend_from_slice(&compressed);
+            Ok(bytes)
+        }
+    }
+}
+
+/// 将增量快照编码为指定格式
+pub fn encode_delta(delta: &SnapshotDelta, format: &PersistFormat) -> Result<Vec<u8>> {
+    match format {
+        PersistFormat::Json => Ok(serde_json::to_vec_pretty(delta)?),
+        PersistFormat::Bincode => {
+            let header = bincode::serialize(&DeltaHeader {
+                base: delta.base.clone(),
+                sequence: delta.sequence,
+                removed: delta.removed.clone(),
+            })?;
+            let data = encode(&delta.data, format)?;
+
+            let mut bytes = Vec::with_capacity(DELTA_MAGIC.len() + 4 + header.len() + data.len());
+            bytes.extend_from_slice(DELTA_MAGIC);
+            bytes.extend_from_slice(&(header.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(&header);
+            bytes.extend_from_slice(&data);
+            Ok(bytes)
+        }
+    }
+}
+
+/// 解码增量快照，自动识别格式
+pub fn decode_delta(bytes: &[u8]) -> Result<SnapshotDelta> {
+    let Some(rest) = bytes.strip_prefix(DELTA_MAGIC) else {
+        return Ok(serde_json::from_slice(bytes)?);
+    };
+    let Some((len, rest)) = rest.split_first_chunk::<4>() else {
+        bail!("Truncated incremental snapshot header");
+    };
+    let len = u32::from_le_bytes(*len) as usize;
+    if rest.len() < len {
+        bail!("Truncated incremental snapshot header");
+    }
+    let (header, data) = rest.split_at(len);
+    let header: DeltaHeader = bincode::deserialize(header)?;
+    let (data, _) = decode(data)?;
+    Ok(SnapshotDelta {
+        base: header.base,
+        sequence: header.sequence,
+        removed: header.removed,
+        data,
+    })
+}
+
+/// 将增量快照合并到基础快照：按 lock_key 去掉已删除的锁并以增量中的锁覆盖，命名空间策略和序列以增量为准，
+/// 结果只取决于两份快照的内容
+pub fn merge(base: SnapshotData, delta: SnapshotDelta) -> SnapshotData {
+    let mut locks: BTreeMap<String, LockInfo> = base
+        .locks
+        .into_iter()
+        .map(|lock_info| (lock_info.get_lock_key(), lock_info))
+        .collect();
+    for lock_key in &delta.removed {
+        locks.remove(lock_key);
+    }
+    for lock_info in delta.data.locks {
+        locks.insert(lock_info.get_lock_key(), lock_info);
+    }
+    SnapshotData {
+        locks: locks.into_values().collect(),
+        namespaces: delta.data.namespaces,
+        sequences: delta.data.sequences,
+        records: delta.data.records,
+    }
+}
+
+/// 快照文件的 SHA-256，增量快照据此对应到基础快照
+pub fn checksum(bytes: &[u8]) -> String {
+    sha256_hex(bytes)
+}
+
+/// 在快照末尾追加 SHA-256 校验和
+pub fn append_checksum(bytes: &mut Vec<u8>) {
+    let checksum = sha256_hex(bytes);
+    bytes.extend_from_slice(CHECKSUM_PREFIX);
+    bytes.extend_from_slice(checksum.as_bytes());
+    bytes.push(b'\n');
+}
+
+/// 校验并去掉快照末尾的校验和，没有校验和的旧文件原样返回
+pub fn verify_checksum(bytes: &[u8]) -> Result<&[u8]> {
+    if bytes.len() < CHECKSUM_LEN || !bytes.ends_with(b"\n") {
+        return Ok(bytes);
+    }
+    let (content, footer) = bytes.split_at(bytes.len() - CHECKSUM_LEN);
+    let Some(checksum) = footer.strip_prefix(CHECKSUM_PREFIX) else {
+        return Ok(bytes);
+    };
+    if &checksum[..64] != sha256_hex(content).as_bytes() {
+        return Err(ChecksumMismatch.into());
+    }
+    Ok(content)
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    digest::digest(&digest::SHA256, bytes)
+        .as_ref()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// 解码快照，自动识别格式，返回快照数据和文件实际使用的格式
+pub fn decode(bytes: &[u8]) -> Result<(SnapshotData, PersistFormat)> {
+    if bytes.starts_with(MAGIC) {
+        let version = match bytes.get(MAGIC.len()) {
+            Some(v) => *v,
+            None => bail!("Truncated snapshot header"),
+        };
+
+        let raw = zstd::decode_all(&bytes[MAGIC.len() + 1..])?;
+        let data = match version {
+            FORMAT_VERSION => bincode::deserialize(&raw)?,
+            1 => SnapshotData {
+                locks: upgrade(bincode::deserialize::<Vec<legacy::LockInfoV1>>(&raw)?)?,
+                namespaces: Vec::new(),
+                sequences: BTreeMap::new(),
+                records: BTreeMap::new(),
+            },
+            2 => {
+                let snapshot: legacy::SnapshotV2 = bincode::deserialize(&raw)?;
+                SnapshotData {
+                    locks: upgrade(snapshot.locks)?,
+                    namespaces: upgrade(snapshot.namespaces)?,
+                    sequences: BTreeMap::new(),
+                    records: BTreeMap::new(),
+                }
+            }
+            3 => {
+                let snapshot: legacy::SnapshotV3 = bincode::deserialize(&raw)?;
+                SnapshotData {
+                    locks: upgrade(snapshot.locks)?,
+                    namespaces: upgrade(snapshot.namespaces)?,
+                    sequences: BTreeMap::new(),
+                    records: BTreeMap::new(),
+                }
+            }
+            4 => {
+                let snapshot: legacy::SnapshotV4 = bincode::deserialize(&raw)?;
+                SnapshotData {
+                    locks: upgrade(snapshot.locks)?,
+                    namespaces: upgrade(snapshot.namespaces)?,
+                    sequences: BTreeMap::new(),
+                    records: BTreeMap::new(),
+                }
+            }
+            5 => {
+                let snapshot: legacy::SnapshotV5 = bincode::deserialize(&raw)?;
+                SnapshotData {
+                    locks: upgrade(snapshot.locks)?,
+                    namespaces: upgrade(snapshot.namespaces)?,
+                    sequences: BTreeMap::new(),
+                    records: BTreeMap::new(),
+                }
+            }
+            6 => {
+                let snapshot: legacy::SnapshotV6 = bincode::deserialize(&raw)?;
+                SnapshotData {
+                    locks: upgrade(snapshot.locks)?,
+                    namespaces: upgrade(snapshot.namespaces)?,
+                    sequences: BTreeMap::new(),
+                    records: BTreeMap::new(),
+                }
+            }
+            7 => {
+                let snapshot: legacy::SnapshotV7 = bincode::deserialize(&raw)?;
+                SnapshotData {
+                    locks: upgrade(snapshot.locks)?,
+                    namespaces: upgrade(snapshot.namespaces)?,
+                    sequences: BTreeMap::new(),
+                    records: BTreeMap::new(),
+                }
+            }
+            8 => {
+                let snapshot: legacy::SnapshotV8 = bincode::deserialize(&raw)?;
+                SnapshotData {
+                    locks: upgrade(snapshot.locks)?,
+                    namespaces: upgrade(snapshot.namespaces)?,
+                    sequences: BTreeMap::new(),
+                    records: BTreeMap::new(),
+                }
+            }
+            9 => {
+                let snapshot: legacy::SnapshotV9 = bincode::deserialize(&raw)?;
+                SnapshotData {
+                    locks: upgrade(snapshot.locks)?,
+                    namespaces: upgrade(snapshot.namespaces)?,
+                    sequences: snapshot.sequences,
+                    records: BTreeMap::new(),
+                }
+            }
+            10 => {
+                let snapshot: legacy::SnapshotV10 = bincode::deserialize(&raw)?;
+                SnapshotData {
+                    locks: upgrade(snapshot.locks)?,
+                    namespaces: upgrade(snapshot.namespaces)?,
+                    sequences: snapshot.sequences,
+                    records: BTreeMap::new(),
+                }
+            }
+            11 => {
+                let snapshot: legacy::SnapshotV11 = bincode::deserialize(&raw)?;
+                SnapshotData {
+                    locks: upgrade(snapshot.locks)?,
+                    namespaces: upgrade(snapshot.namespaces)?,
+                    sequences: snapshot.sequences,
+                    records: BTreeMap::new(),
+                }
+            }
+            12 => {
+                let snapshot: legacy::SnapshotV12 = bincode::deserialize(&raw)?;
+                SnapshotData {
+                    locks: upgrade(snapshot.locks)?,
+                    namespaces: upgrade(snapshot.namespaces)?,
+                    sequences: snapshot.sequences,
+                    records: BTreeMap::new(),
+                }
+            }
+            13 => {
+                let snapshot: legacy::SnapshotV13 = bincode::deserialize(&raw)?;
+                SnapshotData {
+                    locks: snapshot.locks,
+                    namespaces: snapshot.namespaces,
+                    sequences: snapshot.sequences,
+                    records: BTreeMap::new(),
+                }
+            }
+            _ => bail!(
+                "Unsupported snapshot version {} (expected {})",
+                version,
+                FORMAT_VERSION
+            ),
+        };
+        Ok((data, PersistFormat::Bincode))
+    } else {
+        let data = match serde_json::from_slice(bytes)? {
+            JsonSnapshot::Current(data) => data,
+            JsonSnapshot::LocksOnly(locks) => SnapshotData {
+                locks,
+                namespaces: Vec::new(),
+                sequences: BTreeMap::new(),
+                records: BTreeMap::new(),
+            },
+        };
+        Ok((data, PersistFormat::Json))
+    }
+}
+
+/// 通过 JSON 将旧结构转换为当前结构，新增字段取默认值
+fn upgrade<T: Serialize, U: DeserializeOwned>(items: Vec<T>) -> Result<Vec<U>> {
+    items
+        .into_iter()
+        .map(|item| Ok(serde_json::from_value(serde_json::to_value(item)?)?))
+        .collect()
+}
+
+/// 旧版本二进制快照中的结构，bincode 不是自描述格式，读取旧文件时必须使用当时的字段布局
+mod legacy {
+    use crate::models::{ClientContext, LockInfo, NamespacePolicy, PreemptionNotice};
+    use chrono::{DateTime, Utc};
+    use serde::{Deserialize, Serialize};
+    use std::collections::BTreeMap;
+
+    /// 版本 2 至 6 中的 NamespacePolicy
+    #[derive(Serialize, Deserialize)]
+    pub struct NamespacePolicyV1 {
+        pub name: String,
+        pub default_timeout: Option<u64>,
+        pub max_timeout: Option<u64>,
+        pub max_locks: Option<u64>,
+        pub allow_queue: bool,
+    }
+
+    /// 版本 7 至 11 中的 NamespacePolicy，增加了 hierarchical
+    #[derive(Serialize, Deserialize)]
+    pub struct NamespacePolicyV7 {
+        pub name: String,
+        pub default_timeout: Option<u64>,
+        pub max_timeout: Option<u64>,
+        pub max_locks: Option<u64>,
+        pub allow_queue: bool,
+        pub hierarchical: bool,
+    }
+
+    /// 版本 1、2 中的 LockInfo
+    #[derive(Serialize, Deserialize)]
+    pub struct LockInfoV1 {
+        pub lock_id: String,
+        pub namespace: String,
+        pub user_id: String,
+        pub user_name: String,
+        pub business_id: String,
+        pub timeout: u64,
+        pub locked_at: DateTime<Utc>,
+        pub last_heartbeat: DateTime<Utc>,
+    }
+
+    /// 版本 2 的快照
+    #[derive(Deserialize)]
+    pub struct SnapshotV2 {
+        pub locks: Vec<LockInfoV1>,
+        pub namespaces: Vec<NamespacePolicyV1>,
+    }
+
+    /// 版本 3 中的 LockInfo，增加了 metadata
+    #[derive(Serialize, Deserialize)]
+    pub struct LockInfoV3 {
+        pub lock_id: String,
+        pub namespace: String,
+        pub user_id: String,
+        pub user_name: String,
+        pub business_id: String,
+        pub timeout: u64,
+        pub locked_at: DateTime<Utc>,
+        pub last_heartbeat: DateTime<Utc>,
+        pub metadata: BTreeMap<String, String>,
+    }
+
+    /// 版本 3 的快照
+    #[derive(Deserialize)]
+    pub struct SnapshotV3 {
+        pub locks: Vec<LockInfoV3>,
+        pub namespaces: Vec<NamespacePolicyV1>,
+    }
+
+    /// 版本 4 中的 LockInfo，增加了 tags
+    #[derive(Serialize, Deserialize)]
+    pub struct LockInfoV4 {
+        pub lock_id: String,
+        pub namespace: String,
+        pub user_id: String,
+        pub user_name: String,
+        pub business_id: String,
+        pub timeout: u64,
+        pub locked_at: DateTime<Utc>,
+        pub last_heartbeat: DateTime<Utc>,
+        pub metadata: BTreeMap<String, String>,
+        pub tags: Vec<String>,
+    }
+
+    /// 版本 4 的快照
+    #[derive(Deserialize)]
+    pub struct SnapshotV4 {
+        pub locks: Vec<LockInfoV4>,
+        pub namespaces: Vec<NamespacePolicyV1>,
+    }
+
+    /// 版本 5 中的 LockInfo，增加了 max_hold_seconds
+    #[derive(Serialize, Deserialize)]
+    pub struct LockInfoV5 {
+        pub lock_id: String,
+        pub namespace: String,
+        pub user_id: String,
+        pub user_name: String,
+        pub business_id: String,
+        pub timeout: u64,
+        pub locked_at: DateTime<Utc>,
+        pub last_heartbeat: DateTime<Utc>,
+        pub metadata: BTreeMap<String, String>,
+        pub tags: Vec<String>,
+        pub max_hold_seconds: Option<u64>,
+    }
+
+    /// 版本 5 的快照
+    #[derive(Deserialize)]
+    pub struct SnapshotV5 {
+        pub locks: Vec<LockInfoV5>,
+        pub namespaces: Vec<NamespacePolicyV1>,
+    }
+
+    /// 版本 6、7 中的 LockInfo，增加了 preemption
+    #[derive(Serialize, Deserialize)]
+    pub struct LockInfoV6 {
+        pub lock_id: String,
+        pub namespace: String,
+        pub user_id: String,
+        pub user_name: String,
+        pub business_id: String,
+        pub timeout: u64,
+        pub locked_at: DateTime<Utc>,
+        pub last_heartbeat: DateTime<Utc>,
+        pub metadata: BTreeMap<String, String>,
+        pub tags: Vec<String>,
+        pub max_hold_seconds: Option<u64>,
+        pub preemption: Option<PreemptionNotice>,
+    }
+
+    /// 版本 6 的快照
+    #[derive(Deserialize)]
+    pub struct SnapshotV6 {
+        pub locks: Vec<LockInfoV6>,
+        pub namespaces: Vec<NamespacePolicyV1>,
+    }
+
+    /// 版本 7 的快照，NamespacePolicy 增加了 hierarchical
+    #[derive(Deserialize)]
+    pub struct SnapshotV7 {
+        pub locks: Vec<LockInfoV6>,
+        pub namespaces: Vec<NamespacePolicyV7>,
+    }
+
+    /// 版本 8、9 中的 LockInfo，增加了 version
+    #[derive(Serialize, Deserialize)]
+    pub struct LockInfoV8 {
+        pub lock_id: String,
+        pub namespace: String,
+        pub user_id: String,
+        pub user_name: String,
+        pub business_id: String,
+        pub timeout: u64,
+        pub locked_at: DateTime<Utc>,
+        pub last_heartbeat: DateTime<Utc>,
+        pub metadata: BTreeMap<String, String>,
+        pub tags: Vec<String>,
+        pub max_hold_seconds: Option<u64>,
+        pub preemption: Option<PreemptionNotice>,
+        pub version: u64,
+    }
+
+    /// 版本 8 的快照，之后增加了 sequences
+    #[derive(Deserialize)]
+    pub struct SnapshotV8 {
+        pub locks: Vec<LockInfoV8>,
+        pub namespaces: Vec<NamespacePolicyV7>,
+    }
+
+    /// 版本 9 的快照，之后 LockInfo 增加了 reason
+    #[derive(Deserialize)]
+    pub struct SnapshotV9 {
+        pub locks: Vec<LockInfoV8>,
+        pub namespaces: Vec<NamespacePolicyV7>,
+        pub sequences: BTreeMap<String, u64>,
+    }
+
+    /// 版本 10 中的 LockInfo，增加了 reason
+    #[derive(Serialize, Deserialize)]
+    pub struct LockInfoV10 {
+        pub lock_id: String,
+        pub namespace: String,
+        pub user_id: String,
+        pub user_name: String,
+        pub business_id: String,
+        pub timeout: u64,
+        pub locked_at: DateTime<Utc>,
+        pub last_heartbeat: DateTime<Utc>,
+        pub metadata: BTreeMap<String, String>,
+        pub tags: Vec<String>,
+        pub max_hold_seconds: Option<u64>,
+        pub preemption: Option<PreemptionNotice>,
+        pub version: u64,
+        pub reason: Option<String>,
+    }
+
+    /// 版本 10 的快照，之后 LockInfo 增加了 client
+    #[derive(Deserialize)]
+    pub struct SnapshotV10 {
+        pub locks: Vec<LockInfoV10>,
+        pub namespaces: Vec<NamespacePolicyV7>,
+        pub sequences: BTreeMap<String, u64>,
+    }
+
+    /// 版本 11、12 中的 LockInfo，增加了 client
+    #[derive(Serialize, Deserialize)]
+    pub struct LockInfoV11 {
+        pub lock_id: String,
+        pub namespace: String,
+        pub user_id: String,
+        pub user_name: String,
+        pub business_id: String,
+        pub timeout: u64,
+        pub locked_at: DateTime<Utc>,
+        pub last_heartbeat: DateTime<Utc>,
+        pub metadata: BTreeMap<String, String>,
+        pub tags: Vec<String>,
+        pub max_hold_seconds: Option<u64>,
+        pub preemption: Option<PreemptionNotice>,
+        pub version: u64,
+        pub reason: Option<String>,
+        pub client: Option<ClientContext>,
+    }
+
+    /// 版本 11 的快照，之后 NamespacePolicy 增加了 advisory
+    #[derive(Deserialize)]
+    pub struct SnapshotV11 {
+        pub locks: Vec<LockInfoV11>,
+        pub namespaces: Vec<NamespacePolicyV7>,
+        pub sequences: BTreeMap<String, u64>,
+    }
+
+    /// 版本 12 中的 NamespacePolicy，增加了 advisory
+    #[derive(Serialize, Deserialize)]
+    pub struct NamespacePolicyV12 {
+        pub name: String,
+        pub default_timeout: Option<u64>,
+        pub max_timeout: Option<u64>,
+        pub max_locks: Option<u64>,
+        pub allow_queue: bool,
+        pub hierarchical: bool,
+        pub advisory: bool,
+    }
+
+    /// 版本 12 的快照，之后 LockInfo 增加了 grace_seconds、due_at 等，NamespacePolicy 增加了事件路由、卡住阈值、冻结和最长持有时间
+    #[derive(Deserialize)]
+    pub struct SnapshotV12 {
+        pub locks: Vec<LockInfoV11>,
+        pub namespaces: Vec<NamespacePolicyV12>,
+        pub sequences: BTreeMap<String, u64>,
+    }
+
+    /// 版本 13 的快照，之后增加了 records
+    #[derive(Deserialize)]
+    pub struct SnapshotV13 {
+        pub locks: Vec<LockInfo>,
+        pub namespaces: Vec<NamespacePolicy>,
+        pub sequences: BTreeMap<String, u64>,
+    }
+}
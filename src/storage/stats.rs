@@ -0,0 +1,224 @@
+//! 锁统计：内存存储和 Raft 存储使用进程内计数器，直方图分桶规则各存储共用
+
+use crate::models::{ContendedLock, HeldLock, Histogram, HistogramBucket, LockInfo, LockStats};
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use parking_lot::Mutex;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// 持有时长直方图的桶上界（秒）
+pub const HOLD_TIME_BUCKETS: &[f64] = &[
+    1.0, 5.0, 15.0, 30.0, 60.0, 300.0, 900.0, 1800.0, 3600.0, 7200.0, 14400.0, 43200.0, 86400.0,
+];
+
+/// 每个锁申请失败次数直方图的桶上界
+pub const CONTENTION_BUCKETS: &[f64] = &[1.0, 2.0, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0];
+
+pub struct StatsCounters {
+    since: DateTime<Utc>,
+    acquired: AtomicU64,
+    conflicts: AtomicU64,
+    expired: AtomicU64,
+    contention: DashMap<String, u64>, // lock_key -> 申请失败次数
+    max_hold: DashMap<String, f64>,   // lock_key -> 单次最长持有时长（秒）
+    hold_time: Mutex<HistogramCounter>,
+}
+
+impl StatsCounters {
+    pub fn new() -> Self {
+        Self {
+            since: Utc::now(),
+            acquired: AtomicU64::new(0),
+            conflicts: AtomicU64::new(0),
+            expired: AtomicU64::new(0),
+            contention: DashMap::new(),
+            max_hold: DashMap::new(),
+            hold_time: Mutex::new(HistogramCounter::new(HOLD_TIME_BUCKETS)),
+        }
+    }
+
+    /// 记录一次申请结果
+    pub fn record_acquire(&self, lock_key: &str, acquired: bool) {
+        if acquired {
+            self.acquired.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.conflicts.fetch_add(1, Ordering::Relaxed);
+            *self.contention.entry(lock_key.to_string()).or_default() += 1;
+        }
+    }
+
+    /// 记录被释放的锁截至 now 的持有时长
+    pub fn record_released(&self, lock_info: &LockInfo, now: DateTime<Utc>) {
+        let held_secs = lock_info.held_secs_at(now);
+        self.hold_time.lock().observe(held_secs);
+        let mut max_hold = self.max_hold.entry(lock_info.get_lock_key()).or_default();
+        if held_secs > *max_hold {
+            *max_hold = held_secs;
+        }
+    }
+
+    /// 记录过期被清理的锁
+    pub fn record_expired(&self, lock_info: &LockInfo, now: DateTime<Utc>) {
+        self.expired.fetch_add(1, Ordering::Relaxed);
+        self.record_released(lock_info, now);
+    }
+
+    /// 结合截至 now 仍持有的锁生成统计信息
+    pub fn snapshot(&self, locks: &[LockInfo], top_n: usize, now: DateTime<Utc>) -> LockStats {
+        let mut top_contended: Vec<ContendedLock> = self
+            .contention
+            .iter()
+            .map(|entry| ContendedLock {
+                lock_key: entry.key().clone(),
+                conflicts: *entry.value(),
+            })
+            .collect();
+        top_contended.sort_by(|a, b| {
+            b.conflicts
+                .cmp(&a.conflicts)
+                .then_with(|| a.lock_key.cmp(&b.lock_key))
+        });
+
+        let mut contention = HistogramCounter::new(CONTENTION_BUCKETS);
+        for lock in &top_contended {
+            contention.observe(lock.conflicts as f64);
+        }
+        top_contended.truncate(top_n);
+
+        let longest_held = longest_held(
+            self.max_hold
+                .iter()
+                .map(|entry| (entry.key().clone(), *entry.value())),
+            locks,
+            top_n,
+            now,
+        );
+
+        let mut stats = active_lock_stats(locks, self.since, now);
+        stats.acquired = self.acquired.load(Ordering::Relaxed);
+        stats.conflicts = self.conflicts.load(Ordering::Relaxed);
+        stats.expired = self.expired.load(Ordering::Relaxed);
+        stats.top_contended = top_contended;
+        stats.longest_held = longest_held;
+        stats.hold_time = self.hold_time.lock().to_histogram();
+        stats.contention = contention.to_histogram();
+        stats
+    }
+}
+
+impl Default for StatsCounters {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 固定桶的直方图计数
+pub struct HistogramCounter {
+    bounds: &'static [f64],
+    counts: Vec<u64>, // 每个桶的非累计计数，最后一个为 +Inf
+    count: u64,
+    sum: f64,
+}
+
+impl HistogramCounter {
+    pub fn new(bounds: &'static [f64]) -> Self {
+        Self {
+            bounds,
+            counts: vec![0; bounds.len() + 1],
+            count: 0,
+            sum: 0.0,
+        }
+    }
+
+    pub fn observe(&mut self, value: f64) {
+        self.counts[bucket_index(self.bounds, value)] += 1;
+        self.count += 1;
+        self.sum += value;
+    }
+
+    pub fn to_histogram(&self) -> Histogram {
+        cumulative_histogram(self.bounds, &self.counts, self.count, self.sum)
+    }
+}
+
+/// 样本所在桶的下标，超过所有上界时为 `bounds.len()`（+Inf 桶）
+pub fn bucket_index(bounds: &[f64], value: f64) -> usize {
+    bounds
+        .iter()
+        .position(|bound| value <= *bound)
+        .unwrap_or(bounds.len())
+}
+
+/// 由每个桶的非累计计数生成累计直方图
+pub fn cumulative_histogram(bounds: &[f64], counts: &[u64], count: u64, sum: f64) -> Histogram {
+    let mut cumulative = 0;
+    let buckets = bounds
+        .iter()
+        .zip(counts)
+        .map(|(bound, bucket_count)| {
+            cumulative += bucket_count;
+            HistogramBucket {
+                le: *bound,
+                count: cumulative,
+            }
+        })
+        .collect();
+    Histogram {
+        buckets,
+        count,
+        sum,
+    }
+}
+
+/// 合并已释放锁的最长持有时长和截至 now 仍持有的锁，取最长的 top_n 个
+pub fn longest_held(
+    released: impl Iterator<Item = (String, f64)>,
+    locks: &[LockInfo],
+    top_n: usize,
+    now: DateTime<Utc>,
+) -> Vec<HeldLock> {
+    let mut max_hold: BTreeMap<String, f64> = released.collect();
+    for lock_info in locks.iter().filter(|lock_info| !lock_info.is_expired_at(now)) {
+        let held_secs = lock_info.held_secs_at(now);
+        let max_hold_secs = max_hold.entry(lock_info.get_lock_key()).or_default();
+        if held_secs > *max_hold_secs {
+            *max_hold_secs = held_secs;
+        }
+    }
+
+    let mut longest_held: Vec<HeldLock> = max_hold
+        .into_iter()
+        .map(|(lock_key, max_hold_secs)| HeldLock {
+            lock_key,
+            max_hold_secs,
+        })
+        .collect();
+    longest_held.sort_by(|a, b| {
+        b.max_hold_secs
+            .total_cmp(&a.max_hold_secs)
+            .then_with(|| a.lock_key.cmp(&b.lock_key))
+    });
+    longest_held.truncate(top_n);
+    longest_held
+}
+
+/// 按命名空间统计截至 now 未过期的锁，计数字段为空
+pub fn active_lock_stats(locks: &[LockInfo], since: DateTime<Utc>, now: DateTime<Utc>) -> LockStats {
+    let mut namespaces: BTreeMap<String, u64> = BTreeMap::new();
+    for lock_info in locks.iter().filter(|lock_info| !lock_info.is_expired_at(now)) {
+        *namespaces.entry(lock_info.namespace.clone()).or_default() += 1;
+    }
+    LockStats {
+        active_locks: namespaces.values().sum(),
+        namespaces,
+        acquired: 0,
+        conflicts: 0,
+        expired: 0,
+        top_contended: Vec::new(),
+        longest_held: Vec::new(),
+        hold_time: Histogram::default(),
+        contention: Histogram::default(),
+        since,
+    }
+}
@@ -0,0 +1,185 @@
+//! 存储操作超时
+//!
+//! 配置 `STORAGE_OP_TIMEOUT_MS` 后，每个存储操作超过该时长未完成时返回 [`StorageTimeout`]，接口返回错误码 7004（HTTP 504），
+//! 不再等待停滞的 Redis 或无法提交日志的 Raft 集群。Redis 存储的超时位于故障转移之内，超时计入断路器的连续失败次数。
+//!
+//! 超时只是不再等待结果，写操作可能已在存储中生效（如锁已写入），客户端应查询锁状态确认，未确认的锁到期后自动释放。
+
+use crate::models::{
+    LockCondition, LockInfo, LockStats, NamespacePolicy, PreemptionNotice, RateLimitDecision, TokenBucket,
+};
+use crate::storage::{Acquire, LockStorage, Takeover};
+use anyhow::Result;
+use async_trait::async_trait;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 存储操作超时
+#[derive(Debug, thiserror::Error)]
+#[error("storage operation {op} timed out after {timeout_ms}ms")]
+pub struct StorageTimeout {
+    pub op: &'static str,
+    pub timeout_ms: u64,
+}
+
+pub struct TimeoutStorage {
+    inner: Arc<dyn LockStorage>,
+    timeout: Duration,
+}
+
+impl TimeoutStorage {
+    pub fn new(inner: Arc<dyn LockStorage>, timeout: Duration) -> Self {
+        Self { inner, timeout }
+    }
+
+    /// timeout_ms 为 0 时不包装
+    pub fn wrap(inner: Arc<dyn LockStorage>, timeout_ms: u64) -> Arc<dyn LockStorage> {
+        if timeout_ms == 0 {
+            return inner;
+        }
+        Arc::new(Self::new(inner, Duration::from_millis(timeout_ms)))
+    }
+
+    async fn call<T>(&self, op: &'static str, future: impl Future<Output = Result<T>>) -> Result<T> {
+        match tokio::time::timeout(self.timeout, future).await {
+            Ok(result) => result,
+            Err(_) => Err(StorageTimeout {
+                op,
+                timeout_ms: self.timeout.as_millis() as u64,
+            }
+            .into()),
+        }
+    }
+}
+
+#[async_trait]
+impl LockStorage for TimeoutStorage {
+    async fn try_acquire(&self, lock_info: LockInfo) -> Result<Acquire> {
+        self.call("try_acquire", self.inner.try_acquire(lock_info)).await
+    }
+
+    async fn takeover(&self, lock_info: LockInfo, condition: &LockCondition) -> Result<Takeover> {
+        self.call("takeover", self.inner.takeover(lock_info, condition)).await
+    }
+
+    async fn restore(&self, lock_info: LockInfo) -> Result<bool> {
+        self.call("restore", self.inner.restore(lock_info)).await
+    }
+
+    async fn get_lock(&self, lock_key: &str) -> Result<Option<LockInfo>> {
+        self.call("get_lock", self.inner.get_lock(lock_key)).await
+    }
+
+    async fn update_heartbeat(&self, lock_id: &str) -> Result<Option<LockInfo>> {
+        self.call("update_heartbeat", self.inner.update_heartbeat(lock_id)).await
+    }
+
+    async fn update_heartbeat_version(&self, lock_id: &str, version: u64) -> Result<Option<LockInfo>> {
+        self.call(
+            "update_heartbeat_version",
+            self.inner.update_heartbeat_version(lock_id, version),
+        )
+        .await
+    }
+
+    async fn release(&self, lock_id: &str) -> Result<Option<LockInfo>> {
+        self.call("release", self.inner.release(lock_id)).await
+    }
+
+    async fn release_version(&self, lock_id: &str, version: u64) -> Result<Option<LockInfo>> {
+        self.call("release_version", self.inner.release_version(lock_id, version)).await
+    }
+
+    async fn release_owned(&self, lock_key: &str, user_id: &str) -> Result<Option<LockInfo>> {
+        self.call("release_owned", self.inner.release_owned(lock_key, user_id)).await
+    }
+
+    async fn cleanup_expired(&self) -> Result<Vec<LockInfo>> {
+        self.call("cleanup_expired", self.inner.cleanup_expired()).await
+    }
+
+    async fn list_locks(&self) -> Result<Vec<LockInfo>> {
+        self.call("list_locks", self.inner.list_locks()).await
+    }
+
+    async fn list_prefix(&self, key_prefix: &str) -> Result<Vec<LockInfo>> {
+        self.call("list_prefix", self.inner.list_prefix(key_prefix)).await
+    }
+
+    async fn get_lock_by_id(&self, lock_id: &str) -> Result<Option<LockInfo>> {
+        self.call("get_lock_by_id", self.inner.get_lock_by_id(lock_id)).await
+    }
+
+    async fn force_release(&self, lock_key: &str) -> Result<Option<LockInfo>> {
+        self.call("force_release", self.inner.force_release(lock_key)).await
+    }
+
+    async fn mark_preempted(&self, lock_id: &str, notice: PreemptionNotice) -> Result<Option<LockInfo>> {
+        self.call("mark_preempted", self.inner.mark_preempted(lock_id, notice)).await
+    }
+
+    async fn transfer(&self, lock_id: &str, new_lock: LockInfo) -> Result<Option<LockInfo>> {
+        self.call("transfer", self.inner.transfer(lock_id, new_lock)).await
+    }
+
+    async fn rewrite_lock(&self, lock_info: LockInfo) -> Result<bool> {
+        self.call("rewrite_lock", self.inner.rewrite_lock(lock_info)).await
+    }
+
+    async fn list_by_user(&self, user_id: &str) -> Result<Vec<LockInfo>> {
+        self.call("list_by_user", self.inner.list_by_user(user_id)).await
+    }
+
+    async fn list_by_business_id(&self, business_id: &str) -> Result<Vec<LockInfo>> {
+        self.call("list_by_business_id", self.inner.list_by_business_id(business_id)).await
+    }
+
+    async fn release_all_by_user(&self, user_id: &str) -> Result<Vec<LockInfo>> {
+        self.call("release_all_by_user", self.inner.release_all_by_user(user_id)).await
+    }
+
+    async fn count_locks(&self, namespace: &str) -> Result<u64> {
+        self.call("count_locks", self.inner.count_locks(namespace)).await
+    }
+
+    async fn stats(&self, top_n: usize) -> Result<LockStats> {
+        self.call("stats", self.inner.stats(top_n)).await
+    }
+
+    async fn list_namespaces(&self) -> Result<Vec<NamespacePolicy>> {
+        self.call("list_namespaces", self.inner.list_namespaces()).await
+    }
+
+    async fn get_namespace(&self, name: &str) -> Result<Option<NamespacePolicy>> {
+        self.call("get_namespace", self.inner.get_namespace(name)).await
+    }
+
+    async fn put_namespace(&self, policy: NamespacePolicy) -> Result<()> {
+        self.call("put_namespace", self.inner.put_namespace(policy)).await
+    }
+
+    async fn delete_namespace(&self, name: &str) -> Result<bool> {
+        self.call("delete_namespace", self.inner.delete_namespace(name)).await
+    }
+
+    async fn next_sequence(&self, name: &str, count: u64) -> Result<u64> {
+        self.call("next_sequence", self.inner.next_sequence(name, count)).await
+    }
+
+    async fn take_tokens(&self, key: &str, bucket: TokenBucket, cost: u64) -> Result<RateLimitDecision> {
+        self.call("take_tokens", self.inner.take_tokens(key, bucket, cost)).await
+    }
+
+    async fn get_record(&self, kind: &str, id: &str) -> Result<Option<String>> {
+        self.call("get_record", self.inner.get_record(kind, id)).await
+    }
+
+    async fn list_records(&self, kind: &str) -> Result<Vec<(String, String)>> {
+        self.call("list_records", self.inner.list_records(kind)).await
+    }
+
+    async fn swap_record(&self, kind: &str, id: &str, expected: Option<&str>, value: Option<&str>) -> Result<bool> {
+        self.call("swap_record", self.inner.swap_record(kind, id, expected, value)).await
+    }
+}
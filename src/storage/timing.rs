@@ -0,0 +1,149 @@
+//! 存储调用计时
+//!
+//! 请求中的每次存储调用耗时计入该请求的 `storage` 阶段，见 [`crate::latency`]。不在请求中的调用（如后台清理）不计时。
+
+use crate::latency::{self, Stage};
+use crate::models::{
+    LockCondition, LockInfo, LockStats, NamespacePolicy, PreemptionNotice, RateLimitDecision, TokenBucket,
+};
+use crate::storage::{Acquire, LockStorage, Takeover};
+use anyhow::Result;
+use async_trait::async_trait;
+use std::sync::Arc;
+
+pub struct TimedStorage {
+    inner: Arc<dyn LockStorage>,
+}
+
+impl TimedStorage {
+    pub fn new(inner: Arc<dyn LockStorage>) -> Self {
+        Self { inner }
+    }
+}
+
+#[async_trait]
+impl LockStorage for TimedStorage {
+    async fn try_acquire(&self, lock_info: LockInfo) -> Result<Acquire> {
+        latency::time(Stage::Storage, self.inner.try_acquire(lock_info)).await
+    }
+
+    async fn takeover(&self, lock_info: LockInfo, condition: &LockCondition) -> Result<Takeover> {
+        latency::time(Stage::Storage, self.inner.takeover(lock_info, condition)).await
+    }
+
+    async fn restore(&self, lock_info: LockInfo) -> Result<bool> {
+        latency::time(Stage::Storage, self.inner.restore(lock_info)).await
+    }
+
+    async fn get_lock(&self, lock_key: &str) -> Result<Option<LockInfo>> {
+        latency::time(Stage::Storage, self.inner.get_lock(lock_key)).await
+    }
+
+    async fn update_heartbeat(&self, lock_id: &str) -> Result<Option<LockInfo>> {
+        latency::time(Stage::Storage, self.inner.update_heartbeat(lock_id)).await
+    }
+
+    async fn update_heartbeat_version(&self, lock_id: &str, version: u64) -> Result<Option<LockInfo>> {
+        latency::time(Stage::Storage, self.inner.update_heartbeat_version(lock_id, version)).await
+    }
+
+    async fn release(&self, lock_id: &str) -> Result<Option<LockInfo>> {
+        latency::time(Stage::Storage, self.inner.release(lock_id)).await
+    }
+
+    async fn release_version(&self, lock_id: &str, version: u64) -> Result<Option<LockInfo>> {
+        latency::time(Stage::Storage, self.inner.release_version(lock_id, version)).await
+    }
+
+    async fn release_owned(&self, lock_key: &str, user_id: &str) -> Result<Option<LockInfo>> {
+        latency::time(Stage::Storage, self.inner.release_owned(lock_key, user_id)).await
+    }
+
+    async fn cleanup_expired(&self) -> Result<Vec<LockInfo>> {
+        latency::time(Stage::Storage, self.inner.cleanup_expired()).await
+    }
+
+    async fn list_locks(&self) -> Result<Vec<LockInfo>> {
+        latency::time(Stage::Storage, self.inner.list_locks()).await
+    }
+
+    async fn list_prefix(&self, key_prefix: &str) -> Result<Vec<LockInfo>> {
+        latency::time(Stage::Storage, self.inner.list_prefix(key_prefix)).await
+    }
+
+    async fn get_lock_by_id(&self, lock_id: &str) -> Result<Option<LockInfo>> {
+        latency::time(Stage::Storage, self.inner.get_lock_by_id(lock_id)).await
+    }
+
+    async fn force_release(&self, lock_key: &str) -> Result<Option<LockInfo>> {
+        latency::time(Stage::Storage, self.inner.force_release(lock_key)).await
+    }
+
+    async fn mark_preempted(&self, lock_id: &str, notice: PreemptionNotice) -> Result<Option<LockInfo>> {
+        latency::time(Stage::Storage, self.inner.mark_preempted(lock_id, notice)).await
+    }
+
+    async fn transfer(&self, lock_id: &str, new_lock: LockInfo) -> Result<Option<LockInfo>> {
+        latency::time(Stage::Storage, self.inner.transfer(lock_id, new_lock)).await
+    }
+
+    async fn rewrite_lock(&self, lock_info: LockInfo) -> Result<bool> {
+        latency::time(Stage::Storage, self.inner.rewrite_lock(lock_info)).await
+    }
+
+    async fn list_by_user(&self, user_id: &str) -> Result<Vec<LockInfo>> {
+        latency::time(Stage::Storage, self.inner.list_by_user(user_id)).await
+    }
+
+    async fn list_by_business_id(&self, business_id: &str) -> Result<Vec<LockInfo>> {
+        latency::time(Stage::Storage, self.inner.list_by_business_id(business_id)).await
+    }
+
+    async fn release_all_by_user(&self, user_id: &str) -> Result<Vec<LockInfo>> {
+        latency::time(Stage::Storage, self.inner.release_all_by_user(user_id)).await
+    }
+
+    async fn count_locks(&self, namespace: &str) -> Result<u64> {
+        latency::time(Stage::Storage, self.inner.count_locks(namespace)).await
+    }
+
+    async fn stats(&self, top_n: usize) -> Result<LockStats> {
+        latency::time(Stage::Storage, self.inner.stats(top_n)).await
+    }
+
+    async fn list_namespaces(&self) -> Result<Vec<NamespacePolicy>> {
+        latency::time(Stage::Storage, self.inner.list_namespaces()).await
+    }
+
+    async fn get_namespace(&self, name: &str) -> Result<Option<NamespacePolicy>> {
+        latency::time(Stage::Storage, self.inner.get_namespace(name)).await
+    }
+
+    async fn put_namespace(&self, policy: NamespacePolicy) -> Result<()> {
+        latency::time(Stage::Storage, self.inner.put_namespace(policy)).await
+    }
+
+    async fn delete_namespace(&self, name: &str) -> Result<bool> {
+        latency::time(Stage::Storage, self.inner.delete_namespace(name)).await
+    }
+
+    async fn next_sequence(&self, name: &str, count: u64) -> Result<u64> {
+        latency::time(Stage::Storage, self.inner.next_sequence(name, count)).await
+    }
+
+    async fn take_tokens(&self, key: &str, bucket: TokenBucket, cost: u64) -> Result<RateLimitDecision> {
+        latency::time(Stage::Storage, self.inner.take_tokens(key, bucket, cost)).await
+    }
+
+    async fn get_record(&self, kind: &str, id: &str) -> Result<Option<String>> {
+        latency::time(Stage::Storage, self.inner.get_record(kind, id)).await
+    }
+
+    async fn list_records(&self, kind: &str) -> Result<Vec<(String, String)>> {
+        latency::time(Stage::Storage, self.inner.list_records(kind)).await
+    }
+
+    async fn swap_record(&self, kind: &str, id: &str, expected: Option<&str>, value: Option<&str>) -> Result<bool> {
+        latency::time(Stage::Storage, self.inner.swap_record(kind, id, expected, value)).await
+    }
+}
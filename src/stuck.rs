@@ -0,0 +1,153 @@
+//! 卡住的锁检测
+//!
+//! 后台任务每 `LOCK_STUCK_CHECK_INTERVAL` 秒扫描一次，持有时间超过阈值的锁视为卡住（持有人可能还在心跳但不再释放），
+//! 阈值为命名空间策略的 `stuck_after`，未配置时使用 `LOCK_STUCK_THRESHOLD`，0 表示不检测。永久锁同样检测，在场记录不检测。
+//! 卡住的锁可通过管理接口查看并导出为 Prometheus 指标，配置 `LOCK_STUCK_WEBHOOK_URL` 时第一次检测到时回调该地址。
+//! 检测结果保存在本实例进程内，多实例部署时每个实例独立检测，回调也由每个实例各发送一次；
+//! 配置 `LEADER_ELECTION=true` 时只由当选的实例检测，见 [`crate::leader`]。
+
+use crate::http;
+use crate::leader::LEADER_NAMESPACE;
+use crate::models::{LockInfo, NamespacePolicy, StuckLock};
+use crate::presence;
+use crate::storage::LockStorage;
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use log::{error, warn};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// 回调请求超时
+const CALLBACK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+pub struct StuckLockDetector {
+    default_threshold: u64,
+    webhook_url: Option<String>,
+    client: reqwest::Client,
+    stuck: DashMap<String, StuckLock>, // lock_id -> 最近一次扫描时卡住的锁
+    detected_total: AtomicU64,
+}
+
+impl StuckLockDetector {
+    pub fn new(default_threshold: u64, webhook_url: Option<String>) -> Self {
+        Self {
+            default_threshold,
+            webhook_url,
+            client: http::client(CALLBACK_TIMEOUT),
+            stuck: DashMap::new(),
+            detected_total: AtomicU64::new(0),
+        }
+    }
+
+    /// 最近一次扫描时卡住的锁，按持有时长降序
+    pub fn list(&self) -> Vec<StuckLock> {
+        let mut stuck: Vec<StuckLock> = self.stuck.iter().map(|entry| entry.value().clone()).collect();
+        stuck.sort_by_key(|entry| std::cmp::Reverse(entry.held_seconds));
+        stuck
+    }
+
+    /// 清空检测结果，本实例不再运行检测时调用
+    pub fn clear(&self) {
+        self.stuck.clear();
+    }
+
+    /// 检测到卡住的锁的累计次数，同一把锁持续卡住只计一次
+    pub fn detected_total(&self) -> u64 {
+        self.detected_total.load(Ordering::Relaxed)
+    }
+
+    fn threshold(&self, policies: &HashMap<String, NamespacePolicy>, lock_info: &LockInfo) -> u64 {
+        policies
+            .get(&lock_info.namespace)
+            .and_then(|policy| policy.stuck_after)
+            .unwrap_or(self.default_threshold)
+    }
+
+    /// 扫描全部锁，替换检测结果，新卡住的锁记录日志并回调
+    pub async fn scan(&self, storage: &dyn LockStorage, now: DateTime<Utc>) {
+        let policies: HashMap<String, NamespacePolicy> = match storage.list_namespaces().await {
+            Ok(policies) => policies
+                .into_iter()
+                .map(|policy| (policy.name.clone(), policy))
+                .collect(),
+            Err(e) => {
+                error!("[STUCK] Failed to list namespace policies: {}", e);
+                return;
+            }
+        };
+        if self.default_threshold == 0 && !policies.values().any(|policy| policy.stuck_after.is_some_and(|secs| secs > 0)) {
+            self.stuck.clear();
+            return;
+        }
+        let locks = match storage.list_locks().await {
+            Ok(locks) => locks,
+            Err(e) => {
+                error!("[STUCK] Failed to list locks: {}", e);
+                return;
+            }
+        };
+
+        let mut stuck = HashMap::new();
+        for lock_info in locks {
+            if presence::is_presence_namespace(&lock_info.namespace)
+                || lock_info.namespace == LEADER_NAMESPACE
+                || lock_info.is_expired_at(now)
+            {
+                continue;
+            }
+            let threshold = self.threshold(&policies, &lock_info);
+            let held_seconds = lock_info.held_secs_at(now) as u64;
+            if threshold == 0 || held_seconds < threshold {
+                continue;
+            }
+            let detected_at = self
+                .stuck
+                .get(&lock_info.lock_id)
+                .filter(|entry| entry.lock.locked_at == lock_info.locked_at)
+                .map(|entry| entry.detected_at);
+            let newly_stuck = detected_at.is_none();
+            let entry = StuckLock {
+                lock: lock_info,
+                held_seconds,
+                threshold_seconds: threshold,
+                detected_at: detected_at.unwrap_or(now),
+            };
+            if newly_stuck {
+                self.detected_total.fetch_add(1, Ordering::Relaxed);
+                warn!(
+                    "[STUCK] Lock held longer than {}s - lock_id: {}, namespace: {}, business_id: {}, user_id: {}, held: {}s",
+                    threshold, entry.lock.lock_id, entry.lock.namespace, entry.lock.business_id, entry.lock.user_id, held_seconds
+                );
+                self.notify(&entry);
+            }
+            stuck.insert(entry.lock.lock_id.clone(), entry);
+        }
+        self.stuck.retain(|lock_id, _| stuck.contains_key(lock_id));
+        for (lock_id, entry) in stuck {
+            self.stuck.insert(lock_id, entry);
+        }
+    }
+
+    /// 配置了回调地址时异步回调，失败只记录日志
+    fn notify(&self, entry: &StuckLock) {
+        let Some(webhook_url) = self.webhook_url.clone() else {
+            return;
+        };
+        let client = self.client.clone();
+        let entry = entry.clone();
+        tokio::spawn(async move {
+            let result = client
+                .post(&webhook_url)
+                .json(&entry)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status());
+            if let Err(e) = result {
+                error!(
+                    "[STUCK] Failed to deliver stuck lock {} to {}: {}",
+                    entry.lock.lock_id, webhook_url, e
+                );
+            }
+        });
+    }
+}
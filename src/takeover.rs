@@ -0,0 +1,287 @@
+//! 锁接管请求
+//!
+//! 申请被拒绝的用户可以请求持有人让出锁：服务端发布 `takeover_requested` 事件（配置 `LOCK_TAKEOVER_WEBHOOK_URL` 时同时回调该地址），
+//! 持有人在 `LOCK_TAKEOVER_TIMEOUT` 秒内同意则将锁转移给申请人并发布 `takeover_approved` 和 `acquired` 事件，拒绝时发布 `takeover_declined` 事件，
+//! 超时未答复或持有人已释放锁时请求过期并发布 `takeover_expired` 事件。
+//! 接管请求保存在本实例进程内，多实例部署时请求和答复需路由到同一实例，实例重启后未答复的请求丢失。
+
+use crate::events::{EventBus, LockEvent, LockEventType};
+use crate::http;
+use crate::models::{LockInfo, TakeoverRequest, TakeoverState};
+use crate::storage::LockStorage;
+use chrono::{DateTime, Duration, Utc};
+use dashmap::DashMap;
+
+/// 回调请求超时
+const CALLBACK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// 已答复或已过期的请求保留的时间，供申请人查询结果
+const RETENTION_SECS: i64 = 300;
+
+struct Entry {
+    request: TakeoverRequest,
+    holder: LockInfo,    // 请求时持有人的锁
+    lock_info: LockInfo, // 批准后申请人持有的锁
+    responding: bool,    // 持有人正在答复
+    resolved_at: Option<DateTime<Utc>>,
+}
+
+/// 答复接管请求失败的原因
+#[derive(Debug)]
+pub enum TakeoverError {
+    /// 请求不存在、已答复、已过期或不是发给该锁的
+    NotFound,
+    /// 持有人的锁已释放或已过期，请求随之过期
+    LockGone,
+    Storage(anyhow::Error),
+}
+
+pub struct TakeoverBroker {
+    requests: DashMap<String, Entry>, // request_id -> 接管请求
+    webhook_url: Option<String>,
+    client: reqwest::Client,
+}
+
+impl TakeoverBroker {
+    pub fn new(webhook_url: Option<String>) -> Self {
+        Self {
+            requests: DashMap::new(),
+            webhook_url,
+            client: http::client(CALLBACK_TIMEOUT),
+        }
+    }
+
+    /// 登记接管请求，同一申请人对同一持有人已有待答复的请求时返回该请求，第二个返回值表示是否新登记
+    pub fn request(
+        &self,
+        request_id: String,
+        holder: &LockInfo,
+        lock_info: LockInfo,
+        message: Option<String>,
+        wait: Duration,
+        now: DateTime<Utc>,
+    ) -> (TakeoverRequest, bool) {
+        if let Some(existing) = self.requests.iter().find(|entry| {
+            entry.request.state == TakeoverState::Pending
+                && entry.holder.lock_id == holder.lock_id
+                && entry.request.user_id == lock_info.user_id
+        }) {
+            return (existing.request.clone(), false);
+        }
+
+        let request = TakeoverRequest {
+            request_id: request_id.clone(),
+            namespace: holder.namespace.clone(),
+            business_id: holder.business_id.clone(),
+            holder_user_id: holder.user_id.clone(),
+            holder_user_name: holder.user_name.clone(),
+            user_id: lock_info.user_id.clone(),
+            user_name: lock_info.user_name.clone(),
+            message,
+            state: TakeoverState::Pending,
+            created_at: now,
+            expires_at: now + wait,
+            lock_id: None,
+        };
+        self.requests.insert(
+            request_id,
+            Entry {
+                request: request.clone(),
+                holder: holder.clone(),
+                lock_info,
+                responding: false,
+                resolved_at: None,
+            },
+        );
+        (request, true)
+    }
+
+    /// user_id 自己发出的接管请求
+    pub fn get(&self, request_id: &str, user_id: &str) -> Option<TakeoverRequest> {
+        self.requests
+            .get(request_id)
+            .filter(|entry| entry.request.user_id == user_id)
+            .map(|entry| entry.request.clone())
+    }
+
+    /// 发给 lock_id 持有人、尚未答复的请求，按请求时间排列
+    pub fn pending(&self, lock_id: &str) -> Vec<TakeoverRequest> {
+        let mut requests: Vec<TakeoverRequest> = self
+            .requests
+            .iter()
+            .filter(|entry| entry.holder.lock_id == lock_id && entry.request.state == TakeoverState::Pending)
+            .map(|entry| entry.request.clone())
+            .collect();
+        requests.sort_by_key(|request| request.created_at);
+        requests
+    }
+
+    /// 持有人（lock_id）答复接管请求，同意时将锁转移给申请人
+    pub async fn respond(
+        &self,
+        storage: &dyn LockStorage,
+        events: &EventBus,
+        request_id: &str,
+        lock_id: &str,
+        approve: bool,
+        now: DateTime<Utc>,
+    ) -> Result<TakeoverRequest, TakeoverError> {
+        let (holder, mut lock_info) = {
+            let Some(mut entry) = self.requests.get_mut(request_id) else {
+                return Err(TakeoverError::NotFound);
+            };
+            if entry.request.state != TakeoverState::Pending || entry.responding || entry.holder.lock_id != lock_id {
+                return Err(TakeoverError::NotFound);
+            }
+            if !approve {
+                let request = self.resolve(&mut entry, TakeoverState::Declined, now);
+                let event = LockEvent::new(LockEventType::TakeoverDeclined, entry.holder.clone())
+                    .with_takeover(request.clone());
+                drop(entry);
+                log::info!(
+                    "[TAKEOVER] Takeover declined - request_id: {}, lock_key: {}, requested_by: {}",
+                    request_id,
+                    request.get_lock_key(),
+                    request.user_id
+                );
+                self.publish(events, event);
+                return Ok(request);
+            }
+            entry.responding = true;
+            (entry.holder.clone(), entry.lock_info.clone())
+        };
+
+        lock_info.locked_at = now;
+        lock_info.last_heartbeat = now;
+        let active = match storage.get_lock_by_id(lock_id).await {
+            Ok(current) => current.is_some_and(|current| !current.is_expired_at(now)),
+            Err(e) => return Err(self.abort(request_id, e)),
+        };
+        let previous = if active {
+            match storage.transfer(lock_id, lock_info.clone()).await {
+                Ok(previous) => previous,
+                Err(e) => return Err(self.abort(request_id, e)),
+            }
+        } else {
+            None
+        };
+
+        let Some(mut entry) = self.requests.get_mut(request_id) else {
+            return Err(TakeoverError::NotFound);
+        };
+        let Some(previous) = previous else {
+            let request = self.resolve(&mut entry, TakeoverState::Expired, now);
+            drop(entry);
+            self.publish(
+                events,
+                LockEvent::new(LockEventType::TakeoverExpired, holder).with_takeover(request),
+            );
+            return Err(TakeoverError::LockGone);
+        };
+        lock_info.version = previous.version + 1;
+        entry.request.lock_id = Some(lock_info.lock_id.clone());
+        let request = self.resolve(&mut entry, TakeoverState::Approved, now);
+        drop(entry);
+
+        log::info!(
+            "[TAKEOVER] Takeover approved, lock transferred - request_id: {}, lock_key: {}, from: {}, to: {}",
+            request_id,
+            request.get_lock_key(),
+            previous.user_id,
+            lock_info.user_id
+        );
+        self.publish(
+            events,
+            LockEvent::new(LockEventType::TakeoverApproved, previous).with_takeover(request.clone()),
+        );
+        events.publish(LockEvent::new(LockEventType::Acquired, lock_info));
+        Ok(request)
+    }
+
+    /// 发布新登记的接管请求
+    pub fn notify_requested(&self, events: &EventBus, holder: LockInfo, request: TakeoverRequest) {
+        self.publish(
+            events,
+            LockEvent::new(LockEventType::TakeoverRequested, holder).with_takeover(request),
+        );
+    }
+
+    /// 使超时未答复或持有人已释放锁的请求过期，清理超过保留时间的已结束请求
+    pub async fn expire_due(&self, storage: &dyn LockStorage, events: &EventBus, now: DateTime<Utc>) {
+        let pending: Vec<(String, String, DateTime<Utc>)> = self
+            .requests
+            .iter()
+            .filter(|entry| entry.request.state == TakeoverState::Pending && !entry.responding)
+            .map(|entry| (entry.key().clone(), entry.holder.lock_id.clone(), entry.request.expires_at))
+            .collect();
+
+        for (request_id, holder_lock_id, expires_at) in pending {
+            if now < expires_at {
+                match storage.get_lock_by_id(&holder_lock_id).await {
+                    Ok(Some(lock_info)) if !lock_info.is_expired_at(now) => continue,
+                    Ok(_) => {}
+                    Err(e) => {
+                        log::error!("[TAKEOVER] Failed to load lock {}: {}", holder_lock_id, e);
+                        continue;
+                    }
+                }
+            }
+            let Some(mut entry) = self.requests.get_mut(&request_id) else {
+                continue;
+            };
+            if entry.request.state != TakeoverState::Pending || entry.responding {
+                continue;
+            }
+            let request = self.resolve(&mut entry, TakeoverState::Expired, now);
+            let event = LockEvent::new(LockEventType::TakeoverExpired, entry.holder.clone()).with_takeover(request);
+            drop(entry);
+            log::info!("[TAKEOVER] Takeover request expired - request_id: {}", request_id);
+            self.publish(events, event);
+        }
+
+        let retention = Duration::seconds(RETENTION_SECS);
+        self.requests
+            .retain(|_, entry| entry.resolved_at.is_none_or(|resolved_at| now - resolved_at < retention));
+    }
+
+    fn resolve(&self, entry: &mut Entry, state: TakeoverState, now: DateTime<Utc>) -> TakeoverRequest {
+        entry.request.state = state;
+        entry.responding = false;
+        entry.resolved_at = Some(now);
+        entry.request.clone()
+    }
+
+    /// 答复时存储出错，请求恢复为待答复
+    fn abort(&self, request_id: &str, e: anyhow::Error) -> TakeoverError {
+        if let Some(mut entry) = self.requests.get_mut(request_id) {
+            entry.responding = false;
+        }
+        TakeoverError::Storage(e)
+    }
+
+    /// 发布事件，配置了回调地址时异步回调，失败只记录日志
+    fn publish(&self, events: &EventBus, event: LockEvent) {
+        if let Some(webhook_url) = self.webhook_url.clone() {
+            let client = self.client.clone();
+            let event = event.clone();
+            tokio::spawn(async move {
+                let result = client
+                    .post(&webhook_url)
+                    .json(&event)
+                    .send()
+                    .await
+                    .and_then(|response| response.error_for_status());
+                if let Err(e) = result {
+                    log::error!(
+                        "[TAKEOVER] Failed to deliver {:?} event for lock {} to {}: {}",
+                        event.event,
+                        event.lock.lock_id,
+                        webhook_url,
+                        e
+                    );
+                }
+            });
+        }
+        events.publish(event);
+    }
+}
@@ -0,0 +1,230 @@
+//! 多租户
+//!
+//! 配置 `TENANTS`（租户的 API Key）或 `TENANT_JWT_SECRET`（HS256 签名的 JWT，租户取自 `tenant_id` 声明）后，
+//! 锁接口要求请求携带 `Authorization: Bearer <API Key 或 JWT>`，认证失败返回 401（错误码 4001）。
+//! 租户位于命名空间之上：请求中的命名空间在存储中保存为 `<tenant_id>/<namespace>`，序列名和限流键同样加上租户前缀，
+//! 按 lock_id、user_id 或请求 ID 操作时只能访问本租户的锁，因此各租户的锁、命名空间策略、统计和锁历史完全隔离。
+//! 每个租户同时持有的锁数量受 `TENANTS` 中配置的配额或 `TENANT_MAX_LOCKS` 限制。管理接口不区分租户。
+
+use crate::clock;
+use crate::config::Config;
+use crate::lockops::OpError;
+use crate::models::{accepts_problem_json, ApiResponse, LockError, LockInfo, LockStats};
+use crate::presence;
+use crate::storage::stats::{active_lock_stats, longest_held};
+use crate::storage::LockStorage;
+use crate::v2;
+use crate::validation::{is_valid_namespace, MAX_STATS_TOP};
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::{header, StatusCode};
+use actix_web::middleware::Next;
+use actix_web::{web, Error, HttpMessage};
+use anyhow::Result;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use ring::hmac;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// 租户与命名空间之间的分隔符，命名空间中不允许出现
+pub const SEPARATOR: char = '/';
+
+/// 请求所属的租户
+#[derive(Debug, Clone)]
+pub struct Tenant {
+    pub id: String,
+}
+
+impl Tenant {
+    /// 本租户的命名空间、序列名或限流键在存储中的名称
+    pub fn scope(&self, name: &str) -> String {
+        format!("{}{}{}", self.id, SEPARATOR, name)
+    }
+
+    /// 存储中的命名空间是否属于本租户
+    pub fn owns(&self, namespace: &str) -> bool {
+        tenant_of(namespace) == Some(self.id.as_str())
+    }
+
+    pub fn owns_lock(&self, lock_info: &LockInfo) -> bool {
+        self.owns(&lock_info.namespace)
+    }
+
+    /// 本租户的锁的锁键前缀
+    pub fn key_prefix(&self) -> String {
+        format!("{}{}", self.id, SEPARATOR)
+    }
+}
+
+/// 存储中的命名空间所属的租户，未区分租户的命名空间返回 None
+pub fn tenant_of(namespace: &str) -> Option<&str> {
+    namespace.split_once(SEPARATOR).map(|(tenant_id, _)| tenant_id)
+}
+
+/// lock_id 对应的锁是否可由 tenant 操作：未启用多租户、锁不存在或属于本租户时为 true
+pub async fn may_access_lock(storage: &dyn LockStorage, tenant: Option<&Tenant>, lock_id: &str) -> Result<bool> {
+    let Some(tenant) = tenant else {
+        return Ok(true);
+    };
+    Ok(storage
+        .get_lock_by_id(lock_id)
+        .await?
+        .is_none_or(|lock_info| tenant.owns_lock(&lock_info)))
+}
+
+/// 租户的锁统计：当前持有的锁按命名空间计数（不含在场记录），竞争和持有时长排行取自全服务排行的前 100 个锁中本租户的锁。
+/// 申请、冲突和过期次数及直方图为全服务累计，不按租户区分，租户的统计中为空
+pub async fn stats(storage: &dyn LockStorage, tenant: &Tenant, top_n: usize, now: DateTime<Utc>) -> Result<LockStats> {
+    let key_prefix = tenant.key_prefix();
+    let global = storage.stats(MAX_STATS_TOP).await?;
+    let locks: Vec<LockInfo> = storage
+        .list_prefix(&key_prefix)
+        .await?
+        .into_iter()
+        .filter(|lock_info| !presence::is_presence_namespace(&lock_info.namespace))
+        .collect();
+
+    let mut stats = active_lock_stats(&locks, global.since, now);
+    stats.top_contended = global
+        .top_contended
+        .into_iter()
+        .filter(|lock| lock.lock_key.starts_with(&key_prefix))
+        .take(top_n)
+        .collect();
+    stats.longest_held = longest_held(
+        global
+            .longest_held
+            .into_iter()
+            .filter(|lock| lock.lock_key.starts_with(&key_prefix))
+            .map(|lock| (lock.lock_key, lock.max_hold_secs)),
+        &locks,
+        top_n,
+        now,
+    );
+    Ok(stats)
+}
+
+#[derive(Deserialize)]
+struct JwtHeader {
+    alg: String,
+}
+
+#[derive(Deserialize)]
+struct JwtClaims {
+    tenant_id: String,
+    exp: Option<i64>,
+}
+
+/// 租户认证和配额
+pub struct TenantRegistry {
+    api_keys: HashMap<String, String>, // API Key -> tenant_id
+    max_locks: HashMap<String, u64>,   // tenant_id -> 配额
+    default_max_locks: u64,
+    jwt_key: Option<hmac::Key>,
+}
+
+impl TenantRegistry {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            api_keys: config
+                .tenants
+                .iter()
+                .map(|tenant| (tenant.api_key.clone(), tenant.tenant_id.clone()))
+                .collect(),
+            max_locks: config
+                .tenants
+                .iter()
+                .filter_map(|tenant| Some((tenant.tenant_id.clone(), tenant.max_locks?)))
+                .collect(),
+            default_max_locks: config.tenant_max_locks,
+            jwt_key: config
+                .tenant_jwt_secret
+                .as_ref()
+                .map(|secret| hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes())),
+        }
+    }
+
+    /// 是否启用多租户
+    pub fn is_enabled(&self) -> bool {
+        !self.api_keys.is_empty() || self.jwt_key.is_some()
+    }
+
+    /// 按 API Key 或 JWT 认证，返回请求所属的租户
+    pub fn authenticate(&self, token: &str, now: DateTime<Utc>) -> Option<Tenant> {
+        if let Some(tenant_id) = self.api_keys.get(token) {
+            return Some(Tenant { id: tenant_id.clone() });
+        }
+        let tenant_id = self.verify_jwt(token, now)?;
+        is_valid_namespace(&tenant_id).then_some(Tenant { id: tenant_id })
+    }
+
+    /// tenant_id 同时持有的锁数量上限，0 表示不限制
+    pub fn max_locks(&self, tenant_id: &str) -> u64 {
+        self.max_locks.get(tenant_id).copied().unwrap_or(self.default_max_locks)
+    }
+
+    /// 校验 HS256 签名和过期时间，返回 `tenant_id` 声明
+    fn verify_jwt(&self, token: &str, now: DateTime<Utc>) -> Option<String> {
+        let key = self.jwt_key.as_ref()?;
+        let mut parts = token.split('.');
+        let (header, claims, signature) = (parts.next()?, parts.next()?, parts.next()?);
+        if parts.next().is_some() {
+            return None;
+        }
+        let signed = &token[..header.len() + 1 + claims.len()];
+        let header: JwtHeader = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(header).ok()?).ok()?;
+        if header.alg != "HS256" {
+            return None;
+        }
+        hmac::verify(key, signed.as_bytes(), &URL_SAFE_NO_PAD.decode(signature).ok()?).ok()?;
+        let claims: JwtClaims = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(claims).ok()?).ok()?;
+        if claims.exp.is_some_and(|exp| now.timestamp() >= exp) {
+            return None;
+        }
+        Some(claims.tenant_id)
+    }
+}
+
+/// 启用多租户时认证锁接口的请求，并将租户放入请求扩展供处理器和参数提取器使用
+pub async fn tenant_guard(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let registry = req
+        .app_data::<web::Data<Arc<TenantRegistry>>>()
+        .filter(|registry| registry.is_enabled())
+        .filter(|_| {
+            !req.path().starts_with("/api/admin")
+                && !req.path().starts_with("/api/swagger-ui")
+                && !req.path().starts_with("/api/api-docs")
+        })
+        .cloned();
+    let Some(registry) = registry else {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    };
+
+    let tenant = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .and_then(|token| registry.authenticate(token, clock::now(req.request())));
+    let Some(tenant) = tenant else {
+        log::warn!("[TENANT] Unauthorized request: {} {}", req.method(), req.path());
+        let error = ApiResponse::<()>::error(LockError::Unauthorized, "Unauthorized".to_string());
+        let response = if v2::is_v2(req.request()) {
+            v2::error_response(req.request(), OpError::new(error.code, error.message))
+        } else if accepts_problem_json(req.request()) {
+            error.into_problem_response(req.request())
+        } else {
+            error.respond_with_status(req.request(), StatusCode::UNAUTHORIZED)
+        };
+        return Ok(req.into_response(response));
+    };
+
+    req.extensions_mut().insert(tenant);
+    Ok(next.call(req).await?.map_into_boxed_body())
+}
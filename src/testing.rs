@@ -0,0 +1,437 @@
+//! 供下游 crate 测试锁处理逻辑的工具，无需启动 Redis 或 Docker
+//!
+//! - [`ManualClock`]：手动推进的时钟，配合 [`Clock`] 验证过期逻辑而无需等待
+//! - [`MockStorage`]：基于内存存储的确定性存储，时钟可控，可按操作脚本化注入失败
+//! - [`TestServer`]：在当前进程内监听随机端口的锁服务，可直接用 HTTP 客户端访问
+//! - [`lock_info`]：构造直接写入存储的锁
+//!
+//! ```ignore
+//! let storage = Arc::new(MockStorage::new());
+//! let server = TestServer::builder()
+//!     .storage(storage.clone())
+//!     .clock(storage.clock().clone())
+//!     .start()
+//!     .await?;
+//! // 使用 server.url() 访问接口；推进时钟使锁过期，令下一次心跳失败
+//! storage.advance(Duration::from_secs(61));
+//! storage.fail_next("update_heartbeat", 1);
+//! server.stop().await;
+//! ```
+
+use crate::clock::{self, Clock};
+use crate::config::Config;
+use crate::events::EventBus;
+use crate::models::{
+    LockCondition, LockInfo, LockStats, NamespacePolicy, PreemptionNotice, RateLimitDecision, TokenBucket,
+};
+use crate::service::LockService;
+use crate::storage::memory::MemoryStorage;
+use crate::storage::{Acquire, LockStorage, Takeover};
+use actix_web::dev::ServerHandle;
+use actix_web::{App, HttpServer};
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::net::{SocketAddr, TcpListener};
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use uuid::Uuid;
+
+/// 手动推进的时钟
+pub struct ManualClock {
+    now: Mutex<DateTime<Utc>>,
+}
+
+impl ManualClock {
+    pub fn new(start: DateTime<Utc>) -> Self {
+        Self {
+            now: Mutex::new(start),
+        }
+    }
+
+    /// 将时钟向后推进 duration
+    pub fn advance(&self, duration: Duration) {
+        let mut now = self.now.lock();
+        *now += chrono::Duration::from_std(duration).unwrap_or(chrono::Duration::MAX);
+    }
+
+    /// 将时钟设置为 now
+    pub fn set(&self, now: DateTime<Utc>) {
+        *self.now.lock() = now;
+    }
+}
+
+impl Default for ManualClock {
+    /// 从当前系统时间开始
+    fn default() -> Self {
+        Self::new(Utc::now())
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock()
+    }
+}
+
+/// 测试用的锁：user_id 以 now 作为加锁时间持有 namespace 下的 business_id，其余字段取默认值
+pub fn lock_info(namespace: &str, business_id: &str, user_id: &str, timeout: u64, now: DateTime<Utc>) -> LockInfo {
+    LockInfo {
+        lock_id: Uuid::new_v4().to_string(),
+        namespace: namespace.to_string(),
+        user_id: user_id.to_string(),
+        user_name: format!("{} name", user_id),
+        business_id: business_id.to_string(),
+        timeout,
+        locked_at: now,
+        last_heartbeat: now,
+        metadata: Default::default(),
+        tags: Vec::new(),
+        max_hold_seconds: None,
+        grace_seconds: 0,
+        preemption: None,
+        version: 1,
+        reason: None,
+        client: None,
+        due_at: None,
+    }
+}
+
+/// [`MockStorage`] 按脚本注入的失败
+#[derive(Debug, Error)]
+#[error("injected failure in {operation}")]
+pub struct InjectedFailure {
+    pub operation: String,
+}
+
+/// 确定性的测试存储
+///
+/// 行为与内存存储相同，但使用 [`ManualClock`] 判断过期；[`MockStorage::fail_next`] 令指定操作（[`LockStorage`] 的方法名，
+/// 如 `try_acquire`）接下来的若干次调用返回 [`InjectedFailure`]，并记录每个操作的调用次数。
+pub struct MockStorage {
+    inner: MemoryStorage,
+    clock: Arc<ManualClock>,
+    failures: Mutex<HashMap<String, usize>>, // 操作 -> 剩余失败次数
+    calls: Mutex<HashMap<String, usize>>,    // 操作 -> 调用次数
+}
+
+impl MockStorage {
+    pub fn new() -> Self {
+        Self::with_clock(Arc::new(ManualClock::default()))
+    }
+
+    /// 使用指定的时钟，可与其他组件共享
+    pub fn with_clock(clock: Arc<ManualClock>) -> Self {
+        Self {
+            inner: MemoryStorage::new().with_clock(clock.clone()),
+            clock,
+            failures: Mutex::new(HashMap::new()),
+            calls: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn clock(&self) -> &Arc<ManualClock> {
+        &self.clock
+    }
+
+    /// 将时钟向后推进 duration
+    pub fn advance(&self, duration: Duration) {
+        self.clock.advance(duration);
+    }
+
+    /// 令 operation 接下来的 times 次调用失败，`usize::MAX` 表示一直失败
+    pub fn fail_next(&self, operation: &str, times: usize) {
+        self.failures.lock().insert(operation.to_string(), times);
+    }
+
+    /// 清除所有尚未触发的失败
+    pub fn clear_failures(&self) {
+        self.failures.lock().clear();
+    }
+
+    /// operation 被调用的次数（包括注入失败的调用）
+    pub fn calls(&self, operation: &str) -> usize {
+        self.calls.lock().get(operation).copied().unwrap_or(0)
+    }
+
+    fn check(&self, operation: &str) -> Result<()> {
+        *self.calls.lock().entry(operation.to_string()).or_default() += 1;
+        let mut failures = self.failures.lock();
+        let Some(remaining) = failures.get_mut(operation) else {
+            return Ok(());
+        };
+        if *remaining != usize::MAX {
+            *remaining -= 1;
+        }
+        if *remaining == 0 {
+            failures.remove(operation);
+        }
+        Err(InjectedFailure {
+            operation: operation.to_string(),
+        }
+        .into())
+    }
+}
+
+impl Default for MockStorage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl LockStorage for MockStorage {
+    async fn try_acquire(&self, lock_info: LockInfo) -> Result<Acquire> {
+        self.check("try_acquire")?;
+        self.inner.try_acquire(lock_info).await
+    }
+
+    async fn takeover(&self, lock_info: LockInfo, condition: &LockCondition) -> Result<Takeover> {
+        self.check("takeover")?;
+        self.inner.takeover(lock_info, condition).await
+    }
+
+    async fn restore(&self, lock_info: LockInfo) -> Result<bool> {
+        self.check("restore")?;
+        self.inner.restore(lock_info).await
+    }
+
+    async fn get_lock(&self, lock_key: &str) -> Result<Option<LockInfo>> {
+        self.check("get_lock")?;
+        self.inner.get_lock(lock_key).await
+    }
+
+    async fn update_heartbeat(&self, lock_id: &str) -> Result<Option<LockInfo>> {
+        self.check("update_heartbeat")?;
+        self.inner.update_heartbeat(lock_id).await
+    }
+
+    async fn update_heartbeat_version(&self, lock_id: &str, version: u64) -> Result<Option<LockInfo>> {
+        self.check("update_heartbeat_version")?;
+        self.inner.update_heartbeat_version(lock_id, version).await
+    }
+
+    async fn release(&self, lock_id: &str) -> Result<Option<LockInfo>> {
+        self.check("release")?;
+        self.inner.release(lock_id).await
+    }
+
+    async fn release_version(&self, lock_id: &str, version: u64) -> Result<Option<LockInfo>> {
+        self.check("release_version")?;
+        self.inner.release_version(lock_id, version).await
+    }
+
+    async fn release_owned(&self, lock_key: &str, user_id: &str) -> Result<Option<LockInfo>> {
+        self.check("release_owned")?;
+        self.inner.release_owned(lock_key, user_id).await
+    }
+
+    async fn cleanup_expired(&self) -> Result<Vec<LockInfo>> {
+        self.check("cleanup_expired")?;
+        self.inner.cleanup_expired().await
+    }
+
+    async fn list_locks(&self) -> Result<Vec<LockInfo>> {
+        self.check("list_locks")?;
+        self.inner.list_locks().await
+    }
+
+    async fn list_prefix(&self, key_prefix: &str) -> Result<Vec<LockInfo>> {
+        self.check("list_prefix")?;
+        self.inner.list_prefix(key_prefix).await
+    }
+
+    async fn get_lock_by_id(&self, lock_id: &str) -> Result<Option<LockInfo>> {
+        self.check("get_lock_by_id")?;
+        self.inner.get_lock_by_id(lock_id).await
+    }
+
+    async fn force_release(&self, lock_key: &str) -> Result<Option<LockInfo>> {
+        self.check("force_release")?;
+        self.inner.force_release(lock_key).await
+    }
+
+    async fn mark_preempted(&self, lock_id: &str, notice: PreemptionNotice) -> Result<Option<LockInfo>> {
+        self.check("mark_preempted")?;
+        self.inner.mark_preempted(lock_id, notice).await
+    }
+
+    async fn transfer(&self, lock_id: &str, new_lock: LockInfo) -> Result<Option<LockInfo>> {
+        self.check("transfer")?;
+        self.inner.transfer(lock_id, new_lock).await
+    }
+
+    async fn rewrite_lock(&self, lock_info: LockInfo) -> Result<bool> {
+        self.check("rewrite_lock")?;
+        self.inner.rewrite_lock(lock_info).await
+    }
+
+    async fn list_by_user(&self, user_id: &str) -> Result<Vec<LockInfo>> {
+        self.check("list_by_user")?;
+        self.inner.list_by_user(user_id).await
+    }
+
+    async fn list_by_business_id(&self, business_id: &str) -> Result<Vec<LockInfo>> {
+        self.check("list_by_business_id")?;
+        self.inner.list_by_business_id(business_id).await
+    }
+
+    async fn release_all_by_user(&self, user_id: &str) -> Result<Vec<LockInfo>> {
+        self.check("release_all_by_user")?;
+        self.inner.release_all_by_user(user_id).await
+    }
+
+    async fn count_locks(&self, namespace: &str) -> Result<u64> {
+        self.check("count_locks")?;
+        self.inner.count_locks(namespace).await
+    }
+
+    async fn stats(&self, top_n: usize) -> Result<LockStats> {
+        self.check("stats")?;
+        self.inner.stats(top_n).await
+    }
+
+    async fn list_namespaces(&self) -> Result<Vec<NamespacePolicy>> {
+        self.check("list_namespaces")?;
+        self.inner.list_namespaces().await
+    }
+
+    async fn get_namespace(&self, name: &str) -> Result<Option<NamespacePolicy>> {
+        self.check("get_namespace")?;
+        self.inner.get_namespace(name).await
+    }
+
+    async fn put_namespace(&self, policy: NamespacePolicy) -> Result<()> {
+        self.check("put_namespace")?;
+        self.inner.put_namespace(policy).await
+    }
+
+    async fn delete_namespace(&self, name: &str) -> Result<bool> {
+        self.check("delete_namespace")?;
+        self.inner.delete_namespace(name).await
+    }
+
+    async fn next_sequence(&self, name: &str, count: u64) -> Result<u64> {
+        self.check("next_sequence")?;
+        self.inner.next_sequence(name, count).await
+    }
+
+    async fn take_tokens(&self, key: &str, bucket: TokenBucket, cost: u64) -> Result<RateLimitDecision> {
+        self.check("take_tokens")?;
+        self.inner.take_tokens(key, bucket, cost).await
+    }
+
+    async fn get_record(&self, kind: &str, id: &str) -> Result<Option<String>> {
+        self.check("get_record")?;
+        self.inner.get_record(kind, id).await
+    }
+
+    async fn list_records(&self, kind: &str) -> Result<Vec<(String, String)>> {
+        self.check("list_records")?;
+        self.inner.list_records(kind).await
+    }
+
+    async fn swap_record(&self, kind: &str, id: &str, expected: Option<&str>, value: Option<&str>) -> Result<bool> {
+        self.check("swap_record")?;
+        self.inner.swap_record(kind, id, expected, value).await
+    }
+}
+
+/// 在当前进程内运行的锁服务，监听 127.0.0.1 的随机端口
+pub struct TestServer {
+    addr: SocketAddr,
+    handle: ServerHandle,
+    service: LockService,
+}
+
+impl TestServer {
+    pub fn builder() -> TestServerBuilder {
+        TestServerBuilder::default()
+    }
+
+    /// 服务地址，如 `http://127.0.0.1:38271`，接口位于其下的 `/api`
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn service(&self) -> &LockService {
+        &self.service
+    }
+
+    /// 停止服务并等待正在处理的请求结束
+    pub async fn stop(self) {
+        self.handle.stop(true).await;
+    }
+}
+
+/// [`TestServer`] 的构建器
+#[derive(Default)]
+pub struct TestServerBuilder {
+    config: Option<Config>,
+    storage: Option<Arc<dyn LockStorage>>,
+    clock: Option<Arc<dyn Clock>>,
+    event_bus: Option<EventBus>,
+}
+
+impl TestServerBuilder {
+    /// 服务配置，默认按环境变量加载（未设置时均为默认值）
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// 锁存储，默认为新的 [`MockStorage`]
+    pub fn storage(mut self, storage: Arc<dyn LockStorage>) -> Self {
+        self.storage = Some(storage);
+        self
+    }
+
+    /// 服务判断锁是否过期的时钟，应与存储使用同一个时钟。
+    /// 默认的 [`MockStorage`] 使用其自身的时钟，传入自定义存储时默认为系统时钟
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    /// 事件总线，可注册自定义的 `EventSink` 断言发布的锁事件
+    pub fn event_bus(mut self, event_bus: EventBus) -> Self {
+        self.event_bus = Some(event_bus);
+        self
+    }
+
+    /// 启动服务，不运行过期清理等定时任务，测试结果只取决于请求和时钟
+    pub async fn start(self) -> std::io::Result<TestServer> {
+        let config = self.config.unwrap_or_else(Config::from_env);
+        let (storage, clock): (Arc<dyn LockStorage>, Arc<dyn Clock>) = match self.storage {
+            Some(storage) => (storage, self.clock.unwrap_or_else(clock::system)),
+            None => {
+                let storage = MockStorage::new();
+                let clock = self.clock.unwrap_or_else(|| storage.clock().clone());
+                (Arc::new(storage), clock)
+            }
+        };
+        let service = LockService::new(config, storage, self.event_bus.unwrap_or_default()).with_clock(clock);
+
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        let app_service = service.clone();
+        let server = HttpServer::new(move || {
+            let app_service = app_service.clone();
+            App::new().configure(move |cfg| app_service.configure(cfg))
+        })
+        .workers(1)
+        .disable_signals()
+        .listen(listener)?
+        .run();
+        let handle = server.handle();
+        tokio::spawn(server);
+        Ok(TestServer { addr, handle, service })
+    }
+}
@@ -0,0 +1,49 @@
+//! 请求超时
+//!
+//! 配置 `REQUEST_TIMEOUT_MS` 后，`/api` 下的请求超过该时长仍未处理完时返回 HTTP 504（错误码 7005），不受 `HTTP_STATUS_MODE` 影响。
+//! 单个存储操作的超时见 [`crate::storage::timeout`]。等待锁释放 `/api/lock/wait-release` 按请求的 `timeout` 参数等待，不受影响。
+//!
+//! 超时后不再等待处理结果，已开始的写操作可能已经生效，客户端应查询锁状态确认。
+
+use crate::config::Config;
+use crate::lockops::OpError;
+use crate::models::{accepts_problem_json, ApiResponse, LockError};
+use crate::v2;
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::StatusCode;
+use actix_web::middleware::Next;
+use actix_web::{web, Error};
+use std::time::Duration;
+
+/// 自行控制等待时长的长轮询接口
+pub const LONG_POLL_PATHS: [&str; 1] = ["/api/lock/wait-release"];
+
+pub async fn request_timeout(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let timeout_ms = req
+        .app_data::<web::Data<Config>>()
+        .map_or(0, |config| config.request_timeout_ms);
+    if timeout_ms == 0 || LONG_POLL_PATHS.contains(&req.path()) || req.path().starts_with("/api/swagger-ui") {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    }
+
+    let request = req.request().clone();
+    match tokio::time::timeout(Duration::from_millis(timeout_ms), next.call(req)).await {
+        Ok(response) => Ok(response?.map_into_boxed_body()),
+        Err(_) => {
+            log::warn!("[TIMEOUT] {} {} timed out after {}ms", request.method(), request.path(), timeout_ms);
+            let error = ApiResponse::<()>::error(LockError::RequestTimeout, format!("Request timed out after {}ms", timeout_ms));
+            let response = if v2::is_v2(&request) {
+                v2::error_response(&request, OpError::new(error.code, error.message))
+            } else if accepts_problem_json(&request) {
+                error.into_problem_response(&request)
+            } else {
+                error.respond_with_status(&request, StatusCode::GATEWAY_TIMEOUT)
+            };
+            Ok(ServiceResponse::new(request, response))
+        }
+    }
+}
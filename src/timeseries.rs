@@ -0,0 +1,131 @@
+//! 锁统计时间序列
+//!
+//! 后台任务每 `STATS_SAMPLE_INTERVAL` 秒采样一次锁统计，保存在进程内的环形缓冲区中，保留最近 `STATS_SAMPLE_RETENTION` 秒，
+//! 通过 `GET /api/stats/timeseries` 按时间段聚合查询，可作为 Grafana JSON / Infinity 数据源直接绘图，无需部署 Prometheus。
+//! 采样读取的是存储中的统计，多实例共享 Redis 时各实例的序列相同；采样记录在本实例进程内，重启后清空。
+
+use crate::models::{LockStats, TimeSeries, TimeSeriesMetric, TimeSeriesPoint};
+use crate::tenant::Tenant;
+use chrono::{DateTime, TimeZone, Utc};
+use parking_lot::Mutex;
+use std::collections::{BTreeMap, VecDeque};
+
+/// 一次采样
+struct Sample {
+    at: DateTime<Utc>,
+    active_locks: u64,
+    namespaces: BTreeMap<String, u64>,
+    acquired: u64,
+    conflicts: u64,
+    expired: u64,
+}
+
+impl Sample {
+    /// 指标在采样时的值，namespace 为 None 时 tenant 不为 None 则统计该租户的全部命名空间；
+    /// 申请、冲突和过期次数为全服务累计，不区分命名空间和租户
+    fn value(&self, metric: TimeSeriesMetric, namespace: Option<&str>, tenant: Option<&Tenant>) -> u64 {
+        match metric {
+            TimeSeriesMetric::ActiveLocks => match (namespace, tenant) {
+                (Some(namespace), _) => self.namespaces.get(namespace).copied().unwrap_or(0),
+                (None, Some(tenant)) => self
+                    .namespaces
+                    .iter()
+                    .filter(|(namespace, _)| tenant.owns(namespace))
+                    .map(|(_, count)| count)
+                    .sum(),
+                (None, None) => self.active_locks,
+            },
+            TimeSeriesMetric::Acquired => self.acquired,
+            TimeSeriesMetric::Conflicts => self.conflicts,
+            TimeSeriesMetric::Expired => self.expired,
+        }
+    }
+}
+
+pub struct StatsSampler {
+    interval: u64,
+    capacity: usize,
+    samples: Mutex<VecDeque<Sample>>,
+}
+
+impl StatsSampler {
+    pub fn new(interval: u64, retention: u64) -> Self {
+        let capacity = retention.checked_div(interval).map_or(0, |count| count.max(1) as usize);
+        Self {
+            interval,
+            capacity,
+            samples: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    /// 采样间隔（秒），0 表示不采样
+    pub fn interval(&self) -> u64 {
+        self.interval
+    }
+
+    /// 记录一次采样，超过保留时长的最早采样被丢弃
+    pub fn record(&self, stats: &LockStats, at: DateTime<Utc>) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut samples = self.samples.lock();
+        if samples.len() == self.capacity {
+            samples.pop_front();
+        }
+        samples.push_back(Sample {
+            at,
+            active_locks: stats.active_locks,
+            namespaces: stats.namespaces.clone(),
+            acquired: stats.acquired,
+            conflicts: stats.conflicts,
+            expired: stats.expired,
+        });
+    }
+
+    /// 按 step 秒对齐分段聚合 [from, to] 内的采样：活跃锁数取每段最后一次采样，
+    /// 计数类指标取每段相对上一次采样的增量，计数变小（存储重启）时以当前值为增量
+    pub fn query(
+        &self,
+        metric: TimeSeriesMetric,
+        namespace: Option<&str>,
+        tenant: Option<&Tenant>,
+        step: u64,
+        from: Option<DateTime<Utc>>,
+        to: DateTime<Utc>,
+    ) -> TimeSeries {
+        let step = step.max(1) as i64;
+        let samples = self.samples.lock();
+        let mut points: Vec<TimeSeriesPoint> = Vec::new();
+        let mut previous: Option<u64> = None;
+        for sample in samples.iter().take_while(|sample| sample.at <= to) {
+            let value = sample.value(metric, namespace, tenant);
+            let point_value = match metric {
+                TimeSeriesMetric::ActiveLocks => value,
+                _ => match previous {
+                    Some(previous) if value >= previous => value - previous,
+                    Some(_) => value,
+                    None => 0,
+                },
+            };
+            previous = Some(value);
+            if from.is_some_and(|from| sample.at < from) {
+                continue;
+            }
+            let start = sample.at.timestamp().div_euclid(step) * step;
+            let time = Utc.timestamp_opt(start, 0).single().unwrap_or(sample.at);
+            match points.last_mut() {
+                Some(point) if point.time == time => match metric {
+                    TimeSeriesMetric::ActiveLocks => point.value = point_value,
+                    _ => point.value += point_value,
+                },
+                _ => points.push(TimeSeriesPoint { time, value: point_value }),
+            }
+        }
+        TimeSeries {
+            metric,
+            namespace: namespace.map(str::to_string),
+            step: step as u64,
+            points,
+        }
+    }
+}
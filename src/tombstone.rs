@@ -0,0 +1,101 @@
+//! 锁墓碑
+//!
+//! 锁释放、过期、被强制释放或被抢占后，在 `LOCK_TOMBSTONE_TTL` 秒内保留最后的持有人和结束原因，
+//! 锁空闲时通过 `GET /api/lock/status` 返回，便于排查"锁丢了"的问题。墓碑由订阅锁事件的 `TombstoneTracker` 记录，
+//! 仅包含当前实例处理的事件；Redis 自动过期的锁不产生事件，状态查询时由已过期但尚未删除的锁补全。
+
+use crate::events::{EventSink, LockEvent, LockEventType};
+use crate::models::{LockInfo, ReleaseReason, Tombstone};
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use dashmap::DashMap;
+
+pub struct TombstoneTracker {
+    ttl_secs: u64,
+    tombstones: DashMap<String, Tombstone>, // lock_key -> 最近一次持有的墓碑
+}
+
+impl TombstoneTracker {
+    pub fn new(ttl_secs: u64) -> Self {
+        Self {
+            ttl_secs,
+            tombstones: DashMap::new(),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.ttl_secs > 0
+    }
+
+    /// 锁最近一次持有的墓碑，已超过保留时间时为 None
+    pub fn get(&self, lock_key: &str, now: DateTime<Utc>) -> Option<Tombstone> {
+        self.tombstones
+            .get(lock_key)
+            .map(|tombstone| tombstone.clone())
+            .filter(|tombstone| tombstone.retained_until > now)
+    }
+
+    /// 已过期但尚未被清理的锁对应的墓碑
+    pub fn from_expired(&self, lock_info: &LockInfo) -> Option<Tombstone> {
+        let ended_at = lock_info.expiry_deadline()?;
+        self.build(lock_info, ReleaseReason::Expired, ended_at)
+    }
+
+    /// 删除超过保留时间的墓碑
+    pub fn prune(&self, now: DateTime<Utc>) {
+        self.tombstones.retain(|_, tombstone| tombstone.retained_until > now);
+    }
+
+    fn build(&self, lock_info: &LockInfo, reason: ReleaseReason, ended_at: DateTime<Utc>) -> Option<Tombstone> {
+        if !self.enabled() {
+            return None;
+        }
+        Some(Tombstone {
+            lock_id: lock_info.lock_id.clone(),
+            user_id: lock_info.user_id.clone(),
+            user_name: lock_info.user_name.clone(),
+            locked_at: lock_info.locked_at,
+            ended_at,
+            reason,
+            retained_until: ended_at + Duration::seconds(self.ttl_secs as i64),
+        })
+    }
+}
+
+#[async_trait]
+impl EventSink for TombstoneTracker {
+    fn name(&self) -> &str {
+        "tombstones"
+    }
+
+    async fn publish(&self, event: &LockEvent) -> Result<()> {
+        let reason = match event.event {
+            LockEventType::Released => ReleaseReason::Explicit,
+            LockEventType::Expired => ReleaseReason::Expired,
+            LockEventType::ForceReleased => ReleaseReason::Forced,
+            LockEventType::Preempted => ReleaseReason::Preempted,
+            LockEventType::TakeoverApproved => ReleaseReason::TakenOver,
+            // 锁再次被持有后不再需要墓碑
+            LockEventType::Acquired | LockEventType::ReservationGranted => {
+                let lock_key = event.lock.get_lock_key();
+                self.tombstones
+                    .remove_if(&lock_key, |_, tombstone| tombstone.ended_at <= event.timestamp);
+                return Ok(());
+            }
+            _ => return Ok(()),
+        };
+        // 过期事件在清理时发布，结束时间取锁的到期时间；启动恢复窗口结束时释放的锁尚未到期，取事件时间
+        let ended_at = match reason {
+            ReleaseReason::Expired => event
+                .lock
+                .expiry_deadline()
+                .map_or(event.timestamp, |deadline| deadline.min(event.timestamp)),
+            _ => event.timestamp,
+        };
+        if let Some(tombstone) = self.build(&event.lock, reason, ended_at) {
+            self.tombstones.insert(event.lock.get_lock_key(), tombstone);
+        }
+        Ok(())
+    }
+}
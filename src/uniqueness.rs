@@ -0,0 +1,64 @@
+//! 跨命名空间的 business_id 唯一性
+//!
+//! 同一资源因历史路由出现在多个命名空间中时，用户可能在两个命名空间中同时编辑同一条记录。
+//! `LOCK_UNIQUE_BUSINESS_IDS` 配置的模式（`*` 匹配任意字符）匹配的 business_id 同一时间只能在一个命名空间中被锁定，
+//! 不区分持有人；多租户时只在同一租户的命名空间之间互斥。存储层维护 business_id 到锁的索引，检查无需遍历全部锁。
+//! 与层级路径锁相同，检查与获取锁不是原子操作，不同命名空间的并发申请可能都成功。
+
+use crate::models::LockInfo;
+use crate::presence;
+use crate::storage::LockStorage;
+use crate::tenant;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+
+/// business_id 是否匹配任一模式
+pub fn is_unique(patterns: &[String], business_id: &str) -> bool {
+    patterns.iter().any(|pattern| matches(pattern, business_id))
+}
+
+/// 通配符匹配，`*` 匹配任意数量（含零个）的字符，其他字符按原样比较
+fn matches(pattern: &str, value: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = value.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // 没有通配符时要求完全相同
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+/// 查找 lock_info 的 business_id 在同一租户的其他命名空间中截至 now 未过期的锁，
+/// business_id 不匹配任何模式时返回 None；在场记录的命名空间不参与
+pub async fn find_conflict(
+    storage: &dyn LockStorage,
+    patterns: &[String],
+    lock_info: &LockInfo,
+    now: DateTime<Utc>,
+) -> Result<Option<LockInfo>> {
+    if !is_unique(patterns, &lock_info.business_id) || presence::is_presence_namespace(&lock_info.namespace) {
+        return Ok(None);
+    }
+    let tenant_id = tenant::tenant_of(&lock_info.namespace);
+    Ok(storage
+        .list_by_business_id(&lock_info.business_id)
+        .await?
+        .into_iter()
+        .find(|other| {
+            other.namespace != lock_info.namespace
+                && other.business_id == lock_info.business_id
+                && tenant::tenant_of(&other.namespace) == tenant_id
+                && !presence::is_presence_namespace(&other.namespace)
+                && !other.is_expired_at(now)
+        }))
+}
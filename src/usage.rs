@@ -0,0 +1,136 @@
+//! 按用户的用量统计 `GET /api/stats/users`
+//!
+//! 当前持有的锁数量在查询时从存储统计，包括所有实例获取的锁；获取次数、已结束的持有次数和持有时长取自本实例的锁事件，
+//! 累计持有时长另加上当前持有的锁已持有的时长。用于在启用 `LOCK_MAX_PER_USER` 之前找出持有锁最多、最久的用户。
+//! 用户持有的锁达到 [`Config::user_quota_warning`] 时，申请成功后发布 `user_quota_warning` 事件，不拒绝申请，
+//! 数量从低于提醒数量变为达到时发布一次。在场记录不计入，多租户时按租户内的 `user_id` 统计。
+//!
+//! [`Config::user_quota_warning`]: crate::config::Config::user_quota_warning
+
+use crate::events::{EventSink, LockEvent, LockEventType};
+use crate::models::{LockInfo, UserUsage, UserUsageSort};
+use crate::presence;
+use crate::tenant::{self, Tenant};
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use std::collections::BTreeMap;
+
+#[derive(Default, Clone, Copy)]
+struct Counters {
+    acquired: u64,
+    completed: u64,
+    hold_seconds: f64,
+    warnings: u64,
+}
+
+#[derive(Default)]
+pub struct UsageTracker {
+    users: DashMap<(String, String), Counters>, // (tenant_id, user_id) -> 本实例的累计数量，未区分租户时 tenant_id 为空
+}
+
+impl UsageTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 按 sort 排序的前 top 个用户，locks 为存储中当前的锁，tenant 为请求所属的租户
+    pub fn snapshot(
+        &self,
+        locks: &[LockInfo],
+        tenant: Option<&Tenant>,
+        sort: UserUsageSort,
+        top: usize,
+        now: DateTime<Utc>,
+    ) -> Vec<UserUsage> {
+        let tenant_id = tenant.map_or("", |tenant| tenant.id.as_str());
+        let mut users: BTreeMap<String, UserUsage> = BTreeMap::new();
+        for entry in self.users.iter().filter(|entry| entry.key().0 == tenant_id) {
+            let counters = *entry.value();
+            users.insert(
+                entry.key().1.clone(),
+                UserUsage {
+                    user_id: entry.key().1.clone(),
+                    held: 0,
+                    acquired: counters.acquired,
+                    completed: counters.completed,
+                    hold_seconds: counters.hold_seconds,
+                    warnings: counters.warnings,
+                },
+            );
+        }
+        for lock_info in locks {
+            if !counted(lock_info) || user_key(lock_info).0 != tenant_id || lock_info.is_expired_at(now) {
+                continue;
+            }
+            let usage = users.entry(lock_info.user_id.clone()).or_insert_with(|| UserUsage {
+                user_id: lock_info.user_id.clone(),
+                held: 0,
+                acquired: 0,
+                completed: 0,
+                hold_seconds: 0.0,
+                warnings: 0,
+            });
+            usage.held += 1;
+            usage.hold_seconds += lock_info.held_secs_at(now);
+        }
+
+        let mut users: Vec<UserUsage> = users.into_values().collect();
+        users.sort_by(|a, b| {
+            let order = match sort {
+                UserUsageSort::Held => b.held.cmp(&a.held),
+                UserUsageSort::HoldSeconds => b.hold_seconds.total_cmp(&a.hold_seconds),
+                UserUsageSort::Acquired => b.acquired.cmp(&a.acquired),
+            };
+            order.then_with(|| a.user_id.cmp(&b.user_id))
+        });
+        users.truncate(top);
+        users
+    }
+}
+
+/// 是否计入用户用量，在场记录不计入
+fn counted(lock_info: &LockInfo) -> bool {
+    !presence::is_presence_namespace(&lock_info.namespace)
+}
+
+fn user_key(lock_info: &LockInfo) -> (String, String) {
+    (
+        tenant::tenant_of(&lock_info.namespace).unwrap_or_default().to_string(),
+        lock_info.user_id.clone(),
+    )
+}
+
+#[async_trait]
+impl EventSink for UsageTracker {
+    fn name(&self) -> &str {
+        "usage"
+    }
+
+    async fn publish(&self, event: &LockEvent) -> Result<()> {
+        if !counted(&event.lock) {
+            return Ok(());
+        }
+        match event.event {
+            LockEventType::Acquired => {
+                self.users.entry(user_key(&event.lock)).or_default().acquired += 1;
+            }
+            LockEventType::Released
+            | LockEventType::Expired
+            | LockEventType::ForceReleased
+            | LockEventType::Preempted
+            | LockEventType::TakeoverApproved => {
+                let hold_secs = event.lock.held_secs_at(event.timestamp);
+                let mut counters = self.users.entry(user_key(&event.lock)).or_default();
+                counters.completed += 1;
+                counters.hold_seconds += hold_secs;
+            }
+            LockEventType::UserQuotaWarning => {
+                self.users.entry(user_key(&event.lock)).or_default().warnings += 1;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
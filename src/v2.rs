@@ -0,0 +1,301 @@
+//! v2 锁接口 `/api/v2/lock`
+//!
+//! 与 v1 共用 [`LockOps`] 服务层，行为完全相同，只是响应格式不同：
+//! - 始终按错误码返回对应的 HTTP 状态码，不受 `HTTP_STATUS_MODE` 影响
+//! - 成功时返回 `{"data": ..., "server_time": ...}`，失败时返回 `{"error": {...}, "server_time": ...}`，
+//!   错误对象的字段固定，v1 中与其他字段同级输出的扩展字段放在 `error.details` 中
+//! - 锁信息附带 `expires_at` 和 `remaining_seconds`
+//! - 锁冲突、路径冲突、条件不满足、抢占和死锁错误在 `error.holder` 中返回当前持有人
+//!
+//! v2 下的参数校验失败、存储不可用和故障注入错误同样使用 v2 的错误格式。
+
+use crate::clock;
+use crate::codec;
+use crate::config::Config;
+use crate::lockops::{LockOps, OpError, OpResult};
+use crate::models::{
+    error_status, error_title, AcquireLockRequest, AcquireLockSuccess, AdvisoryHolder, HeartbeatRequest, HeartbeatSuccess, LockExpiry, LockInfo,
+    LockStatus, LockStatusQuery, ReleaseLockRequest, Tombstone,
+};
+use crate::peer;
+use crate::tenant::Tenant;
+use crate::validation::{ValidJson, ValidQuery};
+use actix_web::body::BoxBody;
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+/// v2 接口的路径前缀
+const PREFIX: &str = "/api/v2/";
+
+/// 注册 `/v2` 下的接口，挂载在 `/api` 下
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/v2")
+            .route("/lock/acquire", web::post().to(acquire_lock))
+            .route("/lock/status", web::get().to(lock_status))
+            .route("/lock/heartbeat", web::post().to(heartbeat))
+            .route("/lock/release", web::post().to(release_lock)),
+    );
+}
+
+/// 请求是否访问 v2 接口
+pub fn is_v2(req: &HttpRequest) -> bool {
+    req.path().starts_with(PREFIX)
+}
+
+/// v2 成功响应
+#[derive(Debug, Serialize, ToSchema)]
+pub struct V2Success<T> {
+    pub data: T,
+    pub server_time: DateTime<Utc>,
+}
+
+/// v2 错误响应
+#[derive(Debug, Serialize, ToSchema)]
+pub struct V2ErrorResponse {
+    pub error: V2Error,
+    pub server_time: DateTime<Utc>,
+}
+
+/// v2 错误对象
+#[derive(Debug, Serialize, ToSchema)]
+pub struct V2Error {
+    /// 错误码，与 v1 相同
+    #[schema(example = 1001)]
+    pub code: i32,
+    #[schema(example = "Lock already held")]
+    pub title: String,
+    #[schema(example = "Lock already held by 张三")]
+    pub message: String,
+    /// 与申请冲突的锁的持有人
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub holder: Option<LockHolder>,
+    /// 错误的详细信息，如排队位置、死锁环和字段级校验错误
+    #[serde(skip_serializing_if = "serde_json::Map::is_empty")]
+    #[schema(value_type = Object)]
+    pub details: serde_json::Map<String, serde_json::Value>,
+}
+
+/// 锁的持有人，不包含 lock_id
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LockHolder {
+    #[schema(example = "user123")]
+    pub user_id: String,
+    #[schema(example = "张三")]
+    pub user_name: String,
+    #[schema(example = "order:order_001")]
+    pub lock_key: String,
+    pub locked_at: DateTime<Utc>,
+    /// 持有人停止心跳后锁的过期时间（含宽限期），此后他人可以获取，永久锁为 null
+    pub expires_at: Option<DateTime<Utc>>,
+    /// 距离过期的剩余秒数（含宽限期），永久锁为 null
+    pub remaining_seconds: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl LockHolder {
+    fn new(lock_info: &LockInfo, now: DateTime<Utc>) -> Self {
+        Self {
+            user_id: lock_info.user_id.clone(),
+            user_name: lock_info.user_name.clone(),
+            lock_key: lock_info.get_lock_key(),
+            locked_at: lock_info.locked_at,
+            expires_at: lock_info.expiry_deadline(),
+            remaining_seconds: lock_info.deadline_secs_at(now),
+            reason: lock_info.reason.clone(),
+        }
+    }
+}
+
+/// 附带过期时间的锁信息
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LockView {
+    #[serde(flatten)]
+    pub lock: LockInfo,
+    /// 不再心跳时锁的过期时间，永久锁为 null
+    pub expires_at: Option<DateTime<Utc>>,
+    /// 距离过期的剩余秒数，永久锁为 null
+    pub remaining_seconds: Option<u64>,
+}
+
+impl LockView {
+    /// 以 now 计算剩余有效时间
+    pub fn new(lock: LockInfo, now: DateTime<Utc>) -> Self {
+        Self {
+            expires_at: lock.expires_at(),
+            remaining_seconds: lock.remaining_secs_at(now),
+            lock,
+        }
+    }
+}
+
+/// v2 锁状态
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LockStatusV2 {
+    pub locked: bool,
+    pub lock: Option<LockView>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tombstone: Option<Tombstone>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub holders: Option<Vec<AdvisoryHolder>>,
+    pub unconfirmed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub confirm_before: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expiry: Option<LockExpiry>,
+}
+
+impl LockStatusV2 {
+    pub fn new(status: LockStatus, now: DateTime<Utc>) -> Self {
+        Self {
+            locked: status.locked,
+            lock: status.lock.map(|lock| LockView::new(lock, now)),
+            tombstone: status.tombstone,
+            holders: status.holders,
+            unconfirmed: status.unconfirmed,
+            confirm_before: status.confirm_before,
+            expiry: status.expiry,
+        }
+    }
+}
+
+/// v2 释放锁成功响应
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ReleaseSuccess {
+    pub released: bool,
+    #[schema(example = "550e8400-e29b-41d4-a716-446655440000")]
+    pub lock_id: String,
+    /// 释放时锁的版本号
+    #[schema(example = 2)]
+    pub version: u64,
+}
+
+/// v2 响应，按操作结果输出成功数据或错误对象
+pub struct V2Response<T>(pub OpResult<T>);
+
+impl<T: Serialize> Responder for V2Response<T> {
+    type Body = BoxBody;
+
+    fn respond_to(self, req: &HttpRequest) -> HttpResponse<Self::Body> {
+        match self.0 {
+            Ok(data) => codec::respond(
+                req,
+                StatusCode::OK,
+                &V2Success {
+                    data,
+                    server_time: clock::now(req),
+                },
+            ),
+            Err(e) => error_response(req, e),
+        }
+    }
+}
+
+/// v2 错误响应，HTTP 状态码由错误码决定
+pub fn error_response(req: &HttpRequest, e: OpError) -> HttpResponse {
+    let status = match error_status(e.code) {
+        StatusCode::OK => StatusCode::INTERNAL_SERVER_ERROR,
+        status => status,
+    };
+    let now = clock::now(req);
+    codec::respond(
+        req,
+        status,
+        &V2ErrorResponse {
+            error: V2Error {
+                code: e.code,
+                title: error_title(e.code).to_string(),
+                message: e.message,
+                holder: e.holder.as_deref().map(|holder| LockHolder::new(holder, now)),
+                details: e.extensions,
+            },
+            server_time: now,
+        },
+    )
+}
+
+/// 申请锁（v2）
+#[utoipa::path(
+    post,
+    path = "/api/v2/lock/acquire",
+    tag = "lock-v2",
+    request_body = AcquireLockRequest,
+    responses(
+        (status = 200, description = "申请锁成功", body = V2Success<AcquireLockSuccess>),
+        (status = 409, description = "锁已被占用，error.holder 为当前持有人", body = V2ErrorResponse),
+        (status = 412, description = "条件获取不满足条件", body = V2ErrorResponse)
+    )
+)]
+pub async fn acquire_lock(
+    ops: web::Data<LockOps>,
+    config: web::Data<Config>,
+    http_req: HttpRequest,
+    req: ValidJson<AcquireLockRequest>,
+) -> V2Response<AcquireLockSuccess> {
+    let client = peer::client_context(&http_req, req.hostname.clone(), &config.trusted_proxies);
+    V2Response(ops.acquire(&req, client).await)
+}
+
+/// 查询锁状态（v2）
+#[utoipa::path(
+    get,
+    path = "/api/v2/lock/status",
+    tag = "lock-v2",
+    params(LockStatusQuery),
+    responses(
+        (status = 200, description = "锁状态", body = V2Success<LockStatusV2>)
+    )
+)]
+pub async fn lock_status(ops: web::Data<LockOps>, query: ValidQuery<LockStatusQuery>) -> V2Response<LockStatusV2> {
+    V2Response(
+        ops.status(&query.namespace, &query.business_id)
+            .await
+            .map(|status| LockStatusV2::new(status, ops.clock.now())),
+    )
+}
+
+/// 心跳（v2）
+#[utoipa::path(
+    post,
+    path = "/api/v2/lock/heartbeat",
+    tag = "lock-v2",
+    request_body = HeartbeatRequest,
+    responses(
+        (status = 200, description = "心跳成功", body = V2Success<HeartbeatSuccess>),
+        (status = 404, description = "锁不存在或已过期", body = V2ErrorResponse)
+    )
+)]
+pub async fn heartbeat(
+    ops: web::Data<LockOps>,
+    tenant: Option<web::ReqData<Tenant>>,
+    req: ValidJson<HeartbeatRequest>,
+) -> V2Response<HeartbeatSuccess> {
+    V2Response(ops.heartbeat(&req, tenant.as_deref()).await)
+}
+
+/// 释放锁（v2）
+#[utoipa::path(
+    post,
+    path = "/api/v2/lock/release",
+    tag = "lock-v2",
+    request_body = ReleaseLockRequest,
+    responses(
+        (status = 200, description = "释放锁成功", body = V2Success<ReleaseSuccess>),
+        (status = 404, description = "锁不存在或不属于当前用户", body = V2ErrorResponse)
+    )
+)]
+pub async fn release_lock(
+    ops: web::Data<LockOps>,
+    tenant: Option<web::ReqData<Tenant>>,
+    req: ValidJson<ReleaseLockRequest>,
+) -> V2Response<ReleaseSuccess> {
+    V2Response(ops.release(&req, tenant.as_deref()).await.map(|lock_info| ReleaseSuccess {
+        released: true,
+        lock_id: lock_info.lock_id,
+        version: lock_info.version,
+    }))
+}
@@ -0,0 +1,880 @@
+//! 请求参数校验
+//!
+//! 请求 DTO 实现 [`Validate`]，处理器使用 [`ValidJson`] / [`ValidQuery`] 代替 `web::Json` / `web::Query`
+//! 提取参数即可在进入处理器前完成校验，校验失败时返回错误码 1000 和字段级错误详情。
+//! [`ValidJson`] 同样接受 MessagePack 请求体，见 [`crate::codec`]。
+
+use crate::clock;
+use crate::codec::Body;
+use crate::health::PROBE_NAMESPACE;
+use crate::leader::LEADER_NAMESPACE;
+use crate::lockops::OpError;
+use crate::advisory;
+use crate::models::{
+    AcquireLockRequest, AdminLoginQuery, ApiResponse, AuditQuery, HandoffRequest, LockError, CancelReservationRequest, CheckoutRequest, ChaosSettings, ExpireLocksRequest, ForceReleaseRequest,
+    CreateSessionRequest, ExportRecord, GracefulReleaseRequest, HeartbeatRequest, ImportQuery, LeaveQueueQuery, LegacyImportQuery, ListLocksQuery, LockExport, LockInfo, ListReservationsQuery, LockStatusQuery, RateLimitRequest, ReleaseAllRequest, ReleaseByTagRequest,
+    PresenceJoinRequest, PresenceLeaveRequest, PresenceListQuery, ReleaseLockRequest, ReserveLockRequest, RequestTakeoverRequest, RespondTakeoverRequest, PendingTakeoversQuery, TakeoverStatusQuery, SequenceQuery, SessionRequest, StatsQuery, UserStatsQuery, TimeSeriesMetric, TimeSeriesQuery, VerifyLockRequest, WaitReleaseQuery, MAX_TAGS, MAX_TAG_LEN,
+};
+use crate::presence;
+use crate::tenant::Tenant;
+use crate::v2;
+use actix_web::dev::Payload;
+use actix_web::error::InternalError;
+use actix_web::{web, FromRequest, HttpMessage, HttpRequest, Responder};
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::future::Future;
+use std::ops::Deref;
+use std::pin::Pin;
+use utoipa::ToSchema;
+
+pub const MAX_NAMESPACE_LEN: usize = 64;
+pub const MAX_ID_LEN: usize = 128;
+pub const MAX_USER_NAME_LEN: usize = 128;
+pub const MAX_BUSINESS_ID_LEN: usize = 256;
+pub const MAX_STATS_TOP: usize = 100;
+pub const MAX_URL_LEN: usize = 2048;
+pub const MAX_REASON_LEN: usize = 512;
+pub const MAX_HOSTNAME_LEN: usize = 255;
+pub const MAX_CHAOS_LATENCY_MS: u64 = 60_000;
+pub const MAX_PRIORITY: i32 = 100;
+pub const MAX_WAIT_RELEASE_TIMEOUT: u64 = 300;
+pub const MAX_SEQUENCE_BATCH: u64 = 10_000;
+pub const MAX_RATE_LIMIT_CAPACITY: u64 = 1_000_000;
+pub const MAX_AUDIT_COUNT: usize = 1000;
+/// 最长持有时间、预计归还时间等时长参数的上限（10 年）
+pub const MAX_DURATION_SECS: u64 = 10 * 365 * 24 * 3600;
+/// 预约开始时间最多提前的秒数（1 年）
+pub const MAX_RESERVE_AHEAD_SECS: i64 = 365 * 24 * 3600;
+
+/// 字段级错误
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct FieldError {
+    #[schema(example = "business_id")]
+    pub field: String,
+    #[schema(example = "must not be empty")]
+    pub message: String,
+}
+
+/// 校验错误集合
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct ValidationErrors {
+    pub errors: Vec<FieldError>,
+}
+
+impl ValidationErrors {
+    pub fn add(&mut self, field: &str, message: impl Into<String>) {
+        self.errors.push(FieldError {
+            field: field.to_string(),
+            message: message.into(),
+        });
+    }
+
+    /// 必填字段，不能为空白且不超过最大长度
+    pub fn required(&mut self, field: &str, value: &str, max_len: usize) {
+        if value.trim().is_empty() {
+            self.add(field, "must not be empty");
+        } else if value.len() > max_len {
+            self.add(field, format!("must be at most {} bytes", max_len));
+        }
+    }
+
+    /// 可选字段，提供时校验规则与必填字段相同
+    pub fn optional(&mut self, field: &str, value: Option<&str>, max_len: usize) {
+        if let Some(value) = value {
+            self.required(field, value, max_len);
+        }
+    }
+
+    /// 命名空间只允许字母、数字、`-`、`_`、`.`，避免与锁键分隔符 `:` 冲突
+    pub fn namespace(&mut self, field: &str, value: &str) {
+        if value.is_empty() {
+            self.add(field, "must not be empty");
+        } else if value.len() > MAX_NAMESPACE_LEN {
+            self.add(field, format!("must be at most {} bytes", MAX_NAMESPACE_LEN));
+        } else if !is_valid_namespace(value) {
+            self.add(field, "may only contain letters, digits, '-', '_' and '.'");
+        } else if value == PROBE_NAMESPACE {
+            self.add(field, "is reserved for the readiness self-test");
+        } else if value == LEADER_NAMESPACE {
+            self.add(field, "is reserved for background task leader election");
+        } else if presence::is_presence_namespace(value) {
+            self.add(field, format!("must not start with '{}', which is reserved for presence", presence::NAMESPACE_PREFIX));
+        }
+    }
+
+    /// 存储中的命名空间，导入等管理接口使用，可以带有租户前缀
+    pub fn scoped_namespace(&mut self, field: &str, value: &str) {
+        if value.split(crate::tenant::SEPARATOR).any(|part| part.len() > MAX_NAMESPACE_LEN) {
+            self.add(field, format!("must be at most {} bytes per part", MAX_NAMESPACE_LEN));
+        } else if !is_valid_scoped_namespace(value) {
+            self.add(field, "may only contain letters, digits, '-', '_' and '.', optionally prefixed by '<tenant_id>/'");
+        }
+    }
+
+    /// 在场者的 business_id，不能包含在场记录业务键的分隔符
+    pub fn presence_business_id(&mut self, field: &str, value: &str) {
+        self.required(field, value, MAX_BUSINESS_ID_LEN);
+        if !advisory::is_valid_business_id(value) {
+            self.add(field, format!("must not contain '{}'", advisory::SEPARATOR));
+        }
+    }
+
+    /// 回调地址，必须是 http 或 https 地址
+    pub fn url(&mut self, field: &str, value: &str) {
+        if value.len() > MAX_URL_LEN {
+            self.add(field, format!("must be at most {} bytes", MAX_URL_LEN));
+        } else if !(value.starts_with("http://") || value.starts_with("https://")) {
+            self.add(field, "must be an http or https URL");
+        }
+    }
+
+    /// 可选的时长（秒），提供时须大于 0 且不超过 [`MAX_DURATION_SECS`]
+    pub fn duration(&mut self, field: &str, value: Option<u64>) {
+        match value {
+            Some(0) => self.add(field, "must be greater than 0"),
+            Some(secs) if secs > MAX_DURATION_SECS => {
+                self.add(field, format!("must be at most {} seconds", MAX_DURATION_SECS))
+            }
+            _ => {}
+        }
+    }
+
+    pub fn into_result(self) -> Result<(), ValidationErrors> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(self)
+        }
+    }
+
+    /// 转换为错误响应，遵循 HTTP 状态码模式和 problem+json 协商
+    fn into_error(self, req: &HttpRequest) -> actix_web::Error {
+        let message = format!(
+            "Validation failed: {}",
+            self.errors
+                .iter()
+                .map(|error| format!("{} {}", error.field, error.message))
+                .collect::<Vec<_>>()
+                .join("; ")
+        );
+        let response = if v2::is_v2(req) {
+            v2::error_response(req, OpError::new(LockError::ValidationFailed, message.clone()).with_extensions(&self))
+        } else {
+            ApiResponse::<()>::error(LockError::ValidationFailed, message.clone())
+                .with_extensions(&self)
+                .respond_to(req)
+        };
+        InternalError::from_response(message, response).into()
+    }
+}
+
+/// 命名空间字符集校验
+pub fn is_valid_namespace(value: &str) -> bool {
+    !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+/// 存储中的命名空间校验，可以带有租户前缀 `<tenant_id>/`
+pub fn is_valid_scoped_namespace(value: &str) -> bool {
+    match value.split_once(crate::tenant::SEPARATOR) {
+        Some((tenant_id, namespace)) => is_valid_namespace(tenant_id) && is_valid_namespace(namespace),
+        None => is_valid_namespace(value),
+    }
+}
+
+/// 可校验的请求参数
+pub trait Validate {
+    fn validate(&self) -> Result<(), ValidationErrors>;
+
+    /// 依赖当前时间的校验，在 validate 通过后调用，now 取自应用注册的时钟
+    fn validate_at(&self, _now: DateTime<Utc>) -> Result<(), ValidationErrors> {
+        Ok(())
+    }
+
+    /// 启用多租户时在校验通过后调用，将命名空间等名称转换为租户在存储中的名称
+    fn scope(&mut self, _tenant: &Tenant) {}
+}
+
+/// 将校验通过的参数限定到请求所属的租户，见 [`crate::tenant`]
+fn scope_to_tenant<T: Validate>(req: &HttpRequest, value: &mut T) {
+    if let Some(tenant) = req.extensions().get::<Tenant>() {
+        value.scope(tenant);
+    }
+}
+
+type ExtractFuture<T> = Pin<Box<dyn Future<Output = Result<T, actix_web::Error>>>>;
+
+/// 带校验的 JSON 或 MessagePack 请求体
+pub struct ValidJson<T>(pub T);
+
+impl<T> Deref for ValidJson<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: DeserializeOwned + Validate + 'static> FromRequest for ValidJson<T> {
+    type Error = actix_web::Error;
+    type Future = ExtractFuture<Self>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let body = Body::<T>::from_request(req, payload);
+        let req = req.clone();
+        Box::pin(async move {
+            let mut value = body.await?.into_inner();
+            value
+                .validate()
+                .and_then(|()| value.validate_at(clock::now(&req)))
+                .map_err(|errors| errors.into_error(&req))?;
+            scope_to_tenant(&req, &mut value);
+            Ok(ValidJson(value))
+        })
+    }
+}
+
+/// 带校验的查询参数
+pub struct ValidQuery<T>(pub T);
+
+impl<T> Deref for ValidQuery<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: DeserializeOwned + Validate + 'static> FromRequest for ValidQuery<T> {
+    type Error = actix_web::Error;
+    type Future = ExtractFuture<Self>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let result = web::Query::<T>::from_query(req.query_string())
+            .map_err(actix_web::Error::from)
+            .and_then(|query| {
+                let mut value = query.into_inner();
+                value
+                    .validate()
+                    .and_then(|()| value.validate_at(clock::now(req)))
+                    .map_err(|errors| errors.into_error(req))?;
+                scope_to_tenant(req, &mut value);
+                Ok(ValidQuery(value))
+            });
+        Box::pin(async move { result })
+    }
+}
+
+impl Validate for AcquireLockRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.namespace("namespace", &self.namespace);
+        errors.required("user_id", &self.user_id, MAX_ID_LEN);
+        errors.required("user_name", &self.user_name, MAX_USER_NAME_LEN);
+        errors.required("business_id", &self.business_id, MAX_BUSINESS_ID_LEN);
+        if self.timeout == Some(0) {
+            errors.add("timeout", "must be greater than 0, use permanent for locks that never expire");
+        }
+        if self.permanent && self.timeout.is_some() {
+            errors.add("timeout", "must not be set for permanent locks");
+        }
+        if let Some(session_id) = &self.session_id {
+            errors.required("session_id", session_id, MAX_ID_LEN);
+            if self.timeout.is_some() {
+                errors.add("timeout", "must not be set for session locks, the session timeout is used");
+            }
+            if self.permanent {
+                errors.add("session_id", "must not be set for permanent locks");
+            }
+        }
+        if !(-MAX_PRIORITY..=MAX_PRIORITY).contains(&self.priority) {
+            errors.add(
+                "priority",
+                format!("must be between -{} and {}", MAX_PRIORITY, MAX_PRIORITY),
+            );
+        }
+        errors.duration("max_hold_seconds", self.max_hold_seconds);
+        if self.permanent && self.max_hold_seconds.is_some() {
+            errors.add("max_hold_seconds", "must not be set for permanent locks");
+        }
+        if self.grace_seconds.is_some() && !self.preempt {
+            errors.add("grace_seconds", "requires preempt");
+        }
+        if let Some(holder) = &self.if_holder_is {
+            errors.required("if_holder_is", holder, MAX_ID_LEN);
+        }
+        if self.preempt && self.condition().is_some() {
+            errors.add("preempt", "must not be combined with if_holder_is or if_version");
+        }
+        if let Some(reason) = &self.reason {
+            errors.required("reason", reason, MAX_REASON_LEN);
+        }
+        if let Some(hostname) = &self.hostname {
+            errors.required("hostname", hostname, MAX_HOSTNAME_LEN);
+        }
+        if self.permanent && self.warn_before_seconds.is_some() {
+            errors.add("warn_before_seconds", "must not be set for permanent locks");
+        }
+        if self.tags.len() > MAX_TAGS {
+            errors.add("tags", format!("must contain at most {} tags", MAX_TAGS));
+        }
+        for tag in &self.tags {
+            errors.required("tags", tag, MAX_TAG_LEN);
+        }
+        for key in self.metadata.keys() {
+            if key.is_empty() {
+                errors.add("metadata", "keys must not be empty");
+            }
+        }
+        errors.duration("warn_before_seconds", self.warn_before_seconds);
+        if let Some(url) = &self.warning_callback_url {
+            if self.warn_before_seconds.is_none() {
+                errors.add("warning_callback_url", "requires warn_before_seconds");
+            }
+            errors.url("warning_callback_url", url);
+        }
+        errors.duration("due_in_seconds", self.due_in_seconds);
+        errors.into_result()
+    }
+
+    fn scope(&mut self, tenant: &Tenant) {
+        self.namespace = tenant.scope(&self.namespace);
+    }
+}
+
+impl Validate for CheckoutRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        self.acquire_request().validate()
+    }
+
+    fn scope(&mut self, tenant: &Tenant) {
+        self.namespace = tenant.scope(&self.namespace);
+    }
+}
+
+impl Validate for ReserveLockRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.namespace("namespace", &self.namespace);
+        errors.required("user_id", &self.user_id, MAX_ID_LEN);
+        errors.required("user_name", &self.user_name, MAX_USER_NAME_LEN);
+        errors.required("business_id", &self.business_id, MAX_BUSINESS_ID_LEN);
+        errors.duration("hold_for", Some(self.hold_for));
+        if let Some(reason) = &self.reason {
+            errors.required("reason", reason, MAX_REASON_LEN);
+        }
+        if self.tags.len() > MAX_TAGS {
+            errors.add("tags", format!("must contain at most {} tags", MAX_TAGS));
+        }
+        for tag in &self.tags {
+            errors.required("tags", tag, MAX_TAG_LEN);
+        }
+        for key in self.metadata.keys() {
+            if key.is_empty() {
+                errors.add("metadata", "keys must not be empty");
+            }
+        }
+        errors.into_result()
+    }
+
+    fn validate_at(&self, now: DateTime<Utc>) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        if self.acquire_at <= now {
+            errors.add("acquire_at", "must be in the future");
+        } else if (self.acquire_at - now).num_seconds() > MAX_RESERVE_AHEAD_SECS {
+            errors.add("acquire_at", format!("must be at most {} seconds ahead", MAX_RESERVE_AHEAD_SECS));
+        }
+        errors.into_result()
+    }
+
+    fn scope(&mut self, tenant: &Tenant) {
+        self.namespace = tenant.scope(&self.namespace);
+    }
+}
+
+impl Validate for CancelReservationRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.required("lock_id", &self.lock_id, MAX_ID_LEN);
+        errors.required("user_id", &self.user_id, MAX_ID_LEN);
+        errors.into_result()
+    }
+}
+
+impl Validate for RequestTakeoverRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.namespace("namespace", &self.namespace);
+        errors.required("user_id", &self.user_id, MAX_ID_LEN);
+        errors.required("user_name", &self.user_name, MAX_USER_NAME_LEN);
+        errors.required("business_id", &self.business_id, MAX_BUSINESS_ID_LEN);
+        if self.timeout == Some(0) {
+            errors.add("timeout", "must be greater than 0");
+        }
+        if let Some(message) = &self.message {
+            errors.required("message", message, MAX_REASON_LEN);
+        }
+        if let Some(hostname) = &self.hostname {
+            errors.required("hostname", hostname, MAX_HOSTNAME_LEN);
+        }
+        errors.into_result()
+    }
+
+    fn scope(&mut self, tenant: &Tenant) {
+        self.namespace = tenant.scope(&self.namespace);
+    }
+}
+
+impl Validate for RespondTakeoverRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.required("request_id", &self.request_id, MAX_ID_LEN);
+        errors.required("lock_id", &self.lock_id, MAX_ID_LEN);
+        errors.into_result()
+    }
+}
+
+impl Validate for TakeoverStatusQuery {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.required("request_id", &self.request_id, MAX_ID_LEN);
+        errors.required("user_id", &self.user_id, MAX_ID_LEN);
+        errors.into_result()
+    }
+}
+
+impl Validate for PendingTakeoversQuery {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.required("lock_id", &self.lock_id, MAX_ID_LEN);
+        errors.into_result()
+    }
+}
+
+impl Validate for PresenceJoinRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.namespace("namespace", &self.namespace);
+        errors.presence_business_id("business_id", &self.business_id);
+        errors.required("user_id", &self.user_id, MAX_ID_LEN);
+        errors.required("user_name", &self.user_name, MAX_USER_NAME_LEN);
+        if self.timeout == Some(0) {
+            errors.add("timeout", "must be greater than 0");
+        }
+        for key in self.metadata.keys() {
+            if key.is_empty() {
+                errors.add("metadata", "keys must not be empty");
+            }
+        }
+        errors.into_result()
+    }
+
+    fn scope(&mut self, tenant: &Tenant) {
+        self.namespace = tenant.scope(&self.namespace);
+    }
+}
+
+impl Validate for PresenceLeaveRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.namespace("namespace", &self.namespace);
+        errors.presence_business_id("business_id", &self.business_id);
+        errors.required("user_id", &self.user_id, MAX_ID_LEN);
+        errors.into_result()
+    }
+
+    fn scope(&mut self, tenant: &Tenant) {
+        self.namespace = tenant.scope(&self.namespace);
+    }
+}
+
+impl Validate for PresenceListQuery {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.namespace("namespace", &self.namespace);
+        errors.presence_business_id("business_id", &self.business_id);
+        errors.into_result()
+    }
+
+    fn scope(&mut self, tenant: &Tenant) {
+        self.namespace = tenant.scope(&self.namespace);
+    }
+}
+
+impl Validate for ListReservationsQuery {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        if let Some(namespace) = &self.namespace {
+            errors.namespace("namespace", namespace);
+        }
+        errors.into_result()
+    }
+
+    fn scope(&mut self, tenant: &Tenant) {
+        self.namespace = self.namespace.as_deref().map(|namespace| tenant.scope(namespace));
+    }
+}
+
+impl Validate for HeartbeatRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.required("lock_id", &self.lock_id, MAX_ID_LEN);
+        errors.into_result()
+    }
+}
+
+impl Validate for ReleaseLockRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.optional("lock_id", self.lock_id.as_deref(), MAX_ID_LEN);
+        errors.namespace("namespace", &self.namespace);
+        errors.optional("business_id", self.business_id.as_deref(), MAX_BUSINESS_ID_LEN);
+        errors.optional("user_id", self.user_id.as_deref(), MAX_ID_LEN);
+        errors.into_result()
+    }
+
+    fn scope(&mut self, tenant: &Tenant) {
+        self.namespace = tenant.scope(&self.namespace);
+    }
+}
+
+impl Validate for VerifyLockRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.required("lock_id", &self.lock_id, MAX_ID_LEN);
+        errors.namespace("namespace", &self.namespace);
+        errors.required("business_id", &self.business_id, MAX_BUSINESS_ID_LEN);
+        errors.optional("user_id", self.user_id.as_deref(), MAX_ID_LEN);
+        errors.into_result()
+    }
+
+    fn scope(&mut self, tenant: &Tenant) {
+        self.namespace = tenant.scope(&self.namespace);
+    }
+}
+
+impl Validate for ReleaseAllRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.required("user_id", &self.user_id, MAX_ID_LEN);
+        errors.into_result()
+    }
+}
+
+impl Validate for GracefulReleaseRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.required("user_id", &self.user_id, MAX_ID_LEN);
+        errors.optional("session_id", self.session_id.as_deref(), MAX_ID_LEN);
+        if self.drain_seconds == Some(0) {
+            errors.add("drain_seconds", "must be greater than 0");
+        }
+        errors.into_result()
+    }
+}
+
+impl Validate for CreateSessionRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.required("user_id", &self.user_id, MAX_ID_LEN);
+        if self.timeout == Some(0) {
+            errors.add("timeout", "must be greater than 0");
+        }
+        errors.into_result()
+    }
+}
+
+impl Validate for SessionRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.required("session_id", &self.session_id, MAX_ID_LEN);
+        errors.into_result()
+    }
+}
+
+impl Validate for ForceReleaseRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.optional("lock_id", self.lock_id.as_deref(), MAX_ID_LEN);
+        errors.namespace("namespace", &self.namespace);
+        errors.optional("business_id", self.business_id.as_deref(), MAX_BUSINESS_ID_LEN);
+        errors.into_result()
+    }
+}
+
+impl Validate for ReleaseByTagRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.required("tag", &self.tag, MAX_TAG_LEN);
+        if let Some(namespace) = &self.namespace {
+            errors.namespace("namespace", namespace);
+        }
+        errors.into_result()
+    }
+}
+
+impl Validate for ExpireLocksRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        // 至少指定一个时间条件，避免误过期全部的锁
+        if self.older_than_seconds.is_none() && self.no_heartbeat_for.is_none() {
+            errors.add("older_than_seconds", "is required unless no_heartbeat_for is set");
+        }
+        if let Some(namespace) = &self.namespace {
+            errors.namespace("namespace", namespace);
+        }
+        errors.into_result()
+    }
+}
+
+impl Validate for LockStatusQuery {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.namespace("namespace", &self.namespace);
+        errors.required("business_id", &self.business_id, MAX_BUSINESS_ID_LEN);
+        errors.into_result()
+    }
+
+    fn scope(&mut self, tenant: &Tenant) {
+        self.namespace = tenant.scope(&self.namespace);
+    }
+}
+
+impl Validate for LeaveQueueQuery {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.namespace("namespace", &self.namespace);
+        errors.required("business_id", &self.business_id, MAX_BUSINESS_ID_LEN);
+        errors.required("user_id", &self.user_id, MAX_ID_LEN);
+        errors.into_result()
+    }
+
+    fn scope(&mut self, tenant: &Tenant) {
+        self.namespace = tenant.scope(&self.namespace);
+    }
+}
+
+impl Validate for WaitReleaseQuery {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.namespace("namespace", &self.namespace);
+        errors.required("business_id", &self.business_id, MAX_BUSINESS_ID_LEN);
+        if self.timeout > MAX_WAIT_RELEASE_TIMEOUT {
+            errors.add("timeout", format!("must be at most {}", MAX_WAIT_RELEASE_TIMEOUT));
+        }
+        errors.into_result()
+    }
+
+    fn scope(&mut self, tenant: &Tenant) {
+        self.namespace = tenant.scope(&self.namespace);
+    }
+}
+
+impl Validate for SequenceQuery {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.required("name", &self.name, MAX_ID_LEN);
+        if !(1..=MAX_SEQUENCE_BATCH).contains(&self.batch) {
+            errors.add("batch", format!("must be between 1 and {}", MAX_SEQUENCE_BATCH));
+        }
+        errors.into_result()
+    }
+
+    fn scope(&mut self, tenant: &Tenant) {
+        self.name = tenant.scope(&self.name);
+    }
+}
+
+impl Validate for RateLimitRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.required("key", &self.key, MAX_BUSINESS_ID_LEN);
+        if !(1..=MAX_RATE_LIMIT_CAPACITY).contains(&self.capacity) {
+            errors.add("capacity", format!("must be between 1 and {}", MAX_RATE_LIMIT_CAPACITY));
+        }
+        if !self.refill_per_sec.is_finite() || self.refill_per_sec <= 0.0 {
+            errors.add("refill_per_sec", "must be greater than 0");
+        }
+        if self.cost == 0 || self.cost > self.capacity {
+            errors.add("cost", "must be between 1 and capacity");
+        }
+        errors.into_result()
+    }
+
+    fn scope(&mut self, tenant: &Tenant) {
+        self.key = tenant.scope(&self.key);
+    }
+}
+
+impl Validate for ListLocksQuery {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        if let Some(namespace) = &self.namespace {
+            errors.namespace("namespace", namespace);
+        }
+        errors.optional("tag", self.tag.as_deref(), MAX_TAG_LEN);
+        errors.into_result()
+    }
+
+    fn scope(&mut self, tenant: &Tenant) {
+        self.namespace = self.namespace.as_deref().map(|namespace| tenant.scope(namespace));
+    }
+}
+
+impl Validate for StatsQuery {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        if self.top > MAX_STATS_TOP {
+            errors.add("top", format!("must be at most {}", MAX_STATS_TOP));
+        }
+        errors.into_result()
+    }
+}
+
+impl Validate for AuditQuery {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        if self.count == 0 || self.count > MAX_AUDIT_COUNT {
+            errors.add("count", format!("must be between 1 and {}", MAX_AUDIT_COUNT));
+        }
+        // Stream 记录 ID 为 `<毫秒时间戳>-<序号>`，序号可省略
+        let valid_id = |id: &str| {
+            let (ms, seq) = id.split_once('-').unwrap_or((id, "0"));
+            ms.parse::<u64>().is_ok() && seq.parse::<u64>().is_ok()
+        };
+        if self.after.as_deref().is_some_and(|after| !valid_id(after)) {
+            errors.add("after", "must be a stream entry ID such as 1704067200000-0");
+        }
+        errors.into_result()
+    }
+}
+
+impl Validate for HandoffRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.required("successor", &self.successor, MAX_ID_LEN);
+        errors.into_result()
+    }
+}
+
+impl Validate for UserStatsQuery {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        if self.top > MAX_STATS_TOP {
+            errors.add("top", format!("must be at most {}", MAX_STATS_TOP));
+        }
+        errors.into_result()
+    }
+}
+
+impl Validate for TimeSeriesQuery {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        if let Some(namespace) = &self.namespace {
+            errors.namespace("namespace", namespace);
+            if self.metric != TimeSeriesMetric::ActiveLocks {
+                errors.add("namespace", "is only supported for metric active_locks");
+            }
+        }
+        if self.step == Some(0) {
+            errors.add("step", "must be greater than 0");
+        }
+        if let (Some(from), Some(to)) = (self.from, self.to) {
+            if from > to {
+                errors.add("from", "must not be after to");
+            }
+        }
+        errors.into_result()
+    }
+
+    fn scope(&mut self, tenant: &Tenant) {
+        self.namespace = self.namespace.as_deref().map(|namespace| tenant.scope(namespace));
+    }
+}
+
+impl ValidationErrors {
+    fn import_lock(&mut self, lock_info: &LockInfo) {
+        self.required("locks.lock_id", &lock_info.lock_id, MAX_ID_LEN);
+        self.scoped_namespace("locks.namespace", &lock_info.namespace);
+        self.required("locks.business_id", &lock_info.business_id, MAX_BUSINESS_ID_LEN);
+        self.required("locks.user_id", &lock_info.user_id, MAX_ID_LEN);
+    }
+}
+
+impl Validate for LockExport {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        for lock_info in &self.locks {
+            errors.import_lock(lock_info);
+        }
+        for policy in &self.namespaces {
+            errors.scoped_namespace("namespaces.name", &policy.name);
+        }
+        errors.into_result()
+    }
+}
+
+impl Validate for ExportRecord {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        match self {
+            ExportRecord::Lock(lock_info) => errors.import_lock(lock_info),
+            ExportRecord::Namespace(policy) => errors.scoped_namespace("namespaces.name", &policy.name),
+        }
+        errors.into_result()
+    }
+}
+
+impl Validate for ChaosSettings {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        for (field, rate) in [
+            ("latency_rate", self.latency_rate),
+            ("error_rate", self.error_rate),
+            ("expire_rate", self.expire_rate),
+        ] {
+            if !(0.0..=1.0).contains(&rate) {
+                errors.add(field, "must be between 0 and 1");
+            }
+        }
+        if self.latency_ms > MAX_CHAOS_LATENCY_MS {
+            errors.add("latency_ms", format!("must be at most {}", MAX_CHAOS_LATENCY_MS));
+        }
+        errors.into_result()
+    }
+}
+
+impl Validate for AdminLoginQuery {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        if let Some(return_to) = &self.return_to {
+            // 只允许跳转到本站，防止开放重定向
+            if return_to.len() > MAX_URL_LEN {
+                errors.add("return_to", format!("must be at most {} bytes", MAX_URL_LEN));
+            } else if !return_to.starts_with('/') || return_to.starts_with("//") || return_to.contains('\\') {
+                errors.add("return_to", "must be a path on this server");
+            }
+        }
+        errors.into_result()
+    }
+}
+
+impl Validate for ImportQuery {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        Ok(())
+    }
+}
+
+impl Validate for LegacyImportQuery {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        if self.timeout == Some(0) {
+            errors.add("timeout", "must be greater than 0");
+        }
+        errors.into_result()
+    }
+}
@@ -0,0 +1,108 @@
+//! 并发的相同申请只执行一次
+//!
+//! `before_acquire` 钩子在写入锁之前调用，钩子暂停时第一个申请仍在执行中，此时到达的申请按请求内容决定是否合并。
+
+use async_trait::async_trait;
+use fe_lock_service::hooks::{HookRejection, LockHook};
+use fe_lock_service::models::{AcquireLockRequest, LockInfo};
+use fe_lock_service::testing::TestServer;
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Barrier;
+
+/// 记录调用次数，并在写入锁之前暂停
+struct Pause {
+    calls: AtomicUsize,
+    barrier: Option<Barrier>, // 设置时等待指定数量的申请同时到达，否则固定暂停一段时间
+}
+
+impl Pause {
+    fn new(barrier: Option<Barrier>) -> Arc<Self> {
+        Arc::new(Self {
+            calls: AtomicUsize::new(0),
+            barrier,
+        })
+    }
+}
+
+#[async_trait]
+impl LockHook for Pause {
+    fn name(&self) -> &str {
+        "pause"
+    }
+
+    async fn before_acquire(&self, _req: &AcquireLockRequest, _lock_info: &mut LockInfo) -> Result<(), HookRejection> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        match &self.barrier {
+            Some(barrier) => {
+                let _ = tokio::time::timeout(Duration::from_secs(5), barrier.wait()).await;
+            }
+            None => tokio::time::sleep(Duration::from_millis(300)).await,
+        }
+        Ok(())
+    }
+}
+
+async fn acquire(client: &reqwest::Client, server: &TestServer, body: Value) -> Value {
+    let response: Value = client
+        .post(format!("{}/api/lock/acquire", server.url()))
+        .json(&body)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(response["success"], true, "{}", response);
+    response["data"].clone()
+}
+
+fn body(timeout: u64) -> Value {
+    json!({
+        "namespace": "order",
+        "user_id": "u1",
+        "user_name": "张三",
+        "business_id": "1001",
+        "timeout": timeout,
+    })
+}
+
+#[tokio::test]
+async fn identical_concurrent_requests_are_coalesced() {
+    let server = TestServer::builder().start().await.unwrap();
+    let hook = Pause::new(None);
+    let _ = server.service().clone().with_hook(hook.clone());
+    let client = reqwest::Client::new();
+
+    let (first, second) = tokio::join!(
+        acquire(&client, &server, body(60)),
+        acquire(&client, &server, body(60))
+    );
+
+    assert_eq!(hook.calls.load(Ordering::SeqCst), 1);
+    assert_eq!(first["lock_id"], second["lock_id"]);
+    assert_eq!(first["version"], second["version"]);
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn requests_with_different_parameters_are_not_coalesced() {
+    let server = TestServer::builder().start().await.unwrap();
+    // 两个申请都到达钩子后才继续，合并时第二个申请不会调用钩子
+    let hook = Pause::new(Some(Barrier::new(2)));
+    let _ = server.service().clone().with_hook(hook.clone());
+    let client = reqwest::Client::new();
+
+    let (first, second) = tokio::join!(
+        acquire(&client, &server, body(60)),
+        acquire(&client, &server, body(120))
+    );
+
+    assert_eq!(hook.calls.load(Ordering::SeqCst), 2);
+    // 同一用户的第二个申请为重入，保留已持有的锁
+    assert_eq!(first["lock_id"], second["lock_id"]);
+    assert_ne!(first["version"], second["version"]);
+    server.stop().await;
+}
@@ -0,0 +1,95 @@
+//! 节点间 gossip 接口的认证和成员数上限
+
+use chrono::Utc;
+use fe_lock_service::cluster::{MemberInfo, MAX_MEMBERS};
+use fe_lock_service::config::{ClusterDiscovery, Config};
+use fe_lock_service::testing::TestServer;
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+
+const TOKEN: &str = "peer-secret";
+
+async fn start() -> TestServer {
+    let mut config = Config::from_env();
+    config.cluster_discovery = ClusterDiscovery::Static;
+    config.cluster_node_id = "fe-lock-0".to_string();
+    config.cluster_peer_token = Some(TOKEN.to_string());
+    TestServer::builder().config(config).start().await.unwrap()
+}
+
+fn member(node_id: String) -> MemberInfo {
+    MemberInfo {
+        addr: format!("{}:8080", node_id),
+        node_id,
+        incarnation: 1,
+        heartbeat: 1,
+        status: "ready".to_string(),
+        storage: "memory".to_string(),
+        shards: Vec::new(),
+        version: "0.1.0".to_string(),
+        started_at: Utc::now(),
+    }
+}
+
+async fn gossip(server: &TestServer, token: Option<&str>, members: Vec<MemberInfo>) -> reqwest::Response {
+    let mut request = reqwest::Client::new()
+        .post(format!("{}/cluster/gossip", server.url()))
+        .json(&json!({ "members": members }));
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+    request.send().await.unwrap()
+}
+
+#[tokio::test]
+async fn gossip_requires_peer_token() {
+    let server = start().await;
+
+    for token in [None, Some("wrong")] {
+        let response = gossip(&server, token, vec![member("fe-lock-9".to_string())]).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    let response = gossip(&server, Some(TOKEN), vec![member("fe-lock-1".to_string())]).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let reply: Value = response.json().await.unwrap();
+    let node_ids: Vec<&str> = reply["members"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|member| member["node_id"].as_str().unwrap())
+        .collect();
+    assert_eq!(node_ids, ["fe-lock-0"]);
+
+    // 未认证的请求中的成员没有加入成员表
+    let reply: Value = gossip(&server, Some(TOKEN), Vec::new()).await.json().await.unwrap();
+    let node_ids: Vec<&str> = reply["members"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|member| member["node_id"].as_str().unwrap())
+        .collect();
+    assert_eq!(node_ids, ["fe-lock-0", "fe-lock-1"]);
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn member_count_is_capped() {
+    let server = start().await;
+
+    let oversized = (0..=MAX_MEMBERS).map(|i| member(format!("node-{}", i))).collect();
+    let response = gossip(&server, Some(TOKEN), oversized).await;
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    // 分两次发送，成员表达到上限后不再加入新的实例
+    let first = (0..MAX_MEMBERS).map(|i| member(format!("a-{}", i))).collect();
+    assert_eq!(gossip(&server, Some(TOKEN), first).await.status(), StatusCode::OK);
+    let second = (0..10).map(|i| member(format!("b-{}", i))).collect();
+    assert_eq!(gossip(&server, Some(TOKEN), second).await.status(), StatusCode::OK);
+
+    let reply: Value = gossip(&server, Some(TOKEN), Vec::new()).await.json().await.unwrap();
+    let members = reply["members"].as_array().unwrap();
+    assert_eq!(members.len(), MAX_MEMBERS);
+    assert!(members.iter().all(|member| !member["node_id"].as_str().unwrap().starts_with("b-")));
+    server.stop().await;
+}
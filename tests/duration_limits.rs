@@ -0,0 +1,110 @@
+//! 时长参数的上限
+//!
+//! 客户端传入的时长用于计算截止时间，超过上限的值应返回参数校验错误，而不是在计算时溢出导致请求处理崩溃。
+
+use chrono::{Duration, TimeZone, Utc};
+use fe_lock_service::models::LockError;
+use fe_lock_service::testing::{MockStorage, TestServer};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+async fn post(server: &TestServer, path: &str, body: Value) -> Value {
+    reqwest::Client::new()
+        .post(format!("{}{}", server.url(), path))
+        .json(&body)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap()
+}
+
+fn acquire(extra: Value) -> Value {
+    let mut body = json!({
+        "namespace": "order",
+        "user_id": "u1",
+        "user_name": "张三",
+        "business_id": "1001",
+    });
+    body.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+    body
+}
+
+/// 超过上限的字段被拒绝，之后服务仍可正常处理申请
+async fn assert_rejected(server: &TestServer, path: &str, body: Value, field: &str) {
+    let response = post(server, path, body).await;
+    assert_eq!(response["success"], false, "{}", response);
+    assert_eq!(response["code"], LockError::ValidationFailed as i32);
+    assert!(response["message"].as_str().unwrap().contains(field), "{}", response);
+
+    let response = post(server, "/api/lock/acquire", acquire(json!({"business_id": field}))).await;
+    assert_eq!(response["success"], true, "{}", response);
+}
+
+#[tokio::test]
+async fn oversized_max_hold_is_rejected() {
+    let server = TestServer::builder().start().await.unwrap();
+
+    let body = acquire(json!({"max_hold_seconds": 100_000_000_000_000_000u64}));
+    assert_rejected(&server, "/api/lock/acquire", body, "max_hold_seconds").await;
+
+    let response = post(&server, "/api/lock/acquire", acquire(json!({"max_hold_seconds": 7200}))).await;
+    assert_eq!(response["success"], true, "{}", response);
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn oversized_due_in_is_rejected() {
+    let server = TestServer::builder().start().await.unwrap();
+
+    let body = acquire(json!({"due_in_seconds": u64::MAX}));
+    assert_rejected(&server, "/api/lock/acquire", body, "due_in_seconds").await;
+    let body = acquire(json!({"business_id": "banner.psd", "due_in_seconds": 100_000_000_000_000_000u64}));
+    assert_rejected(&server, "/api/lock/checkout", body, "due_in_seconds").await;
+
+    let body = acquire(json!({"business_id": "banner.psd", "due_in_seconds": 86400}));
+    let response = post(&server, "/api/lock/checkout", body).await;
+    assert_eq!(response["success"], true, "{}", response);
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn oversized_warn_before_is_rejected() {
+    let server = TestServer::builder().start().await.unwrap();
+
+    let body = acquire(json!({"warn_before_seconds": u64::MAX}));
+    assert_rejected(&server, "/api/lock/acquire", body, "warn_before_seconds").await;
+
+    let response = post(&server, "/api/lock/acquire", acquire(json!({"warn_before_seconds": 30}))).await;
+    assert_eq!(response["success"], true, "{}", response);
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn reservations_are_bounded_and_checked_against_the_service_clock() {
+    let storage = Arc::new(MockStorage::new());
+    let now = Utc.with_ymd_and_hms(2030, 1, 1, 0, 0, 0).unwrap();
+    storage.clock().set(now);
+    let server = TestServer::builder()
+        .storage(storage.clone())
+        .clock(storage.clock().clone())
+        .start()
+        .await
+        .unwrap();
+    let reserve = |acquire_at: chrono::DateTime<Utc>, hold_for: u64| {
+        acquire(json!({"business_id": "db", "acquire_at": acquire_at, "hold_for": hold_for}))
+    };
+
+    let body = reserve(now + Duration::hours(1), 100_000_000_000_000_000);
+    assert_rejected(&server, "/api/lock/reserve", body, "hold_for").await;
+    let body = reserve(now + Duration::days(400), 3600);
+    assert_rejected(&server, "/api/lock/reserve", body, "acquire_at").await;
+    // 系统时间早于服务的时钟，以服务的时钟为准
+    let body = reserve(now - Duration::minutes(1), 3600);
+    assert_rejected(&server, "/api/lock/reserve", body, "acquire_at").await;
+
+    let response = post(&server, "/api/lock/reserve", reserve(now + Duration::hours(1), 3600)).await;
+    assert_eq!(response["success"], true, "{}", response);
+    server.stop().await;
+}
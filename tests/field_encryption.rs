@@ -0,0 +1,116 @@
+//! 敏感字段加密的密钥轮换
+//!
+//! 以旧密钥写入的锁和记录在新密钥成为主密钥后仍可读取，`rewrap` 以新密钥重新加密后移除旧密钥不影响读取。
+
+use chrono::Utc;
+use fe_lock_service::reservation;
+use fe_lock_service::storage::fieldcrypt::{EncryptedStorage, FieldCipher};
+use fe_lock_service::storage::memory::MemoryStorage;
+use fe_lock_service::storage::LockStorage;
+use fe_lock_service::testing;
+use std::sync::Arc;
+
+const RECORD: &str = r#"{"user_id":"u1"}"#;
+
+fn cipher(key_ids: &[&str]) -> Arc<FieldCipher> {
+    let keys = key_ids
+        .iter()
+        .map(|key_id| (key_id.to_string(), key_id.repeat(32).into_bytes()[..32].to_vec()))
+        .collect();
+    Arc::new(FieldCipher::new(keys).unwrap())
+}
+
+fn encrypted(inner: &Arc<MemoryStorage>, key_ids: &[&str]) -> EncryptedStorage {
+    EncryptedStorage::new(inner.clone(), cipher(key_ids))
+}
+
+/// 写入一把带 metadata 的锁和一条预约记录，返回锁键
+async fn seed(storage: &EncryptedStorage) -> String {
+    let mut lock_info = testing::lock_info("order", "1001", "u1", 60, Utc::now());
+    lock_info.user_name = "张三".to_string();
+    lock_info.metadata.insert("phone".to_string(), "13800000000".to_string());
+    let lock_key = lock_info.get_lock_key();
+    assert!(storage.try_acquire(lock_info).await.unwrap().acquired);
+    assert!(storage
+        .swap_record(reservation::RECORD_KIND, "r1", None, Some(RECORD))
+        .await
+        .unwrap());
+    lock_key
+}
+
+async fn assert_readable(storage: &EncryptedStorage, lock_key: &str) {
+    let lock_info = storage.get_lock(lock_key).await.unwrap().unwrap();
+    assert_eq!(lock_info.user_name, "张三");
+    assert_eq!(lock_info.metadata["phone"], "13800000000");
+    let record = storage.get_record(reservation::RECORD_KIND, "r1").await.unwrap();
+    assert_eq!(record.as_deref(), Some(RECORD));
+}
+
+/// 内层存储中保存的 user_name、metadata 和记录使用的密钥
+async fn stored_key_ids(inner: &MemoryStorage, lock_key: &str) -> Vec<String> {
+    let key_id = |value: &str| {
+        value
+            .strip_prefix("enc:v1:")
+            .and_then(|sealed| sealed.split_once(':'))
+            .map(|(key_id, _)| key_id.to_string())
+            .unwrap_or_else(|| "plaintext".to_string())
+    };
+    let lock_info = inner.get_lock(lock_key).await.unwrap().unwrap();
+    let record = inner.get_record(reservation::RECORD_KIND, "r1").await.unwrap().unwrap();
+    vec![
+        key_id(&lock_info.user_name),
+        key_id(&lock_info.metadata["phone"]),
+        key_id(&record),
+    ]
+}
+
+#[tokio::test]
+async fn values_are_sealed_with_primary_key() {
+    let inner = Arc::new(MemoryStorage::new());
+    let storage = encrypted(&inner, &["k1"]);
+
+    let lock_key = seed(&storage).await;
+
+    assert_eq!(stored_key_ids(&inner, &lock_key).await, ["k1", "k1", "k1"]);
+    assert_readable(&storage, &lock_key).await;
+}
+
+#[tokio::test]
+async fn rotation_rewraps_and_old_key_can_be_removed() {
+    let inner = Arc::new(MemoryStorage::new());
+    let lock_key = seed(&encrypted(&inner, &["k1"])).await;
+    let version = inner.get_lock(&lock_key).await.unwrap().unwrap().version;
+
+    // 新密钥放在最前面，旧密钥的密文仍可读取
+    let rotated = encrypted(&inner, &["k2", "k1"]);
+    assert_readable(&rotated, &lock_key).await;
+
+    assert_eq!(rotated.rewrap().await.unwrap(), 2);
+    assert_eq!(stored_key_ids(&inner, &lock_key).await, ["k2", "k2", "k2"]);
+    assert_eq!(inner.get_lock(&lock_key).await.unwrap().unwrap().version, version);
+    assert_eq!(rotated.rewrap().await.unwrap(), 0);
+
+    assert_readable(&encrypted(&inner, &["k2"]), &lock_key).await;
+    assert!(encrypted(&inner, &["k1"]).get_lock(&lock_key).await.is_err());
+}
+
+#[tokio::test]
+async fn plaintext_written_before_encryption_is_rewrapped() {
+    let inner = Arc::new(MemoryStorage::new());
+    let mut lock_info = testing::lock_info("order", "1001", "u1", 60, Utc::now());
+    lock_info.user_name = "张三".to_string();
+    lock_info.metadata.insert("phone".to_string(), "13800000000".to_string());
+    let lock_key = lock_info.get_lock_key();
+    assert!(inner.try_acquire(lock_info).await.unwrap().acquired);
+    assert!(inner
+        .swap_record(reservation::RECORD_KIND, "r1", None, Some(RECORD))
+        .await
+        .unwrap());
+
+    let storage = encrypted(&inner, &["k1"]);
+    assert_readable(&storage, &lock_key).await;
+
+    assert_eq!(storage.rewrap().await.unwrap(), 2);
+    assert_eq!(stored_key_ids(&inner, &lock_key).await, ["k1", "k1", "k1"]);
+    assert_readable(&storage, &lock_key).await;
+}
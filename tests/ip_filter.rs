@@ -0,0 +1,120 @@
+//! 按来源地址限制公共接口和管理接口
+//!
+//! 测试客户端从 127.0.0.1 直连，经 `TRUSTED_PROXIES` 中的代理转发时按 `X-Forwarded-For` 识别客户端地址。
+
+use fe_lock_service::config::Config;
+use fe_lock_service::models::LockError;
+use fe_lock_service::testing::TestServer;
+use ipnet::IpNet;
+use serde_json::{json, Value};
+
+const ADMIN_TOKEN: &str = "admin-token";
+
+fn nets(list: &[&str]) -> Vec<IpNet> {
+    list.iter().map(|net| net.parse().unwrap()).collect()
+}
+
+fn config() -> Config {
+    let mut config = Config::from_env();
+    config.admin_token = Some(ADMIN_TOKEN.to_string());
+    config
+}
+
+async fn acquire(server: &TestServer, forwarded_for: Option<&str>) -> (u16, Value) {
+    let mut request = reqwest::Client::new()
+        .post(format!("{}/api/lock/acquire", server.url()))
+        .json(&json!({
+            "namespace": "order",
+            "user_id": "u1",
+            "user_name": "张三",
+            "business_id": "1001",
+        }));
+    if let Some(forwarded_for) = forwarded_for {
+        request = request.header("X-Forwarded-For", forwarded_for);
+    }
+    let response = request.send().await.unwrap();
+    (response.status().as_u16(), response.json().await.unwrap())
+}
+
+async fn admin_status(server: &TestServer) -> u16 {
+    reqwest::Client::new()
+        .get(format!("{}/api/admin/locks", server.url()))
+        .bearer_auth(ADMIN_TOKEN)
+        .send()
+        .await
+        .unwrap()
+        .status()
+        .as_u16()
+}
+
+async fn readyz_status(server: &TestServer) -> u16 {
+    reqwest::get(format!("{}/readyz", server.url())).await.unwrap().status().as_u16()
+}
+
+fn assert_rejected(status: u16, body: &Value) {
+    assert_eq!(status, 403, "{}", body);
+    assert_eq!(body["code"], LockError::IpNotAllowed as i32, "{}", body);
+}
+
+#[tokio::test]
+async fn public_allowlist_rejects_other_addresses() {
+    let mut config = config();
+    config.public_ip_allowlist = nets(&["10.0.0.0/8"]);
+    let server = TestServer::builder().config(config).start().await.unwrap();
+
+    let (status, body) = acquire(&server, None).await;
+    assert_rejected(status, &body);
+    // 管理接口和就绪探针不受公共接口的列表限制
+    assert_eq!(admin_status(&server).await, 200);
+    assert_eq!(readyz_status(&server).await, 200);
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn denylist_takes_precedence_over_allowlist() {
+    let mut config = config();
+    config.public_ip_allowlist = nets(&["127.0.0.0/8"]);
+    config.public_ip_denylist = nets(&["127.0.0.1/32"]);
+    let server = TestServer::builder().config(config).start().await.unwrap();
+
+    let (status, body) = acquire(&server, None).await;
+    assert_rejected(status, &body);
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn admin_lists_guard_admin_routes_only() {
+    let mut config = config();
+    config.admin_ip_denylist = nets(&["127.0.0.1/32"]);
+    let server = TestServer::builder().config(config).start().await.unwrap();
+
+    assert_eq!(admin_status(&server).await, 403);
+    let metrics = reqwest::get(format!("{}/metrics", server.url())).await.unwrap();
+    assert_eq!(metrics.status().as_u16(), 403);
+    let (status, body) = acquire(&server, None).await;
+    assert_eq!(status, 200);
+    assert_eq!(body["success"], true, "{}", body);
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn forwarded_address_is_used_only_behind_trusted_proxies() {
+    let mut config = config();
+    config.public_ip_allowlist = nets(&["10.0.0.0/8"]);
+    config.public_ip_denylist = nets(&["10.9.0.0/16"]);
+    let server = TestServer::builder().config(config.clone()).start().await.unwrap();
+
+    // 直连地址不是受信任的代理时忽略 X-Forwarded-For
+    let (status, body) = acquire(&server, Some("10.1.2.3")).await;
+    assert_rejected(status, &body);
+    server.stop().await;
+
+    config.trusted_proxies = nets(&["127.0.0.1/32"]);
+    let server = TestServer::builder().config(config).start().await.unwrap();
+    let (status, body) = acquire(&server, Some("10.1.2.3")).await;
+    assert_eq!(status, 200);
+    assert_eq!(body["success"], true, "{}", body);
+    let (status, body) = acquire(&server, Some("10.9.0.7")).await;
+    assert_rejected(status, &body);
+    server.stop().await;
+}
@@ -0,0 +1,70 @@
+//! 内存存储的并发申请和容量上限
+//!
+//! 同一把锁的并发申请只有一个成功，已满时为腾出空间扫描过期锁的频率受限。
+
+use chrono::{TimeZone, Utc};
+use fe_lock_service::clock::Clock;
+use fe_lock_service::config::MemoryEviction;
+use fe_lock_service::storage::memory::{MemoryStorage, StorageFull};
+use fe_lock_service::storage::LockStorage;
+use fe_lock_service::testing::{self, ManualClock};
+use std::sync::Arc;
+use std::time::Duration;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn concurrent_acquires_grant_one_holder() {
+    let storage = Arc::new(MemoryStorage::new());
+    let lock_infos: Vec<_> = (0..32)
+        .map(|i| testing::lock_info("order", "1001", &format!("u{}", i), 60, Utc::now()))
+        .collect();
+
+    let tasks = lock_infos.iter().cloned().map(|lock_info| {
+        let storage = storage.clone();
+        tokio::spawn(async move { storage.try_acquire(lock_info).await.unwrap().acquired })
+    });
+    let acquired: Vec<bool> = futures_util::future::join_all(tasks)
+        .await
+        .into_iter()
+        .map(Result::unwrap)
+        .collect();
+
+    assert_eq!(acquired.iter().filter(|acquired| **acquired).count(), 1);
+    let winner = &lock_infos[acquired.iter().position(|acquired| *acquired).unwrap()];
+    let stored = storage.get_lock(&winner.get_lock_key()).await.unwrap().unwrap();
+    assert_eq!(stored.lock_id, winner.lock_id);
+    for lock_info in &lock_infos {
+        let found = storage.get_lock_by_id(&lock_info.lock_id).await.unwrap();
+        assert_eq!(found.is_some(), lock_info.lock_id == winner.lock_id);
+    }
+    assert_eq!(storage.len(), 1);
+}
+
+#[tokio::test]
+async fn full_store_evicts_expired_locks_at_most_once_per_interval() {
+    let clock = Arc::new(ManualClock::new(Utc.with_ymd_and_hms(2030, 1, 1, 0, 0, 0).unwrap()));
+    let storage = MemoryStorage::new()
+        .with_max_locks(2, MemoryEviction::EvictExpired)
+        .with_clock(clock.clone());
+    let acquire = |business_id: &str| {
+        storage.try_acquire(testing::lock_info("order", business_id, "u1", 10, clock.now()))
+    };
+
+    assert!(acquire("a").await.unwrap().acquired);
+    assert!(acquire("b").await.unwrap().acquired);
+    clock.advance(Duration::from_secs(11));
+
+    // 已满时清理过期的锁后写入
+    assert!(acquire("c").await.unwrap().acquired);
+    assert_eq!(storage.evicted(), 2);
+    assert!(acquire("d").await.unwrap().acquired);
+
+    // c 和 d 已过期，但距上次清理不足一秒，直接拒绝
+    clock.advance(Duration::from_secs(11));
+    let error = acquire("e").await.unwrap_err();
+    assert!(error.downcast_ref::<StorageFull>().is_some());
+    assert_eq!(storage.evicted(), 2);
+
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    assert!(acquire("e").await.unwrap().acquired);
+    assert_eq!(storage.evicted(), 4);
+}
@@ -0,0 +1,358 @@
+//! 管理接口的 OIDC 登录
+//!
+//! 测试中启动一个模拟的身份提供方，提供 OpenID 元数据、JWKS 和令牌接口，用 `tests/data/oidc_signing_key.pk8` 签发 RS256 的 ID Token。
+//! 登录状态和会话保存在锁存储中，回调和后续请求可以发往共享存储的任一实例。
+
+use actix_web::dev::ServerHandle;
+use actix_web::{web, App, HttpResponse, HttpServer};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use fe_lock_service::clock::Clock;
+use fe_lock_service::config::{AdminRole, Config};
+use fe_lock_service::models::LockError;
+use fe_lock_service::oidc;
+use fe_lock_service::storage::LockStorage;
+use fe_lock_service::testing::{ManualClock, MockStorage, TestServer};
+use parking_lot::Mutex;
+use reqwest::header::{COOKIE, LOCATION, SET_COOKIE};
+use reqwest::{Response, StatusCode, Url};
+use ring::rand::SystemRandom;
+use ring::signature::{RsaKeyPair, RsaPublicKeyComponents, RSA_PKCS1_SHA256};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+const CLIENT_ID: &str = "fe-lock-admin";
+const SIGNING_KEY: &[u8] = include_bytes!("data/oidc_signing_key.pk8");
+
+/// 模拟的身份提供方，令牌接口按授权码返回预先登记的声明
+struct Provider {
+    issuer: String,
+    key: RsaKeyPair,
+    codes: Mutex<HashMap<String, Value>>,
+}
+
+impl Provider {
+    async fn start() -> (Arc<Self>, ServerHandle) {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let provider = Arc::new(Self {
+            issuer: format!("http://{}", listener.local_addr().unwrap()),
+            key: RsaKeyPair::from_pkcs8(SIGNING_KEY).unwrap(),
+            codes: Mutex::new(HashMap::new()),
+        });
+        let data = web::Data::new(provider.clone());
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(data.clone())
+                .route("/.well-known/openid-configuration", web::get().to(discovery))
+                .route("/jwks", web::get().to(jwks))
+                .route("/token", web::post().to(token))
+        })
+        .workers(1)
+        .listen(listener)
+        .unwrap()
+        .run();
+        let handle = server.handle();
+        tokio::spawn(server);
+        (provider, handle)
+    }
+
+    /// 登记授权码 code 换取的 ID Token 声明
+    fn issue(&self, code: &str, claims: Value) {
+        self.codes.lock().insert(code.to_string(), claims);
+    }
+
+    fn sign(&self, claims: &Value) -> String {
+        let header = URL_SAFE_NO_PAD.encode(json!({"alg": "RS256", "typ": "JWT", "kid": "test"}).to_string());
+        let signed = format!("{}.{}", header, URL_SAFE_NO_PAD.encode(claims.to_string()));
+        let mut signature = vec![0; self.key.public().modulus_len()];
+        self.key
+            .sign(&RSA_PKCS1_SHA256, &SystemRandom::new(), signed.as_bytes(), &mut signature)
+            .unwrap();
+        format!("{}.{}", signed, URL_SAFE_NO_PAD.encode(signature))
+    }
+}
+
+async fn discovery(provider: web::Data<Arc<Provider>>) -> HttpResponse {
+    HttpResponse::Ok().json(json!({
+        "issuer": provider.issuer,
+        "authorization_endpoint": format!("{}/authorize", provider.issuer),
+        "token_endpoint": format!("{}/token", provider.issuer),
+        "jwks_uri": format!("{}/jwks", provider.issuer),
+    }))
+}
+
+async fn jwks(provider: web::Data<Arc<Provider>>) -> HttpResponse {
+    let public = RsaPublicKeyComponents::<Vec<u8>>::from(provider.key.public());
+    HttpResponse::Ok().json(json!({
+        "keys": [{"kty": "RSA", "kid": "test", "n": URL_SAFE_NO_PAD.encode(public.n), "e": URL_SAFE_NO_PAD.encode(public.e)}],
+    }))
+}
+
+async fn token(provider: web::Data<Arc<Provider>>, form: web::Form<HashMap<String, String>>) -> HttpResponse {
+    let claims = form
+        .get("code")
+        .filter(|_| form.get("client_id").map(String::as_str) == Some(CLIENT_ID))
+        .and_then(|code| provider.codes.lock().remove(code));
+    match claims {
+        Some(claims) => HttpResponse::Ok().json(json!({ "id_token": provider.sign(&claims) })),
+        None => HttpResponse::BadRequest().json(json!({ "error": "invalid_grant" })),
+    }
+}
+
+fn config(issuer: &str) -> Config {
+    let mut config = Config::from_env();
+    config.admin_token = None;
+    config.oidc_issuer = Some(issuer.to_string());
+    config.oidc_client_id = CLIENT_ID.to_string();
+    config.oidc_client_secret = Some("client-secret".to_string());
+    config.oidc_redirect_url = Some("http://lock.example.com/api/admin/auth/callback".to_string());
+    config.oidc_role_mapping = vec![("sre".to_string(), AdminRole::Admin), ("dev".to_string(), AdminRole::Viewer)];
+    config.oidc_session_ttl = 3600;
+    config
+}
+
+/// 不跟随重定向的客户端，用于检查跳转地址
+fn client() -> reqwest::Client {
+    reqwest::Client::builder().redirect(reqwest::redirect::Policy::none()).build().unwrap()
+}
+
+fn set_cookies(response: &Response) -> HashMap<String, String> {
+    response
+        .headers()
+        .get_all(SET_COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok()?.split(';').next()?.split_once('='))
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect()
+}
+
+/// 发起登录，返回 state 和 nonce
+async fn begin(server: &TestServer, return_to: Option<&str>) -> (String, String) {
+    let mut request = client().get(format!("{}/api/admin/auth/login", server.url()));
+    if let Some(return_to) = return_to {
+        request = request.query(&[("return_to", return_to)]);
+    }
+    let response = request.send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::FOUND);
+    let location = Url::parse(response.headers()[LOCATION].to_str().unwrap()).unwrap();
+    let params: HashMap<_, _> = location.query_pairs().into_owned().collect();
+    assert_eq!(params["client_id"], CLIENT_ID);
+    assert_eq!(set_cookies(&response)[oidc::LOGIN_COOKIE], params["state"]);
+    (params["state"].clone(), params["nonce"].clone())
+}
+
+async fn callback(server: &TestServer, state: &str, code: &str, login_cookie: Option<&str>) -> Response {
+    let mut request = client()
+        .get(format!("{}/api/admin/auth/callback", server.url()))
+        .query(&[("state", state), ("code", code)]);
+    if let Some(login_cookie) = login_cookie {
+        request = request.header(COOKIE, format!("{}={}", oidc::LOGIN_COOKIE, login_cookie));
+    }
+    request.send().await.unwrap()
+}
+
+fn claims(provider: &Provider, clock: &ManualClock, nonce: &str, groups: &[&str]) -> Value {
+    let now = clock.now().timestamp();
+    json!({
+        "iss": provider.issuer,
+        "aud": CLIENT_ID,
+        "sub": "alice",
+        "name": "Alice",
+        "groups": groups,
+        "nonce": nonce,
+        "iat": now,
+        "exp": now + 300,
+    })
+}
+
+async fn assert_login_failed(response: Response, code: LockError) {
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["success"], false, "{}", body);
+    assert_eq!(body["code"], code as i32, "{}", body);
+}
+
+#[tokio::test]
+async fn login_session_is_shared_through_storage() {
+    let (provider, provider_handle) = Provider::start().await;
+    let storage = Arc::new(MockStorage::new());
+    let clock = storage.clock();
+    let mut servers = Vec::new();
+    for _ in 0..2 {
+        let server = TestServer::builder()
+            .config(config(&provider.issuer))
+            .storage(storage.clone())
+            .clock(clock.clone())
+            .start()
+            .await
+            .unwrap();
+        servers.push(server);
+    }
+
+    // 在一个实例上发起登录，回调发往另一个实例
+    let (state, nonce) = begin(&servers[0], Some("/ui/locks")).await;
+    provider.issue("code-1", claims(&provider, clock, &nonce, &["dev", "sre"]));
+    let response = callback(&servers[1], &state, "code-1", Some(&state)).await;
+    assert_eq!(response.status(), StatusCode::FOUND);
+    assert_eq!(response.headers()[LOCATION], "/ui/locks");
+    let cookies = set_cookies(&response);
+    let session_id = cookies[oidc::SESSION_COOKIE].clone();
+    let csrf_token = cookies[oidc::CSRF_COOKIE].clone();
+    assert_eq!(cookies[oidc::LOGIN_COOKIE], "");
+
+    // 存储中的会话以会话 ID 的摘要为键
+    let records = storage.list_records(oidc::SESSION_RECORD_KIND).await.unwrap();
+    assert_eq!(records.len(), 1);
+    assert_ne!(records[0].0, session_id);
+
+    let session_cookie = format!("{}={}", oidc::SESSION_COOKIE, session_id);
+    let session: Value = client()
+        .get(format!("{}/api/admin/auth/session", servers[0].url()))
+        .header(COOKIE, &session_cookie)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(session["data"]["subject"], "alice", "{}", session);
+    assert_eq!(session["data"]["role"], "admin", "{}", session);
+    assert_eq!(session["data"]["csrf_token"], csrf_token.as_str(), "{}", session);
+
+    // 修改类请求需携带 CSRF 令牌
+    let logout = |csrf: Option<String>| {
+        let mut request = client()
+            .post(format!("{}/api/admin/auth/logout", servers[1].url()))
+            .header(COOKIE, &session_cookie);
+        if let Some(csrf) = csrf {
+            request = request.header(oidc::CSRF_HEADER, csrf);
+        }
+        request.send()
+    };
+    let response = logout(None).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response: Value = logout(Some(csrf_token)).await.unwrap().json().await.unwrap();
+    assert_eq!(response["data"], true, "{}", response);
+
+    let response = client()
+        .get(format!("{}/api/admin/auth/session", servers[0].url()))
+        .header(COOKIE, &session_cookie)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    for server in servers {
+        server.stop().await;
+    }
+    provider_handle.stop(true).await;
+}
+
+#[tokio::test]
+async fn callback_requires_the_matching_login_state() {
+    let (provider, provider_handle) = Provider::start().await;
+    let storage = Arc::new(MockStorage::new());
+    let clock = storage.clock();
+    let server = TestServer::builder()
+        .config(config(&provider.issuer))
+        .storage(storage.clone())
+        .clock(clock.clone())
+        .start()
+        .await
+        .unwrap();
+
+    let (state, nonce) = begin(&server, None).await;
+    provider.issue("code-1", claims(&provider, clock, &nonce, &["sre"]));
+    // 没有或不一致的登录状态 Cookie 不消耗 state
+    assert_login_failed(callback(&server, &state, "code-1", None).await, LockError::LoginFailed).await;
+    let (other_state, _) = begin(&server, None).await;
+    assert_login_failed(callback(&server, &state, "code-1", Some(&other_state)).await, LockError::LoginFailed).await;
+
+    let response = callback(&server, &state, "code-1", Some(&state)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["data"]["subject"], "alice", "{}", body);
+
+    // 同一 state 只能完成一次登录
+    provider.issue("code-2", claims(&provider, clock, &nonce, &["sre"]));
+    assert_login_failed(callback(&server, &state, "code-2", Some(&state)).await, LockError::LoginFailed).await;
+
+    // ID Token 的 nonce 需与发起登录时的一致
+    let (state, _) = begin(&server, None).await;
+    provider.issue("code-3", claims(&provider, clock, "other-nonce", &["sre"]));
+    assert_login_failed(callback(&server, &state, "code-3", Some(&state)).await, LockError::LoginFailed).await;
+
+    // 用户组没有映射的角色时拒绝
+    let (state, nonce) = begin(&server, None).await;
+    provider.issue("code-4", claims(&provider, clock, &nonce, &["marketing"]));
+    assert_login_failed(callback(&server, &state, "code-4", Some(&state)).await, LockError::Forbidden).await;
+
+    // 发起登录超过 LOGIN_TTL 后 state 失效
+    let (state, nonce) = begin(&server, None).await;
+    clock.advance(Duration::from_secs(oidc::LOGIN_TTL as u64));
+    provider.issue("code-5", claims(&provider, clock, &nonce, &["sre"]));
+    assert_login_failed(callback(&server, &state, "code-5", Some(&state)).await, LockError::LoginFailed).await;
+
+    server.stop().await;
+    provider_handle.stop(true).await;
+}
+
+#[tokio::test]
+async fn return_to_must_be_a_path_on_this_server() {
+    let (provider, provider_handle) = Provider::start().await;
+    let server = TestServer::builder().config(config(&provider.issuer)).start().await.unwrap();
+
+    for return_to in ["https://evil.example.com/", "//evil.example.com/", "/\\evil.example.com", "ui/locks"] {
+        let body: Value = client()
+            .get(format!("{}/api/admin/auth/login", server.url()))
+            .query(&[("return_to", return_to)])
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(body["code"], LockError::ValidationFailed as i32, "{}: {}", return_to, body);
+    }
+    begin(&server, Some("/ui/locks?tab=mine")).await;
+
+    server.stop().await;
+    provider_handle.stop(true).await;
+}
+
+#[tokio::test]
+async fn pending_logins_are_capped() {
+    let (provider, provider_handle) = Provider::start().await;
+    let storage = Arc::new(MockStorage::new());
+    let clock = storage.clock();
+    let server = TestServer::builder()
+        .config(config(&provider.issuer))
+        .storage(storage.clone())
+        .clock(clock.clone())
+        .start()
+        .await
+        .unwrap();
+
+    for _ in 0..oidc::MAX_PENDING_LOGINS {
+        begin(&server, None).await;
+    }
+    let body: Value = client()
+        .get(format!("{}/api/admin/auth/login", server.url()))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["code"], LockError::Overloaded as i32, "{}", body);
+
+    // 过期的登录在发起新登录时清理
+    clock.advance(Duration::from_secs(oidc::LOGIN_TTL as u64));
+    begin(&server, None).await;
+    let pending = storage.list_records(oidc::PENDING_RECORD_KIND).await.unwrap();
+    assert_eq!(pending.len(), 1);
+
+    server.stop().await;
+    provider_handle.stop(true).await;
+}
@@ -0,0 +1,85 @@
+//! 响应中的服务端时间
+//!
+//! `server_time` 取服务的时钟，与处理请求时判断过期使用的时间一致，包括中间件直接返回的错误响应。
+
+use chrono::{DateTime, TimeZone, Utc};
+use fe_lock_service::config::Config;
+use fe_lock_service::testing::{MockStorage, TestServer};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+async fn start(config: Config) -> (TestServer, DateTime<Utc>) {
+    let storage = Arc::new(MockStorage::new());
+    let now = Utc.with_ymd_and_hms(2030, 1, 1, 0, 0, 0).unwrap();
+    storage.clock().set(now);
+    let server = TestServer::builder()
+        .config(config)
+        .storage(storage.clone())
+        .clock(storage.clock().clone())
+        .start()
+        .await
+        .unwrap();
+    (server, now)
+}
+
+fn server_time(response: &Value) -> DateTime<Utc> {
+    response["server_time"].as_str().unwrap().parse().unwrap()
+}
+
+#[tokio::test]
+async fn responses_use_the_service_clock() {
+    let (server, now) = start(Config::from_env()).await;
+    let client = reqwest::Client::new();
+    let body = json!({"namespace": "order", "user_id": "u1", "user_name": "张三", "business_id": "1001"});
+
+    let response: Value = client
+        .post(format!("{}/api/lock/acquire", server.url()))
+        .json(&body)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(response["success"], true, "{}", response);
+    assert_eq!(server_time(&response), now);
+
+    let response: Value = client
+        .post(format!("{}/api/lock/acquire", server.url()))
+        .header("Accept", "application/problem+json")
+        .json(&json!({
+            "namespace": "order",
+            "user_id": "u1",
+            "user_name": "张三",
+            "business_id": "1002",
+            "max_hold_seconds": u64::MAX,
+        }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(response["code"], 1000, "{}", response);
+    assert_eq!(server_time(&response), now);
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn middleware_rejections_use_the_service_clock() {
+    let mut config = Config::from_env();
+    config.public_ip_denylist = vec!["127.0.0.1/32".parse().unwrap()];
+    let (server, now) = start(config).await;
+
+    let response: Value = reqwest::Client::new()
+        .get(format!("{}/api/lock/list", server.url()))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(response["success"], false, "{}", response);
+    assert_eq!(server_time(&response), now);
+    server.stop().await;
+}
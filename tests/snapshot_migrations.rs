@@ -0,0 +1,249 @@
+//! 旧版本快照文件的加载
+//!
+//! bincode 不是自描述格式，旧版本写入的文件必须按当时的字段布局读取。这里按各版本的布局逐个字段编码
+//! （bincode 中结构体即各字段编码的拼接），不依赖 `snapshot` 中的旧结构，验证升级后已有字段保留、新增字段取默认值。
+
+use chrono::{DateTime, TimeZone, Utc};
+use fe_lock_service::config::PersistFormat;
+use fe_lock_service::models::{ClientContext, LockInfo, NamespacePolicy, PreemptionNotice};
+use fe_lock_service::storage::snapshot::{self, SnapshotData};
+use fe_lock_service::testing;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+fn locked_at() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2024, 5, 1, 8, 0, 0).unwrap()
+}
+
+fn notice() -> PreemptionNotice {
+    PreemptionNotice {
+        user_id: "admin".to_string(),
+        user_name: "管理员".to_string(),
+        deadline: Utc.with_ymd_and_hms(2024, 5, 1, 8, 5, 0).unwrap(),
+    }
+}
+
+fn client() -> ClientContext {
+    ClientContext {
+        ip: Some("10.0.3.17".to_string()),
+        user_agent: None,
+        hostname: Some("batch-worker-03".to_string()),
+    }
+}
+
+/// 依次追加字段的 bincode 编码
+#[derive(Default)]
+struct Fields(Vec<u8>);
+
+impl Fields {
+    fn push<T: Serialize>(mut self, value: T) -> Self {
+        self.0.extend(bincode::serialize(&value).unwrap());
+        self
+    }
+
+    fn push_vec(mut self, items: Vec<Fields>) -> Self {
+        self.0.extend(bincode::serialize(&(items.len() as u64)).unwrap());
+        for item in items {
+            self.0.extend(item.0);
+        }
+        self
+    }
+}
+
+/// version 版本中 LockInfo 的布局
+fn legacy_lock(version: u8) -> Fields {
+    let mut fields = Fields::default()
+        .push("lock-1")
+        .push("order")
+        .push("u1")
+        .push("张三")
+        .push("1001")
+        .push(30u64)
+        .push(locked_at())
+        .push(locked_at());
+    if version >= 3 {
+        fields = fields.push(BTreeMap::from([("source".to_string(), "web".to_string())]));
+    }
+    if version >= 4 {
+        fields = fields.push(vec!["urgent".to_string()]);
+    }
+    if version >= 5 {
+        fields = fields.push(Some(600u64));
+    }
+    if version >= 6 {
+        fields = fields.push(Some(notice()));
+    }
+    if version >= 8 {
+        fields = fields.push(7u64);
+    }
+    if version >= 10 {
+        fields = fields.push(Some("月结"));
+    }
+    if version >= 11 {
+        fields = fields.push(Some(client()));
+    }
+    fields
+}
+
+/// version 版本中 NamespacePolicy 的布局
+fn legacy_policy(version: u8) -> Fields {
+    let mut fields = Fields::default()
+        .push("order")
+        .push(Some(60u64))
+        .push(Some(3600u64))
+        .push(Some(1000u64))
+        .push(false);
+    if version >= 7 {
+        fields = fields.push(true);
+    }
+    if version >= 12 {
+        fields = fields.push(true);
+    }
+    fields
+}
+
+fn legacy_snapshot(version: u8) -> Fields {
+    if version == 1 {
+        return Fields::default().push_vec(vec![legacy_lock(1)]);
+    }
+    let mut fields = Fields::default()
+        .push_vec(vec![legacy_lock(version)])
+        .push_vec(vec![legacy_policy(version)]);
+    if version >= 9 {
+        fields = fields.push(BTreeMap::from([("invoice".to_string(), 42u64)]));
+    }
+    fields
+}
+
+/// 二进制快照文件：魔数、版本号和 zstd 压缩的 bincode 编码
+fn file(version: u8, raw: &[u8]) -> Vec<u8> {
+    let mut bytes = b"FLCK".to_vec();
+    bytes.push(version);
+    bytes.extend(zstd::encode_all(raw, 3).unwrap());
+    bytes
+}
+
+fn decode(bytes: &[u8]) -> SnapshotData {
+    let (data, format) = snapshot::decode(bytes).unwrap();
+    assert_eq!(format, PersistFormat::Bincode);
+    data
+}
+
+fn assert_lock(lock: &LockInfo, version: u8) {
+    assert_eq!(lock.lock_id, "lock-1");
+    assert_eq!(lock.namespace, "order");
+    assert_eq!(lock.user_id, "u1");
+    assert_eq!(lock.user_name, "张三");
+    assert_eq!(lock.business_id, "1001");
+    assert_eq!(lock.timeout, 30);
+    assert_eq!(lock.locked_at, locked_at());
+    assert_eq!(lock.last_heartbeat, locked_at());
+    assert_eq!(lock.metadata.get("source").map(String::as_str), (version >= 3).then_some("web"));
+    assert_eq!(lock.tags.is_empty(), version < 4);
+    assert_eq!(lock.max_hold_seconds, (version >= 5).then_some(600));
+    assert_eq!(
+        lock.preemption.as_ref().map(|notice| notice.user_id.as_str()),
+        (version >= 6).then_some("admin")
+    );
+    assert_eq!(lock.version, if version >= 8 { 7 } else { 1 });
+    assert_eq!(lock.reason.as_deref(), (version >= 10).then_some("月结"));
+    assert_eq!(lock.client, (version >= 11).then(client));
+    assert_eq!(lock.grace_seconds, 0);
+    assert_eq!(lock.due_at, None);
+}
+
+fn assert_policy(policy: &NamespacePolicy, version: u8) {
+    assert_eq!(policy.name, "order");
+    assert_eq!(policy.default_timeout, Some(60));
+    assert_eq!(policy.max_timeout, Some(3600));
+    assert_eq!(policy.max_locks, Some(1000));
+    assert!(!policy.allow_queue);
+    assert_eq!(policy.hierarchical, version >= 7);
+    assert_eq!(policy.advisory, version >= 12);
+    assert!(policy.event_routes.is_empty());
+    assert_eq!(policy.freeze, None);
+}
+
+#[test]
+fn legacy_versions_are_upgraded() {
+    for version in 1..=12u8 {
+        let data = decode(&file(version, &legacy_snapshot(version).0));
+
+        assert_eq!(data.locks.len(), 1, "version {}", version);
+        assert_lock(&data.locks[0], version);
+        if version == 1 {
+            assert!(data.namespaces.is_empty());
+        } else {
+            assert_eq!(data.namespaces.len(), 1, "version {}", version);
+            assert_policy(&data.namespaces[0], version);
+        }
+        let expected_sequences = match version {
+            9.. => BTreeMap::from([("invoice".to_string(), 42)]),
+            _ => BTreeMap::new(),
+        };
+        assert_eq!(data.sequences, expected_sequences, "version {}", version);
+        assert!(data.records.is_empty());
+    }
+}
+
+#[test]
+fn version_13_keeps_current_layout_without_records() {
+    let mut lock = testing::lock_info("order", "1001", "u1", 30, locked_at());
+    lock.grace_seconds = 5;
+    let raw = Fields::default()
+        .push(vec![lock.clone()])
+        .push(Vec::<NamespacePolicy>::new())
+        .push(BTreeMap::from([("invoice".to_string(), 3u64)]));
+
+    let data = decode(&file(13, &raw.0));
+
+    assert_eq!(data.locks.len(), 1);
+    assert_eq!(data.locks[0].lock_id, lock.lock_id);
+    assert_eq!(data.locks[0].grace_seconds, 5);
+    assert_eq!(data.sequences.get("invoice"), Some(&3));
+    assert!(data.records.is_empty());
+}
+
+#[test]
+fn current_version_round_trips() {
+    let data = SnapshotData {
+        locks: vec![testing::lock_info("order", "1001", "u1", 30, locked_at())],
+        namespaces: Vec::new(),
+        sequences: BTreeMap::from([("invoice".to_string(), 9)]),
+        records: BTreeMap::from([(
+            "reservation".to_string(),
+            BTreeMap::from([("r1".to_string(), "{}".to_string())]),
+        )]),
+    };
+
+    for format in [PersistFormat::Bincode, PersistFormat::Json] {
+        let bytes = snapshot::encode(&data, &format).unwrap();
+        let (decoded, decoded_format) = snapshot::decode(&bytes).unwrap();
+        assert_eq!(decoded_format, format);
+        assert_eq!(decoded.locks[0].lock_id, data.locks[0].lock_id);
+        assert_eq!(decoded.sequences, data.sequences);
+        assert_eq!(decoded.records, data.records);
+    }
+}
+
+#[test]
+fn json_lock_array_is_accepted() {
+    let lock = testing::lock_info("order", "1001", "u1", 30, locked_at());
+    let bytes = serde_json::to_vec(&vec![lock.clone()]).unwrap();
+
+    let (data, format) = snapshot::decode(&bytes).unwrap();
+
+    assert_eq!(format, PersistFormat::Json);
+    assert_eq!(data.locks.len(), 1);
+    assert_eq!(data.locks[0].lock_id, lock.lock_id);
+    assert!(data.namespaces.is_empty());
+}
+
+#[test]
+fn unknown_version_is_rejected() {
+    let bytes = file(99, &legacy_snapshot(12).0);
+
+    let error = snapshot::decode(&bytes).unwrap_err();
+
+    assert!(error.to_string().contains("Unsupported snapshot version 99"));
+}
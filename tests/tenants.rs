@@ -0,0 +1,196 @@
+//! 多租户的隔离、JWT 过期和配额
+//!
+//! 租户只能访问本租户的锁，其他租户的锁视为不存在；JWT 的 `exp` 按服务时钟判断；同时持有的锁数量受配额限制。
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use fe_lock_service::clock::Clock;
+use fe_lock_service::config::{Config, TenantKey};
+use fe_lock_service::models::LockError;
+use fe_lock_service::testing::{MockStorage, TestServer};
+use ring::hmac;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+
+const JWT_SECRET: &str = "tenant-jwt-secret";
+
+fn tenant(tenant_id: &str, api_key: &str, max_locks: Option<u64>) -> TenantKey {
+    TenantKey {
+        tenant_id: tenant_id.to_string(),
+        api_key: api_key.to_string(),
+        max_locks,
+    }
+}
+
+fn config(tenants: Vec<TenantKey>) -> Config {
+    let mut config = Config::from_env();
+    config.tenants = tenants;
+    config.tenant_jwt_secret = Some(JWT_SECRET.to_string());
+    config.tenant_max_locks = 0;
+    config
+}
+
+/// 按 HS256 签名的租户 JWT
+fn jwt(claims: Value) -> String {
+    let header = URL_SAFE_NO_PAD.encode(json!({"alg": "HS256", "typ": "JWT"}).to_string());
+    let claims = URL_SAFE_NO_PAD.encode(claims.to_string());
+    let signed = format!("{}.{}", header, claims);
+    let key = hmac::Key::new(hmac::HMAC_SHA256, JWT_SECRET.as_bytes());
+    let signature = URL_SAFE_NO_PAD.encode(hmac::sign(&key, signed.as_bytes()).as_ref());
+    format!("{}.{}", signed, signature)
+}
+
+async fn post(server: &TestServer, token: &str, path: &str, body: Value) -> (u16, Value) {
+    let response = reqwest::Client::new()
+        .post(format!("{}/api{}", server.url(), path))
+        .bearer_auth(token)
+        .json(&body)
+        .send()
+        .await
+        .unwrap();
+    (response.status().as_u16(), response.json().await.unwrap())
+}
+
+async fn get(server: &TestServer, token: &str, path: &str) -> Value {
+    reqwest::Client::new()
+        .get(format!("{}/api{}", server.url(), path))
+        .bearer_auth(token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap()
+}
+
+fn acquire_body(business_id: &str) -> Value {
+    json!({
+        "namespace": "order",
+        "user_id": "u1",
+        "user_name": "张三",
+        "business_id": business_id,
+    })
+}
+
+#[tokio::test]
+async fn tenants_cannot_see_each_others_locks() {
+    let server = TestServer::builder()
+        .config(config(vec![tenant("acme", "acme-key", None), tenant("globex", "globex-key", None)]))
+        .start()
+        .await
+        .unwrap();
+
+    let (_, acquired) = post(&server, "acme-key", "/lock/acquire", acquire_body("1001")).await;
+    assert_eq!(acquired["success"], true, "{}", acquired);
+    let lock_id = acquired["data"]["lock_id"].as_str().unwrap().to_string();
+
+    let (_, heartbeat) = post(&server, "globex-key", "/lock/heartbeat", json!({ "lock_id": lock_id })).await;
+    assert_eq!(heartbeat["code"], LockError::HeartbeatLockNotFound as i32, "{}", heartbeat);
+    let (_, release) = post(&server, "globex-key", "/lock/release", json!({ "lock_id": lock_id })).await;
+    assert_eq!(release["code"], LockError::ReleaseLockNotFound as i32, "{}", release);
+
+    let status = get(&server, "acme-key", "/lock/status?namespace=order&business_id=1001").await;
+    assert_eq!(status["data"]["lock"]["namespace"], "acme/order", "{}", status);
+    let status = get(&server, "globex-key", "/lock/status?namespace=order&business_id=1001").await;
+    assert_eq!(status["data"]["locked"], false, "{}", status);
+    let list = get(&server, "globex-key", "/lock/list").await;
+    assert_eq!(list["data"].as_array().map(Vec::len), Some(0), "{}", list);
+
+    // 同名的命名空间和业务键在另一个租户下是不同的锁
+    let (_, other) = post(&server, "globex-key", "/lock/acquire", acquire_body("1001")).await;
+    assert_eq!(other["success"], true, "{}", other);
+    assert_ne!(other["data"]["lock_id"], lock_id.as_str(), "{}", other);
+
+    let (_, heartbeat) = post(&server, "acme-key", "/lock/heartbeat", json!({ "lock_id": lock_id })).await;
+    assert_eq!(heartbeat["success"], true, "{}", heartbeat);
+    let (_, release) = post(&server, "acme-key", "/lock/release", json!({ "lock_id": lock_id })).await;
+    assert_eq!(release["success"], true, "{}", release);
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn missing_or_unknown_credentials_are_rejected() {
+    let server = TestServer::builder()
+        .config(config(vec![tenant("acme", "acme-key", None)]))
+        .start()
+        .await
+        .unwrap();
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/api/lock/acquire", server.url()))
+        .json(&acquire_body("1001"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+
+    let (status, body) = post(&server, "wrong-key", "/lock/acquire", acquire_body("1001")).await;
+    assert_eq!(status, 401);
+    assert_eq!(body["code"], LockError::Unauthorized as i32, "{}", body);
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn tenant_jwt_expires_on_the_service_clock() {
+    let storage = Arc::new(MockStorage::new());
+    let clock = storage.clock();
+    let server = TestServer::builder()
+        .config(config(Vec::new()))
+        .storage(storage.clone())
+        .clock(clock.clone())
+        .start()
+        .await
+        .unwrap();
+
+    let exp = clock.now().timestamp() + 60;
+    let token = jwt(json!({ "tenant_id": "acme", "exp": exp }));
+    let (status, acquired) = post(&server, &token, "/lock/acquire", acquire_body("1001")).await;
+    assert_eq!(status, 200);
+    assert_eq!(acquired["success"], true, "{}", acquired);
+    let status = get(&server, &token, "/lock/status?namespace=order&business_id=1001").await;
+    assert_eq!(status["data"]["lock"]["namespace"], "acme/order", "{}", status);
+
+    clock.advance(Duration::from_secs(60));
+    let (status, body) = post(&server, &token, "/lock/acquire", acquire_body("1002")).await;
+    assert_eq!(status, 401);
+    assert_eq!(body["code"], LockError::Unauthorized as i32, "{}", body);
+
+    // 其他密钥签名的 JWT 不被接受
+    let forged = {
+        let mut parts: Vec<String> = jwt(json!({ "tenant_id": "acme" })).split('.').map(str::to_string).collect();
+        let key = hmac::Key::new(hmac::HMAC_SHA256, b"other-secret");
+        let signed = format!("{}.{}", parts[0], parts[1]);
+        parts[2] = URL_SAFE_NO_PAD.encode(hmac::sign(&key, signed.as_bytes()).as_ref());
+        parts.join(".")
+    };
+    let (status, _) = post(&server, &forged, "/lock/acquire", acquire_body("1003")).await;
+    assert_eq!(status, 401);
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn tenant_quota_limits_held_locks() {
+    let server = TestServer::builder()
+        .config(config(vec![tenant("acme", "acme-key", Some(1)), tenant("globex", "globex-key", None)]))
+        .start()
+        .await
+        .unwrap();
+
+    let (_, first) = post(&server, "acme-key", "/lock/acquire", acquire_body("1001")).await;
+    assert_eq!(first["success"], true, "{}", first);
+    let (_, second) = post(&server, "acme-key", "/lock/acquire", acquire_body("1002")).await;
+    assert_eq!(second["success"], false, "{}", second);
+    assert_eq!(second["code"], LockError::TenantQuotaReached as i32, "{}", second);
+
+    // 配额按租户计数
+    let (_, other) = post(&server, "globex-key", "/lock/acquire", acquire_body("1002")).await;
+    assert_eq!(other["success"], true, "{}", other);
+
+    let lock_id = first["data"]["lock_id"].as_str().unwrap();
+    let (_, release) = post(&server, "acme-key", "/lock/release", json!({ "lock_id": lock_id })).await;
+    assert_eq!(release["success"], true, "{}", release);
+    let (_, retry) = post(&server, "acme-key", "/lock/acquire", acquire_body("1002")).await;
+    assert_eq!(retry["success"], true, "{}", retry);
+    server.stop().await;
+}
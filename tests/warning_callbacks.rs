@@ -0,0 +1,70 @@
+//! 过期提醒回调地址的限制
+//!
+//! 回调由服务端发起，只允许 `LOCK_WARNING_CALLBACK_HOSTS` 中的主机，未配置时不允许指定回调地址。
+
+use fe_lock_service::config::Config;
+use fe_lock_service::models::LockError;
+use fe_lock_service::testing::TestServer;
+use serde_json::{json, Value};
+
+async fn acquire(server: &TestServer, business_id: &str, callback_url: &str) -> Value {
+    reqwest::Client::new()
+        .post(format!("{}/api/lock/acquire", server.url()))
+        .json(&json!({
+            "namespace": "order",
+            "user_id": "u1",
+            "user_name": "张三",
+            "business_id": business_id,
+            "warn_before_seconds": 10,
+            "warning_callback_url": callback_url,
+        }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap()
+}
+
+fn assert_rejected(response: &Value) {
+    assert_eq!(response["success"], false, "{}", response);
+    assert_eq!(response["code"], LockError::InvalidLockRequest as i32, "{}", response);
+    assert!(response["message"].as_str().unwrap().contains("LOCK_WARNING_CALLBACK_HOSTS"), "{}", response);
+}
+
+#[tokio::test]
+async fn callbacks_are_disabled_by_default() {
+    let mut config = Config::from_env();
+    config.lock_warning_callback_hosts = Vec::new();
+    let server = TestServer::builder().config(config).start().await.unwrap();
+
+    assert_rejected(&acquire(&server, "1001", "http://hooks.example.com/expiring").await);
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn only_allowlisted_hosts_are_accepted() {
+    let mut config = Config::from_env();
+    config.lock_warning_callback_hosts = vec!["hooks.example.com".to_string(), "*.ops.example.com".to_string()];
+    let server = TestServer::builder().config(config).start().await.unwrap();
+
+    for (business_id, callback_url) in [
+        ("1001", "http://169.254.169.254/latest/meta-data/"),
+        ("1002", "http://localhost:8080/admin"),
+        ("1003", "https://hooks.example.com.evil.test/expiring"),
+        ("1004", "https://ops.example.com/expiring"),
+        ("1005", "https://evilops.example.com/expiring"),
+    ] {
+        assert_rejected(&acquire(&server, business_id, callback_url).await);
+    }
+
+    for (business_id, callback_url) in [
+        ("2001", "https://hooks.example.com/expiring"),
+        ("2002", "https://HOOKS.example.com:8443/expiring"),
+        ("2003", "http://alerts.ops.example.com/expiring"),
+    ] {
+        let response = acquire(&server, business_id, callback_url).await;
+        assert_eq!(response["success"], true, "{}", response);
+    }
+    server.stop().await;
+}